@@ -1,11 +1,14 @@
 use clap::Parser;
 use kanata_tcp_protocol::*;
 use simplelog::*;
-use std::io::{BufRead, BufReader, Write, stdin};
-use std::net::{SocketAddr, TcpStream};
+use std::io::{BufRead, BufReader, Read, Write, stdin};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
 use std::process::exit;
 use std::time::Duration;
 
+mod tls;
+
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
@@ -13,6 +16,23 @@ struct Args {
     #[clap(short, long)]
     port: Option<u16>,
 
+    /// Host that kanata's TCP server is listening on
+    #[clap(long, default_value = "127.0.0.1")]
+    host: String,
+
+    /// Connect with TLS, for servers configured with tcp-server-tls-cert
+    #[clap(long)]
+    tls: bool,
+
+    /// PEM file with the certificate that the server's TLS certificate is verified against,
+    /// e.g. the server's own self-signed certificate
+    #[clap(long)]
+    tls_ca: Option<PathBuf>,
+
+    /// Whether to verify the server's TLS certificate
+    #[clap(long, default_value_t = true, action = clap::ArgAction::Set)]
+    tls_verify: bool,
+
     /// Enable debug logging
     #[clap(short, long)]
     debug: bool,
@@ -35,14 +55,41 @@ fn main() {
         }
     };
     log::info!("attempting to connect to kanata");
-    let kanata_conn = TcpStream::connect_timeout(
-        &SocketAddr::from(([127, 0, 0, 1], port)),
-        Duration::from_secs(5),
-    )
-    .expect("connect to kanata");
+    let address = (args.host.as_str(), port)
+        .to_socket_addrs()
+        .expect("valid host")
+        .next()
+        .expect("host has an address");
+    let kanata_conn =
+        TcpStream::connect_timeout(&address, Duration::from_secs(5)).expect("connect to kanata");
+    let (mut writer_stream, reader_stream): (Box<dyn Write + Send>, Box<dyn Read>) = if args.tls {
+        let config = match tls::client_config(args.tls_ca.as_deref(), args.tls_verify) {
+            Ok(config) => config,
+            Err(e) => {
+                log::error!("could not set up TLS: {e}");
+                exit(1);
+            }
+        };
+        let stream = tls::TlsStream::connect(kanata_conn, config, &args.host)
+            .expect("TLS handshake with kanata");
+        (
+            Box::new(stream.try_clone().expect("clone writer")),
+            Box::new(stream),
+        )
+    } else {
+        (
+            Box::new(kanata_conn.try_clone().expect("clone writer")),
+            Box::new(kanata_conn),
+        )
+    };
     log::info!("successfully connected");
-    let writer_stream = kanata_conn.try_clone().expect("clone writer");
-    let reader_stream = kanata_conn;
+    writer_stream
+        .write_all(
+            serde_json::to_string(&ClientMessage::Subscribe(vec!["LayerChange".into()]))
+                .expect("deserializable")
+                .as_bytes(),
+        )
+        .expect("stream writable");
     std::thread::spawn(move || write_to_kanata(writer_stream));
     read_from_kanata(reader_stream);
 }
@@ -52,6 +99,9 @@ fn print_usage() {
         "\n\
     You can also use any other software to connect to kanata over TCP.\n\
     The protocol is plaintext JSON with newline terminated messages.
+\n\
+    Clients subscribe to layer change notifications with:\n\
+    {}
 \n\
     Layer change notifications from kanata look like:\n\
     {}
@@ -65,13 +115,19 @@ fn print_usage() {
     - reload previous: {}\n\
     - reload specific index: {}\n\
     - reload specific file: {}
+\n\
+    With \"wait\":true, reload commands are answered once the reload is done, e.g.:\n\
+    {}
 \n\
     Server responses for commands look like:\n\
     - Success: {}\n\
     - Error: {}
     ",
+        serde_json::to_string(&ClientMessage::Subscribe(vec!["LayerChange".into()]))
+            .expect("deserializable"),
         serde_json::to_string(&ServerMessage::LayerChange {
-            new: "newly-changed-to-layer".into()
+            new: "newly-changed-to-layer".into(),
+            old: Some("previous-layer".into()),
         })
         .expect("deserializable"),
         serde_json::to_string(&ClientMessage::ChangeLayer {
@@ -105,6 +161,13 @@ fn print_usage() {
             timeout_ms: None,
         })
         .expect("deserializable"),
+        serde_json::to_string(&ServerMessage::ReloadResult {
+            success: false,
+            error: Some("failed to parse config file: kanata.kbd:42: Unknown key".to_string()),
+            duration_ms: 3,
+            timeout_ms: None,
+        })
+        .expect("deserializable"),
         serde_json::to_string(&ServerResponse::Ok).expect("deserializable"),
         serde_json::to_string(&ServerResponse::Error {
             msg: "Invalid config index: 5. Only 2 configs are available (0-1).".to_string()
@@ -136,7 +199,7 @@ fn init_logger(args: &Args) {
     );
 }
 
-fn write_to_kanata(mut s: TcpStream) {
+fn write_to_kanata(mut s: impl Write) {
     log::info!("writer starting");
     log::info!("writer: enter commands to send to kanata:");
     log::info!("  - layer name: change to that layer");
@@ -162,21 +225,21 @@ fn write_to_kanata(mut s: TcpStream) {
         } else if command == "reload" {
             log::info!("writer: telling kanata to reload current config");
             serde_json::to_string(&ClientMessage::Reload {
-                wait: None,
+                wait: Some(true),
                 timeout_ms: None,
             })
             .expect("deserializable")
         } else if command == "reload-next" {
             log::info!("writer: telling kanata to reload next config");
             serde_json::to_string(&ClientMessage::ReloadNext {
-                wait: None,
+                wait: Some(true),
                 timeout_ms: None,
             })
             .expect("deserializable")
         } else if command == "reload-prev" {
             log::info!("writer: telling kanata to reload previous config");
             serde_json::to_string(&ClientMessage::ReloadPrev {
-                wait: None,
+                wait: Some(true),
                 timeout_ms: None,
             })
             .expect("deserializable")
@@ -187,7 +250,7 @@ fn write_to_kanata(mut s: TcpStream) {
                     log::info!("writer: telling kanata to reload config at index {index}");
                     serde_json::to_string(&ClientMessage::ReloadNum {
                         index,
-                        wait: None,
+                        wait: Some(true),
                         timeout_ms: None,
                     })
                     .expect("deserializable")
@@ -203,7 +266,7 @@ fn write_to_kanata(mut s: TcpStream) {
             log::info!("writer: telling kanata to reload config file \"{path}\"");
             serde_json::to_string(&ClientMessage::ReloadFile {
                 path,
-                wait: None,
+                wait: Some(true),
                 timeout_ms: None,
             })
             .expect("deserializable")
@@ -218,7 +281,7 @@ fn write_to_kanata(mut s: TcpStream) {
     }
 }
 
-fn read_from_kanata(s: TcpStream) {
+fn read_from_kanata(s: impl Read) {
     log::info!("reader starting");
     let mut reader = BufReader::new(s);
     let mut msg = String::new();
@@ -248,8 +311,19 @@ fn read_from_kanata(s: TcpStream) {
             }
         };
         match parsed_msg {
-            ServerMessage::LayerChange { new } => {
-                log::info!("reader: kanata changed layers to \"{new}\"");
+            ServerMessage::LayerChange { new, old } => match old {
+                Some(old) => {
+                    log::info!("reader: kanata changed layers from \"{old}\" to \"{new}\"")
+                }
+                None => log::info!("reader: kanata is on layer \"{new}\""),
+            },
+            ServerMessage::ReloadResult {
+                success: true,
+                duration_ms,
+                ..
+            } => log::info!("reader: reload succeeded in {duration_ms} ms"),
+            ServerMessage::ReloadResult { error, .. } => {
+                log::error!("reader: reload failed: {}", error.unwrap_or_default())
             }
             msg => {
                 log::info!("got msg: {msg:?}");
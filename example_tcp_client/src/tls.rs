@@ -0,0 +1,164 @@
+//! TLS connections to a kanata TCP server with `tcp-server-tls-cert` and `tcp-server-tls-key`.
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, verify_tls12_signature, verify_tls13_signature};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime, pem::PemObject};
+use rustls::{
+    ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// An encrypted connection. Clones share the TLS session, so that one clone can wait for server
+/// messages while another sends commands.
+pub struct TlsStream {
+    conn: Arc<Mutex<ClientConnection>>,
+    sock: TcpStream,
+}
+
+/// Builds the client configuration. The server certificate is checked against the certificates in
+/// `ca_file`, e.g. kanata's self-signed certificate, unless `verify` is false.
+pub fn client_config(ca_file: Option<&Path>, verify: bool) -> anyhow::Result<Arc<ClientConfig>> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+    let config = if verify {
+        let Some(ca_file) = ca_file else {
+            anyhow::bail!("--tls-ca is needed to verify the server certificate");
+        };
+        let mut roots = RootCertStore::empty();
+        for cert in CertificateDer::pem_file_iter(ca_file)? {
+            roots.add(cert?)?;
+        }
+        builder.with_root_certificates(roots).with_no_client_auth()
+    } else {
+        log::warn!("the server certificate is not verified");
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoVerification(provider)))
+            .with_no_client_auth()
+    };
+    Ok(Arc::new(config))
+}
+
+impl TlsStream {
+    pub fn connect(
+        sock: TcpStream,
+        config: Arc<ClientConfig>,
+        server_name: &str,
+    ) -> anyhow::Result<Self> {
+        let server_name = ServerName::try_from(server_name.to_owned())?;
+        let mut conn = ClientConnection::new(config, server_name)?;
+        while conn.is_handshaking() {
+            conn.complete_io(&mut &sock)?;
+        }
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            sock,
+        })
+    }
+
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            conn: self.conn.clone(),
+            sock: self.sock.try_clone()?,
+        })
+    }
+}
+
+fn write_pending(conn: &mut ClientConnection, mut sock: &TcpStream) -> io::Result<()> {
+    while conn.wants_write() {
+        conn.write_tls(&mut sock)?;
+    }
+    Ok(())
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let mut buf = [0u8; 4096];
+        loop {
+            match self.conn.lock().unwrap().reader().read(out) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                res => return res,
+            }
+            let n = self.sock.read(&mut buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            let mut conn = self.conn.lock().unwrap();
+            let mut received = &buf[..n];
+            while !received.is_empty() {
+                conn.read_tls(&mut received)?;
+                conn.process_new_packets().map_err(io::Error::other)?;
+            }
+            write_pending(&mut conn, &self.sock)?;
+        }
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let n = conn.writer().write(buf)?;
+        write_pending(&mut conn, &self.sock)?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        conn.writer().flush()?;
+        write_pending(&mut conn, &self.sock)
+    }
+}
+
+/// Accepts any server certificate, for `--tls-verify false`. The connection is still encrypted
+/// but is open to man-in-the-middle attacks.
+#[derive(Debug)]
+struct NoVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
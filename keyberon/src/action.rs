@@ -24,6 +24,11 @@ pub enum SequenceEvent<'a, T: 'a> {
         /// How long (in ticks) this Delay will last
         duration: u32, // NOTE: This isn't a u16 because that's only max ~65 seconds (assuming 1000 ticks/sec)
     },
+    /// A Delay whose duration is read from a runtime variable when the delay is reached
+    DelayVar {
+        /// Index of the variable in `Layout::vars`
+        var: u16,
+    },
     /// Custom event in sequence.
     Custom(&'a T),
     /// Cancels the running sequence and can be used to mark the end of a sequence
@@ -41,6 +46,7 @@ impl<T> Debug for SequenceEvent<'_, T> {
             Self::Delay { duration } => {
                 f.debug_struct("Delay").field("duration", duration).finish()
             }
+            Self::DelayVar { var } => f.debug_struct("DelayVar").field("var", var).finish(),
             Self::Custom(_) => write!(f, "Custom"),
             Self::Complete => write!(f, "Complete"),
         }
@@ -172,6 +178,13 @@ where
     /// because a human might have a slow release but they did
     /// indeed want a hold to activate.
     pub on_press_reset_timeout_to: Option<std::num::NonZeroU16>,
+    /// Minimum and maximum of the timeout if it is adaptive. An adaptive
+    /// `timeout` is scaled by the layout's recent typing interval when the
+    /// key is pressed.
+    pub adaptive_timeout: Option<(u16, u16)>,
+    /// If set, `timeout` is only the default: the layout's timeout for the
+    /// layer that the action was resolved from applies instead, if any.
+    pub layer_timeout: bool,
 }
 
 /// Define one shot key behaviour.
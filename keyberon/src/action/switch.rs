@@ -49,6 +49,10 @@ const INPUT_VAL: u16 = 851;
 const HISTORICAL_INPUT_VAL: u16 = 852;
 const LAYER_VAL: u16 = 853;
 const BASE_LAYER_VAL: u16 = 854;
+const LAYER_ACTIVE_VAL: u16 = 855;
+const VAR_EQ_VAL: u16 = 856;
+const VAR_GT_VAL: u16 = 857;
+const VAR_LT_VAL: u16 = 858;
 
 // Binary values:
 // 0b0100 ...
@@ -71,6 +75,14 @@ pub enum BooleanOperator {
     Not,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Comparison of a runtime variable with a constant.
+pub enum VarComparison {
+    Eq,
+    Gt,
+    Lt,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// OpCode for a switch case boolean expression.
 pub struct OpCode(u16);
@@ -87,6 +99,8 @@ enum OpCodeType {
     TicksSinceGreaterThan(TicksSinceNthKey),
     Layer(u16),
     BaseLayer(u16),
+    LayerActive(u16),
+    Var(VarComparison, u16),
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -118,6 +132,27 @@ struct TicksSinceNthKey {
     ticks_since: u16,
 }
 
+#[derive(Debug, Clone, Default)]
+/// Values of runtime variables, indexed by variable number. A variable that has never been set is
+/// `None` and compares as 0.
+pub struct SwitchVars<'v> {
+    pub values: &'v [Option<i32>],
+    /// The most recent variable that was read while unset.
+    pub unset_read: Option<u16>,
+}
+
+impl SwitchVars<'_> {
+    fn get(&mut self, var: u16) -> i32 {
+        match self.values.get(usize::from(var)).copied().flatten() {
+            Some(v) => v,
+            None => {
+                self.unset_read = Some(var);
+                0
+            }
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 /// Whether or not a case should break out of the switch if it evaluates to true or fallthrough to
 /// the next case.
@@ -131,6 +166,8 @@ impl<'a, T> Switch<'a, T> {
     /// the currently active keys, and historically pressed keys.
     ///
     /// The `historical_keys` parameter should iterate in the order of most-recent-first.
+    /// The `layers` parameter should iterate over the active layers, starting with the current
+    /// layer.
     pub fn actions<A1, A2, H1, H2, L>(
         &self,
         active_keys: A1,
@@ -139,7 +176,7 @@ impl<'a, T> Switch<'a, T> {
         historical_positions: H2,
         layers: L,
         default_layer: u16,
-    ) -> SwitchActions<'a, T, A1, A2, H1, H2, L>
+    ) -> SwitchActions<'a, 'static, T, A1, A2, H1, H2, L>
     where
         A1: Iterator<Item = KeyCode> + Clone,
         A2: Iterator<Item = KCoord> + Clone,
@@ -155,6 +192,7 @@ impl<'a, T> Switch<'a, T> {
             historical_positions,
             layers,
             default_layer,
+            vars: SwitchVars::default(),
             case_index: 0,
         }
     }
@@ -162,7 +200,7 @@ impl<'a, T> Switch<'a, T> {
 
 #[derive(Debug, Clone)]
 /// Iterator returned by `Switch::actions`.
-pub struct SwitchActions<'a, T, A1, A2, H1, H2, L>
+pub struct SwitchActions<'a, 'v, T, A1, A2, H1, H2, L>
 where
     A1: Iterator<Item = KeyCode> + Clone,
     A2: Iterator<Item = KCoord> + Clone,
@@ -177,10 +215,47 @@ where
     historical_positions: H2,
     layers: L,
     default_layer: u16,
+    vars: SwitchVars<'v>,
     case_index: usize,
 }
 
-impl<'a, T, A1, A2, H1, H2, L> Iterator for SwitchActions<'a, T, A1, A2, H1, H2, L>
+impl<'a, T, A1, A2, H1, H2, L> SwitchActions<'a, '_, T, A1, A2, H1, H2, L>
+where
+    A1: Iterator<Item = KeyCode> + Clone,
+    A2: Iterator<Item = KCoord> + Clone,
+    H1: Iterator<Item = HistoricalEvent<KeyCode>> + Clone,
+    H2: Iterator<Item = HistoricalEvent<KCoord>> + Clone,
+    L: Iterator<Item = u16> + Clone,
+{
+    /// Use these runtime variable values for `var` conditions. Without this, all variables are
+    /// unset.
+    pub fn with_vars<'v>(
+        self,
+        values: &'v [Option<i32>],
+    ) -> SwitchActions<'a, 'v, T, A1, A2, H1, H2, L> {
+        SwitchActions {
+            cases: self.cases,
+            active_keys: self.active_keys,
+            active_positions: self.active_positions,
+            historical_keys: self.historical_keys,
+            historical_positions: self.historical_positions,
+            layers: self.layers,
+            default_layer: self.default_layer,
+            vars: SwitchVars {
+                values,
+                unset_read: None,
+            },
+            case_index: self.case_index,
+        }
+    }
+
+    /// The most recent variable that a condition read while it was unset.
+    pub fn unset_var_read(&self) -> Option<u16> {
+        self.vars.unset_read
+    }
+}
+
+impl<'a, T, A1, A2, H1, H2, L> Iterator for SwitchActions<'a, '_, T, A1, A2, H1, H2, L>
 where
     A1: Iterator<Item = KeyCode> + Clone,
     A2: Iterator<Item = KCoord> + Clone,
@@ -201,6 +276,7 @@ where
                 self.historical_positions.clone(),
                 self.layers.clone(),
                 self.default_layer,
+                &mut self.vars,
             ) {
                 let ret_ac = case.1;
                 match case.2 {
@@ -315,6 +391,23 @@ impl OpCode {
         (Self(BASE_LAYER_VAL), Self(base_layer))
     }
 
+    /// Return OpCodes specifying a check for a layer anywhere in the active layer stack.
+    pub fn new_layer_active(layer: u16) -> (Self, Self) {
+        assert!(usize::from(layer) < crate::layout::MAX_LAYERS);
+        (Self(LAYER_ACTIVE_VAL), Self(layer))
+    }
+
+    /// Return OpCodes specifying a comparison of the runtime variable numbered `var` with
+    /// `value`.
+    pub fn new_var(cmp: VarComparison, var: u16, value: i16) -> [Self; 3] {
+        let op = match cmp {
+            VarComparison::Eq => VAR_EQ_VAL,
+            VarComparison::Gt => VAR_GT_VAL,
+            VarComparison::Lt => VAR_LT_VAL,
+        };
+        [Self(op), Self(var), Self(value as u16)]
+    }
+
     /// Return the interpretation of this `OpCode`.
     fn opcode_type(self, next: Option<OpCode>) -> OpCodeType {
         if self.0 < KEY_MAX {
@@ -329,6 +422,10 @@ impl OpCode {
                 }),
                 LAYER_VAL => OpCodeType::Layer(op2.0),
                 BASE_LAYER_VAL => OpCodeType::BaseLayer(op2.0),
+                LAYER_ACTIVE_VAL => OpCodeType::LayerActive(op2.0),
+                VAR_EQ_VAL => OpCodeType::Var(VarComparison::Eq, op2.0),
+                VAR_GT_VAL => OpCodeType::Var(VarComparison::Gt, op2.0),
+                VAR_LT_VAL => OpCodeType::Var(VarComparison::Lt, op2.0),
                 _ => unreachable!("unexpected opcode {self:?}"),
             }
         } else {
@@ -366,6 +463,7 @@ impl From<u16> for OperatorAndEndIndex {
 }
 
 /// Evaluate the return value of an expression evaluated on the given key codes.
+#[allow(clippy::too_many_arguments)]
 fn evaluate_boolean(
     bool_expr: &[OpCode],
     key_codes: impl Iterator<Item = KeyCode> + Clone,
@@ -374,6 +472,7 @@ fn evaluate_boolean(
     historical_inputs: impl Iterator<Item = HistoricalEvent<KCoord>> + Clone,
     layers: impl Iterator<Item = u16> + Clone,
     default_layer: u16,
+    vars: &mut SwitchVars,
 ) -> bool {
     let mut ret = true;
     let mut current_index = 0;
@@ -465,6 +564,22 @@ fn evaluate_boolean(
                 current_index += 1;
                 ret = default_layer == base_layer;
             }
+            OpCodeType::LayerActive(layer) => {
+                // opcode has size 2
+                current_index += 1;
+                ret = layers.clone().any(|l| l == layer);
+            }
+            OpCodeType::Var(cmp, var) => {
+                // opcode has size 3
+                let value = i32::from(bool_expr[current_index + 2].0 as i16);
+                current_index += 2;
+                let var_value = vars.get(var);
+                ret = match cmp {
+                    VarComparison::Eq => var_value == value,
+                    VarComparison::Gt => var_value > value,
+                    VarComparison::Lt => var_value < value,
+                };
+            }
         };
         if current_op == Not {
             ret = !ret;
@@ -493,6 +608,7 @@ fn evaluate_bool_test(opcodes: &[OpCode], keycodes: impl Iterator<Item = KeyCode
         [].iter().copied(),
         [].iter().copied(),
         0,
+        &mut SwitchVars::default(),
     )
 }
 
@@ -861,6 +977,7 @@ fn switch_historical_1() {
         [].iter().copied(),
         [].iter().copied(),
         0,
+        &mut SwitchVars::default(),
     ));
     assert!(evaluate_boolean(
         opcode_true2.as_slice(),
@@ -870,6 +987,7 @@ fn switch_historical_1() {
         [].iter().copied(),
         [].iter().copied(),
         0,
+        &mut SwitchVars::default(),
     ));
     assert!(!evaluate_boolean(
         opcode_false.as_slice(),
@@ -879,6 +997,7 @@ fn switch_historical_1() {
         [].iter().copied(),
         [].iter().copied(),
         0,
+        &mut SwitchVars::default(),
     ));
     assert!(!evaluate_boolean(
         opcode_false2.as_slice(),
@@ -888,6 +1007,7 @@ fn switch_historical_1() {
         [].iter().copied(),
         [].iter().copied(),
         0,
+        &mut SwitchVars::default(),
     ));
 }
 
@@ -973,6 +1093,7 @@ fn switch_historical_bools() {
                 [].iter().copied(),
                 [].iter().copied(),
                 0,
+                &mut SwitchVars::default(),
             ),
             expectation
         );
@@ -1089,6 +1210,7 @@ fn switch_historical_ticks_since() {
                 [].iter().copied(),
                 [].iter().copied(),
                 0,
+                &mut SwitchVars::default(),
             ),
             expectation
         );
@@ -1323,6 +1445,7 @@ fn switch_inputs() {
                 [].iter().copied(),
                 [].iter().copied(),
                 0,
+                &mut SwitchVars::default(),
             ),
             expectation
         );
@@ -1391,6 +1514,7 @@ fn switch_historical_inputs() {
                 historical_inputs.iter().copied(),
                 [].iter().copied(),
                 0,
+                &mut SwitchVars::default(),
             ),
             expectation
         );
@@ -1402,3 +1526,53 @@ fn switch_historical_inputs() {
     test(&opcodes_true_or1, true);
     test(&opcodes_true_or2, true);
 }
+
+#[test]
+fn switch_vars() {
+    let [op1, op2, op3] = OpCode::new_var(VarComparison::Eq, 0, 2);
+    let [op4, op5, op6] = OpCode::new_var(VarComparison::Gt, 1, -3);
+    let [op7, op8, op9] = OpCode::new_var(VarComparison::Lt, 2, 1);
+    let values = [Some(2), Some(-2), None];
+    let test = |opcodes: &[OpCode], expectation: bool, unset_read: Option<u16>| {
+        let mut vars = SwitchVars {
+            values: &values,
+            unset_read: None,
+        };
+        assert_eq!(
+            evaluate_boolean(
+                opcodes,
+                [].iter().copied(),
+                [].iter().copied(),
+                [].iter().copied(),
+                [].iter().copied(),
+                [].iter().copied(),
+                0,
+                &mut vars,
+            ),
+            expectation
+        );
+        assert_eq!(vars.unset_read, unset_read);
+    };
+    test(&[op1, op2, op3], true, None);
+    test(&[op4, op5, op6], true, None);
+    // An unset variable compares as 0.
+    test(&[op7, op8, op9], true, Some(2));
+    test(
+        &[OpCode::new_bool(And, 7), op1, op2, op3, op4, op5, op6],
+        true,
+        None,
+    );
+    test(
+        &[
+            OpCode::new_bool(Not, 4),
+            op4,
+            op5,
+            op6,
+            OpCode::new_key(KeyCode::A),
+        ],
+        false,
+        None,
+    );
+    let [op10, op11, op12] = OpCode::new_var(VarComparison::Gt, 0, 2);
+    test(&[op10, op11, op12], false, None);
+}
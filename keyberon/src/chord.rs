@@ -45,6 +45,10 @@ pub struct ChordV2<'a, T> {
     pub disabled_layers: &'a [u16],
     /// When should the action for this chord be released.
     pub release_behaviour: ReleaseBehaviour,
+    /// The number of ticks after activation during which releasing any participating key
+    /// releases the chord, regardless of `release_behaviour`. Used by `chord-hold` so that an
+    /// early release is a tap of its tap-hold action.
+    pub tap_release_window: u16,
 }
 
 #[derive(Debug, Clone)]
@@ -80,10 +84,30 @@ struct ActiveChord<'a, T> {
     status: ActiveChordStatus,
     /// Tracks how old an action is.
     delay: u16,
+    /// Ticks left during which releasing any participating key releases the chord.
+    tap_release_ticks: u16,
 }
 
 fn tick_ach<T>(acc: &mut ActiveChord<T>) {
     acc.delay = acc.delay.saturating_add(1);
+    acc.tap_release_ticks = acc.tap_release_ticks.saturating_sub(1);
+}
+
+/// Releases the input key `j` from the active chord.
+fn release_key_from_ach<T>(ach: &mut ActiveChord<T>, j: u16) {
+    if !ach.participating_keys.contains(&j) {
+        return;
+    }
+    if ach.tap_release_ticks > 0 {
+        ach.remaining_keys_to_release.clear();
+    }
+    ach.remaining_keys_to_release.retain(|pk| *pk != j);
+    if ach.remaining_keys_to_release.is_empty() {
+        ach.status = match ach.status {
+            Unread | UnreadReleased => UnreadReleased,
+            Releasable | Released => Released,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -167,6 +191,14 @@ impl<'a, T> ChordsV2<'a, T> {
         self.queue.is_empty() && self.active_chords.is_empty()
     }
 
+    /// Drops queued inputs and active chords without running their actions.
+    pub fn clear_chv2(&mut self) {
+        self.queue.clear();
+        self.active_chords.clear();
+        self.ticks_to_ignore_chord = 0;
+        self.ticks_until_next_state_change = 0;
+    }
+
     pub fn accepts_chords_chv2(&self) -> bool {
         self.ticks_to_ignore_chord == 0
     }
@@ -285,18 +317,7 @@ impl<'a, T> ChordsV2<'a, T> {
         for qd in self.queue.iter() {
             if let Event::Release(_, j) = qd.event {
                 // Release the key from active chords.
-                achs.iter_mut().for_each(|ach| {
-                    if !ach.participating_keys.contains(&j) {
-                        return;
-                    }
-                    ach.remaining_keys_to_release.retain(|pk| *pk != j);
-                    if ach.remaining_keys_to_release.is_empty() {
-                        ach.status = match ach.status {
-                            Unread | UnreadReleased => UnreadReleased,
-                            Releasable | Released => Released,
-                        }
-                    }
-                });
+                achs.iter_mut().for_each(|ach| release_key_from_ach(ach, j));
             }
             drainq.push_back(*qd);
         }
@@ -329,18 +350,7 @@ impl<'a, T> ChordsV2<'a, T> {
             }
             Event::Release(_, j) => {
                 // Release the key from active chords.
-                achs.iter_mut().for_each(|ach| {
-                    if !ach.participating_keys.contains(&j) {
-                        return;
-                    }
-                    ach.remaining_keys_to_release.retain(|pk| *pk != j);
-                    if ach.remaining_keys_to_release.is_empty() {
-                        ach.status = match ach.status {
-                            Unread | UnreadReleased => UnreadReleased,
-                            Releasable | Released => Released,
-                        }
-                    }
-                });
+                achs.iter_mut().for_each(|ach| release_key_from_ach(ach, j));
                 if presses.is_empty() {
                     drainq.push_back(*qd);
                     false
@@ -593,5 +603,6 @@ fn get_active_chord<'a, T>(
             ActiveChordStatus::Unread
         },
         delay: since,
+        tap_release_ticks: cch.tap_release_window,
     }
 }
@@ -113,10 +113,32 @@ where
     pub active_sequences: ArrayDeque<SequenceState<'a, T>, 4, arraydeque::behavior::Wrapping>,
     pub action_queue: ActionQueue<'a, T>,
     pub rpt_action: Option<&'a Action<'a, T>>,
+    /// The most recent action that a repeat key should replay. Unlike `rpt_action`, this is not
+    /// updated by the layout itself; the user of the layout decides which actions to keep here.
+    pub rpt_replay_action: Option<&'a Action<'a, T>>,
     pub historical_keys: History<KeyCode>,
     pub historical_inputs: History<KCoord>,
     pub quick_tap_hold_timeout: bool,
     pub chords_v2: Option<ChordsV2<'a, T>>,
+    /// Values of runtime variables read by switch `var` conditions. Unset variables are `None`.
+    pub vars: std::vec::Vec<Option<i32>>,
+    /// The most recent variable read by a switch while unset. Reset by the user.
+    pub unset_var_read: Option<u16>,
+    /// The most recent variable read by a sequence delay while out of range, with its value.
+    /// Reset by the user.
+    pub clamped_var_read: Option<(u16, i32)>,
+    /// Average interval between recent presses, set by the user. Scales the timeout of adaptive
+    /// hold-tap actions.
+    pub typing_interval: Option<u16>,
+    /// If set, the timeout of every adaptive hold-tap action instead of the scaled timeout.
+    pub pinned_adaptive_timeout: Option<u16>,
+    /// The timeout of the most recently pressed adaptive hold-tap action. Reset by the user.
+    pub last_adaptive_timeout: Option<u16>,
+    /// Hold-tap timeouts by layer, used by hold-tap actions with `layer_timeout` set that are
+    /// resolved from a layer with a timeout.
+    pub layer_hold_timeouts: std::vec::Vec<Option<u16>>,
+    /// The layer that the most recently pressed action was resolved from.
+    action_layer: Option<u16>,
     rpt_multikey_key_buffer: MultiKeyBuffer<'a, T>,
     trans_resolution_behavior_v2: bool,
     delegate_to_first_layer: bool,
@@ -476,6 +498,11 @@ pub enum WaitingAction {
 }
 
 impl<'a, T: std::fmt::Debug> WaitingState<'a, T> {
+    /// Whether this is a hold-tap waiting to resolve, as opposed to a tap-dance or chord.
+    pub fn is_hold_tap(&self) -> bool {
+        matches!(self.config, WaitingConfig::HoldTap(..))
+    }
+
     fn tick_wt(
         &mut self,
         queued: &mut Queue,
@@ -898,6 +925,13 @@ pub struct SequenceState<'a, T: 'a> {
     remaining_events: &'a [SequenceEvent<'a, T>],
 }
 
+impl<'a, T> SequenceState<'a, T> {
+    /// Events of the sequence that have not been processed yet.
+    pub fn remaining_events(&self) -> &'a [SequenceEvent<'a, T>] {
+        self.remaining_events
+    }
+}
+
 type ReleasedOneShotKeys = Vec<KCoord, ONE_SHOT_MAX_ACTIVE>;
 
 // Using a u16 for indices instead of usize.
@@ -964,12 +998,15 @@ pub struct OneShotState {
 pub enum OneShotRetainableState {
     KeyCode { coord: KCoord, kc: KeyCode },
     Layer { coord: KCoord, layer: u16 },
+    Custom { coord: KCoord },
 }
 
 impl OneShotRetainableState {
     pub fn coord(&self) -> KCoord {
         match self {
-            Self::KeyCode { coord, .. } | Self::Layer { coord, .. } => *coord,
+            Self::KeyCode { coord, .. } | Self::Layer { coord, .. } | Self::Custom { coord } => {
+                *coord
+            }
         }
     }
 }
@@ -981,6 +1018,18 @@ enum OneShotHandlePressKey {
 }
 
 impl OneShotState {
+    /// Ends all active one-shots without releasing them.
+    fn clear(&mut self) {
+        self.keys.clear();
+        self.released_keys.clear();
+        self.other_pressed_keys.clear();
+        self.state_to_retain_on_release.clear();
+        self.timeout = 0;
+        self.release_on_next_tick = false;
+        self.pause_input_processing_ticks = 0;
+        self.ticks_to_ignore_events = 0;
+    }
+
     fn tick_osh(&mut self) -> Option<ReleasedOneShotKeys> {
         if self.keys.is_empty() {
             return None;
@@ -1170,6 +1219,7 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
             active_sequences: ArrayDeque::new(),
             action_queue: ArrayDeque::new(),
             rpt_action: None,
+            rpt_replay_action: None,
             historical_keys: History::new(),
             historical_inputs: History::new(),
             rpt_multikey_key_buffer: unsafe { MultiKeyBuffer::new() },
@@ -1177,6 +1227,14 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
             trans_resolution_behavior_v2: true,
             delegate_to_first_layer: false,
             chords_v2: None,
+            vars: std::vec::Vec::new(),
+            typing_interval: None,
+            pinned_adaptive_timeout: None,
+            last_adaptive_timeout: None,
+            layer_hold_timeouts: std::vec::Vec::new(),
+            action_layer: None,
+            unset_var_read: None,
+            clamped_var_read: None,
             contextual_execution: ContextualExecution::new(),
             tap_hold_tracker: Default::default(),
         }
@@ -1378,6 +1436,7 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
         if let Some(Some((coord, delay, action, layer_stack))) = self.action_queue.pop_front() {
             // If there's anything in the action queue, don't process anything else yet - execute
             // everything. Otherwise an action may never be released.
+            self.action_layer = None;
             return self.do_action(action, coord, delay, false, &mut layer_stack.into_iter());
         }
         self.queue.iter_mut().for_each(Queued::tick_qd);
@@ -1486,6 +1545,7 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
                             self.oneshot.handle_release((0, 0));
                             self.states.retain(|s| s.seq_release(keycode).is_some());
                         }
+                        #[allow(clippy::collapsible_match)]
                         Some(SequenceEvent::Delay { duration }) => {
                             // Setup a delay that will be decremented once per tick until 0
                             if duration > 0 {
@@ -1493,6 +1553,12 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
                                 seq.delay = duration - 1;
                             }
                         }
+                        Some(SequenceEvent::DelayVar { var }) => {
+                            let duration = self.var_delay(var);
+                            if duration > 0 {
+                                seq.delay = duration - 1;
+                            }
+                        }
                         Some(SequenceEvent::Custom(custom)) => {
                             let _ = self.states.push(State::SeqCustomPending(custom));
                         }
@@ -1523,6 +1589,23 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
         }
     }
 
+    /// Read a variable as a sequence delay. Unset variables are 0 and values outside of
+    /// 0-65535 are clamped.
+    fn var_delay(&mut self, var: u16) -> u32 {
+        let value = match self.vars.get(usize::from(var)).copied().flatten() {
+            Some(value) => value,
+            None => {
+                self.unset_var_read = Some(var);
+                0
+            }
+        };
+        let duration = value.clamp(0, i32::from(u16::MAX));
+        if duration != value {
+            self.clamped_var_read = Some((var, value));
+        }
+        duration as u32
+    }
+
     fn process_extra_waitings(&mut self, current_custom: CustomEvent<'a, T>) -> CustomEvent<'a, T> {
         if !matches!(current_custom, CustomEvent::NoEvent) {
             return current_custom;
@@ -1623,6 +1706,15 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
                                         },
                                     )
                             }
+                            Custom { coord, .. }
+                                if *coord == (i, j)
+                                    && self.oneshot.state_to_retain_on_release.contains(
+                                        &OneShotRetainableState::Custom { coord: *coord },
+                                    ) =>
+                            {
+                                // Custom is a valid oneshot state for mouse buttons.
+                                true
+                            }
                             // Everything else is not a valid oneshot state,
                             // if it falls on the same coordinate
                             // as a oneshot key, it should still be released here.
@@ -1705,7 +1797,7 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
         &self,
         coord: KCoord,
         layer_stack: &mut (impl Iterator<Item = u16> + Clone),
-    ) -> &'a Action<'a, T> {
+    ) -> (&'a Action<'a, T>, Option<u16>) {
         use crate::action::Action::*;
         let x = coord.0 as usize;
         let y = coord.1 as usize;
@@ -1716,10 +1808,14 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
             let action = &self.layers[usize::from(layer)][x][y];
             match action {
                 Trans => continue,
-                action => return action,
+                action => return (action, Some(layer)),
             }
         }
-        if x == 0 { &self.src_keys[y] } else { &NoOp }
+        if x == 0 {
+            (&self.src_keys[y], None)
+        } else {
+            (&NoOp, None)
+        }
     }
     fn do_action(
         &mut self,
@@ -1731,7 +1827,7 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
     ) -> CustomEvent<'a, T> {
         let mut action = action;
         if let Trans = action {
-            action = self.resolve_coord(coord, layer_stack);
+            (action, self.action_layer) = self.resolve_coord(coord, layer_stack);
         }
         let action = action;
 
@@ -1825,7 +1921,25 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
                 config,
                 tap_hold_interval,
                 on_press_reset_timeout_to,
+                adaptive_timeout,
+                layer_timeout,
             }) => {
+                let mut on_press_reset_timeout_to = *on_press_reset_timeout_to;
+                let timeout = match adaptive_timeout {
+                    Some((min, max)) => {
+                        let timeout = self.adaptive_hold_timeout(*timeout, *min, *max);
+                        self.last_adaptive_timeout = Some(timeout);
+                        timeout
+                    }
+                    None if *layer_timeout => {
+                        let timeout = self.layer_hold_timeout(*timeout);
+                        if on_press_reset_timeout_to.is_some() {
+                            on_press_reset_timeout_to = std::num::NonZeroU16::new(timeout);
+                        }
+                        timeout
+                    }
+                    None => *timeout,
+                };
                 let mut custom = CustomEvent::NoEvent;
                 if *tap_hold_interval == 0
                     || coord != self.last_press_tracker.coord
@@ -1841,7 +1955,7 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
                         timeout: timeout.saturating_sub(ticks),
                         delay: delay.saturating_sub(ticks),
                         ticks,
-                        on_press_reset_timeout_to: *on_press_reset_timeout_to,
+                        on_press_reset_timeout_to,
                         hold,
                         tap,
                         timeout_action,
@@ -2149,6 +2263,9 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
                 if !is_oneshot {
                     self.oneshot
                         .handle_press(OneShotHandlePressKey::Other(coord));
+                } else {
+                    self.oneshot
+                        .add_state_to_retain(OneShotRetainableState::Custom { coord });
                 }
                 self.rpt_action = Some(action);
                 if self.states.push(State::Custom { value, coord }).is_ok() {
@@ -2186,20 +2303,28 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
                 let active_coords = self.states.iter().filter_map(State::coord);
                 let historical_keys = self.historical_keys.iter_hevents();
                 let historical_coords = self.historical_inputs.iter_hevents();
-                let layers = self.trans_resolution_layer_order().into_iter();
+                let mut layers = self.active_held_layers().collect::<LayerStack>();
+                let _ = layers.push(self.default_layer as u16);
+                let layers = layers.into_iter();
                 let action_queue = &mut self.action_queue;
-                for ac in sw.actions(
-                    active_keys,
-                    active_coords,
-                    historical_keys,
-                    historical_coords,
-                    layers,
-                    // Note on truncating cast: I expect default layer to be in range by other
-                    // assertions.
-                    self.default_layer as u16,
-                ) {
+                let mut actions = sw
+                    .actions(
+                        active_keys,
+                        active_coords,
+                        historical_keys,
+                        historical_coords,
+                        layers,
+                        // Note on truncating cast: I expect default layer to be in range by other
+                        // assertions.
+                        self.default_layer as u16,
+                    )
+                    .with_vars(&self.vars);
+                for ac in actions.by_ref() {
                     action_queue.push_back(Some((coord, 0, ac, layer_stack.collect())));
                 }
+                if let Some(var) = actions.unset_var_read() {
+                    self.unset_var_read = Some(var);
+                }
                 // Switch is not properly repeatable. This has to use the action queue for the
                 // purpose of proper Custom action handling, because a single switch action can
                 // activate multiple inner actions. But because of the use of the action queue,
@@ -2210,6 +2335,28 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
         CustomEvent::NoEvent
     }
 
+    /// The timeout of a hold-tap action using the timeout of its layer: the timeout of the layer
+    /// the action was resolved from, or the action's own default timeout.
+    fn layer_hold_timeout(&self, default: u16) -> u16 {
+        self.action_layer
+            .and_then(|layer| self.layer_hold_timeouts.get(usize::from(layer)).copied())
+            .flatten()
+            .unwrap_or(default)
+    }
+
+    /// The timeout of an adaptive hold-tap action. The base timeout applies when the typing
+    /// interval equals it; faster typing lengthens the timeout and slower typing shortens it.
+    fn adaptive_hold_timeout(&self, base: u16, min: u16, max: u16) -> u16 {
+        if let Some(timeout) = self.pinned_adaptive_timeout {
+            return timeout;
+        }
+        match self.typing_interval {
+            Some(interval) => (u32::from(base) * u32::from(base) / u32::from(interval.max(1)))
+                .clamp(u32::from(min), u32::from(max)) as u16,
+            None => base.clamp(min, max),
+        }
+    }
+
     /// Obtain the index of the current active layer
     pub fn current_layer(&self) -> usize {
         self.states
@@ -2226,6 +2373,15 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
             .rev()
     }
 
+    /// Queues an action to be done on the next tick as if it had been pressed at `coord`, e.g. to
+    /// repeat a previous action.
+    pub fn queue_action(&mut self, coord: KCoord, action: &'a Action<'a, T>) {
+        let layer_stack = self.trans_resolution_layer_order();
+        let _ = self
+            .action_queue
+            .push_back(Some((coord, 0, action, layer_stack)));
+    }
+
     /// Returns a list indices of layers that should be used for [`Action::Trans`] resolution.
     pub fn trans_resolution_layer_order(&self) -> LayerStack {
         let current_layer = self.current_layer();
@@ -2252,6 +2408,26 @@ impl<'a, const C: usize, const R: usize, T: 'a + Copy + std::fmt::Debug> Layout<
             self.default_layer = value
         }
     }
+
+    /// Drops all key states and everything pending, i.e. held keys and layers, undecided
+    /// hold-taps, tap-dances and chords, one-shots, sequences and queued events and actions, then
+    /// sets the default layer. The keys that were output are no longer in
+    /// [`keycodes`](Self::keycodes), so the user of the layout releases them.
+    pub fn clear_state(&mut self, default_layer: usize) {
+        self.states.clear();
+        self.waiting = None;
+        self.extra_waiting.clear();
+        self.tap_dance_eager = None;
+        self.queue.clear();
+        self.oneshot.clear();
+        self.keys_to_suppress_for_one_cycle.clear();
+        self.active_sequences.clear();
+        self.action_queue.clear();
+        if let Some(chv2) = self.chords_v2.as_mut() {
+            chv2.clear_chv2();
+        }
+        self.set_default_layer(default_layer);
+    }
 }
 
 #[cfg(test)]
@@ -2278,6 +2454,8 @@ mod test {
             [[
                 HoldTap(&HoldTapAction {
                     on_press_reset_timeout_to: None,
+                    adaptive_timeout: None,
+                    layer_timeout: false,
                     timeout: 200,
                     hold: l(1),
                     tap: k(Space),
@@ -2287,6 +2465,8 @@ mod test {
                 }),
                 HoldTap(&HoldTapAction {
                     on_press_reset_timeout_to: None,
+                    adaptive_timeout: None,
+                    layer_timeout: false,
                     timeout: 200,
                     hold: k(LCtrl),
                     timeout_action: k(LShift),
@@ -2332,6 +2512,8 @@ mod test {
             [[
                 HoldTap(&HoldTapAction {
                     on_press_reset_timeout_to: None,
+                    adaptive_timeout: None,
+                    layer_timeout: false,
                     timeout: 200,
                     hold: l(1),
                     tap: k(Space),
@@ -2341,6 +2523,8 @@ mod test {
                 }),
                 HoldTap(&HoldTapAction {
                     on_press_reset_timeout_to: None,
+                    adaptive_timeout: None,
+                    layer_timeout: false,
                     timeout: 200,
                     hold: k(LCtrl),
                     timeout_action: k(LCtrl),
@@ -2385,6 +2569,8 @@ mod test {
         static LAYERS: Layers<2, 1> = &[[[
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 200,
                 hold: k(LAlt),
                 timeout_action: k(LAlt),
@@ -2394,6 +2580,8 @@ mod test {
             }),
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 20,
                 hold: k(LCtrl),
                 timeout_action: k(LCtrl),
@@ -2436,6 +2624,8 @@ mod test {
         static LAYERS: Layers<2, 1> = &[[[
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 200,
                 hold: k(LAlt),
                 timeout_action: k(LAlt),
@@ -2495,6 +2685,8 @@ mod test {
         static LAYERS: Layers<2, 1> = &[[[
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 200,
                 hold: k(LAlt),
                 timeout_action: k(LAlt),
@@ -2536,6 +2728,8 @@ mod test {
         static LAYERS: Layers<3, 1> = &[[[
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 200,
                 hold: k(LAlt),
                 timeout_action: k(LAlt),
@@ -2545,6 +2739,8 @@ mod test {
             }),
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 200,
                 hold: k(RAlt),
                 timeout_action: k(RAlt),
@@ -2554,6 +2750,8 @@ mod test {
             }),
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 200,
                 hold: k(LCtrl),
                 timeout_action: k(LCtrl),
@@ -2719,6 +2917,8 @@ mod test {
         static LAYERS: Layers<4, 1> = &[[[
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 200,
                 hold: k(Kb1),
                 timeout_action: k(Kb1),
@@ -2728,6 +2928,8 @@ mod test {
             }),
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 200,
                 hold: k(Kb3),
                 timeout_action: k(Kb3),
@@ -2737,6 +2939,8 @@ mod test {
             }),
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 200,
                 hold: k(Kb5),
                 timeout_action: k(Kb5),
@@ -2746,6 +2950,8 @@ mod test {
             }),
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 200,
                 hold: k(Kb7),
                 timeout_action: k(Kb7),
@@ -2820,6 +3026,8 @@ mod test {
         static LAYERS: Layers<2, 1> = &[[[
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 200,
                 hold: k(LAlt),
                 timeout_action: k(LAlt),
@@ -2876,6 +3084,8 @@ mod test {
         static LAYERS: Layers<3, 1> = &[[[
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 200,
                 hold: k(LAlt),
                 timeout_action: k(LAlt),
@@ -2886,6 +3096,8 @@ mod test {
             k(Enter),
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 200,
                 hold: k(LAlt),
                 timeout_action: k(LAlt),
@@ -3000,6 +3212,8 @@ mod test {
     fn tap_hold_interval_short_hold() {
         static LAYERS: Layers<1, 1> = &[[[HoldTap(&HoldTapAction {
             on_press_reset_timeout_to: None,
+            adaptive_timeout: None,
+            layer_timeout: false,
             timeout: 50,
             hold: k(LAlt),
             timeout_action: k(LAlt),
@@ -3044,6 +3258,8 @@ mod test {
         static LAYERS: Layers<2, 1> = &[[[
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 50,
                 hold: k(LAlt),
                 timeout_action: k(LAlt),
@@ -3053,6 +3269,8 @@ mod test {
             }),
             HoldTap(&HoldTapAction {
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
                 timeout: 200,
                 hold: k(RAlt),
                 timeout_action: k(RAlt),
@@ -3571,6 +3789,8 @@ mod test {
                 }),
                 HoldTap(&HoldTapAction {
                     on_press_reset_timeout_to: None,
+                    adaptive_timeout: None,
+                    layer_timeout: false,
                     timeout: 100,
                     hold: k(LAlt),
                     timeout_action: k(LAlt),
@@ -3637,6 +3857,8 @@ mod test {
                         }),
                         &HoldTap(&HoldTapAction {
                             on_press_reset_timeout_to: None,
+                            adaptive_timeout: None,
+                            layer_timeout: false,
                             timeout: 100,
                             hold: k(LAlt),
                             timeout_action: k(LAlt),
@@ -4078,6 +4300,8 @@ mod test {
                     1,
                     &HoldTap(&HoldTapAction {
                         on_press_reset_timeout_to: None,
+                        adaptive_timeout: None,
+                        layer_timeout: false,
                         timeout: 100,
                         hold: k(A),
                         timeout_action: k(A),
@@ -4090,6 +4314,8 @@ mod test {
                     2,
                     &HoldTap(&HoldTapAction {
                         on_press_reset_timeout_to: None,
+                        adaptive_timeout: None,
+                        layer_timeout: false,
                         timeout: 100,
                         hold: k(B),
                         timeout_action: k(B),
@@ -4342,6 +4568,8 @@ mod test {
                 NoOp,
                 HoldTap(&HoldTapAction {
                     on_press_reset_timeout_to: None,
+                    adaptive_timeout: None,
+                    layer_timeout: false,
                     timeout: 50,
                     hold: k(Space),
                     timeout_action: k(Space),
@@ -4390,6 +4618,8 @@ mod test {
                 NoOp,
                 HoldTap(&HoldTapAction {
                     on_press_reset_timeout_to: None,
+                    adaptive_timeout: None,
+                    layer_timeout: false,
                     timeout: 50,
                     hold: Trans,
                     timeout_action: Trans,
@@ -4626,6 +4856,8 @@ mod test {
                 NoOp,
                 HoldTap(&HoldTapAction {
                     on_press_reset_timeout_to: None,
+                    adaptive_timeout: None,
+                    layer_timeout: false,
                     timeout: 50,
                     hold: k(B),
                     timeout_action: k(B),
@@ -4640,6 +4872,8 @@ mod test {
                 Layer(3),
                 HoldTap(&HoldTapAction {
                     on_press_reset_timeout_to: None,
+                    adaptive_timeout: None,
+                    layer_timeout: false,
                     timeout: 50,
                     hold: k(C),
                     timeout_action: k(C),
@@ -4654,6 +4888,8 @@ mod test {
                 NoOp,
                 HoldTap(&HoldTapAction {
                     on_press_reset_timeout_to: None,
+                    adaptive_timeout: None,
+                    layer_timeout: false,
                     timeout: 50,
                     hold: k(D),
                     timeout_action: k(D),
@@ -4768,6 +5004,8 @@ mod test {
             config: HoldTapConfig::Default,
             tap_hold_interval: 0,
             on_press_reset_timeout_to: None,
+            adaptive_timeout: None,
+            layer_timeout: false,
         })]]];
         let mut layout = Layout::new(LAYERS);
         // Nothing set initially.
@@ -4801,6 +5039,8 @@ mod test {
                 config: HoldTapConfig::Default,
                 tap_hold_interval: 0,
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
             }),
             k(A),
         ]]];
@@ -4834,6 +5074,8 @@ mod test {
                 config: HoldTapConfig::PermissiveHold,
                 tap_hold_interval: 0,
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
             }),
             k(A),
         ]]];
@@ -4869,6 +5111,8 @@ mod test {
                 config: HoldTapConfig::HoldOnOtherKeyPress,
                 tap_hold_interval: 0,
                 on_press_reset_timeout_to: None,
+                adaptive_timeout: None,
+                layer_timeout: false,
             }),
             k(A),
         ]]];
@@ -4936,4 +5180,45 @@ mod test {
         assert!(layout.tap_hold_tracker.take_hold_activated().is_none());
         assert!(layout.tap_hold_tracker.take_tap_activated().is_none());
     }
+
+    #[test]
+    fn clear_state_drops_held_keys_layers_and_one_shots() {
+        static LAYERS: Layers<4, 1> = &[
+            [[
+                OneShot(&crate::action::OneShot {
+                    timeout: 100,
+                    action: &k(LShift),
+                    end_config: OneShotEndConfig::EndOnFirstPress,
+                }),
+                l(1),
+                k(A),
+                k(LCtrl),
+            ]],
+            [[Trans, Trans, k(B), Trans]],
+        ];
+        let mut layout = Layout::new(LAYERS);
+        layout.event(Press(0, 3));
+        layout.event(Press(0, 1));
+        layout.tick();
+        layout.event(Press(0, 0));
+        layout.event(Release(0, 0));
+        for _ in 0..5 {
+            layout.tick();
+        }
+        assert_keys(&[LShift, LCtrl], layout.keycodes());
+        assert_eq!(1, layout.current_layer());
+
+        layout.clear_state(0);
+        assert_keys(&[], layout.keycodes());
+        assert_eq!(0, layout.current_layer());
+
+        // Releases of keys held during the clear do nothing.
+        layout.event(Release(0, 1));
+        layout.event(Release(0, 3));
+        layout.event(Press(0, 2));
+        for _ in 0..3 {
+            layout.tick();
+        }
+        assert_keys(&[A], layout.keycodes());
+    }
 }
@@ -45,7 +45,7 @@ pub(crate) fn parse_defchordv2(
                 );
                 let chord_definitions = parse_chord_file(file_name).unwrap();
                 let processed = chord_definitions.iter().map(|chord_def| {
-                    let chunk = chord_translation.translate_chord(chord_def);
+                    let chunk = chord_translation.translate_chord(chord_def, s)?;
                     parse_single_chord(&chunk, s, &mut all_participating_key_sets)
                 });
                 Ok::<_, ParseError>(processed.collect_vec())
@@ -101,7 +101,17 @@ fn parse_single_chord(
             "Duplicate participating-keys, key sets may be used only once."
         );
     }
-    let action = parse_action(&chunk[1], s)?;
+    let (action, tap_release_window) = match chunk[1].list(s.vars()) {
+        Some([name, params @ ..]) if name.atom(s.vars()) == Some(CHORD_HOLD) => {
+            parse_chord_hold(params, s).map_err(|mut e| {
+                if e.span.is_none() {
+                    e.span = Some(chunk[1].span())
+                };
+                e
+            })?
+        }
+        _ => (parse_action(&chunk[1], s)?, 0),
+    };
     let timeout = parse_timeout(&chunk[2], s)?;
     let release_behaviour = parse_release_behaviour(&chunk[3], s)?;
     let disabled_layers = parse_disabled_layers(&chunk[4], s)?;
@@ -111,6 +121,7 @@ fn parse_single_chord(
         pending_duration: timeout,
         disabled_layers: s.a.sref_vec(disabled_layers),
         release_behaviour,
+        tap_release_window,
     };
     Ok(s.a.sref(chord).clone())
 }
@@ -208,9 +219,15 @@ fn parse_input(input: &str) -> Result<Vec<ChordDefinition>> {
             );
             let keys = caps.next().expect(&error_message);
             let action = caps.next().expect(&error_message);
+            let overrides = caps
+                .map(str::trim)
+                .filter(|field| !field.is_empty())
+                .map(str::to_string)
+                .collect();
             Ok(ChordDefinition {
                 keys: keys.to_string(),
                 action: action.to_string(),
+                overrides,
             })
         })
         .collect()
@@ -220,6 +237,9 @@ fn parse_input(input: &str) -> Result<Vec<ChordDefinition>> {
 struct ChordDefinition {
     keys: String,
     action: String,
+    /// Optional timeout and release behaviour for this chord only,
+    /// in extra tab-separated fields after the action.
+    overrides: Vec<String>,
 }
 
 struct ChordTranslation<'a> {
@@ -321,20 +341,43 @@ impl<'a> ChordTranslation<'a> {
         action_strings
     }
 
-    fn translate_chord(&self, chord_def: &ChordDefinition) -> Vec<SExpr> {
+    /// Translates a chord file line into a chord entry.
+    /// The timeout and release behaviour of the include entry are used
+    /// unless the line overrides them.
+    fn translate_chord(&self, chord_def: &ChordDefinition, s: &ParserState) -> Result<Vec<SExpr>> {
         let sexpr_string = format!(
-            "(({}) (macro {}))",
+            "(({}) (macro {}) {})",
             self.participant_keys(&chord_def.keys).join(" "),
-            self.action(&chord_def.action).join(" ")
+            self.action(&chord_def.action).join(" "),
+            chord_def.overrides.join(" "),
         );
-        let mut participant_action = sexpr::parse(&sexpr_string, self.file_name).unwrap()[0]
-            .t
-            .clone();
+        let mut participant_action = sexpr::parse(&sexpr_string, self.file_name)?[0].t.clone();
+        let overrides = participant_action.split_off(2);
+        let mut timeout = None;
+        let mut release_behaviour = None;
+        for item in overrides.iter() {
+            match item.atom(s.vars()) {
+                Some("first-release" | "all-released") => {
+                    if release_behaviour.replace(item).is_some() {
+                        bail_expr!(
+                            item,
+                            "Chord release behaviour is overridden more than once.\n\
+                            Use only one of: first-release | all-released"
+                        );
+                    }
+                }
+                _ => {
+                    if timeout.replace(item).is_some() {
+                        bail_expr!(item, "Chord timeout is overridden more than once");
+                    }
+                }
+            }
+        }
         participant_action.extend_from_slice(&[
-            self.timeout.clone(),
-            self.release_behaviour.clone(),
+            timeout.unwrap_or(self.timeout).clone(),
+            release_behaviour.unwrap_or(self.release_behaviour).clone(),
             self.disabled_layers.clone(),
         ]);
-        participant_action
+        Ok(participant_action)
     }
 }
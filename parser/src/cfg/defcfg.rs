@@ -6,6 +6,7 @@ use crate::custom_action::*;
 use crate::keys::*;
 #[allow(unused)]
 use crate::{anyhow_expr, anyhow_span, bail, bail_expr, bail_span};
+use std::time::Duration;
 
 #[cfg(any(target_os = "linux", target_os = "android", target_os = "unknown"))]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -69,6 +70,22 @@ pub enum LinuxCfgOutputBusType {
 pub struct CfgMacosOptions {
     pub macos_dev_names_include: Option<Vec<String>>,
     pub macos_dev_names_exclude: Option<Vec<String>>,
+    pub macos_write_error_policy: WriteErrorPolicy,
+}
+
+/// What to do when writing to the DriverKit virtual keyboard fails with an error other than the
+/// keyboard being disconnected.
+#[cfg(any(target_os = "macos", target_os = "unknown"))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WriteErrorPolicy {
+    /// Exit kanata with the error.
+    #[default]
+    Exit,
+    /// Release the input devices and wait for the output to recover, as when the virtual keyboard
+    /// is disconnected.
+    Recover,
+    /// Retry the write this many times with increasing delays, then exit.
+    Retry(u8),
 }
 
 #[cfg(any(
@@ -88,6 +105,8 @@ pub struct CfgWinterceptOptions {
 pub struct CfgWindowsOptions {
     pub windows_altgr: AltGrBehaviour,
     pub sync_keystates: bool,
+    /// Whether numpad keys report their NumLock-on keys regardless of the NumLock state.
+    pub numlock_normalize: bool,
 }
 
 #[cfg(all(any(target_os = "windows", target_os = "unknown"), feature = "gui"))]
@@ -132,29 +151,209 @@ impl Default for CfgOptionsGui {
     }
 }
 
+/// Framing used by the TCP server for client connections.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TcpServerProtocol {
+    /// Newline-delimited JSON directly over the TCP stream.
+    #[default]
+    Raw,
+    /// JSON messages carried in WebSocket text frames.
+    Ws,
+}
+
+/// JSON message format used by the TCP server.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TcpServerFormat {
+    /// Messages named by their only key, e.g. `{"ChangeLayer":{"new":"base"}}`.
+    #[default]
+    Legacy,
+    /// Messages named by a `type` field, e.g. `{"type":"SetLayer","name":"base"}`.
+    Tagged,
+}
+
+/// How input keys are identified.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EventMode {
+    /// Keys are identified by the key the OS keyboard layout assigns them.
+    #[default]
+    Logical,
+    /// Keys are identified by their physical position. `defsrc` also accepts PC scan codes.
+    Raw,
+}
+
+/// How plain `tap-hold` actions resolve when other keys are pressed before the hold timeout.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TapHoldMode {
+    /// Hold only when the timeout expires, like QMK's default behaviour.
+    #[default]
+    Timeout,
+    /// Hold as soon as another key is pressed, like `tap-hold-press`.
+    HoldOnOtherKeyPress,
+    /// Hold as soon as another key is pressed and released, like `tap-hold-release`.
+    PermissiveHold,
+}
+
+/// How input keys are debounced, configured by `debounce-algorithm`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DebounceAlgorithm {
+    /// Process a key change only after the key has not changed for the debounce time.
+    #[default]
+    Defer,
+    /// Process a key change immediately, then ignore changes of the key for the debounce time.
+    Eager,
+}
+
+/// Shape of the speed ramp of held `movemouse` actions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeedCurveShape {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+/// Speed of held `movemouse` actions over time, configured by `mouse-speed-curve`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeedCurve {
+    pub shape: SpeedCurveShape,
+    /// Multiplier when the key is first pressed.
+    pub start: f32,
+    /// Multiplier once the key has been held for `ramp_ms`.
+    pub max: f32,
+    pub ramp_ms: u16,
+}
+
+impl SpeedCurve {
+    /// Multiplier of the base movement distance after the action has been held for
+    /// `held_duration`.
+    pub fn speed_multiplier(&self, held_duration: Duration) -> f32 {
+        let t = (held_duration.as_secs_f32() * 1000.0 / f32::from(self.ramp_ms)).min(1.0);
+        let eased = match self.shape {
+            SpeedCurveShape::Linear => t,
+            SpeedCurveShape::EaseIn => t * t,
+            SpeedCurveShape::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            SpeedCurveShape::EaseInOut => t * t * (3.0 - 2.0 * t),
+        };
+        self.start + (self.max - self.start) * eased
+    }
+}
+
+/// Location of the Unix domain socket for the IPC server.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "unknown"
+))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnixSocketPath {
+    /// `/run/user/<UID>/kanata.sock`, resolved at startup.
+    Default,
+    Path(String),
+}
+
+/// Keys that can shut down or suspend the computer, which may only be output if they are listed in
+/// `allow-dangerous-keys`.
+pub const DANGEROUS_KEYS: [(&str, OsCode); 2] = [
+    ("system-power", OsCode::KEY_POWER),
+    ("system-sleep", OsCode::KEY_SLEEP),
+];
+
 #[derive(Debug)]
 pub struct CfgOptions {
     pub process_unmapped_keys: bool,
     pub process_unmapped_keys_exceptions: Option<Vec<(OsCode, SExpr)>>,
     pub block_unmapped_keys: bool,
+    /// Keys that are never processed, even if they are in `defsrc` or `process-unmapped-keys` is
+    /// enabled.
+    pub passthrough_keys: Vec<OsCode>,
+    /// Key that is pressed and released by the output self-test.
+    pub self_test_key: OsCode,
+    /// Key that stops all running macros when pressed, before being processed as usual.
+    pub macro_cancel_key: Option<OsCode>,
     pub allow_hardware_repeat: bool,
     pub start_alias: Option<String>,
     pub enable_cmd: bool,
+    /// Keys from [`DANGEROUS_KEYS`] that may be output.
+    pub allow_dangerous_keys: Vec<OsCode>,
     pub sequence_timeout: u16,
     pub sequence_input_mode: SequenceInputMode,
     pub sequence_backtrack_modcancel: bool,
     pub sequence_always_on: bool,
+    pub sequence_notifications: bool,
     pub log_layer_changes: bool,
     pub delegate_to_first_layer: bool,
     pub movemouse_inherit_accel_state: bool,
+    /// Whether mouse buttons held by a mouse lock action are released when the base layer
+    /// changes away from the layer the lock was engaged on.
+    pub mouse_lock_release_on_layer_change: bool,
+    /// Speed ramp of held `movemouse` actions. `None` moves at a constant speed.
+    pub mouse_speed_curve: Option<SpeedCurve>,
     pub movemouse_smooth_diagonals: bool,
     pub override_release_on_activation: bool,
+    pub clean_mods_on_action: bool,
     pub dynamic_macro_max_presses: u16,
     pub dynamic_macro_replay_delay_behaviour: ReplayDelayBehaviour,
+    pub dynamic_macro_persist_file: Option<String>,
     pub concurrent_tap_hold: bool,
     pub rapid_event_delay: u16,
     pub trans_resolution_behavior_v2: bool,
     pub chords_v2_min_idle: u16,
+    /// Address the TCP server listens on when `--port` is not given.
+    pub tcp_server_address: Option<std::net::SocketAddr>,
+    pub tcp_server_protocol: TcpServerProtocol,
+    pub tcp_server_format: TcpServerFormat,
+    pub tcp_server_token: Option<String>,
+    /// PEM file with the certificate chain that the TCP server uses for TLS.
+    pub tcp_server_tls_cert: Option<String>,
+    /// PEM file with the private key that the TCP server uses for TLS.
+    pub tcp_server_tls_key: Option<String>,
+    pub tcp_max_clients: u16,
+    /// How long a TCP command waits for the processing thread before it is answered with an
+    /// error instead.
+    pub tcp_command_timeout_ms: u16,
+    /// Upper bound on `KeyEvent` messages sent to TCP clients that enabled `MonitorKeys`.
+    pub monitor_max_events_per_second: u16,
+    /// Upper bound on key events written to the OS per second, 0 meaning no limit.
+    pub output_rate_limit: u16,
+    pub output_rate_limit_policy: OutputRateLimitPolicy,
+    /// Idle time in milliseconds and the unparsed action for `on-idle`.
+    pub on_idle: Option<(u32, SExpr)>,
+    /// Unparsed action for `on-resume`.
+    pub on_resume: Option<SExpr>,
+    /// Unparsed action for `on-exit`, run when the exit hotkey is pressed.
+    pub on_exit: Option<SExpr>,
+    /// Milliseconds to wait for the `on-exit` action to finish before exiting anyway.
+    pub on_exit_timeout: u32,
+    /// Output keys held for longer than this without a physical key holding them are released.
+    pub max_output_hold_ms: Option<u32>,
+    /// Debounce time of input keys without an entry in `debounce_keys`. 0 disables debouncing.
+    pub debounce_ms: u16,
+    pub debounce_algorithm: DebounceAlgorithm,
+    /// Per-key debounce times that override `debounce_ms`.
+    pub debounce_keys: Vec<(OsCode, u16)>,
+    pub runtime_vars_persist_on_reload: bool,
+    /// Whether `rpt` replays macros, or only repeats the last key after a macro.
+    pub rpt_repeats_macros: bool,
+    /// Number of recent press intervals averaged for `tap-hold-adaptive`.
+    pub tap_hold_adaptive_window: u16,
+    /// Maximum number of layers pushed by `layer-push` at once.
+    pub layer_stack_max_depth: u16,
+    /// Hold timeout of tap-hold actions with `_` as the timeout, on layers without `tap-timeout`.
+    pub tap_hold_timeout: u16,
+    /// Resolution strategy of plain `tap-hold` actions.
+    pub tap_hold_mode: TapHoldMode,
+    pub event_mode: EventMode,
+    /// Write dead keys of the OS keyboard layout, and the key after each, straight to the output.
+    #[cfg(any(target_os = "windows", target_os = "macos", target_os = "unknown"))]
+    pub track_dead_keys: bool,
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "unknown"
+    ))]
+    pub unix_socket_path: Option<UnixSocketPath>,
     #[cfg(any(
         all(target_os = "windows", feature = "interception_driver"),
         target_os = "linux",
@@ -183,24 +382,68 @@ impl Default for CfgOptions {
             process_unmapped_keys: false,
             process_unmapped_keys_exceptions: None,
             block_unmapped_keys: false,
+            passthrough_keys: vec![],
+            self_test_key: OsCode::KEY_F24,
+            macro_cancel_key: None,
             allow_hardware_repeat: true,
             start_alias: None,
             enable_cmd: false,
+            allow_dangerous_keys: vec![],
             sequence_timeout: 1000,
             sequence_input_mode: SequenceInputMode::HiddenSuppressed,
             sequence_backtrack_modcancel: true,
             sequence_always_on: false,
+            sequence_notifications: false,
             log_layer_changes: true,
             delegate_to_first_layer: false,
             movemouse_inherit_accel_state: false,
+            mouse_lock_release_on_layer_change: true,
+            mouse_speed_curve: None,
             movemouse_smooth_diagonals: false,
             override_release_on_activation: false,
+            clean_mods_on_action: false,
             dynamic_macro_max_presses: 128,
             dynamic_macro_replay_delay_behaviour: ReplayDelayBehaviour::Recorded,
+            dynamic_macro_persist_file: None,
             concurrent_tap_hold: false,
             rapid_event_delay: 5,
             trans_resolution_behavior_v2: true,
             chords_v2_min_idle: 5,
+            tcp_server_address: None,
+            tcp_server_protocol: TcpServerProtocol::Raw,
+            tcp_server_format: TcpServerFormat::Legacy,
+            tcp_server_token: None,
+            tcp_server_tls_cert: None,
+            tcp_server_tls_key: None,
+            tcp_max_clients: 8,
+            tcp_command_timeout_ms: 1000,
+            monitor_max_events_per_second: 500,
+            output_rate_limit: 2000,
+            output_rate_limit_policy: OutputRateLimitPolicy::Delay,
+            on_idle: None,
+            on_exit: None,
+            on_exit_timeout: 3000,
+            max_output_hold_ms: None,
+            debounce_ms: 0,
+            debounce_algorithm: DebounceAlgorithm::Defer,
+            debounce_keys: vec![],
+            on_resume: None,
+            runtime_vars_persist_on_reload: false,
+            rpt_repeats_macros: true,
+            tap_hold_adaptive_window: 8,
+            layer_stack_max_depth: 8,
+            tap_hold_timeout: 200,
+            tap_hold_mode: TapHoldMode::Timeout,
+            event_mode: EventMode::Logical,
+            #[cfg(any(target_os = "windows", target_os = "macos", target_os = "unknown"))]
+            track_dead_keys: false,
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "android",
+                target_os = "macos",
+                target_os = "unknown"
+            ))]
+            unix_socket_path: None,
             #[cfg(any(
                 all(target_os = "windows", feature = "interception_driver"),
                 target_os = "linux",
@@ -231,6 +474,11 @@ pub fn parse_defcfg(expr: &[SExpr]) -> Result<CfgOptions> {
     let mut cfg = CfgOptions::default();
     let mut exprs = check_first_expr(expr.iter(), "defcfg")?;
     let mut is_process_unmapped_keys_defined = false;
+    let mut tls_expr = None;
+    let mut speed_curve_shape = None;
+    let mut speed_start = (None, 1.0);
+    let mut speed_max = (None, 3.0);
+    let mut speed_ramp_ms = (None, 500);
     // Read k-v pairs from the configuration
     loop {
         let key = match exprs.next() {
@@ -241,6 +489,40 @@ pub fn parse_defcfg(expr: &[SExpr]) -> Result<CfgOptions> {
                         "The item process-unmapped-keys is not defined in defcfg. Consider whether process-unmapped-keys should be yes vs. no."
                     );
                 }
+                if let Some(expr) = tls_expr {
+                    if cfg.tcp_server_tls_cert.is_none() || cfg.tcp_server_tls_key.is_none() {
+                        bail_expr!(
+                            expr,
+                            "tcp-server-tls-cert and tcp-server-tls-key must be used together"
+                        );
+                    }
+                    if cfg.tcp_server_protocol == TcpServerProtocol::Ws {
+                        bail_expr!(expr, "TLS is not supported with tcp-server-protocol ws");
+                    }
+                }
+                match speed_curve_shape {
+                    Some(shape) => {
+                        if let (Some(expr), true) =
+                            (speed_max.0.or(speed_start.0), speed_max.1 < speed_start.1)
+                        {
+                            bail_expr!(
+                                expr,
+                                "mouse-speed-max must not be less than mouse-speed-start"
+                            );
+                        }
+                        cfg.mouse_speed_curve = Some(SpeedCurve {
+                            shape,
+                            start: speed_start.1,
+                            max: speed_max.1,
+                            ramp_ms: speed_ramp_ms.1,
+                        });
+                    }
+                    None => {
+                        if let Some(expr) = speed_start.0.or(speed_max.0).or(speed_ramp_ms.0) {
+                            bail_expr!(expr, "This option requires mouse-speed-curve to be set");
+                        }
+                    }
+                }
                 return Ok(cfg);
             }
         };
@@ -266,6 +548,9 @@ pub fn parse_defcfg(expr: &[SExpr]) -> Result<CfgOptions> {
                     "sequence-always-on" => {
                         cfg.sequence_always_on = parse_defcfg_val_bool(val, label)?
                     }
+                    "sequence-notifications" => {
+                        cfg.sequence_notifications = parse_defcfg_val_bool(val, label)?
+                    }
                     "dynamic-macro-max-presses" => {
                         cfg.dynamic_macro_max_presses = parse_cfg_val_u16(val, label, false)?;
                     }
@@ -284,6 +569,13 @@ pub fn parse_defcfg(expr: &[SExpr]) -> Result<CfgOptions> {
                                 anyhow_expr!(val, "this option must be one of: constant | recorded")
                             })??;
                     }
+                    "dynamic-macro-persist-file" => {
+                        let path = sexpr_to_str_or_err(val, label)?;
+                        if path.is_empty() {
+                            bail_expr!(val, "{label} must not be empty");
+                        }
+                        cfg.dynamic_macro_persist_file = Some(path.to_owned());
+                    }
                     "linux-dev" => {
                         #[cfg(any(
                             target_os = "linux",
@@ -324,6 +616,18 @@ pub fn parse_defcfg(expr: &[SExpr]) -> Result<CfgOptions> {
                             cfg.linux_opts.linux_dev_names_exclude = Some(parse_dev(val)?);
                         }
                     }
+                    "self-test-key" => {
+                        let v = sexpr_to_str_or_err(val, label)?;
+                        cfg.self_test_key = str_to_oscode(v)
+                            .ok_or_else(|| anyhow_expr!(val, "unknown key for {label}: {v}"))?;
+                    }
+                    "macro-cancel-key" => {
+                        let v = sexpr_to_str_or_err(val, label)?;
+                        cfg.macro_cancel_key =
+                            Some(str_to_oscode(v).ok_or_else(|| {
+                                anyhow_expr!(val, "unknown key for {label}: {v}")
+                            })?);
+                    }
                     "linux-unicode-u-code" => {
                         #[cfg(any(
                             target_os = "linux",
@@ -485,6 +789,12 @@ pub fn parse_defcfg(expr: &[SExpr]) -> Result<CfgOptions> {
                             cfg.windows_opts.sync_keystates = parse_defcfg_val_bool(val, label)?;
                         }
                     }
+                    "windows-numlock-normalize" => {
+                        #[cfg(any(target_os = "windows", target_os = "unknown"))]
+                        {
+                            cfg.windows_opts.numlock_normalize = parse_defcfg_val_bool(val, label)?;
+                        }
+                    }
                     "windows-interception-mouse-hwid" => {
                         #[cfg(any(
                             all(feature = "interception_driver", target_os = "windows"),
@@ -677,6 +987,13 @@ pub fn parse_defcfg(expr: &[SExpr]) -> Result<CfgOptions> {
                             cfg.macos_opts.macos_dev_names_exclude = Some(dev_names);
                         }
                     }
+                    "write-error-policy" => {
+                        #[cfg(any(target_os = "macos", target_os = "unknown"))]
+                        {
+                            cfg.macos_opts.macos_write_error_policy =
+                                parse_write_error_policy(val, label)?;
+                        }
+                    }
                     "tray-icon" => {
                         #[cfg(all(
                             any(target_os = "windows", target_os = "unknown"),
@@ -824,6 +1141,23 @@ pub fn parse_defcfg(expr: &[SExpr]) -> Result<CfgOptions> {
                     "block-unmapped-keys" => {
                         cfg.block_unmapped_keys = parse_defcfg_val_bool(val, label)?
                     }
+                    "passthrough-keys" => {
+                        let list = val
+                            .list(None)
+                            .ok_or_else(|| anyhow_expr!(val, "Expected (key1 ... keyN)."))?;
+                        let mut keys: Vec<OsCode> = vec![];
+                        for key_expr in list.iter() {
+                            let key =
+                                key_expr.atom(None).and_then(str_to_oscode).ok_or_else(|| {
+                                    anyhow_expr!(key_expr, "Expected a known key name.")
+                                })?;
+                            if keys.contains(&key) {
+                                bail_expr!(key_expr, "Duplicate key name is not allowed.");
+                            }
+                            keys.push(key);
+                        }
+                        cfg.passthrough_keys = keys;
+                    }
                     "allow-hardware-repeat" => {
                         cfg.allow_hardware_repeat = parse_defcfg_val_bool(val, label)?
                     }
@@ -831,6 +1165,34 @@ pub fn parse_defcfg(expr: &[SExpr]) -> Result<CfgOptions> {
                         cfg.start_alias = parse_defcfg_val_string(val, label)?
                     }
                     "danger-enable-cmd" => cfg.enable_cmd = parse_defcfg_val_bool(val, label)?,
+                    "allow-dangerous-keys" => {
+                        let list = val
+                            .list(None)
+                            .ok_or_else(|| anyhow_expr!(val, "Expected (key1 ... keyN)."))?;
+                        let mut keys: Vec<OsCode> = vec![];
+                        for key_expr in list.iter() {
+                            let key = key_expr
+                                .atom(None)
+                                .and_then(|name| {
+                                    DANGEROUS_KEYS
+                                        .iter()
+                                        .find(|(dangerous, _)| *dangerous == name)
+                                })
+                                .map(|(_, osc)| *osc)
+                                .ok_or_else(|| {
+                                    anyhow_expr!(
+                                        key_expr,
+                                        "Expected one of: {}",
+                                        DANGEROUS_KEYS.map(|(name, _)| name).join(" ")
+                                    )
+                                })?;
+                            if keys.contains(&key) {
+                                bail_expr!(key_expr, "Duplicate key name is not allowed.");
+                            }
+                            keys.push(key);
+                        }
+                        cfg.allow_dangerous_keys = keys;
+                    }
                     "sequence-backtrack-modcancel" => {
                         cfg.sequence_backtrack_modcancel = parse_defcfg_val_bool(val, label)?
                     }
@@ -862,12 +1224,92 @@ pub fn parse_defcfg(expr: &[SExpr]) -> Result<CfgOptions> {
                     "movemouse-inherit-accel-state" => {
                         cfg.movemouse_inherit_accel_state = parse_defcfg_val_bool(val, label)?
                     }
+                    "mouse-lock-release-on-layer-change" => {
+                        cfg.mouse_lock_release_on_layer_change = parse_defcfg_val_bool(val, label)?
+                    }
+                    "mouse-speed-curve" => {
+                        speed_curve_shape = Some(match sexpr_to_str_or_err(val, label)? {
+                            "linear" => SpeedCurveShape::Linear,
+                            "ease-in" => SpeedCurveShape::EaseIn,
+                            "ease-out" => SpeedCurveShape::EaseOut,
+                            "ease-in-out" => SpeedCurveShape::EaseInOut,
+                            v => bail_expr!(
+                                val,
+                                "{label} got {v}. It accepts: linear, ease-in, ease-out, ease-in-out"
+                            ),
+                        });
+                    }
+                    "mouse-speed-start" => {
+                        speed_start = (Some(val), parse_defcfg_val_multiplier(val, label)?);
+                    }
+                    "mouse-speed-max" => {
+                        speed_max = (Some(val), parse_defcfg_val_multiplier(val, label)?);
+                    }
+                    "mouse-speed-ramp-ms" => {
+                        speed_ramp_ms = (Some(val), parse_cfg_val_u16(val, label, true)?);
+                    }
                     "override-release-on-activation" => {
                         cfg.override_release_on_activation = parse_defcfg_val_bool(val, label)?
                     }
+                    "clean-mods-on-action" => {
+                        cfg.clean_mods_on_action = parse_defcfg_val_bool(val, label)?
+                    }
                     "concurrent-tap-hold" => {
                         cfg.concurrent_tap_hold = parse_defcfg_val_bool(val, label)?
                     }
+                    "runtime-vars-persist-on-reload" => {
+                        cfg.runtime_vars_persist_on_reload = parse_defcfg_val_bool(val, label)?
+                    }
+                    "rpt-repeats-macros" => {
+                        cfg.rpt_repeats_macros = parse_defcfg_val_bool(val, label)?
+                    }
+                    "tap-hold-adaptive-window" => {
+                        cfg.tap_hold_adaptive_window = parse_cfg_val_u16(val, label, true)?
+                    }
+                    "layer-stack-max-depth" => {
+                        const MAX_DEPTH: u16 = 32;
+                        let depth = parse_cfg_val_u16(val, label, true)?;
+                        if depth > MAX_DEPTH {
+                            bail_expr!(val, "{label} must be at most {MAX_DEPTH}");
+                        }
+                        cfg.layer_stack_max_depth = depth;
+                    }
+                    "tap-hold-timeout" => {
+                        cfg.tap_hold_timeout = parse_cfg_val_u16(val, label, true)?
+                    }
+                    "tap-hold-mode" => {
+                        let v = sexpr_to_str_or_err(val, label)?;
+                        cfg.tap_hold_mode = match v {
+                            "timeout" => TapHoldMode::Timeout,
+                            "hold-on-other-key-press" => TapHoldMode::HoldOnOtherKeyPress,
+                            "permissive-hold" => TapHoldMode::PermissiveHold,
+                            _ => bail_expr!(
+                                val,
+                                "{label} got {v}. It accepts: 'timeout', \
+                                 'hold-on-other-key-press' or 'permissive-hold'"
+                            ),
+                        };
+                    }
+                    "event-mode" => {
+                        let v = sexpr_to_str_or_err(val, label)?;
+                        cfg.event_mode = match v {
+                            "logical" => EventMode::Logical,
+                            "raw" => EventMode::Raw,
+                            _ => {
+                                bail_expr!(val, "{label} got {}. It accepts: 'logical' or 'raw'", v)
+                            }
+                        };
+                    }
+                    "track-dead-keys" => {
+                        #[cfg(any(
+                            target_os = "windows",
+                            target_os = "macos",
+                            target_os = "unknown"
+                        ))]
+                        {
+                            cfg.track_dead_keys = parse_defcfg_val_bool(val, label)?
+                        }
+                    }
                     "rapid-event-delay" => {
                         cfg.rapid_event_delay = parse_cfg_val_u16(val, label, false)?
                     }
@@ -896,6 +1338,165 @@ pub fn parse_defcfg(expr: &[SExpr]) -> Result<CfgOptions> {
                         }
                         cfg.chords_v2_min_idle = min_idle;
                     }
+                    "tcp-server-address" => {
+                        let v = sexpr_to_str_or_err(val, label)?;
+                        let address = match v.parse::<u16>() {
+                            Ok(port) => format!("127.0.0.1:{port}"),
+                            Err(_) => v.to_owned(),
+                        };
+                        cfg.tcp_server_address = Some(address.parse().map_err(|e| {
+                            anyhow_expr!(
+                                val,
+                                "{label} got {v}: {e}.\n\
+                                 Use a port, e.g. 5829, or an address and port, \
+                                 e.g. 0.0.0.0:5829 or [::1]:5829"
+                            )
+                        })?);
+                    }
+                    "tcp-server-protocol" => {
+                        let v = sexpr_to_str_or_err(val, label)?;
+                        cfg.tcp_server_protocol = match v {
+                            "raw" => TcpServerProtocol::Raw,
+                            "ws" => TcpServerProtocol::Ws,
+                            _ => bail_expr!(val, "{label} got {}. It accepts: 'raw' or 'ws'", v),
+                        };
+                    }
+                    "tcp-server-format" => {
+                        let v = sexpr_to_str_or_err(val, label)?;
+                        cfg.tcp_server_format = match v {
+                            "legacy" => TcpServerFormat::Legacy,
+                            "tagged" => TcpServerFormat::Tagged,
+                            _ => bail_expr!(
+                                val,
+                                "{label} got {}. It accepts: 'legacy' or 'tagged'",
+                                v
+                            ),
+                        };
+                    }
+                    "tcp-server-token" => {
+                        let token = sexpr_to_str_or_err(val, label)?;
+                        if token.is_empty() {
+                            bail_expr!(val, "{label} must not be empty");
+                        }
+                        cfg.tcp_server_token = Some(token.to_owned());
+                    }
+                    "tcp-server-tls-cert" | "tcp-server-tls-key" => {
+                        let path = sexpr_to_str_or_err(val, label)?;
+                        if path.is_empty() {
+                            bail_expr!(val, "{label} must not be empty");
+                        }
+                        match label {
+                            "tcp-server-tls-cert" => {
+                                cfg.tcp_server_tls_cert = Some(path.to_owned())
+                            }
+                            _ => cfg.tcp_server_tls_key = Some(path.to_owned()),
+                        }
+                        tls_expr = Some(key);
+                    }
+                    "tcp-max-clients" => {
+                        cfg.tcp_max_clients = parse_cfg_val_u16(val, label, true)?;
+                    }
+                    "tcp-command-timeout-ms" => {
+                        cfg.tcp_command_timeout_ms = parse_cfg_val_u16(val, label, true)?;
+                    }
+                    "monitor-max-events-per-second" => {
+                        cfg.monitor_max_events_per_second = parse_cfg_val_u16(val, label, true)?;
+                    }
+                    "output-rate-limit" => {
+                        cfg.output_rate_limit = parse_cfg_val_u16(val, label, false)?;
+                    }
+                    "output-rate-limit-policy" => {
+                        cfg.output_rate_limit_policy = match sexpr_to_str_or_err(val, label)? {
+                            "delay" => OutputRateLimitPolicy::Delay,
+                            "drop" => OutputRateLimitPolicy::Drop,
+                            _ => bail_expr!(val, "{label} must be one of: delay | drop"),
+                        };
+                    }
+                    "on-idle" => {
+                        const ERR_MSG: &str =
+                            "expects a list of an idle time in milliseconds and an action";
+                        let (idle_ms, action) = match sexpr_to_list_or_err(val, label)? {
+                            [idle_ms, action] => (idle_ms, action),
+                            _ => bail_expr!(val, "{label} {ERR_MSG}"),
+                        };
+                        let idle_ms = match idle_ms.atom(None).map(str::parse::<u32>) {
+                            Some(Ok(ms)) if ms > 0 => ms,
+                            _ => bail_expr!(
+                                idle_ms,
+                                "{label} {ERR_MSG}, and the idle time must be non-zero"
+                            ),
+                        };
+                        cfg.on_idle = Some((idle_ms, action.clone()));
+                    }
+                    "on-resume" => {
+                        cfg.on_resume = Some(val.clone());
+                    }
+                    "on-exit" => {
+                        cfg.on_exit = Some(val.clone());
+                    }
+                    "max-output-hold-ms" => {
+                        cfg.max_output_hold_ms = match sexpr_to_str_or_err(val, label)?.parse() {
+                            Ok(ms) if ms > 0 => Some(ms),
+                            _ => {
+                                bail_expr!(val, "{label} must be a non-zero number of milliseconds")
+                            }
+                        };
+                    }
+                    "debounce-ms" => cfg.debounce_ms = parse_cfg_val_u16(val, label, false)?,
+                    "debounce-algorithm" => {
+                        let v = sexpr_to_str_or_err(val, label)?;
+                        cfg.debounce_algorithm = match v {
+                            "defer" => DebounceAlgorithm::Defer,
+                            "eager" => DebounceAlgorithm::Eager,
+                            _ => {
+                                bail_expr!(val, "{label} got {v}. It accepts: 'defer' or 'eager'")
+                            }
+                        };
+                    }
+                    "debounce-keys" => {
+                        let err = "Expected (key1 ms1 ... keyN msN).";
+                        let list = val.list(None).ok_or_else(|| anyhow_expr!(val, "{err}"))?;
+                        if list.len() % 2 != 0 {
+                            bail_expr!(val, "{err}");
+                        }
+                        let mut keys: Vec<(OsCode, u16)> = vec![];
+                        for pair in list.chunks_exact(2) {
+                            let key =
+                                pair[0].atom(None).and_then(str_to_oscode).ok_or_else(|| {
+                                    anyhow_expr!(&pair[0], "Expected a known key name.")
+                                })?;
+                            if keys.iter().any(|(k, _)| *k == key) {
+                                bail_expr!(&pair[0], "Duplicate key name is not allowed.");
+                            }
+                            keys.push((key, parse_cfg_val_u16(&pair[1], label, false)?));
+                        }
+                        cfg.debounce_keys = keys;
+                    }
+                    "on-exit-timeout" => {
+                        cfg.on_exit_timeout = match sexpr_to_str_or_err(val, label)?.parse() {
+                            Ok(ms) if ms > 0 => ms,
+                            _ => {
+                                bail_expr!(val, "{label} must be a non-zero number of milliseconds")
+                            }
+                        };
+                    }
+                    "unix-socket-path" => {
+                        #[cfg(any(
+                            target_os = "linux",
+                            target_os = "android",
+                            target_os = "macos",
+                            target_os = "unknown"
+                        ))]
+                        {
+                            let v = sexpr_to_str_or_err(val, label)?;
+                            cfg.unix_socket_path = match v.to_ascii_lowercase().as_str() {
+                                "" => bail_expr!(val, "{label} must not be empty"),
+                                "yes" | "true" => Some(UnixSocketPath::Default),
+                                "no" | "false" => None,
+                                _ => Some(UnixSocketPath::Path(v.to_owned())),
+                            };
+                        }
+                    }
                     "mouse-movement-key" => {
                         #[cfg(any(
                             all(target_os = "windows", feature = "interception_driver"),
@@ -915,7 +1516,7 @@ pub fn parse_defcfg(expr: &[SExpr]) -> Result<CfgOptions> {
                             }
                         }
                     }
-                    _ => bail_expr!(key, "Unknown defcfg option {}", label),
+                    _ => bail_expr!(key, "{}", unknown_defcfg_option_msg(label)),
                 };
             }
             SExpr::List(_) => {
@@ -925,6 +1526,165 @@ pub fn parse_defcfg(expr: &[SExpr]) -> Result<CfgOptions> {
     }
 }
 
+/// Names of the `defcfg` options, for the error message of an unknown option. Tests check that
+/// this matches the options that `parse_defcfg` accepts.
+pub const DEFCFG_OPTIONS: &[&str] = &[
+    "sequence-timeout",
+    "sequence-input-mode",
+    "sequence-always-on",
+    "sequence-notifications",
+    "dynamic-macro-max-presses",
+    "dynamic-macro-replay-delay-behaviour",
+    "dynamic-macro-persist-file",
+    "linux-dev",
+    "linux-dev-names-include",
+    "linux-dev-names-exclude",
+    "self-test-key",
+    "macro-cancel-key",
+    "linux-unicode-u-code",
+    "linux-unicode-termination",
+    "linux-x11-repeat-delay-rate",
+    "linux-use-trackpoint-property",
+    "linux-output-device-name",
+    "linux-output-device-bus-type",
+    "linux-device-detect-mode",
+    "windows-altgr",
+    "windows-sync-keystates",
+    "windows-numlock-normalize",
+    "windows-interception-mouse-hwid",
+    "windows-interception-mouse-hwids",
+    "windows-interception-mouse-hwids-exclude",
+    "windows-interception-keyboard-hwids",
+    "windows-interception-keyboard-hwids-exclude",
+    "macos-dev-names-include",
+    "macos-dev-names-exclude",
+    "write-error-policy",
+    "tray-icon",
+    "icon-match-layer-name",
+    "tooltip-layer-changes",
+    "tooltip-show-blank",
+    "tooltip-no-base",
+    "tooltip-duration",
+    "notify-cfg-reload",
+    "notify-cfg-reload-silent",
+    "notify-error",
+    "tooltip-size",
+    "process-unmapped-keys",
+    "block-unmapped-keys",
+    "passthrough-keys",
+    "allow-hardware-repeat",
+    "alias-to-trigger-on-load",
+    "danger-enable-cmd",
+    "allow-dangerous-keys",
+    "sequence-backtrack-modcancel",
+    "log-layer-changes",
+    "delegate-to-first-layer",
+    "linux-continue-if-no-devs-found",
+    "movemouse-smooth-diagonals",
+    "movemouse-inherit-accel-state",
+    "mouse-lock-release-on-layer-change",
+    "mouse-speed-curve",
+    "mouse-speed-start",
+    "mouse-speed-max",
+    "mouse-speed-ramp-ms",
+    "override-release-on-activation",
+    "clean-mods-on-action",
+    "concurrent-tap-hold",
+    "runtime-vars-persist-on-reload",
+    "rpt-repeats-macros",
+    "tap-hold-adaptive-window",
+    "layer-stack-max-depth",
+    "tap-hold-timeout",
+    "tap-hold-mode",
+    "event-mode",
+    "track-dead-keys",
+    "rapid-event-delay",
+    "transparent-key-resolution",
+    "chords-v2-min-idle",
+    "tcp-server-address",
+    "tcp-server-protocol",
+    "tcp-server-format",
+    "tcp-server-token",
+    "tcp-server-tls-cert",
+    "tcp-server-tls-key",
+    "tcp-max-clients",
+    "tcp-command-timeout-ms",
+    "monitor-max-events-per-second",
+    "output-rate-limit",
+    "output-rate-limit-policy",
+    "on-idle",
+    "on-resume",
+    "on-exit",
+    "max-output-hold-ms",
+    "debounce-ms",
+    "debounce-algorithm",
+    "debounce-keys",
+    "on-exit-timeout",
+    "unix-socket-path",
+    "mouse-movement-key",
+];
+
+fn unknown_defcfg_option_msg(label: &str) -> String {
+    let suggestion = DEFCFG_OPTIONS
+        .iter()
+        .map(|opt| (levenshtein(label, opt), opt))
+        .filter(|(distance, _)| *distance <= 3.max(label.len() / 4))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, opt)| format!("\nDid you mean {opt}?"))
+        .unwrap_or_default();
+    format!(
+        "Unknown defcfg option {label}.{suggestion}\nKnown options: {}",
+        DEFCFG_OPTIONS.join(", ")
+    )
+}
+
+/// The number of single character insertions, deletions and substitutions to turn `a` into `b`.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev_row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev_row[j] + usize::from(ca != *cb);
+            row.push(substitution.min(prev_row[j + 1] + 1).min(row[j] + 1));
+        }
+        prev_row = row;
+    }
+    prev_row[b.len()]
+}
+
+#[cfg(any(target_os = "macos", target_os = "unknown"))]
+fn parse_write_error_policy(expr: &SExpr, label: &str) -> Result<WriteErrorPolicy> {
+    const ERR_MSG: &str = "Valid values are exit, recover, or (retry <count>)";
+    if let Some(list) = expr.list(None) {
+        match list {
+            [name, count] if name.atom(None) == Some("retry") => {
+                let count = count
+                    .atom(None)
+                    .and_then(|c| str::parse::<u8>(c).ok())
+                    .filter(|c| *c > 0)
+                    .ok_or_else(|| {
+                        anyhow_expr!(count, "retry count must be a number from 1 to 255")
+                    })?;
+                return Ok(WriteErrorPolicy::Retry(count));
+            }
+            _ => bail_expr!(expr, "Invalid value for {label}.\n{ERR_MSG}"),
+        }
+    }
+    match sexpr_to_str_or_err(expr, label)? {
+        "exit" => Ok(WriteErrorPolicy::Exit),
+        "recover" => Ok(WriteErrorPolicy::Recover),
+        v => bail_expr!(expr, "Invalid value for {label}: {v}.\n{ERR_MSG}"),
+    }
+}
+
+fn parse_defcfg_val_multiplier(expr: &SExpr, label: &str) -> Result<f32> {
+    match sexpr_to_str_or_err(expr, label)?.parse::<f32>() {
+        Ok(v) if v.is_finite() && v > 0.0 => Ok(v),
+        _ => bail_expr!(expr, "{label} must be a positive number, e.g. 1.5"),
+    }
+}
+
 fn parse_defcfg_val_string(expr: &SExpr, _label: &str) -> Result<Option<String>> {
     match expr {
         SExpr::Atom(v) => Ok(Some(v.t.clone())),
@@ -936,7 +1696,7 @@ pub const FALSE_VALUES: [&str; 3] = ["no", "false", "0"];
 pub const TRUE_VALUES: [&str; 3] = ["yes", "true", "1"];
 pub const BOOLEAN_VALUES: [&str; 6] = ["yes", "true", "1", "no", "false", "0"];
 
-fn parse_defcfg_val_bool(expr: &SExpr, label: &str) -> Result<bool> {
+pub(crate) fn parse_defcfg_val_bool(expr: &SExpr, label: &str) -> Result<bool> {
     match &expr {
         SExpr::Atom(v) => {
             let val = v.t.trim_atom_quotes().to_ascii_lowercase();
@@ -962,7 +1722,7 @@ fn parse_defcfg_val_bool(expr: &SExpr, label: &str) -> Result<bool> {
     }
 }
 
-fn parse_cfg_val_u16(expr: &SExpr, label: &str, exclude_zero: bool) -> Result<u16> {
+pub(crate) fn parse_cfg_val_u16(expr: &SExpr, label: &str, exclude_zero: bool) -> Result<u16> {
     let start = if exclude_zero { 1 } else { 0 };
     match &expr {
         SExpr::Atom(v) => Ok(str::parse::<u16>(v.t.trim_atom_quotes())
@@ -1050,10 +1810,6 @@ fn sexpr_to_str_or_err<'a>(expr: &'a SExpr, label: &str) -> Result<&'a str> {
     }
 }
 
-#[cfg(any(
-    all(feature = "interception_driver", target_os = "windows"),
-    target_os = "unknown"
-))]
 fn sexpr_to_list_or_err<'a>(expr: &'a SExpr, label: &str) -> Result<&'a [SExpr]> {
     match expr {
         SExpr::Atom(_) => bail_expr!(expr, "The value for {label} must be a list"),
@@ -1130,6 +1886,16 @@ pub enum AltGrBehaviour {
 ))]
 pub const HWID_ARR_SZ: usize = 1024;
 
+/// What happens to key events written above `output-rate-limit`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputRateLimitPolicy {
+    /// Wait until the event is within the limit.
+    #[default]
+    Delay,
+    /// Discard the event. Releases are only discarded if their press was discarded.
+    Drop,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ReplayDelayBehaviour {
     /// Always use a fixed number of ticks between presses and releases.
@@ -82,12 +82,35 @@ struct CfgError {
     file_content: Option<String>,
 }
 
+const GUIDE_LINK: &str = "For more info, see the configuration guide:
+https://github.com/jtroo/kanata/blob/main/docs/config.adoc";
+
 pub(super) fn help(err_msg: impl AsRef<str>) -> String {
-    format!(
-        r"{}
+    format!("{}\n\n{GUIDE_LINK}", err_msg.as_ref())
+}
 
-For more info, see the configuration guide:
-https://github.com/jtroo/kanata/blob/main/docs/config.adoc",
-        err_msg.as_ref(),
-    )
+/// Condenses a configuration error into a single line of the form `<file>:<line>: <message>`,
+/// for consumers that cannot display the full diagnostic report.
+pub fn error_summary(err: &miette::Error) -> String {
+    let msg = match err.help() {
+        Some(help) => help.to_string().trim_end_matches(GUIDE_LINK).to_string(),
+        None => err.to_string(),
+    };
+    let msg = msg
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let location = err
+        .labels()
+        .and_then(|mut labels| labels.next())
+        .and_then(|label| {
+            let contents = err.source_code()?.read_span(label.inner(), 0, 0).ok()?;
+            Some(format!("{}:{}", contents.name()?, contents.line() + 1))
+        });
+    match location {
+        Some(location) => format!("{location}: {msg}"),
+        None => msg,
+    }
 }
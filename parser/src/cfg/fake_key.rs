@@ -177,6 +177,34 @@ fn parse_delay(
         })))))
 }
 
+pub(crate) fn parse_fakekeys(
+    ac_params: &[SExpr],
+    s: &ParserState,
+) -> Result<&'static KanataAction> {
+    const ERR_MSG: &str =
+        "fakekeys expects one or more lists: (<(tap|press|release|toggle)> <key-name>)";
+    if ac_params.is_empty() {
+        bail!("{ERR_MSG}");
+    }
+    let ops = ac_params
+        .iter()
+        .map(|op| {
+            let Some([action, name]) = op.list(s.vars()) else {
+                bail_expr!(op, "{ERR_MSG}");
+            };
+            let action = match action.atom(s.vars()) {
+                Some("tap") => FakeKeyAction::Tap,
+                Some("press") => FakeKeyAction::Press,
+                Some("release") => FakeKeyAction::Release,
+                Some("toggle") => FakeKeyAction::Toggle,
+                _ => bail_expr!(action, "{ERR_MSG}\nUnknown virtual key action"),
+            };
+            Ok((parse_vkey_coord(name, s)?, action))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    custom(CustomAction::FakeKeys(s.a.sref_vec(ops)), &s.a)
+}
+
 pub(crate) fn parse_vkey_coord(param: &SExpr, s: &ParserState) -> Result<Coord> {
     let name = param
         .atom(s.vars())
@@ -216,7 +244,15 @@ pub(crate) fn parse_on_press(
     ac_params: &[SExpr],
     s: &ParserState,
 ) -> Result<&'static KanataAction> {
-    const ERR_MSG: &str = "on-press expects two parameters: <action> <key-name>";
+    const ERR_MSG: &str = "on-press expects two parameters: <action> <key-name>\n\
+                           or three parameters: <action> on-release <action>";
+    if ac_params.len() == 3 && ac_params[1].atom(s.vars()) == Some(ON_RELEASE) {
+        let press_action = parse_action(&ac_params[0], s)?;
+        let release_action = parse_release_action(&ac_params[2], s)?;
+        return Ok(s.a.sref(Action::MultipleActions(
+            s.a.sref(s.a.sref_vec(vec![*press_action, *release_action])),
+        )));
+    }
     if ac_params.len() != 2 {
         bail!("{ERR_MSG}");
     }
@@ -232,7 +268,11 @@ pub(crate) fn parse_on_release(
     ac_params: &[SExpr],
     s: &ParserState,
 ) -> Result<&'static KanataAction> {
-    const ERR_MSG: &str = "on-release expects two parameters: <action> <key-name>";
+    const ERR_MSG: &str = "on-release expects two parameters: <action> <key-name>\n\
+                           or one parameter: <action>";
+    if ac_params.len() == 1 {
+        return parse_release_action(&ac_params[0], s);
+    }
     if ac_params.len() != 2 {
         bail!("{ERR_MSG}");
     }
@@ -244,6 +284,19 @@ pub(crate) fn parse_on_release(
     ))))
 }
 
+/// Parses an action that is tapped when the key is released, using a hidden fake key.
+fn parse_release_action(expr: &SExpr, s: &ParserState) -> Result<&'static KanataAction> {
+    let action = parse_action(expr, s)?;
+    let y = add_hidden_fake_key(expr, action, s)?;
+    let (x, y) = get_fake_key_coords(y);
+    Ok(s.a.sref(Action::Custom(s.a.sref(s.a.sref_slice(
+        CustomAction::FakeKeyOnRelease {
+            coord: Coord { x, y },
+            action: FakeKeyAction::Tap,
+        },
+    )))))
+}
+
 pub(crate) fn parse_on_idle(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
     const ERR_MSG: &str = "on-idle expects three parameters: <timeout> <action> <key-name>";
     if ac_params.len() != 3 {
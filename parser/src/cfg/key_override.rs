@@ -17,6 +17,34 @@ pub struct OverrideStates {
     mods_pressed: u8,
     oscs_to_remove: Vec<OsCode>,
     oscs_to_add: Vec<OsCode>,
+    /// Action overrides that match the current keys.
+    matched_actions: Vec<OverrideAction>,
+    /// Action overrides whose fake key is pressed, or which were released and still suppress
+    /// their input modifiers.
+    active_actions: Vec<ActiveOverrideAction>,
+}
+
+/// The fake key column of an action override and the input modifiers that it suppresses.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct OverrideAction {
+    y: u16,
+    in_mod_oscs: Box<[OsCode]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ActiveOverrideAction {
+    action: OverrideAction,
+    /// False once the override no longer matches. The input modifiers stay suppressed until
+    /// running macros and queued inputs are done, so that they do not leak into the action's
+    /// output.
+    matched: bool,
+}
+
+/// Whether the fake key of an action override should be pressed or released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverrideActionEvent {
+    Press,
+    Release,
 }
 
 impl Default for OverrideStates {
@@ -31,12 +59,15 @@ impl OverrideStates {
             mods_pressed: 0,
             oscs_to_add: Vec::new(),
             oscs_to_remove: Vec::new(),
+            matched_actions: Vec::new(),
+            active_actions: Vec::new(),
         }
     }
 
     fn cleanup(&mut self) {
         self.oscs_to_add.clear();
         self.oscs_to_remove.clear();
+        self.matched_actions.clear();
         self.mods_pressed = 0;
     }
 
@@ -44,13 +75,7 @@ impl OverrideStates {
         if let Some(mod_mask) = mask_for_key(osc) {
             self.mods_pressed |= mod_mask;
         } else {
-            overrides.update_keys(
-                osc,
-                self.mods_pressed,
-                &mut self.oscs_to_add,
-                &mut self.oscs_to_remove,
-                active_layer,
-            );
+            overrides.update_keys(osc, self.mods_pressed, self, active_layer);
         }
     }
 
@@ -65,6 +90,65 @@ impl OverrideStates {
     pub fn removed_oscs(&self) -> impl Iterator<Item = OsCode> + '_ {
         self.oscs_to_remove.iter().copied()
     }
+
+    /// Whether any action override matches or still suppresses its input modifiers.
+    pub fn has_actions(&self) -> bool {
+        !self.matched_actions.is_empty() || !self.active_actions.is_empty()
+    }
+
+    /// Press the fake keys of newly matched action overrides and release those that no longer
+    /// match, through `fake_key_event`. Must be called after `Overrides::override_keys`.
+    ///
+    /// Keys in `action_keys` are outputs of actions and are kept in `kcs` even if the override
+    /// removed them. The input modifiers of released action overrides stay removed from `kcs`
+    /// until `action_pending` is false, i.e. until running macros and queued inputs are done.
+    pub fn update_actions(
+        &mut self,
+        kcs: &mut Vec<KeyCode>,
+        action_keys: &[KeyCode],
+        action_pending: bool,
+        mut fake_key_event: impl FnMut(u16, OverrideActionEvent),
+    ) {
+        if !action_pending {
+            self.active_actions.retain(|active| active.matched);
+        }
+        for active in self.active_actions.iter_mut() {
+            if active.matched && !self.matched_actions.contains(&active.action) {
+                active.matched = false;
+                fake_key_event(active.action.y, OverrideActionEvent::Release);
+            }
+        }
+        for action in self.matched_actions.iter() {
+            match self
+                .active_actions
+                .iter_mut()
+                .find(|active| active.action == *action)
+            {
+                Some(active) if active.matched => {}
+                Some(active) => {
+                    active.matched = true;
+                    fake_key_event(action.y, OverrideActionEvent::Press);
+                }
+                None => {
+                    self.active_actions.push(ActiveOverrideAction {
+                        action: action.clone(),
+                        matched: true,
+                    });
+                    fake_key_event(action.y, OverrideActionEvent::Press);
+                }
+            }
+        }
+        for active in self.active_actions.iter().filter(|active| !active.matched) {
+            kcs.retain(|kc| {
+                !active.action.in_mod_oscs.contains(&(*kc).into()) || action_keys.contains(kc)
+            });
+        }
+        for kc in action_keys.iter() {
+            if self.is_key_overridden((*kc).into()) && !kcs.contains(kc) {
+                kcs.push(*kc);
+            }
+        }
+    }
 }
 
 /// A collection of global key overrides.
@@ -109,8 +193,10 @@ impl Overrides {
     pub fn output_non_mods_for_input_non_mod(&self, in_osc: OsCode) -> Vec<OsCode> {
         let mut ret = Vec::new();
         if let Some(ovds) = self.overrides_by_osc.get(&in_osc) {
-            for out_osc in ovds.iter().map(|ovd| ovd.out_non_mod_osc) {
-                ret.push(out_osc);
+            for ovd in ovds.iter() {
+                if let OverrideOutput::Keys { non_mod_osc, .. } = ovd.output {
+                    ret.push(non_mod_osc);
+                }
             }
         }
         ret
@@ -124,8 +210,7 @@ impl Overrides {
         &self,
         active_osc: OsCode,
         active_mod_mask: u8,
-        oscs_to_add: &mut Vec<OsCode>,
-        oscs_to_remove: &mut Vec<OsCode>,
+        states: &mut OverrideStates,
         active_layer: u16,
     ) {
         let Some(ovds) = self.overrides_by_osc.get(&active_osc) else {
@@ -145,8 +230,19 @@ impl Overrides {
             mask & active_mod_mask == mask && exclude_mask & active_mod_mask == 0
         }) {
             log::debug!("using override {ovd:?}");
-            ovd.add_override_keys(oscs_to_add);
-            ovd.add_removed_keys(oscs_to_remove);
+            match &ovd.output {
+                OverrideOutput::Keys { .. } => ovd.add_override_keys(&mut states.oscs_to_add),
+                OverrideOutput::Action(y) => {
+                    let action = OverrideAction {
+                        y: *y,
+                        in_mod_oscs: ovd.in_mod_oscs.clone(),
+                    };
+                    if !states.matched_actions.contains(&action) {
+                        states.matched_actions.push(action);
+                    }
+                }
+            }
+            ovd.add_removed_keys(&mut states.oscs_to_remove);
         }
     }
 }
@@ -155,15 +251,26 @@ impl Overrides {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Override {
     in_non_mod_osc: OsCode,
-    out_non_mod_osc: OsCode,
     in_mod_oscs: Box<[OsCode]>,
-    out_mod_oscs: Box<[OsCode]>,
+    output: OverrideOutput,
     excluded_mod_oscs: Option<Box<[OsCode]>>,
     excluded_layers: Option<Box<[u16]>>,
 }
 
+/// What an override outputs in place of its input keys.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum OverrideOutput {
+    /// A key chord, which replaces the input keys directly.
+    Keys {
+        non_mod_osc: OsCode,
+        mod_oscs: Box<[OsCode]>,
+    },
+    /// An action in the fake key row, which is pressed while the override matches.
+    Action(u16),
+}
+
 impl Override {
-    pub fn try_new(in_oscs: &[OsCode], out_oscs: &[OsCode]) -> Result<Self> {
+    fn try_new_input(in_oscs: &[OsCode], output: OverrideOutput) -> Result<Self> {
         let mut in_nmoscs = in_oscs
             .iter()
             .copied()
@@ -174,6 +281,21 @@ impl Override {
         if in_nmoscs.next().is_some() {
             bail!("override must contain exactly one input non-modifier key; found multiple");
         }
+        let in_mod_oscs = in_oscs
+            .iter()
+            .copied()
+            .filter(|osc| osc.is_modifier())
+            .collect::<Vec<_>>();
+        Ok(Self {
+            in_non_mod_osc,
+            in_mod_oscs: in_mod_oscs.into_boxed_slice(),
+            output,
+            excluded_mod_oscs: None,
+            excluded_layers: None,
+        })
+    }
+
+    pub fn try_new(in_oscs: &[OsCode], out_oscs: &[OsCode]) -> Result<Self> {
         let mut out_nmoscs = out_oscs
             .iter()
             .copied()
@@ -184,24 +306,23 @@ impl Override {
         if out_nmoscs.next().is_some() {
             bail!("override must contain exactly one output non-modifier key; found multiple");
         }
-        let in_mod_oscs = in_oscs
-            .iter()
-            .copied()
-            .filter(|osc| osc.is_modifier())
-            .collect::<Vec<_>>();
         let out_mod_oscs = out_oscs
             .iter()
             .copied()
             .filter(|osc| osc.is_modifier())
             .collect::<Vec<_>>();
-        Ok(Self {
-            in_non_mod_osc,
-            out_non_mod_osc,
-            in_mod_oscs: in_mod_oscs.into_boxed_slice(),
-            out_mod_oscs: out_mod_oscs.into_boxed_slice(),
-            excluded_mod_oscs: None,
-            excluded_layers: None,
-        })
+        Self::try_new_input(
+            in_oscs,
+            OverrideOutput::Keys {
+                non_mod_osc: out_non_mod_osc,
+                mod_oscs: out_mod_oscs.into_boxed_slice(),
+            },
+        )
+    }
+
+    /// Create an override that presses the action in fake key column `y` while it matches.
+    pub fn try_new_action(in_oscs: &[OsCode], y: u16) -> Result<Self> {
+        Self::try_new_input(in_oscs, OverrideOutput::Action(y))
     }
 
     pub fn try_new_v2(
@@ -210,10 +331,18 @@ impl Override {
         excluded_mod_oscs: Box<[OsCode]>,
         excluded_layers: Box<[u16]>,
     ) -> Result<Self> {
-        let mut override_cfg = Self::try_new(in_oscs, out_oscs)?;
-        override_cfg.excluded_mod_oscs = Some(excluded_mod_oscs);
-        override_cfg.excluded_layers = Some(excluded_layers);
-        Ok(override_cfg)
+        Ok(Self::try_new(in_oscs, out_oscs)?.with_exclusions(excluded_mod_oscs, excluded_layers))
+    }
+
+    /// Add the `defoverridesv2` exclusions to the override.
+    pub fn with_exclusions(
+        mut self,
+        excluded_mod_oscs: Box<[OsCode]>,
+        excluded_layers: Box<[u16]>,
+    ) -> Self {
+        self.excluded_mod_oscs = Some(excluded_mod_oscs);
+        self.excluded_layers = Some(excluded_layers);
+        self
     }
 
     fn get_mod_mask(&self) -> u8 {
@@ -235,13 +364,20 @@ impl Override {
     }
 
     fn add_override_keys(&self, oscs_to_add: &mut Vec<OsCode>) {
-        for osc in self.out_mod_oscs.iter().copied() {
+        let OverrideOutput::Keys {
+            non_mod_osc,
+            mod_oscs,
+        } = &self.output
+        else {
+            return;
+        };
+        for osc in mod_oscs.iter().copied() {
             if !oscs_to_add.contains(&osc) {
                 oscs_to_add.push(osc);
             }
         }
-        if !oscs_to_add.contains(&self.out_non_mod_osc) {
-            oscs_to_add.push(self.out_non_mod_osc);
+        if !oscs_to_add.contains(non_mod_osc) {
+            oscs_to_add.push(*non_mod_osc);
         }
     }
 
@@ -2,7 +2,40 @@ use crate::cfg::*;
 use crate::*;
 
 pub(crate) const DEFLAYER_ICON: [&str; 3] = ["icon", "🖻", "🖼"];
-pub(crate) type LayerIcons = HashMap<String, Option<String>>;
+pub(crate) const DEFLAYER_PROCESS_UNMAPPED_KEYS: &str = "process-unmapped-keys";
+pub(crate) const DEFLAYER_ALLOW_HARDWARE_REPEAT: &str = "allow-hardware-repeat";
+pub(crate) const DEFLAYER_TAP_TIMEOUT: &str = "tap-timeout";
+const DEFLAYER_BOOL_OPTS: [&str; 2] = [
+    DEFLAYER_PROCESS_UNMAPPED_KEYS,
+    DEFLAYER_ALLOW_HARDWARE_REPEAT,
+];
+const DEFLAYER_NUM_OPTS: [&str; 1] = [DEFLAYER_TAP_TIMEOUT];
+pub(crate) type LayerOptsByName = HashMap<String, LayerOpts>;
+
+/// Options given with the layer name in `deflayer` and `deflayermap`.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct LayerOpts {
+    pub icon: Option<String>,
+    pub process_unmapped_keys: Option<bool>,
+    pub allow_hardware_repeat: Option<bool>,
+    pub tap_timeout: Option<u16>,
+}
+
+impl LayerOpts {
+    pub(crate) fn from_parsed(layer_opts: &HashMap<String, String>) -> Self {
+        let get_bool = |key: &str| layer_opts.get(key).map(|v| v == "yes");
+        Self {
+            icon: layer_opts
+                .get(DEFLAYER_ICON[0])
+                .map(|icon_s| icon_s.trim_atom_quotes().to_owned()),
+            process_unmapped_keys: get_bool(DEFLAYER_PROCESS_UNMAPPED_KEYS),
+            allow_hardware_repeat: get_bool(DEFLAYER_ALLOW_HARDWARE_REPEAT),
+            tap_timeout: layer_opts
+                .get(DEFLAYER_TAP_TIMEOUT)
+                .and_then(|v| v.parse().ok()),
+        }
+    }
+}
 
 pub fn parse_layer_opts(list: &[SExpr]) -> Result<HashMap<String, String>> {
     let mut layer_opts: HashMap<String, String> = HashMap::default();
@@ -11,7 +44,6 @@ pub fn parse_layer_opts(list: &[SExpr]) -> Result<HashMap<String, String>> {
         let key_expr = &kv[0];
         let val_expr = &kv[1];
         // Read k-v pairs from the configuration
-        // todo: add hashmap for future options, currently only parse icons
         let opt_key = key_expr.atom(None)
             .ok_or_else(|| anyhow_expr!(key_expr, "No lists are allowed in {DEFLAYER} options"))
             .and_then(|opt_key| {
@@ -26,8 +58,14 @@ pub fn parse_layer_opts(list: &[SExpr]) -> Result<HashMap<String, String>> {
                         );
                     }
                     Ok(DEFLAYER_ICON[0])
+                } else if let Some(opt_key) = DEFLAYER_BOOL_OPTS
+                    .iter()
+                    .chain(DEFLAYER_NUM_OPTS.iter())
+                    .find(|k| **k == opt_key)
+                {
+                    Ok(*opt_key)
                 } else {
-                    bail_expr!(key_expr, "Invalid option in {DEFLAYER}: {opt_key}, expected one of {DEFLAYER_ICON:?}")
+                    bail_expr!(key_expr, "Invalid option in {DEFLAYER}: {opt_key}, expected one of {DEFLAYER_ICON:?}, {DEFLAYER_BOOL_OPTS:?} or {DEFLAYER_NUM_OPTS:?}")
                 }
             })?;
         if layer_opts.contains_key(opt_key) {
@@ -39,7 +77,17 @@ pub fn parse_layer_opts(list: &[SExpr]) -> Result<HashMap<String, String>> {
                 "No lists are allowed in {DEFLAYER}'s option values"
             )
         })?;
-        layer_opts.insert(opt_key.to_owned(), opt_val.to_owned());
+        let opt_val = if DEFLAYER_BOOL_OPTS.contains(&opt_key) {
+            match parse_defcfg_val_bool(val_expr, opt_key)? {
+                true => "yes".to_owned(),
+                false => "no".to_owned(),
+            }
+        } else if DEFLAYER_NUM_OPTS.contains(&opt_key) {
+            parse_cfg_val_u16(val_expr, opt_key, true)?.to_string()
+        } else {
+            opt_val.to_owned()
+        };
+        layer_opts.insert(opt_key.to_owned(), opt_val);
     }
     let rem = opts.remainder();
     if !rem.is_empty() {
@@ -5,6 +5,9 @@
 pub const LAYER_SWITCH: &str = "layer-switch";
 pub const LAYER_TOGGLE: &str = "layer-toggle";
 pub const LAYER_WHILE_HELD: &str = "layer-while-held";
+pub const LAYER_WHILE_HELD_TIMEOUT: &str = "layer-while-held-timeout";
+pub const LAYER_PUSH: &str = "layer-push";
+pub const LAYER_CYCLE: &str = "layer-cycle";
 pub const TAP_HOLD: &str = "tap-hold";
 pub const TAP_HOLD_PRESS: &str = "tap-hold-press";
 pub const TAP_HOLD_PRESS_A: &str = "tap⬓↓";
@@ -21,7 +24,10 @@ pub const TAP_HOLD_EXCEPT_KEYS: &str = "tap-hold-except-keys";
 pub const TAP_HOLD_EXCEPT_KEYS_A: &str = "tap⬓⤫keys";
 pub const TAP_HOLD_TAP_KEYS: &str = "tap-hold-tap-keys";
 pub const TAP_HOLD_TAP_KEYS_A: &str = "tap⬓tapkeys";
+pub const TAP_HOLD_ADAPTIVE: &str = "tap-hold-adaptive";
+pub const CHORD_HOLD: &str = "chord-hold";
 pub const MULTI: &str = "multi";
+pub const MULTI_SEQ: &str = "multi-seq";
 pub const MACRO: &str = "macro";
 pub const MACRO_REPEAT: &str = "macro-repeat";
 pub const MACRO_REPEAT_A: &str = "macro⟳";
@@ -37,6 +43,7 @@ pub const MACRO_REPEAT_CANCEL_ON_NEXT_PRESS_CANCEL_ON_RELEASE: &str =
     "macro-repeat-release-cancel-and-cancel-on-press";
 pub const UNICODE: &str = "unicode";
 pub const SYM: &str = "🔣";
+pub const STR: &str = "str";
 pub const ONE_SHOT: &str = "one-shot";
 pub const ONE_SHOT_PRESS: &str = "one-shot-press";
 pub const ONE_SHOT_PRESS_A: &str = "one-shot↓";
@@ -50,6 +57,8 @@ pub const ONE_SHOT_PAUSE_PROCESSING: &str = "one-shot-pause-processing";
 pub const TAP_DANCE: &str = "tap-dance";
 pub const TAP_DANCE_EAGER: &str = "tap-dance-eager";
 pub const CHORD: &str = "chord";
+pub const PRESS_KEY: &str = "press-key";
+pub const PRESS_KEY_A: &str = "key↓";
 pub const RELEASE_KEY: &str = "release-key";
 pub const RELEASE_KEY_A: &str = "key↑";
 pub const RELEASE_LAYER: &str = "release-layer";
@@ -65,6 +74,7 @@ pub const ON_PRESS_FAKEKEY_DELAY_A: &str = "on↓fakekey-delay";
 pub const ON_RELEASE_FAKEKEY_DELAY: &str = "on-release-fakekey-delay";
 pub const ON_RELEASE_FAKEKEY_DELAY_A: &str = "on↑fakekey-delay";
 pub const ON_IDLE_FAKEKEY: &str = "on-idle-fakekey";
+pub const FAKEKEYS: &str = "fakekeys";
 pub const MWHEEL_UP: &str = "mwheel-up";
 pub const MWHEEL_DOWN: &str = "mwheel-down";
 pub const MWHEEL_LEFT: &str = "mwheel-left";
@@ -77,6 +87,10 @@ pub const MWHEEL_ACCEL_UP: &str = "mwheel-accel-up";
 pub const MWHEEL_ACCEL_DOWN: &str = "mwheel-accel-down";
 pub const MWHEEL_ACCEL_LEFT: &str = "mwheel-accel-left";
 pub const MWHEEL_ACCEL_RIGHT: &str = "mwheel-accel-right";
+pub const MWHEEL_FINE_UP: &str = "mwheel-fine-up";
+pub const MWHEEL_FINE_DOWN: &str = "mwheel-fine-down";
+pub const MWHEEL_FINE_LEFT: &str = "mwheel-fine-left";
+pub const MWHEEL_FINE_RIGHT: &str = "mwheel-fine-right";
 pub const MOVEMOUSE_UP: &str = "movemouse-up";
 pub const MOVEMOUSE_DOWN: &str = "movemouse-down";
 pub const MOVEMOUSE_LEFT: &str = "movemouse-left";
@@ -86,6 +100,8 @@ pub const MOVEMOUSE_ACCEL_DOWN: &str = "movemouse-accel-down";
 pub const MOVEMOUSE_ACCEL_LEFT: &str = "movemouse-accel-left";
 pub const MOVEMOUSE_ACCEL_RIGHT: &str = "movemouse-accel-right";
 pub const MOVEMOUSE_SPEED: &str = "movemouse-speed";
+pub const MOVEMOUSE_SPEED_RESET: &str = "movemouse-speed-reset";
+pub const MOVEMOUSE_POLAR: &str = "movemouse-polar";
 pub const MOVEMOUSE_UP_A: &str = "🖱↑";
 pub const MOVEMOUSE_DOWN_A: &str = "🖱↓";
 pub const MOVEMOUSE_LEFT_A: &str = "🖱←";
@@ -97,6 +113,7 @@ pub const MOVEMOUSE_ACCEL_RIGHT_A: &str = "🖱accel→";
 pub const MOVEMOUSE_SPEED_A: &str = "🖱speed";
 pub const SETMOUSE: &str = "setmouse";
 pub const SETMOUSE_A: &str = "set🖱";
+pub const SETMOUSE_PCT: &str = "setmouse-pct";
 pub const DYNAMIC_MACRO_RECORD: &str = "dynamic-macro-record";
 pub const DYNAMIC_MACRO_PLAY: &str = "dynamic-macro-play";
 pub const ARBITRARY_CODE: &str = "arbitrary-code";
@@ -105,6 +122,7 @@ pub const CMD_LOG: &str = "cmd-log";
 pub const PUSH_MESSAGE: &str = "push-msg";
 pub const CMD_OUTPUT_KEYS: &str = "cmd-output-keys";
 pub const FORK: &str = "fork";
+pub const FORK_MULTI: &str = "fork-multi";
 pub const CAPS_WORD: &str = "caps-word";
 pub const CAPS_WORD_A: &str = "word⇪";
 pub const CAPS_WORD_CUSTOM: &str = "caps-word-custom";
@@ -120,6 +138,9 @@ pub const SEQUENCE_NOERASE: &str = "sequence-noerase";
 pub const UNMOD: &str = "unmod";
 pub const UNSHIFT: &str = "unshift";
 pub const UNSHIFT_A: &str = "un⇧";
+pub const CLEAN_MODS: &str = "clean-mods";
+pub const PRESERVE_MODS: &str = "preserve-mods";
+pub const CHORD_OUTPUT: &str = "chord-output";
 pub const LIVE_RELOAD_NUM: &str = "lrld-num";
 pub const LIVE_RELOAD_FILE: &str = "lrld-file";
 pub const ON_PRESS: &str = "on-press";
@@ -136,12 +157,20 @@ pub const CLIPBOARD_RESTORE: &str = "clipboard-restore";
 pub const CLIPBOARD_SAVE_SET: &str = "clipboard-save-set";
 pub const CLIPBOARD_SAVE_CMD_SET: &str = "clipboard-save-cmd-set";
 pub const CLIPBOARD_SAVE_SWAP: &str = "clipboard-save-swap";
+pub const STICKY_LAYER: &str = "sticky-layer";
+pub const HID: &str = "hid";
+pub const SETVAR: &str = "setvar";
+pub const INCVAR: &str = "incvar";
+pub const DECVAR: &str = "decvar";
 
 pub fn is_list_action(ac: &str) -> bool {
     const LIST_ACTIONS: &[&str] = &[
         LAYER_SWITCH,
         LAYER_TOGGLE,
         LAYER_WHILE_HELD,
+        LAYER_WHILE_HELD_TIMEOUT,
+        LAYER_PUSH,
+        LAYER_CYCLE,
         TAP_HOLD,
         TAP_HOLD_PRESS,
         TAP_HOLD_PRESS_A,
@@ -158,7 +187,10 @@ pub fn is_list_action(ac: &str) -> bool {
         TAP_HOLD_EXCEPT_KEYS_A,
         TAP_HOLD_TAP_KEYS,
         TAP_HOLD_TAP_KEYS_A,
+        TAP_HOLD_ADAPTIVE,
+        CHORD_HOLD,
         MULTI,
+        MULTI_SEQ,
         MACRO,
         MACRO_REPEAT,
         MACRO_REPEAT_A,
@@ -168,6 +200,7 @@ pub fn is_list_action(ac: &str) -> bool {
         MACRO_REPEAT_RELEASE_CANCEL_A,
         UNICODE,
         SYM,
+        STR,
         ONE_SHOT,
         ONE_SHOT_PRESS,
         ONE_SHOT_PRESS_A,
@@ -180,6 +213,8 @@ pub fn is_list_action(ac: &str) -> bool {
         TAP_DANCE,
         TAP_DANCE_EAGER,
         CHORD,
+        PRESS_KEY,
+        PRESS_KEY_A,
         RELEASE_KEY,
         RELEASE_KEY_A,
         RELEASE_LAYER,
@@ -207,6 +242,10 @@ pub fn is_list_action(ac: &str) -> bool {
         MWHEEL_ACCEL_DOWN,
         MWHEEL_ACCEL_LEFT,
         MWHEEL_ACCEL_RIGHT,
+        MWHEEL_FINE_UP,
+        MWHEEL_FINE_DOWN,
+        MWHEEL_FINE_LEFT,
+        MWHEEL_FINE_RIGHT,
         MOVEMOUSE_UP,
         MOVEMOUSE_UP_A,
         MOVEMOUSE_DOWN,
@@ -225,8 +264,11 @@ pub fn is_list_action(ac: &str) -> bool {
         MOVEMOUSE_ACCEL_RIGHT_A,
         MOVEMOUSE_SPEED,
         MOVEMOUSE_SPEED_A,
+        MOVEMOUSE_SPEED_RESET,
+        MOVEMOUSE_POLAR,
         SETMOUSE,
         SETMOUSE_A,
+        SETMOUSE_PCT,
         DYNAMIC_MACRO_RECORD,
         DYNAMIC_MACRO_PLAY,
         ARBITRARY_CODE,
@@ -235,6 +277,7 @@ pub fn is_list_action(ac: &str) -> bool {
         CMD_LOG,
         PUSH_MESSAGE,
         FORK,
+        FORK_MULTI,
         CAPS_WORD,
         CAPS_WORD_A,
         CAPS_WORD_TOGGLE,
@@ -250,12 +293,16 @@ pub fn is_list_action(ac: &str) -> bool {
         UNMOD,
         UNSHIFT,
         UNSHIFT_A,
+        CLEAN_MODS,
+        PRESERVE_MODS,
+        CHORD_OUTPUT,
         LIVE_RELOAD_NUM,
         LIVE_RELOAD_FILE,
         ON_PRESS,
         ON_PRESS_A,
         ON_RELEASE,
         ON_RELEASE_A,
+        FAKEKEYS,
         ON_IDLE,
         ON_PHYSICAL_IDLE,
         HOLD_FOR_DURATION,
@@ -271,6 +318,11 @@ pub fn is_list_action(ac: &str) -> bool {
         CLIPBOARD_SAVE_SET,
         CLIPBOARD_SAVE_CMD_SET,
         CLIPBOARD_SAVE_SWAP,
+        STICKY_LAYER,
+        HID,
+        SETVAR,
+        INCVAR,
+        DECVAR,
     ];
     LIST_ACTIONS.contains(&ac)
 }
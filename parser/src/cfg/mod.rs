@@ -225,7 +225,46 @@ type TapHoldCustomFunc =
     ) -> &'static (dyn Fn(QueuedIter) -> (Option<WaitingAction>, bool) + Send + Sync);
 
 pub type BorrowedKLayout<'a> = Layout<'a, KEYS_IN_ROW, 2, &'a &'a [&'a CustomAction]>;
-pub type KeySeqsToFKeys = Trie<(u8, u16)>;
+/// Maps sequences to the coordinate of the virtual key to tap. The slice holds the keys matched
+/// by `_` and `(any ...)` steps, which `seq-match` outputs. The last item is the sequence's own
+/// timeout, if `defseq` gave it one.
+#[derive(Debug, Clone, Default)]
+pub struct KeySeqsToFKeys {
+    trie: Trie<(u8, u16, &'static [u16], Option<u16>)>,
+    /// Timeouts of the sequences starting with each prefix of a sequence. Only filled in if some
+    /// sequence has its own timeout, so that a lookup does not need to walk the trie.
+    prefix_timeouts: Trie<PrefixTimeouts>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PrefixTimeouts {
+    /// The longest timeout that `defseq` gave a sequence.
+    max: u16,
+    /// Whether a sequence without its own timeout starts with the prefix.
+    has_default: bool,
+}
+
+impl std::ops::Deref for KeySeqsToFKeys {
+    type Target = Trie<(u8, u16, &'static [u16], Option<u16>)>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.trie
+    }
+}
+
+impl KeySeqsToFKeys {
+    /// Returns the longest timeout among the sequences starting with `prefix`, using
+    /// `default_timeout` for sequences without their own. `None` if no sequence has its own
+    /// timeout or none starts with `prefix`.
+    pub fn max_timeout(&self, prefix: &[u16], default_timeout: u16) -> Option<u16> {
+        use crate::trie::GetOrDescendentExistsResult::HasValue;
+        match self.prefix_timeouts.get_or_descendant_exists(prefix) {
+            HasValue(timeouts) if timeouts.has_default => Some(timeouts.max.max(default_timeout)),
+            HasValue(timeouts) => Some(timeouts.max),
+            _ => None,
+        }
+    }
+}
 
 pub struct KanataLayout {
     layout: KLayout,
@@ -258,6 +297,9 @@ pub struct Cfg {
     /// that are received from the OS input mechanism will be forwarded to OS output mechanism
     /// without going through kanata's processing.
     pub mapped_keys: MappedKeys,
+    /// Mapped keys that are only processed because of `process-unmapped-keys`, if a layer
+    /// overrides it. They are passed through while the base layer does not process unmapped keys.
+    pub unmapped_keys: MappedKeys,
     /// The potential outputs for a physical key position. The intention behind this is for sending
     /// key repeats.
     pub key_outputs: KeyOutputs,
@@ -277,6 +319,76 @@ pub struct Cfg {
     pub switch_max_key_timing: u16,
     /// Zipchord-like configuration.
     pub zippy: Option<(ZchPossibleChords, ZchConfig)>,
+    /// Actions configured by `on-idle` and `on-resume` in `defcfg`.
+    pub idle_hooks: IdleHooks,
+    /// Fake key column of the `on-exit` action in `defcfg`.
+    pub on_exit: Option<u16>,
+    /// Fake key columns of the named macros from `defmacro`.
+    pub named_macros: HashMap<String, u16>,
+    /// Time ranges from `defschedule` in which a layer is used as the base layer.
+    pub schedule: Vec<ScheduleEntry>,
+    /// Names of the runtime variables used by the configuration, indexed by variable number.
+    pub runtime_vars: Vec<String>,
+    /// Layers of the `layer-cycle` actions, indexed by cycle number.
+    pub layer_cycles: Vec<Vec<usize>>,
+    /// Number of recent press intervals averaged for `tap-hold-adaptive`. Zero if the
+    /// configuration has no `tap-hold-adaptive` actions.
+    pub adaptive_tap_hold_window: u16,
+    /// Names of the aliases defined in `defalias`, sorted.
+    pub aliases: Vec<String>,
+    /// Names of the defined aliases that are never referenced, sorted.
+    pub unused_aliases: Vec<String>,
+}
+
+/// The `on-idle`, `on-resume` and `defidle` actions are placed in the fake key row after the
+/// virtual keys. These hold the idle times and fake key columns, for tapping the actions.
+#[derive(Debug, Default, Clone)]
+pub struct IdleHooks {
+    /// Idle time in milliseconds and fake key column.
+    pub on_idle: Option<(u32, u16)>,
+    /// Fake key column.
+    pub on_resume: Option<u16>,
+    /// Independent idle watchers from `defidle`.
+    pub watchers: Vec<IdleWatcher>,
+}
+
+/// An idle action from `defidle`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdleWatcher {
+    /// Idle time in milliseconds.
+    pub idle_ms: u32,
+    /// If set, the watcher is only armed while this is the active layer.
+    pub layer: Option<u16>,
+    /// Fake key column.
+    pub y: u16,
+}
+
+/// A time range from `defschedule`. While the local time is in the range, `layer` is used as the
+/// base layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduleEntry {
+    /// Start of the range in minutes since midnight, inclusive.
+    pub start_min: u16,
+    /// End of the range in minutes since midnight, exclusive. If it is not after the start, the
+    /// range continues past midnight into the next day.
+    pub end_min: u16,
+    /// Weekdays on which the range starts, with bit 0 being Monday.
+    pub weekdays: u8,
+    pub layer: u16,
+}
+
+impl ScheduleEntry {
+    /// Whether the range contains the given time. `weekday` is 0 for Monday.
+    pub fn contains(&self, weekday: u8, minute: u16) -> bool {
+        let starts_on = |day: u8| self.weekdays & (1 << day) != 0;
+        if self.start_min < self.end_min {
+            starts_on(weekday) && (self.start_min..self.end_min).contains(&minute)
+        } else {
+            let previous_day = (weekday + 6) % 7;
+            (starts_on(weekday) && minute >= self.start_min)
+                || (starts_on(previous_day) && minute < self.end_min)
+        }
+    }
 }
 
 /// Parse a new configuration from a file.
@@ -312,6 +424,12 @@ pub struct LayerInfo {
     pub name: String,
     pub cfg_text: String,
     pub icon: Option<String>,
+    /// Overrides `process-unmapped-keys` while this is the base layer.
+    pub process_unmapped_keys: Option<bool>,
+    /// Overrides `allow-hardware-repeat` while this is the base layer.
+    pub allow_hardware_repeat: Option<bool>,
+    /// Hold timeout of the tap-hold actions on this layer that have `_` as the timeout.
+    pub tap_timeout: Option<u16>,
 }
 
 #[allow(clippy::type_complexity)] // return type is not pub
@@ -326,6 +444,12 @@ fn populate_cfg_with_icfg(icfg: IntermediateCfg, s: ParserState) -> Cfg {
     let (layers, allocations) = icfg.klayers.get();
     let key_outputs = create_key_outputs(&layers, &icfg.overrides, &icfg.chords_v2);
     let switch_max_key_timing = s.switch_max_key_timing.get();
+    let runtime_vars = s.runtime_vars.take();
+    let adaptive_tap_hold_window = if s.uses_adaptive_tap_hold.get() {
+        icfg.options.tap_hold_adaptive_window
+    } else {
+        0
+    };
     let mut layout = KanataLayout::new(
         Layout::new_with_trans_action_settings(
             s.a.sref(s.defsrc_layer),
@@ -336,7 +460,9 @@ fn populate_cfg_with_icfg(icfg: IntermediateCfg, s: ParserState) -> Cfg {
         allocations,
     );
     layout.bm().chords_v2 = icfg.chords_v2;
+    layout.bm().vars = vec![None; runtime_vars.len()];
     layout.bm().quick_tap_hold_timeout = icfg.options.concurrent_tap_hold;
+    layout.bm().layer_hold_timeouts = icfg.layer_info.iter().map(|l| l.tap_timeout).collect();
     layout.bm().oneshot.pause_input_processing_delay = icfg.options.rapid_event_delay;
     if let Some(s) = icfg.start_action {
         layout
@@ -350,9 +476,18 @@ fn populate_cfg_with_icfg(icfg: IntermediateCfg, s: ParserState) -> Cfg {
         .map(|(k, v)| (k.clone(), v.0))
         .collect();
     fake_keys.shrink_to_fit();
+    let mut aliases: Vec<String> = s.aliases.keys().cloned().collect();
+    aliases.sort();
+    let used_aliases = s.used_aliases.take();
+    let unused_aliases = aliases
+        .iter()
+        .filter(|a| !used_aliases.contains(*a))
+        .cloned()
+        .collect();
     Cfg {
         options: icfg.options,
         mapped_keys: icfg.mapped_keys,
+        unmapped_keys: icfg.unmapped_keys,
         layer_info: icfg.layer_info,
         key_outputs,
         layout,
@@ -361,6 +496,15 @@ fn populate_cfg_with_icfg(icfg: IntermediateCfg, s: ParserState) -> Cfg {
         fake_keys,
         switch_max_key_timing,
         zippy: icfg.zippy,
+        idle_hooks: icfg.idle_hooks,
+        on_exit: icfg.on_exit,
+        named_macros: icfg.named_macros,
+        schedule: icfg.schedule,
+        runtime_vars,
+        layer_cycles: s.layer_cycles.take(),
+        adaptive_tap_hold_window,
+        aliases,
+        unused_aliases,
     }
 }
 
@@ -395,6 +539,7 @@ fn deflocalkeys_variant_applies_to_current_os(variant: &str) -> bool {
 pub struct IntermediateCfg {
     pub options: CfgOptions,
     pub mapped_keys: MappedKeys,
+    pub unmapped_keys: MappedKeys,
     pub layer_info: Vec<LayerInfo>,
     pub klayers: KanataLayers,
     pub sequences: KeySeqsToFKeys,
@@ -402,6 +547,10 @@ pub struct IntermediateCfg {
     pub chords_v2: Option<ChordsV2<'static, KanataCustom>>,
     pub start_action: Option<&'static KanataAction>,
     pub zippy: Option<(ZchPossibleChords, ZchConfig)>,
+    pub idle_hooks: IdleHooks,
+    pub on_exit: Option<u16>,
+    pub named_macros: HashMap<String, u16>,
+    pub schedule: Vec<ScheduleEntry>,
 }
 
 // A snapshot of enviroment variables, or an error message with an explanation
@@ -558,7 +707,7 @@ pub fn parse_cfg_raw_string(
 
     error_on_unknown_top_level_atoms(&spanned_root_exprs)?;
 
-    let mut local_keys: Option<HashMap<String, OsCode>> = None;
+    let mut local_keys: Option<(HashMap<String, OsCode>, UsageOverrides)> = None;
     clear_custom_str_oscode_mapping();
     for def_local_keys_variant in DEFLOCALKEYS_VARIANTS {
         let Some((result, _span)) = spanned_root_exprs
@@ -602,7 +751,20 @@ pub fn parse_cfg_raw_string(
             )
         }
     }
-    replace_custom_str_oscode_mapping(&local_keys.unwrap_or_default());
+    let (local_keys, local_usage_overrides) = local_keys.unwrap_or_default();
+    replace_custom_str_oscode_mapping(&local_keys);
+
+    // Resolved after the key names above, so that usages can be read as custom keys too.
+    let mut usage_overrides: HashMap<(u32, u32), OsCode> = HashMap::default();
+    for (usage, key_expr) in local_usage_overrides {
+        let osc = key_expr
+            .atom(None)
+            .and_then(str_to_oscode)
+            .ok_or_else(|| anyhow_expr!(&key_expr, "Unknown key name for HID usage override"))?;
+        usage_overrides.insert(usage, osc);
+    }
+    #[cfg(any(target_os = "macos", target_os = "unknown"))]
+    replace_page_code_overrides(&usage_overrides);
 
     #[allow(unused_mut)]
     let mut cfg = root_exprs
@@ -679,7 +841,7 @@ pub fn parse_cfg_raw_string(
         bail!("No deflayer expressions exist. At least one layer must be defined.")
     }
 
-    let (layer_idxs, layer_icons) =
+    let (layer_idxs, layer_opts) =
         parse_layer_indexes(&layer_exprs, mapping_order.len(), &vars, &mut lsp_hints)?;
     let mut sorted_idxs: Vec<(&String, &usize)> =
         layer_idxs.iter().map(|tuple| (tuple.0, tuple.1)).collect();
@@ -703,10 +865,16 @@ pub fn parse_cfg_raw_string(
     let layer_info: Vec<LayerInfo> = layer_names
         .into_iter()
         .zip(layer_strings)
-        .map(|(name, cfg_text)| LayerInfo {
-            name: name.clone(),
-            cfg_text,
-            icon: layer_icons.get(&name).unwrap_or(&None).clone(),
+        .map(|(name, cfg_text)| {
+            let opts = layer_opts.get(&name).cloned().unwrap_or_default();
+            LayerInfo {
+                name,
+                cfg_text,
+                icon: opts.icon,
+                process_unmapped_keys: opts.process_unmapped_keys,
+                allow_hardware_repeat: opts.allow_hardware_repeat,
+                tap_timeout: opts.tap_timeout,
+            }
         })
         .collect();
 
@@ -754,8 +922,11 @@ pub fn parse_cfg_raw_string(
                 false
             }
         },
+        allow_dangerous_keys: cfg.allow_dangerous_keys.clone(),
         delegate_to_first_layer: cfg.delegate_to_first_layer,
         default_sequence_timeout: cfg.sequence_timeout,
+        default_tap_hold_timeout: cfg.tap_hold_timeout,
+        default_tap_hold_config: tap_hold_mode_config(cfg.tap_hold_mode),
         default_sequence_input_mode: cfg.sequence_input_mode,
         block_unmapped_keys: cfg.block_unmapped_keys,
         lsp_hints: RefCell::new(lsp_hints),
@@ -793,19 +964,31 @@ pub fn parse_cfg_raw_string(
         .collect::<Vec<_>>();
     parse_aliases(&alias_exprs, s, &env_vars)?;
 
-    let start_action = cfg
-        .start_alias
+    let idle_exprs = root_exprs
+        .iter()
+        .filter(gen_first_atom_filter("defidle"))
+        .collect::<Vec<_>>();
+    let idle_hooks = parse_idle_hooks(&cfg, &idle_exprs, s)?;
+    let on_exit = cfg
+        .on_exit
         .as_ref()
-        .and_then(|start| s.aliases.get(start).copied());
-    if let (Some(_), None) = (cfg.start_alias.as_ref(), start_action) {
-        bail!("alias-to-trigger-on-load was given, but alias could not be found")
-    }
+        .map(|expr| {
+            let action = parse_action(expr, s)?;
+            add_hidden_fake_key(expr, action, s)
+        })
+        .transpose()?;
 
-    let mut klayers = parse_layers(s, &mut mapped_keys, &cfg)?;
+    let macro_exprs = root_exprs
+        .iter()
+        .filter(gen_first_atom_filter("defmacro"))
+        .collect::<Vec<_>>();
+    let named_macros = parse_named_macros(&macro_exprs, s)?;
 
-    resolve_chord_groups(&mut klayers, s)?;
-    let layers = s.a.bref_slice(klayers);
-    s.layers = layers;
+    let schedule_exprs = root_exprs
+        .iter()
+        .filter(gen_first_atom_filter("defschedule"))
+        .collect::<Vec<_>>();
+    let schedule = parse_schedule(&schedule_exprs, s)?;
 
     let override_exprs = root_exprs
         .iter()
@@ -859,6 +1042,21 @@ pub fn parse_cfg_raw_string(
         }
     };
 
+    let start_action = cfg.start_alias.as_ref().and_then(|start| {
+        s.used_aliases.borrow_mut().insert(start.clone());
+        s.aliases.get(start).copied()
+    });
+    if let (Some(_), None) = (cfg.start_alias.as_ref(), start_action) {
+        bail!("alias-to-trigger-on-load was given, but alias could not be found")
+    }
+
+    let mut klayers = parse_layers(s, &mut mapped_keys, &cfg)?;
+    warn_keys_transparent_in_every_layer(src_expr, &klayers, s);
+
+    resolve_chord_groups(&mut klayers, s)?;
+    let layers = s.a.bref_slice(klayers);
+    s.layers = layers;
+
     let defchordsv2_filter = |exprs: &&Vec<SExpr>| -> bool {
         if exprs.is_empty() {
             return false;
@@ -950,10 +1148,42 @@ pub fn parse_cfg_raw_string(
             .extend(refs.0.drain());
     });
 
+    log::info!("process unmapped keys: {}", cfg.process_unmapped_keys);
+    let layers_override_unmapped_keys = layer_info
+        .iter()
+        .any(|layer| layer.process_unmapped_keys.is_some());
+    let layers_process_unmapped_keys = layer_info
+        .iter()
+        .any(|layer| layer.process_unmapped_keys == Some(true));
+    // Keys that are processed depending on the base layer. These are sent for processing, which
+    // passes them through for layers that do not process unmapped keys.
+    let mut unmapped_keys = MappedKeys::default();
+    if cfg.process_unmapped_keys || layers_process_unmapped_keys {
+        let keys = self::unmapped_keys(&cfg, &mapped_keys);
+        mapped_keys.extend(keys.iter().copied());
+        if layers_override_unmapped_keys {
+            unmapped_keys = keys;
+        }
+    }
+    // The macro cancel key must reach the processing loop even if nothing maps it.
+    if let Some(osc) = cfg.macro_cancel_key {
+        mapped_keys.insert(osc);
+    }
+    mapped_keys.shrink_to_fit();
+
+    // Passthrough keys are left out of the mapped keys so that the event loop writes them out
+    // without sending them for processing.
+    for osc in cfg.passthrough_keys.iter() {
+        if mapped_keys.remove(osc) {
+            log::info!("passthrough key {osc:?} will not be processed");
+        }
+    }
+
     let klayers = unsafe { KanataLayers::new(layers, s.a.clone()) };
     Ok(IntermediateCfg {
         options: cfg,
         mapped_keys,
+        unmapped_keys,
         layer_info,
         klayers,
         sequences,
@@ -961,6 +1191,10 @@ pub fn parse_cfg_raw_string(
         chords_v2,
         start_action,
         zippy,
+        idle_hooks,
+        on_exit,
+        named_macros,
+        schedule,
     })
 }
 
@@ -998,7 +1232,10 @@ fn error_on_unknown_top_level_atoms(exprs: &[Spanned<Vec<SExpr>>]) -> Result<()>
                 | "defchordsv2-experimental"
                 | "defzippy"
                 | "defzippy-experimental"
-                | "defseq" => Ok(()),
+                | "defseq"
+                | "defidle"
+                | "defmacro"
+                | "defschedule" => Ok(()),
                 _ => err_span!(expr, "Found unknown configuration item"),
             })
             .ok_or_else(|| {
@@ -1079,18 +1316,45 @@ fn check_first_expr<'a>(
     Ok(exprs)
 }
 
+/// HID usage `(page, code)` overrides from `deflocalkeys-macos`, each with the expression naming
+/// the key that the usage should be read as.
+type UsageOverrides = Vec<((u32, u32), SExpr)>;
+
 /// Parse custom keys from an expression starting with deflocalkeys.
 fn parse_deflocalkeys(
     def_local_keys_variant: &str,
     expr: &[SExpr],
-) -> Result<HashMap<String, OsCode>> {
+) -> Result<(HashMap<String, OsCode>, UsageOverrides)> {
     let mut localkeys = HashMap::default();
+    let mut usage_overrides: UsageOverrides = vec![];
     let mut exprs = check_first_expr(expr.iter(), def_local_keys_variant)?;
     // Read k-v pairs from the configuration
     while let Some(key_expr) = exprs.next() {
         let key = key_expr.atom(None).ok_or_else(|| {
             anyhow_expr!(key_expr, "No lists are allowed in {def_local_keys_variant}")
         })?;
+        let Some(val) = exprs.next() else {
+            bail_expr!(key_expr, "Key without a number in {def_local_keys_variant}")
+        };
+
+        // A HID usage list does not define a new key name; it changes which existing key
+        // name a physical key is read as, so the same name may appear more than once.
+        if let SExpr::List(usage) = val {
+            if def_local_keys_variant != "deflocalkeys-macos" {
+                bail_expr!(
+                    val,
+                    "No lists are allowed in {def_local_keys_variant}.\n\
+                     HID usage lists are only supported in deflocalkeys-macos."
+                );
+            }
+            let usage = parse_hid_usage(&usage.t, val)?;
+            if usage_overrides.iter().any(|(u, _)| *u == usage) {
+                bail_expr!(val, "Duplicate HID usage found in {def_local_keys_variant}");
+            }
+            usage_overrides.push((usage, key_expr.clone()));
+            continue;
+        }
+
         if localkeys.contains_key(key) {
             bail_expr!(
                 key_expr,
@@ -1111,26 +1375,44 @@ fn parse_deflocalkeys(
             continue;
         }
 
-        let osc = match exprs.next() {
-            Some(v) => v
-                .atom(None)
-                .ok_or_else(|| anyhow_expr!(v, "No lists are allowed in {def_local_keys_variant}"))
-                .and_then(|osc| {
-                    osc.parse::<u16>().map_err(|_| {
-                        anyhow_expr!(v, "Unknown number in {def_local_keys_variant}: {osc}")
-                    })
+        let osc = val
+            .atom(None)
+            .ok_or_else(|| anyhow_expr!(val, "No lists are allowed in {def_local_keys_variant}"))
+            .and_then(|osc| {
+                osc.parse::<u16>().map_err(|_| {
+                    anyhow_expr!(val, "Unknown number in {def_local_keys_variant}: {osc}")
                 })
-                .and_then(|osc| {
-                    OsCode::from_u16(osc).ok_or_else(|| {
-                        anyhow_expr!(v, "Unknown number in {def_local_keys_variant}: {osc}")
-                    })
-                })?,
-            None => bail_expr!(key_expr, "Key without a number in {def_local_keys_variant}"),
-        };
+            })
+            .and_then(|osc| {
+                OsCode::from_u16(osc).ok_or_else(|| {
+                    anyhow_expr!(val, "Unknown number in {def_local_keys_variant}: {osc}")
+                })
+            })?;
         log::debug!("custom mapping: {key} {}", osc.as_u16());
         localkeys.insert(key.to_owned(), osc);
     }
-    Ok(localkeys)
+    Ok((localkeys, usage_overrides))
+}
+
+/// Parses `($usage-page $usage-code)`, where each number is decimal or `0x`-prefixed hex.
+fn parse_hid_usage(usage: &[SExpr], usage_expr: &SExpr) -> Result<(u32, u32)> {
+    const ERR_MSG: &str = "HID usage must be a list of two numbers: ($usage-page $usage-code)";
+    let [page, code] = usage else {
+        bail_expr!(usage_expr, "{ERR_MSG}");
+    };
+    let parse_num = |expr: &SExpr| -> Result<u32> {
+        expr.atom(None)
+            .and_then(parse_dec_or_hex)
+            .ok_or_else(|| anyhow_expr!(expr, "{ERR_MSG}"))
+    };
+    Ok((parse_num(page)?, parse_num(code)?))
+}
+
+fn parse_dec_or_hex(n: &str) -> Option<u32> {
+    match n.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => n.parse::<u32>().ok(),
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -1155,8 +1437,14 @@ fn parse_defsrc(
             SExpr::Atom(a) => &a.t,
             _ => bail_expr!(expr, "No lists allowed in defsrc"),
         };
-        let oscode = str_to_oscode(s)
-            .ok_or_else(|| anyhow_expr!(expr, "Unknown key in defsrc: \"{}\"", s))?;
+        let oscode = match s.strip_prefix("0x") {
+            Some(hex) if defcfg.event_mode == EventMode::Raw => u16::from_str_radix(hex, 16)
+                .ok()
+                .and_then(crate::keys::scancode::u16_to_osc)
+                .ok_or_else(|| anyhow_expr!(expr, "Unknown scan code in defsrc: \"{}\"", s))?,
+            _ => str_to_oscode(s)
+                .ok_or_else(|| anyhow_expr!(expr, "Unknown key in defsrc: \"{}\"", s))?,
+        };
         is_mouse_used = match (is_mouse_used, oscode) {
             (
                 MouseInDefsrc::NoMouse,
@@ -1180,54 +1468,45 @@ fn parse_defsrc(
         ordered_codes.push(oscode.into());
     }
 
-    let mapped_exceptions = match &defcfg.process_unmapped_keys_exceptions {
-        Some(excluded_keys) => {
-            for excluded_key in excluded_keys.iter() {
-                log::debug!("process unmapped keys exception: {:?}", excluded_key);
-                if mkeys.contains(&excluded_key.0) {
-                    bail_expr!(
-                        &excluded_key.1,
-                        "Keys cannot be included in defsrc and also excepted in process-unmapped-keys."
-                    );
-                }
-            }
-
-            excluded_keys
-                .iter()
-                .map(|excluded_key| excluded_key.0)
-                .collect()
-        }
-        None => vec![],
-    };
-
-    log::info!("process unmapped keys: {}", defcfg.process_unmapped_keys);
-    if defcfg.process_unmapped_keys {
-        for osc in 0..KEYS_IN_ROW as u16 {
-            if let Some(osc) = OsCode::from_u16(osc) {
-                if osc.is_mouse_code() {
-                    // Bugfix #1879:
-                    // Auto-including mouse activity in mapped keys
-                    // seems strictly incorrect to do, so never do it.
-                    // Users can still choose to opt in if they want.
-                    // Auto-including mouse activity breaks many scenarios.
-                    continue;
-                }
-                match KeyCode::from(osc) {
-                    KeyCode::No => {}
-                    _ => {
-                        if !mapped_exceptions.contains(&osc) {
-                            mkeys.insert(osc);
-                        }
-                    }
-                }
+    if let Some(excluded_keys) = &defcfg.process_unmapped_keys_exceptions {
+        for excluded_key in excluded_keys.iter() {
+            log::debug!("process unmapped keys exception: {:?}", excluded_key);
+            if mkeys.contains(&excluded_key.0) {
+                bail_expr!(
+                    &excluded_key.1,
+                    "Keys cannot be included in defsrc and also excepted in process-unmapped-keys."
+                );
             }
         }
     }
 
-    mkeys.shrink_to_fit();
     Ok((mkeys, ordered_codes, is_mouse_used))
 }
 
+/// Returns the keys that `process-unmapped-keys` adds to the mapped keys.
+fn unmapped_keys(defcfg: &CfgOptions, mapped_keys: &MappedKeys) -> MappedKeys {
+    let mapped_exceptions = defcfg
+        .process_unmapped_keys_exceptions
+        .iter()
+        .flatten()
+        .map(|excluded_key| excluded_key.0)
+        .collect::<Vec<_>>();
+    (0..KEYS_IN_ROW as u16)
+        .filter_map(OsCode::from_u16)
+        .filter(|osc| {
+            // Bugfix #1879:
+            // Auto-including mouse activity in mapped keys
+            // seems strictly incorrect to do, so never do it.
+            // Users can still choose to opt in if they want.
+            // Auto-including mouse activity breaks many scenarios.
+            !osc.is_mouse_code()
+                && KeyCode::from(*osc) != KeyCode::No
+                && !mapped_exceptions.contains(osc)
+                && !mapped_keys.contains(osc)
+        })
+        .collect()
+}
+
 type LayerIndexes = HashMap<String, usize>;
 type Aliases = HashMap<String, &'static KanataAction>;
 
@@ -1240,9 +1519,9 @@ fn parse_layer_indexes(
     expected_len: usize,
     vars: &HashMap<String, SExpr>,
     _lsp_hints: &mut LspHints,
-) -> Result<(LayerIndexes, LayerIcons)> {
+) -> Result<(LayerIndexes, LayerOptsByName)> {
     let mut layer_indexes = HashMap::default();
-    let mut layer_icons = HashMap::default();
+    let mut layer_opts_by_name = HashMap::default();
     for (i, expr_type) in exprs.iter().enumerate() {
         let (mut subexprs, expr, do_element_count_check, deflayer_keyword) = match expr_type {
             SpannedLayerExprs::DefsrcMapping(e) => {
@@ -1261,10 +1540,10 @@ fn parse_layer_indexes(
                 "{deflayer_keyword} requires a layer name after `{deflayer_keyword}` token"
             )
         })?;
-        let (layer_name, _layer_name_span, icon) = {
+        let (layer_name, _layer_name_span, layer_opts) = {
             let name = layer_expr.atom(Some(vars));
             match name {
-                Some(name) => (name.to_owned(), layer_expr.span(), None),
+                Some(name) => (name.to_owned(), layer_expr.span(), LayerOpts::default()),
                 None => {
                     // unwrap: this **must** be a list due to atom() call above.
                     let list = layer_expr.list(Some(vars)).unwrap();
@@ -1276,11 +1555,8 @@ fn parse_layer_indexes(
                             layer_expr,
                             "layer name after {deflayer_keyword} must be a string when enclosed within one pair of parentheses"
                         ))?;
-                    let layer_opts = parse_layer_opts(&list[1..])?;
-                    let icon = layer_opts
-                        .get(DEFLAYER_ICON[0])
-                        .map(|icon_s| icon_s.trim_atom_quotes().to_owned());
-                    (name.to_owned(), first.span(), icon)
+                    let layer_opts = LayerOpts::from_parsed(&parse_layer_opts(&list[1..])?);
+                    (name.to_owned(), first.span(), layer_opts)
                 }
             }
         };
@@ -1314,14 +1590,26 @@ fn parse_layer_indexes(
             }
         }
         if do_element_count_check {
-            let num_actions = expr.t.len() - 2;
-            if num_actions != expected_len {
+            let items = &expr.t[2..];
+            if items.len() > expected_len {
+                bail_expr!(
+                    &items[expected_len],
+                    "Layer {} has {} item(s), but requires {} to match defsrc.\n\
+                     This is the first item without a defsrc key.",
+                    layer_name,
+                    items.len(),
+                    expected_len
+                )
+            }
+            if items.len() < expected_len {
                 bail_span!(
                     expr,
-                    "Layer {} has {} item(s), but requires {} to match defsrc",
+                    "Layer {} has {} item(s), but requires {} to match defsrc.\n\
+                     The last {} defsrc key(s) have no item in this layer.",
                     layer_name,
-                    num_actions,
-                    expected_len
+                    items.len(),
+                    expected_len,
+                    expected_len - items.len()
                 )
             }
         }
@@ -1333,10 +1621,10 @@ fn parse_layer_indexes(
             .insert(layer_name.clone(), _layer_name_span.clone());
 
         layer_indexes.insert(layer_name.clone(), i);
-        layer_icons.insert(layer_name, icon);
+        layer_opts_by_name.insert(layer_name, layer_opts);
     }
 
-    Ok((layer_indexes, layer_icons))
+    Ok((layer_indexes, layer_opts_by_name))
 }
 
 #[derive(Debug, Clone)]
@@ -1365,16 +1653,31 @@ pub struct ParserState {
     layer_idxs: LayerIndexes,
     mapping_order: Vec<usize>,
     virtual_keys: HashMap<String, (usize, &'static KanataAction)>,
+    /// Fake keys that are not virtual keys, for `on-idle`, `on-resume`, `on-exit`, `defidle`,
+    /// `defmacro` and the actions of `on-release`.
+    hidden_fake_keys: RefCell<Vec<(usize, &'static KanataAction)>>,
     chord_groups: HashMap<String, ChordGroup>,
     defsrc_layer: [KanataAction; KEYS_IN_ROW],
     vars: HashMap<String, SExpr>,
     is_cmd_enabled: bool,
+    allow_dangerous_keys: Vec<OsCode>,
     delegate_to_first_layer: bool,
     default_sequence_timeout: u16,
+    default_tap_hold_timeout: u16,
+    /// Resolution strategy of plain `tap-hold` actions, from `tap-hold-mode` of `defcfg`.
+    default_tap_hold_config: HoldTapConfig<'static>,
     default_sequence_input_mode: SequenceInputMode,
     block_unmapped_keys: bool,
     switch_max_key_timing: Cell<u16>,
     multi_action_nest_count: Cell<u16>,
+    /// Whether any `tap-hold-adaptive` action is used.
+    uses_adaptive_tap_hold: Cell<bool>,
+    /// Names of runtime variables, indexed by variable number.
+    runtime_vars: RefCell<Vec<String>>,
+    /// Layers of the `layer-cycle` actions, indexed by cycle number.
+    layer_cycles: RefCell<Vec<Vec<usize>>>,
+    /// Names of the aliases referenced so far.
+    used_aliases: RefCell<HashSet<String>>,
     pctx: ParserContext,
     pub lsp_hints: RefCell<LspHints>,
     a: Arc<Allocations>,
@@ -1384,6 +1687,31 @@ impl ParserState {
     fn vars(&self) -> Option<&HashMap<String, SExpr>> {
         Some(&self.vars)
     }
+
+    /// Returns the number of the runtime variable with this name, adding it if it is new.
+    fn runtime_var_idx(&self, name: &str) -> u16 {
+        let mut runtime_vars = self.runtime_vars.borrow_mut();
+        match runtime_vars.iter().position(|v| v == name) {
+            Some(idx) => idx as u16,
+            None => {
+                runtime_vars.push(name.to_owned());
+                (runtime_vars.len() - 1) as u16
+            }
+        }
+    }
+
+    /// Returns the number of the layer cycle with these layers, adding it if it is new. Cycles
+    /// with the same layers in the same order share their state.
+    fn layer_cycle_idx(&self, layers: Vec<usize>) -> u16 {
+        let mut layer_cycles = self.layer_cycles.borrow_mut();
+        match layer_cycles.iter().position(|c| *c == layers) {
+            Some(idx) => idx as u16,
+            None => {
+                layer_cycles.push(layers);
+                (layer_cycles.len() - 1) as u16
+            }
+        }
+    }
 }
 
 impl Default for ParserState {
@@ -1397,15 +1725,23 @@ impl Default for ParserState {
             mapping_order: Default::default(),
             defsrc_layer: [KanataAction::NoOp; KEYS_IN_ROW],
             virtual_keys: Default::default(),
+            hidden_fake_keys: Default::default(),
             chord_groups: Default::default(),
             vars: Default::default(),
             is_cmd_enabled: default_cfg.enable_cmd,
+            allow_dangerous_keys: default_cfg.allow_dangerous_keys,
             delegate_to_first_layer: default_cfg.delegate_to_first_layer,
             default_sequence_timeout: default_cfg.sequence_timeout,
+            default_tap_hold_timeout: default_cfg.tap_hold_timeout,
+            default_tap_hold_config: tap_hold_mode_config(default_cfg.tap_hold_mode),
             default_sequence_input_mode: default_cfg.sequence_input_mode,
             block_unmapped_keys: default_cfg.block_unmapped_keys,
             switch_max_key_timing: Cell::new(0),
             multi_action_nest_count: Cell::new(0),
+            uses_adaptive_tap_hold: Cell::new(false),
+            runtime_vars: Default::default(),
+            layer_cycles: Default::default(),
+            used_aliases: Default::default(),
             lsp_hints: Default::default(),
             a: unsafe { Allocations::new() },
             pctx: ParserContext::default(),
@@ -1660,6 +1996,8 @@ fn parse_action_atom(ac_span: &Spanned<String>, s: &ParserState) -> Result<&'sta
         "lrld" => return custom(CustomAction::LiveReload, &s.a),
         "lrld-next" | "lrnx" => return custom(CustomAction::LiveReloadNext, &s.a),
         "lrld-prev" | "lrpv" => return custom(CustomAction::LiveReloadPrev, &s.a),
+        "clear-state" => return custom(CustomAction::ClearState, &s.a),
+        "passthrough-while-held" => return custom(CustomAction::PassthroughWhileHeld, &s.a),
         "sldr" => {
             return custom(
                 CustomAction::SequenceLeader(
@@ -1670,6 +2008,7 @@ fn parse_action_atom(ac_span: &Spanned<String>, s: &ParserState) -> Result<&'sta
             );
         }
         "scnl" => return custom(CustomAction::SequenceCancel, &s.a),
+        "seq-match" | "sequence-match" => return custom(CustomAction::SequenceMatch, &s.a),
         "mlft" | "mouseleft" => return custom(CustomAction::Mouse(Btn::Left), &s.a),
         "mrgt" | "mouseright" => return custom(CustomAction::Mouse(Btn::Right), &s.a),
         "mmid" | "mousemid" => return custom(CustomAction::Mouse(Btn::Mid), &s.a),
@@ -1680,6 +2019,13 @@ fn parse_action_atom(ac_span: &Spanned<String>, s: &ParserState) -> Result<&'sta
         "mmtp" | "mousetapmid" => return custom(CustomAction::MouseTap(Btn::Mid), &s.a),
         "mftp" | "mousetapforward" => return custom(CustomAction::MouseTap(Btn::Forward), &s.a),
         "mbtp" | "mousetapbackward" => return custom(CustomAction::MouseTap(Btn::Backward), &s.a),
+        "mllk" | "mouselockleft" => return custom(CustomAction::MouseLock(Btn::Left), &s.a),
+        "mrlk" | "mouselockright" => return custom(CustomAction::MouseLock(Btn::Right), &s.a),
+        "mmlk" | "mouselockmid" => return custom(CustomAction::MouseLock(Btn::Mid), &s.a),
+        "mflk" | "mouselockforward" => return custom(CustomAction::MouseLock(Btn::Forward), &s.a),
+        "mblk" | "mouselockbackward" => {
+            return custom(CustomAction::MouseLock(Btn::Backward), &s.a);
+        }
         "mwu" | "mousewheelup" => {
             return custom(
                 CustomAction::MWheelNotch {
@@ -1727,12 +2073,14 @@ fn parse_action_atom(ac_span: &Spanned<String>, s: &ParserState) -> Result<&'sta
         "use-defsrc" => {
             return Ok(s.a.sref(Action::Src));
         }
+        "layer-pop" => return custom(CustomAction::LayerPop, &s.a),
         "mvmt" | "mousemovement" | "🖰mv" => {
             bail_span!(ac_span, "{ac} can only be used as an input")
         }
         _ => {}
     };
     if let Some(oscode) = str_to_oscode(ac) {
+        check_dangerous_key(oscode, s, ac_span)?;
         if matches!(ac, "comp" | "cmp") {
             log::warn!(
                 "comp/cmp/cmps is not actually a compose key even though its correpsonding code is KEY_COMPOSE. Its actual functionality is context menu which somewhat behaves like right-click.\nTo remove this warning, replace this usage with an equivalent key name such as: menu"
@@ -1743,6 +2091,7 @@ fn parse_action_atom(ac_span: &Spanned<String>, s: &ParserState) -> Result<&'sta
     if let Some(alias) = ac.strip_prefix('@') {
         return match s.aliases.get(alias) {
             Some(ac) => {
+                s.used_aliases.borrow_mut().insert(alias.to_owned());
                 #[cfg(feature = "lsp")]
                 s.lsp_hints
                     .borrow_mut()
@@ -1790,9 +2139,31 @@ fn parse_action_atom(ac_span: &Spanned<String>, s: &ParserState) -> Result<&'sta
     if keys.contains(&KEY_OVERLAP) {
         bail!("O- is only valid in sequences for lists of keys");
     }
+    for key in keys.iter() {
+        check_dangerous_key((*key).into(), s, ac_span)?;
+    }
     Ok(s.a.sref(Action::MultipleKeyCodes(s.a.sref(s.a.sref_vec(keys)))))
 }
 
+/// Errors if the key can shut down or suspend the computer and is not in `allow-dangerous-keys`.
+fn check_dangerous_key(osc: OsCode, s: &ParserState, ac_span: &Spanned<String>) -> Result<()> {
+    if s.allow_dangerous_keys.contains(&osc) {
+        return Ok(());
+    }
+    if let Some((name, _)) = DANGEROUS_KEYS
+        .iter()
+        .find(|(_, dangerous)| *dangerous == osc)
+    {
+        bail_span!(
+            ac_span,
+            "{} shuts down or suspends the computer, so it could be pressed by accident \
+            and lose unsaved work.\nTo output it, add it to defcfg: allow-dangerous-keys ({name})",
+            ac_span.t
+        );
+    }
+    Ok(())
+}
+
 /// Parse a `kanata_keyberon::action::Action` from a `SExpr::List`.
 fn parse_action_list(ac: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
     if ac.is_empty() {
@@ -1808,7 +2179,8 @@ fn parse_action_list(ac: &[SExpr], s: &ParserState) -> Result<&'static KanataAct
     match ac_type.as_str() {
         LAYER_SWITCH => parse_layer_base(&ac[1..], s),
         LAYER_TOGGLE | LAYER_WHILE_HELD => parse_layer_toggle(&ac[1..], s),
-        TAP_HOLD => parse_tap_hold(&ac[1..], s, HoldTapConfig::Default),
+        LAYER_WHILE_HELD_TIMEOUT => parse_layer_while_held_timeout(&ac[1..], s),
+        TAP_HOLD => parse_tap_hold(&ac[1..], s, s.default_tap_hold_config),
         TAP_HOLD_PRESS | TAP_HOLD_PRESS_A => {
             parse_tap_hold(&ac[1..], s, HoldTapConfig::HoldOnOtherKeyPress)
         }
@@ -1831,7 +2203,10 @@ fn parse_action_list(ac: &[SExpr], s: &ParserState) -> Result<&'static KanataAct
         TAP_HOLD_TAP_KEYS | TAP_HOLD_TAP_KEYS_A => {
             parse_tap_hold_keys(&ac[1..], s, TAP_HOLD_TAP_KEYS, custom_tap_hold_tap_keys)
         }
+        TAP_HOLD_ADAPTIVE => parse_tap_hold_adaptive(&ac[1..], s),
+        CHORD_HOLD => bail!("{CHORD_HOLD} can only be used as the action of a defchordsv2 chord"),
         MULTI => parse_multi(&ac[1..], s),
+        MULTI_SEQ => parse_multi_seq(&ac[1..], s),
         MACRO => parse_macro(&ac[1..], s, RepeatMacro::No),
         MACRO_REPEAT | MACRO_REPEAT_A => parse_macro(&ac[1..], s, RepeatMacro::Yes),
         MACRO_RELEASE_CANCEL | MACRO_RELEASE_CANCEL_A => {
@@ -1853,6 +2228,7 @@ fn parse_action_list(ac: &[SExpr], s: &ParserState) -> Result<&'static KanataAct
             parse_macro_cancel_on_next_press_cancel_on_release(&ac[1..], s, RepeatMacro::Yes)
         }
         UNICODE | SYM => parse_unicode(&ac[1..], s),
+        STR => parse_str(&ac[1..], s),
         ONE_SHOT | ONE_SHOT_PRESS | ONE_SHOT_PRESS_A => {
             parse_one_shot(&ac[1..], s, OneShotEndConfig::EndOnFirstPress)
         }
@@ -1869,6 +2245,7 @@ fn parse_action_list(ac: &[SExpr], s: &ParserState) -> Result<&'static KanataAct
         TAP_DANCE => parse_tap_dance(&ac[1..], s, TapDanceConfig::Lazy),
         TAP_DANCE_EAGER => parse_tap_dance(&ac[1..], s, TapDanceConfig::Eager),
         CHORD => parse_chord(&ac[1..], s),
+        PRESS_KEY | PRESS_KEY_A => parse_press_key(&ac[1..], s),
         RELEASE_KEY | RELEASE_KEY_A => parse_release_key(&ac[1..], s),
         RELEASE_LAYER | RELEASE_LAYER_A => parse_release_layer(&ac[1..], s),
         ON_PRESS_FAKEKEY | ON_PRESS_FAKEKEY_A => parse_on_press_fake_key_op(&ac[1..], s),
@@ -1881,6 +2258,7 @@ fn parse_action_list(ac: &[SExpr], s: &ParserState) -> Result<&'static KanataAct
         }
         ON_IDLE_FAKEKEY => parse_on_idle_fakekey(&ac[1..], s),
         ON_PRESS | ON_PRESS_A => parse_on_press(&ac[1..], s),
+        FAKEKEYS => parse_fakekeys(&ac[1..], s),
         ON_RELEASE | ON_RELEASE_A => parse_on_release(&ac[1..], s),
         ON_IDLE => parse_on_idle(&ac[1..], s),
         ON_PHYSICAL_IDLE => parse_on_physical_idle(&ac[1..], s),
@@ -1893,6 +2271,10 @@ fn parse_action_list(ac: &[SExpr], s: &ParserState) -> Result<&'static KanataAct
         MWHEEL_ACCEL_DOWN => parse_mwheel_accel(&ac[1..], MWheelDirection::Down, s),
         MWHEEL_ACCEL_LEFT => parse_mwheel_accel(&ac[1..], MWheelDirection::Left, s),
         MWHEEL_ACCEL_RIGHT => parse_mwheel_accel(&ac[1..], MWheelDirection::Right, s),
+        MWHEEL_FINE_UP => parse_mwheel_fine(&ac[1..], MWheelDirection::Up, s),
+        MWHEEL_FINE_DOWN => parse_mwheel_fine(&ac[1..], MWheelDirection::Down, s),
+        MWHEEL_FINE_LEFT => parse_mwheel_fine(&ac[1..], MWheelDirection::Left, s),
+        MWHEEL_FINE_RIGHT => parse_mwheel_fine(&ac[1..], MWheelDirection::Right, s),
         MOVEMOUSE_UP | MOVEMOUSE_UP_A => parse_move_mouse(&ac[1..], MoveDirection::Up, s),
         MOVEMOUSE_DOWN | MOVEMOUSE_DOWN_A => parse_move_mouse(&ac[1..], MoveDirection::Down, s),
         MOVEMOUSE_LEFT | MOVEMOUSE_LEFT_A => parse_move_mouse(&ac[1..], MoveDirection::Left, s),
@@ -1910,7 +2292,10 @@ fn parse_action_list(ac: &[SExpr], s: &ParserState) -> Result<&'static KanataAct
             parse_move_mouse_accel(&ac[1..], MoveDirection::Right, s)
         }
         MOVEMOUSE_SPEED | MOVEMOUSE_SPEED_A => parse_move_mouse_speed(&ac[1..], s),
+        MOVEMOUSE_SPEED_RESET => parse_move_mouse_speed_reset(&ac[1..], s),
+        MOVEMOUSE_POLAR => parse_move_mouse_polar(&ac[1..], s),
         SETMOUSE | SETMOUSE_A => parse_set_mouse(&ac[1..], s),
+        SETMOUSE_PCT => parse_set_mouse_pct(&ac[1..], s),
         DYNAMIC_MACRO_RECORD => parse_dynamic_macro_record(&ac[1..], s),
         DYNAMIC_MACRO_PLAY => parse_dynamic_macro_play(&ac[1..], s),
         ARBITRARY_CODE => parse_arbitrary_code(&ac[1..], s),
@@ -1919,6 +2304,7 @@ fn parse_action_list(ac: &[SExpr], s: &ParserState) -> Result<&'static KanataAct
         CMD_LOG => parse_cmd_log(&ac[1..], s),
         PUSH_MESSAGE => parse_push_message(&ac[1..], s),
         FORK => parse_fork(&ac[1..], s),
+        FORK_MULTI => parse_fork_multi(&ac[1..], s),
         CAPS_WORD | CAPS_WORD_A => {
             parse_caps_word(&ac[1..], CapsWordRepressBehaviour::Overwrite, s)
         }
@@ -1937,6 +2323,9 @@ fn parse_action_list(ac: &[SExpr], s: &ParserState) -> Result<&'static KanataAct
         SEQUENCE_NOERASE => parse_sequence_noerase(&ac[1..], s),
         UNMOD => parse_unmod(UNMOD, &ac[1..], s),
         UNSHIFT | UNSHIFT_A => parse_unmod(UNSHIFT, &ac[1..], s),
+        CLEAN_MODS => parse_clean_mods(CLEAN_MODS, &ac[1..], s, true),
+        PRESERVE_MODS => parse_clean_mods(PRESERVE_MODS, &ac[1..], s, false),
+        CHORD_OUTPUT => parse_chord_output(&ac[1..], s),
         LIVE_RELOAD_NUM => parse_live_reload_num(&ac[1..], s),
         LIVE_RELOAD_FILE => parse_live_reload_file(&ac[1..], s),
         CLIPBOARD_SET => parse_clipboard_set(&ac[1..], s),
@@ -1946,6 +2335,13 @@ fn parse_action_list(ac: &[SExpr], s: &ParserState) -> Result<&'static KanataAct
         CLIPBOARD_SAVE_SET => parse_clipboard_save_set(&ac[1..], s),
         CLIPBOARD_SAVE_CMD_SET => parse_cmd(&ac[1..], s, CmdType::ClipboardSaveSet),
         CLIPBOARD_SAVE_SWAP => parse_clipboard_save_swap(&ac[1..], s),
+        STICKY_LAYER => parse_sticky_layer(&ac[1..], s),
+        LAYER_PUSH => parse_layer_push(&ac[1..], s),
+        LAYER_CYCLE => parse_layer_cycle(&ac[1..], s),
+        HID => parse_hid(&ac[1..], s),
+        SETVAR => parse_setvar(&ac[1..], s),
+        INCVAR => parse_incvar(&ac[1..], s, false),
+        DECVAR => parse_incvar(&ac[1..], s, true),
         _ => unreachable!(),
     }
 }
@@ -1962,6 +2358,91 @@ fn parse_layer_toggle(ac_params: &[SExpr], s: &ParserState) -> Result<&'static K
     Ok(s.a.sref(Action::Layer(idx)))
 }
 
+fn parse_layer_cycle(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
+    const ERR_MSG: &str = "layer-cycle expects 1 param: (<layer-name> <layer-name> ...)";
+    let [layer_list] = ac_params else {
+        bail!("{ERR_MSG}\nFound {} params instead", ac_params.len());
+    };
+    let layer_names = layer_list
+        .list(s.vars())
+        .ok_or_else(|| anyhow_expr!(layer_list, "{ERR_MSG}\nThe layer names must be a list"))?;
+    if layer_names.len() < 2 {
+        bail_expr!(
+            layer_list,
+            "{ERR_MSG}\nThe list must have at least 2 layers"
+        );
+    }
+    let layers = layer_names
+        .iter()
+        .map(|name| {
+            set_layer_change_lsp_hint(name, &mut s.lsp_hints.borrow_mut());
+            layer_idx(std::slice::from_ref(name), &s.layer_idxs, s)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let cycle = s.layer_cycle_idx(layers);
+    Ok(s.a.sref(Action::Custom(
+        s.a.sref(s.a.sref_slice(CustomAction::LayerCycle(cycle))),
+    )))
+}
+
+fn parse_layer_while_held_timeout(
+    ac_params: &[SExpr],
+    s: &ParserState,
+) -> Result<&'static KanataAction> {
+    const ERR_MSG: &str = "layer-while-held-timeout expects 2 params: <layer-name> <timeout-ms>";
+    if ac_params.len() != 2 {
+        bail!("{ERR_MSG}\nFound {} params instead", ac_params.len());
+    }
+    let layer = layer_idx(&ac_params[..1], &s.layer_idxs, s)?;
+    set_layer_change_lsp_hint(&ac_params[0], &mut s.lsp_hints.borrow_mut());
+    let timeout = ac_params[1]
+        .atom(s.vars())
+        .and_then(|t| str::parse::<u32>(t).ok())
+        .filter(|&t| t > 0)
+        .ok_or_else(|| {
+            anyhow_expr!(
+                &ac_params[1],
+                "{ERR_MSG}\ntimeout-ms must be a non-zero number of milliseconds"
+            )
+        })?;
+    let actions = vec![
+        Action::Layer(layer),
+        Action::Custom(s.a.sref(s.a.sref_slice(CustomAction::LayerWhileHeldTimeout { timeout }))),
+    ];
+    Ok(s.a.sref(Action::MultipleActions(s.a.sref(s.a.sref_vec(actions)))))
+}
+
+fn parse_sticky_layer(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
+    const ERR_MSG: &str = "sticky-layer expects 1 or 2 params: <layer-name> <?tap-timeout>";
+    if !matches!(ac_params.len(), 1 | 2) {
+        bail!("{ERR_MSG}\nFound {} params instead", ac_params.len());
+    }
+    let layer = layer_idx(&ac_params[..1], &s.layer_idxs, s)?;
+    set_layer_change_lsp_hint(&ac_params[0], &mut s.lsp_hints.borrow_mut());
+    let timeout = match ac_params.get(1) {
+        Some(timeout) => parse_non_zero_u16(timeout, s, "tap-timeout")?,
+        None => 200,
+    };
+    Ok(s.a.sref(Action::Custom(s.a.sref(s.a.sref_slice(
+        CustomAction::StickyLayer {
+            layer: layer as u16,
+            timeout,
+        },
+    )))))
+}
+
+fn parse_layer_push(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
+    if ac_params.len() != 1 {
+        bail!(
+            "layer-push expects 1 param: <layer-name>\nFound {} params instead",
+            ac_params.len()
+        );
+    }
+    let layer = layer_idx(ac_params, &s.layer_idxs, s)?;
+    set_layer_change_lsp_hint(&ac_params[0], &mut s.lsp_hints.borrow_mut());
+    custom(CustomAction::LayerPush(layer as u16), &s.a)
+}
+
 #[allow(unused_variables)]
 fn set_layer_change_lsp_hint(layer_name_expr: &SExpr, lsp_hints: &mut LspHints) {
     #[cfg(feature = "lsp")]
@@ -2010,7 +2491,7 @@ Params in order:
         )
     }
     let tap_repress_timeout = parse_u16(&ac_params[0], s, "tap repress timeout")?;
-    let hold_timeout = parse_non_zero_u16(&ac_params[1], s, "hold timeout")?;
+    let (hold_timeout, layer_timeout) = parse_hold_timeout(&ac_params[1], s)?;
     let tap_action = parse_action(&ac_params[2], s)?;
     let hold_action = parse_action(&ac_params[3], s)?;
     if matches!(tap_action, Action::HoldTap { .. }) {
@@ -2024,6 +2505,94 @@ Params in order:
         hold: *hold_action,
         timeout_action: *hold_action,
         on_press_reset_timeout_to: None,
+        adaptive_timeout: None,
+        layer_timeout,
+    }))))
+}
+
+fn tap_hold_mode_config(mode: TapHoldMode) -> HoldTapConfig<'static> {
+    match mode {
+        TapHoldMode::Timeout => HoldTapConfig::Default,
+        TapHoldMode::HoldOnOtherKeyPress => HoldTapConfig::HoldOnOtherKeyPress,
+        TapHoldMode::PermissiveHold => HoldTapConfig::PermissiveHold,
+    }
+}
+
+/// Parse the `chord-hold` action of a defchordsv2 chord, returning the tap-hold action and the
+/// hold timeout during which releasing any participating key taps it.
+fn parse_chord_hold(ac_params: &[SExpr], s: &ParserState) -> Result<(&'static KanataAction, u16)> {
+    if ac_params.len() != 3 {
+        bail!(
+            r"{CHORD_HOLD} expects 3 items after it, got {}.
+Params in order:
+<hold-timeout> <tap-action> <hold-action>",
+            ac_params.len(),
+        )
+    }
+    let hold_timeout = parse_non_zero_u16(&ac_params[0], s, "hold timeout")?;
+    let tap_action = parse_action(&ac_params[1], s)?;
+    let hold_action = parse_action(&ac_params[2], s)?;
+    if matches!(tap_action, Action::HoldTap { .. }) {
+        bail!("tap-hold does not work in the tap-action of {CHORD_HOLD}")
+    }
+    let action = s.a.sref(Action::HoldTap(s.a.sref(HoldTapAction {
+        config: HoldTapConfig::Default,
+        tap_hold_interval: 0,
+        timeout: hold_timeout,
+        tap: *tap_action,
+        hold: *hold_action,
+        timeout_action: *hold_action,
+        on_press_reset_timeout_to: None,
+        adaptive_timeout: None,
+        layer_timeout: false,
+    })));
+    Ok((action, hold_timeout))
+}
+
+/// Parse the hold timeout of a tap-hold action. `_` uses the `tap-timeout` of the layer the
+/// action is on, or the `tap-hold-timeout` of `defcfg`.
+fn parse_hold_timeout(expr: &SExpr, s: &ParserState) -> Result<(u16, bool)> {
+    match expr.atom(s.vars()) {
+        Some("_") => Ok((s.default_tap_hold_timeout, true)),
+        _ => Ok((parse_non_zero_u16(expr, s, "hold timeout")?, false)),
+    }
+}
+
+fn parse_tap_hold_adaptive(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
+    if ac_params.len() != 6 {
+        bail!(
+            r"{TAP_HOLD_ADAPTIVE} expects 6 items after it, got {}.
+Params in order:
+<tap-repress-timeout> <base-timeout> <min-timeout> <max-timeout> <tap-action> <hold-action>",
+            ac_params.len(),
+        )
+    }
+    let tap_repress_timeout = parse_u16(&ac_params[0], s, "tap repress timeout")?;
+    let base_timeout = parse_non_zero_u16(&ac_params[1], s, "base timeout")?;
+    let min_timeout = parse_non_zero_u16(&ac_params[2], s, "min timeout")?;
+    let max_timeout = parse_non_zero_u16(&ac_params[3], s, "max timeout")?;
+    if !(min_timeout..=max_timeout).contains(&base_timeout) {
+        bail_expr!(
+            &ac_params[1],
+            "base timeout must be between the min timeout and the max timeout"
+        );
+    }
+    let tap_action = parse_action(&ac_params[4], s)?;
+    let hold_action = parse_action(&ac_params[5], s)?;
+    if matches!(tap_action, Action::HoldTap { .. }) {
+        bail!("tap-hold does not work in the tap-action of tap-hold")
+    }
+    s.uses_adaptive_tap_hold.set(true);
+    Ok(s.a.sref(Action::HoldTap(s.a.sref(HoldTapAction {
+        config: HoldTapConfig::Default,
+        tap_hold_interval: tap_repress_timeout,
+        timeout: base_timeout,
+        tap: *tap_action,
+        hold: *hold_action,
+        timeout_action: *hold_action,
+        on_press_reset_timeout_to: None,
+        adaptive_timeout: Some((min_timeout, max_timeout)),
+        layer_timeout: false,
     }))))
 }
 
@@ -2057,7 +2626,7 @@ fn parse_tap_hold_timeout(
         _ => unreachable!("other configs not expected"),
     };
     let tap_repress_timeout = parse_u16(&ac_params[0], s, "tap repress timeout")?;
-    let hold_timeout = parse_non_zero_u16(&ac_params[1], s, "hold timeout")?;
+    let (hold_timeout, layer_timeout) = parse_hold_timeout(&ac_params[1], s)?;
     let tap_action = parse_action(&ac_params[2], s)?;
     let hold_action = parse_action(&ac_params[3], s)?;
     let timeout_action = parse_action(&ac_params[4], s)?;
@@ -2084,6 +2653,8 @@ fn parse_tap_hold_timeout(
         hold: *hold_action,
         timeout_action: *timeout_action,
         on_press_reset_timeout_to,
+        adaptive_timeout: None,
+        layer_timeout,
     }))))
 }
 
@@ -2103,7 +2674,7 @@ Params in order:
         )
     }
     let tap_repress_timeout = parse_u16(&ac_params[0], s, "tap repress timeout")?;
-    let hold_timeout = parse_non_zero_u16(&ac_params[1], s, "hold timeout")?;
+    let (hold_timeout, layer_timeout) = parse_hold_timeout(&ac_params[1], s)?;
     let tap_action = parse_action(&ac_params[2], s)?;
     let hold_action = parse_action(&ac_params[3], s)?;
     let tap_trigger_keys = parse_key_list(&ac_params[4], s, "tap-trigger-keys")?;
@@ -2118,6 +2689,8 @@ Params in order:
         hold: *hold_action,
         timeout_action: *hold_action,
         on_press_reset_timeout_to: None,
+        adaptive_timeout: None,
+        layer_timeout,
     }))))
 }
 
@@ -2135,7 +2708,7 @@ Params in order:
         )
     }
     let tap_repress_timeout = parse_u16(&ac_params[0], s, "tap repress timeout")?;
-    let hold_timeout = parse_non_zero_u16(&ac_params[1], s, "hold timeout")?;
+    let (hold_timeout, layer_timeout) = parse_hold_timeout(&ac_params[1], s)?;
     let tap_action = parse_action(&ac_params[2], s)?;
     let hold_action = parse_action(&ac_params[3], s)?;
     let tap_trigger_keys_on_press =
@@ -2157,6 +2730,8 @@ Params in order:
         hold: *hold_action,
         timeout_action: *hold_action,
         on_press_reset_timeout_to: None,
+        adaptive_timeout: None,
+        layer_timeout,
     }))))
 }
 
@@ -2242,6 +2817,22 @@ fn parse_non_zero_u16(expr: &SExpr, s: &ParserState, label: &str) -> Result<u16>
         .ok_or_else(|| anyhow_expr!(expr, "{label} must be 1-65535"))
 }
 
+/// Parse a `$name` reference to a runtime variable. Returns `None` for other expressions,
+/// including `$name` references to a `defvar` variable.
+fn parse_runtime_var_ref(expr: &SExpr, s: &ParserState) -> Option<u16> {
+    expr.atom(s.vars())
+        .and_then(|a| a.strip_prefix('$'))
+        .filter(|name| !name.is_empty())
+        .map(|name| s.runtime_var_idx(name))
+}
+
+fn parse_non_zero_u16_or_var(expr: &SExpr, s: &ParserState, label: &str) -> Result<RuntimeU16> {
+    match parse_runtime_var_ref(expr, s) {
+        Some(var) => Ok(RuntimeU16::Var(var)),
+        None => parse_non_zero_u16(expr, s, label).map(RuntimeU16::Num),
+    }
+}
+
 fn parse_key_list(expr: &SExpr, s: &ParserState, label: &str) -> Result<Vec<OsCode>> {
     expr.list(s.vars())
         .map(|keys| {
@@ -2322,6 +2913,94 @@ fn parse_multi(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAc
     Ok(s.a.sref(Action::MultipleActions(s.a.sref(s.a.sref_vec(actions)))))
 }
 
+fn parse_multi_seq(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
+    const ERR_MSG: &str =
+        "multi-seq expects actions, each of which may be preceded by a delay in milliseconds";
+    if ac_params.is_empty() {
+        bail!("{ERR_MSG}\nfound no items");
+    }
+    // Elements without a delay in between are grouped and activate in the same tick, like multi.
+    let mut groups: Vec<(u16, Vec<SExpr>)> = vec![(0, vec![])];
+    let mut delay: Option<(u16, &SExpr)> = None;
+    for expr in ac_params {
+        if let Some(ms) = expr.atom(s.vars()).and_then(|a| str::parse::<u16>(a).ok()) {
+            if delay.is_some() {
+                bail_expr!(expr, "{ERR_MSG}\nfound two delays in a row");
+            }
+            delay = Some((ms, expr));
+            continue;
+        }
+        match delay.take() {
+            Some((ms, _)) if ms > 0 => groups.push((ms, vec![expr.clone()])),
+            _ => groups.last_mut().expect("nonempty").1.push(expr.clone()),
+        }
+    }
+    if let Some((_, expr)) = delay {
+        bail_expr!(expr, "{ERR_MSG}\na delay must be followed by an action");
+    }
+    let mut group_actions = vec![];
+    for (ms, exprs) in groups {
+        let action = match exprs.len() {
+            0 => continue,
+            1 => parse_action(&exprs[0], s)?,
+            _ => parse_multi(&exprs, s)?,
+        };
+        let has_unsupported = |ac: &KanataAction| {
+            matches!(
+                ac,
+                Action::HoldTap(..) | Action::TapDance(..) | Action::Chords(..)
+            )
+        };
+        if has_unsupported(action)
+            || matches!(action, Action::MultipleActions(acs) if acs.iter().any(has_unsupported))
+        {
+            bail_expr!(
+                &exprs[0],
+                "multi-seq does not support tap-hold, tap-dance or chords"
+            );
+        }
+        group_actions.push((ms, action));
+    }
+
+    let (first_delay, first) = group_actions[0];
+    let steps: Vec<MultiSeqStep> = group_actions[1..]
+        .iter()
+        .map(|&(delay, action)| MultiSeqStep { delay, action })
+        .collect();
+    let mut actions: Vec<KanataAction> = vec![];
+    let mut custom_actions: Vec<&'static CustomAction> = vec![];
+    let steps = if first_delay > 0 {
+        // Even the first element is delayed, so all of them are steps.
+        let mut all = vec![MultiSeqStep {
+            delay: first_delay,
+            action: first,
+        }];
+        all.extend(steps);
+        all
+    } else {
+        match first {
+            Action::MultipleActions(acs) => {
+                for ac in acs.iter() {
+                    match ac {
+                        Action::Custom(cacs) => custom_actions.extend(cacs.iter()),
+                        _ => actions.push(*ac),
+                    }
+                }
+            }
+            Action::Custom(cacs) => custom_actions.extend(cacs.iter()),
+            _ => actions.push(*first),
+        }
+        steps
+    };
+    if !steps.is_empty() {
+        custom_actions.push(s.a.sref(CustomAction::MultiSeq(s.a.sref_vec(steps))));
+    }
+    // Keys release in the reverse order of activation.
+    custom_actions.push(s.a.sref(CustomAction::ReverseReleaseOrder));
+    actions.push(Action::Custom(s.a.sref(s.a.sref_vec(custom_actions))));
+    Ok(s.a.sref(Action::MultipleActions(s.a.sref(s.a.sref_vec(actions)))))
+}
+
 const MACRO_ERR: &str = "Action macro only accepts delays, keys, chords, chorded sub-macros, and a subset of special actions.\nThe macro section of the documentation describes this in more detail:\nhttps://github.com/jtroo/kanata/blob/main/docs/config.adoc#macro";
 enum RepeatMacro {
     Yes,
@@ -2375,6 +3054,61 @@ fn parse_macro_release_cancel(
     ])))))
 }
 
+fn parse_clean_mods(
+    name: &str,
+    ac_params: &[SExpr],
+    s: &ParserState,
+    clean: bool,
+) -> Result<&'static KanataAction> {
+    if ac_params.len() != 1 {
+        bail!("{name} expects 1 parameter: <action>");
+    }
+    let mut actions = match parse_action(&ac_params[0], s)? {
+        Action::MultipleActions(acs) => acs.to_vec(),
+        ac => vec![*ac],
+    };
+    let mut custom_actions: Vec<&'static CustomAction> =
+        vec![s.a.sref(CustomAction::CleanModsOnAction(clean))];
+    actions.retain(|ac| match ac {
+        Action::Custom(acs) => {
+            custom_actions.extend(acs.iter());
+            false
+        }
+        _ => true,
+    });
+    actions.push(Action::Custom(s.a.sref(s.a.sref_vec(custom_actions))));
+    Ok(s.a.sref(Action::MultipleActions(s.a.sref(s.a.sref_vec(actions)))))
+}
+
+fn parse_chord_output(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
+    const ERR_MSG: &str = "chord-output expects at least two key names";
+    if ac_params.len() < 2 {
+        bail!("{ERR_MSG}\nfound {} items", ac_params.len());
+    }
+    let mut keys: Vec<KeyCode> = vec![];
+    for param in ac_params {
+        let key = param
+            .atom(s.vars())
+            .and_then(str_to_oscode)
+            .ok_or_else(|| anyhow_expr!(param, "{ERR_MSG}\nfound invalid key name"))?
+            .into();
+        if keys.contains(&key) {
+            bail_expr!(param, "Duplicate key name in chord-output is not allowed.");
+        }
+        keys.push(key);
+    }
+    let keys = s.a.sref_vec(keys);
+    // Keys are pressed in the listed order; the release is reversed. Unlike with
+    // `MultipleKeyCodes`, the keys stay held when other actions activate.
+    let custom_actions = vec![
+        s.a.sref(CustomAction::ChordOutput(keys)),
+        s.a.sref(CustomAction::ReverseReleaseOrder),
+    ];
+    let mut actions: Vec<KanataAction> = keys.iter().map(|&kc| Action::KeyCode(kc)).collect();
+    actions.push(Action::Custom(s.a.sref(s.a.sref_vec(custom_actions))));
+    Ok(s.a.sref(Action::MultipleActions(s.a.sref(s.a.sref_vec(actions)))))
+}
+
 fn parse_macro_cancel_on_next_press(
     ac_params: &[SExpr],
     s: &ParserState,
@@ -2452,6 +3186,9 @@ fn parse_macro_item_impl<'a>(
     &'a [SExpr],
 )> {
     if num_parse_mode == MacroNumberParseMode::Delay {
+        if let Some(var) = parse_runtime_var_ref(&acs[0], s) {
+            return Ok((vec![SequenceEvent::DelayVar { var }], &acs[1..]));
+        }
         if let Some(a) = acs[0].atom(s.vars()) {
             match parse_non_zero_u16(&acs[0], s, "delay") {
                 Ok(duration) => {
@@ -2666,6 +3403,60 @@ fn parse_unicode(ac_params: &[SExpr], s: &ParserState) -> Result<&'static Kanata
         .ok_or_else(|| anyhow_expr!(&ac_params[0], "{ERR_STR}"))?
 }
 
+fn parse_str(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
+    const ERR_MSG: &str = "str expects 1 or 2 parameters: <string> [delay between characters (ms)]";
+    if ac_params.is_empty() || ac_params.len() > 2 {
+        bail!("{ERR_MSG}, found {}", ac_params.len());
+    }
+    let Some(text) = ac_params[0].atom(s.vars()) else {
+        bail_expr!(&ac_params[0], "{ERR_MSG}\nThe string cannot be a list");
+    };
+    let text =
+        unescape_str(text.trim_atom_quotes()).map_err(|e| anyhow_expr!(&ac_params[0], "{e}"))?;
+    if text.is_empty() {
+        bail_expr!(&ac_params[0], "{ERR_MSG}\nThe string cannot be empty");
+    }
+    let delay = match ac_params.get(1) {
+        Some(delay) => parse_u16(delay, s, "delay")?,
+        None => 0,
+    };
+    custom(
+        CustomAction::Str {
+            text: s.a.sref_str(text),
+            delay,
+        },
+        &s.a,
+    )
+}
+
+/// Replaces the escape sequences `\n`, `\t`, `\"` and `\\` in a `str` string.
+fn unescape_str(text: &str) -> std::result::Result<String, String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        unescaped.push(match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some(c) => {
+                return Err(format!(
+                    "Unknown escape sequence \\{c} in string.\nUse \\\\ for a backslash."
+                ));
+            }
+            None => return Err(
+                "Unterminated escape sequence at the end of the string.\nUse \\\\ for a backslash."
+                    .to_string(),
+            ),
+        });
+    }
+    Ok(unescaped)
+}
+
 enum CmdType {
     /// Execute command in own thread.
     Standard,
@@ -2844,9 +3635,15 @@ fn parse_one_shot(
     let action = parse_action(&ac_params[1], s)?;
     if !matches!(
         action,
-        Action::Layer(..) | Action::KeyCode(..) | Action::MultipleKeyCodes(..)
+        Action::Layer(..)
+            | Action::KeyCode(..)
+            | Action::MultipleKeyCodes(..)
+            | Action::Custom(&[&CustomAction::Mouse(_)])
     ) {
-        bail!("one-shot is only allowed to contain layer-while-held, a keycode, or a chord");
+        bail!(
+            "one-shot is only allowed to contain layer-while-held, a keycode, a chord, \
+             or a mouse button"
+        );
     }
 
     Ok(s.a.sref(Action::OneShot(s.a.sref(OneShot {
@@ -2935,6 +3732,18 @@ fn parse_chord(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAc
     }))))
 }
 
+fn parse_press_key(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
+    const ERR_MSG: &str = "press-key expects exactly one keycode (e.g. lalt)";
+    if ac_params.len() != 1 {
+        bail!("{ERR_MSG}: found {} items", ac_params.len());
+    }
+    let ac = parse_action(&ac_params[0], s)?;
+    match ac {
+        Action::KeyCode(kc) => custom(CustomAction::PressKey(*kc), &s.a),
+        _ => err_expr!(&ac_params[0], "{}", ERR_MSG),
+    }
+}
+
 fn parse_release_key(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
     const ERR_MSG: &str = "release-key expects exactly one keycode (e.g. lalt)";
     if ac_params.len() != 1 {
@@ -2942,9 +3751,11 @@ fn parse_release_key(ac_params: &[SExpr], s: &ParserState) -> Result<&'static Ka
     }
     let ac = parse_action(&ac_params[0], s)?;
     match ac {
-        Action::KeyCode(kc) => {
-            Ok(s.a.sref(Action::ReleaseState(ReleasableState::KeyCode(*kc))))
-        }
+        // Release both a key held by a key press and a key held by press-key.
+        Action::KeyCode(kc) => Ok(s.a.sref(Action::MultipleActions(s.a.sref(s.a.sref_vec(vec![
+            Action::ReleaseState(ReleasableState::KeyCode(*kc)),
+            Action::Custom(s.a.sref(s.a.sref_slice(CustomAction::ReleaseKey(*kc)))),
+        ]))))),
         _ => err_expr!(&ac_params[0], "{}", ERR_MSG),
     }
 }
@@ -3341,6 +4152,183 @@ fn parse_virtual_keys(exprs: &[&Vec<SExpr>], s: &mut ParserState) -> Result<()>
     Ok(())
 }
 
+/// Allocates a fake key column after the virtual keys for an action that is not a virtual key.
+fn add_hidden_fake_key(
+    expr: &SExpr,
+    action: &'static KanataAction,
+    s: &ParserState,
+) -> Result<u16> {
+    if s.pctx.is_within_defvirtualkeys {
+        // Virtual keys are numbered as they are parsed, so they must come first.
+        bail_expr!(expr, "This action cannot be used within defvirtualkeys");
+    }
+    let mut hidden_fake_keys = s.hidden_fake_keys.borrow_mut();
+    let idx = s.virtual_keys.len() + hidden_fake_keys.len();
+    if idx >= KEYS_IN_ROW {
+        bail_expr!(
+            expr,
+            "Maximum number of virtual keys is {KEYS_IN_ROW}, including on-idle, on-resume, \
+             on-exit, defidle, defmacro, defoverrides and on-release actions"
+        );
+    }
+    hidden_fake_keys.push((idx, action));
+    Ok(idx as u16)
+}
+
+/// Allocates fake key columns for the `on-idle`, `on-resume` and `defidle` actions.
+fn parse_idle_hooks(
+    cfg: &CfgOptions,
+    exprs: &[&Vec<SExpr>],
+    s: &mut ParserState,
+) -> Result<IdleHooks> {
+    let mut hooks = IdleHooks::default();
+    let add_hook_key = |expr: &SExpr, s: &mut ParserState| -> Result<u16> {
+        let action = parse_action(expr, s)?;
+        add_hidden_fake_key(expr, action, s)
+    };
+    if let Some((idle_ms, expr)) = &cfg.on_idle {
+        hooks.on_idle = Some((*idle_ms, add_hook_key(expr, s)?));
+    }
+    if let Some(expr) = &cfg.on_resume {
+        if hooks.on_idle.is_none() {
+            bail_expr!(expr, "on-resume requires on-idle to also be defined");
+        }
+        hooks.on_resume = Some(add_hook_key(expr, s)?);
+    }
+    for expr in exprs {
+        let mut subexprs = check_first_expr(expr.iter(), "defidle")?;
+        while let Some(time_expr) = subexprs.next() {
+            const ERR_MSG: &str =
+                "defidle expects an idle time or a list of idle time and layer name";
+            let (ms_expr, layer) = match time_expr {
+                SExpr::Atom(_) => (time_expr, None),
+                SExpr::List(l) => match &l.t[..] {
+                    [ms_expr, layer_expr] => {
+                        let layer = layer_expr
+                            .atom(s.vars())
+                            .and_then(|l| s.layer_idxs.get(l))
+                            .ok_or_else(|| anyhow_expr!(layer_expr, "Unknown layer name"))?;
+                        (ms_expr, Some(*layer as u16))
+                    }
+                    _ => bail_expr!(time_expr, "{ERR_MSG}"),
+                },
+            };
+            let idle_ms = ms_expr
+                .atom(s.vars())
+                .and_then(|ms| ms.parse::<u32>().ok())
+                .filter(|ms| *ms > 0)
+                .ok_or_else(|| {
+                    anyhow_expr!(
+                        ms_expr,
+                        "Idle time must be a non-zero number of milliseconds"
+                    )
+                })?;
+            let Some(action_expr) = subexprs.next() else {
+                bail_expr!(
+                    time_expr,
+                    "Idle time has no action - you must add an action."
+                );
+            };
+            let y = add_hook_key(action_expr, s)?;
+            hooks.watchers.push(IdleWatcher { idle_ms, layer, y });
+        }
+    }
+    Ok(hooks)
+}
+
+/// Allocates fake key columns for the named macros of `defmacro`, which can be played with the
+/// `PlayMacro` TCP command.
+fn parse_named_macros(exprs: &[&Vec<SExpr>], s: &mut ParserState) -> Result<HashMap<String, u16>> {
+    let mut macros = HashMap::default();
+    for expr in exprs {
+        let mut subexprs = check_first_expr(expr.iter(), "defmacro")?;
+        while let Some(name_expr) = subexprs.next() {
+            let name = name_expr
+                .atom(s.vars())
+                .ok_or_else(|| anyhow_expr!(name_expr, "Macro name must not be a list."))?
+                .to_owned();
+            let Some(macro_expr) = subexprs.next() else {
+                bail_expr!(name_expr, "Macro name has no macro - you must add a macro.");
+            };
+            let items = macro_expr.list(s.vars()).ok_or_else(|| {
+                anyhow_expr!(macro_expr, "Macro must be a list of macro actions.")
+            })?;
+            let action = parse_macro(items, s, RepeatMacro::No)?;
+            let y = add_hidden_fake_key(macro_expr, action, s)?;
+            if macros.insert(name.clone(), y).is_some() {
+                bail_expr!(name_expr, "Duplicate macro name: {name}");
+            }
+        }
+    }
+    Ok(macros)
+}
+
+/// Parses the time ranges of `defschedule`, e.g. `(09:00 17:00 mon tue wed thu fri) work`.
+fn parse_schedule(exprs: &[&Vec<SExpr>], s: &ParserState) -> Result<Vec<ScheduleEntry>> {
+    const ERR_MSG: &str = "defschedule expects a list of start time, end time and \
+                           optional weekdays, e.g. (09:00 17:00 mon tue)";
+    let parse_time = |expr: &SExpr, max_min: u16| -> Result<u16> {
+        expr.atom(s.vars())
+            .and_then(|t| t.split_once(':'))
+            .and_then(|(h, m)| Some((h.parse::<u16>().ok()?, m.parse::<u16>().ok()?)))
+            .filter(|(_, m)| *m < 60)
+            .map(|(h, m)| h * 60 + m)
+            .filter(|t| *t <= max_min)
+            .ok_or_else(|| anyhow_expr!(expr, "Time must be in 24 hour HH:MM format"))
+    };
+    let mut entries = vec![];
+    for expr in exprs {
+        let mut subexprs = check_first_expr(expr.iter(), "defschedule")?;
+        while let Some(range_expr) = subexprs.next() {
+            let Some([start_expr, end_expr, weekday_exprs @ ..]) = range_expr.list(s.vars()) else {
+                bail_expr!(range_expr, "{ERR_MSG}");
+            };
+            let start_min = parse_time(start_expr, 23 * 60 + 59)?;
+            let end_min = parse_time(end_expr, 24 * 60)? % (24 * 60);
+            if start_min == end_min {
+                bail_expr!(end_expr, "End time must differ from the start time");
+            }
+            let mut weekdays = 0;
+            for weekday_expr in weekday_exprs {
+                let day = match weekday_expr.atom(s.vars()) {
+                    Some("mon") => 0,
+                    Some("tue") => 1,
+                    Some("wed") => 2,
+                    Some("thu") => 3,
+                    Some("fri") => 4,
+                    Some("sat") => 5,
+                    Some("sun") => 6,
+                    _ => bail_expr!(
+                        weekday_expr,
+                        "Unknown weekday, expected one of: mon tue wed thu fri sat sun"
+                    ),
+                };
+                weekdays |= 1 << day;
+            }
+            if weekday_exprs.is_empty() {
+                weekdays = 0b111_1111;
+            }
+            let Some(layer_expr) = subexprs.next() else {
+                bail_expr!(
+                    range_expr,
+                    "Time range has no layer - you must add a layer name."
+                );
+            };
+            let layer = layer_expr
+                .atom(s.vars())
+                .and_then(|l| s.layer_idxs.get(l))
+                .ok_or_else(|| anyhow_expr!(layer_expr, "Unknown layer name"))?;
+            entries.push(ScheduleEntry {
+                start_min,
+                end_min,
+                weekdays,
+                layer: *layer as u16,
+            });
+        }
+    }
+    Ok(entries)
+}
+
 fn parse_distance(expr: &SExpr, s: &ParserState, label: &str) -> Result<u16> {
     expr.atom(s.vars())
         .map(str::parse::<u16>)
@@ -3401,6 +4389,43 @@ fn parse_mwheel_accel(
     )))))
 }
 
+fn parse_mwheel_fine(
+    ac_params: &[SExpr],
+    direction: MWheelDirection,
+    s: &ParserState,
+) -> Result<&'static KanataAction> {
+    const ERR_MSG: &str = "mwheel-fine expects 2 or 4 parameters:\n\
+                           <interval (ms)> <delta>\n\
+                           <interval (ms)> <acceleration time (ms)> <min delta> <max delta>";
+    let (interval, accel_time, min_distance, max_distance) = match ac_params.len() {
+        2 => {
+            let interval = parse_non_zero_u16(&ac_params[0], s, "interval")?;
+            let delta = parse_distance(&ac_params[1], s, "delta")?;
+            (interval, 0, delta, delta)
+        }
+        4 => {
+            let interval = parse_non_zero_u16(&ac_params[0], s, "interval")?;
+            let accel_time = parse_non_zero_u16(&ac_params[1], s, "acceleration time")?;
+            let min_distance = parse_distance(&ac_params[2], s, "min delta")?;
+            let max_distance = parse_distance(&ac_params[3], s, "max delta")?;
+            if min_distance > max_distance {
+                bail!("min delta should be less than max delta")
+            }
+            (interval, accel_time, min_distance, max_distance)
+        }
+        n => bail!("{ERR_MSG}\nFound {n} parameters instead"),
+    };
+    Ok(s.a.sref(Action::Custom(s.a.sref(s.a.sref_slice(
+        CustomAction::MWheelFine {
+            direction,
+            interval,
+            accel_time,
+            min_distance,
+            max_distance,
+        },
+    )))))
+}
+
 fn parse_move_mouse(
     ac_params: &[SExpr],
     direction: MoveDirection,
@@ -3410,7 +4435,7 @@ fn parse_move_mouse(
     if ac_params.len() != 2 {
         bail!("{ERR_MSG}, found {}", ac_params.len());
     }
-    let interval = parse_non_zero_u16(&ac_params[0], s, "interval")?;
+    let interval = parse_non_zero_u16_or_var(&ac_params[0], s, "interval")?;
     let distance = parse_distance(&ac_params[1], s, "distance")?;
     Ok(s.a.sref(Action::Custom(s.a.sref(s.a.sref_slice(
         CustomAction::MoveMouse {
@@ -3432,7 +4457,7 @@ fn parse_move_mouse_accel(
             ac_params.len()
         );
     }
-    let interval = parse_non_zero_u16(&ac_params[0], s, "interval")?;
+    let interval = parse_non_zero_u16_or_var(&ac_params[0], s, "interval")?;
     let accel_time = parse_non_zero_u16(&ac_params[1], s, "acceleration time")?;
     let min_distance = parse_distance(&ac_params[2], s, "min distance")?;
     let max_distance = parse_distance(&ac_params[3], s, "max distance")?;
@@ -3450,16 +4475,51 @@ fn parse_move_mouse_accel(
     )))))
 }
 
+fn parse_move_mouse_polar(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
+    const ERR_MSG: &str =
+        "movemouse-polar expects 2 parameters: <angle (degrees clockwise from up)> <speed (px/s)>";
+    if ac_params.len() != 2 {
+        bail!("{ERR_MSG}, found {}", ac_params.len());
+    }
+    let angle = parse_u16(&ac_params[0], s, "angle")?;
+    if angle >= 360 {
+        bail_expr!(&ac_params[0], "angle must be 0-359");
+    }
+    let speed = parse_non_zero_u16(&ac_params[1], s, "speed")?;
+    Ok(s.a.sref(Action::Custom(s.a.sref(
+        s.a.sref_slice(CustomAction::MoveMousePolar { angle, speed }),
+    ))))
+}
+
 fn parse_move_mouse_speed(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
-    if ac_params.len() != 1 {
-        bail!(
-            "movemouse-speed expects one parameter, found {}\n<speed scaling % (1-65535)>",
-            ac_params.len()
-        );
+    const ERR_MSG: &str = "movemouse-speed expects 1 or 2 parameters: \
+        <speed scaling % (1-65535)> <?scale-mwheel>";
+    if !matches!(ac_params.len(), 1 | 2) {
+        bail!("{ERR_MSG}\nFound {} parameters instead", ac_params.len());
     }
     let speed = parse_non_zero_u16(&ac_params[0], s, "speed scaling %")?;
+    let scale_mwheel = match ac_params.get(1) {
+        None => false,
+        Some(expr) if expr.atom(s.vars()) == Some("scale-mwheel") => true,
+        Some(expr) => bail_expr!(expr, "{ERR_MSG}\nThe second parameter must be scale-mwheel"),
+    };
+    Ok(s.a.sref(Action::Custom(s.a.sref(s.a.sref_slice(
+        CustomAction::MoveMouseSpeed {
+            speed,
+            scale_mwheel,
+        },
+    )))))
+}
+
+fn parse_move_mouse_speed_reset(
+    ac_params: &[SExpr],
+    s: &ParserState,
+) -> Result<&'static KanataAction> {
+    if let Some(expr) = ac_params.first() {
+        bail_expr!(expr, "{MOVEMOUSE_SPEED_RESET} expects no parameters");
+    }
     Ok(s.a.sref(Action::Custom(
-        s.a.sref(s.a.sref_slice(CustomAction::MoveMouseSpeed { speed })),
+        s.a.sref(s.a.sref_slice(CustomAction::MoveMouseSpeedReset)),
     )))
 }
 
@@ -3477,6 +4537,42 @@ fn parse_set_mouse(ac_params: &[SExpr], s: &ParserState) -> Result<&'static Kana
     )))
 }
 
+fn parse_set_mouse_pct(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
+    const ERR_MSG: &str = "setmouse-pct expects two or three parameters: <x%> <y%> ?<monitor>";
+    if !(2..=3).contains(&ac_params.len()) {
+        bail!("{ERR_MSG}, found {}", ac_params.len());
+    }
+    let parse_pct = |expr: &SExpr| -> Result<u8> {
+        let pct = expr
+            .atom(s.vars())
+            .and_then(|a| a.parse::<i32>().ok())
+            .ok_or_else(|| anyhow_expr!(expr, "{ERR_MSG}\nPercentages must be numbers"))?;
+        Ok(pct.clamp(0, 100) as u8)
+    };
+    let x = parse_pct(&ac_params[0])?;
+    let y = parse_pct(&ac_params[1])?;
+    let monitor = match ac_params.get(2) {
+        None => MonitorSelector::Current,
+        Some(expr) => match expr.atom(s.vars()) {
+            Some("current") => MonitorSelector::Current,
+            Some("primary") => MonitorSelector::Primary,
+            Some(a) => MonitorSelector::Index(a.parse::<u8>().map_err(|_| {
+                anyhow_expr!(
+                    expr,
+                    "{ERR_MSG}\nThe monitor must be current, primary, or a monitor index"
+                )
+            })?),
+            None => bail_expr!(
+                expr,
+                "{ERR_MSG}\nThe monitor must be current, primary, or a monitor index"
+            ),
+        },
+    };
+    Ok(s.a.sref(Action::Custom(
+        s.a.sref(s.a.sref_slice(CustomAction::SetMousePct { x, y, monitor })),
+    )))
+}
+
 fn parse_dynamic_macro_record(
     ac_params: &[SExpr],
     s: &ParserState,
@@ -3720,7 +4816,11 @@ fn parse_layers(
         }
 
         // Set fake keys on every layer.
-        for (y, action) in s.virtual_keys.values() {
+        for (y, action) in s
+            .virtual_keys
+            .values()
+            .chain(s.hidden_fake_keys.borrow().iter())
+        {
             let (x, y) = get_fake_key_coords(*y);
             layers_cfg[layer_level][x as usize][y as usize] = **action;
         }
@@ -3743,14 +4843,85 @@ fn parse_layers(
     Ok(layers_cfg)
 }
 
+/// Warns about defsrc keys that every layer leaves transparent.
+/// This can be intentional but is often caused by a layer item in the wrong position.
+/// Layers from `deflayermap` leave unlisted keys transparent by design,
+/// so the check is only done when every layer is a `deflayer`.
+fn warn_keys_transparent_in_every_layer(
+    defsrc: &[SExpr],
+    layers: &IntermediateLayers,
+    s: &ParserState,
+) {
+    if s.layer_exprs
+        .iter()
+        .any(|layer| matches!(layer, LayerExprs::CustomMapping(_)))
+    {
+        return;
+    }
+    let transparent_keys = defsrc
+        .iter()
+        .skip(1)
+        .zip(s.mapping_order.iter())
+        .filter(|&(_, &osc)| layers.iter().all(|layer| layer[0][osc] == Action::Trans))
+        .map(|(key_expr, _)| {
+            format!(
+                "{} (line {})",
+                key_expr.atom(None).unwrap_or_default(),
+                key_expr.span().start.line + 1
+            )
+        })
+        .collect::<Vec<_>>();
+    if !transparent_keys.is_empty() {
+        log::warn!(
+            "{}: defsrc keys that are transparent in every layer: {}",
+            defsrc[0].span().file_name(),
+            transparent_keys.join(", ")
+        );
+    }
+}
+
 const SEQ_ERR: &str = "defseq expects pairs of parameters: <virtual_key_name> <key_list>";
 
+/// A `defseq` item waiting to be inserted into the sequence trie.
+struct SequenceEntry<'a> {
+    key_seq_expr: &'a SExpr,
+    coord: (u8, u16),
+    /// Number of `_` steps, then number of `(any ...)` steps.
+    /// Entries are inserted from least to most general.
+    generality: (usize, usize),
+    /// Every key list the entry matches, with the keys matched by its wildcard steps.
+    key_lists: Vec<(Vec<u16>, Vec<u16>)>,
+    /// Overrides the sequence timeout while this entry can still be completed.
+    timeout: Option<u16>,
+}
+
 fn parse_sequences(exprs: &[&Vec<SExpr>], s: &ParserState) -> Result<KeySeqsToFKeys> {
-    let mut sequences = Trie::new();
+    let mut entries: Vec<SequenceEntry> = vec![];
     for expr in exprs {
         let mut subexprs = check_first_expr(expr.iter(), "defseq")?.peekable();
+        let first_entry = entries.len();
+        let mut group_timeout = None;
+        let mut entry_timeout_set = false;
 
         while let Some(vkey_expr) = subexprs.next() {
+            if let Some(timeout) = parse_sequence_timeout(vkey_expr, s)? {
+                // At the start of defseq, the timeout applies to every entry in it.
+                // Otherwise it applies to the entry right before it.
+                if entries.len() == first_entry {
+                    if group_timeout.is_some() {
+                        bail_expr!(vkey_expr, "{SEQ_ERR}\nThe defseq timeout is already set");
+                    }
+                    group_timeout = Some(timeout);
+                } else {
+                    if entry_timeout_set {
+                        bail_expr!(vkey_expr, "{SEQ_ERR}\nThe sequence timeout is already set");
+                    }
+                    entries.last_mut().expect("entry exists").timeout = Some(timeout);
+                    entry_timeout_set = true;
+                }
+                continue;
+            }
+            entry_timeout_set = false;
             let vkey = vkey_expr.atom(s.vars()).ok_or_else(|| {
                 anyhow_expr!(vkey_expr, "{SEQ_ERR}\nvirtual_key_name must not be a list")
             })?;
@@ -3776,83 +4947,258 @@ fn parse_sequences(exprs: &[&Vec<SExpr>], s: &ParserState) -> Result<KeySeqsToFK
                 bail_expr!(key_seq_expr, "{SEQ_ERR}\nkey_list cannot be empty");
             }
 
-            let keycode_seq = parse_sequence_keys(key_seq, s)?;
-
-            // Generate permutations of sequences for overlapping keys.
-            let mut permutations = vec![vec![]];
-            let mut vals = keycode_seq.iter().copied();
-            while let Some(val) = vals.next() {
-                if val & KEY_OVERLAP_MARKER == 0 {
-                    for p in permutations.iter_mut() {
-                        p.push(val);
+            let (keycode_seqs, generality) = parse_sequence_steps(key_seq, s)?;
+
+            let mut key_lists = vec![];
+            for (keycode_seq, matched) in keycode_seqs {
+                // Generate permutations of sequences for overlapping keys.
+                let mut permutations = vec![vec![]];
+                let mut vals = keycode_seq.iter().copied();
+                while let Some(val) = vals.next() {
+                    if val & KEY_OVERLAP_MARKER == 0 {
+                        for p in permutations.iter_mut() {
+                            p.push(val);
+                        }
+                        continue;
                     }
-                    continue;
-                }
 
-                if val == 0x0400 {
-                    bail_expr!(
-                        key_seq_expr,
-                        "O-(...) lists must have a minimum of 2 elements"
-                    );
-                }
-                let mut values_to_permute = vec![val];
-                for val in vals.by_ref() {
                     if val == 0x0400 {
-                        break;
+                        bail_expr!(
+                            key_seq_expr,
+                            "O-(...) lists must have a minimum of 2 elements"
+                        );
+                    }
+                    let mut values_to_permute = vec![val];
+                    for val in vals.by_ref() {
+                        if val == 0x0400 {
+                            break;
+                        }
+                        values_to_permute.push(val);
                     }
-                    values_to_permute.push(val);
-                }
 
-                let ps = match values_to_permute.len() {
-                    0 | 1 => bail_expr!(
-                        key_seq_expr,
-                        "O-(...) lists must have a minimum of 2 elements"
-                    ),
-                    2..=6 => gen_permutations(&values_to_permute[..]),
-                    _ => bail_expr!(
-                        key_seq_expr,
-                        "O-(...) lists must have a maximum of 6 elements"
-                    ),
-                };
+                    let ps = match values_to_permute.len() {
+                        0 | 1 => bail_expr!(
+                            key_seq_expr,
+                            "O-(...) lists must have a minimum of 2 elements"
+                        ),
+                        2..=6 => gen_permutations(&values_to_permute[..]),
+                        _ => bail_expr!(
+                            key_seq_expr,
+                            "O-(...) lists must have a maximum of 6 elements"
+                        ),
+                    };
 
-                let mut new_permutations: Vec<Vec<u16>> = vec![];
-                for p in permutations.iter() {
-                    for p2 in ps.iter() {
-                        new_permutations.push(
-                            p.iter()
-                                .copied()
-                                .chain(p2.iter().copied().chain([KEY_OVERLAP_MARKER]))
-                                .collect(),
-                        );
+                    let mut new_permutations: Vec<Vec<u16>> = vec![];
+                    for p in permutations.iter() {
+                        for p2 in ps.iter() {
+                            new_permutations.push(
+                                p.iter()
+                                    .copied()
+                                    .chain(p2.iter().copied().chain([KEY_OVERLAP_MARKER]))
+                                    .collect(),
+                            );
+                        }
                     }
+                    permutations = new_permutations;
                 }
-                permutations = new_permutations;
+                key_lists.extend(permutations.into_iter().map(|p| (p, matched.clone())));
             }
 
-            for p in permutations.into_iter() {
-                if sequences.ancestor_exists(&p) {
-                    bail_expr!(
-                        key_seq_expr,
-                        "Sequence has a conflict: its sequence contains an earlier defined sequence"
-                    );
+            entries.push(SequenceEntry {
+                key_seq_expr,
+                coord: s
+                    .virtual_keys
+                    .get(vkey)
+                    .map(|(y, _)| get_fake_key_coords(*y))
+                    .expect("vk exists, checked earlier"),
+                generality,
+                key_lists,
+                timeout: group_timeout,
+            });
+        }
+    }
+
+    // Stable sort: exact sequences keep their definition order and are inserted before any
+    // sequence with wildcard steps, so an exact match always wins an overlap.
+    entries.sort_by_key(|entry| entry.generality);
+    let mut sequences = Trie::new();
+    let mut inserted_timeouts = vec![];
+    for entry in entries {
+        let is_exact = entry.generality == (0, 0);
+        let mut inserted_any = false;
+        for (p, matched) in entry.key_lists {
+            let conflict = if sequences.ancestor_exists(&p) {
+                "Sequence has a conflict: its sequence contains an earlier defined sequence"
+            } else if sequences.descendant_exists(&p) {
+                "Sequence has a conflict: its sequence is contained within an earlier defined seqence"
+            } else {
+                sequences.insert(
+                    &p,
+                    (
+                        entry.coord.0,
+                        entry.coord.1,
+                        s.a.sref_vec(matched),
+                        entry.timeout,
+                    ),
+                );
+                inserted_timeouts.push((p, entry.timeout));
+                inserted_any = true;
+                continue;
+            };
+            if is_exact {
+                bail_expr!(entry.key_seq_expr, "{conflict}");
+            }
+            // A more specific sequence already covers this key list.
+        }
+        if !inserted_any {
+            bail_expr!(
+                entry.key_seq_expr,
+                "Sequence has a conflict: every key list it matches is covered by a more specific or earlier defined sequence"
+            );
+        }
+    }
+
+    let mut prefix_timeouts = Trie::new();
+    if inserted_timeouts
+        .iter()
+        .any(|(_, timeout)| timeout.is_some())
+    {
+        let mut by_prefix: HashMap<&[u16], PrefixTimeouts> = HashMap::default();
+        for (p, timeout) in inserted_timeouts.iter() {
+            for len in 0..=p.len() {
+                let timeouts = by_prefix.entry(&p[..len]).or_default();
+                match timeout {
+                    Some(timeout) => timeouts.max = timeouts.max.max(*timeout),
+                    None => timeouts.has_default = true,
                 }
-                if sequences.descendant_exists(&p) {
+            }
+        }
+        for (prefix, timeouts) in by_prefix {
+            prefix_timeouts.insert(prefix, timeouts);
+        }
+    }
+    Ok(KeySeqsToFKeys {
+        trie: sequences,
+        prefix_timeouts,
+    })
+}
+
+/// Parses `(timeout <ms>)`, which can appear in `defseq` where a virtual key name is expected.
+/// Returns `None` for anything else.
+fn parse_sequence_timeout(expr: &SExpr, s: &ParserState) -> Result<Option<u16>> {
+    let Some(list) = expr.list(s.vars()) else {
+        return Ok(None);
+    };
+    if list.first().and_then(|e| e.atom(s.vars())) != Some("timeout") {
+        return Ok(None);
+    }
+    if list.len() != 2 {
+        bail_expr!(
+            expr,
+            "{SEQ_ERR}\ntimeout expects 1 parameter: <milliseconds>"
+        );
+    }
+    parse_non_zero_u16(&list[1], s, "sequence timeout").map(Some)
+}
+
+/// Upper bound on the key lists that `_` and `(any ...)` steps of a single sequence expand into.
+const MAX_SEQ_WILDCARD_EXPANSIONS: usize = 10_000;
+
+fn is_sequence_wildcard_step(expr: &SExpr, s: &ParserState) -> bool {
+    expr.atom(s.vars()) == Some("_")
+        || expr
+            .list(s.vars())
+            .and_then(|l| l.first())
+            .and_then(|first| first.atom(s.vars()))
+            == Some("any")
+}
+
+/// Expands the `_` and `(any ...)` steps of a sequence key list into every concrete key list
+/// it matches. Each key list is paired with the keys its wildcard steps matched. Also returns
+/// the number of `_` and `(any ...)` steps.
+#[allow(clippy::type_complexity)] // return type is not pub
+fn parse_sequence_steps(
+    exprs: &[SExpr],
+    s: &ParserState,
+) -> Result<(Vec<(Vec<u16>, Vec<u16>)>, (usize, usize))> {
+    let mut expansions: Vec<(Vec<u16>, Vec<u16>)> = vec![(vec![], vec![])];
+    let mut wildcard_count = 0;
+    let mut class_count = 0;
+    let mut exprs_remaining = exprs;
+    while !exprs_remaining.is_empty() {
+        let fixed_len = exprs_remaining
+            .iter()
+            .position(|e| is_sequence_wildcard_step(e, s))
+            .unwrap_or(exprs_remaining.len());
+        if fixed_len > 0 {
+            let keys = parse_sequence_keys(&exprs_remaining[..fixed_len], s)?;
+            for (seq, _) in expansions.iter_mut() {
+                seq.extend(keys.iter().copied());
+            }
+            exprs_remaining = &exprs_remaining[fixed_len..];
+            continue;
+        }
+
+        let step_expr = &exprs_remaining[0];
+        exprs_remaining = &exprs_remaining[1..];
+        let alternatives: Vec<Vec<u16>> = match step_expr.list(s.vars()) {
+            None => {
+                wildcard_count += 1;
+                // Right shift is pushed into sequences as left shift, so this covers both.
+                let lsft = u16::from(OsCode::KEY_LEFTSHIFT) | 0x8000;
+                SEQ_WILDCARD_KEYS
+                    .iter()
+                    .flat_map(|osc| {
+                        let k = u16::from(*osc);
+                        [vec![k], vec![lsft, k | 0x8000]]
+                    })
+                    .collect()
+            }
+            Some(class) => {
+                class_count += 1;
+                if class.len() < 2 {
                     bail_expr!(
-                        key_seq_expr,
-                        "Sequence has a conflict: its sequence is contained within an earlier defined seqence"
+                        step_expr,
+                        "{SEQ_ERR}\n(any ...) must contain at least one key"
                     );
                 }
-                sequences.insert(
-                    p,
-                    s.virtual_keys
-                        .get(vkey)
-                        .map(|(y, _)| get_fake_key_coords(*y))
-                        .expect("vk exists, checked earlier"),
-                );
+                class[1..]
+                    .iter()
+                    .map(|item| {
+                        let keys = parse_sequence_keys(std::slice::from_ref(item), s)?;
+                        if keys.iter().any(|k| k & KEY_OVERLAP_MARKER != 0) {
+                            bail_expr!(item, "{SEQ_ERR}\nO-(...) cannot be used within (any ...)");
+                        }
+                        Ok(keys)
+                    })
+                    .collect::<Result<_>>()?
             }
+        };
+        if expansions.len() * alternatives.len() > MAX_SEQ_WILDCARD_EXPANSIONS {
+            bail_expr!(
+                step_expr,
+                "{SEQ_ERR}\nThis sequence matches too many key lists; \
+                use (any ...) or fewer _ steps"
+            );
         }
+        expansions = expansions
+            .iter()
+            .flat_map(|(seq, matched)| {
+                alternatives.iter().map(move |alt| {
+                    let mut seq = seq.clone();
+                    seq.extend(alt.iter().copied());
+                    let mut matched = matched.clone();
+                    matched.extend(
+                        alt.iter()
+                            .copied()
+                            .filter(|k| !OsCode::from(k & MASK_KEYCODES).is_modifier()),
+                    );
+                    (seq, matched)
+                })
+            })
+            .collect();
     }
-    Ok(sequences)
+    Ok((expansions, (wildcard_count, class_count)))
 }
 
 fn parse_sequence_keys(exprs: &[SExpr], s: &ParserState) -> Result<Vec<u16>> {
@@ -3968,32 +5314,171 @@ fn parse_arbitrary_code(ac_params: &[SExpr], s: &ParserState) -> Result<&'static
     )))
 }
 
-fn parse_overrides(exprs: &[SExpr], s: &ParserState) -> Result<Overrides> {
+/// HID usage pages forwarded by the macOS DriverKit virtual keyboard, with the largest usage id
+/// it accepts for each.
+const HID_ACTION_PAGES: &[(u16, u16)] = &[
+    // keyboard/keypad
+    (0x07, 0xFF),
+    // consumer
+    (0x0C, 0xFFFF),
+    // Apple vendor top case
+    (0xFF, 0xFFFF),
+    // Apple vendor keyboard
+    (0xFF01, 0xFFFF),
+];
+
+fn parse_hid(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
+    const ERR_MSG: &str = "hid expects two parameters: <usage-page> <usage-id>";
+    let [page_expr, id_expr] = ac_params else {
+        bail!("{ERR_MSG}\nFound {} params instead", ac_params.len());
+    };
+    let parse_num = |expr: &SExpr, label: &str| -> Result<u16> {
+        expr.atom(s.vars())
+            .and_then(parse_dec_or_hex)
+            .and_then(|n| u16::try_from(n).ok())
+            .ok_or_else(|| {
+                anyhow_expr!(
+                    expr,
+                    "{label} must be a number from 0 to 0xFFFF, decimal or 0x-prefixed hex"
+                )
+            })
+    };
+    let page = parse_num(page_expr, "usage-page")?;
+    let id = parse_num(id_expr, "usage-id")?;
+    let Some((_, max_id)) = HID_ACTION_PAGES.iter().find(|(p, _)| *p == page) else {
+        bail_expr!(
+            page_expr,
+            "usage-page {page:#x} is not supported, expected one of: {}",
+            HID_ACTION_PAGES
+                .iter()
+                .map(|(p, _)| format!("{p:#x}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    };
+    if id == 0 || id > *max_id {
+        bail_expr!(
+            id_expr,
+            "usage-id for page {page:#x} must be from 0x1 to {max_id:#x}, got {id:#x}"
+        );
+    }
+    Ok(s.a.sref(Action::Custom(
+        s.a.sref(s.a.sref_slice(CustomAction::Hid { page, id })),
+    )))
+}
+
+fn parse_runtime_var_name(expr: &SExpr, s: &ParserState) -> Result<u16> {
+    let name = expr
+        .atom(s.vars())
+        .ok_or_else(|| anyhow_expr!(expr, "variable name must be a string, not a list"))?;
+    Ok(s.runtime_var_idx(name))
+}
+
+fn parse_i32(expr: &SExpr, s: &ParserState, label: &str) -> Result<i32> {
+    expr.atom(s.vars())
+        .and_then(|a| a.parse::<i32>().ok())
+        .ok_or_else(|| anyhow_expr!(expr, "{label} must be an integer"))
+}
+
+fn parse_setvar(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
+    const ERR_MSG: &str = "setvar expects 2 params: <variable-name> <value>";
+    let [name, value] = ac_params else {
+        bail!("{ERR_MSG}\nFound {} params instead", ac_params.len());
+    };
+    let var = parse_runtime_var_name(name, s)?;
+    let value = parse_i32(value, s, "value")?;
+    Ok(s.a.sref(Action::Custom(
+        s.a.sref(s.a.sref_slice(CustomAction::SetVar { var, value })),
+    )))
+}
+
+fn parse_incvar(
+    ac_params: &[SExpr],
+    s: &ParserState,
+    decrement: bool,
+) -> Result<&'static KanataAction> {
+    let ac_name = if decrement { DECVAR } else { INCVAR };
+    if !matches!(ac_params.len(), 1 | 2) {
+        bail!(
+            "{ac_name} expects 1 or 2 params: <variable-name> <?amount>\nFound {} params instead",
+            ac_params.len()
+        );
+    }
+    let var = parse_runtime_var_name(&ac_params[0], s)?;
+    let amount = match ac_params.get(1) {
+        Some(amount) => parse_i32(amount, s, "amount")?,
+        None => 1,
+    };
+    let amount = if decrement {
+        amount.saturating_neg()
+    } else {
+        amount
+    };
+    Ok(s.a.sref(Action::Custom(
+        s.a.sref(s.a.sref_slice(CustomAction::IncVar { var, amount })),
+    )))
+}
+
+fn parse_overrides(exprs: &[SExpr], s: &mut ParserState) -> Result<Overrides> {
     const ERR_MSG: &str =
-        "defoverrides expects pairs of parameters: <input key list> <output key list>";
+        "defoverrides expects pairs of parameters: <input key list> <output key list or action>";
     let mut subexprs = check_first_expr(exprs.iter(), "defoverrides")?;
 
     let mut overrides = Vec::<Override>::new();
     while let Some(in_keys_expr) = subexprs.next() {
-        let out_keys_expr = subexprs
+        let out_expr = subexprs
             .next()
             .ok_or_else(|| anyhow_expr!(in_keys_expr, "Missing output keys for input keys"))?;
-        let (in_keys, out_keys) = parse_override_inout_keys(in_keys_expr, out_keys_expr, s)?;
-        overrides
-            .push(Override::try_new(&in_keys, &out_keys).map_err(|e| anyhow!("{ERR_MSG}: {e}"))?);
+        overrides.push(parse_override(in_keys_expr, out_expr, s, ERR_MSG)?);
     }
     log::debug!("All overrides:\n{overrides:#?}");
     Ok(Overrides::new(&overrides))
 }
 
-fn parse_override_inout_keys(
+/// Parses an override. A list starting with a key name as the output is a key chord that replaces
+/// the input keys. Any other output is an action, which is placed in the fake key row and pressed
+/// while the override matches.
+fn parse_override(
     in_keys_expr: &SExpr,
-    out_keys_expr: &SExpr,
-    s: &ParserState,
-) -> Result<(Vec<OsCode>, Vec<OsCode>)> {
-    let out_keys = out_keys_expr
+    out_expr: &SExpr,
+    s: &mut ParserState,
+    err_msg: &str,
+) -> Result<Override> {
+    let in_keys = parse_override_in_keys(in_keys_expr, s)?;
+    let out_keys = out_expr
         .list(s.vars())
-        .ok_or_else(|| anyhow_expr!(out_keys_expr, "Output keys must be a list"))?;
+        .filter(|out_keys| {
+            out_keys
+                .first()
+                .is_none_or(|key_expr| key_expr.atom(s.vars()).and_then(str_to_oscode).is_some())
+        })
+        .map(|out_keys| {
+            out_keys
+                .iter()
+                .try_fold(vec![], |mut keys, key_expr| -> Result<Vec<OsCode>> {
+                    let key = key_expr
+                        .atom(s.vars())
+                        .and_then(str_to_oscode)
+                        .ok_or_else(|| {
+                            anyhow_expr!(key_expr, "Unknown output key name, must use known keys")
+                        })?;
+                    keys.push(key);
+                    Ok(keys)
+                })
+        })
+        .transpose()?;
+    let ovd = match out_keys {
+        Some(out_keys) => Override::try_new(&in_keys, &out_keys),
+        None => {
+            let action = parse_action(out_expr, s)?;
+            let y = add_hidden_fake_key(out_expr, action, s)?;
+            Override::try_new_action(&in_keys, y)
+        }
+    };
+    ovd.map_err(|e| anyhow_expr!(in_keys_expr, "{err_msg}: {e}"))
+}
+
+fn parse_override_in_keys(in_keys_expr: &SExpr, s: &ParserState) -> Result<Vec<OsCode>> {
     let in_keys = in_keys_expr
         .list(s.vars())
         .ok_or_else(|| anyhow_expr!(in_keys_expr, "Input keys must be a list"))?;
@@ -4009,32 +5494,19 @@ fn parse_override_inout_keys(
             keys.push(key);
             Ok(keys)
         })?;
-    let out_keys =
-        out_keys
-            .iter()
-            .try_fold(vec![], |mut keys, key_expr| -> Result<Vec<OsCode>> {
-                let key = key_expr
-                    .atom(s.vars())
-                    .and_then(str_to_oscode)
-                    .ok_or_else(|| {
-                        anyhow_expr!(key_expr, "Unknown output key name, must use known keys")
-                    })?;
-                keys.push(key);
-                Ok(keys)
-            })?;
-    Ok((in_keys, out_keys))
+    Ok(in_keys)
 }
 
-fn parse_overridesv2(exprs: &[SExpr], s: &ParserState) -> Result<Overrides> {
-    const ERR_MSG: &str = "defoverridesv2 expects 4-tuples of parameters: <input key list> <output key list> <without mods> <excluded layers>";
+fn parse_overridesv2(exprs: &[SExpr], s: &mut ParserState) -> Result<Overrides> {
+    const ERR_MSG: &str = "defoverridesv2 expects 4-tuples of parameters: <input key list> <output key list or action> <without mods> <excluded layers>";
     let mut subexprs = check_first_expr(exprs.iter(), "defoverridesv2")?;
 
     let mut overrides = Vec::<Override>::new();
     while let Some(in_keys_expr) = subexprs.next() {
-        let out_keys_expr = subexprs
+        let out_expr = subexprs
             .next()
             .ok_or_else(|| anyhow_expr!(in_keys_expr, "Missing output keys for input keys"))?;
-        let (in_keys, out_keys) = parse_override_inout_keys(in_keys_expr, out_keys_expr, s)?;
+        let ovd = parse_override(in_keys_expr, out_expr, s, ERR_MSG)?;
 
         let without_mods_expr = subexprs
             .next()
@@ -4086,15 +5558,7 @@ fn parse_overridesv2(exprs: &[SExpr], s: &ParserState) -> Result<Overrides> {
             },
         )?;
 
-        overrides.push(
-            Override::try_new_v2(
-                &in_keys,
-                &out_keys,
-                without_mods.into(),
-                excluded_layers.into(),
-            )
-            .map_err(|e| anyhow!("{ERR_MSG}: {e}"))?,
-        );
+        overrides.push(ovd.with_exclusions(without_mods.into(), excluded_layers.into()));
     }
     log::debug!("All overrides:\n{overrides:#?}");
     Ok(Overrides::new(&overrides))
@@ -4121,76 +5585,136 @@ fn parse_fork(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAct
     }))))
 }
 
+/// Parses `fork-multi` into nested forks: each branch is the right action of a fork whose left
+/// action is the remaining branches.
+fn parse_fork_multi(ac_params: &[SExpr], s: &ParserState) -> Result<&'static KanataAction> {
+    const ERR_STR: &str = "fork-multi expects pairs of params followed by a default action: \
+        <trigger-keys> <action> ... <default-action>";
+    if ac_params.len() < 3 || ac_params.len().is_multiple_of(2) {
+        bail!("{ERR_STR}\nFound {} params", ac_params.len());
+    }
+    let (default, branch_exprs) = ac_params.split_last().expect("checked length");
+    let mut branches: Vec<(Vec<KeyCode>, KanataAction)> = vec![];
+    for (i, pair) in branch_exprs.chunks_exact(2).enumerate() {
+        let triggers = parse_key_list(&pair[0], s, "trigger-keys")?
+            .into_iter()
+            .map(KeyCode::from)
+            .collect::<Vec<_>>();
+        for (j, (prev_triggers, _)) in branches.iter().enumerate() {
+            if triggers.iter().any(|k| prev_triggers.contains(k)) {
+                log::warn!(
+                    "fork-multi trigger keys of branch {} overlap with branch {}; \
+                    the earlier branch is used when both match",
+                    i + 1,
+                    j + 1,
+                );
+            }
+        }
+        branches.push((triggers, *parse_action(&pair[1], s)?));
+    }
+    let mut action = *parse_action(default, s)?;
+    for (triggers, right) in branches.into_iter().rev() {
+        action = Action::Fork(s.a.sref(ForkConfig {
+            left: action,
+            right,
+            right_triggers: s.a.sref_vec(triggers),
+        }));
+    }
+    Ok(s.a.sref(action))
+}
+
+const CAPS_WORD_CAPITALIZED_KEYS: &[KeyCode] = &[
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Minus,
+];
+
+const CAPS_WORD_NONTERMINAL_KEYS: &[KeyCode] = &[
+    KeyCode::Kb0,
+    KeyCode::Kb1,
+    KeyCode::Kb2,
+    KeyCode::Kb3,
+    KeyCode::Kb4,
+    KeyCode::Kb5,
+    KeyCode::Kb6,
+    KeyCode::Kb7,
+    KeyCode::Kb8,
+    KeyCode::Kb9,
+    KeyCode::Kp0,
+    KeyCode::Kp1,
+    KeyCode::Kp2,
+    KeyCode::Kp3,
+    KeyCode::Kp4,
+    KeyCode::Kp5,
+    KeyCode::Kp6,
+    KeyCode::Kp7,
+    KeyCode::Kp8,
+    KeyCode::Kp9,
+    KeyCode::BSpace,
+    KeyCode::Delete,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+];
+
 fn parse_caps_word(
     ac_params: &[SExpr],
     repress_behaviour: CapsWordRepressBehaviour,
     s: &ParserState,
 ) -> Result<&'static KanataAction> {
-    const ERR_STR: &str = "caps-word expects 1 param: <timeout>";
-    if ac_params.len() != 1 {
-        bail!("{ERR_STR}\nFound {} params instead of 1", ac_params.len());
+    const ERR_STR: &str = "caps-word expects 1 or 2 params: <timeout> <?extra-non-terminal-keys>";
+    if !matches!(ac_params.len(), 1 | 2) {
+        bail!(
+            "{ERR_STR}\nFound {} params instead of 1 or 2",
+            ac_params.len()
+        );
     }
     let timeout = parse_non_zero_u16(&ac_params[0], s, "timeout")?;
+    let keys_nonterminal = match ac_params.get(1) {
+        Some(extra) => s.a.sref_vec(
+            CAPS_WORD_NONTERMINAL_KEYS
+                .iter()
+                .copied()
+                .chain(
+                    parse_key_list(extra, s, "extra-non-terminal-keys")?
+                        .into_iter()
+                        .map(KeyCode::from),
+                )
+                .collect(),
+        ),
+        None => CAPS_WORD_NONTERMINAL_KEYS,
+    };
     Ok(s.a.sref(Action::Custom(s.a.sref(s.a.sref_slice(
         CustomAction::CapsWord(CapsWordCfg {
             repress_behaviour,
-            keys_to_capitalize: &[
-                KeyCode::A,
-                KeyCode::B,
-                KeyCode::C,
-                KeyCode::D,
-                KeyCode::E,
-                KeyCode::F,
-                KeyCode::G,
-                KeyCode::H,
-                KeyCode::I,
-                KeyCode::J,
-                KeyCode::K,
-                KeyCode::L,
-                KeyCode::M,
-                KeyCode::N,
-                KeyCode::O,
-                KeyCode::P,
-                KeyCode::Q,
-                KeyCode::R,
-                KeyCode::S,
-                KeyCode::T,
-                KeyCode::U,
-                KeyCode::V,
-                KeyCode::W,
-                KeyCode::X,
-                KeyCode::Y,
-                KeyCode::Z,
-                KeyCode::Minus,
-            ],
-            keys_nonterminal: &[
-                KeyCode::Kb0,
-                KeyCode::Kb1,
-                KeyCode::Kb2,
-                KeyCode::Kb3,
-                KeyCode::Kb4,
-                KeyCode::Kb5,
-                KeyCode::Kb6,
-                KeyCode::Kb7,
-                KeyCode::Kb8,
-                KeyCode::Kb9,
-                KeyCode::Kp0,
-                KeyCode::Kp1,
-                KeyCode::Kp2,
-                KeyCode::Kp3,
-                KeyCode::Kp4,
-                KeyCode::Kp5,
-                KeyCode::Kp6,
-                KeyCode::Kp7,
-                KeyCode::Kp8,
-                KeyCode::Kp9,
-                KeyCode::BSpace,
-                KeyCode::Delete,
-                KeyCode::Up,
-                KeyCode::Down,
-                KeyCode::Left,
-                KeyCode::Right,
-            ],
+            keys_to_capitalize: CAPS_WORD_CAPITALIZED_KEYS,
+            keys_nonterminal,
+            keys_rewrite: &[],
             timeout,
         }),
     )))))
@@ -4201,11 +5725,18 @@ fn parse_caps_word_custom(
     repress_behaviour: CapsWordRepressBehaviour,
     s: &ParserState,
 ) -> Result<&'static KanataAction> {
-    const ERR_STR: &str = "caps-word-custom expects 3 param: <timeout> <keys-to-capitalize> <extra-non-terminal-keys>";
-    if ac_params.len() != 3 {
-        bail!("{ERR_STR}\nFound {} params instead of 3", ac_params.len());
+    const ERR_STR: &str = "caps-word-custom expects 3 or 4 params: <timeout> <keys-to-capitalize> <extra-non-terminal-keys> <?rewrites>";
+    if !matches!(ac_params.len(), 3 | 4) {
+        bail!(
+            "{ERR_STR}\nFound {} params instead of 3 or 4",
+            ac_params.len()
+        );
     }
     let timeout = parse_non_zero_u16(&ac_params[0], s, "timeout")?;
+    let keys_rewrite = match ac_params.get(3) {
+        Some(rewrites) => parse_caps_word_rewrites(rewrites, s)?,
+        None => vec![],
+    };
     Ok(s.a.sref(Action::Custom(
         s.a.sref(
             s.a.sref_slice(CustomAction::CapsWord(CapsWordCfg {
@@ -4222,12 +5753,33 @@ fn parse_caps_word_custom(
                         .map(KeyCode::from)
                         .collect(),
                 ),
+                keys_rewrite: s.a.sref_vec(keys_rewrite),
                 timeout,
             })),
         ),
     )))
 }
 
+/// Parses a list of `(<from-key> <to-key>)` pairs, e.g. `((spc min))`.
+fn parse_caps_word_rewrites(expr: &SExpr, s: &ParserState) -> Result<Vec<(KeyCode, KeyCode)>> {
+    const ERR_MSG: &str = "rewrites must be a list of (<from-key> <to-key>) pairs";
+    let Some(pairs) = expr.list(s.vars()) else {
+        bail_expr!(expr, "{ERR_MSG}");
+    };
+    let mut rewrites: Vec<(KeyCode, KeyCode)> = vec![];
+    for pair in pairs {
+        let (from, to) = match parse_key_list(pair, s, "rewrite")?[..] {
+            [from, to] => (KeyCode::from(from), KeyCode::from(to)),
+            _ => bail_expr!(pair, "{ERR_MSG}"),
+        };
+        if rewrites.iter().any(|(f, _)| *f == from) {
+            bail_expr!(pair, "This key is already rewritten");
+        }
+        rewrites.push((from, to));
+    }
+    Ok(rewrites)
+}
+
 fn parse_macro_record_stop_truncate(
     ac_params: &[SExpr],
     s: &ParserState,
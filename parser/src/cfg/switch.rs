@@ -9,6 +9,7 @@ pub fn parse_switch(ac_params: &[SExpr], s: &ParserState) -> Result<&'static Kan
     let mut cases = vec![];
 
     let mut params = ac_params.iter();
+    #[allow(clippy::while_let_loop)]
     loop {
         let Some(key_match) = params.next() else {
             break;
@@ -91,6 +92,11 @@ pub fn parse_switch_case_bool(
             InputHistory,
             Layer,
             BaseLayer,
+            LayerActive,
+            VarEq,
+            VarGt,
+            VarLt,
+            VkeyActive,
         }
         #[derive(Copy, Clone)]
         enum InputType {
@@ -116,7 +122,12 @@ pub fn parse_switch_case_bool(
                 "input" => Some(AllowedListOps::Input),
                 "input-history" => Some(AllowedListOps::InputHistory),
                 "layer" => Some(AllowedListOps::Layer),
-                "base-layer" => Some(AllowedListOps::BaseLayer),
+                "base-layer" | "base-layer-is" => Some(AllowedListOps::BaseLayer),
+                "layer-active" => Some(AllowedListOps::LayerActive),
+                "var-eq" => Some(AllowedListOps::VarEq),
+                "var-gt" => Some(AllowedListOps::VarGt),
+                "var-lt" => Some(AllowedListOps::VarLt),
+                "vkey-active" => Some(AllowedListOps::VkeyActive),
                 _ => None,
             })
             .ok_or_else(|| {
@@ -124,7 +135,8 @@ pub fn parse_switch_case_bool(
                     op_expr,
                     "lists inside switch logic must begin with one of:\n\
                     or | and | not | key-history | key-timing\n\
-                    | input | input-history | layer | base-layer",
+                    | input | input-history | layer | base-layer | layer-active\n\
+                    | var-eq | var-gt | var-lt | vkey-active",
                 )
             })?;
 
@@ -176,6 +188,16 @@ pub fn parse_switch_case_bool(
                 ops.extend(&[op1, op2]);
                 Ok(())
             }
+            AllowedListOps::VkeyActive => {
+                if l.len() != 2 {
+                    bail_expr!(op_expr, "vkey-active must have 1 parameter: vkey-name");
+                }
+                // Same check as (input virtual $name).
+                let vkey = parse_vkey_coord(&l[1], s)?.y;
+                let (op1, op2) = OpCode::new_active_input((InputType::Virtual.to_row(), vkey));
+                ops.extend(&[op1, op2]);
+                Ok(())
+            }
             AllowedListOps::InputHistory => {
                 if l.len() != 4 {
                     bail_expr!(
@@ -242,7 +264,7 @@ pub fn parse_switch_case_bool(
                     .set(std::cmp::max(s.switch_max_key_timing.get(), ticks_since));
                 Ok(())
             }
-            AllowedListOps::Layer | AllowedListOps::BaseLayer => {
+            AllowedListOps::Layer | AllowedListOps::BaseLayer | AllowedListOps::LayerActive => {
                 if l.len() != 2 {
                     bail_expr!(
                         op_expr,
@@ -250,6 +272,7 @@ pub fn parse_switch_case_bool(
                         match op {
                             AllowedListOps::Layer => "layer",
                             AllowedListOps::BaseLayer => "base-layer",
+                            AllowedListOps::LayerActive => "layer-active",
                             _ => unreachable!(),
                         }
                     );
@@ -265,11 +288,36 @@ pub fn parse_switch_case_bool(
                 let (op1, op2) = match op {
                     AllowedListOps::Layer => OpCode::new_layer(layer),
                     AllowedListOps::BaseLayer => OpCode::new_base_layer(layer),
+                    AllowedListOps::LayerActive => OpCode::new_layer_active(layer),
                     _ => unreachable!(),
                 };
                 ops.extend(&[op1, op2]);
                 Ok(())
             }
+            AllowedListOps::VarEq | AllowedListOps::VarGt | AllowedListOps::VarLt => {
+                let (name, cmp) = match op {
+                    AllowedListOps::VarEq => ("var-eq", VarComparison::Eq),
+                    AllowedListOps::VarGt => ("var-gt", VarComparison::Gt),
+                    AllowedListOps::VarLt => ("var-lt", VarComparison::Lt),
+                    _ => unreachable!(),
+                };
+                if l.len() != 3 {
+                    bail_expr!(
+                        op_expr,
+                        "{name} must have 2 parameters: variable-name, value"
+                    );
+                }
+                let var = l[1]
+                    .atom(s.vars())
+                    .map(|atom| s.runtime_var_idx(atom))
+                    .ok_or_else(|| anyhow_expr!(&l[1], "variable name must not be a list"))?;
+                let value = l[2]
+                    .atom(s.vars())
+                    .and_then(|atom| atom.parse::<i16>().ok())
+                    .ok_or_else(|| anyhow_expr!(&l[2], "value must be -32768-32767"))?;
+                ops.extend(&OpCode::new_var(cmp, var, value));
+                Ok(())
+            }
             AllowedListOps::Or | AllowedListOps::And | AllowedListOps::Not => {
                 let op = match op {
                     AllowedListOps::Or => BooleanOperator::Or,
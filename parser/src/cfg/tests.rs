@@ -184,6 +184,29 @@ fn test_span_of_an_unterminated_block_comment_error() {
     assert_eq!(span.end.line_beginning, 0);
 }
 
+#[test]
+fn error_summary_has_file_and_line() {
+    let _lk = lock(&CFG_PARSE_LOCK);
+    let mut s = ParserState::default();
+    let source = "(defsrc a)\n(deflayer base\n  nonexistent-key)\n";
+    let err: miette::Error = parse_cfg_raw_string(
+        source,
+        &mut s,
+        &PathBuf::from("test"),
+        &mut FileContentProvider {
+            get_file_content_fn: &mut |_| unimplemented!(),
+        },
+        DEF_LOCAL_KEYS,
+        Err("env vars not implemented".into()),
+    )
+    .map(|_| ())
+    .expect_err("should error on unknown key")
+    .into();
+    let summary = error_summary(&err);
+    assert!(summary.starts_with("test:3: "), "{summary}");
+    assert!(!summary.contains('\n'), "{summary}");
+}
+
 #[test]
 fn parse_action_vars() {
     let source = r#"
@@ -218,6 +241,7 @@ fn parse_action_vars() {
   tde (tap-dance-eager $two $td)
   unc (unicode $one)
   rlk (release-key $one)
+  prk (press-key $one)
   mul (multi $two $one)
   mwu (mwheel-up $one $two)
   mwua (🖱☸↑ $one $two)
@@ -269,6 +293,7 @@ fn parse_action_vars() {
   opfda (on↓fakekey-delay 200)
   orfda (on↑fakekey-delay 200)
   relka (key↑ $one)
+  prka (key↓ $one)
   rella (layer↑ base)
   fla $full-action
   frk (fork $one $two $five)
@@ -321,6 +346,26 @@ fn parse_all_keys() {
     .unwrap();
 }
 
+#[test]
+fn oscode_to_str_round_trips() {
+    let _lk = lock(&CFG_PARSE_LOCK);
+    // Undo key names changed by the deflocalkeys of other tests.
+    replace_custom_str_oscode_mapping(&HashMap::default());
+    for code in 0..=u16::from(OsCode::KEY_MAX) {
+        let Some(osc) = OsCode::from_u16(code) else {
+            continue;
+        };
+        let name = oscode_to_str(osc);
+        if let Some(parsed) = str_to_oscode(&name) {
+            assert_eq!(parsed, osc, "{name}");
+        }
+    }
+    assert_eq!(oscode_to_str(OsCode::KEY_LEFTALT), "lalt");
+    assert_eq!(oscode_to_str(OsCode::KEY_A), "a");
+    assert_eq!(oscode_to_str(OsCode::KEY_F12), "f12");
+    assert_eq!(oscode_to_str(OsCode::KEY_MENU), "key_menu");
+}
+
 #[test]
 fn parse_file_with_utf8_bom() {
     let _lk = lock(&CFG_PARSE_LOCK);
@@ -334,6 +379,33 @@ fn parse_zippychord_file() {
     new_from_file(&std::path::PathBuf::from("./test_cfgs/testzch.kbd")).unwrap();
 }
 
+#[test]
+#[cfg(feature = "zippychord")]
+fn parse_zippychord_macro_output_errors() {
+    let parse_zippy = |content: &'static str| {
+        let _lk = lock(&CFG_PARSE_LOCK);
+        parse_cfg_raw_string(
+            "(defsrc)(deflayer base)(defzippy file)",
+            &mut ParserState::default(),
+            &PathBuf::from("test"),
+            &mut FileContentProvider {
+                get_file_content_fn: &mut |_| Ok(content.into()),
+            },
+            DEF_LOCAL_KEYS,
+            Err("env vars not implemented".into()),
+        )
+        .map(|_| ())
+        .map_err(|e| e.msg)
+    };
+    parse_zippy("kr\tkind regards,(macro ret 50 S-a)\n").expect("valid macro output");
+    let err = parse_zippy("kr\tkind regards,(macro ret\n").unwrap_err();
+    assert!(err.contains("Unterminated"), "{err}");
+    let err = parse_zippy("kr\tkind regards,(macro (unicode r))\n").unwrap_err();
+    assert!(err.contains("only keys, output chords and delays"), "{err}");
+    let err = parse_zippy("kr\tkind regards,(macro notakey)\n").unwrap_err();
+    assert!(err.contains("(macro notakey)"), "{err}");
+}
+
 #[test]
 fn disallow_nested_tap_hold() {
     let _lk = lock(&CFG_PARSE_LOCK);
@@ -1255,6 +1327,43 @@ new 316
         .expect("parses");
 }
 
+#[test]
+fn parse_deflocalkeys_macos_hid_usage() {
+    let source = r#"
+(deflocalkeys-win ì 187 ò 187)
+(deflocalkeys-macos
+  ì 13
+  grv (0x07 0x64)
+  ì (7 53)
+  grv (0x07 0x32)
+)
+(defsrc a)
+(deflayer base a)
+"#;
+    parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("parses");
+
+    for usage in ["(0x07)", "(0x07 0x64 1)", "(0x07 zz)", "((7) 1)"] {
+        let source = format!("(deflocalkeys-macos grv {usage}) (defsrc a) (deflayer base a)");
+        parse_cfg(&source).map(|_| ()).expect_err("fails");
+    }
+
+    let source = "
+(deflocalkeys-macos grv (0x07 0x64) ì (7 100))
+(defsrc a)
+(deflayer base a)
+";
+    parse_cfg(source).map(|_| ()).expect_err("fails");
+
+    let source = "
+(deflocalkeys-linux grv (0x07 0x64))
+(defsrc a)
+(deflayer base a)
+";
+    parse_cfg(source).map(|_| ()).expect_err("fails");
+}
+
 #[test]
 fn use_default_overridable_mappings() {
     let source = r#"
@@ -1380,6 +1489,8 @@ fn parse_all_defcfg() {
     let source = r#"
 (defcfg
   process-unmapped-keys yes
+  passthrough-keys (f13 f14)
+  self-test-key f23
   danger-enable-cmd yes
   sequence-timeout 2000
   sequence-input-mode visible-backspaced
@@ -1390,8 +1501,27 @@ fn parse_all_defcfg() {
   movemouse-smooth-diagonals yes
   override-release-on-activation yes
   dynamic-macro-max-presses 1000
+  dynamic-macro-persist-file "macros.json"
   concurrent-tap-hold yes
   rapid-event-delay 5
+  tcp-server-address [::1]:5829
+  tcp-server-protocol raw
+  tcp-server-format tagged
+  tcp-server-token "secret"
+  tcp-server-tls-cert "cert.pem"
+  tcp-server-tls-key "key.pem"
+  tcp-max-clients 4
+  tcp-command-timeout-ms 500
+  monitor-max-events-per-second 200
+  output-rate-limit 1000
+  output-rate-limit-policy drop
+  on-idle (60000 (layer-switch base))
+  on-resume XX
+  on-exit (layer-switch base)
+  on-exit-timeout 5000
+  runtime-vars-persist-on-reload yes
+  rpt-repeats-macros no
+  tap-hold-adaptive-window 4
   linux-dev /dev/input/dev1:/dev/input/dev2
   linux-dev-names-include "Name 1:Name 2"
   linux-dev-names-exclude "Name 3:Name 4"
@@ -2029,6 +2159,406 @@ fn parse_defseq_overlap_too_many() {
         .expect_err("fails");
 }
 
+#[test]
+fn parse_defseq_wildcards() {
+    let source = r#"
+(defsrc)
+(deflayer base)
+(defvirtualkeys v v w w x x)
+(defseq v (a _) w (a b c) x ((any S-b c) _))
+"#;
+    let cfg = parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("parses");
+    use crate::trie::GetOrDescendentExistsResult::*;
+    let a = u16::from(OsCode::KEY_A);
+    let b = u16::from(OsCode::KEY_B);
+    let c = u16::from(OsCode::KEY_C);
+    let lsft = u16::from(OsCode::KEY_LEFTSHIFT);
+    // The exact sequence takes priority over the wildcard's `a b`.
+    assert_eq!(cfg.sequences.get_or_descendant_exists([a, b]), InTrie);
+    assert!(matches!(
+        cfg.sequences.get_or_descendant_exists([a, c]),
+        HasValue((_, _, &[k], _)) if k == c
+    ));
+    assert!(matches!(
+        cfg.sequences.get_or_descendant_exists([a, lsft | 0x8000, c | 0x8000]),
+        HasValue((_, _, &[k], _)) if k == c | 0x8000
+    ));
+    assert!(matches!(
+        cfg.sequences.get_or_descendant_exists([lsft | 0x8000, b | 0x8000, a]),
+        HasValue((_, _, &[k1, k2], _)) if k1 == b | 0x8000 && k2 == a
+    ));
+    assert_eq!(cfg.sequences.get_or_descendant_exists([b, a]), NotInTrie);
+}
+
+#[test]
+fn parse_defseq_wildcard_conflicts() {
+    let shadowed = r#"
+(defsrc)
+(deflayer base)
+(defvirtualkeys v v w w)
+(defseq v (a (any b c)) w (a b) w (a c))
+"#;
+    parse_cfg(shadowed).map(|_| ()).expect_err("fails");
+    let too_many = r#"
+(defsrc)
+(deflayer base)
+(defvirtualkeys v v)
+(defseq v (_ _ _))
+"#;
+    parse_cfg(too_many).map(|_| ()).expect_err("fails");
+    let empty_class = r#"
+(defsrc)
+(deflayer base)
+(defvirtualkeys v v)
+(defseq v (a (any)))
+"#;
+    parse_cfg(empty_class).map(|_| ()).expect_err("fails");
+}
+
+#[test]
+fn parse_defseq_timeouts() {
+    let source = r#"
+(defsrc)
+(deflayer base)
+(defvirtualkeys v v w w x x)
+(defseq (timeout 3000) v (a b) w (a c) (timeout 400))
+(defseq x (b c))
+"#;
+    let cfg = parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("parses");
+    use crate::trie::GetOrDescendentExistsResult::*;
+    let a = u16::from(OsCode::KEY_A);
+    let b = u16::from(OsCode::KEY_B);
+    let c = u16::from(OsCode::KEY_C);
+    let timeout_of = |keys: [u16; 2]| match cfg.sequences.get_or_descendant_exists(keys) {
+        HasValue((_, _, _, timeout)) => timeout,
+        _ => panic!("sequence not found"),
+    };
+    assert_eq!(timeout_of([a, b]), Some(3000));
+    assert_eq!(timeout_of([a, c]), Some(400));
+    assert_eq!(timeout_of([b, c]), None);
+    // The longest timeout of the sequences that can still be completed.
+    assert_eq!(cfg.sequences.max_timeout(&[], 1000), Some(3000));
+    assert_eq!(cfg.sequences.max_timeout(&[a], 1000), Some(3000));
+    assert_eq!(cfg.sequences.max_timeout(&[a, c], 1000), Some(400));
+    assert_eq!(cfg.sequences.max_timeout(&[b], 1000), Some(1000));
+    assert_eq!(cfg.sequences.max_timeout(&[c], 1000), None);
+
+    for invalid in [
+        "(defseq v (a b) (timeout 0))",
+        "(defseq v (a b) (timeout -5))",
+        "(defseq v (a b) (timeout))",
+        "(defseq v (a b) (timeout 100) (timeout 200))",
+        "(defseq (timeout 100) (timeout 200) v (a b))",
+    ] {
+        let source = format!("(defsrc)\n(deflayer base)\n(defvirtualkeys v v)\n{invalid}");
+        parse_cfg(&source).map(|_| ()).expect_err("fails");
+    }
+}
+
+#[test]
+fn parse_caps_word_extra_keys_and_rewrites() {
+    let source = r#"
+(defsrc a b)
+(deflayer base
+  (caps-word 1000 (kp-))
+  (caps-word-custom 1000 (a b min) () ((spc min) (tab min)))
+)
+"#;
+    parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("parses");
+
+    for invalid in [
+        "(caps-word 1000 (kp-) (a))",
+        "(caps-word-custom 1000 (a) () (spc min))",
+        "(caps-word-custom 1000 (a) () ((spc)))",
+        "(caps-word-custom 1000 (a) () ((spc min) (spc a)))",
+    ] {
+        let source = format!("(defsrc a)\n(deflayer base {invalid})");
+        parse_cfg(&source).map(|_| ()).expect_err("fails");
+    }
+}
+
+#[test]
+fn parse_hid_action() {
+    let source = "(defsrc a b c)\n(deflayer base (hid 0x0c 0xcd) (hid 7 4) (hid 0xff01 0x0010))";
+    parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("parses");
+
+    for invalid in [
+        "(hid 0x0c)",
+        "(hid 0x0c 0xcd 1)",
+        "(hid 0x01 0x81)",
+        "(hid 0x07 0x100)",
+        "(hid 0x0c 0)",
+        "(hid 0x0c 0x10000)",
+        "(hid 0x0c zz)",
+    ] {
+        let source = format!("(defsrc a)\n(deflayer base {invalid})");
+        parse_cfg(&source).map(|_| ()).expect_err("fails");
+    }
+}
+
+#[test]
+fn parse_fork_multi() {
+    let source = "
+(defsrc a)
+(deflayer base (fork-multi (lsft rsft) b (lsft lctl) c d))
+";
+    parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("overlapping key sets are allowed");
+
+    for invalid in [
+        "(fork-multi (lsft) b)",
+        "(fork-multi (lsft) b (lctl) c)",
+        "(fork-multi lsft b c)",
+        "(fork-multi (lsft) notanaction c)",
+    ] {
+        let source = format!("(defsrc a)\n(deflayer base {invalid})");
+        parse_cfg(&source).map(|_| ()).expect_err("fails");
+    }
+}
+
+#[test]
+fn passthrough_keys_are_not_mapped() {
+    let source = "
+(defcfg process-unmapped-keys yes passthrough-keys (b f13))
+(defsrc a b)
+(deflayer base c d)
+";
+    let icfg = parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("parses");
+    assert!(icfg.mapped_keys.contains(&OsCode::KEY_A));
+    assert!(icfg.mapped_keys.contains(&OsCode::KEY_C));
+    assert!(!icfg.mapped_keys.contains(&OsCode::KEY_B));
+    assert!(!icfg.mapped_keys.contains(&OsCode::KEY_F13));
+
+    for invalid in [
+        "passthrough-keys f13",
+        "passthrough-keys (f13 f13)",
+        "passthrough-keys (notakey)",
+    ] {
+        let source = format!("(defcfg {invalid})\n(defsrc a)\n(deflayer base a)");
+        parse_cfg(&source).map(|_| ()).expect_err("fails");
+    }
+}
+
+#[test]
+fn parse_tap_hold_adaptive() {
+    let source = "
+(defsrc a)
+(deflayer base (tap-hold-adaptive 0 200 100 400 a lctl))
+";
+    parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("parses");
+
+    for invalid in [
+        "(tap-hold-adaptive 0 200 100 400 a)",
+        "(tap-hold-adaptive 0 200 300 400 a lctl)",
+        "(tap-hold-adaptive 0 200 100 150 a lctl)",
+        "(tap-hold-adaptive 0 200 0 400 a lctl)",
+    ] {
+        let source = format!("(defsrc a)\n(deflayer base {invalid})");
+        parse_cfg(&source).map(|_| ()).expect_err("fails");
+    }
+}
+
+#[test]
+fn parse_defschedule() {
+    let source = "
+(defsrc a)
+(deflayer base a)
+(deflayer work b)
+(defschedule (09:00 17:30 mon fri) work (22:00 24:00) base (23:00 01:00 sun) work)
+";
+    let icfg = parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("parses");
+    assert_eq!(
+        icfg.schedule,
+        vec![
+            ScheduleEntry {
+                start_min: 9 * 60,
+                end_min: 17 * 60 + 30,
+                weekdays: 0b1_0001,
+                layer: 1,
+            },
+            ScheduleEntry {
+                start_min: 22 * 60,
+                end_min: 0,
+                weekdays: 0b111_1111,
+                layer: 0,
+            },
+            ScheduleEntry {
+                start_min: 23 * 60,
+                end_min: 60,
+                weekdays: 0b100_0000,
+                layer: 1,
+            },
+        ]
+    );
+    let [work, evening, overnight] = icfg.schedule[..] else {
+        panic!("three entries");
+    };
+    assert!(work.contains(0, 9 * 60));
+    assert!(work.contains(4, 17 * 60 + 29));
+    assert!(!work.contains(4, 17 * 60 + 30));
+    assert!(!work.contains(1, 12 * 60));
+    assert!(evening.contains(3, 23 * 60 + 59));
+    assert!(!evening.contains(3, 0));
+    // Ranges past midnight continue into the next day.
+    assert!(overnight.contains(6, 23 * 60));
+    assert!(overnight.contains(0, 59));
+    assert!(!overnight.contains(0, 60));
+    assert!(!overnight.contains(6, 30));
+
+    for invalid in [
+        "(defschedule (09:00 17:00) nope)",
+        "(defschedule (09:00 17:00))",
+        "(defschedule (09:00) base)",
+        "(defschedule 09:00 base)",
+        "(defschedule (09:60 17:00) base)",
+        "(defschedule (24:00 17:00) base)",
+        "(defschedule (9 17) base)",
+        "(defschedule (09:00 09:00) base)",
+        "(defschedule (09:00 17:00 monday) base)",
+    ] {
+        let source = format!("{invalid}\n(defsrc a)\n(deflayer base a)");
+        parse_cfg(&source).map(|_| ()).expect_err("fails");
+    }
+}
+
+#[test]
+fn parse_defmacro() {
+    let source = "
+(defvirtualkeys vk a)
+(defmacro m1 (a 10 b) m2 (S-c))
+(defsrc a)
+(deflayer base a)
+";
+    let icfg = parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("parses");
+    // Macros are placed after the virtual keys.
+    assert_eq!(icfg.named_macros.get("m1"), Some(&1));
+    assert_eq!(icfg.named_macros.get("m2"), Some(&2));
+
+    for invalid in [
+        "(defmacro m1 (a) m1 (b))",
+        "(defmacro m1 a)",
+        "(defmacro m1)",
+        "(defmacro (m1) (a))",
+    ] {
+        let source = format!("{invalid}\n(defsrc a)\n(deflayer base a)");
+        parse_cfg(&source).map(|_| ()).expect_err("fails");
+    }
+}
+
+#[test]
+fn parse_override_actions() {
+    for source in [
+        "(defoverrides (lsft 3) (unicode №) (lctl v) (macro h i) (lsft a) (lsft 9))",
+        "(defoverridesv2 (lsft 3) (unicode №) (lctl) () (lalt x) (multi lctl c) () ())",
+    ] {
+        let source = format!("{source}\n(defsrc a)\n(deflayer base a)");
+        parse_cfg(&source)
+            .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+            .expect("parses");
+    }
+
+    for invalid in [
+        "(defoverrides (lsft 3) (not-an-action))",
+        "(defoverrides (lsft 3) ())",
+        "(defoverrides (lsft 3) (unicode))",
+    ] {
+        let source = format!("{invalid}\n(defsrc a)\n(deflayer base a)");
+        parse_cfg(&source).map(|_| ()).expect_err("fails");
+    }
+}
+
+#[test]
+fn parse_runtime_vars() {
+    let source = "
+(defvar fixed 20)
+(defsrc a b c d e f)
+(deflayer base
+  (setvar mode -1)
+  (incvar count 2)
+  (switch ((var-eq mode 2)) (incvar mode) break ((var-lt count -5)) a break)
+  (macro (decvar count) a $gap b $fixed c)
+  (movemouse-up $interval 1)
+  (movemouse-accel-down $interval 500 1 5))
+";
+    parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("parses");
+
+    for invalid in [
+        "(setvar mode)",
+        "(setvar mode x)",
+        "(setvar (mode) 1)",
+        "(incvar)",
+        "(incvar count 1 2)",
+        "(decvar)",
+        "(decvar count x)",
+        "(movemouse-up $ 1)",
+        "(switch ((var-eq mode)) a break)",
+        "(switch ((var-gt mode 40000)) a break)",
+    ] {
+        let source = format!("(defsrc a)\n(deflayer base {invalid})");
+        parse_cfg(&source).map(|_| ()).expect_err("fails");
+    }
+}
+
+#[test]
+fn parse_movemouse_speed() {
+    for invalid in [
+        "(movemouse-speed)",
+        "(movemouse-speed 0)",
+        "(movemouse-speed 50 mwheel)",
+        "(movemouse-speed 50 scale-mwheel scale-mwheel)",
+        "(movemouse-speed-reset 100)",
+    ] {
+        let source = format!("(defsrc a)\n(deflayer base {invalid})");
+        parse_cfg(&source).map(|_| ()).expect_err("fails");
+    }
+    parse_cfg("(defsrc a)\n(deflayer base (movemouse-speed-reset))").expect("parses");
+}
+
+#[test]
+fn parse_layer_cycle() {
+    let source = "
+(defsrc a b c)
+(deflayer l1 (layer-cycle (l1 l2)) (layer-cycle (l1 l2)) (layer-cycle (l2 l1)))
+(deflayer l2 (layer-cycle (l1 l2)) (layer-cycle (l1 l2)) (layer-cycle (l2 l1)))
+";
+    init_log();
+    let cfg = {
+        let _lk = lock(&CFG_PARSE_LOCK);
+        new_from_str(source, Default::default()).expect("parses")
+    };
+    assert_eq!(cfg.layer_cycles, [vec![0, 1], vec![1, 0]]);
+
+    for invalid in [
+        "(layer-cycle)",
+        "(layer-cycle l1)",
+        "(layer-cycle (l1))",
+        "(layer-cycle (l1 nope))",
+        "(layer-cycle (l1 l2) (l1 l2))",
+    ] {
+        let source = format!("(defsrc a)\n(deflayer l1 {invalid})\n(deflayer l2 a)");
+        parse_cfg(&source).map(|_| ()).expect_err("fails");
+    }
+}
+
 #[test]
 fn parse_layer_opts_icon() {
     let _lk = lock(&CFG_PARSE_LOCK);
@@ -2097,6 +2627,75 @@ fn reverse_release_order_must_be_within_multi() {
     );
 }
 
+#[test]
+fn chord_output_rejects_invalid_keys() {
+    let parse = |action: &str| {
+        let source = format!("(defsrc a) (deflayer base {action})");
+        parse_cfg(&source).map(|_| ())
+    };
+    parse("(chord-output lctl lalt del)").expect("passes");
+    let e = parse("(chord-output lctl)").expect_err("fails");
+    assert!(
+        e.msg
+            .starts_with("chord-output expects at least two key names")
+    );
+    let e = parse("(chord-output lctl notakey)").expect_err("fails");
+    assert!(e.msg.ends_with("found invalid key name"));
+    let e = parse("(chord-output lctl lalt lctl)").expect_err("fails");
+    assert_eq!(e.msg, "Duplicate key name in chord-output is not allowed.");
+}
+
+#[test]
+fn layer_while_held_timeout_parses() {
+    let parse = |action: &str| {
+        let source = format!("(defsrc a) (deflayer base {action}) (deflayer nav a)");
+        parse_cfg(&source).map(|_| ())
+    };
+    parse("(layer-while-held-timeout nav 300000)").expect("passes");
+    let e = parse("(layer-while-held-timeout nav)").expect_err("fails");
+    assert!(e.msg.ends_with("Found 1 params instead"));
+    let e = parse("(layer-while-held-timeout nav 0)").expect_err("fails");
+    assert!(
+        e.msg
+            .ends_with("timeout-ms must be a non-zero number of milliseconds")
+    );
+}
+
+#[test]
+fn multi_seq_parses_delays() {
+    let parse = |action: &str| {
+        let source = format!("(defsrc a) (deflayer base {action})");
+        parse_cfg(&source).map(|_| ())
+    };
+    parse("(multi-seq lctl 10 c)").expect("passes");
+    parse("(multi-seq 5 lctl lalt 0 del)").expect("passes");
+    let e = parse("(multi-seq)").expect_err("fails");
+    assert!(e.msg.ends_with("found no items"));
+    let e = parse("(multi-seq lctl 10 20 c)").expect_err("fails");
+    assert!(e.msg.ends_with("found two delays in a row"));
+    let e = parse("(multi-seq lctl 10)").expect_err("fails");
+    assert!(e.msg.ends_with("a delay must be followed by an action"));
+    let e = parse("(multi-seq lctl 10 (tap-hold 200 200 a b))").expect_err("fails");
+    assert_eq!(
+        e.msg,
+        "multi-seq does not support tap-hold, tap-dance or chords"
+    );
+}
+
+#[test]
+fn switch_vkey_active_checks_vkey_name() {
+    let parse = |name: &str| {
+        let source = format!(
+            "(defsrc a) (defvirtualkeys flag nop0)
+             (deflayer base (switch ((vkey-active {name})) b break))"
+        );
+        parse_cfg(&source).map(|_| ())
+    };
+    parse("flag").expect("passes");
+    let e = parse("nope").expect_err("fails");
+    assert_eq!(e.msg, "unknown virtual key name: nope");
+}
+
 #[test]
 fn parse_clipboard_actions() {
     let source = "
@@ -2110,3 +2709,541 @@ fn parse_clipboard_actions() {
 ";
     parse_cfg(source).map(|_| ()).expect("success");
 }
+
+#[test]
+fn unused_aliases_are_reported() {
+    init_log();
+    let _lk = lock(&CFG_PARSE_LOCK);
+    let cfg = new_from_str(
+        "
+(defcfg alias-to-trigger-on-load start)
+(defsrc a b)
+(defalias
+  start XX
+  nested lctl
+  x (tap-hold 200 200 a @nested)
+  unused c
+  also-unused d
+)
+(deflayer base @x b)
+",
+        Default::default(),
+    )
+    .expect("parses");
+    assert_eq!(
+        cfg.aliases,
+        ["also-unused", "nested", "start", "unused", "x"]
+    );
+    assert_eq!(cfg.unused_aliases, ["also-unused", "unused"]);
+}
+
+#[test]
+fn tcp_server_tls_options_must_be_used_together() {
+    let err = parse_cfg(
+        "
+(defcfg tcp-server-tls-cert cert.pem)
+(defsrc a)
+(deflayer base a)
+",
+    )
+    .map(|_| ())
+    .expect_err("fails")
+    .msg;
+    assert!(err.contains("must be used together"), "{err}");
+    let err = parse_cfg(
+        "
+(defcfg tcp-server-protocol ws tcp-server-tls-cert cert.pem tcp-server-tls-key key.pem)
+(defsrc a)
+(deflayer base a)
+",
+    )
+    .map(|_| ())
+    .expect_err("fails")
+    .msg;
+    assert!(err.contains("tcp-server-protocol ws"), "{err}");
+    let err = parse_cfg(
+        "
+(defcfg tcp-server-tls-cert cert.pem tcp-server-tls-key key.pem tcp-server-protocol ws)
+(defsrc a)
+(deflayer base a)
+",
+    )
+    .map(|_| ())
+    .expect_err("fails")
+    .msg;
+    assert!(err.contains("tcp-server-protocol ws"), "{err}");
+}
+
+#[test]
+fn included_chords_can_override_timeout_and_release_behaviour() {
+    use kanata_keyberon::chord::ReleaseBehaviour;
+    let icfg = parse_cfg(
+        "
+(defcfg concurrent-tap-hold yes)
+(defsrc a s d f)
+(deflayer base a s d f)
+(defchordsv2
+  (include ./test_cfgs/chords-overrides.tsv) () 100 first-release ()
+)
+",
+    )
+    .expect("parses");
+    let chords = icfg.chords_v2.expect("chords are defined");
+    let chord_for = |key: OsCode, other: OsCode| {
+        let mut keys = [u16::from(key), u16::from(other)];
+        keys.sort();
+        *chords.chords().mapping[&u16::from(key)]
+            .chords
+            .iter()
+            .find(|chord| chord.participating_keys == keys)
+            .expect("chord exists")
+    };
+    let defaults = chord_for(OsCode::KEY_A, OsCode::KEY_S);
+    assert_eq!(defaults.pending_duration, 100);
+    assert_eq!(defaults.release_behaviour, ReleaseBehaviour::OnFirstRelease);
+    let both_overridden = chord_for(OsCode::KEY_S, OsCode::KEY_D);
+    assert_eq!(both_overridden.pending_duration, 300);
+    assert_eq!(
+        both_overridden.release_behaviour,
+        ReleaseBehaviour::OnLastRelease
+    );
+    let release_overridden = chord_for(OsCode::KEY_D, OsCode::KEY_F);
+    assert_eq!(release_overridden.pending_duration, 100);
+    assert_eq!(
+        release_overridden.release_behaviour,
+        ReleaseBehaviour::OnFirstRelease
+    );
+}
+
+#[test]
+fn included_chords_reject_conflicting_overrides() {
+    let err = parse_cfg(
+        "
+(defcfg concurrent-tap-hold yes)
+(defsrc a s)
+(deflayer base a s)
+(defchordsv2
+  (include ./test_cfgs/chords-overrides-bad.tsv) () 100 all-released ()
+)
+",
+    )
+    .expect_err("fails");
+    assert!(
+        err.msg
+            .starts_with("Chord release behaviour is overridden more than once"),
+        "{}",
+        err.msg
+    );
+    assert_eq!(
+        err.span.expect("has span").file_name(),
+        "./test_cfgs/chords-overrides-bad.tsv"
+    );
+}
+
+#[test]
+fn layer_with_extra_items_points_at_first_extra_item() {
+    let source = "(defsrc a b)\n(deflayer base a b c d)\n";
+    let err = parse_cfg(source).expect_err("fails");
+    assert_eq!(
+        err.msg,
+        "Layer base has 4 item(s), but requires 2 to match defsrc.\n\
+         This is the first item without a defsrc key."
+    );
+    let span = err.span.expect("has span");
+    assert_eq!(&source[span.start()..span.end()], "c");
+}
+
+#[test]
+fn layer_with_missing_items_reports_count() {
+    let err = parse_cfg("(defsrc a b c)\n(deflayer base a b c)\n(deflayer other x)\n")
+        .expect_err("fails");
+    assert_eq!(
+        err.msg,
+        "Layer other has 1 item(s), but requires 3 to match defsrc.\n\
+         The last 2 defsrc key(s) have no item in this layer."
+    );
+}
+
+#[test]
+fn media_key_names_parse() {
+    let keys = "media-play media-pause media-stop media-next-track media-prev-track \
+                media-vol-up media-vol-down media-mute";
+    parse_cfg(&format!("(defsrc {keys}) (deflayer base {keys})")).expect("parses");
+}
+
+#[test]
+fn media_key_names_round_trip() {
+    let _lk = lock(&CFG_PARSE_LOCK);
+    // Undo key names changed by the deflocalkeys of other tests.
+    replace_custom_str_oscode_mapping(&HashMap::default());
+    for name in [
+        "media-play",
+        "media-pause",
+        "media-stop",
+        "media-next-track",
+        "media-prev-track",
+        "media-vol-up",
+        "media-vol-down",
+        "media-mute",
+    ] {
+        let osc = str_to_oscode(name).expect(name);
+        // The key must be one that input from the OS can produce, so that it matches in defsrc.
+        assert_eq!(OsCode::from_u16(osc.as_u16()), Some(osc), "{name}");
+        assert_eq!(str_to_oscode(&oscode_to_str(osc)), Some(osc), "{name}");
+    }
+}
+
+#[test]
+fn dangerous_keys_require_opt_in() {
+    let e =
+        parse_cfg("(defsrc a b) (deflayer base system-sleep C-system-power)").expect_err("fails");
+    assert!(e.msg.contains("allow-dangerous-keys (system-sleep)"));
+    let e = parse_cfg(
+        "(defcfg allow-dangerous-keys (system-sleep))
+         (defsrc a b) (deflayer base system-sleep C-system-power)",
+    )
+    .expect_err("fails");
+    assert!(e.msg.contains("allow-dangerous-keys (system-power)"));
+    parse_cfg(
+        "(defcfg allow-dangerous-keys (system-power system-sleep))
+         (defsrc a b system-power) (deflayer base system-sleep C-system-power _)",
+    )
+    .expect("passes");
+    parse_cfg("(defcfg allow-dangerous-keys (system-wake)) (defsrc a) (deflayer base a)")
+        .expect_err("fails");
+}
+
+#[test]
+fn layer_opts_process_unmapped_keys_and_hardware_repeat() {
+    let cfg = parse_cfg(
+        "(defsrc a)
+         (deflayer (base process-unmapped-keys yes allow-hardware-repeat no) a)
+         (deflayermap (other icon o.ico process-unmapped-keys no) a b)",
+    )
+    .expect("parses");
+    assert_eq!(cfg.layer_info[0].process_unmapped_keys, Some(true));
+    assert_eq!(cfg.layer_info[0].allow_hardware_repeat, Some(false));
+    assert_eq!(cfg.layer_info[1].process_unmapped_keys, Some(false));
+    assert_eq!(cfg.layer_info[1].allow_hardware_repeat, None);
+    assert!(cfg.mapped_keys.contains(&OsCode::KEY_C));
+    assert!(cfg.unmapped_keys.contains(&OsCode::KEY_C));
+    assert!(!cfg.unmapped_keys.contains(&OsCode::KEY_A));
+    parse_cfg("(defsrc a) (deflayer (base process-unmapped-keys maybe) a)").expect_err("fails");
+}
+
+#[test]
+fn layer_tap_timeout_parses() {
+    let cfg = parse_cfg(
+        "(defcfg tap-hold-timeout 250)
+         (defsrc a b)
+         (deflayer (base tap-timeout 150) (tap-hold 0 _ a b) (tap-hold-release 0 100 a b))",
+    )
+    .expect("parses");
+    assert_eq!(cfg.layer_info[0].tap_timeout, Some(150));
+    parse_cfg("(defsrc a) (deflayer (base tap-timeout 0) a)").expect_err("fails");
+    parse_cfg("(defsrc a) (deflayer base (tap-hold 0 0 a b))").expect_err("fails");
+}
+
+#[test]
+fn tap_hold_mode_parses() {
+    for mode in ["timeout", "hold-on-other-key-press", "permissive-hold"] {
+        parse_cfg(&format!(
+            "(defcfg tap-hold-mode {mode}) (defsrc a) (deflayer base (tap-hold 0 200 a b))"
+        ))
+        .expect("parses");
+    }
+    let e =
+        parse_cfg("(defcfg tap-hold-mode eager) (defsrc a) (deflayer base a)").expect_err("fails");
+    assert!(e.msg.contains("permissive-hold"));
+}
+
+#[test]
+fn debounce_options_parse() {
+    let cfg = parse_cfg(
+        "(defcfg debounce-ms 5 debounce-algorithm eager debounce-keys (e 20 a 0))
+         (defsrc a e) (deflayer base a e)",
+    )
+    .expect("parses");
+    assert_eq!(cfg.options.debounce_ms, 5);
+    assert_eq!(cfg.options.debounce_algorithm, DebounceAlgorithm::Eager);
+    assert_eq!(
+        cfg.options.debounce_keys,
+        vec![(OsCode::KEY_E, 20), (OsCode::KEY_A, 0)]
+    );
+    for bad in [
+        "debounce-algorithm sym",
+        "debounce-keys e",
+        "debounce-keys (e)",
+        "debounce-keys (e 5 e 10)",
+        "debounce-keys (nokey 5)",
+        "debounce-keys (e -1)",
+    ] {
+        parse_cfg(&format!("(defcfg {bad}) (defsrc e) (deflayer base e)")).expect_err("fails");
+    }
+}
+
+#[test]
+fn raw_event_mode_defsrc_scancodes() {
+    let cfg = parse_cfg(
+        "(defcfg event-mode raw)
+         (defsrc 0x10 0xe01d b)
+         (deflayer base a b c)",
+    )
+    .expect("parses");
+    assert!(cfg.mapped_keys.contains(&OsCode::KEY_Q));
+    assert!(cfg.mapped_keys.contains(&OsCode::KEY_RIGHTCTRL));
+    assert!(cfg.mapped_keys.contains(&OsCode::KEY_B));
+    let e = parse_cfg("(defcfg event-mode raw) (defsrc 0x10 0x1234) (deflayer base a b)")
+        .expect_err("fails");
+    assert!(e.msg.contains("Unknown scan code in defsrc"));
+    parse_cfg("(defcfg event-mode raw) (defsrc 0x10 q) (deflayer base a b)").expect_err("fails");
+    parse_cfg("(defsrc 0x10) (deflayer base a)").expect_err("fails");
+    parse_cfg("(defcfg event-mode physical) (defsrc a) (deflayer base a)").expect_err("fails");
+}
+
+#[test]
+fn parse_setmouse_pct() {
+    parse_cfg(
+        "(defsrc a b c d)
+         (deflayer base (setmouse-pct 50 50) (setmouse-pct 0 100 current)
+          (setmouse-pct 200 -5 primary) (setmouse-pct 10 90 2))",
+    )
+    .expect("parses");
+    parse_cfg("(defsrc a) (deflayer base (setmouse-pct 50))").expect_err("fails");
+    parse_cfg("(defsrc a) (deflayer base (setmouse-pct 50 50 current 1))").expect_err("fails");
+    parse_cfg("(defsrc a) (deflayer base (setmouse-pct 50% 50))").expect_err("fails");
+    parse_cfg("(defsrc a) (deflayer base (setmouse-pct 50 50 secondary))").expect_err("fails");
+}
+
+#[test]
+fn parse_mwheel_fine() {
+    parse_cfg(
+        "(defsrc a b)
+         (deflayer base (mwheel-fine-up 16 30) (mwheel-fine-left 16 500 10 120))",
+    )
+    .expect("parses");
+    parse_cfg("(defsrc a) (deflayer base (mwheel-fine-down 16))").expect_err("fails");
+    parse_cfg("(defsrc a) (deflayer base (mwheel-fine-down 16 500 30))").expect_err("fails");
+    parse_cfg("(defsrc a) (deflayer base (mwheel-fine-down 16 500 120 10))").expect_err("fails");
+    parse_cfg("(defsrc a) (deflayer base (mwheel-fine-down 0 30))").expect_err("fails");
+}
+
+#[test]
+fn parse_mouse_speed_curve() {
+    let cfg = parse_cfg(
+        "(defcfg mouse-speed-curve ease-out mouse-speed-start 0.5 mouse-speed-ramp-ms 200)
+         (defsrc a) (deflayer base a)",
+    )
+    .expect("parses");
+    let curve = cfg.options.mouse_speed_curve.expect("curve is set");
+    assert_eq!(curve.speed_multiplier(std::time::Duration::ZERO), 0.5);
+    assert_eq!(
+        curve.speed_multiplier(std::time::Duration::from_millis(100)),
+        2.375
+    );
+    assert_eq!(
+        curve.speed_multiplier(std::time::Duration::from_secs(1)),
+        3.0
+    );
+    parse_cfg("(defcfg mouse-speed-curve quadratic) (defsrc a) (deflayer base a)")
+        .expect_err("fails");
+    parse_cfg("(defcfg mouse-speed-max 2) (defsrc a) (deflayer base a)").expect_err("fails");
+    parse_cfg("(defcfg mouse-speed-curve linear mouse-speed-start 4) (defsrc a) (deflayer base a)")
+        .expect_err("fails");
+    parse_cfg(
+        "(defcfg mouse-speed-curve linear mouse-speed-ramp-ms 0) (defsrc a) (deflayer base a)",
+    )
+    .expect_err("fails");
+}
+
+#[test]
+fn parse_max_output_hold_ms() {
+    let cfg = parse_cfg("(defcfg max-output-hold-ms 30000) (defsrc a) (deflayer base a)")
+        .expect("parses");
+    assert_eq!(cfg.options.max_output_hold_ms, Some(30000));
+    parse_cfg("(defcfg max-output-hold-ms 0) (defsrc a) (deflayer base a)").expect_err("fails");
+}
+
+#[test]
+fn parse_movemouse_polar() {
+    parse_cfg("(defsrc a b) (deflayer base (movemouse-polar 0 500) (movemouse-polar 359 1))")
+        .expect("parses");
+    parse_cfg("(defsrc a) (deflayer base (movemouse-polar 360 500))").expect_err("fails");
+    parse_cfg("(defsrc a) (deflayer base (movemouse-polar 45 0))").expect_err("fails");
+    parse_cfg("(defsrc a) (deflayer base (movemouse-polar 45))").expect_err("fails");
+}
+
+#[test]
+fn parse_chord_hold() {
+    parse_cfg(
+        "
+(defcfg concurrent-tap-hold yes)
+(defsrc d f)
+(deflayer base d f)
+(defchordsv2
+  (d f) (chord-hold 200 esc lctl) 50 all-released ()
+)
+",
+    )
+    .expect("parses");
+    let err = parse_cfg("(defsrc a) (deflayer base (chord-hold 200 esc lctl))").expect_err("fails");
+    assert!(err.msg.contains("defchordsv2"), "{}", err.msg);
+    parse_cfg(
+        "
+(defcfg concurrent-tap-hold yes)
+(defsrc d f)
+(deflayer base d f)
+(defchordsv2
+  (d f) (chord-hold 200 esc) 50 all-released ()
+)
+",
+    )
+    .expect_err("fails");
+}
+
+#[test]
+fn unknown_defcfg_option_suggests_closest_match() {
+    let source = "(defcfg\n  process-unmaped-keys yes)\n(defsrc a)\n(deflayer base a)\n";
+    let err = parse_cfg(source).expect_err("fails");
+    assert!(
+        err.msg.starts_with(
+            "Unknown defcfg option process-unmaped-keys.\n\
+             Did you mean process-unmapped-keys?\n\
+             Known options: sequence-timeout, "
+        ),
+        "{}",
+        err.msg
+    );
+    let span = err.span.expect("has span");
+    assert_eq!(&source[span.start()..span.end()], "process-unmaped-keys");
+
+    let err =
+        parse_cfg("(defcfg completely-wrong yes) (defsrc a) (deflayer base a)").expect_err("fails");
+    assert!(!err.msg.contains("Did you mean"), "{}", err.msg);
+}
+
+#[test]
+fn listed_defcfg_options_are_known() {
+    for opt in crate::cfg::defcfg::DEFCFG_OPTIONS {
+        if let Err(e) = parse_cfg(&format!("(defcfg {opt} 0) (defsrc a) (deflayer base a)")) {
+            assert!(!e.msg.starts_with("Unknown defcfg option"), "{opt}");
+        }
+    }
+}
+
+#[test]
+fn known_defcfg_options_are_listed() {
+    // Old names that are still accepted but not suggested.
+    const UNLISTED: &[&str] = &["chords-v2-min-idle-experimental"];
+    let source = include_str!("defcfg.rs");
+    let start = source.find("match label {").expect("options match");
+    let end = source
+        .find("unknown_defcfg_option_msg(label)")
+        .expect("fallback arm");
+    let mut count = 0;
+    for line in source[start..end].lines() {
+        // Arms of the options match, unlike nested matches, are indented by exactly 20 spaces.
+        let Some(arm) = line.strip_prefix(&" ".repeat(20)) else {
+            continue;
+        };
+        let Some((pattern, _)) = arm.split_once(" =>") else {
+            continue;
+        };
+        if !pattern.starts_with('"') {
+            continue;
+        }
+        for opt in pattern.split(" | ") {
+            let opt = opt.trim_matches('"');
+            count += 1;
+            assert!(
+                crate::cfg::defcfg::DEFCFG_OPTIONS.contains(&opt) || UNLISTED.contains(&opt),
+                "{opt} is missing from DEFCFG_OPTIONS"
+            );
+        }
+    }
+    assert!(
+        count >= crate::cfg::defcfg::DEFCFG_OPTIONS.len(),
+        "found only {count} options"
+    );
+}
+
+#[test]
+fn parse_str() {
+    parse_cfg(r##"(defsrc a b) (deflayer base (str "a b\n") (str r#"say \"hi\""# 10))"##)
+        .expect("parses");
+    for (source, msg) in [
+        (r#"(defsrc a) (deflayer base (str))"#, "str expects 1 or 2"),
+        (r#"(defsrc a) (deflayer base (str ""))"#, "cannot be empty"),
+        (
+            r#"(defsrc a) (deflayer base (str "a\q"))"#,
+            "Unknown escape",
+        ),
+        (
+            r#"(defsrc a) (deflayer base (str "a\"))"#,
+            "Unterminated escape",
+        ),
+        (r#"(defsrc a) (deflayer base (str "a" -1))"#, "delay"),
+    ] {
+        let err = parse_cfg(source).expect_err("fails");
+        assert!(err.msg.contains(msg), "{source}: {}", err.msg);
+    }
+}
+
+#[test]
+fn parse_on_release_action() {
+    parse_cfg(
+        "(defsrc a b)
+         (defvirtualkeys v c)
+         (deflayer base (on-release (macro x y)) (on-press b on-release (on-press tap-vkey v)))",
+    )
+    .expect("parses");
+    let err = parse_cfg(
+        "(defsrc a)
+         (defvirtualkeys v (on-release b))
+         (deflayer base a)",
+    )
+    .expect_err("fails");
+    assert!(err.msg.contains("within defvirtualkeys"), "{}", err.msg);
+    let err = parse_cfg("(defsrc a) (deflayer base (on-press a on-hold b))").expect_err("fails");
+    assert!(
+        err.msg.contains("<action> on-release <action>"),
+        "{}",
+        err.msg
+    );
+}
+
+#[test]
+fn parse_fakekeys() {
+    parse_cfg(
+        "(defsrc a b)
+         (defvirtualkeys
+           vk-a a
+           vk-b b
+           cleanup (fakekeys (release vk-a) (tap vk-b) (toggle vk-a) (press vk-b)))
+         (deflayer base (fakekeys (tap cleanup)) (on-idle-fakekey cleanup tap 1000))",
+    )
+    .expect("parses");
+    let source = "(defsrc a)
+         (defvirtualkeys vk-a a)
+         (deflayer base (fakekeys (release vk-a) (tap vk-c)))";
+    let err = parse_cfg(source).expect_err("fails");
+    let span = err.span.expect("has span");
+    assert_eq!(&source[span.start()..span.end()], "vk-c");
+    for (source, msg) in [
+        (
+            "(defsrc a) (deflayer base (fakekeys))",
+            "expects one or more",
+        ),
+        (
+            "(defsrc a) (defvirtualkeys v a) (deflayer base (fakekeys (hold v)))",
+            "Unknown virtual key action",
+        ),
+        (
+            "(defsrc a) (defvirtualkeys v a) (deflayer base (fakekeys tap v))",
+            "expects one or more",
+        ),
+    ] {
+        let err = parse_cfg(source).expect_err("fails");
+        assert!(err.msg.contains(msg), "{source}: {}", err.msg);
+    }
+}
@@ -121,3 +121,246 @@ fn non_applicable_os_deflocalkeys_always_succeeds_parsing() {
         .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
         .expect("passes");
 }
+
+#[test]
+fn tcp_server_protocol_parses() {
+    let source = "
+(defcfg tcp-server-protocol ws)
+(defsrc)
+(deflayer base)
+";
+    let icfg = parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("passes");
+    assert_eq!(icfg.options.tcp_server_protocol, TcpServerProtocol::Ws);
+
+    let source = "
+(defcfg)
+(defsrc)
+(deflayer base)
+";
+    let icfg = parse_cfg(source).expect("passes");
+    assert_eq!(icfg.options.tcp_server_protocol, TcpServerProtocol::Raw);
+
+    let source = "
+(defcfg tcp-server-protocol http)
+(defsrc)
+(deflayer base)
+";
+    parse_cfg(source).map(|_| ()).expect_err("fails");
+}
+
+#[test]
+fn tcp_server_address_parses() {
+    let address = |value: &str| {
+        let source = format!("(defcfg tcp-server-address {value}) (defsrc) (deflayer base)");
+        parse_cfg(&source).map(|icfg| icfg.options.tcp_server_address.unwrap().to_string())
+    };
+    assert_eq!(address("5829").unwrap(), "127.0.0.1:5829");
+    assert_eq!(address("0.0.0.0:5829").unwrap(), "0.0.0.0:5829");
+    assert_eq!(address("[::1]:5829").unwrap(), "[::1]:5829");
+    assert_eq!(address("192.168.1.100:5829").unwrap(), "192.168.1.100:5829");
+    for bad in ["localhost:5829", "0.0.0.0", "::1:5829", "127.0.0.1:70000"] {
+        let e = address(bad).expect_err("fails");
+        assert!(e.msg.contains("tcp-server-address got"), "{}", e.msg);
+    }
+
+    let icfg = parse_cfg("(defcfg) (defsrc) (deflayer base)").expect("passes");
+    assert_eq!(icfg.options.tcp_server_address, None);
+}
+
+#[test]
+fn tcp_server_format_parses() {
+    let source = "
+(defcfg tcp-server-format tagged)
+(defsrc)
+(deflayer base)
+";
+    let icfg = parse_cfg(source).expect("passes");
+    assert_eq!(icfg.options.tcp_server_format, TcpServerFormat::Tagged);
+
+    let source = "
+(defcfg)
+(defsrc)
+(deflayer base)
+";
+    let icfg = parse_cfg(source).expect("passes");
+    assert_eq!(icfg.options.tcp_server_format, TcpServerFormat::Legacy);
+
+    let source = "
+(defcfg tcp-server-format json)
+(defsrc)
+(deflayer base)
+";
+    parse_cfg(source).map(|_| ()).expect_err("fails");
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+fn unix_socket_path_parses() {
+    let source = r#"
+(defcfg unix-socket-path "/tmp/kanata test.sock")
+(defsrc)
+(deflayer base)
+"#;
+    let icfg = parse_cfg(source).expect("passes");
+    assert_eq!(
+        icfg.options.unix_socket_path,
+        Some(UnixSocketPath::Path("/tmp/kanata test.sock".into()))
+    );
+
+    let source = "
+(defcfg unix-socket-path yes)
+(defsrc)
+(deflayer base)
+";
+    let icfg = parse_cfg(source).expect("passes");
+    assert_eq!(icfg.options.unix_socket_path, Some(UnixSocketPath::Default));
+
+    let source = "
+(defcfg unix-socket-path no)
+(defsrc)
+(deflayer base)
+";
+    let icfg = parse_cfg(source).expect("passes");
+    assert_eq!(icfg.options.unix_socket_path, None);
+}
+
+#[test]
+fn on_idle_and_on_resume_parse() {
+    let source = "
+(defcfg on-idle (30000 (layer-switch base)) on-resume (macro a b))
+(defvirtualkeys vk a)
+(defsrc)
+(deflayer base)
+";
+    let icfg = parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("passes");
+    // Hook actions are placed after the virtual keys.
+    assert_eq!(icfg.idle_hooks.on_idle, Some((30000, 1)));
+    assert_eq!(icfg.idle_hooks.on_resume, Some(2));
+
+    let source = "
+(defcfg on-idle (0 a))
+(defsrc)
+(deflayer base)
+";
+    parse_cfg(source).map(|_| ()).expect_err("fails");
+
+    for invalid in [
+        "on-idle 1000",
+        "on-idle 1000 a",
+        "on-idle (1000)",
+        "on-idle (1000 a b)",
+        "on-idle ((1000) a)",
+    ] {
+        let source = format!("(defcfg {invalid})\n(defsrc)\n(deflayer base)");
+        parse_cfg(&source).map(|_| ()).expect_err(invalid);
+    }
+
+    let source = "
+(defcfg on-resume a)
+(defsrc)
+(deflayer base)
+";
+    parse_cfg(source).map(|_| ()).expect_err("fails");
+}
+
+#[test]
+fn on_exit_parses() {
+    let source = "
+(defcfg on-exit (macro a b) on-exit-timeout 500)
+(defvirtualkeys vk a)
+(defsrc)
+(deflayer base)
+";
+    let icfg = parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("passes");
+    assert_eq!(icfg.on_exit, Some(1));
+    assert_eq!(icfg.options.on_exit_timeout, 500);
+
+    let icfg = parse_cfg("(defsrc)(deflayer base)").expect("passes");
+    assert_eq!(icfg.on_exit, None);
+    assert_eq!(icfg.options.on_exit_timeout, 3000);
+
+    let source = "
+(defcfg on-exit-timeout 0)
+(defsrc)
+(deflayer base)
+";
+    let e = parse_cfg(source).map(|_| ()).expect_err("fails");
+    assert!(
+        e.msg.contains("non-zero number of milliseconds"),
+        "{}",
+        e.msg
+    );
+}
+
+#[test]
+fn defidle_parse() {
+    let source = "
+(defcfg on-idle (30000 a))
+(defidle 5000 (layer-switch base) (300000 other) b)
+(defsrc)
+(deflayer base)
+(deflayer other)
+";
+    let icfg = parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("passes");
+    assert_eq!(icfg.idle_hooks.on_idle, Some((30000, 0)));
+    assert_eq!(
+        icfg.idle_hooks.watchers,
+        vec![
+            IdleWatcher {
+                idle_ms: 5000,
+                layer: None,
+                y: 1,
+            },
+            IdleWatcher {
+                idle_ms: 300000,
+                layer: Some(1),
+                y: 2,
+            },
+        ]
+    );
+
+    for source in [
+        "(defidle 0 a) (defsrc) (deflayer base)",
+        "(defidle 1000) (defsrc) (deflayer base)",
+        "(defidle (1000 nope) a) (defsrc) (deflayer base)",
+        "(defidle (1000) a) (defsrc) (deflayer base)",
+    ] {
+        parse_cfg(source).map(|_| ()).expect_err("fails");
+    }
+}
+
+#[test]
+fn self_test_key_parses() {
+    let source = "
+(defcfg self-test-key f23)
+(defsrc)
+(deflayer base)
+";
+    let icfg = parse_cfg(source)
+        .map_err(|e| eprintln!("{:?}", miette::Error::from(e)))
+        .expect("passes");
+    assert_eq!(icfg.options.self_test_key, OsCode::KEY_F23);
+
+    let source = "
+(defcfg)
+(defsrc)
+(deflayer base)
+";
+    let icfg = parse_cfg(source).expect("passes");
+    assert_eq!(icfg.options.self_test_key, OsCode::KEY_F24);
+
+    let source = "
+(defcfg self-test-key notakey)
+(defsrc)
+(deflayer base)
+";
+    parse_cfg(source).map(|_| ()).expect_err("fails");
+}
@@ -30,6 +30,15 @@
 //!   -> chord: (space a)
 //!   -> output: "Washington"
 //!   -> note: do observe the two spaces between 'w' and 'a'
+//!
+//! "kr => kind regards,(macro ret)Alice"
+//!   -> chord: (k r)
+//!   -> output: "kind regards," then enter, then "Alice"
+//!   -> note: the (macro ...) part is parsed like the macro action
+//!
+//! "gco => (no-follow-case)git checkout"
+//!   -> chord: (g c o)
+//!   -> output: "git checkout", never capitalized by shift or caps-word
 use super::*;
 use crate::bail_expr;
 
@@ -66,6 +75,7 @@ mod inner {
     use super::*;
 
     use crate::anyhow_expr;
+    use crate::bail;
     use crate::subset::*;
 
     use parking_lot::Mutex;
@@ -150,11 +160,17 @@ mod inner {
     pub struct ZchChordOutput {
         pub zch_output: Box<[ZchOutput]>,
         pub zch_followups: Option<Arc<Mutex<ZchPossibleChords>>>,
+        /// Whether the output is capitalized when the chord is pressed with shift, and
+        /// uppercased with caps-word. False for `(no-follow-case)` outputs.
+        pub zch_follow_case: bool,
     }
 
     /// Zch output can be uppercase, lowercase, altgr, and shift-altgr characters.
     /// The parser should ensure all `OsCode`s in variants containing them
     /// are visible characters that are backspacable.
+    ///
+    /// The `Macro*` variants are the events of a `(macro ...)` part of the output. These are
+    /// typed as-is: they are never erased and capitalization and smart space do not apply.
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub enum ZchOutput {
         Lowercase(OsCode),
@@ -165,32 +181,33 @@ mod inner {
         NoEraseUppercase(OsCode),
         NoEraseAltGr(OsCode),
         NoEraseShiftAltGr(OsCode),
+        MacroPress(OsCode),
+        MacroRelease(OsCode),
+        /// Delay in milliseconds.
+        MacroDelay(u16),
     }
 
     impl ZchOutput {
-        pub fn osc(self) -> OsCode {
-            use ZchOutput::*;
-            match self {
-                Lowercase(osc)
-                | Uppercase(osc)
-                | AltGr(osc)
-                | ShiftAltGr(osc)
-                | NoEraseLowercase(osc)
-                | NoEraseUppercase(osc)
-                | NoEraseAltGr(osc)
-                | NoEraseShiftAltGr(osc) => osc,
-            }
+        /// The key of a character output. None for macro events.
+        pub fn osc(self) -> Option<OsCode> {
+            self.osc_and_is_noerase().map(|(osc, _)| osc)
         }
-        pub fn osc_and_is_noerase(self) -> (OsCode, bool) {
+        pub fn osc_and_is_noerase(self) -> Option<(OsCode, bool)> {
             use ZchOutput::*;
             match self {
-                Lowercase(osc) | Uppercase(osc) | AltGr(osc) | ShiftAltGr(osc) => (osc, false),
+                Lowercase(osc) | Uppercase(osc) | AltGr(osc) | ShiftAltGr(osc) => {
+                    Some((osc, false))
+                }
                 NoEraseLowercase(osc)
                 | NoEraseUppercase(osc)
                 | NoEraseAltGr(osc)
-                | NoEraseShiftAltGr(osc) => (osc, true),
+                | NoEraseShiftAltGr(osc) => Some((osc, true)),
+                MacroPress(_) | MacroRelease(_) | MacroDelay(_) => None,
             }
         }
+        pub fn is_macro(self) -> bool {
+            self.osc().is_none()
+        }
         pub fn display_len(outs: impl AsRef<[Self]>) -> i16 {
             outs.as_ref().iter().copied().fold(0i16, |mut len, out| {
                 len += out.output_char_count();
@@ -199,9 +216,9 @@ mod inner {
         }
         pub fn output_char_count(self) -> i16 {
             match self.osc_and_is_noerase() {
-                (OsCode::KEY_BACKSPACE, _) => -1,
-                (_, false) => 1,
-                (_, true) => 0,
+                Some((OsCode::KEY_BACKSPACE, _)) => -1,
+                Some((_, false)) => 1,
+                Some((_, true)) | None => 0,
             }
         }
     }
@@ -247,6 +264,10 @@ mod inner {
 
         /// Define keys for punctuation, which is relevant to smart space auto-erasure of added spaces.
         pub zch_cfg_smart_space_punctuation: HashSet<ZchOutput>,
+
+        /// Whether outputs follow the case of the chord: capitalized if the first chord key is
+        /// pressed with shift, and uppercased with caps-word.
+        pub zch_cfg_follow_case: bool,
     }
 
     impl Default for ZchConfig {
@@ -263,12 +284,15 @@ mod inner {
                     puncs.shrink_to_fit();
                     puncs
                 },
+                zch_cfg_follow_case: false,
             }
         }
     }
 
     const NO_ERASE: &str = "no-erase";
     const SINGLE_OUTPUT_MULTI_KEY: &str = "single-output";
+    const OUTPUT_MACRO_START: &str = "(macro ";
+    const OUTPUT_NO_FOLLOW_CASE: &str = "(no-follow-case)";
 
     enum ZchIoMappingType {
         NoErase,
@@ -326,6 +350,7 @@ mod inner {
         const CHORD_DEADLINE: &str = "on-first-press-chord-deadline";
         const SMART_SPACE: &str = "smart-space";
         const SMART_SPACE_PUNCTUATION: &str = "smart-space-punctuation";
+        const FOLLOW_CASE: &str = "follow-case";
 
         let mut idle_reactivate_time_seen = false;
         let mut key_name_mappings_seen = false;
@@ -333,6 +358,7 @@ mod inner {
         let mut smart_space_seen = false;
         let mut smart_space_punctuation_seen = false;
         let mut smart_space_punctuation_val_expr = None;
+        let mut follow_case_seen = false;
 
         let mut user_cfg_char_to_output: HashMap<char, Vec<ZchOutput>> = HashMap::default();
         let mut pairs = exprs[2..].chunks_exact(2);
@@ -391,6 +417,24 @@ mod inner {
                         })?;
                 }
 
+                FOLLOW_CASE => {
+                    if follow_case_seen {
+                        bail_expr!(
+                            config_name,
+                            "This is the 2nd instance; it can only be defined once"
+                        );
+                    }
+                    follow_case_seen = true;
+                    config.zch_cfg_follow_case = config_value
+                        .atom(s.vars())
+                        .and_then(|val| match val {
+                            "yes" => Some(true),
+                            "no" => Some(false),
+                            _ => None,
+                        })
+                        .ok_or_else(|| anyhow_expr!(&config_value, "Must be: yes | no"))?;
+                }
+
                 SMART_SPACE_PUNCTUATION => {
                     if smart_space_punctuation_seen {
                         bail_expr!(
@@ -591,37 +635,60 @@ mod inner {
                     }
 
                     let mut char_buf: [u8; 4] = [0; 4];
-                    let output = {
-                        output
-                            .chars()
-                            .try_fold(vec![], |mut zch_output, out_char| -> Result<_> {
-                                if let Some(out) = user_cfg_char_to_output.get(&out_char) {
-                                    zch_output.extend(out.iter());
-                                    return Ok(zch_output);
-                                }
-
-                                let out_key = out_char.to_lowercase().next().unwrap();
-                                let key_name = out_key.encode_utf8(&mut char_buf);
-                                let osc = match key_name as &str {
-                                    " " => OsCode::KEY_SPACE,
-                                    _ => str_to_oscode(key_name).ok_or_else(|| {
-                                        anyhow_expr!(
-                                            &exprs[1],
-                                            "Unknown output key name '{}':\n{}: {line}",
-                                            out_char,
-                                            line_number + 1,
-                                        )
-                                    })?,
-                                };
-                                let out = match out_char.is_uppercase() {
-                                    true => ZchOutput::Uppercase(osc),
-                                    false => ZchOutput::Lowercase(osc),
-                                };
-                                zch_output.push(out);
-                                Ok(zch_output)
-                            })?
-                            .into_boxed_slice()
-                    };
+                    let mut zch_output = vec![];
+                    let (follow_case, mut output_left_to_parse) =
+                        match output.strip_prefix(OUTPUT_NO_FOLLOW_CASE) {
+                            Some(output) => (false, output),
+                            None => (true, output),
+                        };
+                    while let Some(macro_start) = output_left_to_parse.find(OUTPUT_MACRO_START) {
+                        parse_zippy_text_output(
+                            &output_left_to_parse[..macro_start],
+                            &user_cfg_char_to_output,
+                            &mut zch_output,
+                        )
+                        .map_err(|out_char| {
+                            anyhow_expr!(
+                                &exprs[1],
+                                "Unknown output key name '{out_char}':\n{}: {line}",
+                                line_number + 1,
+                            )
+                        })?;
+                        let macro_len =
+                            output_macro_len(&output_left_to_parse[macro_start..]).ok_or_else(
+                                || {
+                                    anyhow_expr!(
+                                        &exprs[1],
+                                        "Unterminated (macro ...) in output:\n{}: {line}",
+                                        line_number + 1,
+                                    )
+                                },
+                            )?;
+                        let macro_text =
+                            &output_left_to_parse[macro_start..macro_start + macro_len];
+                        parse_zippy_macro_output(macro_text, s, &mut zch_output).map_err(|e| {
+                            anyhow_expr!(
+                                &exprs[1],
+                                "Invalid {macro_text} in output: {}\n{}: {line}",
+                                e.msg,
+                                line_number + 1,
+                            )
+                        })?;
+                        output_left_to_parse = &output_left_to_parse[macro_start + macro_len..];
+                    }
+                    parse_zippy_text_output(
+                        output_left_to_parse,
+                        &user_cfg_char_to_output,
+                        &mut zch_output,
+                    )
+                    .map_err(|out_char| {
+                        anyhow_expr!(
+                            &exprs[1],
+                            "Unknown output key name '{out_char}':\n{}: {line}",
+                            line_number + 1,
+                        )
+                    })?;
+                    let output = zch_output.into_boxed_slice();
                     let mut input_left_to_parse = input;
                     let mut chord_chars;
                     let mut input_chord = ZchInputKeys::zchik_new();
@@ -682,6 +749,7 @@ mod inner {
                                     Arc::new(ZchChordOutput {
                                         zch_output: output,
                                         zch_followups: None,
+                                        zch_follow_case: follow_case,
                                     }),
                                 );
                                 break;
@@ -698,6 +766,7 @@ mod inner {
                                             ZchChordOutput {
                                                 zch_output: next_nested_map.zch_output.clone(),
                                                 zch_followups: Some(map),
+                                                zch_follow_case: next_nested_map.zch_follow_case,
                                             }
                                             .into(),
                                         );
@@ -716,6 +785,7 @@ mod inner {
                                     Arc::new(ZchChordOutput {
                                         zch_output: Box::new([]),
                                         zch_followups: Some(map),
+                                        zch_follow_case: true,
                                     }),
                                 );
                             }
@@ -733,6 +803,86 @@ mod inner {
         ))
     }
 
+    /// Parses the characters of a text part of an output line. Returns the first character
+    /// without a known key on failure.
+    fn parse_zippy_text_output(
+        text: &str,
+        user_cfg_char_to_output: &HashMap<char, Vec<ZchOutput>>,
+        zch_output: &mut Vec<ZchOutput>,
+    ) -> std::result::Result<(), char> {
+        let mut char_buf: [u8; 4] = [0; 4];
+        for out_char in text.chars() {
+            if let Some(out) = user_cfg_char_to_output.get(&out_char) {
+                zch_output.extend(out.iter());
+                continue;
+            }
+            let out_key = out_char.to_lowercase().next().unwrap();
+            let key_name = out_key.encode_utf8(&mut char_buf);
+            let osc = match key_name as &str {
+                " " => OsCode::KEY_SPACE,
+                _ => str_to_oscode(key_name).ok_or(out_char)?,
+            };
+            zch_output.push(match out_char.is_uppercase() {
+                true => ZchOutput::Uppercase(osc),
+                false => ZchOutput::Lowercase(osc),
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the length of the `(macro ...)` at the start of `output`, up to and including
+    /// its matching closing parenthesis, or None if it is not closed.
+    fn output_macro_len(output: &str) -> Option<usize> {
+        let mut depth = 0;
+        for (i, c) in output.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i + 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Parses the items of a `(macro ...)` part of an output line like the macro action.
+    /// Only keys, output chords and delays are supported.
+    fn parse_zippy_macro_output(
+        macro_text: &str,
+        s: &ParserState,
+        zch_output: &mut Vec<ZchOutput>,
+    ) -> Result<()> {
+        let exprs = sexpr::parse(macro_text, "")?;
+        let items = &exprs[0].t[1..];
+        if items.is_empty() {
+            bail!("macro expects at least one item after it");
+        }
+        let mut items_remainder = items;
+        while !items_remainder.is_empty() {
+            let events;
+            (events, items_remainder) = parse_macro_item(items_remainder, s)?;
+            for event in events {
+                zch_output.push(match event {
+                    SequenceEvent::Press(kc) if kc != KEY_OVERLAP => {
+                        ZchOutput::MacroPress(kc.into())
+                    }
+                    SequenceEvent::Release(kc) if kc != KEY_OVERLAP => {
+                        ZchOutput::MacroRelease(kc.into())
+                    }
+                    SequenceEvent::Delay { duration } => {
+                        ZchOutput::MacroDelay(duration.try_into().unwrap_or(u16::MAX))
+                    }
+                    _ => bail!("only keys, output chords and delays are supported"),
+                });
+            }
+        }
+        Ok(())
+    }
+
     fn parse_single_zippy_output_mapping(
         output: &str,
         output_expr: &SExpr,
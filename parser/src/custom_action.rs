@@ -7,7 +7,10 @@ use anyhow::{Result, anyhow};
 use core::fmt;
 use kanata_keyberon::key_code::KeyCode;
 
-use crate::{cfg::SimpleSExpr, keys::OsCode};
+use crate::{
+    cfg::{KanataAction, SimpleSExpr},
+    keys::OsCode,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum CustomAction {
@@ -16,8 +19,15 @@ pub enum CustomAction {
     CmdOutputKeys(&'static [&'static str]),
     PushMessage(&'static [SimpleSExpr]),
     Unicode(char),
+    /// Types each character of `text` like `Unicode`, waiting `delay` ms between characters.
+    Str {
+        text: &'static str,
+        delay: u16,
+    },
     Mouse(Btn),
     MouseTap(Btn),
+    /// Toggles holding a mouse button down after the key is released, e.g. for dragging.
+    MouseLock(Btn),
     FakeKey {
         coord: Coord,
         action: FakeKeyAction,
@@ -26,6 +36,8 @@ pub enum CustomAction {
         coord: Coord,
         action: FakeKeyAction,
     },
+    /// Virtual key actions applied in order, ahead of other pending events.
+    FakeKeys(&'static [(Coord, FakeKeyAction)]),
     FakeKeyOnIdle(FakeKeyOnIdle),
     FakeKeyOnPhysicalIdle(FakeKeyOnIdle),
     FakeKeyHoldForDuration(FakeKeyHoldForDuration),
@@ -40,21 +52,40 @@ pub enum CustomAction {
     MWheelNotch {
         direction: MWheelDirection,
     },
+    /// Smooth scrolling in 1/120ths of a notch, ramping from `min_distance` to `max_distance`
+    /// over `accel_time` ms while held. An `accel_time` of 0 scrolls at a constant `min_distance`.
+    MWheelFine {
+        direction: MWheelDirection,
+        interval: u16,
+        accel_time: u16,
+        min_distance: u16,
+        max_distance: u16,
+    },
     MoveMouse {
         direction: MoveDirection,
-        interval: u16,
+        interval: RuntimeU16,
         distance: u16,
     },
     MoveMouseAccel {
         direction: MoveDirection,
-        interval: u16,
+        interval: RuntimeU16,
         accel_time: u16,
         min_distance: u16,
         max_distance: u16,
     },
+    MoveMousePolar {
+        /// Degrees clockwise from up.
+        angle: u16,
+        /// Pixels per second.
+        speed: u16,
+    },
     MoveMouseSpeed {
         speed: u16,
+        /// Whether the speed also applies to `mwheel` actions.
+        scale_mwheel: bool,
     },
+    /// Restores the normal speed, even while `movemouse-speed` keys are held.
+    MoveMouseSpeedReset,
     SequenceCancel,
     SequenceLeader(u16, SequenceInputMode),
     /// Purpose:
@@ -63,6 +94,9 @@ pub enum CustomAction {
     /// is larger than the number of backspace-able symbols typed within the application.
     /// This custom action is a marker to accomplish the use case.
     SequenceNoerase(u16),
+    /// Outputs the keys matched by the `_` and `(any ...)` steps of the most recently completed
+    /// sequence, so that its virtual key can incorporate them.
+    SequenceMatch,
     LiveReload,
     LiveReloadNext,
     LiveReloadPrev,
@@ -74,6 +108,8 @@ pub enum CustomAction {
     Repeat,
     CancelMacroOnRelease,
     CancelMacroOnNextPress(u32),
+    /// Overrides `clean-mods-on-action` for macros started by the same key press.
+    CleanModsOnAction(bool),
     DynamicMacroRecord(u16),
     DynamicMacroRecordStop(u16),
     DynamicMacroPlay(u16),
@@ -83,6 +119,16 @@ pub enum CustomAction {
         x: u16,
         y: u16,
     },
+    /// Return to the first layer, dropping all held, latched and pending state.
+    ClearState,
+    /// While held, all other key presses are written out without being processed.
+    PassthroughWhileHeld,
+    /// Moves the mouse to a position given in percent of the work area of a monitor.
+    SetMousePct {
+        x: u8,
+        y: u8,
+        monitor: MonitorSelector,
+    },
     Unmodded {
         keys: &'static [KeyCode],
         mods: UnmodMods,
@@ -91,6 +137,17 @@ pub enum CustomAction {
         keys: &'static [KeyCode],
     },
     ReverseReleaseOrder,
+    /// Keys pressed together by `chord-output`. While they are held, `clean-mods-on-action`
+    /// applies to the other modifiers.
+    ChordOutput(&'static [KeyCode]),
+    /// The steps of `multi-seq` after the first one, each activated at the key's coordinate after
+    /// its delay while the key is still held.
+    MultiSeq(&'static [MultiSeqStep]),
+    /// Presses a key that stays held, regardless of physical key releases, until a `release-key`
+    /// of the same key.
+    PressKey(KeyCode),
+    /// Releases a key held by `PressKey`.
+    ReleaseKey(KeyCode),
     ClipboardSet(&'static str),
     ClipboardCmdSet(&'static [&'static str]),
     ClipboardSave(u16),
@@ -98,6 +155,38 @@ pub enum CustomAction {
     ClipboardSaveSet(u16, &'static str),
     ClipboardSaveCmdSet(u16, &'static [&'static str]),
     ClipboardSaveSwap(u16, u16),
+    /// Deactivates the layer of `layer-while-held-timeout` if no key is pressed for `timeout` ms
+    /// while it is held.
+    LayerWhileHeldTimeout {
+        timeout: u32,
+    },
+    /// Activate the layer until the matching [`CustomAction::LayerPop`].
+    LayerPush(u16),
+    /// Deactivate the layer of the most recent [`CustomAction::LayerPush`] that is still active.
+    LayerPop,
+    /// Switch the base layer to the next layer of the `layer-cycle` with this number.
+    LayerCycle(u16),
+    /// Tap to activate the layer for the next key press, hold to activate it while held,
+    /// tap twice to lock it. A press held for `timeout` ms is never counted as a tap.
+    StickyLayer {
+        layer: u16,
+        timeout: u16,
+    },
+    /// A raw HID usage, pressed while the key is held. Only sent on macOS.
+    Hid {
+        page: u16,
+        id: u16,
+    },
+    /// Set the runtime variable numbered `var`.
+    SetVar {
+        var: u16,
+        value: i32,
+    },
+    /// Add `amount` to the runtime variable numbered `var`, treating an unset variable as 0.
+    IncVar {
+        var: u16,
+        amount: i32,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -121,6 +210,30 @@ impl fmt::Display for Btn {
     }
 }
 
+/// A step of `multi-seq`. Steps compare equal only if they are the same allocation, since actions
+/// do not implement `Eq` and `Hash`.
+#[derive(Debug, Clone, Copy)]
+pub struct MultiSeqStep {
+    /// Milliseconds after the previous step.
+    pub delay: u16,
+    pub action: &'static KanataAction,
+}
+
+impl PartialEq for MultiSeqStep {
+    fn eq(&self, other: &Self) -> bool {
+        self.delay == other.delay && std::ptr::eq(self.action, other.action)
+    }
+}
+
+impl Eq for MultiSeqStep {}
+
+impl std::hash::Hash for MultiSeqStep {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.delay.hash(state);
+        std::ptr::hash(self.action, state);
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Coord {
     pub x: u8,
@@ -209,10 +322,33 @@ impl fmt::Display for MoveDirection {
     }
 }
 
+/// The monitor that `setmouse-pct` positions the mouse on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MonitorSelector {
+    /// The monitor that the mouse is on.
+    Current,
+    Primary,
+    /// Index of the monitor in the order that the OS lists them, starting at 0.
+    Index(u8),
+}
+
+/// A number in an action that is either fixed in the configuration or read from a runtime
+/// variable, written as `$name`, when the action runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuntimeU16 {
+    Num(u16),
+    /// Index of the runtime variable.
+    Var(u16),
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CapsWordCfg {
     pub keys_to_capitalize: &'static [KeyCode],
     pub keys_nonterminal: &'static [KeyCode],
+    /// Keys that are replaced by another key while caps-word is active, e.g. space by minus.
+    /// They do not terminate caps-word; the replacement is capitalized if it is in
+    /// `keys_to_capitalize`.
+    pub keys_rewrite: &'static [(KeyCode, KeyCode)],
     pub timeout: u16,
     pub repress_behaviour: CapsWordRepressBehaviour,
 }
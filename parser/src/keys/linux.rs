@@ -762,16 +762,3 @@ impl OsCode {
         }
     }
 }
-
-use crate::custom_action::Btn;
-impl From<Btn> for OsCode {
-    fn from(btn: Btn) -> Self {
-        match btn {
-            Btn::Left => OsCode::BTN_LEFT,
-            Btn::Right => OsCode::BTN_RIGHT,
-            Btn::Mid => OsCode::BTN_MIDDLE,
-            Btn::Forward => OsCode::BTN_EXTRA,
-            Btn::Backward => OsCode::BTN_SIDE,
-        }
-    }
-}
@@ -1,4 +1,23 @@
 use super::OsCode;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap as HashMap;
+
+/// HID usage `(page, code)` to `OsCode` overrides from `deflocalkeys-macos`.
+static PAGE_CODE_OVERRIDES: Lazy<Mutex<HashMap<(u32, u32), OsCode>>> =
+    Lazy::new(|| Mutex::new(HashMap::default()));
+
+/// Replaces the HID usage overrides that [`page_code_override`] consults.
+pub fn replace_page_code_overrides(overrides: &HashMap<(u32, u32), OsCode>) {
+    let mut cur = PAGE_CODE_OVERRIDES.lock();
+    cur.clone_from(overrides);
+    cur.shrink_to_fit();
+}
+
+/// Returns the configured `OsCode` for a HID usage, if it overrides the built-in mapping.
+pub fn page_code_override(pc: &PageCode) -> Option<OsCode> {
+    PAGE_CODE_OVERRIDES.lock().get(&(pc.page, pc.code)).copied()
+}
 
 // because the parser can't handle oscode u16 values > 767
 // and macos has fucked up key coding (page and code)
@@ -606,6 +625,18 @@ impl TryFrom<OsCode> for PageCode {
                 page: 0x0C,
                 code: 0xCD,
             }),
+            OsCode::KEY_PLAYCD => Ok(PageCode {
+                page: 0x0C,
+                code: 0xB0,
+            }),
+            OsCode::KEY_PAUSECD => Ok(PageCode {
+                page: 0x0C,
+                code: 0xB1,
+            }),
+            OsCode::KEY_STOPCD => Ok(PageCode {
+                page: 0x0C,
+                code: 0xB7,
+            }),
             OsCode::KEY_FN => Ok(PageCode {
                 page: 0xFF,
                 code: 0x03,
@@ -1278,6 +1309,18 @@ impl TryFrom<PageCode> for OsCode {
                 page: 0x0C,
                 code: 0xCD,
             } => Ok(OsCode::KEY_PLAYPAUSE),
+            PageCode {
+                page: 0x0C,
+                code: 0xB0,
+            } => Ok(OsCode::KEY_PLAYCD),
+            PageCode {
+                page: 0x0C,
+                code: 0xB1,
+            } => Ok(OsCode::KEY_PAUSECD),
+            PageCode {
+                page: 0x0C,
+                code: 0xB7,
+            } => Ok(OsCode::KEY_STOPCD),
             PageCode {
                 page: 0xFF,
                 code: 0x03,
@@ -12,13 +12,15 @@ mod macos;
 #[cfg(any(target_os = "windows", target_os = "unknown"))]
 mod windows;
 #[cfg(any(target_os = "macos", target_os = "unknown"))]
-pub use macos::PageCode;
+pub use macos::{PageCode, page_code_override, replace_page_code_overrides};
 
 #[cfg(target_os = "windows")]
 pub use windows::VK_KPENTER_FAKE;
 
 mod mappings;
 
+pub mod scancode;
+
 #[cfg(target_os = "unknown")]
 #[derive(Clone, Copy)]
 pub enum Platform {
@@ -253,7 +255,7 @@ pub fn str_to_oscode(s: &str) -> Option<OsCode> {
         "IntlBackslash" | "102d" | "lsgt" | "nubs" | "nonusbslash" | "﹨" | "<" => OsCode::KEY_102ND,
         "ScrollLock" | "scrlck" | "slck" | "⇳🔒" => OsCode::KEY_SCROLLLOCK,
         "Pause" | "pause" | "break" | "brk" => OsCode::KEY_PAUSE,
-        "WakeUp" | "wkup" => OsCode::KEY_WAKEUP,
+        "WakeUp" | "wkup" | "system-wake" => OsCode::KEY_WAKEUP,
         "Escape" | "esc" | "⎋" => OsCode::KEY_ESC,
         "ShiftRight" | "RightShift" | "rshift" | "rshft" | "rsft" | "⇧›" => OsCode::KEY_RIGHTSHIFT,
         "ControlLeft" | "lctrl" | "lctl" | "ctl" | "‹⎈" | "‹⌃" => OsCode::KEY_LEFTCTRL,
@@ -279,17 +281,29 @@ pub fn str_to_oscode(s: &str) -> Option<OsCode> {
         "Home" | "home" | "⇤" | "⤒" | "↖" | "⇱" => OsCode::KEY_HOME,
         "End" | "end" | "⇥" | "⤓" | "↘" | "⇲" => OsCode::KEY_END,
         "NumLock" | "nlck" | "nlk" | "⇭"=> OsCode::KEY_NUMLOCK,
-        "VolumeMute" | "mute"  | "🔇" | "🔈⓪" | "🔈⓿" | "🔈₀" => OsCode::KEY_MUTE,
-        "VolumeUp" | "volu" | "🔊" | "🔈+" | "🔈➕" | "🔈₊" | "🔈⊕" => OsCode::KEY_VOLUMEUP,
-        "VolumeDown" | "voldwn" | "vold" | "🔉" | "🔈−" | "🔈➖" | "🔈₋" | "🔈⊖" => OsCode::KEY_VOLUMEDOWN,
+        "VolumeMute" | "mute" | "media-mute" | "🔇" | "🔈⓪" | "🔈⓿" | "🔈₀" => OsCode::KEY_MUTE,
+        "VolumeUp" | "volu" | "media-vol-up" | "🔊" | "🔈+" | "🔈➕" | "🔈₊" | "🔈⊕" => OsCode::KEY_VOLUMEUP,
+        "VolumeDown" | "voldwn" | "vold" | "media-vol-down" | "🔉" | "🔈−" | "🔈➖" | "🔈₋" | "🔈⊖" => OsCode::KEY_VOLUMEDOWN,
         "EjectCD" | "eject" => OsCode::KEY_EJECTCD,
         "brup" | "bru" | "🔆" => OsCode::KEY_BRIGHTNESSUP,
         "brdown" | "brdwn" | "brdn" | "🔅" => OsCode::KEY_BRIGHTNESSDOWN,
         "blup" | "⌨💡+" | "⌨💡➕" | "⌨💡₊" | "⌨💡⊕" => OsCode::KEY_KBDILLUMUP,
         "bldn" | "⌨💡−" | "⌨💡➖" | "⌨💡₋" | "⌨💡⊖" => OsCode::KEY_KBDILLUMDOWN,
-        "MediaTrackNext" | "next" | "▶▶" => OsCode::KEY_NEXTSONG,
+        "MediaTrackNext" | "next" | "media-next-track" | "▶▶" => OsCode::KEY_NEXTSONG,
         "MediaPlayPause" | "pp" | "▶⏸" => OsCode::KEY_PLAYPAUSE,
-        "MediaTrackPrevious" | "prev" | "◀◀" => OsCode::KEY_PREVIOUSSONG,
+        // Windows has no separate play and pause keys and reports its media stop key as
+        // KEY_STOP, so the names map to the keys that Windows input actually produces.
+        #[cfg(target_os = "windows")]
+        "MediaPlay" | "media-play" | "MediaPause" | "media-pause" => OsCode::KEY_PLAYPAUSE,
+        #[cfg(target_os = "windows")]
+        "MediaStop" | "media-stop" => OsCode::KEY_STOP,
+        #[cfg(not(target_os = "windows"))]
+        "MediaPlay" | "media-play" => OsCode::KEY_PLAYCD,
+        #[cfg(not(target_os = "windows"))]
+        "MediaPause" | "media-pause" => OsCode::KEY_PAUSECD,
+        #[cfg(not(target_os = "windows"))]
+        "MediaStop" | "media-stop" => OsCode::KEY_STOPCD,
+        "MediaTrackPrevious" | "prev" | "media-prev-track" | "◀◀" => OsCode::KEY_PREVIOUSSONG,
         "F1" | "f1" => OsCode::KEY_F1,
         "F2" | "f2" => OsCode::KEY_F2,
         "F3" | "f3" => OsCode::KEY_F3,
@@ -356,10 +370,9 @@ pub fn str_to_oscode(s: &str) -> Option<OsCode> {
         // NOTE: these are linux-only right now due to missing the mappings in windows.rs
         #[cfg(any(target_os = "linux", target_os = "android", target_os = "unknown"))]
         "plyr" | "player" => OsCode::KEY_PLAYER,
-        #[cfg(any(target_os = "linux", target_os = "android", target_os = "unknown"))]
-        "powr" | "power" => OsCode::KEY_POWER,
-        #[cfg(any(target_os = "linux", target_os = "android", target_os = "unknown"))]
-        "zzz" | "sleep" => OsCode::KEY_SLEEP,
+        #[cfg(not(target_os = "windows"))]
+        "powr" | "power" | "system-power" => OsCode::KEY_POWER,
+        "zzz" | "sleep" | "system-sleep" => OsCode::KEY_SLEEP,
 
         "sls" | "SpotLightSearch" => OsCode::KEY_249,
         "dtn" | "Dictation" => OsCode::KEY_250,
@@ -388,6 +401,89 @@ pub fn str_to_oscode(s: &str) -> Option<OsCode> {
     })
 }
 
+/// Convert an `OsCode` to the short name used for it in configurations, e.g. `lalt` for
+/// `KEY_LEFTALT`. Keys without a short name use the lowercase `Display` form, which is the
+/// configuration name of letters, digits and function keys, or the lowercase `Debug` form if
+/// that name belongs to another key.
+pub fn oscode_to_str(osc: OsCode) -> String {
+    use OsCode::*;
+    let name = match osc {
+        KEY_GRAVE => "grv",
+        KEY_MINUS => "min",
+        KEY_EQUAL => "eql",
+        KEY_BACKSPACE => "bspc",
+        KEY_LEFTBRACE => "lbrc",
+        KEY_RIGHTBRACE => "rbrc",
+        KEY_CAPSLOCK => "caps",
+        KEY_SEMICOLON => "scln",
+        KEY_APOSTROPHE => "apo",
+        KEY_ENTER => "ret",
+        KEY_LEFTSHIFT => "lsft",
+        KEY_RIGHTSHIFT => "rsft",
+        KEY_LEFTCTRL => "lctl",
+        KEY_RIGHTCTRL => "rctl",
+        KEY_LEFTALT => "lalt",
+        KEY_RIGHTALT => "ralt",
+        KEY_LEFTMETA => "lmet",
+        KEY_RIGHTMETA => "rmet",
+        KEY_COMMA => "comm",
+        KEY_DOT => ".",
+        KEY_SLASH => "/",
+        KEY_BACKSLASH => "bksl",
+        KEY_SPACE => "spc",
+        KEY_ESC => "esc",
+        KEY_COMPOSE => "comp",
+        KEY_DELETE => "del",
+        KEY_INSERT => "ins",
+        KEY_PAGEUP => "pgup",
+        KEY_PAGEDOWN => "pgdn",
+        KEY_RIGHT => "rght",
+        KEY_LEFT => "left",
+        KEY_NUMLOCK => "nlck",
+        KEY_SCROLLLOCK => "slck",
+        KEY_102ND => "102d",
+        KEY_YEN => "¥",
+        KEY_KPENTER => "kprt",
+        KEY_KPSLASH => "kp/",
+        KEY_KPPLUS => "kp+",
+        KEY_KPASTERISK => "kp*",
+        KEY_KPMINUS => "kp-",
+        KEY_KPDOT => "kp.",
+        KEY_MUTE => "mute",
+        KEY_VOLUMEUP => "volu",
+        KEY_VOLUMEDOWN => "vold",
+        KEY_NEXTSONG => "next",
+        KEY_PLAYPAUSE => "pp",
+        KEY_PREVIOUSSONG => "prev",
+        #[cfg(target_os = "windows")]
+        KEY_STOP => "media-stop",
+        #[cfg(not(target_os = "windows"))]
+        KEY_PLAYCD => "media-play",
+        #[cfg(not(target_os = "windows"))]
+        KEY_PAUSECD => "media-pause",
+        #[cfg(not(target_os = "windows"))]
+        KEY_STOPCD => "media-stop",
+        BTN_LEFT => "mlft",
+        BTN_RIGHT => "mrgt",
+        BTN_MIDDLE => "mmid",
+        BTN_SIDE => "mbck",
+        BTN_EXTRA => "mfwd",
+        MouseWheelUp => "mwu",
+        MouseWheelDown => "mwd",
+        MouseWheelLeft => "mwl",
+        MouseWheelRight => "mwr",
+        _ => {
+            let name = osc.to_string().to_lowercase();
+            // Avoid names that configurations use for a different key, e.g. `menu`.
+            return match str_to_oscode(&name) {
+                Some(parsed) if parsed != osc => format!("{osc:?}").to_lowercase(),
+                _ => name,
+            };
+        }
+    };
+    name.to_owned()
+}
+
 /// This is a shameless copy of evdev_rs::enums::EV_KEY.
 /// I've added the Copy trait and I'll be able
 /// to added my own Impl(s) to it
@@ -1263,3 +1359,16 @@ impl From<&KeyCode> for OsCode {
         (*item).into()
     }
 }
+
+use crate::custom_action::Btn;
+impl From<Btn> for OsCode {
+    fn from(btn: Btn) -> Self {
+        match btn {
+            Btn::Left => OsCode::BTN_LEFT,
+            Btn::Right => OsCode::BTN_RIGHT,
+            Btn::Mid => OsCode::BTN_MIDDLE,
+            Btn::Forward => OsCode::BTN_EXTRA,
+            Btn::Backward => OsCode::BTN_SIDE,
+        }
+    }
+}
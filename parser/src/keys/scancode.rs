@@ -1,7 +1,9 @@
-use kanata_parser::keys::OsCode;
+//! The PC scan code set 1 numbering of physical key positions. Extended keys, which are sent
+//! with an `E0` prefix byte, are numbered `0xE000` plus their scan code.
+
+use super::OsCode;
 
 #[rustfmt::skip]
-#[allow(unused)]
 pub fn u16_to_osc(input: u16) -> Option<OsCode> {
     Some(if input < 0xE000 {
         match input {
@@ -170,8 +172,7 @@ pub fn u16_to_osc(input: u16) -> Option<OsCode> {
     })
 }
 
-#[allow(unused)]
-pub(crate) fn osc_to_u16(osc: OsCode) -> Option<u16> {
+pub fn osc_to_u16(osc: OsCode) -> Option<u16> {
     Some(match osc {
         OsCode::KEY_ESC => 0x01,
         OsCode::KEY_1 => 0x02,
@@ -403,6 +403,7 @@ impl OsCode {
             VK_OEM_8 => Some(OsCode::KEY_252),
             VK_OEM_102 => Some(OsCode::KEY_102ND),
             VK_PLAY => Some(OsCode::KEY_PLAY),
+            VK_SLEEP => Some(OsCode::KEY_SLEEP),
             VK_SNAPSHOT => Some(OsCode::KEY_PRINT),
             VK_BROWSER_SEARCH => Some(OsCode::KEY_SEARCH),
             VK_BROWSER_FAVORITES => Some(OsCode::KEY_FAVORITES),
@@ -1045,6 +1046,7 @@ impl OsCode {
             OsCode::KEY_252 => VK_OEM_8,
             OsCode::KEY_102ND => VK_OEM_102,
             OsCode::KEY_PLAY => VK_PLAY,
+            OsCode::KEY_SLEEP => VK_SLEEP,
             OsCode::KEY_PRINT => VK_SNAPSHOT,
             OsCode::KEY_SEARCH => VK_BROWSER_SEARCH,
             OsCode::KEY_FAVORITES => VK_BROWSER_FAVORITES,
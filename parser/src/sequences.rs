@@ -1,3 +1,4 @@
+use crate::keys::OsCode;
 use kanata_keyberon::key_code::KeyCode;
 
 pub const MASK_KEYCODES: u16 = 0x03FF;
@@ -22,8 +23,77 @@ pub fn mod_mask_for_keycode(kc: KeyCode) -> u16 {
     }
 }
 
+/// Keys that the `_` wildcard step of a sequence can match,
+/// each either on its own or together with shift.
+pub const SEQ_WILDCARD_KEYS: &[OsCode] = {
+    use OsCode::*;
+    &[
+        KEY_A,
+        KEY_B,
+        KEY_C,
+        KEY_D,
+        KEY_E,
+        KEY_F,
+        KEY_G,
+        KEY_H,
+        KEY_I,
+        KEY_J,
+        KEY_K,
+        KEY_L,
+        KEY_M,
+        KEY_N,
+        KEY_O,
+        KEY_P,
+        KEY_Q,
+        KEY_R,
+        KEY_S,
+        KEY_T,
+        KEY_U,
+        KEY_V,
+        KEY_W,
+        KEY_X,
+        KEY_Y,
+        KEY_Z,
+        KEY_1,
+        KEY_2,
+        KEY_3,
+        KEY_4,
+        KEY_5,
+        KEY_6,
+        KEY_7,
+        KEY_8,
+        KEY_9,
+        KEY_0,
+        KEY_GRAVE,
+        KEY_MINUS,
+        KEY_EQUAL,
+        KEY_LEFTBRACE,
+        KEY_RIGHTBRACE,
+        KEY_BACKSLASH,
+        KEY_SEMICOLON,
+        KEY_APOSTROPHE,
+        KEY_COMMA,
+        KEY_DOT,
+        KEY_SLASH,
+        KEY_SPACE,
+    ]
+};
+
+/// Modifier keys to hold while outputting a sequence key that has the given modifier bits set.
+pub fn mods_for_mod_mask(mask: u16) -> impl Iterator<Item = OsCode> {
+    [
+        (0x8000, OsCode::KEY_LEFTSHIFT),
+        (0x4000, OsCode::KEY_LEFTCTRL),
+        (0x2000, OsCode::KEY_LEFTALT),
+        (0x1000, OsCode::KEY_RIGHTALT),
+        (0x0800, OsCode::KEY_LEFTMETA),
+    ]
+    .into_iter()
+    .filter(move |(bit, _)| mask & bit != 0)
+    .map(|(_, osc)| osc)
+}
+
 #[test]
 fn keys_fit_within_mask() {
-    use crate::keys::OsCode;
     assert!(MASK_KEYCODES >= u16::from(OsCode::KEY_MAX));
 }
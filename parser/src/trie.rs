@@ -71,6 +71,11 @@ impl<T> Trie<T> {
         }
     }
 
+    /// Iterates over the values of `key` and every key that starts with it.
+    pub fn descendant_values<'a>(&'a self, key: &'a [u16]) -> impl Iterator<Item = &'a T> {
+        self.inner.iter_prefix(cast_slice(key)).map(|(_, val)| val)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.inner.is_empty()
     }
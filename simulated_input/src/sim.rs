@@ -80,9 +80,17 @@ test/sim.txt in the current working directory and
     /// This flag generates an error if the binary is compiled without simulated output.
     #[arg(short = 'o', long, verbatim_doc_comment)]
     out: Option<String>,
+    /// Use this timeout in milliseconds for every tap-hold-adaptive action
+    /// instead of scaling it from the simulated typing cadence.
+    #[arg(long, value_name = "MS", verbatim_doc_comment)]
+    adaptive_timeout: Option<u16>,
+    /// Enable debug logging, which includes the computed tap-hold-adaptive
+    /// timeouts.
+    #[arg(short, long)]
+    debug: bool,
 }
 
-fn log_init() {
+fn log_init(debug: bool) {
     let mut log_cfg = ConfigBuilder::new();
     if let Err(e) = log_cfg.set_time_offset_to_local() {
         eprintln!("WARNING: could not set log TZ to local: {e:?}");
@@ -92,7 +100,11 @@ fn log_init() {
         "[hour]:[minute]:[second].[subsecond digits:4]"
     ));
     CombinedLogger::init(vec![TermLogger::new(
-        LevelFilter::Info,
+        if debug {
+            LevelFilter::Debug
+        } else {
+            LevelFilter::Info
+        },
         log_cfg.build(),
         TerminalMode::Stderr,
         ColorChoice::AlwaysAnsi,
@@ -100,9 +112,8 @@ fn log_init() {
     .expect("logger can init");
 }
 
-/// Parse CLI arguments
-fn cli_init_fsim() -> Result<(ValidatedArgs, Vec<PathBuf>, Option<String>)> {
-    let args = Args::parse();
+/// Validate CLI arguments
+fn cli_init_fsim(args: Args) -> Result<(ValidatedArgs, Vec<PathBuf>, Option<String>)> {
     let cfg_paths = args.cfg.unwrap_or_else(default_cfg);
     let sim_paths = args.sim.unwrap_or_else(default_sim);
     let sim_appendix = args.out;
@@ -247,8 +258,10 @@ fn kbd_out_log(
     }
 }
 fn main_impl() -> Result<()> {
-    log_init();
-    let (args, sim_paths, _sim_appendix) = cli_init_fsim()?;
+    let args = Args::parse();
+    log_init(args.debug);
+    let adaptive_timeout = args.adaptive_timeout;
+    let (args, sim_paths, _sim_appendix) = cli_init_fsim(args)?;
     #[cfg(not(feature = "simulated_output"))]
     {
         if _sim_appendix.is_some() {
@@ -260,6 +273,7 @@ fn main_impl() -> Result<()> {
 
     for config_sim_file in &sim_paths {
         let mut k = Kanata::new(&args)?;
+        k.layout.bm().pinned_adaptive_timeout = adaptive_timeout;
         log::info!("Evaluating simulation file = {:?}", config_sim_file);
         let s = std::fs::read_to_string(config_sim_file)?;
         for l in s.lines() {
@@ -275,28 +289,19 @@ fn main_impl() -> Result<()> {
                             let key_code =
                                 str_to_oscode(val).ok_or_else(|| anyhow!("unknown key: {val}"))?;
                             kbd_out_log(&mut k.kbd_out, LogFmtT::InKeyDown, Some(key_code), None);
-                            k.handle_input_event(&KeyEvent {
-                                code: key_code,
-                                value: KeyValue::Press,
-                            })?;
+                            k.handle_input_event(&KeyEvent::new(key_code, KeyValue::Press))?;
                         }
                         "release" | "↑" | "u" | "up" => {
                             let key_code =
                                 str_to_oscode(val).ok_or_else(|| anyhow!("unknown key: {val}"))?;
                             kbd_out_log(&mut k.kbd_out, LogFmtT::InKeyUp, Some(key_code), None);
-                            k.handle_input_event(&KeyEvent {
-                                code: key_code,
-                                value: KeyValue::Release,
-                            })?;
+                            k.handle_input_event(&KeyEvent::new(key_code, KeyValue::Release))?;
                         }
                         "repeat" | "⟳" | "r" => {
                             let key_code =
                                 str_to_oscode(val).ok_or_else(|| anyhow!("unknown key: {val}"))?;
                             kbd_out_log(&mut k.kbd_out, LogFmtT::InKeyRep, Some(key_code), None);
-                            k.handle_input_event(&KeyEvent {
-                                code: key_code,
-                                value: KeyValue::Repeat,
-                            })?;
+                            k.handle_input_event(&KeyEvent::new(key_code, KeyValue::Repeat))?;
                         }
                         // Virtual/fake key activation: fakekey:name[:action] or vk:name[:action]
                         // Supported actions: press, release, tap, toggle
@@ -331,19 +336,13 @@ fn main_impl() -> Result<()> {
                                     Some(key_code),
                                     None,
                                 );
-                                k.handle_input_event(&KeyEvent {
-                                    code: key_code,
-                                    value: KeyValue::Press,
-                                })?;
+                                k.handle_input_event(&KeyEvent::new(key_code, KeyValue::Press))?;
                             }
                             "↑" => {
                                 let key_code = str_to_oscode(val)
                                     .ok_or_else(|| anyhow!("unknown key: {val}"))?;
                                 kbd_out_log(&mut k.kbd_out, LogFmtT::InKeyUp, Some(key_code), None);
-                                k.handle_input_event(&KeyEvent {
-                                    code: key_code,
-                                    value: KeyValue::Release,
-                                })?;
+                                k.handle_input_event(&KeyEvent::new(key_code, KeyValue::Release))?;
                             }
                             "⟳" => {
                                 let key_code = str_to_oscode(val)
@@ -354,10 +353,7 @@ fn main_impl() -> Result<()> {
                                     Some(key_code),
                                     None,
                                 );
-                                k.handle_input_event(&KeyEvent {
-                                    code: key_code,
-                                    value: KeyValue::Repeat,
-                                })?;
+                                k.handle_input_event(&KeyEvent::new(key_code, KeyValue::Repeat))?;
                             }
                             "🎭" => {
                                 // Virtual key activation with emoji prefix (defaults to press)
@@ -11,6 +11,9 @@ pub struct CapsWordState {
     /// An extra list of keys that should **not** terminate the caps_word state, in addition to
     /// keys_to_capitalize, but which don't trigger a capitalization.
     pub keys_nonterminal: HashSet<KeyCode>,
+    /// Keys that are replaced in the active keys by another key. These don't terminate the
+    /// caps_word state.
+    pub keys_rewrite: Vec<(KeyCode, KeyCode)>,
     /// The configured timeout for caps_word.
     pub timeout: u16,
     /// The number of ticks remaining for caps_word, after which its state should be cleared. The
@@ -34,6 +37,7 @@ impl CapsWordState {
         Self {
             keys_to_capitalize: cfg.keys_to_capitalize.iter().copied().collect(),
             keys_nonterminal: cfg.keys_nonterminal.iter().copied().collect(),
+            keys_rewrite: cfg.keys_rewrite.to_vec(),
             timeout: cfg.timeout,
             timeout_ticks: cfg.timeout,
         }
@@ -48,10 +52,18 @@ impl CapsWordState {
             return End;
         }
         for kc in active_keys.iter() {
-            if !self.keys_to_capitalize.contains(kc) && !self.keys_nonterminal.contains(kc) {
+            if !self.keys_to_capitalize.contains(kc)
+                && !self.keys_nonterminal.contains(kc)
+                && !self.keys_rewrite.iter().any(|(from, _)| from == kc)
+            {
                 return End;
             }
         }
+        for kc in active_keys.iter_mut() {
+            if let Some((_, to)) = self.keys_rewrite.iter().find(|(from, _)| from == kc) {
+                *kc = *to;
+            }
+        }
         if active_keys
             .last()
             .map(|kc| self.keys_to_capitalize.contains(kc))
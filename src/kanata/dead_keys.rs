@@ -0,0 +1,85 @@
+use super::HashSet;
+use crate::oskbd::KeyValue;
+use kanata_parser::keys::OsCode;
+use std::sync::atomic::AtomicBool;
+
+/// Set by `track-dead-keys`.
+pub static TRACK_DEAD_KEYS: AtomicBool = AtomicBool::new(false);
+
+/// Tracks the dead keys of the OS keyboard layout and the keys that compose with them, which are
+/// written to the output as they are instead of being processed. Kanata would otherwise output
+/// them as new events, which the OS does not compose.
+#[derive(Debug, Default)]
+pub struct DeadKeys {
+    /// Whether a dead key was pressed and the next key press composes with it.
+    pending: bool,
+    /// Keys that were pressed while passed through, so that their repeats and release are too.
+    passed_through: HashSet<OsCode>,
+}
+
+impl DeadKeys {
+    /// Whether the event should be written straight to the output. `is_dead_key` is only called
+    /// for presses and returns whether the key is a dead key in the current keyboard layout. A
+    /// press of a key that is held while passed through counts as a repeat, because Windows
+    /// reports repeats as presses.
+    pub fn passes_through(
+        &mut self,
+        code: OsCode,
+        value: KeyValue,
+        is_dead_key: impl FnOnce(OsCode) -> bool,
+    ) -> bool {
+        match value {
+            KeyValue::Press if self.passed_through.contains(&code) => true,
+            KeyValue::Press => {
+                // Modifiers are processed as usual so they can select the composed character,
+                // e.g. a capital letter.
+                if code.is_modifier() || code == OsCode::KEY_CAPSLOCK {
+                    return false;
+                }
+                if self.pending || is_dead_key(code) {
+                    // A dead key pressed after a dead key composes with it too, but is not
+                    // pending itself.
+                    self.pending = !self.pending;
+                    self.passed_through.insert(code);
+                    return true;
+                }
+                false
+            }
+            KeyValue::Release => self.passed_through.remove(&code),
+            KeyValue::Repeat => self.passed_through.contains(&code),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use OsCode::*;
+
+    #[test]
+    fn dead_key_passes_through_with_next_key() {
+        let mut dk = DeadKeys::default();
+        let dead = |osc| osc == KEY_LEFTBRACE;
+        let mut pass = |code, value| dk.passes_through(code, value, dead);
+        assert!(!pass(KEY_A, KeyValue::Press));
+        assert!(!pass(KEY_A, KeyValue::Release));
+        assert!(pass(KEY_LEFTBRACE, KeyValue::Press));
+        assert!(pass(KEY_LEFTBRACE, KeyValue::Release));
+        // Modifiers pressed in between are processed and do not compose.
+        assert!(!pass(KEY_LEFTSHIFT, KeyValue::Press));
+        assert!(pass(KEY_E, KeyValue::Press));
+        assert!(pass(KEY_E, KeyValue::Repeat));
+        assert!(pass(KEY_E, KeyValue::Press));
+        assert!(!pass(KEY_LEFTSHIFT, KeyValue::Release));
+        assert!(pass(KEY_E, KeyValue::Release));
+        assert!(!pass(KEY_E, KeyValue::Press));
+        assert!(!pass(KEY_E, KeyValue::Release));
+        // A dead key pressed twice composes with itself.
+        assert!(pass(KEY_LEFTBRACE, KeyValue::Press));
+        assert!(pass(KEY_LEFTBRACE, KeyValue::Release));
+        assert!(pass(KEY_LEFTBRACE, KeyValue::Press));
+        assert!(pass(KEY_LEFTBRACE, KeyValue::Release));
+        assert!(!pass(KEY_E, KeyValue::Press));
+    }
+}
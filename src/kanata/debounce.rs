@@ -0,0 +1,143 @@
+use crate::oskbd::{KeyEvent, KeyValue};
+use kanata_parser::cfg::DebounceAlgorithm;
+use kanata_parser::keys::OsCode;
+
+/// Filters chattering input keys, see `debounce-ms`. Works on the physical events before any
+/// other processing so that phantom presses and releases never reach the layout.
+#[derive(Debug, Default)]
+pub struct Debounce {
+    algorithm: DebounceAlgorithm,
+    default_ms: u16,
+    key_ms: Vec<(OsCode, u16)>,
+    /// Keys that are debouncing or are reported as pressed.
+    keys: Vec<DebouncedKey>,
+}
+
+#[derive(Debug)]
+struct DebouncedKey {
+    /// The latest event received for the key.
+    event: KeyEvent,
+    /// Whether the key is pressed as far as the rest of kanata knows.
+    pressed: bool,
+    /// For `defer`, the time until the latest event is processed. For `eager`, the time until
+    /// changes of the key are processed again.
+    ms_left: u16,
+}
+
+impl Debounce {
+    pub fn new(algorithm: DebounceAlgorithm, default_ms: u16, key_ms: Vec<(OsCode, u16)>) -> Self {
+        Self {
+            algorithm,
+            default_ms,
+            key_ms,
+            keys: vec![],
+        }
+    }
+
+    /// Applies new options. The state of keys is kept so that keys held during a live reload are
+    /// still released.
+    pub fn configure(
+        &mut self,
+        algorithm: DebounceAlgorithm,
+        default_ms: u16,
+        key_ms: Vec<(OsCode, u16)>,
+    ) {
+        self.algorithm = algorithm;
+        self.default_ms = default_ms;
+        self.key_ms = key_ms;
+    }
+
+    fn ms_for(&self, osc: OsCode) -> u16 {
+        self.key_ms
+            .iter()
+            .find(|(k, _)| *k == osc)
+            .map(|(_, ms)| *ms)
+            .unwrap_or(self.default_ms)
+    }
+
+    /// Returns whether `event` should be processed now. Events that are not are either dropped as
+    /// chatter or returned later by [`Debounce::tick`].
+    pub(crate) fn accept(&mut self, event: &KeyEvent) -> bool {
+        let ms = self.ms_for(event.code);
+        if ms == 0 {
+            return true;
+        }
+        let pressed = match event.value {
+            KeyValue::Press => true,
+            KeyValue::Release => false,
+            KeyValue::Repeat => {
+                return self
+                    .keys
+                    .iter()
+                    .find(|k| k.event.code == event.code)
+                    .is_none_or(|k| k.pressed && k.ms_left == 0);
+            }
+            KeyValue::Tap | KeyValue::WakeUp => return true,
+        };
+        let key = match self.keys.iter().position(|k| k.event.code == event.code) {
+            Some(i) => &mut self.keys[i],
+            // The press was not seen by debouncing, e.g. because the key was held before kanata
+            // started or before a live reload enabled debouncing. The release must not be lost.
+            None if !pressed => return true,
+            None => {
+                self.keys.push(DebouncedKey {
+                    event: *event,
+                    pressed: false,
+                    ms_left: 0,
+                });
+                self.keys.last_mut().expect("pushed")
+            }
+        };
+        key.event = *event;
+        match self.algorithm {
+            DebounceAlgorithm::Defer => {
+                key.ms_left = ms;
+                false
+            }
+            DebounceAlgorithm::Eager => {
+                if key.ms_left > 0 {
+                    return false;
+                }
+                if key.pressed != pressed {
+                    key.pressed = pressed;
+                    key.ms_left = ms;
+                }
+                true
+            }
+        }
+    }
+
+    /// Advances the debounce timers by 1ms and returns the events that should now be processed.
+    pub(crate) fn tick(&mut self) -> Vec<KeyEvent> {
+        if self.keys.is_empty() {
+            return vec![];
+        }
+        let mut events = vec![];
+        for i in 0..self.keys.len() {
+            let ms = self.ms_for(self.keys[i].event.code);
+            let key = &mut self.keys[i];
+            if key.ms_left == 0 {
+                continue;
+            }
+            key.ms_left -= 1;
+            if key.ms_left > 0 {
+                continue;
+            }
+            let pressed = key.event.value == KeyValue::Press;
+            if key.pressed == pressed {
+                continue;
+            }
+            key.pressed = pressed;
+            events.push(key.event);
+            if self.algorithm == DebounceAlgorithm::Eager {
+                key.ms_left = ms;
+            }
+        }
+        self.keys.retain(|k| k.pressed || k.ms_left > 0);
+        events
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.keys.iter().all(|k| k.ms_left == 0)
+    }
+}
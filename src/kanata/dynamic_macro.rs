@@ -32,6 +32,11 @@ enum WaitingEventType {
 }
 
 impl DynamicMacroRecordState {
+    /// The slot that this recording will be saved into.
+    pub fn macro_id(&self) -> u16 {
+        self.starting_macro_id
+    }
+
     fn new(macro_id: u16) -> Self {
         Self {
             starting_macro_id: macro_id,
@@ -313,3 +318,194 @@ pub fn play_macro(
         }
     }
 }
+
+/// Number of key press and release events in a recorded macro.
+#[cfg(feature = "tcp_server")]
+pub fn macro_event_count(items: &[DynamicMacroItem]) -> usize {
+    items
+        .iter()
+        .filter(|item| !matches!(item, DynamicMacroItem::EndMacro(_)))
+        .count()
+}
+
+/// Removes the recording in `macro_id`, persisting the remaining macros to `persist_file` if set.
+/// Returns whether the slot held a recording.
+#[cfg(feature = "tcp_server")]
+pub fn clear_dynamic_macro(
+    macros: &mut HashMap<u16, Vec<DynamicMacroItem>>,
+    persist_file: Option<&std::path::Path>,
+    macro_id: u16,
+) -> bool {
+    if macros.remove(&macro_id).is_none() {
+        return false;
+    }
+    if let Some(f) = persist_file
+        && let Err(e) = save_dynamic_macros(f, macros)
+    {
+        log::error!("could not save dynamic macros to {}: {e}", f.display());
+    }
+    true
+}
+
+/// Stores a finished recording into its slot, persisting all macros to `persist_file` if set.
+pub fn store_dynamic_macro(
+    macros: &mut HashMap<u16, Vec<DynamicMacroItem>>,
+    persist_file: Option<&std::path::Path>,
+    macro_id: u16,
+    items: Vec<DynamicMacroItem>,
+) {
+    macros.insert(macro_id, items);
+    if let Some(f) = persist_file
+        && let Err(e) = save_dynamic_macros(f, macros)
+    {
+        log::error!("could not save dynamic macros to {}: {e}", f.display());
+    }
+}
+
+/// Version of the on-disk format written by [`save_dynamic_macros`]. Files with a different
+/// version are not loaded.
+const PERSIST_FORMAT_VERSION: u32 = 1;
+
+#[derive(serde::Deserialize)]
+struct PersistedVersion {
+    version: u32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PersistedMacros {
+    version: u32,
+    macros: Vec<PersistedMacro>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PersistedMacro {
+    slot: u16,
+    events: Vec<PersistedEvent>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum PersistedEvent {
+    Press { key: String, delay: u16 },
+    Release { key: String, delay: u16 },
+}
+
+/// Writes all recorded macros to `path` as versioned JSON. Key names are the `OsCode` names so
+/// that the file is readable and does not depend on the platform's numeric key codes.
+pub fn save_dynamic_macros(
+    path: &std::path::Path,
+    macros: &HashMap<u16, Vec<DynamicMacroItem>>,
+) -> std::io::Result<()> {
+    let mut slots: Vec<_> = macros.iter().collect();
+    slots.sort_by_key(|(slot, _)| **slot);
+    let persisted = PersistedMacros {
+        version: PERSIST_FORMAT_VERSION,
+        macros: slots
+            .into_iter()
+            .map(|(slot, items)| PersistedMacro {
+                slot: *slot,
+                events: items
+                    .iter()
+                    .filter_map(|item| match *item {
+                        DynamicMacroItem::Press((osc, delay)) => Some(PersistedEvent::Press {
+                            key: format!("{osc:?}"),
+                            delay,
+                        }),
+                        DynamicMacroItem::Release((osc, delay)) => Some(PersistedEvent::Release {
+                            key: format!("{osc:?}"),
+                            delay,
+                        }),
+                        DynamicMacroItem::EndMacro(_) => None,
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+    let json = serde_json::to_string_pretty(&persisted).map_err(std::io::Error::other)?;
+    // Write to a temporary file first so a crash mid-write cannot corrupt the existing file.
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Reads macros saved by [`save_dynamic_macros`]. A missing, corrupt, or unsupported file is
+/// logged and results in no macros rather than an error.
+pub fn load_dynamic_macros(path: &std::path::Path) -> HashMap<u16, Vec<DynamicMacroItem>> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            log::info!(
+                "dynamic macro file {} does not exist yet, starting with empty slots",
+                path.display()
+            );
+            return HashMap::default();
+        }
+        Err(e) => {
+            log::warn!(
+                "could not read dynamic macro file {}, starting with empty slots: {e}",
+                path.display()
+            );
+            return HashMap::default();
+        }
+    };
+    match parse_persisted_macros(&text) {
+        Ok(macros) => {
+            log::info!(
+                "loaded {} dynamic macro(s) from {}",
+                macros.len(),
+                path.display()
+            );
+            macros
+        }
+        Err(e) => {
+            log::warn!(
+                "ignoring dynamic macro file {}, starting with empty slots: {e}",
+                path.display()
+            );
+            HashMap::default()
+        }
+    }
+}
+
+fn parse_persisted_macros(text: &str) -> Result<HashMap<u16, Vec<DynamicMacroItem>>, String> {
+    let version = serde_json::from_str::<PersistedVersion>(text)
+        .map_err(|e| format!("invalid file: {e}"))?
+        .version;
+    if version != PERSIST_FORMAT_VERSION {
+        return Err(format!(
+            "unsupported version {version}, expected {PERSIST_FORMAT_VERSION}"
+        ));
+    }
+    let persisted: PersistedMacros =
+        serde_json::from_str(text).map_err(|e| format!("invalid file: {e}"))?;
+    let oscodes_by_name: HashMap<String, OsCode> = (0..=u16::MAX)
+        .filter_map(OsCode::from_u16)
+        .map(|osc| (format!("{osc:?}"), osc))
+        .collect();
+    let lookup = |key: &str| {
+        oscodes_by_name
+            .get(key)
+            .copied()
+            .ok_or_else(|| format!("unknown key {key}"))
+    };
+    let mut macros = HashMap::default();
+    for m in persisted.macros {
+        let items = m
+            .events
+            .iter()
+            .map(|ev| {
+                Ok(match ev {
+                    PersistedEvent::Press { key, delay } => {
+                        DynamicMacroItem::Press((lookup(key)?, *delay))
+                    }
+                    PersistedEvent::Release { key, delay } => {
+                        DynamicMacroItem::Release((lookup(key)?, *delay))
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        macros.insert(m.slot, items);
+    }
+    Ok(macros)
+}
@@ -15,6 +15,20 @@ impl Kanata {
     }
 
     pub(super) fn handle_repeat_actual(&mut self, event: &KeyEvent) -> Result<()> {
+        if let Some((held, osc)) = self.rpt_held_key
+            && held == event.code
+        {
+            // The repeat key released its output right away, so tap it again instead of sending
+            // a repeat event for a key that is not held.
+            log::debug!("repeat key  {:?}", KeyCode::from(osc));
+            press_key(&mut self.kbd_out, osc)?;
+            release_key(&mut self.kbd_out, osc)?;
+            return Ok(());
+        }
+        if self.repeat_is_meaningless(event.code) {
+            log::debug!("not repeating layer or modifier action of {:?}", event.code);
+            return Ok(());
+        }
         if let Some(state) = self.sequence_state.get_active() {
             // While in non-visible sequence mode, don't send key repeats. I can't imagine it's a
             // helpful use case for someone trying to type in a sequence that they want to rely on
@@ -102,4 +116,23 @@ impl Kanata {
         }
         Ok(())
     }
+    /// Repeats of layer and modifier actions do nothing useful, so they are never sent, whether
+    /// or not `allow-hardware-repeat` is enabled. This is decided by what the key is doing now,
+    /// so a tap-hold key repeats if it resolved to a tap, but not if it resolved to a hold.
+    fn repeat_is_meaningless(&self, osc: OsCode) -> bool {
+        let coord = (0, u16::from(osc));
+        let mut key_states = self
+            .layout
+            .b()
+            .states
+            .iter()
+            .filter(|s| s.coord() == Some(coord))
+            .peekable();
+        key_states.peek().is_some()
+            && key_states.all(|s| match s {
+                State::LayerModifier { .. } => true,
+                State::NormalKey { keycode, .. } => OsCode::from(*keycode).is_modifier(),
+                _ => false,
+            })
+    }
 }
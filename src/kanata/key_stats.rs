@@ -0,0 +1,47 @@
+use crate::oskbd::{KeyEvent, KeyValue};
+use kanata_parser::keys::{OsCode, oscode_to_str};
+use rustc_hash::FxHashMap as HashMap;
+use std::collections::BTreeMap;
+
+/// Press counts of input keys, for the `GetStats` TCP command.
+#[derive(Debug, Default)]
+pub struct KeyStats {
+    counts: HashMap<OsCode, u64>,
+    /// Events sent by `KeyInject` that have not been processed yet. They are not counted.
+    injected: Vec<(OsCode, KeyValue)>,
+}
+
+impl KeyStats {
+    /// Count the event if it is a press that was not injected.
+    pub(crate) fn record(&mut self, event: &KeyEvent) {
+        if let Some(i) = self
+            .injected
+            .iter()
+            .position(|(code, value)| *code == event.code && *value == event.value)
+        {
+            self.injected.remove(i);
+            return;
+        }
+        if event.value == KeyValue::Press {
+            *self.counts.entry(event.code).or_default() += 1;
+        }
+    }
+
+    /// Exclude the next matching event from the counts. Must be called before the event is
+    /// sent to the processing loop.
+    pub fn mark_injected(&mut self, code: OsCode, value: KeyValue) {
+        self.injected.push((code, value));
+    }
+
+    /// The press counts by key name.
+    pub fn counts(&self) -> BTreeMap<String, u64> {
+        self.counts
+            .iter()
+            .map(|(osc, count)| (oscode_to_str(*osc), *count))
+            .collect()
+    }
+
+    pub fn reset(&mut self) {
+        self.counts.clear();
+    }
+}
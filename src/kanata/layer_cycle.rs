@@ -0,0 +1,42 @@
+use kanata_parser::cfg::BorrowedKLayout;
+
+/// Layers of the `layer-cycle` actions and the layer each cycle last switched to.
+#[derive(Debug, Default)]
+pub struct LayerCycles {
+    /// Layers of each cycle, indexed by cycle number.
+    cycles: Vec<Vec<usize>>,
+    /// Index into the layers of each cycle. Starts at the first layer.
+    selected: Vec<usize>,
+}
+
+impl LayerCycles {
+    pub(crate) fn new(cycles: Vec<Vec<usize>>) -> Self {
+        Self {
+            selected: vec![0; cycles.len()],
+            cycles,
+        }
+    }
+
+    /// Make the layer after the selected one the base layer, wrapping at the end of the list.
+    pub(crate) fn advance(&mut self, cycle: u16, layout: &mut BorrowedKLayout) {
+        let cycle = usize::from(cycle);
+        let layers = &self.cycles[cycle];
+        let selected = (self.selected[cycle] + 1) % layers.len();
+        self.selected[cycle] = selected;
+        log::debug!("layer-cycle {cycle} selected {selected}");
+        layout.set_default_layer(layers[selected]);
+    }
+
+    /// Select the first layer of every cycle again.
+    pub(crate) fn reset(&mut self) {
+        self.selected.fill(0);
+    }
+
+    /// Layers and selected index of each cycle.
+    pub fn iter(&self) -> impl Iterator<Item = (&[usize], usize)> {
+        self.cycles
+            .iter()
+            .map(Vec::as_slice)
+            .zip(self.selected.iter().copied())
+    }
+}
@@ -0,0 +1,40 @@
+use kanata_keyberon::layout::{KCoord, State};
+use kanata_parser::cfg::{BorrowedKLayout, FAKE_KEY_ROW};
+
+/// Coordinate of the layer states added by `layer-push`. No key uses it, so keyberon never
+/// releases the states on its own. The most recent of these states is the top of the stack.
+const LAYER_STACK_COORD: KCoord = (FAKE_KEY_ROW, u16::MAX - 1);
+
+/// Activates the layer on top of the stack, unless the stack already holds `max_depth` layers.
+pub(crate) fn layer_push(layout: &mut BorrowedKLayout, layer: u16, max_depth: u16) {
+    let depth = layout.states.iter().filter(|s| is_pushed_layer(s)).count();
+    if depth >= usize::from(max_depth) {
+        log::warn!("layer-push {layer} ignored, the layer stack is at its max depth {max_depth}");
+        return;
+    }
+    if layout
+        .states
+        .push(State::LayerModifier {
+            value: usize::from(layer),
+            coord: LAYER_STACK_COORD,
+        })
+        .is_err()
+    {
+        log::warn!("layer-push {layer} ignored, too many keys are active");
+    }
+}
+
+/// Deactivates the layer on top of the stack, returning to whatever was active before it was
+/// pushed.
+pub(crate) fn layer_pop(layout: &mut BorrowedKLayout) {
+    match layout.states.iter().rposition(is_pushed_layer) {
+        Some(idx) => {
+            layout.states.remove(idx);
+        }
+        None => log::debug!("layer-pop with an empty layer stack does nothing"),
+    }
+}
+
+fn is_pushed_layer<T>(s: &State<T>) -> bool {
+    matches!(s, State::LayerModifier { coord, .. } if *coord == LAYER_STACK_COORD)
+}
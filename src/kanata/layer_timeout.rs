@@ -0,0 +1,68 @@
+use super::states_has_coord;
+use kanata_keyberon::layout::{KCoord, State};
+use kanata_parser::cfg::BorrowedKLayout;
+
+/// The held `layer-while-held-timeout` keys, whose layers are deactivated when no key has been
+/// pressed for their timeout.
+#[derive(Debug, Default)]
+pub struct LayerHoldTimeouts {
+    held: Vec<HeldLayer>,
+    /// Milliseconds since the most recent key press.
+    idle_ms: u32,
+}
+
+#[derive(Debug)]
+struct HeldLayer {
+    /// Coordinate of the key, which the layer state is at.
+    coord: KCoord,
+    timeout_ms: u32,
+}
+
+impl LayerHoldTimeouts {
+    pub(crate) fn press(&mut self, coord: KCoord, timeout_ms: u32) {
+        self.idle_ms = 0;
+        self.held.push(HeldLayer { coord, timeout_ms });
+    }
+
+    /// Restart the timeouts because a key was pressed.
+    pub(crate) fn key_pressed(&mut self) {
+        self.idle_ms = 0;
+    }
+
+    /// Whether a timeout is counting, so the processing loop must keep ticking.
+    pub(crate) fn is_counting(&self) -> bool {
+        !self.held.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.held.clear();
+    }
+
+    /// Advance by one millisecond and deactivate the layers whose timeout has passed. This only
+    /// removes the layer state, so nothing is output and keys pressed on the layer are still
+    /// released by their own coordinates. The layer is activated again by the next press of the
+    /// key.
+    pub(crate) fn tick(&mut self, layout: &mut BorrowedKLayout) {
+        if self.held.is_empty() {
+            return;
+        }
+        self.idle_ms = self.idle_ms.saturating_add(1);
+        let idle_ms = self.idle_ms;
+        self.held.retain(|held| {
+            if !states_has_coord(&layout.states, held.coord.0, held.coord.1) {
+                return false;
+            }
+            if idle_ms < held.timeout_ms {
+                return true;
+            }
+            log::info!(
+                "no key pressed for {}ms, deactivating layer-while-held-timeout",
+                held.timeout_ms
+            );
+            layout.states.retain(
+                |s| !matches!(s, State::LayerModifier { coord, .. } if *coord == held.coord),
+            );
+            false
+        });
+    }
+}
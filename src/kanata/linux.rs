@@ -39,12 +39,18 @@ impl Kanata {
         // does not work on kanata startup.
         Kanata::set_repeat_rate(k.x11_repeat_rate)?;
         drop(k);
+        crate::tcp_server::notify_ready(kbd_in.device_names(), false);
 
         loop {
             let events = kbd_in.read().map_err(|e| anyhow!("failed read: {}", e))?;
             log::trace!("event count: {}\nevents:\n{events:?}", events.len());
 
-            for in_event in events.iter().copied() {
+            for (in_event, device, device_id) in events.iter().copied() {
+                if in_event.event_type() == evdev::EventType::KEY
+                    && let Some(osc) = OsCode::from_u16(in_event.code())
+                {
+                    check_for_panic_combo(osc, in_event.value() != 0);
+                }
                 if let Some(ms_mvmt_key) = *mouse_movement_key.lock()
                     && let EventSummary::RelativeAxis(_, _, _) = in_event.destructure()
                 {
@@ -55,7 +61,11 @@ impl Kanata {
                 }
 
                 let key_event = match KeyEvent::try_from(in_event) {
-                    Ok(ev) => ev,
+                    Ok(ev) => KeyEvent {
+                        device: Some(device),
+                        device_id,
+                        ..ev
+                    },
                     _ => {
                         // Pass-through non-key and non-scroll events
                         let mut kanata = kanata.lock();
@@ -69,6 +79,7 @@ impl Kanata {
                 };
 
                 check_for_exit(&key_event);
+                crate::tcp_server::monitor_key_event(&key_event);
 
                 if key_event.value == KeyValue::Repeat && !allow_hardware_repeat {
                     continue;
@@ -96,6 +107,10 @@ impl Kanata {
                 // If it hasn't been mapped, send it immediately.
                 if !MAPPED_KEYS.lock().contains(&key_event.code) {
                     let mut kanata = kanata.lock();
+                    if kanata.mouse_locks.is_locked_osc(key_event.code) {
+                        log::debug!("dropping {key_event:?} of a locked mouse button");
+                        continue;
+                    }
                     #[cfg(not(feature = "simulated_output"))]
                     kanata
                         .kbd_out
@@ -105,6 +120,7 @@ impl Kanata {
                 };
 
                 // Send key events to the processing loop
+                log::debug!("sending {key_event:?} to processing loop");
                 if let Err(e) = tx.try_send(key_event) {
                     bail!("failed to send on channel: {}", e)
                 }
@@ -154,7 +170,7 @@ fn handle_scroll(
     kanata: &Mutex<Kanata>,
     in_event: InputEvent,
     code: OsCode,
-    all_events: &[InputEvent],
+    all_events: &[(InputEvent, &str, u32)],
 ) -> Result<bool> {
     let direction: MWheelDirection = code.try_into().unwrap();
     let scroll_distance = in_event.value().unsigned_abs() as u16;
@@ -176,7 +192,7 @@ fn handle_scroll(
                     // scroll event. In this scenario, the hi-res event should be used to call
                     // scroll, and not the normal event. Otherwise, too much scrolling will happen.
                     let mut kanata = kanata.lock();
-                    if !all_events.iter().any(|ev| {
+                    if !all_events.iter().any(|(ev, _, _)| {
                         matches!(
                             ev.destructure(),
                             EventSummary::RelativeAxis(
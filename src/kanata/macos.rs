@@ -1,19 +1,67 @@
 use super::*;
 use anyhow::{Result, anyhow, bail};
-use karabiner_driverkit::is_sink_ready;
 use log::info;
 use parking_lot::Mutex;
 use std::convert::TryFrom;
 use std::sync::Arc;
 use std::sync::mpsc::SyncSender as Sender;
 
+/// Delay before the first retry of a failed write. Each further retry doubles it, up to
+/// `WRITE_RETRY_MAX_DOUBLINGS` times.
+const WRITE_RETRY_BASE_DELAY: std::time::Duration = std::time::Duration::from_millis(10);
+const WRITE_RETRY_MAX_DOUBLINGS: u8 = 6;
+
+/// Result of writing an event that kanata does not process straight to the output.
+enum WriteOutcome {
+    Written,
+    /// The output is lost, so the input devices must be released until it recovers.
+    NeedsRecovery,
+    /// The event loop must exit with this error.
+    Fatal(anyhow::Error),
+}
+
+/// Write an event that kanata does not process straight to the output, handling errors
+/// according to `write-error-policy`.
+fn write_unprocessed(kanata: &Mutex<Kanata>, event: InputEvent) -> WriteOutcome {
+    let mut retries = 0u8;
+    loop {
+        let mut k = kanata.lock();
+        let e = match k.passthrough_write(event) {
+            WriteOutcome::Fatal(e) => e,
+            outcome => return outcome,
+        };
+        match k.write_error_policy {
+            WriteErrorPolicy::Exit => {}
+            WriteErrorPolicy::Recover => {
+                log::warn!("{e} — releasing input devices");
+                return WriteOutcome::NeedsRecovery;
+            }
+            WriteErrorPolicy::Retry(max) if retries < max => {
+                // Don't hold the lock while waiting so the processing loop can keep running.
+                drop(k);
+                let delay = WRITE_RETRY_BASE_DELAY
+                    .saturating_mul(1 << retries.min(WRITE_RETRY_MAX_DOUBLINGS));
+                retries += 1;
+                log::warn!("{e} — retry {retries} of {max} in {delay:?}");
+                std::thread::sleep(delay);
+                continue;
+            }
+            WriteErrorPolicy::Retry(max) => {
+                log::error!("write still failing after {max} retries");
+            }
+        }
+        return WriteOutcome::Fatal(e);
+    }
+}
+
 impl Kanata {
     /// Enter an infinite loop that listens for OS key events and sends them to the processing thread.
     ///
     /// Contains a recovery mechanism: if the DriverKit output connection drops
     /// (daemon crash, not installed, etc.), input devices are released so the
     /// keyboard returns to normal operation. When the connection recovers,
-    /// devices are re-seized and remapping resumes.
+    /// devices are re-seized and remapping resumes. With the CGEvent output backend
+    /// there is no connection to lose, so this never triggers.
     ///
     /// Recovery uses `regrab_input()` rather than recreating `KbdIn` to avoid
     /// re-initializing the pqrs client (via `init_sink()`). A second client
@@ -34,16 +82,27 @@ impl Kanata {
         };
 
         info!("keyboard grabbed, entering event processing loop");
+        crate::tcp_server::notify_ready(kb.device_names().to_vec(), false);
 
+        let mut dead_keys = DeadKeys::default();
+        let mut run_self_test = SELF_TEST.load(std::sync::atomic::Ordering::SeqCst);
         loop {
             // --- Event processing loop ---
             let needs_recovery = loop {
                 // Check output health before blocking on input
-                if !is_sink_ready() {
+                if !output_ready() {
                     log::warn!("DriverKit output lost — releasing input devices");
                     break true;
                 }
 
+                if run_self_test {
+                    run_self_test = false;
+                    if let Err(e) = kanata.lock().self_test() {
+                        log::warn!("{e} — releasing input devices");
+                        break true;
+                    }
+                }
+
                 let event = match kb.read() {
                     Ok(ev) => ev,
                     Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
@@ -55,24 +114,23 @@ impl Kanata {
                 };
 
                 let mut key_event = match KeyEvent::try_from(event) {
-                    Ok(ev) => ev,
+                    Ok(ev) => {
+                        log::debug!("{event:?} read as {:?}", ev.code);
+                        ev
+                    }
                     _ => {
                         log::debug!("{event:?} is unrecognized!");
-                        let mut kanata = kanata.lock();
-                        match kanata.kbd_out.write(event) {
-                            Ok(()) => continue,
-                            Err(e) if e.kind() == std::io::ErrorKind::NotConnected => {
-                                log::warn!(
-                                    "DriverKit output lost during write — releasing input devices"
-                                );
-                                break true;
-                            }
-                            Err(e) => return Err(anyhow!("failed write: {}", e)),
+                        match write_unprocessed(&kanata, event) {
+                            WriteOutcome::Written => continue,
+                            WriteOutcome::NeedsRecovery => break true,
+                            WriteOutcome::Fatal(e) => return Err(e),
                         }
                     }
                 };
 
+                check_for_panic_combo(key_event.code, key_event.value != KeyValue::Release);
                 check_for_exit(&key_event);
+                crate::tcp_server::monitor_key_event(&key_event);
 
                 if key_event.value == KeyValue::Repeat && !allow_hardware_repeat {
                     continue;
@@ -80,16 +138,21 @@ impl Kanata {
 
                 if !MAPPED_KEYS.lock().contains(&key_event.code) {
                     log::debug!("{key_event:?} is not mapped");
-                    let mut kanata = kanata.lock();
-                    match kanata.kbd_out.write(event) {
-                        Ok(()) => continue,
-                        Err(e) if e.kind() == std::io::ErrorKind::NotConnected => {
-                            log::warn!(
-                                "DriverKit output lost during write — releasing input devices"
-                            );
-                            break true;
-                        }
-                        Err(e) => return Err(anyhow!("failed write: {}", e)),
+                    match write_unprocessed(&kanata, event) {
+                        WriteOutcome::Written => continue,
+                        WriteOutcome::NeedsRecovery => break true,
+                        WriteOutcome::Fatal(e) => return Err(e),
+                    }
+                }
+
+                if TRACK_DEAD_KEYS.load(std::sync::atomic::Ordering::Relaxed)
+                    && dead_keys.passes_through(key_event.code, key_event.value, is_dead_key)
+                {
+                    log::debug!("passing through dead key input {key_event:?}");
+                    match write_unprocessed(&kanata, event) {
+                        WriteOutcome::Written => continue,
+                        WriteOutcome::NeedsRecovery => break true,
+                        WriteOutcome::Fatal(e) => return Err(e),
                     }
                 }
 
@@ -118,6 +181,7 @@ impl Kanata {
 
             // --- Release input so the keyboard works normally (unseized) ---
             kb.release_input();
+            crate::tcp_server::notify_input_released();
 
             info!(
                 "Input devices released. Keyboard is usable (without remapping). \
@@ -127,7 +191,7 @@ impl Kanata {
             // --- Wait for the pqrs client to re-establish the connection ---
             loop {
                 std::thread::sleep(std::time::Duration::from_millis(500));
-                if is_sink_ready() {
+                if output_ready() {
                     // Let the pqrs client's callback sequence finish before
                     // we re-seize input devices. The client fires several
                     // callbacks in quick succession (connected, driver_connected,
@@ -144,13 +208,30 @@ impl Kanata {
             if !kb.regrab_input() {
                 bail!("failed to re-grab keyboard devices after DriverKit recovery");
             }
+            // Check that output actually reaches the OS before remapping again.
+            run_self_test = true;
 
             info!("keyboard grabbed, entering event processing loop");
+            crate::tcp_server::notify_ready(kb.device_names().to_vec(), true);
 
             // Back to the event processing loop.
         }
     }
 
+    /// Write an event that kanata does not process straight to the output, once. Only a lost
+    /// DriverKit connection leads to recovery; `write_unprocessed` applies `write-error-policy` to
+    /// the other errors.
+    fn passthrough_write(&mut self, event: InputEvent) -> WriteOutcome {
+        match self.kbd_out.write(event) {
+            Ok(()) => WriteOutcome::Written,
+            Err(e) if e.kind() == std::io::ErrorKind::NotConnected => {
+                log::warn!("DriverKit output lost during write — releasing input devices");
+                WriteOutcome::NeedsRecovery
+            }
+            Err(e) => WriteOutcome::Fatal(anyhow!("failed write: {}", e)),
+        }
+    }
+
     pub fn check_release_non_physical_shift(&mut self) -> Result<()> {
         Ok(())
     }
@@ -59,9 +59,9 @@ fn collect_and_sort_events(
 #[cfg(feature = "passthru_ahk")]
 use std::sync::mpsc::Sender as ASender;
 
-use kanata_keyberon::action::ReleasableState;
+use kanata_keyberon::action::{Action, ReleasableState, SequenceEvent};
 use kanata_keyberon::key_code::*;
-use kanata_keyberon::layout::{CustomEvent, Event, Layout, State};
+use kanata_keyberon::layout::{CustomEvent, Event, KCoord, Layout, State};
 
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -78,7 +78,7 @@ use kanata_parser::cfg::list_actions::*;
 use kanata_parser::cfg::*;
 use kanata_parser::custom_action::*;
 pub use kanata_parser::keys::*;
-use kanata_tcp_protocol::ServerMessage;
+use kanata_tcp_protocol::{SequenceEndReason, ServerMessage, StickyLayerMode};
 
 mod clipboard;
 use clipboard::*;
@@ -125,6 +125,56 @@ mod unknown;
 mod caps_word;
 pub use caps_word::*;
 
+mod sticky_layer;
+pub use sticky_layer::*;
+
+mod layer_stack;
+use layer_stack::*;
+
+mod runtime_vars;
+pub use runtime_vars::*;
+
+mod layer_cycle;
+pub use layer_cycle::*;
+
+mod typing_cadence;
+pub use typing_cadence::*;
+
+mod named_macros;
+pub use named_macros::*;
+
+mod on_exit;
+pub use on_exit::*;
+
+mod multi_seq;
+use multi_seq::*;
+
+mod layer_timeout;
+use layer_timeout::*;
+
+mod dead_keys;
+pub use dead_keys::*;
+
+mod schedule;
+pub use schedule::*;
+
+mod temp_remaps;
+pub use temp_remaps::*;
+mod key_stats;
+pub use key_stats::*;
+mod output_watchdog;
+pub use output_watchdog::*;
+mod debounce;
+pub use debounce::*;
+mod panic_combo;
+pub use panic_combo::*;
+mod mouse_lock;
+pub use mouse_lock::*;
+#[cfg(feature = "tcp_server")]
+mod pressed_keys;
+#[cfg(feature = "tcp_server")]
+pub use pressed_keys::*;
+
 type HashSet<T> = rustc_hash::FxHashSet<T>;
 type HashMap<K, V> = rustc_hash::FxHashMap<K, V>;
 
@@ -138,6 +188,9 @@ pub(crate) static PRESSED_KEYS: Lazy<Mutex<HashSet<OsCode>>> =
 pub(crate) static PRESSED_KEYS: Lazy<Mutex<HashMap<OsCode, web_time::Instant>>> =
     Lazy::new(|| Mutex::new(HashMap::default()));
 
+/// Whether to run the output self-test on startup. Set via the --self-test CLI argument.
+pub static SELF_TEST: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
 /// Exit code to use when emergency exit (LCtrl+Space+Escape) is triggered.
 /// Configurable via --emergency-exit-code CLI argument. Default is 0.
 pub static EMERGENCY_EXIT_CODE: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
@@ -178,13 +231,22 @@ pub struct Kanata {
     /// Horizontal mouse movement state. Is Some(...) when horizontal mouse movement is active and
     /// None otherwise.
     pub move_mouse_state_horizontal: Option<MoveMouseState>,
+    /// State of the active `movemouse-polar` action, if any.
+    pub move_mouse_state_polar: Option<PolarMoveState>,
     /// A list of mouse speed modifiers in percentages by which mouse travel distance is scaled.
     pub move_mouse_speed_modifiers: Vec<u16>,
+    /// Speeds of the held `movemouse-speed` keys that also scale `mwheel` actions.
+    pub mwheel_speed_modifiers: Vec<u16>,
+    /// Keys with a `movemouse-speed` action that were held during a `movemouse-speed-reset`.
+    /// Their speeds were already removed, so releasing them must not remove another key's.
+    pub move_mouse_speed_reset_keys: Vec<KCoord>,
     /// The user configuration for backtracking to find valid sequences. See
     /// <../../docs/sequence-adding-chords-ideas.md> for more info.
     pub sequence_backtrack_modcancel: bool,
     /// The user configuration for sequences be permanently on.
     pub sequence_always_on: bool,
+    /// Whether to send the `Sequence*` TCP notifications.
+    pub sequence_notifications: bool,
     /// Default sequence input mode for use with always-on.
     pub sequence_input_mode: SequenceInputMode,
     /// Default sequence timeout for use with always-on.
@@ -228,6 +290,9 @@ pub struct Kanata {
     /// Tracks the Linux/Macos user configuration for device names (instead of paths) that should be
     /// excluded for interception and processing by kanata.
     pub exclude_names: Option<Vec<String>>,
+    #[cfg(target_os = "macos")]
+    /// What to do when writing to the DriverKit virtual keyboard fails.
+    pub write_error_policy: WriteErrorPolicy,
     #[cfg(target_os = "windows")]
     /// Tracks whether Kanata should try to synchronize keystates with the Windows OS.
     /// Has no effect on Interception. Fixes some use cases related to admin window permissions and
@@ -253,6 +318,34 @@ pub struct Kanata {
     log_layer_changes: bool,
     /// Tracks the caps-word state. Is Some(...) if caps-word is active and None otherwise.
     pub caps_word: Option<CapsWordState>,
+    /// Tracks the state of `sticky-layer` keys.
+    pub sticky_layer: StickyLayerState,
+    /// Mouse buttons held down by the mouse lock actions.
+    pub mouse_locks: MouseLocks,
+    /// Names of the variables used by `setvar`, `incvar` and switch.
+    pub runtime_vars: RuntimeVars,
+    /// The selected layers of `layer-cycle` actions.
+    pub layer_cycles: LayerCycles,
+    /// Recent intervals between presses, for `tap-hold-adaptive`.
+    typing_cadence: TypingCadence,
+    /// Named macros from `defmacro`, played by the `PlayMacro` TCP command.
+    pub named_macros: NamedMacros,
+    /// The `on-exit` action, run by the exit hotkey before exiting.
+    pub on_exit: OnExit,
+    /// Delayed steps of held `multi-seq` keys.
+    multi_seq: MultiSeq,
+    /// Held `layer-while-held-timeout` keys.
+    layer_hold_timeouts: LayerHoldTimeouts,
+    /// Base layer changes from `defschedule`.
+    pub schedule: Schedule,
+    /// Key remaps added by the `TempRemap` TCP command.
+    pub temp_remaps: TempRemaps,
+    /// Press counts for the `GetStats` TCP command.
+    pub key_stats: KeyStats,
+    /// Releases output keys held for longer than `max-output-hold-ms`.
+    output_watchdog: OutputWatchdog,
+    /// Filters chattering input keys, see `debounce-ms`.
+    pub debounce: Debounce,
     /// Config items from `defcfg`.
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub x11_repeat_rate: Option<KeyRepeatSettings>,
@@ -273,9 +366,24 @@ pub struct Kanata {
     pub ticks_since_idle: u16,
     /// Number of ticks since physical keyboards were all idle.
     pub ticks_since_physical_idle: u16,
+    /// Fake keys for the `on-idle` and `on-resume` actions in defcfg.
+    idle_hooks: IdleHooks,
+    /// Milliseconds since the last input event. Only counted until `on-idle` fires.
+    ms_since_input: u32,
+    /// Whether `on-idle` has fired during the current idle period.
+    on_idle_fired: bool,
+    /// Milliseconds each `defidle` watcher has been armed without input. `None` if the watcher
+    /// has fired during the current idle period.
+    idle_watcher_ms: Vec<Option<u32>>,
+    /// Key pressed and released by the output self-test.
+    self_test_key: OsCode,
+    /// Key that stops all running macros when pressed.
+    macro_cancel_key: Option<OsCode>,
     /// If a mousemove action is active and another mousemove action is activated,
     /// reuse the acceleration state.
     movemouse_inherit_accel_state: bool,
+    /// Speed ramp of held `movemouse` actions.
+    mouse_speed_curve: Option<SpeedCurve>,
     /// Removes jaggedneess of vertical and horizontal mouse movements when used
     /// simultaneously at the cost of increased mousemove actions latency.
     movemouse_smooth_diagonals: bool,
@@ -284,29 +392,92 @@ pub struct Kanata {
     /// than the one stored in the buffer, both events are outputted at the same time.
     movemouse_buffer: Option<(Axis, CalculatedMouseMove)>,
     override_release_on_activation: bool,
+    /// Whether physically held modifiers are released while a macro is being output.
+    clean_mods_on_action: bool,
+    /// Per-action override of `clean_mods_on_action` for the macros currently running.
+    clean_mods_override: Option<bool>,
+    /// Modifiers output by the macros currently running, which are not cleaned.
+    clean_mods_macro_mods: Vec<KeyCode>,
     /// Configured maximum for dynamic macro recording, to protect users from themselves if they
     /// have accidentally left it on.
     dynamic_macro_max_presses: u16,
     /// Determines behaviour of replayed dynamic macros.
     dynamic_macro_replay_behaviour: ReplayBehaviour,
+    /// If set, recorded dynamic macros are saved to and loaded from this file.
+    dynamic_macro_persist_file: Option<PathBuf>,
     /// Keys that should be unmodded. If non-empty, any modifier should be cleared.
     unmodded_keys: Vec<KeyCode>,
     /// Modifiers to be cleared in case the above is non-empty.
     unmodded_mods: UnmodMods,
     /// Keys that should be unshifted. If non-empty, left+right shift keys should be cleared.
     unshifted_keys: Vec<KeyCode>,
+    /// Keys pressed by `press-key`. These stay held until `release-key`, a live reload, or a
+    /// safety net clearing stuck states releases them.
+    manually_held_keys: Vec<KeyCode>,
+    /// Keys of the `chord-output` actions that are currently held.
+    chord_output_keys: Vec<KeyCode>,
+    /// Hardware scan codes of the physically held keys that were received with one.
+    input_scancodes: HashMap<OsCode, u16>,
+    /// Output keys that are held using the scan code of the physical key they pass through.
+    output_scancodes: HashMap<OsCode, u16>,
     /// Keep track of last pressed key for [`CustomAction::Repeat`].
     last_pressed_key: KeyCode,
+    /// Whether [`CustomAction::Repeat`] replays macros, see `rpt-repeats-macros` in `defcfg`.
+    rpt_repeats_macros: bool,
+    /// Maximum number of layers pushed by [`CustomAction::LayerPush`] at once.
+    layer_stack_max_depth: u16,
+    /// A held physical key whose [`CustomAction::Repeat`] repeated a single key, and that key.
+    /// Key repeats of the physical key repeat the key again.
+    rpt_held_key: Option<(OsCode, OsCode)>,
     /// Names of fake keys mapped to their index in the fake keys row
     pub virtual_keys: HashMap<String, usize>,
     /// The maximum value of switch's key-timing item in the configuration.
     pub switch_max_key_timing: u16,
     #[cfg(feature = "tcp_server")]
-    tcp_server_address: Option<SocketAddrWrapper>,
+    pub tcp_server_address: Option<SocketAddrWrapper>,
+    /// Framing used for new TCP server client connections.
+    #[cfg(feature = "tcp_server")]
+    pub tcp_server_protocol: TcpServerProtocol,
+    /// JSON message format used for new TCP server client connections.
+    #[cfg(feature = "tcp_server")]
+    pub tcp_server_format: TcpServerFormat,
+    /// If set, TCP server clients must send this token in their first message.
+    #[cfg(feature = "tcp_server")]
+    pub tcp_server_token: Option<String>,
+    /// If set, TCP server connections are encrypted with TLS.
+    #[cfg(feature = "tcp_server")]
+    pub tcp_server_tls: Option<crate::tcp_server::TlsConfig>,
+    /// Connections beyond this many clients are refused.
+    #[cfg(feature = "tcp_server")]
+    pub tcp_max_clients: usize,
+    /// How long client commands wait for the processing thread before failing.
+    #[cfg(feature = "tcp_server")]
+    pub tcp_command_timeout: time::Duration,
+    /// Pressed keys in the last `PressedKeys` notification.
+    #[cfg(feature = "tcp_server")]
+    pressed_keys_sent: PressedKeys,
+    /// Path of the Unix domain socket to serve the TCP protocol on, if enabled.
+    #[cfg(all(
+        feature = "tcp_server",
+        any(target_os = "linux", target_os = "android", target_os = "macos")
+    ))]
+    pub unix_socket_path: Option<PathBuf>,
     #[cfg(all(target_os = "windows", feature = "gui"))]
     /// Various GUI-related options.
     pub gui_opts: CfgOptionsGui,
+    /// Whether the event loop sends hardware key repeats for processing. This is true if any
+    /// layer allows them, and the processing loop drops them for the other layers.
     pub allow_hardware_repeat: bool,
+    /// `allow-hardware-repeat` in `defcfg`, which applies to layers that do not override it.
+    default_allow_hardware_repeat: bool,
+    /// `process-unmapped-keys` in `defcfg`, which applies to layers that do not override it.
+    default_process_unmapped_keys: bool,
+    /// Keys that are sent for processing only because of `process-unmapped-keys`. These are
+    /// passed through while the base layer does not process unmapped keys.
+    unmapped_keys: cfg::MappedKeys,
+    /// Keys whose press was passed through, so their repeats and release are too, even if the base
+    /// layer has changed.
+    passed_through_keys: HashSet<OsCode>,
     /// When > 0, it means macros should be cancelled on the next press.
     /// Upon cancelling this should be set to 0.
     pub macro_on_press_cancel_duration: u32,
@@ -323,9 +494,9 @@ pub struct Kanata {
     /// Time when kanata started (for uptime tracking)
     #[cfg(feature = "tcp_server")]
     start_time: web_time::Instant,
-    /// Whether last reload was successful
+    /// Outcome of the most recent live reload.
     #[cfg(feature = "tcp_server")]
-    last_reload_ok: bool,
+    last_reload: ReloadOutcome,
 }
 
 #[derive(PartialEq, Clone, Copy)]
@@ -343,6 +514,16 @@ impl From<MoveDirection> for Axis {
     }
 }
 
+/// Result of a live reload, reported to TCP clients that wait for it.
+#[cfg(feature = "tcp_server")]
+#[derive(Debug, Clone, Default)]
+pub struct ReloadOutcome {
+    /// Why the reload failed, or `None` if it succeeded.
+    pub error: Option<String>,
+    /// Time taken to parse and apply the configuration.
+    pub duration: time::Duration,
+}
+
 /// Represents reload actions that need to be processed after releasing borrows
 enum ReloadAction {
     Reload,
@@ -364,6 +545,47 @@ pub struct MoveMouseState {
     pub ticks_until_move: u16,
     pub distance: u16,
     pub move_mouse_accel_state: Option<MoveMouseAccelState>,
+    /// Milliseconds since the action was pressed, for `mouse-speed-curve`.
+    pub held_ms: u32,
+}
+
+/// State of a held `movemouse-polar` action.
+#[derive(Clone, Copy)]
+pub struct PolarMoveState {
+    pub angle: u16,
+    pub speed: u16,
+    /// Pixels to move per millisecond. Positive `dx` is right and positive `dy` is down.
+    pub dx: f32,
+    pub dy: f32,
+    /// Sub-pixel distances that have not been moved yet.
+    pub remainder_x: f32,
+    pub remainder_y: f32,
+}
+
+impl PolarMoveState {
+    pub fn new(angle: u16, speed: u16) -> Self {
+        let (sin, cos) = f32::from(angle).to_radians().sin_cos();
+        let px_per_ms = f32::from(speed) / 1000.0;
+        Self {
+            angle,
+            speed,
+            dx: sin * px_per_ms,
+            dy: -cos * px_per_ms,
+            remainder_x: 0.0,
+            remainder_y: 0.0,
+        }
+    }
+
+    /// Advances by one millisecond scaled by `scale` and returns the whole pixels to move.
+    pub fn tick(&mut self, scale: f32) -> (i32, i32) {
+        self.remainder_x += self.dx * scale;
+        self.remainder_y += self.dy * scale;
+        let x = self.remainder_x.trunc();
+        let y = self.remainder_y.trunc();
+        self.remainder_x -= x;
+        self.remainder_y -= y;
+        (x as i32, y as i32)
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -377,7 +599,9 @@ pub struct MoveMouseAccelState {
 
 use once_cell::sync::Lazy;
 
-static MAPPED_KEYS: Lazy<Mutex<cfg::MappedKeys>> =
+/// Keys whose input events the event loops send to the processing loop. Input events of other
+/// keys, including `passthrough-keys`, are written straight out.
+pub(crate) static MAPPED_KEYS: Lazy<Mutex<cfg::MappedKeys>> =
     Lazy::new(|| Mutex::new(cfg::MappedKeys::default()));
 
 const LINUX_PERMISSIONS_ERROR: &str = "Failed to open the output uinput device. Make sure you added the user executing kanata to the 'uinput' group and that the 'uinput' group is configured correctly.\nSee for more detail: https://github.com/jtroo/kanata/blob/main/docs/setup-linux.md";
@@ -392,7 +616,7 @@ impl Kanata {
             }
         };
 
-        let kbd_out = match KbdOut::new(
+        let mut kbd_out = match KbdOut::new(
             #[cfg(any(target_os = "linux", target_os = "android"))]
             &args.symlink_path,
             #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -430,17 +654,41 @@ impl Kanata {
             );
         }
 
-        update_kbd_out(&cfg.options, &kbd_out)?;
+        update_kbd_out(&cfg.options, &mut kbd_out)?;
 
         #[cfg(target_os = "windows")]
         set_win_altgr_behaviour(cfg.options.windows_opts.windows_altgr);
+        #[cfg(target_os = "windows")]
+        NUMLOCK_NORMALIZE.store(
+            cfg.options.windows_opts.numlock_normalize,
+            std::sync::atomic::Ordering::SeqCst,
+        );
+        #[cfg(target_os = "windows")]
+        RAW_EVENT_MODE.store(
+            cfg.options.event_mode == EventMode::Raw,
+            std::sync::atomic::Ordering::SeqCst,
+        );
+        #[cfg(any(target_os = "windows", target_os = "macos"))]
+        TRACK_DEAD_KEYS.store(
+            cfg.options.track_dead_keys,
+            std::sync::atomic::Ordering::SeqCst,
+        );
 
         *MAPPED_KEYS.lock() = cfg.mapped_keys;
+        #[cfg(feature = "tcp_server")]
+        crate::tcp_server::set_key_monitor_rate(cfg.options.monitor_max_events_per_second);
         #[cfg(feature = "zippychord")]
         {
             zch().zch_configure(cfg.zippy.unwrap_or_default());
         }
 
+        #[cfg(feature = "tcp_server")]
+        let tcp_server_tls = crate::tcp_server::load_tls_config(&cfg.options)?;
+        let allow_hardware_repeat = cfg.options.allow_hardware_repeat
+            || cfg
+                .layer_info
+                .iter()
+                .any(|layer| layer.allow_hardware_repeat == Some(true));
         Ok(Self {
             kbd_out,
             cfg_paths: args.paths.clone(),
@@ -455,9 +703,13 @@ impl Kanata {
             hscroll_state: None,
             move_mouse_state_vertical: None,
             move_mouse_state_horizontal: None,
+            move_mouse_state_polar: None,
             move_mouse_speed_modifiers: Vec::new(),
+            mwheel_speed_modifiers: Vec::new(),
+            move_mouse_speed_reset_keys: Vec::new(),
             sequence_backtrack_modcancel: cfg.options.sequence_backtrack_modcancel,
             sequence_always_on: cfg.options.sequence_always_on,
+            sequence_notifications: cfg.options.sequence_notifications,
             sequence_input_mode: cfg.options.sequence_input_mode,
             sequence_timeout: cfg.options.sequence_timeout,
             sequence_state: SequenceState::new(),
@@ -471,6 +723,8 @@ impl Kanata {
             include_names: cfg.options.macos_opts.macos_dev_names_include,
             #[cfg(target_os = "macos")]
             exclude_names: cfg.options.macos_opts.macos_dev_names_exclude,
+            #[cfg(target_os = "macos")]
+            write_error_policy: cfg.options.macos_opts.macos_write_error_policy,
             #[cfg(any(target_os = "linux", target_os = "android"))]
             kbd_in_paths: cfg.options.linux_opts.linux_dev,
             #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -500,17 +754,45 @@ impl Kanata {
                 .windows_interception_keyboard_hwids_exclude,
             dynamic_macro_replay_state: None,
             dynamic_macro_record_state: None,
-            dynamic_macros: Default::default(),
+            dynamic_macros: cfg
+                .options
+                .dynamic_macro_persist_file
+                .as_ref()
+                .map(|f| load_dynamic_macros(f.as_ref()))
+                .unwrap_or_default(),
             log_layer_changes: get_forced_log_layer_changes()
                 .unwrap_or(cfg.options.log_layer_changes),
             caps_word: None,
+            sticky_layer: StickyLayerState::default(),
+            mouse_locks: MouseLocks::new(cfg.options.mouse_lock_release_on_layer_change),
+            runtime_vars: RuntimeVars::new(cfg.runtime_vars),
+            layer_cycles: LayerCycles::new(cfg.layer_cycles),
+            typing_cadence: TypingCadence::new(cfg.adaptive_tap_hold_window),
+            named_macros: NamedMacros::new(cfg.named_macros),
+            on_exit: OnExit::new(cfg.on_exit, cfg.options.on_exit_timeout),
+            multi_seq: MultiSeq::default(),
+            layer_hold_timeouts: LayerHoldTimeouts::default(),
+            schedule: Schedule::new(cfg.schedule),
+            temp_remaps: TempRemaps::default(),
+            key_stats: KeyStats::default(),
+            output_watchdog: OutputWatchdog::new(cfg.options.max_output_hold_ms),
+            debounce: Debounce::new(
+                cfg.options.debounce_algorithm,
+                cfg.options.debounce_ms,
+                cfg.options.debounce_keys,
+            ),
             movemouse_smooth_diagonals: cfg.options.movemouse_smooth_diagonals,
             override_release_on_activation: cfg.options.override_release_on_activation,
+            clean_mods_on_action: cfg.options.clean_mods_on_action,
+            clean_mods_override: None,
+            clean_mods_macro_mods: vec![],
             movemouse_inherit_accel_state: cfg.options.movemouse_inherit_accel_state,
+            mouse_speed_curve: cfg.options.mouse_speed_curve,
             dynamic_macro_max_presses: cfg.options.dynamic_macro_max_presses,
             dynamic_macro_replay_behaviour: ReplayBehaviour {
                 delay: cfg.options.dynamic_macro_replay_delay_behaviour,
             },
+            dynamic_macro_persist_file: cfg.options.dynamic_macro_persist_file.map(PathBuf::from),
             #[cfg(any(target_os = "linux", target_os = "android"))]
             x11_repeat_rate: cfg.options.linux_opts.linux_x11_repeat_delay_rate,
             #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -524,18 +806,63 @@ impl Kanata {
             vkeys_pending_release: HashMap::default(),
             ticks_since_idle: 0,
             ticks_since_physical_idle: 0,
+            idle_watcher_ms: vec![Some(0); cfg.idle_hooks.watchers.len()],
+            self_test_key: cfg.options.self_test_key,
+            macro_cancel_key: cfg.options.macro_cancel_key,
+            idle_hooks: cfg.idle_hooks,
+            ms_since_input: 0,
+            on_idle_fired: false,
             movemouse_buffer: None,
             unmodded_keys: vec![],
             unmodded_mods: UnmodMods::empty(),
             unshifted_keys: vec![],
+            manually_held_keys: vec![],
+            chord_output_keys: vec![],
+            input_scancodes: Default::default(),
+            output_scancodes: Default::default(),
             last_pressed_key: KeyCode::No,
+            rpt_repeats_macros: cfg.options.rpt_repeats_macros,
+            layer_stack_max_depth: cfg.options.layer_stack_max_depth,
+            rpt_held_key: None,
             virtual_keys: cfg.fake_keys,
             switch_max_key_timing: cfg.switch_max_key_timing,
             #[cfg(feature = "tcp_server")]
-            tcp_server_address: args.tcp_server_address.clone(),
+            tcp_server_address: args
+                .tcp_server_address
+                .clone()
+                .or(cfg.options.tcp_server_address.map(SocketAddrWrapper)),
+            #[cfg(feature = "tcp_server")]
+            tcp_server_protocol: cfg.options.tcp_server_protocol,
+            #[cfg(feature = "tcp_server")]
+            tcp_server_format: cfg.options.tcp_server_format,
+            #[cfg(feature = "tcp_server")]
+            tcp_server_tls,
+            #[cfg(feature = "tcp_server")]
+            tcp_server_token: cfg.options.tcp_server_token,
+            #[cfg(feature = "tcp_server")]
+            tcp_max_clients: cfg.options.tcp_max_clients.into(),
+            #[cfg(feature = "tcp_server")]
+            tcp_command_timeout: time::Duration::from_millis(
+                cfg.options.tcp_command_timeout_ms.into(),
+            ),
+            #[cfg(feature = "tcp_server")]
+            pressed_keys_sent: PressedKeys::default(),
+            #[cfg(all(
+                feature = "tcp_server",
+                any(target_os = "linux", target_os = "android", target_os = "macos")
+            ))]
+            unix_socket_path: cfg
+                .options
+                .unix_socket_path
+                .as_ref()
+                .map(crate::tcp_server::resolve_unix_socket_path),
             #[cfg(all(target_os = "windows", feature = "gui"))]
             gui_opts: cfg.options.gui_opts,
-            allow_hardware_repeat: cfg.options.allow_hardware_repeat,
+            allow_hardware_repeat,
+            default_allow_hardware_repeat: cfg.options.allow_hardware_repeat,
+            default_process_unmapped_keys: cfg.options.process_unmapped_keys,
+            unmapped_keys: cfg.unmapped_keys,
+            passed_through_keys: HashSet::default(),
             macro_on_press_cancel_duration: 0,
             saved_clipboard_content: Default::default(),
             #[cfg(any(
@@ -547,7 +874,7 @@ impl Kanata {
             #[cfg(feature = "tcp_server")]
             start_time: web_time::Instant::now(),
             #[cfg(feature = "tcp_server")]
-            last_reload_ok: true,
+            last_reload: ReloadOutcome::default(),
         })
     }
 
@@ -564,7 +891,7 @@ impl Kanata {
             }
         };
 
-        let kbd_out = match KbdOut::new(
+        let mut kbd_out = match KbdOut::new(
             #[cfg(any(target_os = "linux", target_os = "android"))]
             &None,
             #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -584,13 +911,23 @@ impl Kanata {
                 bail!(err)
             }
         };
+        update_kbd_out(&cfg.options, &mut kbd_out)?;
 
         *MAPPED_KEYS.lock() = cfg.mapped_keys;
+        #[cfg(feature = "tcp_server")]
+        crate::tcp_server::set_key_monitor_rate(cfg.options.monitor_max_events_per_second);
         #[cfg(feature = "zippychord")]
         {
             zch().zch_configure(cfg.zippy.unwrap_or_default());
         }
 
+        #[cfg(feature = "tcp_server")]
+        let tcp_server_tls = crate::tcp_server::load_tls_config(&cfg.options)?;
+        let allow_hardware_repeat = cfg.options.allow_hardware_repeat
+            || cfg
+                .layer_info
+                .iter()
+                .any(|layer| layer.allow_hardware_repeat == Some(true));
         Ok(Self {
             kbd_out,
             cfg_paths: vec!["config string".into()],
@@ -605,9 +942,13 @@ impl Kanata {
             hscroll_state: None,
             move_mouse_state_vertical: None,
             move_mouse_state_horizontal: None,
+            move_mouse_state_polar: None,
             move_mouse_speed_modifiers: Vec::new(),
+            mwheel_speed_modifiers: Vec::new(),
+            move_mouse_speed_reset_keys: Vec::new(),
             sequence_backtrack_modcancel: cfg.options.sequence_backtrack_modcancel,
             sequence_always_on: cfg.options.sequence_always_on,
+            sequence_notifications: cfg.options.sequence_notifications,
             sequence_input_mode: cfg.options.sequence_input_mode,
             sequence_timeout: cfg.options.sequence_timeout,
             sequence_state: SequenceState::new(),
@@ -621,6 +962,8 @@ impl Kanata {
             include_names: cfg.options.macos_opts.macos_dev_names_include,
             #[cfg(target_os = "macos")]
             exclude_names: cfg.options.macos_opts.macos_dev_names_exclude,
+            #[cfg(target_os = "macos")]
+            write_error_policy: cfg.options.macos_opts.macos_write_error_policy,
             #[cfg(any(target_os = "linux", target_os = "android"))]
             kbd_in_paths: cfg.options.linux_opts.linux_dev,
             #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -650,17 +993,45 @@ impl Kanata {
                 .windows_interception_keyboard_hwids_exclude,
             dynamic_macro_replay_state: None,
             dynamic_macro_record_state: None,
-            dynamic_macros: Default::default(),
+            dynamic_macros: cfg
+                .options
+                .dynamic_macro_persist_file
+                .as_ref()
+                .map(|f| load_dynamic_macros(f.as_ref()))
+                .unwrap_or_default(),
             log_layer_changes: get_forced_log_layer_changes()
                 .unwrap_or(cfg.options.log_layer_changes),
             caps_word: None,
+            sticky_layer: StickyLayerState::default(),
+            mouse_locks: MouseLocks::new(cfg.options.mouse_lock_release_on_layer_change),
+            runtime_vars: RuntimeVars::new(cfg.runtime_vars),
+            layer_cycles: LayerCycles::new(cfg.layer_cycles),
+            typing_cadence: TypingCadence::new(cfg.adaptive_tap_hold_window),
+            named_macros: NamedMacros::new(cfg.named_macros),
+            on_exit: OnExit::new(cfg.on_exit, cfg.options.on_exit_timeout),
+            multi_seq: MultiSeq::default(),
+            layer_hold_timeouts: LayerHoldTimeouts::default(),
+            schedule: Schedule::new(cfg.schedule),
+            temp_remaps: TempRemaps::default(),
+            key_stats: KeyStats::default(),
+            output_watchdog: OutputWatchdog::new(cfg.options.max_output_hold_ms),
+            debounce: Debounce::new(
+                cfg.options.debounce_algorithm,
+                cfg.options.debounce_ms,
+                cfg.options.debounce_keys,
+            ),
             movemouse_smooth_diagonals: cfg.options.movemouse_smooth_diagonals,
             override_release_on_activation: cfg.options.override_release_on_activation,
+            clean_mods_on_action: cfg.options.clean_mods_on_action,
+            clean_mods_override: None,
+            clean_mods_macro_mods: vec![],
             movemouse_inherit_accel_state: cfg.options.movemouse_inherit_accel_state,
+            mouse_speed_curve: cfg.options.mouse_speed_curve,
             dynamic_macro_max_presses: cfg.options.dynamic_macro_max_presses,
             dynamic_macro_replay_behaviour: ReplayBehaviour {
                 delay: cfg.options.dynamic_macro_replay_delay_behaviour,
             },
+            dynamic_macro_persist_file: cfg.options.dynamic_macro_persist_file.map(PathBuf::from),
             #[cfg(any(target_os = "linux", target_os = "android"))]
             x11_repeat_rate: cfg.options.linux_opts.linux_x11_repeat_delay_rate,
             #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -674,18 +1045,60 @@ impl Kanata {
             vkeys_pending_release: HashMap::default(),
             ticks_since_idle: 0,
             ticks_since_physical_idle: 0,
+            idle_watcher_ms: vec![Some(0); cfg.idle_hooks.watchers.len()],
+            self_test_key: cfg.options.self_test_key,
+            macro_cancel_key: cfg.options.macro_cancel_key,
+            idle_hooks: cfg.idle_hooks,
+            ms_since_input: 0,
+            on_idle_fired: false,
             movemouse_buffer: None,
             unmodded_keys: vec![],
             unmodded_mods: UnmodMods::empty(),
             unshifted_keys: vec![],
+            manually_held_keys: vec![],
+            chord_output_keys: vec![],
+            input_scancodes: Default::default(),
+            output_scancodes: Default::default(),
             last_pressed_key: KeyCode::No,
+            rpt_repeats_macros: cfg.options.rpt_repeats_macros,
+            layer_stack_max_depth: cfg.options.layer_stack_max_depth,
+            rpt_held_key: None,
             virtual_keys: cfg.fake_keys,
             switch_max_key_timing: cfg.switch_max_key_timing,
             #[cfg(feature = "tcp_server")]
-            tcp_server_address: None,
+            tcp_server_address: cfg.options.tcp_server_address.map(SocketAddrWrapper),
+            #[cfg(feature = "tcp_server")]
+            tcp_server_protocol: cfg.options.tcp_server_protocol,
+            #[cfg(feature = "tcp_server")]
+            tcp_server_format: cfg.options.tcp_server_format,
+            #[cfg(feature = "tcp_server")]
+            tcp_server_tls,
+            #[cfg(feature = "tcp_server")]
+            tcp_server_token: cfg.options.tcp_server_token,
+            #[cfg(feature = "tcp_server")]
+            tcp_max_clients: cfg.options.tcp_max_clients.into(),
+            #[cfg(feature = "tcp_server")]
+            tcp_command_timeout: time::Duration::from_millis(
+                cfg.options.tcp_command_timeout_ms.into(),
+            ),
+            #[cfg(feature = "tcp_server")]
+            pressed_keys_sent: PressedKeys::default(),
+            #[cfg(all(
+                feature = "tcp_server",
+                any(target_os = "linux", target_os = "android", target_os = "macos")
+            ))]
+            unix_socket_path: cfg
+                .options
+                .unix_socket_path
+                .as_ref()
+                .map(crate::tcp_server::resolve_unix_socket_path),
             #[cfg(all(target_os = "windows", feature = "gui"))]
             gui_opts: cfg.options.gui_opts,
-            allow_hardware_repeat: cfg.options.allow_hardware_repeat,
+            allow_hardware_repeat,
+            default_allow_hardware_repeat: cfg.options.allow_hardware_repeat,
+            default_process_unmapped_keys: cfg.options.process_unmapped_keys,
+            unmapped_keys: cfg.unmapped_keys,
+            passed_through_keys: HashSet::default(),
             macro_on_press_cancel_duration: 0,
             saved_clipboard_content: Default::default(),
             #[cfg(any(
@@ -698,7 +1111,7 @@ impl Kanata {
             #[cfg(feature = "tcp_server")]
             start_time: web_time::Instant::now(),
             #[cfg(feature = "tcp_server")]
-            last_reload_ok: true,
+            last_reload: ReloadOutcome::default(),
         })
     }
 
@@ -712,26 +1125,135 @@ impl Kanata {
         Ok(Arc::new(Mutex::new(k)))
     }
 
-    fn do_live_reload(&mut self, _tx: &Option<Sender<ServerMessage>>) -> Result<()> {
+    fn do_live_reload(&mut self, tx: &Option<Sender<ServerMessage>>) -> Result<()> {
+        #[cfg(feature = "tcp_server")]
+        let start = web_time::Instant::now();
+        let result = self.reload_cfg_file(tx);
+        #[cfg(feature = "tcp_server")]
+        {
+            self.last_reload = ReloadOutcome {
+                error: result.as_ref().err().map(|e| e.to_string()),
+                duration: start.elapsed(),
+            };
+        }
+        result
+    }
+
+    /// Returns to the first layer and drops all held, latched and pending state: layers, keys,
+    /// one-shots, chords, sequences, sticky layers, caps-word, mouse actions and macros. Mouse
+    /// buttons are released now and other keys output by kanata on the next tick.
+    pub fn clear_state(&mut self) -> Result<()> {
+        log::info!("clearing all state");
+        let layout = self.layout.bm();
+        let held_btns: Vec<Btn> = layout
+            .states
+            .iter()
+            .filter_map(|s| match s {
+                State::Custom { value, .. } => Some(value.iter()),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|ac| match ac {
+                CustomAction::Mouse(btn) => Some(*btn),
+                _ => None,
+            })
+            .collect();
+        layout.clear_state(0);
+        for btn in held_btns {
+            self.kbd_out.release_btn(btn)?;
+        }
+        self.mouse_locks.release_all(&mut self.kbd_out)?;
+        self.sticky_layer = StickyLayerState::default();
+        self.caps_word = None;
+        self.sequence_state = SequenceState::new();
+        self.multi_seq.clear();
+        self.layer_cycles.reset();
+        self.layer_hold_timeouts.clear();
+        self.manually_held_keys.clear();
+        self.chord_output_keys.clear();
+        self.unmodded_keys.clear();
+        self.unshifted_keys.clear();
+        self.override_states = OverrideStates::new();
+        self.vkeys_pending_release.clear();
+        self.rpt_held_key = None;
+        self.scroll_state = None;
+        self.hscroll_state = None;
+        self.move_mouse_state_vertical = None;
+        self.move_mouse_state_horizontal = None;
+        self.move_mouse_state_polar = None;
+        self.movemouse_buffer = None;
+        self.move_mouse_speed_modifiers.clear();
+        self.mwheel_speed_modifiers.clear();
+        self.move_mouse_speed_reset_keys.clear();
+        self.dynamic_macro_replay_state = None;
+        self.dynamic_macro_record_state = None;
+        Ok(())
+    }
+
+    fn reload_cfg_file(&mut self, _tx: &Option<Sender<ServerMessage>>) -> Result<()> {
         let cfg = match cfg::new_from_file(&self.cfg_paths[self.cur_cfg_idx]) {
             Ok(c) => c,
             Err(e) => {
                 log::error!("{e:?}");
-                #[cfg(feature = "tcp_server")]
-                {
-                    self.last_reload_ok = false;
-                }
-                bail!("failed to parse config file");
+                bail!("failed to parse config file: {}", error_summary(&e));
             }
         };
-        update_kbd_out(&cfg.options, &self.kbd_out)?;
+        update_kbd_out(&cfg.options, &mut self.kbd_out)?;
+        #[cfg(feature = "tcp_server")]
+        let tcp_server_tls = crate::tcp_server::load_tls_config(&cfg.options)?;
+        #[cfg(feature = "tcp_server")]
+        let old_layer_name = self.layer_info[self.prev_layer].name.clone();
+        let _old_sticky_layer_name = (self.sticky_layer.mode != StickyLayerMode::Off).then(|| {
+            self.layer_info[usize::from(self.sticky_layer.layer)]
+                .name
+                .clone()
+        });
+        self.sticky_layer = StickyLayerState::default();
+        self.manually_held_keys.clear();
+        self.chord_output_keys.clear();
         #[cfg(target_os = "windows")]
         set_win_altgr_behaviour(cfg.options.windows_opts.windows_altgr);
+        #[cfg(target_os = "windows")]
+        NUMLOCK_NORMALIZE.store(
+            cfg.options.windows_opts.numlock_normalize,
+            std::sync::atomic::Ordering::SeqCst,
+        );
+        #[cfg(target_os = "windows")]
+        RAW_EVENT_MODE.store(
+            cfg.options.event_mode == EventMode::Raw,
+            std::sync::atomic::Ordering::SeqCst,
+        );
+        #[cfg(any(target_os = "windows", target_os = "macos"))]
+        TRACK_DEAD_KEYS.store(
+            cfg.options.track_dead_keys,
+            std::sync::atomic::Ordering::SeqCst,
+        );
         self.sequence_backtrack_modcancel = cfg.options.sequence_backtrack_modcancel;
         self.sequence_always_on = cfg.options.sequence_always_on;
+        self.sequence_notifications = cfg.options.sequence_notifications;
         self.sequence_input_mode = cfg.options.sequence_input_mode;
         self.sequence_timeout = cfg.options.sequence_timeout;
+        self.rpt_repeats_macros = cfg.options.rpt_repeats_macros;
+        self.default_allow_hardware_repeat = cfg.options.allow_hardware_repeat;
+        self.default_process_unmapped_keys = cfg.options.process_unmapped_keys;
+        self.unmapped_keys = cfg.unmapped_keys;
+        self.layer_stack_max_depth = cfg.options.layer_stack_max_depth;
+        let old_var_values = std::mem::take(&mut self.layout.bm().vars);
         self.layout = cfg.layout;
+        self.runtime_vars.reload(
+            cfg.runtime_vars,
+            cfg.options.runtime_vars_persist_on_reload,
+            &old_var_values,
+            self.layout.bm(),
+        );
+        self.layer_cycles = LayerCycles::new(cfg.layer_cycles);
+        self.typing_cadence = TypingCadence::new(cfg.adaptive_tap_hold_window);
+        self.named_macros = NamedMacros::new(cfg.named_macros);
+        self.on_exit
+            .configure(cfg.on_exit, cfg.options.on_exit_timeout);
+        self.multi_seq.clear();
+        self.layer_hold_timeouts.clear();
+        self.schedule = Schedule::new(cfg.schedule);
         self.key_outputs = cfg.key_outputs;
         self.layer_info = cfg.layer_info;
         self.sequences = cfg.sequences;
@@ -740,13 +1262,71 @@ impl Kanata {
             get_forced_log_layer_changes().unwrap_or(cfg.options.log_layer_changes);
         self.movemouse_smooth_diagonals = cfg.options.movemouse_smooth_diagonals;
         self.override_release_on_activation = cfg.options.override_release_on_activation;
+        self.clean_mods_on_action = cfg.options.clean_mods_on_action;
         self.movemouse_inherit_accel_state = cfg.options.movemouse_inherit_accel_state;
+        self.mouse_speed_curve = cfg.options.mouse_speed_curve;
+        self.output_watchdog
+            .configure(cfg.options.max_output_hold_ms);
+        self.debounce.configure(
+            cfg.options.debounce_algorithm,
+            cfg.options.debounce_ms,
+            cfg.options.debounce_keys,
+        );
+        self.mouse_locks.release_all(&mut self.kbd_out)?;
+        self.mouse_locks
+            .configure(cfg.options.mouse_lock_release_on_layer_change);
         self.dynamic_macro_max_presses = cfg.options.dynamic_macro_max_presses;
         self.dynamic_macro_replay_behaviour = ReplayBehaviour {
             delay: cfg.options.dynamic_macro_replay_delay_behaviour,
         };
+        self.dynamic_macro_persist_file = cfg.options.dynamic_macro_persist_file.map(PathBuf::from);
+        if let Some(f) = &self.dynamic_macro_persist_file {
+            self.dynamic_macros = load_dynamic_macros(f);
+        }
         self.switch_max_key_timing = cfg.switch_max_key_timing;
         self.virtual_keys = cfg.fake_keys;
+        self.idle_watcher_ms = vec![Some(0); cfg.idle_hooks.watchers.len()];
+        self.self_test_key = cfg.options.self_test_key;
+        self.macro_cancel_key = cfg.options.macro_cancel_key;
+        self.idle_hooks = cfg.idle_hooks;
+        #[cfg(feature = "tcp_server")]
+        if cfg.options.tcp_server_address.is_some_and(|address| {
+            self.tcp_server_address.as_ref().map(|a| *a.get_ref()) != Some(address)
+        }) {
+            log::warn!(
+                "defcfg option tcp-server-address will not take effect until kanata is restarted!"
+            );
+        }
+        #[cfg(feature = "tcp_server")]
+        {
+            self.tcp_server_protocol = cfg.options.tcp_server_protocol;
+            self.tcp_server_format = cfg.options.tcp_server_format;
+            self.tcp_server_tls = tcp_server_tls;
+            self.tcp_server_token = cfg.options.tcp_server_token;
+            self.tcp_max_clients = cfg.options.tcp_max_clients.into();
+            self.tcp_command_timeout =
+                time::Duration::from_millis(cfg.options.tcp_command_timeout_ms.into());
+            crate::tcp_server::set_key_monitor_rate(cfg.options.monitor_max_events_per_second);
+        }
+        #[cfg(all(
+            feature = "tcp_server",
+            any(target_os = "linux", target_os = "android", target_os = "macos")
+        ))]
+        if self.unix_socket_path
+            != cfg
+                .options
+                .unix_socket_path
+                .as_ref()
+                .map(crate::tcp_server::resolve_unix_socket_path)
+        {
+            log::warn!(
+                "defcfg option unix-socket-path will not take effect until kanata is restarted!"
+            );
+        }
+        #[cfg(target_os = "macos")]
+        {
+            self.write_error_policy = cfg.options.macos_opts.macos_write_error_policy;
+        }
         #[cfg(target_os = "windows")]
         {
             self.windows_sync_keystates = cfg.options.windows_opts.sync_keystates;
@@ -822,37 +1402,127 @@ impl Kanata {
         #[cfg(feature = "tcp_server")]
         if let Some(tx) = _tx {
             let new = self.layer_info[cur_layer].name.clone();
-            match tx.try_send(ServerMessage::LayerChange { new }) {
+            match tx.try_send(ServerMessage::LayerChange {
+                new,
+                old: Some(old_layer_name),
+            }) {
                 Ok(_) => {}
                 Err(error) => {
                     log::error!("could not send LayerChange event notification: {}", error);
                 }
             }
+            if let Some(layer) = _old_sticky_layer_name {
+                send_sticky_layer_change(tx, layer, StickyLayerMode::Off);
+            }
         }
         #[cfg(all(target_os = "windows", feature = "gui"))]
         send_gui_cfg_notice();
 
-        #[cfg(feature = "tcp_server")]
-        {
-            self.last_reload_ok = true;
-        }
-
         Ok(())
     }
 
     /// Update keyberon layout state for press/release, handle repeat separately
     pub fn handle_input_event(&mut self, event: &KeyEvent) -> Result<()> {
         log::debug!("process recv ev {event:?}");
+        // Only input devices chatter.
+        if !event.injected && !self.debounce.accept(event) {
+            log::debug!("debouncing {event:?}");
+            return Ok(());
+        }
+        self.handle_debounced_input_event(event)
+    }
+
+    fn handle_debounced_input_event(&mut self, event: &KeyEvent) -> Result<()> {
         let evc: u16 = event.code.into();
         self.ticks_since_idle = 0;
+        self.ms_since_input = 0;
+        self.idle_watcher_ms.fill(Some(0));
+        if self.on_idle_fired {
+            self.on_idle_fired = false;
+            if let Some(y) = self.idle_hooks.on_resume {
+                log::debug!("input resumed, running on-resume action");
+                handle_fakekey_action(FakeKeyAction::Tap, self.layout.bm(), FAKE_KEY_ROW, y);
+            }
+        }
+        self.key_stats.record(event);
+        self.output_watchdog.record_input(event);
+        self.temp_remaps.expire();
+        if let Some(to) = self.temp_remaps.remap(event) {
+            log::debug!("temporarily remapped {:?} to {to:?}", event.code);
+            return match event.value {
+                KeyValue::Tap => self
+                    .kbd_out
+                    .write_key(to, KeyValue::Press)
+                    .and_then(|()| self.kbd_out.write_key(to, KeyValue::Release)),
+                KeyValue::WakeUp => Ok(()),
+                value => self.kbd_out.write_key(to, value),
+            }
+            .map_err(Into::into);
+        }
+        if event.value == KeyValue::Repeat && !self.base_layer_allows_hardware_repeat() {
+            log::debug!("base layer does not allow hardware repeat, dropping {event:?}");
+            return Ok(());
+        }
+        if self.passes_through_for_layer(event) {
+            if self.mouse_locks.is_locked_osc(event.code) {
+                log::debug!("dropping {event:?} of a locked mouse button");
+                return Ok(());
+            }
+            log::debug!("passing through {event:?}");
+            return match event.value {
+                KeyValue::Repeat => write_key(&mut self.kbd_out, event.code, KeyValue::Repeat),
+                value => {
+                    write_key_passthrough(&mut self.kbd_out, event.code, value, event.scancode)
+                }
+            }
+            .map_err(Into::into);
+        }
+        match (event.value, event.scancode) {
+            (KeyValue::Press, Some(scancode)) => {
+                self.input_scancodes.insert(event.code, scancode);
+            }
+            (KeyValue::Release, _) => {
+                self.input_scancodes.remove(&event.code);
+                if self
+                    .rpt_held_key
+                    .is_some_and(|(held, _)| held == event.code)
+                {
+                    self.rpt_held_key = None;
+                }
+            }
+            _ => {}
+        }
         let kbrn_ev = match event.value {
             KeyValue::Press => {
+                self.typing_cadence.press(self.layout.bm());
+                self.layer_hold_timeouts.key_pressed();
                 if let Some((macro_id, recorded_macro)) = record_press(
                     &mut self.dynamic_macro_record_state,
                     event.code,
                     self.dynamic_macro_max_presses,
                 ) {
-                    self.dynamic_macros.insert(macro_id, recorded_macro);
+                    store_dynamic_macro(
+                        &mut self.dynamic_macros,
+                        self.dynamic_macro_persist_file.as_deref(),
+                        macro_id,
+                        recorded_macro,
+                    );
+                }
+                if self.macro_cancel_key == Some(event.code) {
+                    let layout = self.layout.bm();
+                    if !layout.active_sequences.is_empty()
+                        || layout
+                            .states
+                            .iter()
+                            .any(|s| matches!(s, State::RepeatingSequence { .. }))
+                    {
+                        log::info!("cancelling all macros: macro-cancel-key");
+                        self.macro_on_press_cancel_duration = 0;
+                        layout.active_sequences.clear();
+                        layout.states.retain(|s| {
+                            !matches!(s, State::FakeKey { .. } | State::RepeatingSequence { .. })
+                        });
+                    }
                 }
                 if self.macro_on_press_cancel_duration > 0 {
                     log::debug!("cancelling all macros: other press");
@@ -886,6 +1556,47 @@ impl Kanata {
         Ok(())
     }
 
+    /// Whether the event must be written out unprocessed because a `passthrough-while-held` key
+    /// is held, because the base layer does not process unmapped keys, or because the press of its
+    /// key was. Keys that were already held when `passthrough-while-held` was pressed are still
+    /// released through the layout, so that their state does not get stuck.
+    fn passes_through_for_layer(&mut self, event: &KeyEvent) -> bool {
+        let base_layer = &self.layer_info[self.layout.b().default_layer];
+        match event.value {
+            KeyValue::Press => {
+                let processes_unmapped_keys = base_layer
+                    .process_unmapped_keys
+                    .unwrap_or(self.default_process_unmapped_keys);
+                if !self.passthrough_is_held()
+                    && (processes_unmapped_keys || !self.unmapped_keys.contains(&event.code))
+                {
+                    return false;
+                }
+                self.passed_through_keys.insert(event.code);
+                true
+            }
+            KeyValue::Release => self.passed_through_keys.remove(&event.code),
+            KeyValue::Repeat => self.passed_through_keys.contains(&event.code),
+            _ => false,
+        }
+    }
+
+    fn passthrough_is_held(&self) -> bool {
+        self.layout.b().states.iter().any(|s| match s {
+            State::Custom { value, .. } => value
+                .iter()
+                .any(|ac| matches!(ac, CustomAction::PassthroughWhileHeld)),
+            _ => false,
+        })
+    }
+
+    /// Whether the base layer allows hardware key repeats, see `allow-hardware-repeat`.
+    fn base_layer_allows_hardware_repeat(&self) -> bool {
+        self.layer_info[self.layout.b().default_layer]
+            .allow_hardware_repeat
+            .unwrap_or(self.default_allow_hardware_repeat)
+    }
+
     /// Returns the number of ms elapsed for the procesing loop according to current monotonic time
     /// and stored internal state. Mutates the internal time-tracking state.
     pub fn get_ms_elapsed(&mut self) -> u128 {
@@ -988,18 +1699,44 @@ impl Kanata {
         });
     }
 
+    fn tick_schedule(&mut self) {
+        self.schedule.tick(self.layout.bm());
+    }
+
     fn tick_states(&mut self, _tx: &Option<Sender<ServerMessage>>) -> Result<()> {
+        for event in self.debounce.tick() {
+            self.handle_debounced_input_event(&event)?;
+        }
         self.live_reload_requested |= self.handle_keystate_changes(_tx)?;
+        #[cfg(feature = "tcp_server")]
+        self.notify_pressed_keys(_tx);
         self.handle_scrolling()?;
         self.handle_move_mouse()?;
+        self.mouse_locks
+            .tick(self.layout.b().default_layer, &mut self.kbd_out)?;
         self.tick_sequence_state()?;
+        self.send_sequence_notifications(_tx);
         self.tick_idle_timeout();
         self.tick_physical_idle_timeout();
+        self.tick_idle_hooks();
+        self.named_macros.tick(self.layout.bm());
+        self.multi_seq.tick(self.layout.bm());
+        self.layer_hold_timeouts.tick(self.layout.bm());
+        let layout_is_idle = self.is_idle();
+        if self.on_exit.tick(self.layout.bm(), layout_is_idle) {
+            self.mouse_locks.release_all(&mut self.kbd_out)?;
+            #[cfg(not(test))]
+            exit_kanata(false);
+        }
+        self.tick_schedule();
         self.macro_on_press_cancel_duration = self.macro_on_press_cancel_duration.saturating_sub(1);
         tick_record_state(&mut self.dynamic_macro_record_state);
         zippy_tick(self.caps_word.is_some());
         self.prev_keys.clear();
         self.prev_keys.append(&mut self.cur_keys);
+        for kc in self.output_watchdog.tick(&self.prev_keys) {
+            self.force_release_output(kc)?;
+        }
         self.tick_held_vkeys();
         #[cfg(feature = "simulated_output")]
         {
@@ -1008,14 +1745,43 @@ impl Kanata {
         Ok(())
     }
 
+    /// Releases an output key and removes everything that holds it, so that it is not pressed
+    /// again on the next tick.
+    fn force_release_output(&mut self, kc: KeyCode) -> Result<()> {
+        self.layout.bm().states.retain(|s| s.keycode() != Some(kc));
+        self.manually_held_keys.retain(|k| *k != kc);
+        self.chord_output_keys.retain(|k| *k != kc);
+        self.unmodded_keys.retain(|k| *k != kc);
+        self.unshifted_keys.retain(|k| *k != kc);
+        self.prev_keys.retain(|k| *k != kc);
+        let osc = kc.into();
+        let scancode = self.output_scancodes.remove(&osc);
+        if let Err(e) = write_key_passthrough(&mut self.kbd_out, osc, KeyValue::Release, scancode) {
+            bail!("failed to release key: {:?}", e);
+        }
+        Ok(())
+    }
+
     fn handle_scrolling(&mut self) -> Result<()> {
         if let Some((direction, distance)) = update_scrollstate_get_result(&mut self.scroll_state) {
             match distance {
                 0 => {
+                    if let Some(ScrollState {
+                        fine_scroll_state: Some(FineScrollState { began: true, .. }),
+                        ..
+                    }) = self.scroll_state
+                    {
+                        self.kbd_out.scroll_fine(direction, 0, ScrollPhase::Ended)?;
+                    }
                     self.scroll_state = None;
                 }
                 _ => {
-                    self.kbd_out.scroll(direction, distance)?;
+                    let distance =
+                        apply_mouse_distance_modifiers(distance, &self.mwheel_speed_modifiers);
+                    match fine_scroll_phase(&mut self.scroll_state, distance) {
+                        Some(phase) => self.kbd_out.scroll_fine(direction, distance, phase)?,
+                        None => self.kbd_out.scroll(direction, distance)?,
+                    }
                 }
             }
         }
@@ -1023,10 +1789,22 @@ impl Kanata {
         {
             match distance {
                 0 => {
+                    if let Some(ScrollState {
+                        fine_scroll_state: Some(FineScrollState { began: true, .. }),
+                        ..
+                    }) = self.hscroll_state
+                    {
+                        self.kbd_out.scroll_fine(direction, 0, ScrollPhase::Ended)?;
+                    }
                     self.hscroll_state = None;
                 }
                 _ => {
-                    self.kbd_out.scroll(direction, distance)?;
+                    let distance =
+                        apply_mouse_distance_modifiers(distance, &self.mwheel_speed_modifiers);
+                    match fine_scroll_phase(&mut self.hscroll_state, distance) {
+                        Some(phase) => self.kbd_out.scroll_fine(direction, distance, phase)?,
+                        None => self.kbd_out.scroll(direction, distance)?,
+                    }
                 }
             }
         }
@@ -1048,10 +1826,12 @@ impl Kanata {
             }
         }
         if let Some(mmsv) = &mut self.move_mouse_state_vertical {
+            mmsv.held_ms = mmsv.held_ms.saturating_add(1);
             if mmsv.ticks_until_move == 0 {
                 mmsv.ticks_until_move = mmsv.interval - 1;
+                let distance = apply_speed_curve(mmsv, self.mouse_speed_curve.as_ref());
                 let scaled_distance =
-                    apply_mouse_distance_modifiers(mmsv.distance, &self.move_mouse_speed_modifiers);
+                    apply_mouse_distance_modifiers(distance, &self.move_mouse_speed_modifiers);
                 log::debug!("handle_move_mouse: scaled vdistance: {}", scaled_distance);
 
                 let current_move = CalculatedMouseMove {
@@ -1097,10 +1877,12 @@ impl Kanata {
             }
         }
         if let Some(mmsh) = &mut self.move_mouse_state_horizontal {
+            mmsh.held_ms = mmsh.held_ms.saturating_add(1);
             if mmsh.ticks_until_move == 0 {
                 mmsh.ticks_until_move = mmsh.interval - 1;
+                let distance = apply_speed_curve(mmsh, self.mouse_speed_curve.as_ref());
                 let scaled_distance =
-                    apply_mouse_distance_modifiers(mmsh.distance, &self.move_mouse_speed_modifiers);
+                    apply_mouse_distance_modifiers(distance, &self.move_mouse_speed_modifiers);
                 log::debug!("handle_move_mouse: scaled hdistance: {}", scaled_distance);
 
                 let current_move = CalculatedMouseMove {
@@ -1132,6 +1914,34 @@ impl Kanata {
                 mmsh.ticks_until_move -= 1;
             }
         }
+        if let Some(polar) = &mut self.move_mouse_state_polar {
+            let scale = self
+                .move_mouse_speed_modifiers
+                .iter()
+                .fold(1.0, |scale, speed| scale * f32::from(*speed) / 100.0);
+            let (x, y) = polar.tick(scale);
+            let horizontal = (x != 0).then(|| CalculatedMouseMove {
+                direction: if x > 0 {
+                    MoveDirection::Right
+                } else {
+                    MoveDirection::Left
+                },
+                distance: x.unsigned_abs() as u16,
+            });
+            let vertical = (y != 0).then(|| CalculatedMouseMove {
+                direction: if y > 0 {
+                    MoveDirection::Down
+                } else {
+                    MoveDirection::Up
+                },
+                distance: y.unsigned_abs() as u16,
+            });
+            match (horizontal, vertical) {
+                (Some(h), Some(v)) => self.kbd_out.move_mouse_many(&[h, v])?,
+                (Some(mv), None) | (None, Some(mv)) => self.kbd_out.move_mouse(mv)?,
+                (None, None) => {}
+            }
+        }
         Ok(())
     }
 
@@ -1140,7 +1950,7 @@ impl Kanata {
             state.ticks_until_timeout -= 1;
             if state.ticks_until_timeout == 0 {
                 log::debug!("sequence timeout; exiting sequence state");
-                cancel_sequence(state, &mut self.kbd_out)?;
+                cancel_sequence(state, &mut self.kbd_out, SequenceEndReason::Timeout)?;
             }
         }
         Ok(())
@@ -1180,6 +1990,37 @@ impl Kanata {
         })
     }
 
+    fn tick_idle_hooks(&mut self) {
+        if let Some((idle_ms, y)) = self.idle_hooks.on_idle
+            && !self.on_idle_fired
+            && self.ms_since_input >= idle_ms
+        {
+            log::debug!("no input for {idle_ms}ms, running on-idle action");
+            self.on_idle_fired = true;
+            handle_fakekey_action(FakeKeyAction::Tap, self.layout.bm(), FAKE_KEY_ROW, y);
+        }
+        for (watcher, ms) in self
+            .idle_hooks
+            .watchers
+            .iter()
+            .zip(self.idle_watcher_ms.iter_mut())
+        {
+            if let Some(idle_ms) = *ms
+                && idle_ms >= watcher.idle_ms
+                && is_idle_watcher_armed(watcher, self.layout.b().current_layer())
+            {
+                log::debug!("no input for {idle_ms}ms, running defidle action");
+                *ms = None;
+                handle_fakekey_action(
+                    FakeKeyAction::Tap,
+                    self.layout.bm(),
+                    FAKE_KEY_ROW,
+                    watcher.y,
+                );
+            }
+        }
+    }
+
     /// Sends OS key events according to the change in key state between the current and the
     /// previous keyberon keystate. Also processes any custom actions.
     ///
@@ -1189,6 +2030,16 @@ impl Kanata {
     fn handle_keystate_changes(&mut self, _tx: &Option<Sender<ServerMessage>>) -> Result<bool> {
         let layout = self.layout.bm();
         let custom_event = layout.tick();
+        if self.dynamic_macro_replay_state.is_some() {
+            // Keys of a playing dynamic macro go through the layout, but the playback should be
+            // repeated as a whole.
+            layout.rpt_action = layout.rpt_replay_action;
+        } else if let Some(action) = layout.rpt_action
+            && !is_rpt_action(action)
+        {
+            layout.rpt_replay_action = Some(action);
+        }
+        let sticky_layer_before = (self.sticky_layer.layer, self.sticky_layer.mode);
 
         #[cfg(feature = "tcp_server")]
         if let Some(hold_info) = layout.tap_hold_tracker.take_hold_activated()
@@ -1241,6 +2092,18 @@ impl Kanata {
                         CustomAction::Unshifted { keys } => {
                             self.unshifted_keys.extend(keys.iter());
                         }
+                        CustomAction::CleanModsOnAction(clean) => {
+                            self.clean_mods_override = Some(*clean);
+                        }
+                        CustomAction::PressKey(kc) if !self.manually_held_keys.contains(kc) => {
+                            self.manually_held_keys.push(*kc);
+                        }
+                        CustomAction::ReleaseKey(kc) => {
+                            self.manually_held_keys.retain(|k| k != kc);
+                        }
+                        CustomAction::ChordOutput(keys) => {
+                            self.chord_output_keys.extend(keys.iter());
+                        }
                         _ => {}
                     }
                 }
@@ -1257,12 +2120,25 @@ impl Kanata {
                         CustomAction::ReverseReleaseOrder => {
                             reverse_release_order = true;
                         }
+                        CustomAction::ChordOutput(keys) => {
+                            for kc in keys.iter() {
+                                if let Some(i) = self.chord_output_keys.iter().position(|k| k == kc)
+                                {
+                                    self.chord_output_keys.swap_remove(i);
+                                }
+                            }
+                        }
                         _ => {}
                     }
                 }
             }
             _ => {}
         }
+        for kc in self.manually_held_keys.iter() {
+            if !cur_keys.contains(kc) {
+                cur_keys.push(*kc);
+            }
+        }
         if !self.unmodded_keys.is_empty() {
             for mod_key in self.unmodded_mods.iter() {
                 let kc = match mod_key {
@@ -1284,12 +2160,79 @@ impl Kanata {
             cur_keys.retain(|k| !matches!(k, KeyCode::LShift | KeyCode::RShift));
             cur_keys.extend(self.unshifted_keys.iter());
         }
+        // Macro output is tracked as fake keys in the layout. While a macro or a chord-output
+        // runs, drop held modifiers that it does not press itself. Modifiers pressed or released
+        // in the meantime are reflected in the layout state, so the restore afterwards needs no
+        // bookkeeping.
+        let macro_running = !layout.active_sequences.is_empty()
+            || layout
+                .states
+                .iter()
+                .any(|s| matches!(s, State::FakeKey { .. }));
+        if !macro_running && self.chord_output_keys.is_empty() {
+            self.clean_mods_override = None;
+            self.clean_mods_macro_mods.clear();
+        } else if self
+            .clean_mods_override
+            .unwrap_or(self.clean_mods_on_action)
+        {
+            // Once a macro has pressed a modifier, keep it until the macro ends so that the
+            // user's own copy of it is not released and re-pressed in between.
+            for s in layout.states.iter() {
+                if let State::FakeKey { keycode } = s
+                    && OsCode::from(*keycode).is_modifier()
+                    && !self.clean_mods_macro_mods.contains(keycode)
+                {
+                    self.clean_mods_macro_mods.push(*keycode);
+                }
+            }
+            let will_press = |k: &KeyCode| {
+                layout.active_sequences.iter().any(|seq| {
+                    seq.remaining_events().iter().any(|ev| {
+                        matches!(ev, SequenceEvent::Press(kc) | SequenceEvent::Tap(kc) if kc == k)
+                    })
+                })
+            };
+            cur_keys.retain(|k| {
+                !OsCode::from(*k).is_modifier()
+                    || self.clean_mods_macro_mods.contains(k)
+                    || self.chord_output_keys.contains(k)
+                    || will_press(k)
+            });
+        }
 
         self.overrides.override_keys(
             cur_keys,
             &mut self.override_states,
             layout.current_layer() as u16,
         );
+        if self.override_states.has_actions() {
+            let action_keys = layout
+                .states
+                .iter()
+                .filter_map(|s| match s {
+                    State::FakeKey { keycode } => Some(*keycode),
+                    State::NormalKey {
+                        keycode,
+                        coord: (FAKE_KEY_ROW, _),
+                        ..
+                    } => Some(*keycode),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            self.override_states.update_actions(
+                cur_keys,
+                &action_keys,
+                macro_running || !layout.queue.is_empty(),
+                |y, event| {
+                    let action = match event {
+                        OverrideActionEvent::Press => FakeKeyAction::Press,
+                        OverrideActionEvent::Release => FakeKeyAction::Release,
+                    };
+                    handle_fakekey_action(action, layout, FAKE_KEY_ROW, y);
+                },
+            );
+        }
         mark_overridden_nonmodkeys_for_eager_erasure(&self.override_states, &mut layout.states);
         if self.override_release_on_activation {
             for removed in self.override_states.removed_oscs() {
@@ -1355,7 +2298,11 @@ impl Kanata {
                 continue;
             }
             log::debug!("key release   {:?}", k);
-            if let Err(e) = release_key(&mut self.kbd_out, k.into()) {
+            let osc = k.into();
+            let scancode = self.output_scancodes.remove(&osc);
+            if let Err(e) =
+                write_key_passthrough(&mut self.kbd_out, osc, KeyValue::Release, scancode)
+            {
                 bail!("failed to release key: {:?}", e);
             }
         }
@@ -1370,13 +2317,12 @@ impl Kanata {
                 .sequences
                 .get_or_descendant_exists(&state.overlapped_sequence)
             {
-                HasValue((i, j)) => {
+                HasValue(fkey) => {
                     do_successful_sequence_termination(
                         &mut self.kbd_out,
                         state,
                         layout,
-                        i,
-                        j,
+                        fkey,
                         EndSequenceType::Overlap,
                     )?;
                 }
@@ -1410,8 +2356,12 @@ impl Kanata {
             self.last_pressed_key = *k;
 
             if self.sequence_always_on && self.sequence_state.is_inactive() {
-                self.sequence_state
-                    .activate(self.sequence_input_mode, self.sequence_timeout);
+                self.sequence_state.activate(
+                    self.sequence_input_mode,
+                    self.sequence_timeout,
+                    &self.sequences,
+                    self.sequence_notifications,
+                );
             }
 
             if let Some(state) = self.sequence_state.get_active() {
@@ -1426,7 +2376,14 @@ impl Kanata {
                 )?;
             } else {
                 log::debug!("key press     {:?}", k);
-                if let Err(e) = press_key(&mut self.kbd_out, k.into()) {
+                let osc = k.into();
+                let scancode = self.input_scancodes.get(&osc).copied();
+                if let Some(scancode) = scancode {
+                    self.output_scancodes.insert(osc, scancode);
+                }
+                if let Err(e) =
+                    write_key_passthrough(&mut self.kbd_out, osc, KeyValue::Press, scancode)
+                {
                     bail!("failed to press key: {:?}", e);
                 }
             }
@@ -1440,11 +2397,28 @@ impl Kanata {
                 let mut cmds = vec![];
                 let mut prev_mouse_btn = None;
                 let mut reload_action: Option<ReloadAction> = None;
+                let mut clear_state = false;
                 for custact in custacts.iter() {
                     match custact {
                         // For unicode, only send on the press. No repeat action is supported for this for
                         // now.
-                        CustomAction::Unicode(c) => self.kbd_out.send_unicode(*c)?,
+                        CustomAction::Unicode(c) => {
+                            self.kbd_out.send_unicode(*c)?;
+                            if let Some(state) = self.sequence_state.get_active() {
+                                add_unicode_echo(state, *c);
+                            }
+                        }
+                        CustomAction::Str { text, delay } => {
+                            for (i, c) in text.chars().enumerate() {
+                                if i > 0 && *delay > 0 {
+                                    std::thread::sleep(time::Duration::from_millis((*delay).into()));
+                                }
+                                self.kbd_out.send_unicode(c)?;
+                                if let Some(state) = self.sequence_state.get_active() {
+                                    add_unicode_echo(state, c);
+                                }
+                            }
+                        }
                         CustomAction::LiveReload => {
                             reload_action = Some(ReloadAction::Reload);
                         }
@@ -1460,15 +2434,29 @@ impl Kanata {
                         CustomAction::LiveReloadFile(path) => {
                             reload_action = Some(ReloadAction::ReloadFile(path.to_string()));
                         }
+                        CustomAction::ClearState => {
+                            clear_state = true;
+                        }
                         CustomAction::Mouse(btn) => {
                             log::debug!("click     {:?}", btn);
                             if let Some(pbtn) = prev_mouse_btn {
                                 log::debug!("unclick   {:?}", pbtn);
                                 self.kbd_out.release_btn(pbtn)?;
                             }
-                            self.kbd_out.click_btn(*btn)?;
+                            if self.mouse_locks.take(*btn) {
+                                log::debug!("{btn:?} was locked, now held until release");
+                            } else {
+                                self.kbd_out.click_btn(*btn)?;
+                            }
                             prev_mouse_btn = Some(*btn);
                         }
+                        CustomAction::MouseLock(btn) => {
+                            self.mouse_locks.toggle(
+                                *btn,
+                                layout.default_layer,
+                                &mut self.kbd_out,
+                            )?;
+                        }
                         CustomAction::MouseTap(btn) => {
                             log::debug!("click     {:?}", btn);
                             self.kbd_out.click_btn(*btn)?;
@@ -1480,34 +2468,67 @@ impl Kanata {
                             interval,
                             distance,
                             inertial_scroll_params,
-                        } => match direction {
-                            MWheelDirection::Up | MWheelDirection::Down => {
-                                self.scroll_state = Some(ScrollState {
-                                    direction: *direction,
-                                    distance: *distance,
-                                    ticks_until_scroll: 0,
-                                    interval: *interval,
-                                    scroll_accel_state: inertial_scroll_params.as_ref().map(|isp|
-                                        ScrollAccelState {
+                        } => {
+                            let state = Some(ScrollState {
+                                direction: *direction,
+                                distance: *distance,
+                                ticks_until_scroll: 0,
+                                interval: *interval,
+                                scroll_accel_state: inertial_scroll_params.as_ref().map(|isp| {
+                                    ScrollAccelState {
                                         deceleration_multiplier: isp.deceleration_multiplier.0,
                                         acceleration_multiplier: isp.acceleration_multiplier.0,
                                         max_velocity: isp.maximum_velocity.0,
                                         current_velocity: isp.initial_velocity.0,
                                         scroll_released: false,
-                                        }
-                                    ),
-                                })
+                                    }
+                                }),
+                                fine_scroll_state: None,
+                            });
+                            match direction {
+                                MWheelDirection::Up | MWheelDirection::Down => {
+                                    self.scroll_state = state
+                                }
+                                MWheelDirection::Left | MWheelDirection::Right => {
+                                    self.hscroll_state = state
+                                }
                             }
-                            MWheelDirection::Left | MWheelDirection::Right => {
-                                self.hscroll_state = Some(ScrollState {
-                                    direction: *direction,
-                                    distance: *distance,
-                                    ticks_until_scroll: 0,
-                                    interval: *interval,
-                                    scroll_accel_state: None,
-                                })
+                        }
+                        CustomAction::MWheelFine {
+                            direction,
+                            interval,
+                            accel_time,
+                            min_distance,
+                            max_distance,
+                        } => {
+                            let accel = (*accel_time > 0).then(|| MoveMouseAccelState {
+                                accel_ticks_from_min: 0,
+                                accel_ticks_until_max: *accel_time,
+                                accel_increment: f64::from(max_distance - min_distance)
+                                    / f64::from(*accel_time),
+                                min_distance: *min_distance,
+                                max_distance: *max_distance,
+                            });
+                            let state = Some(ScrollState {
+                                direction: *direction,
+                                distance: *min_distance,
+                                ticks_until_scroll: 0,
+                                interval: *interval,
+                                scroll_accel_state: None,
+                                fine_scroll_state: Some(FineScrollState {
+                                    accel,
+                                    began: false,
+                                }),
+                            });
+                            match direction {
+                                MWheelDirection::Up | MWheelDirection::Down => {
+                                    self.scroll_state = state
+                                }
+                                MWheelDirection::Left | MWheelDirection::Right => {
+                                    self.hscroll_state = state
+                                }
                             }
-                        },
+                        }
                         CustomAction::MWheelNotch { direction } => {
                             self.kbd_out
                                 .scroll(*direction, HI_RES_SCROLL_UNITS_IN_LO_RES)?;
@@ -1516,14 +2537,17 @@ impl Kanata {
                             direction,
                             interval,
                             distance,
-                        } => match direction {
+                        } => {
+                            let interval = self.runtime_vars.resolve(*interval, 1, layout);
+                            match direction {
                             MoveDirection::Up | MoveDirection::Down => {
                                 self.move_mouse_state_vertical = Some(MoveMouseState {
                                     direction: *direction,
                                     distance: *distance,
                                     ticks_until_move: 0,
-                                    interval: *interval,
+                                    interval,
                                     move_mouse_accel_state: None,
+                                    held_ms: 0,
                                 })
                             }
                             MoveDirection::Left | MoveDirection::Right => {
@@ -1531,11 +2555,13 @@ impl Kanata {
                                     direction: *direction,
                                     distance: *distance,
                                     ticks_until_move: 0,
-                                    interval: *interval,
+                                    interval,
                                     move_mouse_accel_state: None,
+                                    held_ms: 0,
                                 })
                             }
-                        },
+                            }
+                        }
                         CustomAction::MoveMouseAccel {
                             direction,
                             interval,
@@ -1543,6 +2569,7 @@ impl Kanata {
                             min_distance,
                             max_distance,
                         } => {
+                            let interval = self.runtime_vars.resolve(*interval, 1, layout);
                             let move_mouse_accel_state = match (
                                 self.movemouse_inherit_accel_state,
                                 &self.move_mouse_state_horizontal,
@@ -1587,8 +2614,9 @@ impl Kanata {
                                         direction: *direction,
                                         distance: *min_distance,
                                         ticks_until_move: 0,
-                                        interval: *interval,
+                                        interval,
                                         move_mouse_accel_state: Some(move_mouse_accel_state),
+                                        held_ms: 0,
                                     })
                                 }
                                 MoveDirection::Left | MoveDirection::Right => {
@@ -1596,19 +2624,46 @@ impl Kanata {
                                         direction: *direction,
                                         distance: *min_distance,
                                         ticks_until_move: 0,
-                                        interval: *interval,
+                                        interval,
                                         move_mouse_accel_state: Some(move_mouse_accel_state),
+                                        held_ms: 0,
                                     })
                                 }
                             }
                         }
-                        CustomAction::MoveMouseSpeed { speed } => {
+                        CustomAction::MoveMousePolar { angle, speed } => {
+                            self.move_mouse_state_polar = Some(PolarMoveState::new(*angle, *speed));
+                        }
+                        CustomAction::MoveMouseSpeed {
+                            speed,
+                            scale_mwheel,
+                        } => {
                             self.move_mouse_speed_modifiers.push(*speed);
+                            if *scale_mwheel {
+                                self.mwheel_speed_modifiers.push(*speed);
+                            }
                             log::debug!(
                                 "movemousespeed modifiers: {:?}",
                                 self.move_mouse_speed_modifiers
                             );
                         }
+                        CustomAction::MoveMouseSpeedReset => {
+                            log::debug!("resetting movemousespeed modifiers");
+                            self.move_mouse_speed_modifiers.clear();
+                            self.mwheel_speed_modifiers.clear();
+                            self.move_mouse_speed_reset_keys = layout
+                                .states
+                                .iter()
+                                .filter_map(|s| match s {
+                                    State::Custom { value, coord }
+                                        if has_move_mouse_speed(value) =>
+                                    {
+                                        Some(*coord)
+                                    }
+                                    _ => None,
+                                })
+                                .collect();
+                        }
                         CustomAction::Cmd(_cmd) => {
                             #[cfg(feature = "cmd")]
                             cmds.push((
@@ -1679,7 +2734,24 @@ impl Kanata {
                                 layout.default_layer,
                                 layout.layers[layout.default_layer][x as usize][y as usize]
                             );
-                            handle_fakekey_action(*action, layout, x, y);
+                            match action {
+                                // A toggle is queued ahead of pending inputs so that they see
+                                // the new state, e.g. in a switch with vkey-active.
+                                FakeKeyAction::Toggle => {
+                                    let event = match states_has_coord(&layout.states, x, y) {
+                                        true => Event::Release(x, y),
+                                        false => Event::Press(x, y),
+                                    };
+                                    layout.event_to_front(event);
+                                }
+                                _ => handle_fakekey_action(*action, layout, x, y),
+                            }
+                        }
+                        CustomAction::FakeKeys(ops) => {
+                            log::debug!("fake keys on press {ops:?}");
+                            for event in fakekeys_events(ops, &layout.states).into_iter().rev() {
+                                layout.event_to_front(event);
+                            }
                         }
                         CustomAction::Delay(delay) => {
                             log::debug!("on-press: sleeping for {delay} ms");
@@ -1688,16 +2760,45 @@ impl Kanata {
                         CustomAction::SequenceCancel => {
                             if let Some(state) = self.sequence_state.get_active() {
                                 log::debug!("pressed cancel sequence key");
-                                cancel_sequence(state, &mut self.kbd_out)?;
+                                cancel_sequence(
+                                    state,
+                                    &mut self.kbd_out,
+                                    SequenceEndReason::Cancelled,
+                                )?;
                             }
                         }
                         CustomAction::SequenceLeader(timeout, input_mode) => {
                             if self.sequence_state.is_inactive() {
                                 log::debug!("entering sequence mode");
-                                self.sequence_state.activate(*input_mode, *timeout);
+                                self.sequence_state.activate(
+                                    *input_mode,
+                                    *timeout,
+                                    &self.sequences,
+                                    self.sequence_notifications,
+                                );
                             } else if *input_mode == SequenceInputMode::HiddenSuppressed {
                                 log::debug!("retriggering sequence mode");
-                                self.sequence_state.activate(*input_mode, *timeout);
+                                self.sequence_state.activate(
+                                    *input_mode,
+                                    *timeout,
+                                    &self.sequences,
+                                    self.sequence_notifications,
+                                );
+                            }
+                        }
+                        CustomAction::SequenceMatch => {
+                            let matched_keys = self.sequence_state.matched_keys;
+                            log::debug!("outputting sequence match {matched_keys:?}");
+                            for k in matched_keys.iter().copied() {
+                                let osc = OsCode::from(k & MASK_KEYCODES);
+                                for modk in mods_for_mod_mask(k) {
+                                    press_key(&mut self.kbd_out, modk)?;
+                                }
+                                press_key(&mut self.kbd_out, osc)?;
+                                release_key(&mut self.kbd_out, osc)?;
+                                for modk in mods_for_mod_mask(k) {
+                                    release_key(&mut self.kbd_out, modk)?;
+                                }
                             }
                         }
                         CustomAction::SequenceNoerase(noerase_count) => {
@@ -1706,7 +2807,31 @@ impl Kanata {
                                 add_noerase(state, *noerase_count);
                             }
                         }
+                        CustomAction::MultiSeq(steps) => {
+                            if let Some(coord) = custom_state_coord(&layout.states, custacts) {
+                                self.multi_seq.start(coord, steps);
+                            }
+                        }
+                        CustomAction::LayerWhileHeldTimeout { timeout } => {
+                            if let Some(coord) = custom_state_coord(&layout.states, custacts) {
+                                self.layer_hold_timeouts.press(coord, *timeout);
+                            }
+                        }
                         CustomAction::Repeat => {
+                            let rpt_coord = layout.states.iter().find_map(|s| match s {
+                                State::Custom { value, coord } if is_rpt_custom(value) => {
+                                    Some(*coord)
+                                }
+                                _ => None,
+                            });
+                            if let Some(action) = layout.rpt_replay_action
+                                && rpt_replays(action, self.rpt_repeats_macros)
+                                && let Some(coord) = rpt_coord
+                            {
+                                log::debug!("repeating the previous action");
+                                layout.queue_action(coord, action);
+                                continue;
+                            }
                             let keycode = self.last_pressed_key;
                             let osc: OsCode = keycode.into();
                             log::debug!("repeating a keypress {osc:?}");
@@ -1728,13 +2853,21 @@ impl Kanata {
                             if do_caps_word {
                                 self.kbd_out.release_key(OsCode::KEY_LEFTSHIFT)?;
                             }
+                            self.rpt_held_key = rpt_coord
+                                .filter(|coord| coord.0 == NORMAL_KEY_ROW)
+                                .map(|coord| (OsCode::from(coord.1), osc));
                         }
                         CustomAction::DynamicMacroRecord(macro_id) => {
                             if let Some((macro_id, prev_recorded_macro)) =
                                 begin_record_macro(*macro_id, &mut self.dynamic_macro_record_state)
                             {
                                 log::debug!("saving macro {prev_recorded_macro:?}");
-                                self.dynamic_macros.insert(macro_id, prev_recorded_macro);
+                                store_dynamic_macro(
+                                    &mut self.dynamic_macros,
+                                    self.dynamic_macro_persist_file.as_deref(),
+                                    macro_id,
+                                    prev_recorded_macro,
+                                );
                             }
                         }
                         CustomAction::DynamicMacroRecordStop(num_actions_to_remove) => {
@@ -1743,7 +2876,12 @@ impl Kanata {
                                 *num_actions_to_remove,
                             ) {
                                 log::debug!("saving macro {prev_recorded_macro:?}");
-                                self.dynamic_macros.insert(macro_id, prev_recorded_macro);
+                                store_dynamic_macro(
+                                    &mut self.dynamic_macros,
+                                    self.dynamic_macro_persist_file.as_deref(),
+                                    macro_id,
+                                    prev_recorded_macro,
+                                );
                             }
                         }
                         CustomAction::DynamicMacroPlay(macro_id) => {
@@ -1782,6 +2920,31 @@ impl Kanata {
                         CustomAction::SetMouse { x, y } => {
                             self.kbd_out.set_mouse(*x, *y)?;
                         }
+                        CustomAction::SetMousePct { x, y, monitor } => {
+                            self.kbd_out.set_mouse_pct(*x, *y, *monitor)?;
+                        }
+                        CustomAction::Hid { page, id } => {
+                            #[cfg(any(target_os = "macos", feature = "simulated_output"))]
+                            self.kbd_out.write_hid(*page, *id, KeyValue::Press)?;
+                            #[cfg(not(any(target_os = "macos", feature = "simulated_output")))]
+                            log::warn!("hid {page:#x} {id:#x} is only supported on macOS");
+                        }
+                        CustomAction::StickyLayer { layer, timeout } => {
+                            self.sticky_layer.press(*layer, *timeout, layout);
+                        }
+                        CustomAction::LayerPush(layer) => {
+                            layer_push(layout, *layer, self.layer_stack_max_depth);
+                        }
+                        CustomAction::LayerPop => layer_pop(layout),
+                        CustomAction::LayerCycle(cycle) => {
+                            self.layer_cycles.advance(*cycle, layout);
+                        }
+                        CustomAction::SetVar { var, value } => {
+                            self.runtime_vars.set(*var, *value, layout);
+                        }
+                        CustomAction::IncVar { var, amount } => {
+                            self.runtime_vars.inc(*var, *amount, layout);
+                        }
                         CustomAction::FakeKeyOnIdle(fkd) => {
                             self.ticks_since_idle = 0;
                             self.waiting_for_idle.insert(*fkd);
@@ -1825,13 +2988,19 @@ impl Kanata {
                         | CustomAction::DelayOnRelease(_)
                         | CustomAction::Unmodded { .. }
                         | CustomAction::Unshifted { .. }
+                        | CustomAction::CleanModsOnAction(_)
+                        | CustomAction::ChordOutput(_)
+                        | CustomAction::PressKey(_)
+                        | CustomAction::ReleaseKey(_)
+                        // Checked by state in `passes_through_for_layer`.
+                        | CustomAction::PassthroughWhileHeld
                         // Note: ReverseReleaseOrder is already handled earlier on.
                         | CustomAction::ReverseReleaseOrder
                         | CustomAction::CancelMacroOnRelease => {}
                     }
                 }
                 #[cfg(feature = "cmd")]
-                run_multi_cmd(cmds);
+                self.on_exit.add_cmd(run_multi_cmd(cmds));
 
                 // Process reload actions after releasing the layout borrow
                 if let Some(action) = reload_action {
@@ -1870,6 +3039,9 @@ impl Kanata {
                         live_reload_requested = true;
                     }
                 }
+                if clear_state {
+                    self.clear_state()?;
+                }
             }
 
             CustomEvent::Release(custacts) => {
@@ -1878,7 +3050,8 @@ impl Kanata {
                     .iter()
                     .fold(None, |pbtn, ac| match ac {
                         CustomAction::Mouse(btn) => Some(btn),
-                        CustomAction::MWheel { direction, .. } => {
+                        CustomAction::MWheel { direction, .. }
+                        | CustomAction::MWheelFine { direction, .. } => {
                             match direction {
                                 MWheelDirection::Up | MWheelDirection::Down => {
                                     if let Some(ss) = &mut self.scroll_state
@@ -1928,7 +3101,33 @@ impl Kanata {
                             }
                             pbtn
                         }
-                        CustomAction::MoveMouseSpeed { speed, .. } => {
+                        CustomAction::MoveMousePolar { angle, speed } => {
+                            if let Some(polar) = &self.move_mouse_state_polar
+                                && polar.angle == *angle
+                                && polar.speed == *speed
+                            {
+                                self.move_mouse_state_polar = None;
+                            }
+                            pbtn
+                        }
+                        CustomAction::MoveMouseSpeed {
+                            speed,
+                            scale_mwheel,
+                        } => {
+                            // The released key's state is already gone from the layout, so a
+                            // reset key that is no longer held is the one being released.
+                            let states = &layout.states;
+                            if let Some(idx) =
+                                self.move_mouse_speed_reset_keys.iter().position(|coord| {
+                                    !states.iter().any(|s| {
+                                        matches!(s, State::Custom { value, coord: c }
+                                            if c == coord && has_move_mouse_speed(value))
+                                    })
+                                })
+                            {
+                                self.move_mouse_speed_reset_keys.remove(idx);
+                                return pbtn;
+                            }
                             if let Some(idx) = self
                                 .move_mouse_speed_modifiers
                                 .iter()
@@ -1936,6 +3135,14 @@ impl Kanata {
                             {
                                 self.move_mouse_speed_modifiers.remove(idx);
                             }
+                            if *scale_mwheel
+                                && let Some(idx) = self
+                                    .mwheel_speed_modifiers
+                                    .iter()
+                                    .position(|s| *s == *speed)
+                            {
+                                self.mwheel_speed_modifiers.remove(idx);
+                            }
                             log::debug!(
                                 "movemousespeed modifiers: {:?}",
                                 self.move_mouse_speed_modifiers
@@ -1953,6 +3160,19 @@ impl Kanata {
                             handle_fakekey_action(*action, layout, x, y);
                             pbtn
                         }
+                        CustomAction::StickyLayer { layer, .. } => {
+                            self.sticky_layer.release(*layer, layout);
+                            pbtn
+                        }
+                        CustomAction::Hid { page, id } => {
+                            #[cfg(any(target_os = "macos", feature = "simulated_output"))]
+                            if let Err(e) = self.kbd_out.write_hid(*page, *id, KeyValue::Release) {
+                                log::error!("failed to release hid {page:#x} {id:#x}: {e:?}");
+                            }
+                            #[cfg(not(any(target_os = "macos", feature = "simulated_output")))]
+                            let _ = (page, id);
+                            pbtn
+                        }
                         CustomAction::CancelMacroOnRelease => {
                             log::debug!("cancelling all macros: releasable macro");
                             layout.active_sequences.clear();
@@ -1999,6 +3219,30 @@ impl Kanata {
             _ => {}
         };
 
+        self.sticky_layer.tick(self.layout.bm());
+        self.runtime_vars.warn_unset_read(self.layout.bm());
+        self.runtime_vars.warn_clamped_read(self.layout.bm());
+        if let Some(timeout) = self.layout.bm().last_adaptive_timeout.take() {
+            log::debug!("tap-hold-adaptive timeout: {timeout}ms");
+        }
+        #[cfg(feature = "tcp_server")]
+        if let Some(tx) = _tx {
+            let (before_layer, before_mode) = sticky_layer_before;
+            let (layer, mode) = (self.sticky_layer.layer, self.sticky_layer.mode);
+            if before_layer != layer && before_mode != StickyLayerMode::Off {
+                let name = self.layer_info[usize::from(before_layer)].name.clone();
+                send_sticky_layer_change(tx, name, StickyLayerMode::Off);
+            }
+            if (before_layer, before_mode) != (layer, mode)
+                && (before_layer == layer || mode != StickyLayerMode::Off)
+            {
+                let name = self.layer_info[usize::from(layer)].name.clone();
+                send_sticky_layer_change(tx, name, mode);
+            }
+        }
+        #[cfg(not(feature = "tcp_server"))]
+        let _ = sticky_layer_before;
+
         self.check_release_non_physical_shift()?;
         Ok(live_reload_requested)
     }
@@ -2013,6 +3257,75 @@ impl Kanata {
         }
     }
 
+    /// Play a dynamic macro on behalf of a TCP client. This uses the same replay state as the
+    /// `dynamic-macro-play` action, so delays and recursion protection are unchanged.
+    #[cfg(feature = "tcp_server")]
+    pub fn play_dynamic_macro(&mut self, slot: u16) -> Result<()> {
+        if !self.dynamic_macros.contains_key(&slot) {
+            bail!("no dynamic macro recorded in slot {slot}");
+        }
+        play_macro(
+            slot,
+            &mut self.dynamic_macro_replay_state,
+            &self.dynamic_macros,
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "tcp_server")]
+    pub fn set_runtime_var(&mut self, name: &str, value: i32) -> Result<()> {
+        self.runtime_vars.set_by_name(name, value, self.layout.bm())
+    }
+
+    /// Remap the input key `from` to the output key `to` for the given duration.
+    #[cfg(feature = "tcp_server")]
+    pub fn add_temp_remap(&mut self, from: &str, to: &str, duration_ms: u64) -> Result<()> {
+        let Some(from_code) = str_to_oscode(from) else {
+            bail!("unknown key: {from}");
+        };
+        let Some(to_code) = str_to_oscode(to) else {
+            bail!("unknown key: {to}");
+        };
+        if !MAPPED_KEYS.lock().contains(&from_code) {
+            bail!("key {from} is not processed by kanata, add it to defsrc to remap it");
+        }
+        self.temp_remaps
+            .add(from_code, to_code, time::Duration::from_millis(duration_ms))?;
+        log::info!("temporarily remapped {from} to {to} for {duration_ms}ms");
+        Ok(())
+    }
+
+    #[cfg(feature = "tcp_server")]
+    pub fn clear_dynamic_macro(&mut self, slot: u16) {
+        if clear_dynamic_macro(
+            &mut self.dynamic_macros,
+            self.dynamic_macro_persist_file.as_deref(),
+            slot,
+        ) {
+            log::info!("cleared dynamic macro {slot}");
+        }
+    }
+
+    #[cfg(feature = "tcp_server")]
+    pub fn dynamic_macro_listing(&self) -> ServerMessage {
+        let mut slots: Vec<_> = self
+            .dynamic_macros
+            .iter()
+            .map(|(slot, items)| kanata_tcp_protocol::DynamicMacroSlot {
+                slot: *slot,
+                events: macro_event_count(items),
+            })
+            .collect();
+        slots.sort_by_key(|s| s.slot);
+        ServerMessage::DynamicMacros {
+            slots,
+            recording: self
+                .dynamic_macro_record_state
+                .as_ref()
+                .map(|state| state.macro_id()),
+        }
+    }
+
     /// Request a live reload of the current configuration file.
     pub fn request_live_reload(&mut self) {
         self.live_reload_requested = true;
@@ -2123,12 +3436,16 @@ impl Kanata {
         let cur_layer = self.layout.bm().current_layer();
         if cur_layer != self.prev_layer {
             let new = self.layer_info[cur_layer].name.clone();
+            let old = self.layer_info[self.prev_layer].name.clone();
             self.prev_layer = cur_layer;
             self.print_layer(cur_layer);
 
             #[cfg(feature = "tcp_server")]
             if let Some(tx) = tx {
-                match tx.try_send(ServerMessage::LayerChange { new }) {
+                match tx.try_send(ServerMessage::LayerChange {
+                    new,
+                    old: Some(old),
+                }) {
                     Ok(_) => {}
                     Err(error) => {
                         log::error!("could not send event notification: {}", error);
@@ -2146,6 +3463,17 @@ impl Kanata {
         }
     }
 
+    /// Press and release the `self-test-key` to check that the output device accepts events.
+    pub fn self_test(&mut self) -> Result<()> {
+        let key = self.self_test_key;
+        self.kbd_out
+            .write_key(key, KeyValue::Press)
+            .and_then(|()| self.kbd_out.write_key(key, KeyValue::Release))
+            .map_err(|e| anyhow::anyhow!("output self-test with {key:?} failed: {e}"))?;
+        log::info!("output self-test with {key:?} passed");
+        Ok(())
+    }
+
     #[cfg(feature = "tcp_server")]
     /// Get engine uptime in seconds
     pub fn get_uptime_s(&self) -> u64 {
@@ -2159,9 +3487,110 @@ impl Kanata {
     }
 
     #[cfg(feature = "tcp_server")]
-    /// Whether the most recent reload succeeded.
-    pub fn last_reload_succeeded(&self) -> bool {
-        self.last_reload_ok
+    /// Snapshot of the layer and pending key states, for `GetState`.
+    pub fn state(&mut self) -> ServerMessage {
+        let layout = self.layout.bm();
+        let layer_name = |layer: usize| self.layer_info[layer].name.clone();
+        ServerMessage::State {
+            active_layer: layer_name(layout.current_layer()),
+            layer_stack: layout
+                .active_held_layers()
+                .map(|layer| layer_name(usize::from(layer)))
+                .collect(),
+            oneshot_active: !layout.oneshot.keys.is_empty(),
+            locked_layer: (self.sticky_layer.mode == StickyLayerMode::Locked)
+                .then(|| layer_name(usize::from(self.sticky_layer.layer))),
+            pending_taphold: layout
+                .waiting
+                .iter()
+                .chain(layout.extra_waiting.iter())
+                .any(|w| w.is_hold_tap()),
+            active_virtual_keys: {
+                let mut names: Vec<String> = self
+                    .virtual_keys
+                    .iter()
+                    .filter(|(_, y)| states_has_coord(&layout.states, FAKE_KEY_ROW, **y as u16))
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                names.sort();
+                names
+            },
+            layer_cycles: self
+                .layer_cycles
+                .iter()
+                .map(|(layers, selected)| kanata_tcp_protocol::LayerCycle {
+                    layers: layers.iter().map(|&layer| layer_name(layer)).collect(),
+                    selected,
+                })
+                .collect(),
+            locked_mouse_buttons: self
+                .mouse_locks
+                .buttons()
+                .map(|btn| oscode_to_str(OsCode::from(btn)))
+                .collect(),
+            uptime_ms: self.start_time.elapsed().as_millis() as u64,
+        }
+    }
+
+    #[cfg(feature = "tcp_server")]
+    /// Response to `RequestPressedKeys`. Between ticks, the output keys of the last tick are in
+    /// `prev_keys`.
+    pub fn pressed_keys(&self) -> ServerMessage {
+        PressedKeys::snapshot(&self.prev_keys).message()
+    }
+
+    #[cfg(feature = "tcp_server")]
+    /// Send a `PressedKeys` notification if the pressed keys changed and a client subscribed.
+    fn notify_pressed_keys(&mut self, tx: &Option<Sender<ServerMessage>>) {
+        use std::sync::atomic::Ordering;
+        let Some(tx) = tx else {
+            return;
+        };
+        if !crate::tcp_server::PRESSED_KEYS_SUBSCRIBED.load(Ordering::Relaxed) {
+            return;
+        }
+        let pressed = PressedKeys::snapshot(&self.cur_keys);
+        if pressed == self.pressed_keys_sent {
+            return;
+        }
+        if let Err(error) = tx.try_send(pressed.message()) {
+            log::error!("could not send PressedKeys event notification: {error}");
+        }
+        self.pressed_keys_sent = pressed;
+    }
+
+    /// Sends the notifications recorded by the sequence state since the last tick. They are
+    /// sent after the keys are processed so that they do not delay sequence matching.
+    fn send_sequence_notifications(&mut self, _tx: &Option<Sender<ServerMessage>>) {
+        if self.sequence_state.notifications.is_empty() {
+            return;
+        }
+        #[cfg(feature = "tcp_server")]
+        if let Some(tx) = _tx {
+            for msg in self.sequence_state.notifications.drain(..) {
+                if let Err(error) = tx.try_send(msg) {
+                    log::error!("could not send sequence event notification: {error}");
+                }
+            }
+        }
+        self.sequence_state.notifications.clear();
+    }
+
+    #[cfg(feature = "tcp_server")]
+    /// Response to a `Ping` health check.
+    pub fn pong(&self) -> ServerMessage {
+        ServerMessage::Pong {
+            uptime_ms: self.start_time.elapsed().as_millis() as u64,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            platform: std::env::consts::OS.to_string(),
+            config_path: self.cfg_paths[self.cur_cfg_idx].display().to_string(),
+        }
+    }
+
+    #[cfg(feature = "tcp_server")]
+    /// Outcome of the most recent reload.
+    pub fn last_reload(&self) -> &ReloadOutcome {
+        &self.last_reload
     }
 
     #[cfg(feature = "tcp_server")]
@@ -2178,13 +3607,45 @@ impl Kanata {
                         panic!("channel disconnected")
                     }
                     Ok(event) => {
-                        let notification = event.as_bytes();
+                        use crate::tcp_server::Encode;
+                        use kanata_parser::cfg::TcpServerFormat;
+                        let legacy_notification = event.as_bytes();
+                        let mut tagged_notification = None;
+                        let kind = event.kind();
                         let mut clients = clients.lock();
                         let mut stale_clients = vec![];
+                        let is_key_event = matches!(event, ServerMessage::KeyEvent { .. });
+                        let is_pressed_keys = matches!(event, ServerMessage::PressedKeys { .. });
+                        let mut has_recipient = false;
                         for (id, client) in &mut *clients {
-                            match client.write_all(&notification) {
+                            let wanted = if is_key_event {
+                                client.monitor_keys
+                            } else {
+                                match &client.subscriptions {
+                                    Some(subscriptions) => subscriptions.contains(kind),
+                                    // `PressedKeys` is sent on every key press, so it always
+                                    // needs to be subscribed to.
+                                    None => !is_pressed_keys,
+                                }
+                            };
+                            if !wanted {
+                                continue;
+                            }
+                            if let Some(caps) = &client.capabilities
+                                && let Some(cap) = event.capability()
+                                && !caps.contains(cap)
+                            {
+                                continue;
+                            }
+                            has_recipient = true;
+                            let notification = match client.format {
+                                TcpServerFormat::Legacy => &legacy_notification,
+                                TcpServerFormat::Tagged => tagged_notification
+                                    .get_or_insert_with(|| event.encode(TcpServerFormat::Tagged)),
+                            };
+                            match client.stream.write_all(notification) {
                                 Ok(_) => {
-                                    log::debug!("layer change notification sent");
+                                    log::debug!("{kind} notification sent");
                                 }
                                 Err(e) => {
                                     log::warn!(
@@ -2200,6 +3661,14 @@ impl Kanata {
                             log::warn!("removing disconnected tcp client: {id}");
                             clients.remove(id);
                         }
+                        if is_key_event && !has_recipient {
+                            crate::tcp_server::KEY_MONITOR_ACTIVE
+                                .store(false, std::sync::atomic::Ordering::Relaxed);
+                        }
+                        if is_pressed_keys && !has_recipient {
+                            crate::tcp_server::PRESSED_KEYS_SUBSCRIBED
+                                .store(false, std::sync::atomic::Ordering::Relaxed);
+                        }
                     }
                 }
             }
@@ -2285,6 +3754,9 @@ impl Kanata {
                                 &mut idle_clear_happened,
                             );
                             k.last_tick = now;
+                            // Ticks are skipped while blocked, so apply a schedule change that
+                            // happened in the meantime before handling the input.
+                            k.tick_schedule();
 
                             // Check for live reload BEFORE processing the key event
                             if k.live_reload_requested
@@ -2327,7 +3799,11 @@ impl Kanata {
 
                             #[cfg(feature = "perf_logging")]
                             log::info!(
-                                "[PERF]: handle key event: {} ns",
+                                "[PERF]: handle key event from {}: {} ns",
+                                events
+                                    .first()
+                                    .and_then(|ev| ev.device)
+                                    .unwrap_or("unknown device"),
                                 (start.elapsed()).as_nanos()
                             );
                             #[cfg(feature = "perf_logging")]
@@ -2396,7 +3872,11 @@ impl Kanata {
 
                             #[cfg(feature = "perf_logging")]
                             log::info!(
-                                "[PERF]: handle key event: {} ns",
+                                "[PERF]: handle key event from {}: {} ns",
+                                events
+                                    .first()
+                                    .and_then(|ev| ev.device)
+                                    .unwrap_or("unknown device"),
                                 (start.elapsed()).as_nanos()
                             );
                             #[cfg(feature = "perf_logging")]
@@ -2475,6 +3955,35 @@ impl Kanata {
             log::info!("ticks since idle: {}", k.ticks_since_idle);
         }
 
+        // The on-idle timer only runs until the action fires; after that, kanata can block until
+        // the next input event.
+        let mut counting_idle_hook_ms = k.idle_hooks.on_idle.is_some() && !k.on_idle_fired;
+        if counting_idle_hook_ms {
+            k.ms_since_input = k.ms_since_input.saturating_add(ms_elapsed.into());
+        }
+        // Layer-scoped defidle watchers restart whenever their layer is not active. Layer changes
+        // happen while processing, so kanata can block while no watcher is armed.
+        let named_macro_pending = k.named_macros.is_pending();
+        let counting_typing_cadence = k.typing_cadence.is_counting();
+        if counting_typing_cadence {
+            k.typing_cadence.tick(ms_elapsed);
+        }
+
+        let current_layer = k.layout.b().current_layer();
+        for (watcher, ms) in k
+            .idle_hooks
+            .watchers
+            .iter()
+            .zip(k.idle_watcher_ms.iter_mut())
+        {
+            if !is_idle_watcher_armed(watcher, current_layer) {
+                *ms = Some(0);
+            } else if let Some(ms) = ms {
+                *ms = ms.saturating_add(ms_elapsed.into());
+                counting_idle_hook_ms = true;
+            }
+        }
+
         let counting_physical_idle_ticks = if k.waiting_for_physical_idle.is_empty() {
             false
         } else {
@@ -2507,8 +4016,13 @@ impl Kanata {
             .map(|cv2| cv2.accepts_chords_chv2())
             .unwrap_or(true);
         is_idle
+            && !EXIT_REQUESTED.load(std::sync::atomic::Ordering::SeqCst)
             && !counting_idle_ticks
             && !counting_physical_idle_ticks
+            && !counting_idle_hook_ms
+            && !counting_typing_cadence
+            && !named_macro_pending
+            && !k.on_exit.is_running()
             && passed_max_switch_timing_check
             && chordsv2_accepts_chords
     }
@@ -2530,9 +4044,14 @@ impl Kanata {
             && self.move_mouse_state_vertical.is_none()
             && self.macro_on_press_cancel_duration == 0
             && self.move_mouse_state_horizontal.is_none()
+            && self.move_mouse_state_polar.is_none()
             && self.dynamic_macro_replay_state.is_none()
             && self.caps_word.is_none()
+            && !self.sticky_layer.needs_ticks()
             && self.vkeys_pending_release.is_empty()
+            && self.debounce.is_idle()
+            && !self.multi_seq.is_pending()
+            && !self.layer_hold_timeouts.is_counting()
             && !self.layout.b().states.iter().any(|s| {
                 matches!(s, State::SeqCustomPending(_) | State::SeqCustomActive(_))
                     || (pressed_keys_means_not_idle && matches!(s, State::NormalKey { .. }))
@@ -2554,14 +4073,31 @@ fn test_unmodmods_bits() {
 }
 
 #[cfg(feature = "cmd")]
-fn run_multi_cmd(cmds: Vec<(Option<log::Level>, Option<log::Level>, Vec<String>)>) {
+fn run_multi_cmd(
+    cmds: Vec<(Option<log::Level>, Option<log::Level>, Vec<String>)>,
+) -> std::thread::JoinHandle<()> {
     std::thread::spawn(move || {
         for (cmd_log_level, cmd_error_log_level, cmd) in cmds {
             if let Err(e) = run_cmd_in_thread(cmd, cmd_log_level, cmd_error_log_level).join() {
                 log::error!("problem joining thread {:?}", e);
             }
         }
-    });
+    })
+}
+
+/// Scales the distance of a plain `movemouse` action by the speed curve. `movemouse-accel`
+/// actions have their own ramp and are left alone.
+fn apply_speed_curve(state: &MoveMouseState, curve: Option<&SpeedCurve>) -> u16 {
+    match (curve, state.move_mouse_accel_state) {
+        (Some(curve), None) => {
+            let multiplier =
+                curve.speed_multiplier(time::Duration::from_millis(u64::from(state.held_ms)));
+            (f32::from(state.distance) * multiplier)
+                .round()
+                .clamp(1.0, f32::from(u16::MAX)) as u16
+        }
+        _ => state.distance,
+    }
 }
 
 fn apply_mouse_distance_modifiers(initial_distance: u16, mods: &Vec<u16>) -> u16 {
@@ -2648,63 +4184,93 @@ fn check_for_exit(_event: &KeyEvent) {
             OsCode::KEY_LEFTCTRL => IS_LCL_PRESSED.store(is_pressed, SeqCst),
             _ => return,
         }
-        const EXIT_MSG: &str = "pressed LControl+Space+Escape, exiting";
         if IS_ESC_PRESSED.load(SeqCst) && IS_SPC_PRESSED.load(SeqCst) && IS_LCL_PRESSED.load(SeqCst)
         {
-            log::info!("{EXIT_MSG}");
-            #[cfg(all(target_os = "windows", feature = "gui"))]
-            {
-                #[cfg(not(feature = "interception_driver"))]
-                native_windows_gui::stop_thread_dispatch();
-                #[cfg(feature = "interception_driver")]
-                send_gui_exit_notice(); // interception driver is running in another thread to allow
-                // GUI take the main one, so it's calling check_for_exit
-                // from a thread that has no access to the main one, so
-                // can't stop main thread's dispatch
-            }
-            // macOS: Direct exit (no special signal handling)
-            #[cfg(target_os = "macos")]
-            {
-                let code = EMERGENCY_EXIT_CODE.load(std::sync::atomic::Ordering::SeqCst);
-                std::process::exit(code);
-            }
-            // Linux/Android: Use SIGTERM to trigger signal handler for cleanup
-            #[cfg(any(target_os = "linux", target_os = "android"))]
-            {
-                signal_hook::low_level::raise(signal_hook::consts::SIGTERM).expect("raise signal");
-            }
-            // Windows non-GUI: Direct exit (no cleanup needed)
-            #[cfg(all(target_os = "windows", not(feature = "gui")))]
-            {
-                let code = EMERGENCY_EXIT_CODE.load(std::sync::atomic::Ordering::SeqCst);
-                std::process::exit(code);
-            }
-            // Unsupported platforms: panic to indicate emergency exit isn't implemented
-            #[cfg(not(any(
-                target_os = "macos",
-                target_os = "linux",
-                target_os = "android",
-                target_os = "windows"
-            )))]
-            {
-                panic!("{EXIT_MSG}");
+            if ON_EXIT_CONFIGURED.load(SeqCst) || MOUSE_BUTTONS_LOCKED.load(SeqCst) {
+                log::info!(
+                    "pressed LControl+Space+Escape, exiting after the on-exit action \
+                     and releasing locked mouse buttons"
+                );
+                request_exit();
+                return;
             }
+            exit_kanata(true);
         }
     }
 }
 
-fn update_kbd_out(_cfg: &CfgOptions, _kbd_out: &KbdOut) -> Result<()> {
+/// Exits after the exit hotkey was pressed. `in_input_thread` is false when exiting from the
+/// processing loop after the `on-exit` action has run, or from the fallback timer of the exit
+/// hotkey.
+fn exit_kanata(_in_input_thread: bool) {
+    const EXIT_MSG: &str = "pressed LControl+Space+Escape, exiting";
+    log::info!("{EXIT_MSG}");
+    #[cfg(all(target_os = "windows", feature = "gui"))]
+    {
+        #[cfg(not(feature = "interception_driver"))]
+        if _in_input_thread {
+            native_windows_gui::stop_thread_dispatch();
+        } else {
+            send_gui_exit_notice();
+        }
+        #[cfg(feature = "interception_driver")]
+        send_gui_exit_notice(); // interception driver is running in another thread to allow
+        // GUI take the main one, so it's calling check_for_exit
+        // from a thread that has no access to the main one, so
+        // can't stop main thread's dispatch
+    }
+    // macOS: Direct exit (no special signal handling)
+    #[cfg(target_os = "macos")]
+    {
+        crate::tcp_server::remove_unix_socket();
+        let code = EMERGENCY_EXIT_CODE.load(std::sync::atomic::Ordering::SeqCst);
+        std::process::exit(code);
+    }
+    // Linux/Android: Use SIGTERM to trigger signal handler for cleanup
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        signal_hook::low_level::raise(signal_hook::consts::SIGTERM).expect("raise signal");
+    }
+    // Windows non-GUI: Direct exit (no cleanup needed)
+    #[cfg(all(target_os = "windows", not(feature = "gui")))]
+    {
+        let code = EMERGENCY_EXIT_CODE.load(std::sync::atomic::Ordering::SeqCst);
+        std::process::exit(code);
+    }
+    // Unsupported platforms: panic to indicate emergency exit isn't implemented
+    #[cfg(not(any(
+        target_os = "macos",
+        target_os = "linux",
+        target_os = "android",
+        target_os = "windows"
+    )))]
+    {
+        panic!("{EXIT_MSG}");
+    }
+}
+
+fn update_kbd_out(cfg: &CfgOptions, kbd_out: &mut KbdOut) -> Result<()> {
+    kbd_out
+        .rate_limiter
+        .set_limit(cfg.output_rate_limit, cfg.output_rate_limit_policy);
     #[cfg(all(
         not(feature = "simulated_output"),
         any(target_os = "linux", target_os = "android")
     ))]
     {
-        _kbd_out.update_unicode_termination(_cfg.linux_opts.linux_unicode_termination);
-        _kbd_out.update_unicode_u_code(_cfg.linux_opts.linux_unicode_u_code);
+        kbd_out.update_unicode_termination(cfg.linux_opts.linux_unicode_termination);
+        kbd_out.update_unicode_u_code(cfg.linux_opts.linux_unicode_u_code);
     }
     Ok(())
 }
 
+#[cfg(feature = "tcp_server")]
+fn send_sticky_layer_change(tx: &Sender<ServerMessage>, layer: String, mode: StickyLayerMode) {
+    if let Err(error) = tx.try_send(ServerMessage::StickyLayerChange { layer, mode }) {
+        log::error!("could not send StickyLayerChange event notification: {error}");
+    }
+}
+
 pub fn handle_fakekey_action<'a, const C: usize, const R: usize, T>(
     action: FakeKeyAction,
     layout: &mut Layout<'a, C, R, T>,
@@ -2729,6 +4295,101 @@ pub fn handle_fakekey_action<'a, const C: usize, const R: usize, T>(
     };
 }
 
+/// The events for the `fakekeys` operations in order. A toggle depends on whether the key is
+/// pressed after the operations before it.
+fn fakekeys_events<T>(ops: &[(Coord, FakeKeyAction)], states: &[State<T>]) -> Vec<Event>
+where
+    T: std::fmt::Debug + Copy,
+{
+    // The pressed state of the keys changed by earlier operations, most recent last.
+    let mut changed: Vec<((u8, u16), bool)> = vec![];
+    let mut events = vec![];
+    for &(Coord { x, y }, action) in ops {
+        let is_pressed = changed
+            .iter()
+            .rev()
+            .find(|(coord, _)| *coord == (x, y))
+            .map(|(_, pressed)| *pressed)
+            .unwrap_or_else(|| states_has_coord(states, x, y));
+        let press = match action {
+            FakeKeyAction::Press => true,
+            FakeKeyAction::Release => false,
+            FakeKeyAction::Tap => {
+                events.push(Event::Press(x, y));
+                false
+            }
+            FakeKeyAction::Toggle => !is_pressed,
+        };
+        events.push(match press {
+            true => Event::Press(x, y),
+            false => Event::Release(x, y),
+        });
+        changed.push(((x, y), press));
+    }
+    events
+}
+
+/// Whether a `defidle` watcher is counting idle time, given the active layer.
+fn is_idle_watcher_armed(watcher: &IdleWatcher, current_layer: usize) -> bool {
+    watcher
+        .layer
+        .is_none_or(|layer| usize::from(layer) == current_layer)
+}
+
+fn is_rpt_custom(custacts: &[&CustomAction]) -> bool {
+    custacts
+        .iter()
+        .any(|custact| matches!(custact, CustomAction::Repeat))
+}
+
+/// Whether the action contains [`CustomAction::Repeat`]. Such an action is not remembered as the
+/// action to repeat, so that `rpt` does not replay itself.
+fn is_rpt_action(action: &Action<&&[&CustomAction]>) -> bool {
+    match action {
+        Action::Custom(custacts) => is_rpt_custom(custacts),
+        Action::MultipleActions(actions) => actions.iter().any(is_rpt_action),
+        _ => false,
+    }
+}
+
+/// Whether [`CustomAction::Repeat`] replays the action itself instead of tapping the most
+/// recently pressed key again. Macros are only replayed if `repeat_macros` is true, since a macro
+/// may have side effects that should not happen twice.
+fn rpt_replays(action: &Action<&&[&CustomAction]>, repeat_macros: bool) -> bool {
+    match action {
+        Action::Custom(custacts) => custacts.iter().any(|custact| match custact {
+            CustomAction::Unicode(_) | CustomAction::Str { .. } => true,
+            CustomAction::DynamicMacroPlay(_) => repeat_macros,
+            _ => false,
+        }),
+        Action::Sequence { .. } | Action::RepeatableSequence { .. } => repeat_macros,
+        Action::MultipleActions(actions) => {
+            !actions
+                .iter()
+                .any(|ac| matches!(ac, Action::Layer(_) | Action::DefaultLayer(_)))
+                && actions
+                    .iter()
+                    .all(|ac| !matches!(ac, Action::Sequence { .. }) || repeat_macros)
+        }
+        _ => false,
+    }
+}
+
+fn has_move_mouse_speed(actions: &[&CustomAction]) -> bool {
+    actions
+        .iter()
+        .any(|ac| matches!(ac, CustomAction::MoveMouseSpeed { .. }))
+}
+
+/// Coordinate of the key whose custom action state holds `value`, i.e. the key that activated
+/// the custom actions of a [`CustomEvent::Press`].
+fn custom_state_coord<T>(states: &[State<T>], value: &T) -> Option<KCoord> {
+    states.iter().rev().find_map(|s| match s {
+        State::Custom { value: v, coord } if std::ptr::eq(*v, value) => Some(*coord),
+        _ => None,
+    })
+}
+
 fn states_has_coord<T>(states: &[State<T>], x: u8, y: u16) -> bool {
     states.iter().any(|s| match s {
         State::NormalKey { coord, .. }
@@ -2784,7 +4445,7 @@ mod collect_and_sort_events_tests {
     use std::sync::mpsc::sync_channel;
 
     fn make_event(code: OsCode, value: KeyValue) -> KeyEvent {
-        KeyEvent { code, value }
+        KeyEvent::new(code, value)
     }
 
     #[test]
@@ -0,0 +1,95 @@
+use crate::oskbd::KbdOut;
+use kanata_parser::custom_action::Btn;
+use kanata_parser::keys::OsCode;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+
+/// Whether any mouse button is locked. The exit hotkey then leaves exiting to the processing
+/// loop, which releases the buttons first.
+pub(crate) static MOUSE_BUTTONS_LOCKED: AtomicBool = AtomicBool::new(false);
+
+/// Mouse buttons held down by the mouse lock actions, e.g. `mllk`, for dragging without holding
+/// a key.
+#[derive(Debug, Default)]
+pub struct MouseLocks {
+    /// Each locked button and the base layer when it was locked.
+    locked: Vec<(Btn, usize)>,
+    release_on_layer_change: bool,
+}
+
+impl MouseLocks {
+    pub fn new(release_on_layer_change: bool) -> Self {
+        Self {
+            locked: vec![],
+            release_on_layer_change,
+        }
+    }
+
+    pub fn configure(&mut self, release_on_layer_change: bool) {
+        self.release_on_layer_change = release_on_layer_change;
+    }
+
+    pub fn buttons(&self) -> impl Iterator<Item = Btn> + '_ {
+        self.locked.iter().map(|(btn, _)| *btn)
+    }
+
+    /// Whether `osc` is a locked button. Physical events for it that kanata would write without
+    /// processing are dropped, so that clicking the physical mouse does not release the lock
+    /// behind its back.
+    pub fn is_locked_osc(&self, osc: OsCode) -> bool {
+        self.buttons().any(|btn| OsCode::from(btn) == osc)
+    }
+
+    /// Presses and locks the button, or releases it if it is already locked.
+    pub fn toggle(&mut self, btn: Btn, layer: usize, kbd_out: &mut KbdOut) -> io::Result<()> {
+        if self.take(btn) {
+            log::debug!("unlock    {btn:?}");
+            kbd_out.release_btn(btn)?;
+        } else {
+            log::debug!("lock      {btn:?}");
+            kbd_out.click_btn(btn)?;
+            self.locked.push((btn, layer));
+            MOUSE_BUTTONS_LOCKED.store(true, SeqCst);
+        }
+        Ok(())
+    }
+
+    /// Unlocks the button without releasing it and returns whether it was locked. Used when a
+    /// plain mouse button action is pressed for a locked button, which then releases the button
+    /// with its own release.
+    pub fn take(&mut self, btn: Btn) -> bool {
+        let len = self.locked.len();
+        self.locked.retain(|(locked, _)| *locked != btn);
+        MOUSE_BUTTONS_LOCKED.store(!self.locked.is_empty(), SeqCst);
+        self.locked.len() != len
+    }
+
+    pub fn release_all(&mut self, kbd_out: &mut KbdOut) -> io::Result<()> {
+        MOUSE_BUTTONS_LOCKED.store(false, SeqCst);
+        for (btn, _) in self.locked.drain(..) {
+            log::info!("releasing locked mouse button {btn:?}");
+            kbd_out.release_btn(btn)?;
+        }
+        Ok(())
+    }
+
+    /// Releases the buttons that were locked on another base layer than `layer`, unless
+    /// `mouse-lock-release-on-layer-change` is disabled. Held layers are not considered, so a
+    /// lock engaged from a held mouse layer survives releasing that layer.
+    pub fn tick(&mut self, layer: usize, kbd_out: &mut KbdOut) -> io::Result<()> {
+        if !self.release_on_layer_change || self.locked.iter().all(|(_, l)| *l == layer) {
+            return Ok(());
+        }
+        let (kept, released): (Vec<_>, Vec<_>) = self
+            .locked
+            .drain(..)
+            .partition(|(_, locked_layer)| *locked_layer == layer);
+        self.locked = kept;
+        MOUSE_BUTTONS_LOCKED.store(!self.locked.is_empty(), SeqCst);
+        for (btn, _) in released {
+            log::info!("layer changed, releasing locked mouse button {btn:?}");
+            kbd_out.release_btn(btn)?;
+        }
+        Ok(())
+    }
+}
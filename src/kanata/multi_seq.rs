@@ -0,0 +1,67 @@
+use super::states_has_coord;
+use kanata_keyberon::layout::KCoord;
+use kanata_parser::cfg::BorrowedKLayout;
+use kanata_parser::custom_action::MultiSeqStep;
+
+/// The delayed steps of the `multi-seq` actions whose keys are held.
+#[derive(Debug, Default)]
+pub struct MultiSeq {
+    runs: Vec<Run>,
+}
+
+#[derive(Debug)]
+struct Run {
+    /// Coordinate of the key, which the steps are activated at so that they are released with it.
+    coord: KCoord,
+    /// Steps that have not been activated yet.
+    steps: &'static [MultiSeqStep],
+    ms_until_next: u16,
+}
+
+impl MultiSeq {
+    pub(crate) fn start(&mut self, coord: KCoord, steps: &'static [MultiSeqStep]) {
+        if let Some(step) = steps.first() {
+            self.runs.push(Run {
+                coord,
+                steps,
+                ms_until_next: step.delay,
+            });
+        }
+    }
+
+    /// Whether steps are waiting to be activated, so the processing loop must keep ticking.
+    pub(crate) fn is_pending(&self) -> bool {
+        !self.runs.is_empty()
+    }
+
+    /// Forget all steps, e.g. on live reload, which frees the actions.
+    pub(crate) fn clear(&mut self) {
+        self.runs.clear();
+    }
+
+    /// Advance by one millisecond and queue the steps whose delay has passed. Steps of released
+    /// keys are dropped.
+    pub(crate) fn tick(&mut self, layout: &mut BorrowedKLayout) {
+        self.runs.retain_mut(|run| {
+            if !states_has_coord(&layout.states, run.coord.0, run.coord.1) {
+                return false;
+            }
+            run.ms_until_next = run.ms_until_next.saturating_sub(1);
+            if run.ms_until_next > 0 {
+                return true;
+            }
+            let Some((step, rest)) = run.steps.split_first() else {
+                return false;
+            };
+            layout.queue_action(run.coord, step.action);
+            run.steps = rest;
+            match rest.first() {
+                Some(next) => {
+                    run.ms_until_next = next.delay;
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+}
@@ -0,0 +1,63 @@
+use super::handle_fakekey_action;
+use anyhow::{Result, bail};
+use kanata_parser::cfg::{BorrowedKLayout, FAKE_KEY_ROW};
+use kanata_parser::custom_action::FakeKeyAction;
+use rustc_hash::FxHashMap as HashMap;
+use std::collections::VecDeque;
+
+/// Minimum time between the starts of two queued macros.
+const START_INTERVAL_MS: u16 = 1000;
+/// Maximum number of macros waiting to be played.
+const MAX_QUEUED: usize = 16;
+
+/// The named macros from `defmacro` and the queue of macros requested by `PlayMacro`. Queued
+/// macros are played one at a time, at most one per second.
+#[derive(Debug)]
+pub struct NamedMacros {
+    /// Fake key columns of the macros.
+    columns: HashMap<String, u16>,
+    queue: VecDeque<u16>,
+    /// Milliseconds since a queued macro was started, counted up to `START_INTERVAL_MS`.
+    ms_since_start: u16,
+}
+
+impl NamedMacros {
+    pub(crate) fn new(columns: HashMap<String, u16>) -> Self {
+        Self {
+            columns,
+            queue: VecDeque::new(),
+            ms_since_start: START_INTERVAL_MS,
+        }
+    }
+
+    /// Queue the macro with the given name. Returns `false` if the queue is full.
+    pub fn queue(&mut self, name: &str) -> Result<bool> {
+        let Some(&y) = self.columns.get(name) else {
+            bail!("unknown macro: {name}");
+        };
+        if self.queue.len() >= MAX_QUEUED {
+            log::warn!("not queueing macro {name}: {MAX_QUEUED} macros are already queued");
+            return Ok(false);
+        }
+        self.queue.push_back(y);
+        Ok(true)
+    }
+
+    /// Whether the processing loop must keep ticking for a queued macro to start on time.
+    pub(crate) fn is_pending(&self) -> bool {
+        !self.queue.is_empty() || self.ms_since_start < START_INTERVAL_MS
+    }
+
+    /// Start the next queued macro if no macro is playing and the rate limit allows it.
+    pub(crate) fn tick(&mut self, layout: &mut BorrowedKLayout) {
+        self.ms_since_start = self.ms_since_start.saturating_add(1).min(START_INTERVAL_MS);
+        if self.ms_since_start < START_INTERVAL_MS || !layout.active_sequences.is_empty() {
+            return;
+        }
+        if let Some(y) = self.queue.pop_front() {
+            log::debug!("playing queued macro");
+            self.ms_since_start = 0;
+            handle_fakekey_action(FakeKeyAction::Tap, layout, FAKE_KEY_ROW, y);
+        }
+    }
+}
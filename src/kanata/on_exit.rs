@@ -0,0 +1,140 @@
+use super::{exit_kanata, handle_fakekey_action};
+use crate::oskbd::{KeyEvent, KeyValue};
+use kanata_parser::cfg::{BorrowedKLayout, FAKE_KEY_ROW};
+use kanata_parser::custom_action::FakeKeyAction;
+use kanata_parser::keys::OsCode;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering::SeqCst};
+use std::sync::mpsc::SyncSender;
+
+/// Whether the active configuration has an `on-exit` action. The exit hotkey then leaves exiting
+/// to the processing loop, which runs the action first.
+pub(crate) static ON_EXIT_CONFIGURED: AtomicBool = AtomicBool::new(false);
+/// Set by the exit hotkey for the processing loop to run `on-exit`, if any, and then exit.
+pub(crate) static EXIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// `on-exit-timeout` of the active configuration, for the fallback timer of the exit hotkey.
+static ON_EXIT_TIMEOUT_MS: AtomicU32 = AtomicU32::new(0);
+/// Extra time the fallback timer gives the processing loop after `on-exit-timeout` before it
+/// exits without it.
+const FALLBACK_GRACE_MS: u32 = 500;
+/// Channel to the processing loop, which may be blocked waiting for input when the exit hotkey is
+/// pressed.
+static EXIT_WAKEUP_TX: Mutex<Option<SyncSender<KeyEvent>>> = Mutex::new(None);
+
+/// Sets the channel used to wake up the processing loop when the exit hotkey is pressed.
+pub fn set_exit_wakeup_channel(tx: SyncSender<KeyEvent>) {
+    *EXIT_WAKEUP_TX.lock() = Some(tx);
+}
+
+/// Leaves exiting to the processing loop so that it runs the `on-exit` action first. Called by
+/// the exit hotkey on the input thread. In case the processing loop is stuck, a timer exits
+/// anyway once the action would have timed out.
+pub(crate) fn request_exit() {
+    if EXIT_REQUESTED.swap(true, SeqCst) {
+        return;
+    }
+    if let Some(tx) = EXIT_WAKEUP_TX.lock().as_ref() {
+        // The channel being full means the processing loop has input to wake up for anyway.
+        let _ = tx.try_send(KeyEvent::new(OsCode::KEY_RESERVED, KeyValue::WakeUp));
+    }
+    let wait_ms = ON_EXIT_TIMEOUT_MS
+        .load(SeqCst)
+        .saturating_add(FALLBACK_GRACE_MS);
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(wait_ms.into()));
+        log::warn!("processing loop did not exit within {wait_ms} ms, exiting anyway");
+        exit_kanata(false);
+    });
+}
+
+/// Ticks to wait after tapping the `on-exit` action before checking whether it has finished, so
+/// that the action has been processed by the layout.
+const MIN_RUN_MS: u32 = 2;
+
+/// The `on-exit` action from `defcfg` and the progress of running it after the exit hotkey.
+#[derive(Debug, Default)]
+pub struct OnExit {
+    /// Fake key column of the action.
+    y: Option<u16>,
+    timeout_ms: u32,
+    running: Option<Running>,
+}
+
+#[derive(Debug, Default)]
+struct Running {
+    elapsed_ms: u32,
+    /// Threads of the `cmd` actions started by the action.
+    #[cfg(feature = "cmd")]
+    cmds: Vec<std::thread::JoinHandle<()>>,
+}
+
+impl OnExit {
+    pub(crate) fn new(y: Option<u16>, timeout_ms: u32) -> Self {
+        let mut on_exit = Self::default();
+        on_exit.configure(y, timeout_ms);
+        on_exit
+    }
+
+    /// Change the action, e.g. after a live reload. An action that is already running continues.
+    pub(crate) fn configure(&mut self, y: Option<u16>, timeout_ms: u32) {
+        ON_EXIT_CONFIGURED.store(y.is_some(), SeqCst);
+        ON_EXIT_TIMEOUT_MS.store(timeout_ms, SeqCst);
+        self.y = y;
+        self.timeout_ms = timeout_ms;
+    }
+
+    /// Whether the action is running, so the processing loop must keep ticking.
+    pub(crate) fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Wait for a `cmd` started while the action is running before exiting.
+    #[cfg(feature = "cmd")]
+    pub(crate) fn add_cmd(&mut self, handle: std::thread::JoinHandle<()>) {
+        if let Some(running) = &mut self.running {
+            running.cmds.push(handle);
+        }
+    }
+
+    /// Start the action if the exit hotkey was pressed and advance it by one millisecond. Returns
+    /// `true` once kanata should exit, i.e. the action has finished or timed out.
+    pub(crate) fn tick(&mut self, layout: &mut BorrowedKLayout, layout_is_idle: bool) -> bool {
+        // Without an action, the exit hotkey is only left to the processing loop for cleanup,
+        // e.g. to release locked mouse buttons.
+        if self.y.is_none() && EXIT_REQUESTED.swap(false, SeqCst) {
+            return true;
+        }
+        if let Some(y) = self.y
+            && self.running.is_none()
+            && EXIT_REQUESTED.swap(false, SeqCst)
+        {
+            log::info!("running on-exit action");
+            handle_fakekey_action(FakeKeyAction::Tap, layout, FAKE_KEY_ROW, y);
+            self.running = Some(Running::default());
+        }
+        let Some(running) = &mut self.running else {
+            return false;
+        };
+        running.elapsed_ms += 1;
+        #[cfg(feature = "cmd")]
+        running.cmds.retain(|cmd| !cmd.is_finished());
+        #[cfg(feature = "cmd")]
+        let cmds_finished = running.cmds.is_empty();
+        #[cfg(not(feature = "cmd"))]
+        let cmds_finished = true;
+        if running.elapsed_ms >= MIN_RUN_MS && layout_is_idle && cmds_finished {
+            log::info!("on-exit action finished, exiting");
+            self.running = None;
+            return true;
+        }
+        if running.elapsed_ms >= self.timeout_ms {
+            log::warn!(
+                "on-exit action did not finish within {} ms, exiting anyway",
+                self.timeout_ms
+            );
+            self.running = None;
+            return true;
+        }
+        false
+    }
+}
@@ -5,7 +5,8 @@ mod zippychord;
 #[cfg(feature = "zippychord")]
 pub(crate) use zippychord::*;
 
-// Functions to send keys except those that fall in the ignorable range.
+// Functions to send keys except those that fall in the ignorable range or are above the
+// `output-rate-limit`.
 // And also have been repurposed to have additional logic to send mouse events, out of convenience.
 //
 // POTENTIAL PROBLEM - G-keys:
@@ -35,6 +36,7 @@ pub(super) const KEY_IGNORE_MAX: u16 = 0x2ad; // KEY_MACRO30
 pub(super) fn write_key(kb: &mut KbdOut, osc: OsCode, val: KeyValue) -> Result<(), std::io::Error> {
     match u16::from(osc) {
         KEY_IGNORE_MIN..=KEY_IGNORE_MAX => Ok(()),
+        _ if !kb.rate_limiter.allow(osc, val) => Ok(()),
         _ => kb.write_key(osc, val),
     }
 }
@@ -42,6 +44,7 @@ pub(super) fn press_key(kb: &mut KbdOut, osc: OsCode) -> Result<(), std::io::Err
     use OsCode::*;
     match u16::from(osc) {
         KEY_IGNORE_MIN..=KEY_IGNORE_MAX => Ok(()),
+        _ if !kb.rate_limiter.allow(osc, KeyValue::Press) => Ok(()),
         _ => match osc {
             BTN_LEFT | BTN_RIGHT | BTN_MIDDLE | BTN_SIDE | BTN_EXTRA => {
                 let btn = osc_to_btn(osc);
@@ -59,6 +62,7 @@ pub(super) fn release_key(kb: &mut KbdOut, osc: OsCode) -> Result<(), std::io::E
     use OsCode::*;
     match u16::from(osc) {
         KEY_IGNORE_MIN..=KEY_IGNORE_MAX => Ok(()),
+        _ if !kb.rate_limiter.allow(osc, KeyValue::Release) => Ok(()),
         _ => match osc {
             BTN_LEFT | BTN_RIGHT | BTN_MIDDLE | BTN_SIDE | BTN_EXTRA => {
                 let btn = osc_to_btn(osc);
@@ -73,6 +77,32 @@ pub(super) fn release_key(kb: &mut KbdOut, osc: OsCode) -> Result<(), std::io::E
         },
     }
 }
+/// Writes a key that is passed through unchanged from the input using the hardware scan code that
+/// it was received with. Keys that need the usual handling, e.g. mouse buttons or keys that
+/// zippychord tracks, are written with `press_key` or `release_key` instead.
+pub(super) fn write_key_passthrough(
+    kb: &mut KbdOut,
+    osc: OsCode,
+    val: KeyValue,
+    scancode: Option<u16>,
+) -> Result<(), std::io::Error> {
+    use OsCode::*;
+    let scancode = match osc {
+        BTN_LEFT | BTN_RIGHT | BTN_MIDDLE | BTN_SIDE | BTN_EXTRA => None,
+        MouseWheelUp | MouseWheelDown | MouseWheelLeft | MouseWheelRight => None,
+        _ if zippy_is_enabled() => None,
+        _ => scancode,
+    };
+    match (scancode, val) {
+        (None, KeyValue::Release) => release_key(kb, osc),
+        (None, _) => press_key(kb, osc),
+        (Some(scancode), _) => match u16::from(osc) {
+            KEY_IGNORE_MIN..=KEY_IGNORE_MAX => Ok(()),
+            _ if !kb.rate_limiter.allow(osc, val) => Ok(()),
+            _ => kb.write_key_scancode(osc, val, scancode),
+        },
+    }
+}
 fn osc_to_btn(osc: OsCode) -> Btn {
     use Btn::*;
     use OsCode::*;
@@ -130,6 +160,17 @@ pub(super) fn zippy_is_idle() -> bool {
     }
 }
 
+fn zippy_is_enabled() -> bool {
+    #[cfg(not(feature = "zippychord"))]
+    {
+        false
+    }
+    #[cfg(feature = "zippychord")]
+    {
+        zch().zch_is_enabled()
+    }
+}
+
 pub(super) fn zippy_tick(_caps_word_is_active: bool) {
     #[cfg(feature = "zippychord")]
     {
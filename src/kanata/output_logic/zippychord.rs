@@ -48,6 +48,77 @@ enum ZchSmartSpaceState {
     Sent,
 }
 
+/// Case applied to the letters of an activation's output.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum ZchCase {
+    #[default]
+    AsDefined,
+    /// The first character is uppercased if it is a letter, e.g. because the chord was pressed
+    /// with shift.
+    Capitalize,
+    /// All letters are uppercased, e.g. because caps-word is active.
+    Upper,
+}
+
+impl ZchCase {
+    fn apply(self, outs: &[ZchOutput]) -> Vec<ZchOutput> {
+        let mut is_first_char = true;
+        outs.iter()
+            .copied()
+            .map(|out| {
+                let Some(osc) = out.osc() else {
+                    return out;
+                };
+                let is_capitalized = match self {
+                    ZchCase::AsDefined => false,
+                    ZchCase::Capitalize => is_first_char,
+                    ZchCase::Upper => true,
+                };
+                is_first_char = false;
+                match out {
+                    ZchOutput::Lowercase(_) if is_capitalized && is_letter(osc) => {
+                        ZchOutput::Uppercase(osc)
+                    }
+                    _ => out,
+                }
+            })
+            .collect()
+    }
+}
+
+fn is_letter(osc: OsCode) -> bool {
+    use OsCode::*;
+    matches!(
+        osc,
+        KEY_A
+            | KEY_B
+            | KEY_C
+            | KEY_D
+            | KEY_E
+            | KEY_F
+            | KEY_G
+            | KEY_H
+            | KEY_I
+            | KEY_J
+            | KEY_K
+            | KEY_L
+            | KEY_M
+            | KEY_N
+            | KEY_O
+            | KEY_P
+            | KEY_Q
+            | KEY_R
+            | KEY_S
+            | KEY_T
+            | KEY_U
+            | KEY_V
+            | KEY_W
+            | KEY_X
+            | KEY_Y
+            | KEY_Z
+    )
+}
+
 #[derive(Debug, Default)]
 struct ZchDynamicState {
     /// Input to compare against configured available chords to output.
@@ -77,6 +148,8 @@ struct ZchDynamicState {
     zchd_characters_to_delete_on_next_activation: i16,
     /// Tracks past activation for additional computation.
     zchd_prior_activation: Option<Arc<ZchChordOutput>>,
+    /// Case that was applied to the output of `zchd_prior_activation`.
+    zchd_prior_activation_case: ZchCase,
     /// Tracker for time until prior state change to know if potential stale data should be
     /// cleared. This is a contingency in case of bugs or weirdness with OS interactions, e.g.
     /// Windows lock screen weirdness.
@@ -101,6 +174,9 @@ struct ZchDynamicState {
     zchd_is_rsft_active: bool,
     /// Current state of altgr which is a factor in smart space erasure.
     zchd_is_altgr_active: bool,
+    /// Whether shift was active when the first key of the current chord was pressed, which
+    /// capitalizes the output.
+    zchd_is_first_key_shifted: bool,
     /// Tracks whether last press was part of a chord or not.
     /// Upon releasing keys, this state determines if zippychording should remain enabled or
     /// disabled.
@@ -199,9 +275,28 @@ impl ZchDynamicState {
     }
 
     fn zchd_press_key(&mut self, osc: OsCode) {
+        if self.zchd_input_keys.zchik_is_empty() {
+            self.zchd_is_first_key_shifted = self.zchd_is_lsft_active || self.zchd_is_rsft_active;
+        }
         self.zchd_input_keys.zchik_insert(osc);
     }
 
+    /// The case to apply to the output of an activation.
+    fn zchd_case(&self, activation: &ZchChordOutput, cfg: &ZchConfig) -> ZchCase {
+        if !cfg.zch_cfg_follow_case || !activation.zch_follow_case {
+            ZchCase::AsDefined
+        } else if self.zchd_is_caps_word_active {
+            ZchCase::Upper
+        } else if self.zchd_is_first_key_shifted
+            || self.zchd_is_lsft_active
+            || self.zchd_is_rsft_active
+        {
+            ZchCase::Capitalize
+        } else {
+            ZchCase::AsDefined
+        }
+    }
+
     fn zchd_release_key(&mut self, osc: OsCode) {
         self.zchd_input_keys.zchik_remove(osc);
         match (self.zchd_last_press, self.zchd_input_keys.zchik_is_empty()) {
@@ -337,6 +432,8 @@ impl ZchState {
 
         match activation {
             HasValue(a) => {
+                let case = self.zchd.zchd_case(&a, &self.zch_cfg);
+                let output = case.apply(&a.zch_output);
                 // Find the longest common prefix length between the prior activation and the new
                 // activation. This value affects both:
                 // - the number of backspaces that need to be done
@@ -350,16 +447,20 @@ impl ZchState {
                         .zchd_prior_activation
                         .as_ref()
                         .map(|prior_activation| {
-                            let current_activation_output = &a.zch_output;
+                            let prior_activation_output = self
+                                .zchd
+                                .zchd_prior_activation_case
+                                .apply(&prior_activation.zch_output);
                             let mut len: i16 = 0;
-                            for (past, current) in prior_activation
-                                .zch_output
+                            for (past, current) in prior_activation_output
                                 .iter()
                                 .copied()
-                                .zip(current_activation_output.iter().copied())
+                                .zip(output.iter().copied())
                             {
-                                if past.osc() == OsCode::KEY_BACKSPACE
-                                    || current.osc() == OsCode::KEY_BACKSPACE
+                                // Macro events are not erased, so the common prefix ends
+                                // before them.
+                                if past.osc().is_none_or(|osc| osc == OsCode::KEY_BACKSPACE)
+                                    || current.osc().is_none_or(|osc| osc == OsCode::KEY_BACKSPACE)
                                     || past != current
                                 {
                                     break;
@@ -371,6 +472,7 @@ impl ZchState {
                         .unwrap_or(0)
                 };
                 self.zchd.zchd_prior_activation = Some(a.clone());
+                self.zchd.zchd_prior_activation_case = case;
                 self.zchd.zchd_same_hold_activation_count += 1;
 
                 self.zchd
@@ -407,14 +509,21 @@ impl ZchState {
                 self.zchd
                     .zchd_prioritized_chords
                     .clone_from(&a.zch_followups);
+                // Whether the shifts held during the activation have been released. Held shifts
+                // apply to the first character. With follow-case, they are released before a
+                // character that is not shifted in the output, e.g. because the output opts out,
+                // and with caps-word they are pressed again for the next shifted character.
+                let follow_case = self.zch_cfg.zch_cfg_follow_case;
                 let mut released_sft = false;
+                // Without follow-case, shifts released for the macro events currently being
+                // sent, to be pressed again before the next character.
+                let mut sft_paused_for_macro: Option<(bool, bool)> = None;
                 #[cfg(feature = "interception_driver")]
                 let mut send_count = 0;
                 if self.zchd.zchd_is_altgr_active && !a.zch_output.is_empty() {
                     kb.release_key(OsCode::KEY_RIGHTALT)?;
                 }
-                for key_to_send in a
-                    .zch_output
+                for key_to_send in output
                     .iter()
                     .copied()
                     .skip(common_prefix_len_from_past_activation as usize)
@@ -430,7 +539,49 @@ impl ZchState {
                         }
                     }
 
+                    let is_shifted = matches!(
+                        key_to_send,
+                        ZchOutput::Uppercase(_)
+                            | ZchOutput::NoEraseUppercase(_)
+                            | ZchOutput::ShiftAltGr(_)
+                            | ZchOutput::NoEraseShiftAltGr(_)
+                    );
+                    if !follow_case {
+                        if key_to_send.is_macro() {
+                            if sft_paused_for_macro.is_none() {
+                                sft_paused_for_macro =
+                                    Some(release_sft_for_macro(released_sft, kb, &self.zchd)?);
+                            }
+                        } else if let Some(paused) = sft_paused_for_macro.take() {
+                            press_sft_after_macro(paused, kb)?;
+                        }
+                    } else if !is_shifted && !released_sft {
+                        released_sft = true;
+                        release_held_sft(kb, &self.zchd)?;
+                    } else if is_shifted && released_sft && self.zchd.zchd_is_caps_word_active {
+                        released_sft = false;
+                        press_held_sft(kb, &self.zchd)?;
+                    }
+
                     match key_to_send {
+                        ZchOutput::MacroPress(osc) => {
+                            if self.zchd.zchd_input_keys.zchik_contains(osc) {
+                                kb.release_key(osc)?;
+                            }
+                            kb.press_key(osc)?;
+                            continue;
+                        }
+                        ZchOutput::MacroRelease(osc) => {
+                            kb.release_key(osc)?;
+                            if self.zchd.zchd_input_keys.zchik_contains(osc) {
+                                kb.press_key(osc)?;
+                            }
+                            continue;
+                        }
+                        ZchOutput::MacroDelay(delay) => {
+                            std::thread::sleep(std::time::Duration::from_millis(delay.into()));
+                            continue;
+                        }
                         ZchOutput::Lowercase(osc) | ZchOutput::NoEraseLowercase(osc) => {
                             type_osc(osc, kb, &self.zchd)?;
                         }
@@ -475,11 +626,19 @@ impl ZchState {
                         }
                     }
                 }
+                // Without caps-word, shifts are pressed again below as after typing characters.
+                if let Some(paused) = sft_paused_for_macro
+                    && self.zchd.zchd_is_caps_word_active
+                {
+                    press_sft_after_macro(paused, kb)?;
+                }
 
+                // Smart space only follows characters, not macro events.
                 if self.zch_cfg.zch_cfg_smart_space != ZchSmartSpaceCfg::Disabled
                     && a.zch_output
                         .last()
-                        .map(|out| !matches!(out.osc(), OsCode::KEY_SPACE | OsCode::KEY_BACKSPACE))
+                        .and_then(|out| out.osc())
+                        .map(|osc| !matches!(osc, OsCode::KEY_SPACE | OsCode::KEY_BACKSPACE))
                         .unwrap_or(false /* if output is empty, don't do smart spacing */)
                 {
                     if self.zch_cfg.zch_cfg_smart_space == ZchSmartSpaceCfg::Full {
@@ -501,11 +660,20 @@ impl ZchState {
                     self.zchd.zchd_prior_activation_output_count += 1;
                     self.zchd.zchd_characters_to_delete_on_next_activation += 1;
 
+                    // With follow-case, the space is never shifted, e.g. by caps-word.
+                    if follow_case && !released_sft {
+                        released_sft = true;
+                        release_held_sft(kb, &self.zchd)?;
+                    }
                     kb.press_key(OsCode::KEY_SPACE)?;
                     kb.release_key(OsCode::KEY_SPACE)?;
                 }
 
-                if !self.zchd.zchd_is_caps_word_active {
+                if self.zchd.zchd_is_caps_word_active {
+                    if released_sft {
+                        press_held_sft(kb, &self.zchd)?;
+                    }
+                } else {
                     // When expanding, lsft/rsft will be released after the first press.
                     if self.zchd.zchd_is_lsft_active {
                         kb.press_key(OsCode::KEY_LEFTSHIFT)?;
@@ -588,6 +756,10 @@ impl ZchState {
     pub(crate) fn zch_is_idle(&self) -> bool {
         self.zchd.zchd_is_idle()
     }
+
+    pub(crate) fn zch_is_enabled(&self) -> bool {
+        !self.zch_chords.is_empty()
+    }
 }
 
 fn type_osc(osc: OsCode, kb: &mut KbdOut, zchd: &ZchDynamicState) -> Result<(), std::io::Error> {
@@ -601,6 +773,62 @@ fn type_osc(osc: OsCode, kb: &mut KbdOut, zchd: &ZchDynamicState) -> Result<(),
     Ok(())
 }
 
+/// Releases the shifts held during the activation so that they do not affect macro events.
+/// Returns which of lsft and rsft were released.
+fn release_sft_for_macro(
+    sft_already_released: bool,
+    kb: &mut KbdOut,
+    zchd: &ZchDynamicState,
+) -> Result<(bool, bool), std::io::Error> {
+    let is_held = zchd.zchd_is_caps_word_active || !sft_already_released;
+    let released = (
+        is_held && zchd.zchd_is_lsft_active,
+        is_held && zchd.zchd_is_rsft_active,
+    );
+    if released.0 {
+        kb.release_key(OsCode::KEY_LEFTSHIFT)?;
+    }
+    if released.1 {
+        kb.release_key(OsCode::KEY_RIGHTSHIFT)?;
+    }
+    Ok(released)
+}
+
+fn press_sft_after_macro(
+    (lsft, rsft): (bool, bool),
+    kb: &mut KbdOut,
+) -> Result<(), std::io::Error> {
+    if lsft {
+        kb.press_key(OsCode::KEY_LEFTSHIFT)?;
+    }
+    if rsft {
+        kb.press_key(OsCode::KEY_RIGHTSHIFT)?;
+    }
+    Ok(())
+}
+
+/// Releases the shifts held by the user.
+fn release_held_sft(kb: &mut KbdOut, zchd: &ZchDynamicState) -> Result<(), std::io::Error> {
+    if zchd.zchd_is_lsft_active {
+        kb.release_key(OsCode::KEY_LEFTSHIFT)?;
+    }
+    if zchd.zchd_is_rsft_active {
+        kb.release_key(OsCode::KEY_RIGHTSHIFT)?;
+    }
+    Ok(())
+}
+
+/// Presses the shifts held by the user again after `release_held_sft`.
+fn press_held_sft(kb: &mut KbdOut, zchd: &ZchDynamicState) -> Result<(), std::io::Error> {
+    if zchd.zchd_is_lsft_active {
+        kb.press_key(OsCode::KEY_LEFTSHIFT)?;
+    }
+    if zchd.zchd_is_rsft_active {
+        kb.press_key(OsCode::KEY_RIGHTSHIFT)?;
+    }
+    Ok(())
+}
+
 fn maybe_press_sft_during_activation(
     sft_already_released: bool,
     kb: &mut KbdOut,
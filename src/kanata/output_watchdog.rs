@@ -0,0 +1,92 @@
+use crate::oskbd::{KeyEvent, KeyValue};
+use kanata_keyberon::key_code::KeyCode;
+use kanata_parser::keys::{OsCode, oscode_to_str};
+
+/// Safety net for `max-output-hold-ms`. Finds output keys that have been held for longer than the
+/// limit while none of the physical keys that were held when they were pressed are still held,
+/// so that a key left stuck by some bug does not stay held until the user presses it again.
+#[derive(Debug, Default)]
+pub struct OutputWatchdog {
+    max_hold_ms: Option<u32>,
+    /// Physically held input keys.
+    physical: Vec<OsCode>,
+    held: Vec<HeldOutput>,
+}
+
+#[derive(Debug)]
+struct HeldOutput {
+    key: KeyCode,
+    held_ms: u32,
+    /// The physical keys that were held when the output key was pressed. The output key is
+    /// considered to be held by the user as long as any of them is still held.
+    origins: Vec<OsCode>,
+}
+
+impl OutputWatchdog {
+    pub fn new(max_hold_ms: Option<u32>) -> Self {
+        Self {
+            max_hold_ms,
+            ..Default::default()
+        }
+    }
+
+    pub fn configure(&mut self, max_hold_ms: Option<u32>) {
+        self.max_hold_ms = max_hold_ms;
+        self.held.clear();
+    }
+
+    pub(crate) fn record_input(&mut self, event: &KeyEvent) {
+        match event.value {
+            KeyValue::Press if !self.physical.contains(&event.code) => {
+                self.physical.push(event.code)
+            }
+            KeyValue::Release => self.physical.retain(|osc| *osc != event.code),
+            _ => {}
+        }
+    }
+
+    /// Advances the hold times of `output_keys`, the keys that are currently output as held,
+    /// and returns the keys that should be force-released.
+    pub(crate) fn tick(&mut self, output_keys: &[KeyCode]) -> Vec<KeyCode> {
+        let Some(max_hold_ms) = self.max_hold_ms else {
+            return vec![];
+        };
+        self.held.retain(|h| output_keys.contains(&h.key));
+        for key in output_keys {
+            match self.held.iter_mut().find(|h| h.key == *key) {
+                Some(h) => h.held_ms = h.held_ms.saturating_add(1),
+                None => self.held.push(HeldOutput {
+                    key: *key,
+                    held_ms: 0,
+                    origins: self.physical.clone(),
+                }),
+            }
+        }
+        let physical = &self.physical;
+        let mut expired = vec![];
+        self.held.retain(|h| {
+            if h.held_ms < max_hold_ms || h.origins.iter().any(|osc| physical.contains(osc)) {
+                return true;
+            }
+            let origins = match h.origins.is_empty() {
+                true => "no physical keys were held when it was pressed".to_string(),
+                false => format!(
+                    "none of the physical keys held when it was pressed ({}) are still held",
+                    h.origins
+                        .iter()
+                        .map(|osc| oscode_to_str(*osc))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ),
+            };
+            log::warn!(
+                "max-output-hold-ms: releasing {} after it was held for {} ms; {origins}",
+                oscode_to_str(OsCode::from(h.key)),
+                h.held_ms
+            );
+            expired.push(h.key);
+            false
+        });
+        expired
+    }
+}
@@ -0,0 +1,109 @@
+use kanata_parser::keys::{OsCode, str_to_oscode};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+
+/// Keys that exit kanata when held together, checked by the input thread before any other
+/// handling so that it works even if the configuration or the processing loop is broken. Set via
+/// the --panic-combo CLI argument.
+static PANIC_COMBO: OnceLock<PanicCombo> = OnceLock::new();
+
+/// Keys of the --panic-combo CLI argument, written as key names joined by `+`, e.g. `lmet+q`.
+#[derive(Debug)]
+pub struct PanicCombo {
+    /// Each key and whether it is held. Atomics avoid taking a lock in the input thread.
+    keys: Vec<(OsCode, AtomicBool)>,
+}
+
+impl PanicCombo {
+    pub fn keys(&self) -> impl Iterator<Item = OsCode> + '_ {
+        self.keys.iter().map(|(osc, _)| *osc)
+    }
+
+    /// Updates the held state of a key and returns whether all keys of the combo are now held.
+    fn update(&self, osc: OsCode, pressed: bool) -> bool {
+        let Some((_, held)) = self.keys.iter().find(|(key, _)| *key == osc) else {
+            return false;
+        };
+        held.store(pressed, SeqCst);
+        pressed && self.keys.iter().all(|(_, held)| held.load(SeqCst))
+    }
+}
+
+impl Clone for PanicCombo {
+    fn clone(&self) -> Self {
+        Self {
+            keys: self
+                .keys()
+                .map(|osc| (osc, AtomicBool::new(false)))
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for PanicCombo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keys: Vec<(OsCode, AtomicBool)> = vec![];
+        for name in s.split('+') {
+            let osc = str_to_oscode(name).ok_or_else(|| {
+                format!("unknown key \"{name}\", expected key names joined by +, e.g. lmet+q")
+            })?;
+            if keys.iter().any(|(key, _)| *key == osc) {
+                return Err(format!("key \"{name}\" is repeated"));
+            }
+            keys.push((osc, AtomicBool::new(false)));
+        }
+        if keys.len() < 2 {
+            return Err("the combo needs at least two keys, e.g. lmet+q".into());
+        }
+        Ok(Self { keys })
+    }
+}
+
+/// Enables the panic combo. Only the first call has an effect.
+pub fn set_panic_combo(combo: PanicCombo) {
+    let _ = PANIC_COMBO.set(combo);
+}
+
+/// Checks an input key against the panic combo and exits if all of its keys are held. This must
+/// not take any lock that the processing loop holds, since that loop may be the one that is stuck.
+pub(crate) fn check_for_panic_combo(osc: OsCode, pressed: bool) {
+    let Some(combo) = PANIC_COMBO.get() else {
+        return;
+    };
+    if !combo.update(osc, pressed) {
+        return;
+    }
+    log::info!("pressed the panic combo, releasing input and exiting");
+    #[cfg(target_os = "macos")]
+    karabiner_driverkit::release_input_only();
+    // Exiting releases the input grab on Linux and removes the hooks on Windows.
+    #[cfg(unix)]
+    crate::tcp_server::remove_unix_socket();
+    std::process::exit(super::EMERGENCY_EXIT_CODE.load(SeqCst));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panic_combo_parses_and_tracks_keys() {
+        let combo: PanicCombo = "lmet+q".parse().expect("parses");
+        assert_eq!(
+            combo.keys().collect::<Vec<_>>(),
+            [OsCode::KEY_LEFTMETA, OsCode::KEY_Q]
+        );
+        assert!(!combo.update(OsCode::KEY_Q, true));
+        assert!(!combo.update(OsCode::KEY_A, true));
+        assert!(combo.update(OsCode::KEY_LEFTMETA, true));
+        assert!(!combo.update(OsCode::KEY_Q, false));
+        assert!(!combo.update(OsCode::KEY_LEFTMETA, true));
+        assert!("q".parse::<PanicCombo>().is_err());
+        assert!("lmet+q+q".parse::<PanicCombo>().is_err());
+        assert!("lmet+nope".parse::<PanicCombo>().is_err());
+        assert!("lmet+".parse::<PanicCombo>().is_err());
+    }
+}
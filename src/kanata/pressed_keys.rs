@@ -0,0 +1,45 @@
+use super::PRESSED_KEYS;
+use kanata_keyberon::key_code::KeyCode;
+use kanata_parser::keys::{OsCode, oscode_to_str};
+use kanata_tcp_protocol::ServerMessage;
+
+/// Keys pressed on the physical keyboard and keys output as pressed by kanata, sorted by key
+/// code, for `RequestPressedKeys` and the `PressedKeys` notification.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PressedKeys {
+    physical: Vec<OsCode>,
+    output: Vec<OsCode>,
+}
+
+impl PressedKeys {
+    /// Copy the physical pressed keys and the given output keys. `PRESSED_KEYS` is only locked
+    /// while copying, so that the event loop updating it is not held up by slow clients.
+    pub(crate) fn snapshot(cur_keys: &[KeyCode]) -> Self {
+        #[cfg(not(all(target_os = "windows", not(feature = "interception_driver"))))]
+        let mut physical = PRESSED_KEYS.lock().iter().copied().collect::<Vec<_>>();
+        #[cfg(all(target_os = "windows", not(feature = "interception_driver")))]
+        let mut physical = PRESSED_KEYS.lock().keys().copied().collect::<Vec<_>>();
+        physical.sort_by_key(|osc| u16::from(*osc));
+        let mut output = cur_keys
+            .iter()
+            .map(|kc| OsCode::from(*kc))
+            .collect::<Vec<_>>();
+        output.sort_by_key(|osc| u16::from(*osc));
+        output.dedup();
+        Self { physical, output }
+    }
+
+    /// The keys by name. The output keys are only included if they differ from the physical
+    /// keys.
+    pub(crate) fn message(&self) -> ServerMessage {
+        let names = |keys: &[OsCode]| {
+            let mut names = keys.iter().copied().map(oscode_to_str).collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        ServerMessage::PressedKeys {
+            keys: names(&self.physical),
+            output: (self.output != self.physical).then(|| names(&self.output)),
+        }
+    }
+}
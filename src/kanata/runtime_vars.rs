@@ -0,0 +1,136 @@
+use anyhow::{Result, bail};
+use kanata_parser::cfg::BorrowedKLayout;
+use kanata_parser::custom_action::RuntimeU16;
+use rustc_hash::FxHashMap as HashMap;
+use rustc_hash::FxHashSet as HashSet;
+use web_time::{Duration, Instant};
+
+/// Minimum time between two warnings about the same variable being out of range.
+const CLAMP_WARNING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Names of the runtime variables changed by `setvar` and `incvar` and compared by switch. The
+/// values are stored in the layout so that switch conditions can read them.
+#[derive(Debug, Default)]
+pub struct RuntimeVars {
+    /// Variable names, indexed by variable number.
+    names: Vec<String>,
+    /// Variables that have already been warned about being read while unset.
+    warned_unset: HashSet<u16>,
+    /// When each variable was last warned about being read while out of range.
+    warned_clamped: HashMap<u16, Instant>,
+}
+
+impl RuntimeVars {
+    pub(crate) fn new(names: Vec<String>) -> Self {
+        Self {
+            names,
+            warned_unset: HashSet::default(),
+            warned_clamped: HashMap::default(),
+        }
+    }
+
+    /// Replace the variables with those of a newly loaded configuration. `old_values` are the
+    /// values from the previous layout; they are kept by name if the new configuration enables
+    /// `runtime-vars-persist-on-reload`.
+    pub(crate) fn reload(
+        &mut self,
+        names: Vec<String>,
+        persist_on_reload: bool,
+        old_values: &[Option<i32>],
+        layout: &mut BorrowedKLayout,
+    ) {
+        if persist_on_reload {
+            for (new_var, name) in names.iter().enumerate() {
+                let old_value = self
+                    .names
+                    .iter()
+                    .position(|n| n == name)
+                    .and_then(|old_var| old_values.get(old_var).copied().flatten());
+                layout.vars[new_var] = old_value;
+            }
+        }
+        *self = Self::new(names);
+    }
+
+    pub(crate) fn set(&self, var: u16, value: i32, layout: &mut BorrowedKLayout) {
+        log::debug!("setvar {} {value}", self.names[usize::from(var)]);
+        layout.vars[usize::from(var)] = Some(value);
+    }
+
+    pub(crate) fn inc(&self, var: u16, amount: i32, layout: &mut BorrowedKLayout) {
+        let value = layout.vars[usize::from(var)]
+            .unwrap_or(0)
+            .saturating_add(amount);
+        self.set(var, value, layout);
+    }
+
+    /// Set the variable with the given name, failing if no such variable is used by the
+    /// configuration.
+    pub fn set_by_name(&self, name: &str, value: i32, layout: &mut BorrowedKLayout) -> Result<()> {
+        let Some(var) = self.names.iter().position(|n| n == name) else {
+            bail!("unknown runtime variable: {name}");
+        };
+        self.set(var as u16, value, layout);
+        Ok(())
+    }
+
+    /// Resolve a number of an action that may reference a variable. The variable's value is
+    /// clamped to `min`-65535.
+    pub(crate) fn resolve(
+        &mut self,
+        num: RuntimeU16,
+        min: u16,
+        layout: &mut BorrowedKLayout,
+    ) -> u16 {
+        let var = match num {
+            RuntimeU16::Num(n) => return n,
+            RuntimeU16::Var(var) => var,
+        };
+        let value = layout.vars[usize::from(var)].unwrap_or_else(|| {
+            layout.unset_var_read = Some(var);
+            0
+        });
+        let clamped = value.clamp(i32::from(min), i32::from(u16::MAX));
+        if clamped != value {
+            self.warn_clamped(var, value, clamped);
+        }
+        clamped as u16
+    }
+
+    fn warn_clamped(&mut self, var: u16, value: i32, clamped: i32) {
+        let now = Instant::now();
+        if self
+            .warned_clamped
+            .get(&var)
+            .is_some_and(|warned| now.duration_since(*warned) < CLAMP_WARNING_INTERVAL)
+        {
+            return;
+        }
+        self.warned_clamped.insert(var, now);
+        log::warn!(
+            "runtime variable {} is {value}, which is out of range, using {clamped}",
+            self.names[usize::from(var)]
+        );
+    }
+
+    /// Warn about a variable that a sequence delay read while out of range. Warnings about the
+    /// same variable are rate-limited.
+    pub(crate) fn warn_clamped_read(&mut self, layout: &mut BorrowedKLayout) {
+        if let Some((var, value)) = layout.clamped_var_read.take() {
+            self.warn_clamped(var, value, value.clamp(0, i32::from(u16::MAX)));
+        }
+    }
+
+    /// Warn about a variable that switch read while unset. Each variable is only warned about
+    /// once per configuration.
+    pub(crate) fn warn_unset_read(&mut self, layout: &mut BorrowedKLayout) {
+        if let Some(var) = layout.unset_var_read.take()
+            && self.warned_unset.insert(var)
+        {
+            log::warn!(
+                "runtime variable {} was read before being set, using 0",
+                self.names[usize::from(var)]
+            );
+        }
+    }
+}
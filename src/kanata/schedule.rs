@@ -0,0 +1,111 @@
+use kanata_parser::cfg::{BorrowedKLayout, ScheduleEntry};
+use web_time::{Duration, Instant};
+
+/// Time between reads of the wall clock.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A local weekday and time of day, as compared against the ranges of `defschedule`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduleTime {
+    /// Day of the week, 0 being Monday.
+    pub weekday: u8,
+    /// Minutes since midnight.
+    pub minute: u16,
+}
+
+impl ScheduleTime {
+    fn now() -> Option<Self> {
+        let now = time::OffsetDateTime::now_local().ok()?;
+        Some(Self {
+            weekday: now.weekday().number_days_from_monday(),
+            minute: u16::from(now.hour()) * 60 + u16::from(now.minute()),
+        })
+    }
+}
+
+/// The time ranges of `defschedule`. The layer of the first range containing the local time is
+/// used as the base layer. The base layer is only changed when the scheduled layer changes, so
+/// layer actions used in the meantime are kept until the next change.
+#[derive(Debug, Default)]
+pub struct Schedule {
+    entries: Vec<ScheduleEntry>,
+    /// Layer activated by the schedule, or `None` if no range contains the last checked time.
+    scheduled_layer: Option<usize>,
+    /// Base layer from before the schedule activated a layer. It is restored when no range
+    /// contains the time anymore.
+    unscheduled_layer: usize,
+    last_check: Option<Instant>,
+    warned_clock: bool,
+    /// Replaces the wall clock in tests.
+    #[cfg(test)]
+    mock_time: Option<ScheduleTime>,
+}
+
+impl Schedule {
+    pub(crate) fn new(entries: Vec<ScheduleEntry>) -> Self {
+        Self {
+            entries,
+            ..Default::default()
+        }
+    }
+
+    /// Use the given time instead of the wall clock and update the base layer for it.
+    #[cfg(all(test, feature = "simulated_output"))]
+    pub(crate) fn set_mock_time(&mut self, time: ScheduleTime, layout: &mut BorrowedKLayout) {
+        self.mock_time = Some(time);
+        self.last_check = Some(Instant::now());
+        self.update(time, layout);
+    }
+
+    /// Read the wall clock at most once per `CHECK_INTERVAL` and update the base layer.
+    pub(crate) fn tick(&mut self, layout: &mut BorrowedKLayout) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        if self
+            .last_check
+            .is_some_and(|last| now.duration_since(last) < CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.last_check = Some(now);
+        #[cfg(test)]
+        let time = self.mock_time.or_else(ScheduleTime::now);
+        #[cfg(not(test))]
+        let time = ScheduleTime::now();
+        match time {
+            Some(time) => self.update(time, layout),
+            None if !self.warned_clock => {
+                log::warn!("could not read the local time, defschedule is inactive");
+                self.warned_clock = true;
+            }
+            None => {}
+        }
+    }
+
+    fn update(&mut self, time: ScheduleTime, layout: &mut BorrowedKLayout) {
+        let layer = self
+            .entries
+            .iter()
+            .find(|entry| entry.contains(time.weekday, time.minute))
+            .map(|entry| usize::from(entry.layer));
+        if layer == self.scheduled_layer {
+            return;
+        }
+        match layer {
+            Some(layer) => {
+                if self.scheduled_layer.is_none() {
+                    self.unscheduled_layer = layout.default_layer;
+                }
+                log::debug!("schedule activating layer {layer}");
+                layout.set_default_layer(layer);
+            }
+            None => {
+                log::debug!("schedule ended, restoring layer {}", self.unscheduled_layer);
+                layout.set_default_layer(self.unscheduled_layer);
+            }
+        }
+        self.scheduled_layer = layer;
+    }
+}
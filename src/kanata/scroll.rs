@@ -6,6 +6,15 @@ pub struct ScrollState {
     pub ticks_until_scroll: u16,
     pub distance: u16,
     pub scroll_accel_state: Option<ScrollAccelState>,
+    pub fine_scroll_state: Option<FineScrollState>,
+}
+
+/// State of a held `mwheel-fine` action, which is output as one smooth scroll gesture.
+pub struct FineScrollState {
+    /// Ramps the distance like `movemouse-accel`. `None` scrolls at a constant distance.
+    pub accel: Option<MoveMouseAccelState>,
+    /// Whether the gesture has started, so that it can be ended on release.
+    pub began: bool,
 }
 
 pub struct ScrollAccelState {
@@ -22,6 +31,20 @@ pub(crate) fn update_scrollstate_get_result(
     let Some(state) = state else {
         return None;
     };
+    if state.distance != 0
+        && let Some(FineScrollState {
+            accel: Some(acs), ..
+        }) = &mut state.fine_scroll_state
+    {
+        if acs.accel_ticks_until_max != 0 {
+            let increment = (acs.accel_increment * f64::from(acs.accel_ticks_from_min)) as u16;
+            state.distance = acs.min_distance + increment;
+            acs.accel_ticks_from_min += 1;
+            acs.accel_ticks_until_max -= 1;
+        } else {
+            state.distance = acs.max_distance;
+        }
+    }
     if state.ticks_until_scroll == 0 {
         state.ticks_until_scroll = state.interval - 1;
         let direction = state.direction;
@@ -53,3 +76,19 @@ pub(crate) fn update_scrollstate_get_result(
         None
     }
 }
+
+/// Returns the phase to output a fine scroll with, or `None` for a regular notched scroll.
+pub(crate) fn fine_scroll_phase(
+    state: &mut Option<ScrollState>,
+    distance: u16,
+) -> Option<ScrollPhase> {
+    let fss = state.as_mut()?.fine_scroll_state.as_mut()?;
+    Some(match (distance, fss.began) {
+        (0, _) => ScrollPhase::Ended,
+        (_, false) => {
+            fss.began = true;
+            ScrollPhase::Began
+        }
+        (_, true) => ScrollPhase::Changed,
+    })
+}
@@ -25,11 +25,24 @@ pub struct SequenceState {
     /// At 0 the sequence state terminates.
     pub ticks_until_timeout: u16,
     /// User-configured sequence timeout setting.
+    /// Sequences with their own timeout in `defseq` use that instead.
     pub sequence_timeout: u16,
     /// Whether the sequence is active or not.
     pub activity: SequenceActivity,
     /// Counter to reduce number of backspaces typed.
     noerase_count: u16,
+    /// Backspaces needed to erase each keystroke echoed by `visible-backspaced`, in input order.
+    /// Unicode output sent while the sequence is active counts towards the preceding keystroke.
+    erase_counts: Vec<u16>,
+    /// Most recent unicode character sent after the last echoed keystroke.
+    last_unicode: Option<char>,
+    /// Keys matched by the wildcard steps of the most recently completed sequence.
+    /// Output by [`CustomAction::SequenceMatch`].
+    pub matched_keys: &'static [u16],
+    /// Whether to record `notifications`, from `sequence-notifications`.
+    notify: bool,
+    /// Notifications for TCP clients recorded since they were last taken.
+    pub notifications: Vec<ServerMessage>,
 }
 
 impl SequenceState {
@@ -43,19 +56,75 @@ impl SequenceState {
             sequence_timeout: 0,
             activity: Inactive,
             noerase_count: 0,
+            erase_counts: vec![],
+            last_unicode: None,
+            matched_keys: &[],
+            notify: false,
+            notifications: vec![],
         }
     }
 
     /// Updates the sequence state parameters, clears buffers, and sets the state to active.
-    pub fn activate(&mut self, input_mode: SequenceInputMode, timeout: u16) {
+    pub fn activate(
+        &mut self,
+        input_mode: SequenceInputMode,
+        timeout: u16,
+        sequences: &cfg::KeySeqsToFKeys,
+        notify: bool,
+    ) {
         self.sequence_input_mode = input_mode;
         self.sequence_timeout = timeout;
-        self.ticks_until_timeout = timeout;
         self.raw_oscs.clear();
         self.sequence.clear();
         self.overlapped_sequence.clear();
         self.activity = Active;
         self.noerase_count = 0;
+        self.erase_counts.clear();
+        self.last_unicode = None;
+        self.ticks_until_timeout = self.pending_timeout(sequences);
+        self.notify = notify;
+        if notify {
+            self.notifications.push(ServerMessage::SequenceStarted {
+                timeout_ms: self.ticks_until_timeout,
+            });
+        }
+    }
+
+    fn notify_progress(&mut self, sequences: &cfg::KeySeqsToFKeys) {
+        if !self.notify {
+            return;
+        }
+        let mut candidates = sequences
+            .descendant_values(&self.sequence)
+            .chain(sequences.descendant_values(&self.overlapped_sequence))
+            .map(|(i, j, _, _)| (*i, *j))
+            .collect::<Vec<_>>();
+        candidates.sort_unstable();
+        candidates.dedup();
+        self.notifications.push(ServerMessage::SequenceProgress {
+            keys: self
+                .raw_oscs
+                .iter()
+                .map(|osc| oscode_to_str(*osc).to_string())
+                .collect(),
+            candidates: candidates.len(),
+        });
+    }
+
+    fn notify_end(&mut self, reason: SequenceEndReason) {
+        if self.notify {
+            self.notifications
+                .push(ServerMessage::SequenceEnded { reason });
+        }
+    }
+
+    /// Returns the time to wait for the next keystroke: the longest timeout among the sequences
+    /// that can still be completed.
+    fn pending_timeout(&self, sequences: &cfg::KeySeqsToFKeys) -> u16 {
+        sequences
+            .max_timeout(&self.sequence, self.sequence_timeout)
+            .max(sequences.max_timeout(&self.overlapped_sequence, self.sequence_timeout))
+            .unwrap_or(self.sequence_timeout)
     }
 
     pub fn is_active(&self) -> bool {
@@ -97,11 +166,10 @@ pub(super) fn do_sequence_press_logic(
     k: &KeyCode,
     mod_mask: u16,
     kbd_out: &mut KbdOut,
-    sequences: &kanata_parser::trie::Trie<(u8, u16)>,
+    sequences: &cfg::KeySeqsToFKeys,
     sequence_backtrack_modcancel: bool,
     layout: &mut BorrowedKLayout,
 ) -> Result<(), anyhow::Error> {
-    state.ticks_until_timeout = state.sequence_timeout;
     let osc = OsCode::from(*k);
     state.raw_oscs.push(osc);
     use kanata_parser::trie::GetOrDescendentExistsResult::*;
@@ -120,6 +188,8 @@ pub(super) fn do_sequence_press_logic(
     match state.sequence_input_mode {
         SequenceInputMode::VisibleBackspaced => {
             press_key(kbd_out, osc)?;
+            state.erase_counts.push(backspaces_to_erase_key(osc));
+            state.last_unicode = None;
         }
         SequenceInputMode::HiddenSuppressed | SequenceInputMode::HiddenDelayType => {}
     }
@@ -232,27 +302,34 @@ pub(super) fn do_sequence_press_logic(
             }
             if res == NotInTrie || state.sequence.is_empty() {
                 log::debug!("invalid keys for seq");
-                cancel_sequence(state, kbd_out)?;
+                cancel_sequence(state, kbd_out, SequenceEndReason::Cancelled)?;
             }
         }
     }
 
     // Check for successful sequence termination.
-    if let HasValue((i, j)) = res_overlapped {
+    if let HasValue((i, j, matched, timeout)) = res_overlapped {
         // First, check for a valid simultaneous completion.
         // Simultaneous completion should take priority.
-        do_successful_sequence_termination(kbd_out, state, layout, i, j, EndSequenceType::Overlap)?;
-    } else if let HasValue((i, j)) = res {
+        do_successful_sequence_termination(
+            kbd_out,
+            state,
+            layout,
+            (i, j, matched, timeout),
+            EndSequenceType::Overlap,
+        )?;
+    } else if let HasValue((i, j, matched, timeout)) = res {
         // Try terminating the overlapping and check if simultaneous termination worked.
         // Simultaneous completion should take priority.
         state.overlapped_sequence.push(KEY_OVERLAP_MARKER);
-        if let HasValue((oi, oj)) = sequences.get_or_descendant_exists(&state.overlapped_sequence) {
+        if let HasValue(overlap_fkey) =
+            sequences.get_or_descendant_exists(&state.overlapped_sequence)
+        {
             do_successful_sequence_termination(
                 kbd_out,
                 state,
                 layout,
-                oi,
-                oj,
+                overlap_fkey,
                 EndSequenceType::Overlap,
             )?;
         } else {
@@ -260,12 +337,15 @@ pub(super) fn do_sequence_press_logic(
                 kbd_out,
                 state,
                 layout,
-                i,
-                j,
+                (i, j, matched, timeout),
                 EndSequenceType::Standard,
             )?;
         }
     }
+    if state.is_active() {
+        state.ticks_until_timeout = state.pending_timeout(sequences);
+        state.notify_progress(sequences);
+    }
     Ok(())
 }
 
@@ -275,12 +355,13 @@ pub(super) fn do_successful_sequence_termination(
     kbd_out: &mut KbdOut,
     state: &mut SequenceState,
     layout: &mut Layout<'_, 767, 2, &&[&CustomAction]>,
-    i: u8,
-    j: u16,
+    (i, j, matched_keys, _): (u8, u16, &'static [u16], Option<u16>),
     seq_type: EndSequenceType,
 ) -> Result<(), anyhow::Error> {
     log::debug!("sequence complete; tapping fake key");
     state.activity = Inactive;
+    state.notify_end(SequenceEndReason::Completed);
+    state.matched_keys = matched_keys;
     let sequence = match seq_type {
         EndSequenceType::Standard => &state.sequence,
         EndSequenceType::Overlap => &state.overlapped_sequence,
@@ -302,36 +383,17 @@ pub(super) fn do_successful_sequence_termination(
                 }
                 _ => true,
             });
-            for k in sequence.iter().copied() {
-                // Check for pressed modifiers and don't input backspaces for
-                // those since they don't output characters that can be
-                // backspaced.
-                if k == KEY_OVERLAP_MARKER {
-                    continue;
-                };
-                let osc = OsCode::from(k & MASK_KEYCODES);
-                match osc {
-                    // Known bug: most non-characters-outputting keys are not
-                    // listed. I'm too lazy to list them all. Just use
-                    // character-outputting keys (and modifiers) in sequences
-                    // please! Or switch to a different input mode? It doesn't
-                    // really make sense to use non-typing characters other
-                    // than modifiers does it? Since those would probably be
-                    // further away from the home row, so why use them? If one
-                    // desired to fix this, a shorter list of keys would
-                    // probably be the list of keys that **do** output
-                    // characters than those that don't.
-                    osc if osc.is_modifier() => continue,
-                    osc if matches!(u16::from(osc), KEY_IGNORE_MIN..=KEY_IGNORE_MAX) => continue,
-                    _ => {
-                        if state.noerase_count > 0 {
-                            state.noerase_count -= 1;
-                        } else {
-                            kbd_out.press_key(OsCode::KEY_BACKSPACE)?;
-                            kbd_out.release_key(OsCode::KEY_BACKSPACE)?;
-                        }
-                    }
-                }
+            // Each non-marker item of the matched sequence is one of the most recent keystrokes.
+            let keystroke_count = sequence
+                .iter()
+                .filter(|k| **k != KEY_OVERLAP_MARKER)
+                .count();
+            let erase_count: u16 = state.erase_counts.iter().rev().take(keystroke_count).sum();
+            let noerase_count = state.noerase_count.min(erase_count);
+            state.noerase_count -= noerase_count;
+            for _ in noerase_count..erase_count {
+                kbd_out.press_key(OsCode::KEY_BACKSPACE)?;
+                kbd_out.release_key(OsCode::KEY_BACKSPACE)?;
             }
         }
     }
@@ -350,8 +412,13 @@ pub(super) fn do_successful_sequence_termination(
     Ok(())
 }
 
-pub(super) fn cancel_sequence(state: &mut SequenceState, kbd_out: &mut KbdOut) -> Result<()> {
+pub(super) fn cancel_sequence(
+    state: &mut SequenceState,
+    kbd_out: &mut KbdOut,
+    reason: SequenceEndReason,
+) -> Result<()> {
     state.activity = Inactive;
+    state.notify_end(reason);
     log::debug!("sequence cancelled");
     match state.sequence_input_mode {
         SequenceInputMode::HiddenDelayType => {
@@ -369,3 +436,66 @@ pub(super) fn cancel_sequence(state: &mut SequenceState, kbd_out: &mut KbdOut) -
 pub(super) fn add_noerase(state: &mut SequenceState, noerase_count: u16) {
     state.noerase_count += noerase_count;
 }
+
+/// Records unicode output sent while a `visible-backspaced` sequence is active so that the
+/// erase phase also removes it.
+pub(super) fn add_unicode_echo(state: &mut SequenceState, c: char) {
+    if state.sequence_input_mode != SequenceInputMode::VisibleBackspaced {
+        return;
+    }
+    let joins_previous = is_grapheme_extender(c) || state.last_unicode == Some(ZWJ);
+    state.last_unicode = Some(c);
+    if joins_previous {
+        return;
+    }
+    // Output before the first keystroke is not part of any sequence.
+    if let Some(count) = state.erase_counts.last_mut() {
+        *count += 1;
+    }
+}
+
+const ZWJ: char = '\u{200D}';
+
+/// Characters that the host renders as part of the preceding grapheme, which a single backspace
+/// usually erases together. Counting them as zero backspaces erases conservatively in hosts
+/// that delete one code point at a time.
+fn is_grapheme_extender(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}' // combining diacritical marks
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{FE00}'..='\u{FE0F}' // variation selectors
+        | '\u{1F3FB}'..='\u{1F3FF}' // emoji skin tone modifiers
+        | ZWJ
+    )
+}
+
+/// Backspaces needed to erase the output of a key echoed by `visible-backspaced`.
+fn backspaces_to_erase_key(osc: OsCode) -> u16 {
+    match osc {
+        // Known bug: most non-characters-outputting keys are not
+        // listed. I'm too lazy to list them all. Just use
+        // character-outputting keys (and modifiers) in sequences
+        // please! Or switch to a different input mode? It doesn't
+        // really make sense to use non-typing characters other
+        // than modifiers does it? Since those would probably be
+        // further away from the home row, so why use them? If one
+        // desired to fix this, a shorter list of keys would
+        // probably be the list of keys that **do** output
+        // characters than those that don't.
+        osc if osc.is_modifier() => 0,
+        osc if matches!(u16::from(osc), KEY_IGNORE_MIN..=KEY_IGNORE_MAX) => 0,
+        // A dead key types nothing until the next key composes with it. If the next key does
+        // not compose, both characters appear and one is left behind, which is the safer way
+        // to be wrong.
+        #[cfg(all(
+            target_os = "windows",
+            not(feature = "simulated_output"),
+            not(feature = "simulated_input")
+        ))]
+        osc if crate::oskbd::is_dead_key(osc) => 0,
+        _ => 1,
+    }
+}
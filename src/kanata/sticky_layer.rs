@@ -0,0 +1,142 @@
+use kanata_keyberon::layout::{KCoord, State};
+use kanata_parser::cfg::{BorrowedKLayout, FAKE_KEY_ROW, NORMAL_KEY_ROW};
+use kanata_parser::custom_action::CustomAction;
+use kanata_tcp_protocol::StickyLayerMode;
+
+/// Coordinate of the layer state added by `sticky-layer`. No key uses it, so keyberon never
+/// releases the state on its own.
+const STICKY_LAYER_COORD: KCoord = (FAKE_KEY_ROW, u16::MAX);
+
+/// State of the most recently pressed `sticky-layer` key.
+///
+/// - tap: latched until the next key press
+/// - hold past the timeout, or press another key while held: active while held
+/// - tap while latched: locked
+/// - tap while locked: off
+#[derive(Debug)]
+pub struct StickyLayerState {
+    pub layer: u16,
+    pub mode: StickyLayerMode,
+    /// Presses held for at least this many ticks are not taps.
+    timeout: u16,
+    /// The number of ticks the key has been held for.
+    held_ticks: u16,
+    /// Whether another key was pressed while the sticky key was held. The press then counts as a
+    /// hold regardless of duration.
+    interrupted: bool,
+    /// Whether a tap of the current press locks the layer.
+    lock_on_tap: bool,
+    /// Coordinates of pressed keys as of the previous tick, used to detect new presses.
+    pressed: Vec<KCoord>,
+}
+
+impl Default for StickyLayerState {
+    fn default() -> Self {
+        Self {
+            layer: 0,
+            mode: StickyLayerMode::Off,
+            timeout: 0,
+            held_ticks: 0,
+            interrupted: false,
+            lock_on_tap: false,
+            pressed: vec![],
+        }
+    }
+}
+
+impl StickyLayerState {
+    pub(crate) fn press(&mut self, layer: u16, timeout: u16, layout: &mut BorrowedKLayout) {
+        let same_layer = self.mode != StickyLayerMode::Off && self.layer == layer;
+        if same_layer && self.mode == StickyLayerMode::Locked {
+            log::debug!("sticky-layer {layer} unlocked");
+            self.deactivate(layout);
+            return;
+        }
+        self.lock_on_tap = same_layer && self.mode == StickyLayerMode::Latched;
+        self.deactivate(layout);
+        self.layer = layer;
+        self.timeout = timeout;
+        self.mode = StickyLayerMode::Held;
+        self.held_ticks = 0;
+        self.interrupted = false;
+        self.pressed = pressed_coords(layout);
+        let _ = layout.states.push(State::LayerModifier {
+            value: usize::from(layer),
+            coord: STICKY_LAYER_COORD,
+        });
+    }
+
+    pub(crate) fn release(&mut self, layer: u16, layout: &mut BorrowedKLayout) {
+        if self.mode != StickyLayerMode::Held || self.layer != layer {
+            return;
+        }
+        let tapped = !self.interrupted && self.held_ticks < self.timeout;
+        if !tapped {
+            self.deactivate(layout);
+        } else if self.lock_on_tap {
+            log::debug!("sticky-layer {layer} locked");
+            self.mode = StickyLayerMode::Locked;
+        } else {
+            log::debug!("sticky-layer {layer} latched");
+            self.mode = StickyLayerMode::Latched;
+            self.pressed = pressed_coords(layout);
+        }
+    }
+
+    /// Should be called every tick after custom actions have been handled, so that a key pressed
+    /// in the same tick as the sticky key is seen only after the sticky key press.
+    pub(crate) fn tick(&mut self, layout: &mut BorrowedKLayout) {
+        match self.mode {
+            StickyLayerMode::Off | StickyLayerMode::Locked => {}
+            StickyLayerMode::Held => {
+                self.held_ticks = self.held_ticks.saturating_add(1);
+                if self.new_key_pressed(layout) {
+                    self.interrupted = true;
+                }
+            }
+            StickyLayerMode::Latched => {
+                // The pressed key has already been resolved with the layer active, so removing
+                // the layer now does not affect its output.
+                if self.new_key_pressed(layout) {
+                    log::debug!("sticky-layer {} latch used", self.layer);
+                    self.deactivate(layout);
+                }
+            }
+        }
+    }
+
+    /// Whether time should keep advancing while no other keys are pressed.
+    pub(crate) fn needs_ticks(&self) -> bool {
+        self.mode == StickyLayerMode::Held
+    }
+
+    pub(crate) fn deactivate(&mut self, layout: &mut BorrowedKLayout) {
+        self.mode = StickyLayerMode::Off;
+        layout.states.retain(
+            |s| !matches!(s, State::LayerModifier { coord, .. } if *coord == STICKY_LAYER_COORD),
+        );
+    }
+
+    fn new_key_pressed(&mut self, layout: &BorrowedKLayout) -> bool {
+        let pressed = pressed_coords(layout);
+        let new_press = pressed.iter().any(|c| !self.pressed.contains(c));
+        self.pressed = pressed;
+        new_press
+    }
+}
+
+/// Coordinates of pressed physical keys, excluding `sticky-layer` keys.
+fn pressed_coords(layout: &BorrowedKLayout) -> Vec<KCoord> {
+    layout
+        .states
+        .iter()
+        .filter(|s| match s {
+            State::Custom { value, .. } => !value
+                .iter()
+                .any(|ac| matches!(ac, CustomAction::StickyLayer { .. })),
+            _ => true,
+        })
+        .filter_map(|s| s.coord())
+        .filter(|c| c.0 == NORMAL_KEY_ROW)
+        .collect()
+}
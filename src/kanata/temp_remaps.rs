@@ -0,0 +1,75 @@
+use crate::oskbd::{KeyEvent, KeyValue};
+use anyhow::{Result, bail};
+use kanata_parser::keys::OsCode;
+use web_time::{Duration, Instant};
+
+/// Maximum number of temporary remaps active at the same time.
+const MAX_TEMP_REMAPS: usize = 16;
+
+#[derive(Debug)]
+struct TempRemap {
+    from: OsCode,
+    to: OsCode,
+    expires_at: Instant,
+    /// Whether `to` was pressed and not yet released. The remap is kept until the release so
+    /// that `to` does not get stuck.
+    held: bool,
+}
+
+/// Remaps added by the `TempRemap` TCP command. A remapped input key bypasses the layers and
+/// outputs its target key until the remap expires.
+#[derive(Debug, Default)]
+pub struct TempRemaps {
+    remaps: Vec<TempRemap>,
+}
+
+impl TempRemaps {
+    /// Remap `from` to `to` for the given duration, replacing an existing remap of `from`.
+    pub fn add(&mut self, from: OsCode, to: OsCode, duration: Duration) -> Result<()> {
+        let expires_at = Instant::now() + duration;
+        if let Some(remap) = self.remaps.iter_mut().find(|r| r.from == from) {
+            if remap.held && remap.to != to {
+                bail!("cannot change the remap of {from:?} while it is held");
+            }
+            remap.to = to;
+            remap.expires_at = expires_at;
+            return Ok(());
+        }
+        if self.remaps.len() >= MAX_TEMP_REMAPS {
+            bail!("too many temporary remaps, at most {MAX_TEMP_REMAPS} can be active");
+        }
+        self.remaps.push(TempRemap {
+            from,
+            to,
+            expires_at,
+            held: false,
+        });
+        Ok(())
+    }
+
+    /// Remove the expired remaps whose target key is not held.
+    pub(crate) fn expire(&mut self) {
+        if self.remaps.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        self.remaps.retain(|r| {
+            let keep = r.held || r.expires_at > now;
+            if !keep {
+                log::info!("temporary remap of {:?} to {:?} expired", r.from, r.to);
+            }
+            keep
+        });
+    }
+
+    /// Returns the key to output instead of the event's key, if it is remapped.
+    pub(crate) fn remap(&mut self, event: &KeyEvent) -> Option<OsCode> {
+        let remap = self.remaps.iter_mut().find(|r| r.from == event.code)?;
+        match event.value {
+            KeyValue::Press => remap.held = true,
+            KeyValue::Release => remap.held = false,
+            _ => {}
+        }
+        Some(remap.to)
+    }
+}
@@ -0,0 +1,56 @@
+use kanata_parser::cfg::BorrowedKLayout;
+use std::collections::VecDeque;
+
+/// Longest tracked interval between presses. Longer pauses are not typing; they count as this
+/// interval so that kanata can stop counting and block while waiting for input.
+const MAX_INTERVAL_MS: u16 = 1000;
+
+/// Rolling average of the intervals between recent physical key presses. The average is stored in
+/// the layout, where it scales the timeouts of `tap-hold-adaptive` actions.
+#[derive(Debug, Default)]
+pub struct TypingCadence {
+    /// Number of intervals in the average. Zero disables tracking.
+    window: usize,
+    intervals: VecDeque<u16>,
+    /// Milliseconds since the last press, or `None` before the first press.
+    ms_since_press: Option<u16>,
+}
+
+impl TypingCadence {
+    pub(crate) fn new(window: u16) -> Self {
+        Self {
+            window: window.into(),
+            intervals: VecDeque::with_capacity(window.into()),
+            ms_since_press: None,
+        }
+    }
+
+    /// Whether the time since the last press still needs to be counted.
+    pub(crate) fn is_counting(&self) -> bool {
+        self.window > 0 && self.ms_since_press.is_some_and(|ms| ms < MAX_INTERVAL_MS)
+    }
+
+    pub(crate) fn tick(&mut self, ms_elapsed: u16) {
+        if let Some(ms) = self.ms_since_press.as_mut() {
+            *ms = ms.saturating_add(ms_elapsed).min(MAX_INTERVAL_MS);
+        }
+    }
+
+    /// Record a physical key press and update the layout's typing interval.
+    pub(crate) fn press(&mut self, layout: &mut BorrowedKLayout) {
+        if self.window == 0 {
+            return;
+        }
+        let Some(interval) = self.ms_since_press.replace(0) else {
+            return;
+        };
+        if self.intervals.len() == self.window {
+            self.intervals.pop_front();
+        }
+        self.intervals.push_back(interval);
+        let average = self.intervals.iter().map(|&ms| u32::from(ms)).sum::<u32>()
+            / self.intervals.len() as u32;
+        log::debug!("average typing interval: {average}ms");
+        layout.typing_interval = Some(average as u16);
+    }
+}
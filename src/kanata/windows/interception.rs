@@ -68,7 +68,7 @@ impl Kanata {
                                 false => KeyValue::Press,
                                 true => KeyValue::Release,
                             };
-                            KeyEvent { code, value }
+                            KeyEvent::new(code, value)
                         }
                         ic::Stroke::Mouse {
                             state,
@@ -104,7 +104,14 @@ impl Kanata {
                             }
                         }
                     };
+                    key_event.device = Some(crate::oskbd::intern_device_name(&format!(
+                        "interception device {dev}"
+                    )));
+                    // Interception numbers devices from 1, so 0 still means unknown.
+                    key_event.device_id = dev as u32;
+                    check_for_panic_combo(key_event.code, key_event.value != KeyValue::Release);
                     check_for_exit(&key_event);
+                    crate::tcp_server::monitor_key_event(&key_event);
                     if !MAPPED_KEYS.lock().contains(&key_event.code) {
                         log::debug!("{key_event:?} is not mapped");
                         intrcptn.send(dev, &strokes[i..i + 1]);
@@ -191,55 +198,25 @@ fn is_device_interceptable(
 }
 fn mouse_state_to_event(state: ic::MouseState, rolling: i16) -> Option<KeyEvent> {
     if state.contains(ic::MouseState::RIGHT_BUTTON_DOWN) {
-        Some(KeyEvent {
-            code: OsCode::BTN_RIGHT,
-            value: KeyValue::Press,
-        })
+        Some(KeyEvent::new(OsCode::BTN_RIGHT, KeyValue::Press))
     } else if state.contains(ic::MouseState::RIGHT_BUTTON_UP) {
-        Some(KeyEvent {
-            code: OsCode::BTN_RIGHT,
-            value: KeyValue::Release,
-        })
+        Some(KeyEvent::new(OsCode::BTN_RIGHT, KeyValue::Release))
     } else if state.contains(ic::MouseState::LEFT_BUTTON_DOWN) {
-        Some(KeyEvent {
-            code: OsCode::BTN_LEFT,
-            value: KeyValue::Press,
-        })
+        Some(KeyEvent::new(OsCode::BTN_LEFT, KeyValue::Press))
     } else if state.contains(ic::MouseState::LEFT_BUTTON_UP) {
-        Some(KeyEvent {
-            code: OsCode::BTN_LEFT,
-            value: KeyValue::Release,
-        })
+        Some(KeyEvent::new(OsCode::BTN_LEFT, KeyValue::Release))
     } else if state.contains(ic::MouseState::MIDDLE_BUTTON_DOWN) {
-        Some(KeyEvent {
-            code: OsCode::BTN_MIDDLE,
-            value: KeyValue::Press,
-        })
+        Some(KeyEvent::new(OsCode::BTN_MIDDLE, KeyValue::Press))
     } else if state.contains(ic::MouseState::MIDDLE_BUTTON_UP) {
-        Some(KeyEvent {
-            code: OsCode::BTN_MIDDLE,
-            value: KeyValue::Release,
-        })
+        Some(KeyEvent::new(OsCode::BTN_MIDDLE, KeyValue::Release))
     } else if state.contains(ic::MouseState::BUTTON_4_DOWN) {
-        Some(KeyEvent {
-            code: OsCode::BTN_SIDE,
-            value: KeyValue::Press,
-        })
+        Some(KeyEvent::new(OsCode::BTN_SIDE, KeyValue::Press))
     } else if state.contains(ic::MouseState::BUTTON_4_UP) {
-        Some(KeyEvent {
-            code: OsCode::BTN_SIDE,
-            value: KeyValue::Release,
-        })
+        Some(KeyEvent::new(OsCode::BTN_SIDE, KeyValue::Release))
     } else if state.contains(ic::MouseState::BUTTON_5_DOWN) {
-        Some(KeyEvent {
-            code: OsCode::BTN_EXTRA,
-            value: KeyValue::Press,
-        })
+        Some(KeyEvent::new(OsCode::BTN_EXTRA, KeyValue::Press))
     } else if state.contains(ic::MouseState::BUTTON_5_UP) {
-        Some(KeyEvent {
-            code: OsCode::BTN_EXTRA,
-            value: KeyValue::Release,
-        })
+        Some(KeyEvent::new(OsCode::BTN_EXTRA, KeyValue::Release))
     } else if state.contains(ic::MouseState::WHEEL) {
         let osc = if rolling >= 0 {
             OsCode::MouseWheelUp
@@ -247,10 +224,7 @@ fn mouse_state_to_event(state: ic::MouseState, rolling: i16) -> Option<KeyEvent>
             OsCode::MouseWheelDown
         };
         if MAPPED_KEYS.lock().contains(&osc) {
-            Some(KeyEvent {
-                code: osc,
-                value: KeyValue::Tap,
-            })
+            Some(KeyEvent::new(osc, KeyValue::Tap))
         } else {
             None
         }
@@ -261,10 +235,7 @@ fn mouse_state_to_event(state: ic::MouseState, rolling: i16) -> Option<KeyEvent>
             OsCode::MouseWheelLeft
         };
         if MAPPED_KEYS.lock().contains(&osc) {
-            Some(KeyEvent {
-                code: osc,
-                value: KeyValue::Tap,
-            })
+            Some(KeyEvent::new(osc, KeyValue::Tap))
         } else {
             None
         }
@@ -28,22 +28,37 @@ impl Kanata {
         let (preprocess_tx, preprocess_rx) = sync_channel(100);
         start_event_preprocessor(preprocess_rx, tx);
         let kb_preprocess_tx = preprocess_tx.clone();
+        let mut dead_keys = DeadKeys::default();
 
         // This callback should return `false` if the input event is **not** handled by the
         // callback and `true` if the input event **is** handled by the callback. Returning false
         // informs the callback caller that the input event should be handed back to the OS for
         // normal processing.
         let _kbhook = KeyboardHook::set_input_cb(move |input_event| {
+            if let Some(osc) = OsCode::from_u16(input_event.code as u16) {
+                check_for_panic_combo(osc, !input_event.up);
+            }
             let mut key_event = match KeyEvent::try_from(input_event) {
                 Ok(ev) => ev,
                 _ => return false,
             };
 
             check_for_exit(&key_event);
+            crate::tcp_server::monitor_key_event(&key_event);
             let oscode = key_event.code;
             if !MAPPED_KEYS.lock().contains(&oscode) {
                 return false;
             }
+            if TRACK_DEAD_KEYS.load(std::sync::atomic::Ordering::Relaxed) {
+                // The extended key prefix is not part of the scan code for the layout.
+                let scancode = u32::from(input_event.scancode.unwrap_or(0) & 0xFF);
+                if dead_keys.passes_through(oscode, key_event.value, |_| {
+                    crate::oskbd::is_dead_key(input_event.code, scancode)
+                }) {
+                    log::debug!("passing through dead key input {oscode:?}");
+                    return false;
+                }
+            }
 
             // Unlike Linux, Windows does not use a separate value for repeat. However, our code
             // needs to differentiate between initial press and repeat press.
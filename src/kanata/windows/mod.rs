@@ -180,7 +180,8 @@ impl Kanata {
 /// Only states in the normal key row are cleared, since those are
 /// the states that might be stuck. A real use case might be to have
 /// a fake key pressed for a long period of time, so make sure those
-/// are not cleared.
+/// are not cleared. Keys held by press-key are released though, since
+/// a missed release-key would otherwise leave them stuck.
 #[cfg(all(not(feature = "interception_driver"), target_os = "windows"))]
 pub fn clear_states_from_inactivity(
     k: &mut parking_lot::MutexGuard<Kanata>,
@@ -193,6 +194,10 @@ pub fn clear_states_from_inactivity(
     {
         *idle_clear_happened = true;
         log::debug!("clearing keyberon normal key states due to inactivity");
+        if !k.manually_held_keys.is_empty() {
+            log::debug!("releasing keys held by press-key due to inactivity");
+            k.manually_held_keys.clear();
+        }
         let layout = k.layout.bm();
         release_normalkey_states(layout);
         let now = web_time::Instant::now();
@@ -75,13 +75,30 @@ mod cli {
             version = 2,
             "[hour]:[minute]:[second].[subsecond digits:4]"
         ));
-        CombinedLogger::init(vec![TermLogger::new(
-            log_lvl,
-            log_cfg.build(),
-            TerminalMode::Mixed,
-            ColorChoice::AlwaysAnsi,
-        )])
-        .expect("logger can init");
+        let log_cfg = log_cfg.build();
+        // The loggers accept all levels and the level is filtered by `log::set_max_level`, so
+        // that it can be changed at runtime with the `SetLogLevel` TCP command.
+        let mut loggers: Vec<Box<dyn SharedLogger>> = vec![];
+        if !args.log_file_only {
+            loggers.push(TermLogger::new(
+                LevelFilter::Trace,
+                log_cfg.clone(),
+                TerminalMode::Mixed,
+                ColorChoice::AlwaysAnsi,
+            ));
+        }
+        if let Some(log_file) = &args.log_file {
+            use main_lib::log_file::*;
+            let file = RotatingFile::new(
+                log_file,
+                args.log_max_size.unwrap_or(DEFAULT_LOG_MAX_SIZE),
+                args.log_keep.unwrap_or(DEFAULT_LOG_KEEP),
+            )
+            .map_err(|e| anyhow::anyhow!("could not open log file {}: {e}", log_file.display()))?;
+            loggers.push(WriteLogger::new(LevelFilter::Trace, log_cfg, file));
+        }
+        CombinedLogger::init(loggers).expect("logger can init");
+        log::set_max_level(log_lvl);
 
         log::info!("kanata v{} starting", env!("CARGO_PKG_VERSION"));
         #[cfg(all(not(feature = "interception_driver"), target_os = "windows"))]
@@ -104,25 +121,17 @@ mod cli {
 
         if args.check {
             log::info!("validating config only and exiting");
-            let status = if let Some(ref cfg_str) = config_string {
+            let valid = if let Some(ref cfg_str) = config_string {
                 use rustc_hash::FxHashMap;
-                match cfg::new_from_str(cfg_str, FxHashMap::default()) {
-                    Ok(_) => 0,
-                    Err(e) => {
-                        log::error!("{e:?}");
-                        1
-                    }
-                }
+                check_cfg(cfg::new_from_str(cfg_str, FxHashMap::default()))
             } else {
-                match cfg::new_from_file(&cfg_paths[0]) {
-                    Ok(_) => 0,
-                    Err(e) => {
-                        log::error!("{e:?}");
-                        1
-                    }
-                }
+                // Every file is checked, since all of them can be switched to by live reload.
+                cfg_paths.iter().fold(true, |valid, path| {
+                    log::info!("checking {}", path.display());
+                    check_cfg(cfg::new_from_file(path)) && valid
+                })
             };
-            std::process::exit(status);
+            std::process::exit(if valid { 0 } else { 1 });
         }
 
         #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -132,15 +141,34 @@ mod cli {
             oskbd::WAIT_DEVICE_MS.store(wait, Ordering::SeqCst);
         }
 
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+        if args.wait_for_device {
+            oskbd::WAIT_FOR_DEVICE.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+
+        #[cfg(target_os = "macos")]
+        if let Some(backend) = args.output_backend {
+            oskbd::set_output_backend(backend);
+        }
+
         if args.log_layer_changes {
             cfg_forced::force_log_layer_changes(true);
         }
 
+        kanata::SELF_TEST.store(args.self_test, std::sync::atomic::Ordering::SeqCst);
+
         // Set emergency exit code from CLI args
         kanata::EMERGENCY_EXIT_CODE.store(
             args.emergency_exit_code,
             std::sync::atomic::Ordering::SeqCst,
         );
+        if let Some(combo) = args.panic_combo {
+            log::info!(
+                "panic combo enabled: {:?}",
+                combo.keys().collect::<Vec<_>>()
+            );
+            kanata::set_panic_combo(combo);
+        }
 
         Ok((
             ValidatedArgs {
@@ -155,6 +183,28 @@ mod cli {
         ))
     }
 
+    /// Logs the outcome of parsing a configuration for `--check`. Returns whether it is valid.
+    fn check_cfg(cfg: cfg::MResult<cfg::Cfg>) -> bool {
+        let cfg = match cfg {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                log::error!("{e:?}");
+                return false;
+            }
+        };
+        let layers = cfg
+            .layer_info
+            .iter()
+            .map(|l| l.name.as_str())
+            .collect::<Vec<_>>();
+        log::info!("{} layers: {}", layers.len(), layers.join(" "));
+        log::info!("{} aliases: {}", cfg.aliases.len(), cfg.aliases.join(" "));
+        for alias in cfg.unused_aliases.iter() {
+            log::warn!("alias {alias} is defined but never used");
+        }
+        true
+    }
+
     pub(crate) fn main_impl() -> Result<()> {
         let (args, config_string) = cli_init()?;
 
@@ -173,6 +223,12 @@ mod cli {
             std::thread::sleep(std::time::Duration::from_secs(2));
         }
 
+        // On macOS the event loop runs the self-test, so that a failure can enter recovery.
+        #[cfg(not(target_os = "macos"))]
+        if kanata::SELF_TEST.load(std::sync::atomic::Ordering::SeqCst) {
+            kanata_arc.lock().self_test()?;
+        }
+
         // Start a processing loop in another thread and run the event loop in this thread.
         //
         // The reason for two different event loops is that the "event loop" only listens for
@@ -181,24 +237,43 @@ mod cli {
 
         let (tx, rx) = std::sync::mpsc::sync_channel(100);
 
-        let (server, ntx, nrx) = if let Some(address) = {
+        #[cfg(feature = "tcp_server")]
+        let tcp_server_address = args
+            .tcp_server_address
+            .or_else(|| kanata_arc.lock().tcp_server_address.clone())
+            .map(|a| a.into_inner());
+        #[cfg(not(feature = "tcp_server"))]
+        let tcp_server_address = None::<std::net::SocketAddr>;
+        #[cfg(all(
+            feature = "tcp_server",
+            any(target_os = "linux", target_os = "android", target_os = "macos")
+        ))]
+        let unix_socket_path = kanata_arc.lock().unix_socket_path.clone();
+        #[cfg(not(all(
+            feature = "tcp_server",
+            any(target_os = "linux", target_os = "android", target_os = "macos")
+        )))]
+        let unix_socket_path = None::<std::path::PathBuf>;
+
+        let (server, ntx, nrx) = if tcp_server_address.is_some() || unix_socket_path.is_some() {
+            let mut server = TcpServer::new(tcp_server_address, unix_socket_path, tx.clone());
+            server.start(kanata_arc.clone());
+            let (ntx, nrx) = std::sync::mpsc::sync_channel(100);
             #[cfg(feature = "tcp_server")]
             {
-                args.tcp_server_address
+                tcp_server::set_notification_channel(Some(ntx.clone()));
+                // Monitored key events get their own channel and relay thread, so that they are
+                // the only notifications dropped when clients can not keep up.
+                let (mtx, mrx) = std::sync::mpsc::sync_channel(100);
+                tcp_server::set_key_monitor_channel(Some(mtx));
+                Kanata::start_notification_loop(mrx, server.connections.clone());
             }
-            #[cfg(not(feature = "tcp_server"))]
-            {
-                None::<SocketAddrWrapper>
-            }
-        } {
-            let mut server = TcpServer::new(address.into_inner(), tx.clone());
-            server.start(kanata_arc.clone());
-            let (ntx, nrx) = std::sync::mpsc::sync_channel(100);
             (Some(server), Some(ntx), Some(nrx))
         } else {
             (None, None, None)
         };
 
+        kanata::set_exit_wakeup_channel(tx.clone());
         Kanata::start_processing_loop(kanata_arc.clone(), rx, ntx, args.nodelay);
 
         if let (Some(server), Some(nrx)) = (server, nrx) {
@@ -218,6 +293,7 @@ pub fn main() -> Result<()> {
     let args = Args::parse();
     let no_wait = args.no_wait;
     let ret = cli::main_impl();
+    tcp_server::remove_unix_socket();
     if let Err(ref e) = ret {
         log::error!("{e}\n");
     }
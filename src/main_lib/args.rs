@@ -96,10 +96,56 @@ kanata.kbd in the current working directory and
     #[arg(short, long, verbatim_doc_comment)]
     pub wait_device_ms: Option<u64>,
 
-    /// Validate configuration file and exit
+    /// Start even if no matching input device is connected and grab one once
+    /// it is plugged in, instead of exiting. Useful at boot, when the
+    /// keyboard may show up after kanata has started.
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+    #[arg(long, verbatim_doc_comment)]
+    pub wait_for_device: bool,
+
+    /// How key events are sent to macOS: driverkit (the default) uses the
+    /// Karabiner DriverKit virtual keyboard, cgevent posts CGEvents and
+    /// works without the driver being installed.
+    #[cfg(target_os = "macos")]
+    #[arg(long, value_name = "driverkit|cgevent", verbatim_doc_comment)]
+    pub output_backend: Option<kanata_state_machine::oskbd::OutputBackend>,
+
+    /// Validate configuration file and exit. The keyboard is not grabbed.
+    /// Unused aliases are reported as warnings.
     #[arg(long, verbatim_doc_comment)]
     pub check: bool,
 
+    /// Press and release the defcfg self-test-key (f24 by default) on
+    /// startup to check that the output device accepts events, and exit with
+    /// an error if it does not. On macOS, a failure instead releases the input
+    /// devices until the DriverKit output recovers.
+    #[arg(long, verbatim_doc_comment)]
+    pub self_test: bool,
+
+    /// Also write logs to this file. The file is rotated once it reaches
+    /// --log-max-size bytes.
+    #[arg(long, value_name = "PATH", verbatim_doc_comment)]
+    pub log_file: Option<PathBuf>,
+
+    /// Maximum size in bytes of the log file before it is rotated.
+    /// The default is 10485760 (10 MiB).
+    #[arg(
+        long,
+        value_name = "BYTES",
+        requires = "log_file",
+        verbatim_doc_comment
+    )]
+    pub log_max_size: Option<u64>,
+
+    /// Number of rotated log files to keep, named <log-file>.1, <log-file>.2, ...
+    /// The default is 3.
+    #[arg(long, value_name = "N", requires = "log_file", verbatim_doc_comment)]
+    pub log_keep: Option<usize>,
+
+    /// Write logs only to --log-file instead of also to stderr.
+    #[arg(long, requires = "log_file", verbatim_doc_comment)]
+    pub log_file_only: bool,
+
     /// Log layer changes even if the configuration file has set the defcfg
     /// option to false. Useful if you are experimenting with a new
     /// configuration but want to default to no logging.
@@ -117,6 +163,13 @@ kanata.kbd in the current working directory and
     /// treat emergency exit as a failure and restart.
     #[arg(long, default_value = "0", verbatim_doc_comment)]
     pub emergency_exit_code: i32,
+
+    /// Keys that exit kanata when held together, e.g. lmet+q. The keys are
+    /// checked as they are read from the keyboard, before the configuration
+    /// is applied, so this works even if the configuration is broken or
+    /// kanata stops processing keys. Disabled by default.
+    #[arg(long, value_name = "KEY+KEY...", verbatim_doc_comment)]
+    pub panic_combo: Option<kanata_state_machine::kanata::PanicCombo>,
 }
 
 #[cfg(test)]
@@ -143,6 +196,17 @@ mod tests {
         assert!(args.nodelay);
     }
 
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn output_backend_flag() {
+        use kanata_state_machine::oskbd::OutputBackend;
+        let args = Args::try_parse_from(["kanata"]).unwrap();
+        assert_eq!(args.output_backend, None);
+        let args = Args::try_parse_from(["kanata", "--output-backend", "cgevent"]).unwrap();
+        assert_eq!(args.output_backend, Some(OutputBackend::CGEvent));
+        assert!(Args::try_parse_from(["kanata", "--output-backend", "uinput"]).is_err());
+    }
+
     #[test]
     fn emergency_exit_code_default() {
         let args = Args::try_parse_from(["kanata"]).unwrap();
@@ -169,4 +233,38 @@ mod tests {
         assert_eq!(args.emergency_exit_code, 1);
         assert!(args.no_wait);
     }
+
+    #[test]
+    fn panic_combo_arg() {
+        let args = Args::try_parse_from(["kanata"]).unwrap();
+        assert!(args.panic_combo.is_none());
+        let args = Args::try_parse_from(["kanata", "--panic-combo", "lctl+lalt+end"]).unwrap();
+        assert_eq!(args.panic_combo.unwrap().keys().count(), 3);
+        assert!(Args::try_parse_from(["kanata", "--panic-combo", "lctl+nokey"]).is_err());
+    }
+
+    #[test]
+    fn log_file_args() {
+        let args = Args::try_parse_from([
+            "kanata",
+            "--log-file",
+            "/tmp/kanata.log",
+            "--log-max-size",
+            "1000",
+            "--log-keep",
+            "5",
+            "--log-file-only",
+        ])
+        .unwrap();
+        assert_eq!(args.log_file, Some(PathBuf::from("/tmp/kanata.log")));
+        assert_eq!(args.log_max_size, Some(1000));
+        assert_eq!(args.log_keep, Some(5));
+        assert!(args.log_file_only);
+    }
+
+    #[test]
+    fn log_rotation_args_require_log_file() {
+        assert!(Args::try_parse_from(["kanata", "--log-keep", "5"]).is_err());
+        assert!(Args::try_parse_from(["kanata", "--log-file-only"]).is_err());
+    }
 }
@@ -0,0 +1,183 @@
+//! Size-based rotating log file used by `--log-file`.
+//!
+//! The writer is handed to `simplelog::WriteLogger`, which serializes all writes behind its own
+//! mutex, so rotation here never races between the event loop and processing threads.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub(crate) const DEFAULT_LOG_MAX_SIZE: u64 = 10 * 1024 * 1024;
+pub(crate) const DEFAULT_LOG_KEEP: usize = 3;
+
+pub(crate) struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    keep: usize,
+    /// `None` if the file could not be reopened after a failed rotation, in which case logs go
+    /// to stderr until a later rotation succeeds.
+    file: Option<File>,
+    size: u64,
+    /// Whether the last write ended a line. A log record may arrive in several writes, so
+    /// rotation only happens between lines.
+    at_line_start: bool,
+    /// Whether the last rotation failed, so that the failure is only reported once.
+    rotation_failed: bool,
+}
+
+impl RotatingFile {
+    /// Opens `path` for appending. Once the file has reached `max_size` bytes, it is renamed
+    /// to `<path>.1`, older files shift up by one, and anything beyond `<path>.<keep>` is deleted.
+    /// With `keep` of 0 the file is simply truncated.
+    pub(crate) fn new(path: &Path, max_size: u64, keep: usize) -> io::Result<Self> {
+        let file = open_append(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_owned(),
+            max_size,
+            keep,
+            file: Some(file),
+            size,
+            at_line_start: true,
+            rotation_failed: false,
+        })
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut p = self.path.clone().into_os_string();
+        p.push(format!(".{n}"));
+        p.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if let Some(file) = &mut self.file {
+            file.flush()?;
+        }
+        // The old handle must not be written to once the file may have been renamed.
+        self.file = None;
+        if self.keep > 0 {
+            let _ = std::fs::remove_file(self.rotated_path(self.keep));
+            for n in (1..self.keep).rev() {
+                let from = self.rotated_path(n);
+                if from.exists() {
+                    std::fs::rename(&from, self.rotated_path(n + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, self.rotated_path(1))?;
+            self.file = Some(open_append(&self.path)?);
+        } else {
+            self.file = Some(File::create(&self.path)?);
+        }
+        self.size = 0;
+        Ok(())
+    }
+
+    /// Keeps logging after a failed rotation: to the file at `path` if it can be reopened,
+    /// otherwise to stderr. Rotation is retried once another `max_size` bytes were written.
+    fn recover_from_failed_rotation(&mut self, e: io::Error) {
+        if self.file.is_none() {
+            self.file = open_append(&self.path).ok();
+        }
+        self.size = 0;
+        if self.rotation_failed {
+            return;
+        }
+        self.rotation_failed = true;
+        // This is the logger itself, so the failure can not be reported with `log`.
+        let path = self.path.display();
+        match self.file {
+            Some(_) => {
+                eprintln!("could not rotate log file {path}, continuing to write to it: {e}")
+            }
+            None => eprintln!("could not rotate log file {path}, logging to stderr instead: {e}"),
+        }
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.at_line_start && self.size >= self.max_size {
+            match self.rotate() {
+                Ok(()) => self.rotation_failed = false,
+                Err(e) => self.recover_from_failed_rotation(e),
+            }
+        }
+        let n = match &mut self.file {
+            Some(file) => file.write(buf)?,
+            None => io::stderr().write(buf)?,
+        };
+        self.size += n as u64;
+        if n > 0 {
+            self.at_line_start = buf[n - 1] == b'\n';
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => io::stderr().flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_and_keeps_n_files() {
+        let dir = std::env::temp_dir().join(format!("kanata-log-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("kanata.log");
+
+        let mut f = RotatingFile::new(&path, 10, 2).unwrap();
+        for line in ["aaaaaaaaaa\n", "bbbbbbbbbb\n", "cccccccccc\n", "ddd"] {
+            f.write_all(line.as_bytes()).unwrap();
+        }
+        // Completing a line never rotates mid-record.
+        f.write_all(b"ddddddd\n").unwrap();
+        f.flush().unwrap();
+
+        let read = |p: &Path| std::fs::read_to_string(p).unwrap();
+        assert_eq!(read(&path), "dddddddddd\n");
+        assert_eq!(read(&dir.join("kanata.log.1")), "cccccccccc\n");
+        assert_eq!(read(&dir.join("kanata.log.2")), "bbbbbbbbbb\n");
+        assert!(!dir.join("kanata.log.3").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn keeps_writing_to_the_file_when_rotation_fails() {
+        let dir = std::env::temp_dir().join(format!("kanata-log-fail-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("kanata.log");
+        // A non-empty directory in place of the rotated file makes the rename fail.
+        std::fs::create_dir_all(dir.join("kanata.log.1/blocker")).unwrap();
+
+        let mut f = RotatingFile::new(&path, 10, 1).unwrap();
+        for line in ["aaaaaaaaaa\n", "bbbbbbbbbb\n", "cccccccccc\n"] {
+            f.write_all(line.as_bytes()).unwrap();
+        }
+        f.flush().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "aaaaaaaaaa\nbbbbbbbbbb\ncccccccccc\n"
+        );
+
+        // Rotation is retried and succeeds once the obstacle is gone.
+        std::fs::remove_dir_all(dir.join("kanata.log.1")).unwrap();
+        f.write_all(b"dddddddddd\n").unwrap();
+        f.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "dddddddddd\n");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
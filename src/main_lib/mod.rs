@@ -1,4 +1,6 @@
 pub(crate) mod args;
+#[cfg(not(feature = "gui"))]
+pub(crate) mod log_file;
 
 #[cfg(all(target_os = "windows", feature = "gui"))]
 pub(crate) mod win_gui;
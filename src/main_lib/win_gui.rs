@@ -85,21 +85,26 @@ fn cli_init() -> Result<ValidatedArgs> {
         version = 2,
         "[hour]:[minute]:[second].[subsecond digits:4]"
     ));
+    // Filter by `log::set_max_level` so that `SetLogLevel` can change the level at runtime.
     if *IS_TERM {
         CombinedLogger::init(vec![
             TermLogger::new(
-                log_lvl,
+                LevelFilter::Trace,
                 log_cfg.build(),
                 TerminalMode::Mixed,
                 ColorChoice::AlwaysAnsi,
             ),
-            log_win::windbg_simple_combo(log_lvl, noti_lvl),
+            log_win::windbg_simple_combo(LevelFilter::Trace, noti_lvl),
         ])
         .expect("logger can init");
     } else {
-        CombinedLogger::init(vec![log_win::windbg_simple_combo(log_lvl, noti_lvl)])
-            .expect("logger can init");
+        CombinedLogger::init(vec![log_win::windbg_simple_combo(
+            LevelFilter::Trace,
+            noti_lvl,
+        )])
+        .expect("logger can init");
     }
+    log::set_max_level(log_lvl);
     log::info!("kanata v{} starting", env!("CARGO_PKG_VERSION"));
     #[cfg(all(not(feature = "interception_driver"), target_os = "windows"))]
     log::info!("using LLHOOK+SendInput for keyboard IO");
@@ -164,15 +169,25 @@ fn main_impl() -> Result<()> {
         #[cfg(feature = "tcp_server")]
         {
             args.tcp_server_address
+                .or_else(|| kanata_arc.lock().tcp_server_address.clone())
         }
         #[cfg(not(feature = "tcp_server"))]
         {
             None::<SocketAddrWrapper>
         }
     } {
-        let mut server = TcpServer::new(address.into_inner(), tx.clone());
+        let mut server = TcpServer::new(Some(address.into_inner()), None, tx.clone());
         server.start(kanata_arc.clone());
         let (ntx, nrx) = std::sync::mpsc::sync_channel(100);
+        #[cfg(feature = "tcp_server")]
+        {
+            tcp_server::set_notification_channel(Some(ntx.clone()));
+            // Monitored key events get their own channel and relay thread, so that they are
+            // the only notifications dropped when clients can not keep up.
+            let (mtx, mrx) = std::sync::mpsc::sync_channel(100);
+            tcp_server::set_key_monitor_channel(Some(mtx));
+            Kanata::start_notification_loop(mrx, server.connections.clone());
+        }
         (Some(server), Some(ntx), Some(nrx))
     } else {
         (None, None, None)
@@ -196,6 +211,7 @@ fn main_impl() -> Result<()> {
     if GUI_EXIT_TX.set(gui_exit_tx).is_err() {
         warn!("Someone else set our ‘GUI_EXIT_TX’");
     };
+    kanata::set_exit_wakeup_channel(tx.clone());
     Kanata::start_processing_loop(kanata_arc.clone(), rx, ntx, args.nodelay);
 
     if let (Some(server), Some(nrx)) = (server, nrx) {
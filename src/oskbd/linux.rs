@@ -18,7 +18,7 @@ use std::fs;
 use std::io;
 use std::os::unix::io::AsRawFd;
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::thread;
 
 use super::*;
@@ -29,7 +29,8 @@ use kanata_parser::custom_action::*;
 use kanata_parser::keys::*;
 
 pub struct KbdIn {
-    devices: HashMap<Token, (Device, String)>,
+    /// Registered devices with their path and their name for [`KeyEvent::device`].
+    devices: HashMap<Token, (Device, String, &'static str)>,
     /// Some(_) if devices are explicitly listed, otherwise None.
     missing_device_paths: Option<Vec<String>>,
     poll: Poll,
@@ -47,6 +48,13 @@ const INOTIFY_TOKEN: Token = Token(INOTIFY_TOKEN_VALUE);
 
 pub static WAIT_DEVICE_MS: AtomicU64 = AtomicU64::new(200);
 
+/// Set by `--wait-for-device`: start without any matching device instead of failing, and seize
+/// devices as they are hot-plugged.
+pub static WAIT_FOR_DEVICE: AtomicBool = AtomicBool::new(false);
+
+/// How often to log that kanata is still waiting while no device is registered.
+const WAITING_LOG_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
 impl KbdIn {
     pub fn new(
         dev_paths: &[String],
@@ -72,7 +80,7 @@ impl KbdIn {
             )
         };
         if devices.is_empty() {
-            if continue_if_no_devices {
+            if continue_if_no_devices || WAIT_FOR_DEVICE.load(Ordering::SeqCst) {
                 log::warn!("no keyboard devices found; kanata is waiting");
             } else {
                 return Err(io::Error::new(
@@ -134,29 +142,53 @@ impl KbdIn {
         self.poll
             .registry()
             .register(&mut SourceFd(&fd), tok, Interest::READABLE)?;
-        self.devices.insert(tok, (dev, path));
+        let name = intern_device_name(&format!("{path} ({})", dev.name().unwrap_or("")));
+        self.devices.insert(tok, (dev, path, name));
         Ok(())
     }
 
-    pub fn read(&mut self) -> Result<Vec<InputEvent>, io::Error> {
+    /// Names of the registered, and thus grabbed, devices, sorted. Devices without a name are
+    /// listed by their path.
+    pub fn device_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .devices
+            .values()
+            .map(|(dev, path, _)| dev.name().unwrap_or(path).to_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Returns the events of the devices that are ready, together with the name and id of the
+    /// device that sent each event.
+    pub fn read(&mut self) -> Result<Vec<(InputEvent, &'static str, u32)>, io::Error> {
         let mut input_events = vec![];
         loop {
             log::trace!("polling");
 
-            if let Err(e) = self.poll.poll(&mut self.events, None) {
+            // While waiting for a device to show up, wake up now and then to say so.
+            let timeout = self.devices.is_empty().then_some(WAITING_LOG_INTERVAL);
+            if let Err(e) = self.poll.poll(&mut self.events, timeout) {
                 log::error!("failed poll: {:?}", e);
                 return Ok(vec![]);
             }
+            if self.events.is_empty() {
+                log::info!("still waiting for a matching keyboard device");
+                continue;
+            }
 
             const EVENT_LIMIT: usize = 48;
 
             let mut do_rediscover = false;
             for event in &self.events {
-                if let Some((device, _)) = self.devices.get_mut(&event.token()) {
+                if let Some((device, _, name)) = self.devices.get_mut(&event.token()) {
+                    let name = *name;
+                    // Tokens of devices start at 1, so 0 still means unknown.
+                    let id = event.token().0 as u32;
                     if let Err(e) = device.fetch_events().map(|evs| {
                         evs.into_iter()
                             .take(EVENT_LIMIT)
-                            .for_each(|ev| input_events.push(ev))
+                            .for_each(|ev| input_events.push((ev, name, id)))
                     }) {
                         // Currently the kind() is uncategorized... not helpful, need to match
                         // on os error. code 19 is ENODEV, "no such device".
@@ -165,7 +197,7 @@ impl KbdIn {
                                 self.poll
                                     .registry()
                                     .deregister(&mut SourceFd(&device.as_raw_fd()))?;
-                                if let Some((_, path)) = self.devices.remove(&event.token()) {
+                                if let Some((_, path, _)) = self.devices.remove(&event.token()) {
                                     log::warn!("removing kbd device: {path}");
                                     if let Some(ref mut missing) = self.missing_device_paths {
                                         missing.push(path);
@@ -241,7 +273,7 @@ impl KbdIn {
                 if !self
                     .devices
                     .values()
-                    .any(|(_, registered_path)| &path == registered_path)
+                    .any(|(_, registered_path, _)| &path == registered_path)
                 {
                     self.register_device(dev, path)
                 } else {
@@ -325,10 +357,11 @@ impl TryFrom<InputEvent> for KeyEvent {
     fn try_from(item: InputEvent) -> Result<Self, Self::Error> {
         use OsCode::*;
         match item.destructure() {
-            evdev::EventSummary::Key(_, k, _) => Ok(Self {
-                code: OsCode::from_u16(k.0).ok_or(())?,
-                value: KeyValue::from(item.value()),
-            }),
+            evdev::EventSummary::Key(_, k, _) => Ok(Self::with_scancode(
+                OsCode::from_u16(k.0).ok_or(())?,
+                KeyValue::from(item.value()),
+                k.0,
+            )),
             evdev::EventSummary::RelativeAxis(_, axis_type, _) => {
                 let dist = item.value();
                 let code: OsCode = match axis_type {
@@ -348,10 +381,7 @@ impl TryFrom<InputEvent> for KeyEvent {
                     }
                     _ => return Err(()),
                 };
-                Ok(KeyEvent {
-                    code,
-                    value: KeyValue::Tap,
-                })
+                Ok(KeyEvent::new(code, KeyValue::Tap))
             }
             _ => Err(()),
         }
@@ -374,6 +404,7 @@ pub struct KbdOut {
     raw_buf: Vec<InputEvent>,
     pub unicode_termination: Cell<UnicodeTermination>,
     pub unicode_u_code: Cell<OsCode>,
+    pub rate_limiter: OutputRateLimiter,
 }
 
 #[cfg(all(not(feature = "simulated_output"), not(feature = "passthru_ahk")))]
@@ -442,6 +473,8 @@ impl KbdOut {
 
             // historically was the only option, so make KEY_U the default
             unicode_u_code: Cell::new(OsCode::KEY_U),
+
+            rate_limiter: OutputRateLimiter::default(),
         })
     }
 
@@ -499,6 +532,19 @@ impl KbdOut {
         Ok(())
     }
 
+    /// Writes a key using the evdev code that it was received with.
+    pub fn write_key_scancode(
+        &mut self,
+        _key: OsCode,
+        value: KeyValue,
+        scancode: u16,
+    ) -> Result<(), io::Error> {
+        let event = InputEvent::new(EventType::KEY.0, scancode, value as i32);
+        log::debug!("send to uinput: {:?}", event);
+        self.device.emit(&[event])?;
+        Ok(())
+    }
+
     pub fn write_code(&mut self, code: u32, value: KeyValue) -> Result<(), io::Error> {
         let event = InputEvent::new(EventType::KEY.0, code as u16, value as i32);
         self.device.emit(&[event])?;
@@ -627,6 +673,20 @@ impl KbdOut {
         }
     }
 
+    /// Smooth scrolling is the regular hi-res scroll on Linux. The accumulated `REL_WHEEL` notches
+    /// keep the aggregate correct for applications that ignore `REL_WHEEL_HI_RES`.
+    pub fn scroll_fine(
+        &mut self,
+        direction: MWheelDirection,
+        hi_res_distance: u16,
+        phase: ScrollPhase,
+    ) -> Result<(), io::Error> {
+        match phase {
+            ScrollPhase::Ended => Ok(()),
+            ScrollPhase::Began | ScrollPhase::Changed => self.scroll(direction, hi_res_distance),
+        }
+    }
+
     pub fn move_mouse(&mut self, mv: CalculatedMouseMove) -> Result<(), io::Error> {
         let (axis, distance) = match mv.direction {
             MoveDirection::Up => (RelativeAxisCode::REL_Y, -i32::from(mv.distance)),
@@ -657,6 +717,18 @@ impl KbdOut {
         );
         Ok(())
     }
+
+    pub fn set_mouse_pct(
+        &mut self,
+        _x: u8,
+        _y: u8,
+        _monitor: MonitorSelector,
+    ) -> Result<(), io::Error> {
+        log::warn!(
+            "setmouse-pct does not work in Linux yet. Maybe try out warpd:\n\thttps://github.com/rvaiya/warpd"
+        );
+        Ok(())
+    }
 }
 
 fn devices_from_input_paths(
@@ -762,12 +834,14 @@ fn handle_signals(symlink: Option<Symlink>) {
             match signal {
                 SIGINT | SIGTERM => {
                     drop(symlink);
+                    crate::tcp_server::remove_unix_socket();
                     signal_hook::low_level::emulate_default_handler(signal)
                         .expect("run original sighandlers");
                     unreachable!();
                 }
                 SIGTSTP => {
                     drop(symlink);
+                    crate::tcp_server::remove_unix_socket();
                     log::warn!("got SIGTSTP, exiting instead of pausing so keyboards don't hang");
                     std::process::exit(SIGTSTP);
                 }
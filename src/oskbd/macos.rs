@@ -13,7 +13,9 @@ use crate::oskbd::KeyEvent;
 use anyhow::anyhow;
 use core_graphics::base::CGFloat;
 use core_graphics::display::{CGDisplay, CGPoint};
-use core_graphics::event::{CGEvent, CGEventTapLocation, CGEventType, CGMouseButton, EventField};
+use core_graphics::event::{
+    CGEvent, CGEventFlags, CGEventTapLocation, CGEventType, CGKeyCode, CGMouseButton, EventField,
+};
 use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
 use kanata_parser::custom_action::*;
 use kanata_parser::keys::*;
@@ -24,6 +26,61 @@ use std::convert::TryFrom;
 use std::fmt;
 use std::io;
 use std::io::Error;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Mechanism used to send key events to the OS, selected with `--output-backend`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputBackend {
+    /// The Karabiner DriverKit virtual keyboard.
+    DriverKit,
+    /// Events posted with `CGEventPost`, which does not need a virtual keyboard.
+    CGEvent,
+}
+
+impl FromStr for OutputBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "driverkit" => Ok(Self::DriverKit),
+            "cgevent" => Ok(Self::CGEvent),
+            _ => Err(format!(
+                "unknown output backend: {s}, expected driverkit or cgevent"
+            )),
+        }
+    }
+}
+
+/// Set by `--wait-for-device`: instead of failing when no device matches, wait for one to be
+/// connected.
+pub static WAIT_FOR_DEVICE: AtomicBool = AtomicBool::new(false);
+const WAIT_FOR_DEVICE_POLL: std::time::Duration = std::time::Duration::from_secs(1);
+/// Log that kanata is still waiting every this many polls.
+const WAITING_LOG_POLLS: u32 = 30;
+
+static OUTPUT_BACKEND: OnceLock<OutputBackend> = OnceLock::new();
+
+/// Selects the output backend. Must be called before kanata is created to have an effect.
+pub fn set_output_backend(backend: OutputBackend) {
+    if OUTPUT_BACKEND.set(backend).is_err() {
+        log::warn!("output backend was already selected, ignoring {backend:?}");
+    }
+}
+
+pub fn output_backend() -> OutputBackend {
+    *OUTPUT_BACKEND.get_or_init(|| OutputBackend::DriverKit)
+}
+
+/// Whether the selected output backend can currently deliver events. Only the DriverKit virtual
+/// keyboard can become unavailable; CGEvent posting is always ready.
+pub fn output_ready() -> bool {
+    match output_backend() {
+        OutputBackend::DriverKit => is_sink_ready(),
+        OutputBackend::CGEvent => true,
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct InputEvent {
@@ -54,6 +111,8 @@ impl From<InputEvent> for DKEvent {
 
 pub struct KbdIn {
     grabbed: bool,
+    /// Names of the seized devices.
+    device_names: Vec<String>,
 }
 
 impl Drop for KbdIn {
@@ -69,12 +128,18 @@ impl KbdIn {
         include_names: Option<Vec<String>>,
         exclude_names: Option<Vec<String>>,
     ) -> Result<Self, anyhow::Error> {
-        if !driver_activated() {
+        let uses_driverkit_output = output_backend() == OutputBackend::DriverKit;
+        if uses_driverkit_output && !driver_activated() {
             return Err(anyhow!(
-                "Karabiner-VirtualHIDDevice driver is not activated."
+                "Karabiner-VirtualHIDDevice driver is not activated. \
+                 Use --output-backend cgevent to run without it."
             ));
         }
 
+        if WAIT_FOR_DEVICE.load(Ordering::SeqCst) {
+            wait_for_matching_device(include_names.as_deref(), exclude_names.as_deref());
+        }
+
         // Based on the definition of include and exclude names, they should never be used together.
         // Kanata config parser should probably enforce this.
         let device_names = if let Some(included_names) = include_names {
@@ -104,6 +169,27 @@ impl KbdIn {
 
         if !device_names.is_empty() || register_device("") {
             if grab() {
+                // An empty name registers every device except the Karabiner virtual keyboard.
+                let device_names = match device_names.is_empty() {
+                    true => fetch_devices()
+                        .iter()
+                        .filter(|k| !k.product_key.to_lowercase().contains("karabiner"))
+                        .map(|k| {
+                            if k.product_key.trim().is_empty() {
+                                format!("{:x}", k.hash)
+                            } else {
+                                k.product_key.clone()
+                            }
+                        })
+                        .collect(),
+                    false => device_names,
+                };
+                if !uses_driverkit_output {
+                    return Ok(Self {
+                        grabbed: true,
+                        device_names,
+                    });
+                }
                 // Wait for the DriverKit virtual keyboard to become ready.
                 // The pqrs client connects asynchronously; give it time.
                 let mut ready = false;
@@ -126,7 +212,10 @@ impl KbdIn {
                          Key output may fail until the daemon connects."
                     );
                 }
-                Ok(Self { grabbed: true })
+                Ok(Self {
+                    grabbed: true,
+                    device_names,
+                })
             } else {
                 Err(anyhow!("grab failed"))
             }
@@ -181,6 +270,39 @@ impl KbdIn {
     pub fn is_grabbed(&self) -> bool {
         self.grabbed
     }
+
+    /// Names of the seized devices.
+    pub fn device_names(&self) -> &[String] {
+        &self.device_names
+    }
+}
+
+/// Blocks until a device that kanata would register is connected. There is no hot-plug
+/// notification on this path, so the device list is polled.
+fn wait_for_matching_device(include_names: Option<&[String]>, exclude_names: Option<&[String]>) {
+    let matches = || {
+        if let Some(included_names) = include_names {
+            return included_names
+                .iter()
+                .any(|n| !n.trim().is_empty() && device_matches(n));
+        }
+        // The Karabiner virtual keyboard is always listed but is never registered.
+        fetch_devices().iter().any(|k| {
+            !k.product_key.to_lowercase().contains("karabiner")
+                && !exclude_names.is_some_and(|ns| ns.iter().any(|n| *k == n.as_str()))
+        })
+    };
+    let mut polls = 0u32;
+    while !matches() {
+        if polls % WAITING_LOG_POLLS == 0 {
+            log::warn!("no matching keyboard device found; kanata is waiting");
+        }
+        polls = polls.wrapping_add(1);
+        std::thread::sleep(WAIT_FOR_DEVICE_POLL);
+    }
+    if polls > 0 {
+        log::info!("matching keyboard device found");
+    }
 }
 
 fn validate_and_register_devices(include_names: Vec<String>) -> Vec<String> {
@@ -238,10 +360,16 @@ impl TryFrom<InputEvent> for KeyEvent {
     type Error = ();
 
     fn try_from(item: InputEvent) -> Result<Self, Self::Error> {
-        if let Ok(oscode) = OsCode::try_from(PageCode {
+        let pagecode = PageCode {
             page: item.page,
             code: item.code,
-        }) {
+        };
+        // Overrides from deflocalkeys-macos take precedence over the built-in mapping.
+        let oscode = match page_code_override(&pagecode) {
+            Some(oscode) => Ok(oscode),
+            None => OsCode::try_from(pagecode),
+        };
+        if let Ok(oscode) = oscode {
             Ok(KeyEvent {
                 code: oscode,
                 value: if item.value == 1 {
@@ -249,6 +377,11 @@ impl TryFrom<InputEvent> for KeyEvent {
                 } else {
                     KeyValue::Release
                 },
+                scancode: Some(item.code as u16),
+                device: None,
+                // `DKEvent` from karabiner-driverkit has no field for the source IOHIDDevice.
+                device_id: 0,
+                injected: false,
             })
         } else {
             Err(())
@@ -276,16 +409,19 @@ impl TryFrom<KeyEvent> for InputEvent {
     }
 }
 
+/// Destination of the key events written by [`KbdOut`]. Mouse and unicode output always use
+/// CGEvent, regardless of the backend.
 #[cfg(all(not(feature = "simulated_output"), not(feature = "passthru_ahk")))]
-pub struct KbdOut {}
+trait KeyOutput: Send {
+    fn write(&mut self, event: InputEvent) -> Result<(), io::Error>;
+}
 
 #[cfg(all(not(feature = "simulated_output"), not(feature = "passthru_ahk")))]
-impl KbdOut {
-    pub fn new() -> Result<Self, io::Error> {
-        Ok(KbdOut {})
-    }
+struct DriverKitOutput;
 
-    pub fn write(&mut self, event: InputEvent) -> Result<(), io::Error> {
+#[cfg(all(not(feature = "simulated_output"), not(feature = "passthru_ahk")))]
+impl KeyOutput for DriverKitOutput {
+    fn write(&mut self, event: InputEvent) -> Result<(), io::Error> {
         let mut devent = event.into();
         log::debug!("Attempting to write {event:?} {devent:?}");
         let rc = send_key(&mut devent);
@@ -297,9 +433,325 @@ impl KbdOut {
         }
         Ok(())
     }
+}
+
+#[cfg(all(not(feature = "simulated_output"), not(feature = "passthru_ahk")))]
+#[derive(Default)]
+struct CGEventOutput {
+    /// Modifier keys currently held down. Their flags are set on every posted event, since
+    /// CGEvent does not track modifier state on its own.
+    held_mods: Vec<OsCode>,
+}
+
+#[cfg(all(not(feature = "simulated_output"), not(feature = "passthru_ahk")))]
+impl KeyOutput for CGEventOutput {
+    fn write(&mut self, event: InputEvent) -> Result<(), io::Error> {
+        // Output events are built from the plain OsCode mapping, so the input overrides from
+        // deflocalkeys-macos must not be applied when converting them back.
+        let Ok(osc) = OsCode::try_from(PageCode {
+            page: event.page,
+            code: event.code,
+        }) else {
+            log::debug!("{event:?} has no OsCode, not writing it");
+            return Ok(());
+        };
+        let Some(keycode) = osc_to_cg_keycode(osc) else {
+            log::debug!("{osc:?} has no CGEvent key code, not writing it");
+            return Ok(());
+        };
+        let keydown = event.value == 1;
+        let is_modifier = cg_modifier_flag(osc).is_some();
+        if is_modifier {
+            self.held_mods.retain(|m| *m != osc);
+            if keydown {
+                self.held_mods.push(osc);
+            }
+        }
+        let flags = self
+            .held_mods
+            .iter()
+            .filter_map(|m| cg_modifier_flag(*m))
+            .fold(CGEventFlags::empty(), |acc, f| acc | f);
+        log::debug!("Attempting to post {osc:?} keydown:{keydown} as CGEvent");
+        let cg_event = CGEvent::new_keyboard_event(KbdOut::make_event_source()?, keycode, keydown)
+            .map_err(|_| io::Error::other("failed to create keyboard event"))?;
+        if is_modifier {
+            cg_event.set_type(CGEventType::FlagsChanged);
+        }
+        cg_event.set_flags(flags);
+        cg_event.post(CGEventTapLocation::HID);
+        Ok(())
+    }
+}
+
+#[cfg(all(not(feature = "simulated_output"), not(feature = "passthru_ahk")))]
+fn cg_modifier_flag(osc: OsCode) -> Option<CGEventFlags> {
+    use OsCode::*;
+    Some(match osc {
+        KEY_LEFTSHIFT | KEY_RIGHTSHIFT => CGEventFlags::CGEventFlagShift,
+        KEY_LEFTCTRL | KEY_RIGHTCTRL => CGEventFlags::CGEventFlagControl,
+        KEY_LEFTALT | KEY_RIGHTALT => CGEventFlags::CGEventFlagAlternate,
+        KEY_LEFTMETA | KEY_RIGHTMETA => CGEventFlags::CGEventFlagCommand,
+        KEY_FN => CGEventFlags::CGEventFlagSecondaryFn,
+        _ => return None,
+    })
+}
+
+#[cfg(all(not(feature = "simulated_output"), not(feature = "passthru_ahk")))]
+/// Maps to the macOS virtual key codes (`kVK_*` in `Events.h`), which follow the ANSI layout.
+#[link(name = "Carbon", kind = "framework")]
+unsafe extern "C" {
+    static kTISPropertyUnicodeKeyLayoutData: *const std::ffi::c_void;
+    fn TISCopyCurrentKeyboardLayoutInputSource() -> *const std::ffi::c_void;
+    fn TISGetInputSourceProperty(
+        source: *const std::ffi::c_void,
+        key: *const std::ffi::c_void,
+    ) -> *const std::ffi::c_void;
+    fn LMGetKbdType() -> u8;
+    #[allow(clippy::too_many_arguments)]
+    fn UCKeyTranslate(
+        key_layout: *const u8,
+        virtual_key_code: u16,
+        key_action: u16,
+        modifier_key_state: u32,
+        keyboard_type: u32,
+        key_translate_options: u32,
+        dead_key_state: *mut u32,
+        max_string_length: usize,
+        actual_string_length: *mut usize,
+        unicode_string: *mut u16,
+    ) -> i32;
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+unsafe extern "C" {
+    fn CFDataGetBytePtr(data: *const std::ffi::c_void) -> *const u8;
+    fn CFRelease(cf: *const std::ffi::c_void);
+}
+
+#[link(name = "CoreGraphics", kind = "framework")]
+unsafe extern "C" {
+    fn CGEventSourceFlagsState(state_id: i32) -> u64;
+}
+
+/// Whether the key is a dead key in the current keyboard layout, with the current state of the
+/// shift, option, control and caps lock keys.
+pub fn is_dead_key(osc: OsCode) -> bool {
+    const K_UC_KEY_ACTION_DOWN: u16 = 0;
+    const COMBINED_SESSION_STATE: i32 = 0;
+    let Some(keycode) = osc_to_cg_keycode(osc) else {
+        return false;
+    };
+    unsafe {
+        let source = TISCopyCurrentKeyboardLayoutInputSource();
+        if source.is_null() {
+            return false;
+        }
+        let layout_data = TISGetInputSourceProperty(source, kTISPropertyUnicodeKeyLayoutData);
+        if layout_data.is_null() {
+            CFRelease(source);
+            return false;
+        }
+        // UCKeyTranslate takes the Carbon modifier bits shifted right by 8.
+        let flags = CGEventSourceFlagsState(COMBINED_SESSION_STATE);
+        let mut modifiers = 0;
+        for (cg_flag, carbon_bit) in [
+            (CGEventFlags::CGEventFlagAlphaShift, 0x04),
+            (CGEventFlags::CGEventFlagShift, 0x02),
+            (CGEventFlags::CGEventFlagControl, 0x10),
+            (CGEventFlags::CGEventFlagAlternate, 0x08),
+        ] {
+            if flags & cg_flag.bits() != 0 {
+                modifiers |= carbon_bit;
+            }
+        }
+        let mut dead_key_state = 0;
+        let mut len = 0;
+        let mut buf = [0u16; 8];
+        let status = UCKeyTranslate(
+            CFDataGetBytePtr(layout_data),
+            keycode,
+            K_UC_KEY_ACTION_DOWN,
+            modifiers,
+            u32::from(LMGetKbdType()),
+            0,
+            &mut dead_key_state,
+            buf.len(),
+            &mut len,
+            buf.as_mut_ptr(),
+        );
+        CFRelease(source);
+        // A dead key produces no characters and leaves the dead key state set for the next key.
+        status == 0 && dead_key_state != 0 && len == 0
+    }
+}
+
+fn osc_to_cg_keycode(osc: OsCode) -> Option<CGKeyCode> {
+    use OsCode::*;
+    Some(match osc {
+        KEY_A => 0x00,
+        KEY_S => 0x01,
+        KEY_D => 0x02,
+        KEY_F => 0x03,
+        KEY_H => 0x04,
+        KEY_G => 0x05,
+        KEY_Z => 0x06,
+        KEY_X => 0x07,
+        KEY_C => 0x08,
+        KEY_V => 0x09,
+        KEY_102ND => 0x0A,
+        KEY_B => 0x0B,
+        KEY_Q => 0x0C,
+        KEY_W => 0x0D,
+        KEY_E => 0x0E,
+        KEY_R => 0x0F,
+        KEY_Y => 0x10,
+        KEY_T => 0x11,
+        KEY_1 => 0x12,
+        KEY_2 => 0x13,
+        KEY_3 => 0x14,
+        KEY_4 => 0x15,
+        KEY_6 => 0x16,
+        KEY_5 => 0x17,
+        KEY_EQUAL => 0x18,
+        KEY_9 => 0x19,
+        KEY_7 => 0x1A,
+        KEY_MINUS => 0x1B,
+        KEY_8 => 0x1C,
+        KEY_0 => 0x1D,
+        KEY_RIGHTBRACE => 0x1E,
+        KEY_O => 0x1F,
+        KEY_U => 0x20,
+        KEY_LEFTBRACE => 0x21,
+        KEY_I => 0x22,
+        KEY_P => 0x23,
+        KEY_ENTER => 0x24,
+        KEY_L => 0x25,
+        KEY_J => 0x26,
+        KEY_APOSTROPHE => 0x27,
+        KEY_K => 0x28,
+        KEY_SEMICOLON => 0x29,
+        KEY_BACKSLASH => 0x2A,
+        KEY_COMMA => 0x2B,
+        KEY_SLASH => 0x2C,
+        KEY_N => 0x2D,
+        KEY_M => 0x2E,
+        KEY_DOT => 0x2F,
+        KEY_TAB => 0x30,
+        KEY_SPACE => 0x31,
+        KEY_GRAVE => 0x32,
+        KEY_BACKSPACE => 0x33,
+        KEY_ESC => 0x35,
+        KEY_RIGHTMETA => 0x36,
+        KEY_LEFTMETA => 0x37,
+        KEY_LEFTSHIFT => 0x38,
+        KEY_CAPSLOCK => 0x39,
+        KEY_LEFTALT => 0x3A,
+        KEY_LEFTCTRL => 0x3B,
+        KEY_RIGHTSHIFT => 0x3C,
+        KEY_RIGHTALT => 0x3D,
+        KEY_RIGHTCTRL => 0x3E,
+        KEY_FN => 0x3F,
+        KEY_F17 => 0x40,
+        KEY_KPDOT => 0x41,
+        KEY_KPASTERISK => 0x43,
+        KEY_KPPLUS => 0x45,
+        KEY_NUMLOCK => 0x47,
+        KEY_VOLUMEUP => 0x48,
+        KEY_VOLUMEDOWN => 0x49,
+        KEY_MUTE => 0x4A,
+        KEY_KPSLASH => 0x4B,
+        KEY_KPENTER => 0x4C,
+        KEY_KPMINUS => 0x4E,
+        KEY_F18 => 0x4F,
+        KEY_F19 => 0x50,
+        KEY_KPEQUAL => 0x51,
+        KEY_KP0 => 0x52,
+        KEY_KP1 => 0x53,
+        KEY_KP2 => 0x54,
+        KEY_KP3 => 0x55,
+        KEY_KP4 => 0x56,
+        KEY_KP5 => 0x57,
+        KEY_KP6 => 0x58,
+        KEY_KP7 => 0x59,
+        KEY_F20 => 0x5A,
+        KEY_KP8 => 0x5B,
+        KEY_KP9 => 0x5C,
+        KEY_YEN => 0x5D,
+        KEY_RO => 0x5E,
+        KEY_KPCOMMA => 0x5F,
+        KEY_F5 => 0x60,
+        KEY_F6 => 0x61,
+        KEY_F7 => 0x62,
+        KEY_F3 => 0x63,
+        KEY_F8 => 0x64,
+        KEY_F9 => 0x65,
+        KEY_F11 => 0x67,
+        KEY_F13 => 0x69,
+        KEY_F16 => 0x6A,
+        KEY_F14 => 0x6B,
+        KEY_F10 => 0x6D,
+        KEY_F12 => 0x6F,
+        KEY_F15 => 0x71,
+        KEY_HELP => 0x72,
+        KEY_HOME => 0x73,
+        KEY_PAGEUP => 0x74,
+        KEY_DELETE => 0x75,
+        KEY_F4 => 0x76,
+        KEY_END => 0x77,
+        KEY_F2 => 0x78,
+        KEY_PAGEDOWN => 0x79,
+        KEY_F1 => 0x7A,
+        KEY_LEFT => 0x7B,
+        KEY_RIGHT => 0x7C,
+        KEY_DOWN => 0x7D,
+        KEY_UP => 0x7E,
+        _ => return None,
+    })
+}
+
+/// `kCGScrollWheelEventScrollPhase` and its `kCGScrollPhase*` values, which the core-graphics
+/// crate does not name.
+const SCROLL_WHEEL_EVENT_SCROLL_PHASE: u32 = 99;
+const SCROLL_PHASE_BEGAN: i64 = 1;
+const SCROLL_PHASE_CHANGED: i64 = 2;
+const SCROLL_PHASE_ENDED: i64 = 4;
+/// Pixels scrolled per line by continuous scroll events, roughly matching a wheel notch.
+const SCROLL_POINTS_PER_LINE: i64 = 10;
+
+#[cfg(all(not(feature = "simulated_output"), not(feature = "passthru_ahk")))]
+pub struct KbdOut {
+    output: Box<dyn KeyOutput>,
+    pub rate_limiter: OutputRateLimiter,
+    /// Smooth scroll distance, in 1/120ths of a line, not yet sent as a whole line delta.
+    accumulated_scroll: u16,
+    accumulated_hscroll: u16,
+}
+
+#[cfg(all(not(feature = "simulated_output"), not(feature = "passthru_ahk")))]
+impl KbdOut {
+    pub fn new() -> Result<Self, io::Error> {
+        let output: Box<dyn KeyOutput> = match output_backend() {
+            OutputBackend::DriverKit => Box::new(DriverKitOutput),
+            OutputBackend::CGEvent => {
+                log::info!("using CGEvent for key output");
+                Box::new(CGEventOutput::default())
+            }
+        };
+        Ok(KbdOut {
+            output,
+            rate_limiter: OutputRateLimiter::default(),
+            accumulated_scroll: 0,
+            accumulated_hscroll: 0,
+        })
+    }
+
+    pub fn write(&mut self, event: InputEvent) -> Result<(), io::Error> {
+        self.output.write(event)
+    }
 
     pub fn write_key(&mut self, key: OsCode, value: KeyValue) -> Result<(), io::Error> {
-        if let Ok(event) = InputEvent::try_from(KeyEvent { value, code: key }) {
+        if let Ok(event) = InputEvent::try_from(KeyEvent::new(key, value)) {
             self.write(event)
         } else {
             log::debug!("couldn't write unrecognized {key:?}");
@@ -307,11 +759,26 @@ impl KbdOut {
         }
     }
 
+    /// Writes a key using the HID usage that it was received with.
+    pub fn write_key_scancode(
+        &mut self,
+        key: OsCode,
+        value: KeyValue,
+        scancode: u16,
+    ) -> Result<(), io::Error> {
+        match InputEvent::try_from(KeyEvent::new(key, value)) {
+            Ok(event) => self.write(InputEvent {
+                code: u32::from(scancode),
+                ..event
+            }),
+            Err(()) => self.write_key(key, value),
+        }
+    }
+
     pub fn write_code(&mut self, code: u32, value: KeyValue) -> Result<(), io::Error> {
-        if let Ok(event) = InputEvent::try_from(KeyEvent {
-            value,
-            code: OsCode::from_u16(code as u16).unwrap(),
-        }) {
+        if let Ok(event) =
+            InputEvent::try_from(KeyEvent::new(OsCode::from_u16(code as u16).unwrap(), value))
+        {
             self.write(event)
         } else {
             log::debug!("couldn't write unrecognized OsCode {code}");
@@ -319,6 +786,24 @@ impl KbdOut {
         }
     }
 
+    /// Writes a raw HID usage. This bypasses the OsCode mapping and is only possible with the
+    /// DriverKit backend, which forwards the keyboard, consumer and Apple vendor pages.
+    pub fn write_hid(&mut self, page: u16, id: u16, value: KeyValue) -> Result<(), io::Error> {
+        if output_backend() != OutputBackend::DriverKit {
+            log::warn!("hid {page:#x} {id:#x} needs the driverkit output backend, not writing it");
+            return Ok(());
+        }
+        let value = match value {
+            KeyValue::Press | KeyValue::Repeat => 1,
+            _ => 0,
+        };
+        self.write(InputEvent {
+            value,
+            page: page.into(),
+            code: id.into(),
+        })
+    }
+
     pub fn press_key(&mut self, key: OsCode) -> Result<(), io::Error> {
         self.write_key(key, KeyValue::Press)
     }
@@ -365,6 +850,65 @@ impl KbdOut {
         event.post(CGEventTapLocation::HID);
         Ok(())
     }
+    /// Sends smooth scrolling as a continuous scroll gesture with phases, like a trackpad. The
+    /// line delta only carries whole lines, accumulated from the fractional distances, so that
+    /// applications reading only the line delta still scroll the correct total.
+    pub fn scroll_fine(
+        &mut self,
+        direction: MWheelDirection,
+        distance: u16,
+        phase: ScrollPhase,
+    ) -> Result<(), io::Error> {
+        let accumulated = match direction {
+            MWheelDirection::Up | MWheelDirection::Down => &mut self.accumulated_scroll,
+            MWheelDirection::Left | MWheelDirection::Right => &mut self.accumulated_hscroll,
+        };
+        *accumulated += distance;
+        let lines = *accumulated / HI_RES_SCROLL_UNITS_IN_LO_RES;
+        *accumulated %= HI_RES_SCROLL_UNITS_IN_LO_RES;
+        if phase == ScrollPhase::Ended {
+            *accumulated = 0;
+        }
+        let sign = match direction {
+            MWheelDirection::Down | MWheelDirection::Left => 1,
+            MWheelDirection::Up | MWheelDirection::Right => -1,
+        };
+        let (line_axis, fixed_axis, point_axis) = match direction {
+            MWheelDirection::Up | MWheelDirection::Down => (
+                EventField::SCROLL_WHEEL_EVENT_DELTA_AXIS_1,
+                EventField::SCROLL_WHEEL_EVENT_FIXED_POINT_DELTA_AXIS_1,
+                EventField::SCROLL_WHEEL_EVENT_POINT_DELTA_AXIS_1,
+            ),
+            MWheelDirection::Left | MWheelDirection::Right => (
+                EventField::SCROLL_WHEEL_EVENT_DELTA_AXIS_2,
+                EventField::SCROLL_WHEEL_EVENT_FIXED_POINT_DELTA_AXIS_2,
+                EventField::SCROLL_WHEEL_EVENT_POINT_DELTA_AXIS_2,
+            ),
+        };
+        let event = Self::make_event()?;
+        event.set_type(CGEventType::ScrollWheel);
+        event.set_integer_value_field(EventField::SCROLL_WHEEL_EVENT_IS_CONTINUOUS, 1);
+        event.set_integer_value_field(
+            SCROLL_WHEEL_EVENT_SCROLL_PHASE,
+            match phase {
+                ScrollPhase::Began => SCROLL_PHASE_BEGAN,
+                ScrollPhase::Changed => SCROLL_PHASE_CHANGED,
+                ScrollPhase::Ended => SCROLL_PHASE_ENDED,
+            },
+        );
+        event.set_integer_value_field(line_axis, sign * i64::from(lines));
+        event.set_double_value_field(
+            fixed_axis,
+            sign as f64 * f64::from(distance) / f64::from(HI_RES_SCROLL_UNITS_IN_LO_RES),
+        );
+        event.set_integer_value_field(
+            point_axis,
+            sign * i64::from(distance) * SCROLL_POINTS_PER_LINE
+                / i64::from(HI_RES_SCROLL_UNITS_IN_LO_RES),
+        );
+        event.post(CGEventTapLocation::HID);
+        Ok(())
+    }
     fn button_action(&mut self, _btn: Btn, is_click: bool) -> Result<(), io::Error> {
         let (event_type, button) = match _btn {
             Btn::Left => (
@@ -475,6 +1019,39 @@ impl KbdOut {
         Ok(())
     }
 
+    pub fn set_mouse_pct(
+        &mut self,
+        x: u8,
+        y: u8,
+        monitor: MonitorSelector,
+    ) -> Result<(), io::Error> {
+        let displays = CGDisplay::active_displays()
+            .map_err(|_| io::Error::other("failed to list displays"))?;
+        let display = match monitor {
+            MonitorSelector::Primary => Some(CGDisplay::main()),
+            MonitorSelector::Index(i) => displays.get(usize::from(i)).map(|&id| CGDisplay::new(id)),
+            MonitorSelector::Current => {
+                let cursor = Self::make_event()?.location();
+                displays
+                    .iter()
+                    .map(|&id| CGDisplay::new(id))
+                    .find(|display| display.bounds().contains(&cursor))
+            }
+        };
+        let Some(display) = display else {
+            log::warn!("setmouse-pct: monitor {monitor:?} not found");
+            return Ok(());
+        };
+        // The display bounds include the menu bar and the Dock.
+        let size = display.bounds().size;
+        let pct_of = |len: CGFloat, pct: u8| (len - 1.0).max(0.0) * CGFloat::from(pct) / 100.0;
+        let point = CGPoint::new(pct_of(size.width, x), pct_of(size.height, y));
+        display
+            .move_cursor_to_point(point)
+            .map_err(|_| io::Error::other("failed to move cursor to point"))?;
+        Ok(())
+    }
+
     fn make_event_source() -> Result<CGEventSource, Error> {
         CGEventSource::new(CGEventSourceStateID::CombinedSessionState)
             .map_err(|_| Error::other("failed to create core graphics event source"))
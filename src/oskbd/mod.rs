@@ -60,8 +60,20 @@ mod sim_passthru; // has KbdOut
 ))]
 pub use sim_passthru::*;
 
+mod rate_limit;
+pub use rate_limit::*;
+
 pub const HI_RES_SCROLL_UNITS_IN_LO_RES: u16 = 120;
 
+/// Stage of a smooth scroll gesture, for outputs that report scrolling as a continuous gesture
+/// rather than as separate wheel notches.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScrollPhase {
+    Began,
+    Changed,
+    Ended,
+}
+
 // ------------------ KeyValue --------------------
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -105,12 +117,59 @@ use kanata_parser::keys::OsCode;
 pub struct KeyEvent {
     pub code: OsCode,
     pub value: KeyValue,
+    /// The hardware scan code that the event was received with, if the input mechanism provides
+    /// one. This is the evdev code on Linux, the `KBDLLHOOKSTRUCT` scan code with `0xE000` added
+    /// for extended keys on Windows, and the HID usage on macOS.
+    pub scancode: Option<u16>,
+    /// The input device that sent the event, if the input mechanism can tell. Only used for
+    /// logging.
+    pub device: Option<&'static str>,
+    /// Identifies the input device that sent the event, 0 if unknown. This is the poll token of
+    /// the evdev device on Linux and the device number on Windows with the Interception driver.
+    /// The Windows hooks and the macOS driver do not report the device, so it is always 0 there.
+    pub device_id: u32,
+    /// Whether the event was sent by the TCP `KeyInject` command rather than an input device.
+    pub injected: bool,
 }
 
 #[allow(dead_code, unused)]
 impl KeyEvent {
     pub fn new(code: OsCode, value: KeyValue) -> Self {
-        Self { code, value }
+        Self {
+            code,
+            value,
+            scancode: None,
+            device: None,
+            device_id: 0,
+            injected: false,
+        }
+    }
+
+    pub fn with_scancode(code: OsCode, value: KeyValue, scancode: u16) -> Self {
+        Self {
+            code,
+            value,
+            scancode: Some(scancode),
+            device: None,
+            device_id: 0,
+            injected: false,
+        }
+    }
+}
+
+/// Returns a `'static` copy of an input device name for [`KeyEvent::device`]. Each distinct name
+/// is leaked only once, so devices that reconnect do not leak memory again.
+#[allow(dead_code)]
+pub fn intern_device_name(name: &str) -> &'static str {
+    static NAMES: parking_lot::Mutex<Vec<&'static str>> = parking_lot::Mutex::new(Vec::new());
+    let mut names = NAMES.lock();
+    match names.iter().find(|n| **n == name) {
+        Some(n) => n,
+        None => {
+            let n: &'static str = Box::leak(name.into());
+            names.push(n);
+            n
+        }
     }
 }
 
@@ -138,6 +197,10 @@ impl fmt::Debug for KeyEvent {
                 &format_args!("{:?} ({})", self.code, self.code.as_u16()),
             )
             .field("value", &self.value)
+            .field("scancode", &self.scancode)
+            .field("device", &self.device)
+            .field("device_id", &self.device_id)
+            .field("injected", &self.injected)
             .finish()
     }
 }
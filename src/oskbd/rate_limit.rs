@@ -0,0 +1,109 @@
+use super::KeyValue;
+use kanata_parser::cfg::OutputRateLimitPolicy;
+use kanata_parser::keys::OsCode;
+use web_time::{Duration, Instant};
+
+/// Time between warnings about the output rate limit being exceeded.
+const WARNING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Caps the number of key events written per second, as configured by `output-rate-limit`.
+/// Bursts of up to one second's worth of events are let through unchanged.
+#[derive(Debug)]
+pub struct OutputRateLimiter {
+    /// Events per second, 0 meaning no limit.
+    rate: u16,
+    policy: OutputRateLimitPolicy,
+    tokens: f64,
+    last_refill: Instant,
+    /// Keys whose press was dropped, so that their release is dropped too.
+    dropped: Vec<OsCode>,
+    last_warning: Option<Instant>,
+}
+
+impl Default for OutputRateLimiter {
+    fn default() -> Self {
+        Self {
+            rate: 0,
+            policy: OutputRateLimitPolicy::Delay,
+            tokens: 0.0,
+            last_refill: Instant::now(),
+            dropped: vec![],
+            last_warning: None,
+        }
+    }
+}
+
+impl OutputRateLimiter {
+    pub fn set_limit(&mut self, rate: u16, policy: OutputRateLimitPolicy) {
+        if self.rate != rate {
+            self.rate = rate;
+            self.tokens = f64::from(rate);
+            self.last_refill = Instant::now();
+        }
+        self.policy = policy;
+    }
+
+    /// Returns whether the key event should be written. With the delay policy, this blocks until
+    /// the event is within the limit and always returns true.
+    pub fn allow(&mut self, osc: OsCode, value: KeyValue) -> bool {
+        if self.rate == 0 {
+            return true;
+        }
+        if value == KeyValue::Release
+            && let Some(i) = self.dropped.iter().position(|dropped| *dropped == osc)
+        {
+            self.dropped.swap_remove(i);
+            return false;
+        }
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return true;
+        }
+        self.warn();
+        match self.policy {
+            OutputRateLimitPolicy::Delay => {
+                let wait = (1.0 - self.tokens) / f64::from(self.rate);
+                std::thread::sleep(Duration::from_secs_f64(wait));
+                self.refill();
+                self.tokens = (self.tokens - 1.0).max(0.0);
+                true
+            }
+            // Releases of written presses are not dropped, so that keys do not get stuck.
+            OutputRateLimitPolicy::Drop if value == KeyValue::Release => true,
+            OutputRateLimitPolicy::Drop => {
+                if value == KeyValue::Press && !self.dropped.contains(&osc) {
+                    self.dropped.push(osc);
+                }
+                false
+            }
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * f64::from(self.rate)).min(f64::from(self.rate));
+        self.last_refill = now;
+    }
+
+    fn warn(&mut self) {
+        let now = Instant::now();
+        if self
+            .last_warning
+            .is_some_and(|last| now.duration_since(last) < WARNING_INTERVAL)
+        {
+            return;
+        }
+        self.last_warning = Some(now);
+        let action = match self.policy {
+            OutputRateLimitPolicy::Delay => "delaying",
+            OutputRateLimitPolicy::Drop => "dropping",
+        };
+        log::warn!(
+            "output rate limit of {} events per second exceeded, {action} key events",
+            self.rate
+        );
+    }
+}
@@ -25,13 +25,17 @@ use std::sync::mpsc::{SendError, Sender as ASender};
 /// Handle for writing keys to the simulated input provider.
 pub struct KbdOut {
     pub tx_kout: Option<ASender<InputEvent>>,
+    pub rate_limiter: OutputRateLimiter,
 }
 
 use std::io::{Error as IoErr, ErrorKind::NotConnected};
 impl KbdOut {
     #[cfg(not(any(target_os = "linux", target_os = "android")))]
     pub fn new() -> Result<Self, io::Error> {
-        Ok(Self { tx_kout: None })
+        Ok(Self {
+            tx_kout: None,
+            rate_limiter: OutputRateLimiter::default(),
+        })
     }
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub fn new(
@@ -40,7 +44,10 @@ impl KbdOut {
         _name: &str,
         _bustype: evdev::BusType,
     ) -> Result<Self, io::Error> {
-        Ok(Self { tx_kout: None })
+        Ok(Self {
+            tx_kout: None,
+            rate_limiter: OutputRateLimiter::default(),
+        })
     }
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub fn write_raw(&mut self, event: InputEvent) -> Result<(), io::Error> {
@@ -88,10 +95,22 @@ impl KbdOut {
         };
         self.write(event)
     }
+    pub fn write_key_scancode(
+        &mut self,
+        key: OsCode,
+        value: KeyValue,
+        _scancode: u16,
+    ) -> Result<(), io::Error> {
+        self.write_key(key, value)
+    }
     pub fn write_code(&mut self, code: u32, value: KeyValue) -> Result<(), io::Error> {
         trace!("out-code:{code};{value:?}");
         Ok(())
     }
+    pub fn write_hid(&mut self, page: u16, id: u16, value: KeyValue) -> Result<(), io::Error> {
+        trace!("out-hid:{page:#x}:{id:#x};{value:?}");
+        Ok(())
+    }
     pub fn press_key(&mut self, key: OsCode) -> Result<(), io::Error> {
         self.write_key(key, KeyValue::Press)
     }
@@ -114,6 +133,15 @@ impl KbdOut {
         trace!("scroll:{direction:?},{distance:?}");
         Ok(())
     }
+    pub fn scroll_fine(
+        &mut self,
+        direction: MWheelDirection,
+        distance: u16,
+        phase: ScrollPhase,
+    ) -> Result<(), io::Error> {
+        trace!("scroll:{direction:?},{distance:?},{phase:?}");
+        Ok(())
+    }
     pub fn move_mouse(&mut self, mv: CalculatedMouseMove) -> Result<(), io::Error> {
         let (direction, distance) = (mv.direction, mv.distance);
         trace!("out🖰:move {direction:?},{distance:?}");
@@ -130,6 +158,15 @@ impl KbdOut {
         log::info!("out🖰:@{x},{y}");
         Ok(())
     }
+    pub fn set_mouse_pct(
+        &mut self,
+        x: u8,
+        y: u8,
+        monitor: MonitorSelector,
+    ) -> Result<(), io::Error> {
+        log::info!("out🖰:@{x}%,{y}%,{monitor:?}");
+        Ok(())
+    }
     pub fn tick(&mut self) {}
 }
 
@@ -171,6 +208,10 @@ impl TryFrom<InputEvent> for KeyEvent {
                 true => KeyValue::Release,
                 false => KeyValue::Press,
             },
+            scancode: None,
+            device: None,
+            device_id: 0,
+            injected: false,
         })
     }
 }
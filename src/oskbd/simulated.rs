@@ -236,6 +236,9 @@ impl LogFmt {
     pub fn set_mouse(&mut self, x: u16, y: u16) {
         self.fmt(LogFmtT::MouseMove, format!("@{x},{y}"))
     }
+    pub fn set_mouse_pct(&mut self, x: u8, y: u8, monitor: MonitorSelector) {
+        self.fmt(LogFmtT::MouseMove, format!("@{x}%,{y}%,{monitor:?}"))
+    }
     pub fn scroll(&mut self, dir: MWheelDirection, dist: u16) {
         self.fmt(LogFmtT::MouseMove, format!("{dir}{dist}"))
     }
@@ -245,6 +248,9 @@ impl LogFmt {
     pub fn write_code(&mut self, code: u32, value: KeyValue) {
         self.fmt(LogFmtT::Code, format!("{code};{value:?}"))
     }
+    pub fn write_hid(&mut self, page: u16, id: u16, value: KeyValue) {
+        self.fmt(LogFmtT::Code, format!("{page:#x}:{id:#x};{value:?}"))
+    }
 
     pub fn end(&self, in_path: &PathBuf, appendix: Option<String>) {
         let pad = self.combo.len().saturating_sub(3);
@@ -338,6 +344,7 @@ impl Outputs {
 pub struct KbdOut {
     pub log: LogFmt,
     pub outputs: Outputs,
+    pub rate_limiter: OutputRateLimiter,
 }
 
 impl KbdOut {
@@ -345,6 +352,7 @@ impl KbdOut {
         Ok(Self {
             log: LogFmt::new(),
             outputs: Outputs::new(),
+            rate_limiter: OutputRateLimiter::default(),
         })
     }
 
@@ -385,11 +393,30 @@ impl KbdOut {
         };
         self.write(event)
     }
+    pub fn write_key_scancode(
+        &mut self,
+        key: OsCode,
+        value: KeyValue,
+        scancode: u16,
+    ) -> Result<(), io::Error> {
+        match value {
+            KeyValue::Release => self.log.release_key(key),
+            _ => self.log.press_key(key),
+        }
+        self.outputs.push(format!("out-sc:{scancode:#x};{value:?}"));
+        Ok(())
+    }
     pub fn write_code(&mut self, code: u32, value: KeyValue) -> Result<(), io::Error> {
         self.log.write_code(code, value);
         self.outputs.push(format!("out-code:{code};{value:?}"));
         Ok(())
     }
+    pub fn write_hid(&mut self, page: u16, id: u16, value: KeyValue) -> Result<(), io::Error> {
+        self.log.write_hid(page, id, value);
+        self.outputs
+            .push(format!("out-hid:{page:#x}:{id:#x};{value:?}"));
+        Ok(())
+    }
     pub fn press_key(&mut self, key: OsCode) -> Result<(), io::Error> {
         self.log.press_key(key);
         self.write_key(key, KeyValue::Press)
@@ -419,6 +446,22 @@ impl KbdOut {
             .push(format!("scroll:{direction:?},{distance:?}"));
         Ok(())
     }
+    pub fn scroll_fine(
+        &mut self,
+        direction: MWheelDirection,
+        distance: u16,
+        phase: ScrollPhase,
+    ) -> Result<(), io::Error> {
+        match phase {
+            ScrollPhase::Ended => self.outputs.push(format!("scroll-end:{direction:?}")),
+            ScrollPhase::Began | ScrollPhase::Changed => {
+                self.log.scroll(direction, distance);
+                self.outputs
+                    .push(format!("scroll:{direction:?},{distance:?}"));
+            }
+        }
+        Ok(())
+    }
     pub fn move_mouse(&mut self, mv: CalculatedMouseMove) -> Result<(), io::Error> {
         let (direction, distance) = (mv.direction, mv.distance);
         self.log.move_mouse(direction, distance);
@@ -440,6 +483,16 @@ impl KbdOut {
         log::info!("out🖰:@{x},{y}");
         Ok(())
     }
+    pub fn set_mouse_pct(
+        &mut self,
+        x: u8,
+        y: u8,
+        monitor: MonitorSelector,
+    ) -> Result<(), io::Error> {
+        self.log.set_mouse_pct(x, y, monitor);
+        self.outputs.push(format!("out🖰:@{x}%,{y}%,{monitor:?}"));
+        Ok(())
+    }
     pub fn tick(&mut self) {
         self.outputs.ticks += 1;
         self.log.ticks += 1;
@@ -483,6 +536,10 @@ impl TryFrom<InputEvent> for KeyEvent {
                 true => KeyValue::Release,
                 false => KeyValue::Press,
             },
+            scancode: None,
+            device: None,
+            device_id: 0,
+            injected: false,
         })
     }
 }
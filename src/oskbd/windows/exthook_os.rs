@@ -86,6 +86,10 @@ impl TryFrom<InputEvent> for KeyEvent {
                 true => KeyValue::Release,
                 false => KeyValue::Press,
             },
+            scancode: None,
+            device: None,
+            device_id: 0,
+            injected: false,
         })
     }
 }
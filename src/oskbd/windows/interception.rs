@@ -6,7 +6,7 @@ use kanata_interception::{Interception, KeyState, MouseFlags, MouseState, ScanCo
 
 use super::OsCodeWrapper;
 use crate::kanata::CalculatedMouseMove;
-use crate::oskbd::KeyValue;
+use crate::oskbd::{KeyValue, OutputRateLimiter, ScrollPhase};
 use kanata_parser::custom_action::*;
 use kanata_parser::keys::*;
 
@@ -151,7 +151,9 @@ thread_local! {
 
 #[cfg(all(not(feature = "simulated_output"), not(feature = "passthru_ahk")))]
 /// Handle for writing keys to the OS.
-pub struct KbdOut {}
+pub struct KbdOut {
+    pub rate_limiter: OutputRateLimiter,
+}
 
 fn write_interception(event: InputEvent) {
     let strokes = [event.0];
@@ -174,7 +176,9 @@ fn write_interception(event: InputEvent) {
 #[cfg(all(not(feature = "simulated_output"), not(feature = "passthru_ahk")))]
 impl KbdOut {
     pub fn new() -> Result<Self, io::Error> {
-        Ok(Self {})
+        Ok(Self {
+            rate_limiter: OutputRateLimiter::default(),
+        })
     }
 
     pub fn write(&mut self, event: InputEvent) -> Result<(), io::Error> {
@@ -194,6 +198,16 @@ impl KbdOut {
         self.write(InputEvent::from_oscode(key, value))
     }
 
+    /// Interception strokes already carry scan codes, so this is the same as `write_key`.
+    pub fn write_key_scancode(
+        &mut self,
+        key: OsCode,
+        value: KeyValue,
+        _scancode: u16,
+    ) -> Result<(), io::Error> {
+        self.write_key(key, value)
+    }
+
     pub fn press_key(&mut self, key: OsCode) -> Result<(), io::Error> {
         self.write_key(key, KeyValue::Press)
     }
@@ -228,6 +242,20 @@ impl KbdOut {
         Ok(())
     }
 
+    /// Windows applications accumulate `WHEEL_DELTA` fractions themselves, so smooth scrolling
+    /// is the regular scroll with a distance below 120.
+    pub fn scroll_fine(
+        &mut self,
+        direction: MWheelDirection,
+        distance: u16,
+        phase: ScrollPhase,
+    ) -> Result<(), io::Error> {
+        match phase {
+            ScrollPhase::Ended => Ok(()),
+            ScrollPhase::Began | ScrollPhase::Changed => self.scroll(direction, distance),
+        }
+    }
+
     pub fn move_mouse(&mut self, mv: CalculatedMouseMove) -> Result<(), io::Error> {
         write_interception(InputEvent::from_mouse_move(mv.direction, mv.distance));
         Ok(())
@@ -242,4 +270,17 @@ impl KbdOut {
         write_interception(InputEvent::from_mouse_set(x, y));
         Ok(())
     }
+
+    pub fn set_mouse_pct(
+        &mut self,
+        x: u8,
+        y: u8,
+        monitor: MonitorSelector,
+    ) -> Result<(), io::Error> {
+        match super::monitor_pct_to_virtual_desk(x, y, monitor) {
+            Some((x, y)) => write_interception(InputEvent::from_mouse_set(x, y)),
+            None => log::warn!("setmouse-pct: monitor {monitor:?} not found"),
+        }
+        Ok(())
+    }
 }
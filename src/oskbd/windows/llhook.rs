@@ -22,7 +22,7 @@ use winapi::shared::windef::*;
 use winapi::um::winuser::*;
 
 use crate::kanata::CalculatedMouseMove;
-use crate::oskbd::{KeyEvent, KeyValue};
+use crate::oskbd::{KeyEvent, KeyValue, OutputRateLimiter, ScrollPhase};
 use kanata_keyberon::key_code::KeyCode;
 use kanata_parser::custom_action::*;
 use kanata_parser::keys::*;
@@ -81,6 +81,10 @@ pub struct InputEvent {
 
     /// Key was released
     pub up: bool,
+
+    /// Hardware scan code, with `0xE000` added for extended keys. Only known for events that
+    /// were received by the hook.
+    pub scancode: Option<u16>,
 }
 
 impl fmt::Display for InputEvent {
@@ -94,6 +98,12 @@ impl fmt::Display for InputEvent {
 impl InputEvent {
     #[rustfmt::skip]
     fn from_hook_lparam(lparam: &KBDLLHOOKSTRUCT) -> Self {
+        let extended = if lparam.flags & 0x1 == 0x1 {
+            0xE000
+        } else {
+            0
+        };
+        let sc_with_ext = (lparam.scanCode as u16) | extended;
         let code = if lparam.vkCode == (VK_RETURN as u32) {
             match lparam.flags & 0x1 {
                 0 => VK_RETURN as u32,
@@ -102,16 +112,21 @@ impl InputEvent {
         } else {
             #[cfg(not(feature = "win_llhook_read_scancodes"))]
             {
-                lparam.vkCode
+                let numlock_off = || unsafe { GetKeyState(VK_NUMLOCK) } & 1 == 0;
+                if super::RAW_EVENT_MODE.load(std::sync::atomic::Ordering::SeqCst) {
+                    crate::oskbd::u16_to_osc(sc_with_ext)
+                        .map(Into::into)
+                        .unwrap_or(lparam.vkCode)
+                } else if super::NUMLOCK_NORMALIZE.load(std::sync::atomic::Ordering::SeqCst)
+                    && numlock_off()
+                {
+                    super::numlock_on_vk(lparam.vkCode, extended != 0).unwrap_or(lparam.vkCode)
+                } else {
+                    lparam.vkCode
+                }
             }
             #[cfg(feature = "win_llhook_read_scancodes")]
             {
-                let extended = if lparam.flags & 0x1 == 0x1 {
-                    0xE000
-                } else {
-                    0
-                };
-                let sc_with_ext = (lparam.scanCode as u16) | extended;
                 log::debug!("converting {sc_with_ext}");
                 crate::oskbd::u16_to_osc(sc_with_ext)
                     .map(Into::into)
@@ -121,6 +136,7 @@ impl InputEvent {
         Self {
             code,
             up: lparam.flags & LLKHF_UP != 0,
+            scancode: Some(sc_with_ext),
         }
     }
 
@@ -128,6 +144,7 @@ impl InputEvent {
         Self {
             code: code.into(),
             up: val.into(),
+            scancode: None,
         }
     }
 }
@@ -141,6 +158,12 @@ impl TryFrom<InputEvent> for KeyEvent {
                 true => KeyValue::Release,
                 false => KeyValue::Press,
             },
+            scancode: item.scancode,
+            device: None,
+            // `KBDLLHOOKSTRUCT` has no device. Raw Input is not an option either: it is only
+            // generated after the hook passes a key on, so it never sees the keys kanata handles.
+            device_id: 0,
+            injected: false,
         })
     }
 }
@@ -150,6 +173,7 @@ impl From<KeyEvent> for InputEvent {
         Self {
             code: item.code.into(),
             up: item.value.into(),
+            scancode: item.scancode,
         }
     }
 }
@@ -233,16 +257,26 @@ unsafe extern "system" fn hook_proc(code: c_int, wparam: WPARAM, lparam: LPARAM)
 
 #[cfg(all(not(feature = "simulated_output"), not(feature = "passthru_ahk")))]
 /// Handle for writing keys to the OS.
-pub struct KbdOut {}
+pub struct KbdOut {
+    pub rate_limiter: OutputRateLimiter,
+}
 
 #[cfg(all(not(feature = "simulated_output"), not(feature = "passthru_ahk")))]
 impl KbdOut {
     pub fn new() -> Result<Self, io::Error> {
-        Ok(Self {})
+        Ok(Self {
+            rate_limiter: OutputRateLimiter::default(),
+        })
     }
 
     pub fn write(&mut self, event: InputEvent) -> Result<(), io::Error> {
-        super::send_key_sendinput(event.code as u16, event.up);
+        match event
+            .scancode
+            .or_else(|| super::raw_mode_scancode(event.code as u16))
+        {
+            Some(scancode) => super::send_scancode_sendinput(scancode, event.up),
+            None => super::send_key_sendinput(event.code as u16, event.up),
+        }
         Ok(())
     }
 
@@ -251,6 +285,20 @@ impl KbdOut {
         self.write(event)
     }
 
+    /// Writes a key using the hardware scan code that it was received with.
+    pub fn write_key_scancode(
+        &mut self,
+        key: OsCode,
+        value: KeyValue,
+        scancode: u16,
+    ) -> Result<(), io::Error> {
+        let event = InputEvent {
+            scancode: Some(scancode),
+            ..InputEvent::from_oscode(key, value)
+        };
+        self.write(event)
+    }
+
     pub fn write_code(&mut self, code: u32, value: KeyValue) -> Result<(), io::Error> {
         super::write_code(code as u16, value)
     }
@@ -307,6 +355,20 @@ impl KbdOut {
         Ok(())
     }
 
+    /// Windows applications accumulate `WHEEL_DELTA` fractions themselves, so smooth scrolling
+    /// is the regular scroll with a distance below 120.
+    pub fn scroll_fine(
+        &mut self,
+        direction: MWheelDirection,
+        distance: u16,
+        phase: ScrollPhase,
+    ) -> Result<(), io::Error> {
+        match phase {
+            ScrollPhase::Ended => Ok(()),
+            ScrollPhase::Began | ScrollPhase::Changed => self.scroll(direction, distance),
+        }
+    }
+
     pub fn move_mouse(&mut self, mv: CalculatedMouseMove) -> Result<(), io::Error> {
         move_mouse(mv.direction, mv.distance);
         Ok(())
@@ -322,6 +384,20 @@ impl KbdOut {
         set_mouse_xy(i32::from(x), i32::from(y));
         Ok(())
     }
+
+    pub fn set_mouse_pct(
+        &mut self,
+        x: u8,
+        y: u8,
+        monitor: MonitorSelector,
+    ) -> Result<(), io::Error> {
+        log::info!("setting mouse {x}% {y}% on monitor {monitor:?}");
+        match super::monitor_pct_to_virtual_desk(x, y, monitor) {
+            Some((x, y)) => set_mouse_xy(i32::from(x), i32::from(y)),
+            None => log::warn!("setmouse-pct: monitor {monitor:?} not found"),
+        }
+        Ok(())
+    }
 }
 
 fn send_btn(flag: u32) {
@@ -5,6 +5,7 @@
 
 #[cfg(not(feature = "simulated_input"))]
 use std::mem;
+use std::sync::atomic::AtomicBool;
 
 #[cfg(not(feature = "simulated_input"))]
 use winapi::um::winuser::*;
@@ -25,9 +26,8 @@ mod exthook_os;
 #[cfg(all(not(feature = "interception_driver"), feature = "simulated_input"))]
 pub use exthook_os::*;
 
-mod scancode_to_usvk;
 #[allow(unused)]
-pub use scancode_to_usvk::*;
+pub use kanata_parser::keys::scancode::*;
 
 #[cfg(feature = "interception_driver")]
 mod interception;
@@ -38,6 +38,79 @@ pub use self::interception::*;
 #[cfg(feature = "interception_driver")]
 pub use interception_convert::*;
 
+/// Set by `windows-numlock-normalize`: numpad keys received by the hook report their NumLock-on
+/// virtual keys even when NumLock is off.
+pub static NUMLOCK_NORMALIZE: AtomicBool = AtomicBool::new(false);
+
+/// Set by `event-mode raw`: the hook identifies keys by their scan code instead of the virtual key
+/// of the OS layout, and keys are output by scan code.
+pub static RAW_EVENT_MODE: AtomicBool = AtomicBool::new(false);
+
+/// The scan code to output a key with in `event-mode raw`.
+#[cfg(not(feature = "simulated_input"))]
+pub fn raw_mode_scancode(code: u16) -> Option<u16> {
+    if !RAW_EVENT_MODE.load(std::sync::atomic::Ordering::SeqCst) {
+        return None;
+    }
+    kanata_parser::keys::OsCode::from_u16(code).and_then(osc_to_u16)
+}
+
+/// The virtual key that a numpad key reports with NumLock on, for a virtual key that it reports
+/// with NumLock off. The navigation keys that report the same virtual keys are extended keys, so
+/// they are left alone.
+#[cfg(not(feature = "simulated_input"))]
+pub fn numlock_on_vk(vk: u32, extended: bool) -> Option<u32> {
+    if extended {
+        return None;
+    }
+    let numpad_vk = match vk as i32 {
+        VK_INSERT => VK_NUMPAD0,
+        VK_END => VK_NUMPAD1,
+        VK_DOWN => VK_NUMPAD2,
+        VK_NEXT => VK_NUMPAD3,
+        VK_LEFT => VK_NUMPAD4,
+        VK_CLEAR => VK_NUMPAD5,
+        VK_RIGHT => VK_NUMPAD6,
+        VK_HOME => VK_NUMPAD7,
+        VK_UP => VK_NUMPAD8,
+        VK_PRIOR => VK_NUMPAD9,
+        VK_DELETE => VK_DECIMAL,
+        _ => return None,
+    };
+    Some(numpad_vk as u32)
+}
+
+/// Whether the key is a dead key in the keyboard layout of the foreground window, with the
+/// current state of the shift, control, alt and caps lock keys.
+#[cfg(not(feature = "simulated_input"))]
+pub fn is_dead_key(vk: u32, scancode: u32) -> bool {
+    unsafe {
+        let thread = GetWindowThreadProcessId(GetForegroundWindow(), std::ptr::null_mut());
+        let layout = GetKeyboardLayout(thread);
+        let mut key_state = [0u8; 256];
+        for vk in [VK_SHIFT, VK_CONTROL, VK_MENU] {
+            if GetAsyncKeyState(vk) as u16 & 0x8000 != 0 {
+                key_state[vk as usize] = 0x80;
+            }
+        }
+        if GetKeyState(VK_CAPITAL) & 1 != 0 {
+            key_state[VK_CAPITAL as usize] = 0x01;
+        }
+        let mut buf = [0u16; 8];
+        // Flag 0x4 leaves the keyboard state of the layout unchanged. Otherwise the dead key
+        // would be consumed here and not compose with the next key.
+        ToUnicodeEx(
+            vk,
+            scancode,
+            key_state.as_ptr(),
+            buf.as_mut_ptr(),
+            buf.len() as i32,
+            0x4,
+            layout,
+        ) < 0
+    }
+}
+
 #[cfg(not(feature = "simulated_input"))]
 fn send_uc(c: char, up: bool) {
     log::debug!("sending unicode {c}");
@@ -227,6 +300,9 @@ fn send_key_sendinput(code: u16, is_key_up: bool) {
                 VK_KPENTER_FAKE => VK_RETURN as u16,
                 _ => code,
             };
+            if OsCode::from_u16(code).is_some_and(needs_extended_key) {
+                kb_input.dwFlags |= KEYEVENTF_EXTENDEDKEY;
+            }
         }
 
         let mut inputs: [INPUT; 1] = mem::zeroed();
@@ -235,3 +311,188 @@ fn send_key_sendinput(code: u16, is_key_up: bool) {
         SendInput(1, inputs.as_mut_ptr(), mem::size_of::<INPUT>() as _);
     }
 }
+
+/// Whether `SendInput` must set `KEYEVENTF_EXTENDEDKEY` for the key. Without the flag, e.g. `ralt`
+/// is received as `lalt` and `home` as numpad 7 by applications that check the flag. Right shift
+/// is not an extended key; it has its own scan code.
+pub fn needs_extended_key(code: kanata_parser::keys::OsCode) -> bool {
+    use kanata_parser::keys::OsCode::*;
+    matches!(
+        code,
+        KEY_RIGHTALT
+            | KEY_RIGHTCTRL
+            | KEY_LEFTMETA
+            | KEY_RIGHTMETA
+            | KEY_COMPOSE
+            | KEY_INSERT
+            | KEY_DELETE
+            | KEY_HOME
+            | KEY_END
+            | KEY_PAGEUP
+            | KEY_PAGEDOWN
+            | KEY_UP
+            | KEY_DOWN
+            | KEY_LEFT
+            | KEY_RIGHT
+            | KEY_KPENTER
+            | KEY_KPSLASH
+            | KEY_MUTE
+            | KEY_VOLUMEDOWN
+            | KEY_VOLUMEUP
+            | KEY_NEXTSONG
+            | KEY_PREVIOUSSONG
+            | KEY_PLAYPAUSE
+            | KEY_STOP
+            | KEY_STOPCD
+    )
+}
+
+/// Sends a hardware scan code as received by the low level hook, where `0xE000` marks an extended
+/// key.
+#[cfg(not(feature = "simulated_input"))]
+fn send_scancode_sendinput(scancode: u16, is_key_up: bool) {
+    unsafe {
+        let mut kb_input: KEYBDINPUT = mem::zeroed();
+        kb_input.dwFlags |= KEYEVENTF_SCANCODE;
+        if is_key_up {
+            kb_input.dwFlags |= KEYEVENTF_KEYUP;
+        }
+        if scancode & 0xE000 == 0xE000 {
+            kb_input.dwFlags |= KEYEVENTF_EXTENDEDKEY;
+        }
+        kb_input.wScan = scancode;
+        let mut inputs: [INPUT; 1] = mem::zeroed();
+        inputs[0].type_ = INPUT_KEYBOARD;
+        *inputs[0].u.ki_mut() = kb_input;
+        SendInput(1, inputs.as_mut_ptr(), mem::size_of::<INPUT>() as _);
+    }
+}
+
+/// The position at a percentage of the work area of a monitor, in the normalized virtual desktop
+/// coordinates of `MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_VIRTUALDESK`.
+#[cfg(not(feature = "simulated_input"))]
+pub fn monitor_pct_to_virtual_desk(
+    x: u8,
+    y: u8,
+    monitor: kanata_parser::custom_action::MonitorSelector,
+) -> Option<(u16, u16)> {
+    let work = monitor_work_area(monitor)?;
+    let pct_of = |lo: i32, hi: i32, pct: u8| {
+        i64::from(lo) + i64::from(hi - 1 - lo).max(0) * i64::from(pct) / 100
+    };
+    let (px, py) = (
+        pct_of(work.left, work.right, x),
+        pct_of(work.top, work.bottom, y),
+    );
+    let (vx, vy, vw, vh) = unsafe {
+        (
+            GetSystemMetrics(SM_XVIRTUALSCREEN),
+            GetSystemMetrics(SM_YVIRTUALSCREEN),
+            GetSystemMetrics(SM_CXVIRTUALSCREEN),
+            GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        )
+    };
+    if vw <= 1 || vh <= 1 {
+        return None;
+    }
+    let normalize = |p: i64, origin: i32, size: i32| {
+        ((p - i64::from(origin)) * 65535 / i64::from(size - 1)).clamp(0, 65535) as u16
+    };
+    Some((normalize(px, vx, vw), normalize(py, vy, vh)))
+}
+
+#[cfg(not(feature = "simulated_input"))]
+fn monitor_work_area(
+    monitor: kanata_parser::custom_action::MonitorSelector,
+) -> Option<winapi::shared::windef::RECT> {
+    use kanata_parser::custom_action::MonitorSelector;
+    use winapi::shared::windef::{HMONITOR, POINT};
+    unsafe {
+        let hmonitor: HMONITOR = match monitor {
+            MonitorSelector::Current => {
+                let mut cursor: POINT = mem::zeroed();
+                if GetCursorPos(&mut cursor) == 0 {
+                    return None;
+                }
+                MonitorFromPoint(cursor, MONITOR_DEFAULTTONEAREST)
+            }
+            // The primary monitor is the one with the origin at its top left corner.
+            MonitorSelector::Primary => {
+                MonitorFromPoint(POINT { x: 0, y: 0 }, MONITOR_DEFAULTTOPRIMARY)
+            }
+            MonitorSelector::Index(i) => {
+                let mut monitors: Vec<HMONITOR> = vec![];
+                EnumDisplayMonitors(
+                    std::ptr::null_mut(),
+                    std::ptr::null(),
+                    Some(collect_monitor),
+                    &mut monitors as *mut Vec<HMONITOR> as winapi::shared::minwindef::LPARAM,
+                );
+                *monitors.get(usize::from(i))?
+            }
+        };
+        let mut info: MONITORINFO = mem::zeroed();
+        info.cbSize = mem::size_of::<MONITORINFO>() as u32;
+        if hmonitor.is_null() || GetMonitorInfoW(hmonitor, &mut info) == 0 {
+            return None;
+        }
+        Some(info.rcWork)
+    }
+}
+
+#[cfg(not(feature = "simulated_input"))]
+unsafe extern "system" fn collect_monitor(
+    hmonitor: winapi::shared::windef::HMONITOR,
+    _hdc: winapi::shared::windef::HDC,
+    _rect: winapi::shared::windef::LPRECT,
+    monitors: winapi::shared::minwindef::LPARAM,
+) -> winapi::shared::minwindef::BOOL {
+    let monitors = unsafe { &mut *(monitors as *mut Vec<winapi::shared::windef::HMONITOR>) };
+    monitors.push(hmonitor);
+    winapi::shared::minwindef::TRUE
+}
+
+/// Whether the key is a dead key in the current keyboard layout, i.e. it types nothing on its own
+/// and instead composes with the next key.
+#[cfg(not(feature = "simulated_input"))]
+pub fn is_dead_key(osc: kanata_parser::keys::OsCode) -> bool {
+    // MAPVK_VK_TO_CHAR sets the top bit of the result for dead keys.
+    unsafe { MapVirtualKeyW(u32::from(osc), MAPVK_VK_TO_CHAR) & 0x8000_0000 != 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kanata_parser::keys::OsCode;
+
+    #[test]
+    fn extended_keys() {
+        for osc in [
+            OsCode::KEY_RIGHTALT,
+            OsCode::KEY_RIGHTCTRL,
+            OsCode::KEY_INSERT,
+            OsCode::KEY_DELETE,
+            OsCode::KEY_HOME,
+            OsCode::KEY_END,
+            OsCode::KEY_PAGEUP,
+            OsCode::KEY_PAGEDOWN,
+            OsCode::KEY_UP,
+            OsCode::KEY_DOWN,
+            OsCode::KEY_LEFT,
+            OsCode::KEY_RIGHT,
+        ] {
+            assert!(needs_extended_key(osc), "{osc:?}");
+        }
+        for osc in [
+            OsCode::KEY_RIGHTSHIFT,
+            OsCode::KEY_LEFTSHIFT,
+            OsCode::KEY_LEFTALT,
+            OsCode::KEY_LEFTCTRL,
+            OsCode::KEY_KP7,
+            OsCode::KEY_ENTER,
+            OsCode::KEY_A,
+        ] {
+            assert!(!needs_extended_key(osc), "{osc:?}");
+        }
+    }
+}
@@ -5,20 +5,36 @@ use crate::oskbd::*;
 use kanata_tcp_protocol::*;
 use parking_lot::Mutex;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::mpsc::SyncSender as Sender;
 
 #[cfg(feature = "tcp_server")]
 type HashMap<K, V> = rustc_hash::FxHashMap<K, V>;
 #[cfg(feature = "tcp_server")]
-use kanata_parser::cfg::SimpleSExpr;
+use kanata_parser::cfg::{SimpleSExpr, TcpServerFormat, TcpServerProtocol};
 #[cfg(feature = "tcp_server")]
-use std::io::Write;
+use parking_lot::MutexGuard;
+#[cfg(feature = "tcp_server")]
+use std::io::{self, Read, Write};
 #[cfg(feature = "tcp_server")]
 use std::net::{TcpListener, TcpStream};
+#[cfg(all(feature = "tcp_server", unix))]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(feature = "tcp_server")]
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+#[cfg(feature = "tcp_server")]
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "tcp_server")]
-pub type Connections = Arc<Mutex<HashMap<String, TcpStream>>>;
+mod tls;
+#[cfg(feature = "tcp_server")]
+pub use tls::{TlsConfig, load_tls_config};
+#[cfg(feature = "tcp_server")]
+mod ws;
+
+#[cfg(feature = "tcp_server")]
+pub type Connections = Arc<Mutex<HashMap<String, ConnectedClient>>>;
 
 #[cfg(not(feature = "tcp_server"))]
 pub type Connections = ();
@@ -26,14 +42,237 @@ pub type Connections = ();
 #[cfg(feature = "tcp_server")]
 use kanata_parser::custom_action::FakeKeyAction;
 
+/// HTTP path that WebSocket clients must request during the handshake.
+#[cfg(feature = "tcp_server")]
+pub const WS_PATH: &str = "/kanata";
+
+/// Write half of a client connection.
+///
+/// In WebSocket mode, every write is expected to be one complete newline-terminated JSON message,
+/// which is sent as a single text frame without the trailing newline.
+#[cfg(feature = "tcp_server")]
+pub enum ClientStream {
+    Raw(TcpStream),
+    Ws(ws::WsStream),
+    #[cfg(feature = "tls")]
+    Tls(tls::TlsStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+#[cfg(feature = "tcp_server")]
+impl ClientStream {
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(match self {
+            ClientStream::Raw(s) => ClientStream::Raw(s.try_clone()?),
+            #[cfg(feature = "tls")]
+            ClientStream::Tls(s) => ClientStream::Tls(s.try_clone()?),
+            #[cfg(unix)]
+            ClientStream::Unix(s) => ClientStream::Unix(s.try_clone()?),
+            ClientStream::Ws(s) => ClientStream::Ws(s.try_clone()?),
+        })
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            ClientStream::Raw(s) => s.peer_addr(),
+            ClientStream::Ws(s) => s.peer_addr(),
+            #[cfg(feature = "tls")]
+            ClientStream::Tls(s) => s.peer_addr(),
+            #[cfg(unix)]
+            ClientStream::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "unix socket peers have no network address",
+            )),
+        }
+    }
+
+    /// Sets the read timeout of the underlying socket, which is shared with all clones and with
+    /// the matching [`ClientReader`].
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            ClientStream::Raw(s) => s.set_read_timeout(timeout),
+            ClientStream::Ws(s) => s.set_read_timeout(timeout),
+            #[cfg(feature = "tls")]
+            ClientStream::Tls(s) => s.set_read_timeout(timeout),
+            #[cfg(unix)]
+            ClientStream::Unix(s) => s.set_read_timeout(timeout),
+        }
+    }
+}
+
+#[cfg(feature = "tcp_server")]
+impl Write for ClientStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ClientStream::Raw(s) => s.write(buf),
+            #[cfg(feature = "tls")]
+            ClientStream::Tls(s) => s.write(buf),
+            #[cfg(unix)]
+            ClientStream::Unix(s) => s.write(buf),
+            ClientStream::Ws(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ClientStream::Raw(s) => s.flush(),
+            #[cfg(feature = "tls")]
+            ClientStream::Tls(s) => s.flush(),
+            #[cfg(unix)]
+            ClientStream::Unix(s) => s.flush(),
+            ClientStream::Ws(s) => s.flush(),
+        }
+    }
+}
+
+/// Features the server supports, reported in `Hello` responses.
+#[cfg(feature = "tcp_server")]
+const SERVER_CAPABILITIES: &[&str] = &[
+    "reload",
+    "layer-names",
+    "fake-key-names",
+    "layer-change",
+    "hold-activated",
+    "tap-activated",
+    "current-layer-name",
+    "current-layer-info",
+    "fake-key",
+    "set-mouse",
+    "dynamic-macros",
+    "subscribe",
+    "key-inject",
+    "monitor-keys",
+    "sticky-layer",
+    "runtime-vars",
+    "get-state",
+    "ping",
+    "play-macro",
+    "temp-remap",
+    "key-stats",
+    "pressed-keys",
+    "log-level",
+];
+
+/// A client in the set of connections that event notifications are relayed to.
+#[cfg(feature = "tcp_server")]
+pub struct ConnectedClient {
+    pub stream: ClientStream,
+    /// Names of the event notifications the client has subscribed to, see [`SUBSCRIBABLE_EVENTS`].
+    /// `None` for clients that never sent `Subscribe` or `Hello`, which are sent every
+    /// notification as they were before subscriptions existed.
+    pub subscriptions: Option<rustc_hash::FxHashSet<String>>,
+    /// Whether the client enabled `MonitorKeys` and should receive `KeyEvent` messages.
+    pub monitor_keys: bool,
+    /// Capabilities agreed on in a versioned `Hello`. Clients that never sent one are `None` and
+    /// are sent every message type.
+    pub capabilities: Option<rustc_hash::FxHashSet<String>>,
+    /// Message format of `tcp-server-format` when the client connected.
+    pub format: TcpServerFormat,
+}
+
+/// Read half of a client connection.
+///
+/// WebSocket data frames are exposed as newline-separated bytes so that the same JSON stream
+/// deserializer handles both protocols.
+#[cfg(feature = "tcp_server")]
+pub enum ClientReader {
+    Raw(TcpStream),
+    Ws(ws::WsStream),
+    #[cfg(feature = "tls")]
+    Tls(tls::TlsStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+#[cfg(feature = "tcp_server")]
+impl Read for ClientReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        match self {
+            ClientReader::Raw(s) => s.read(out),
+            ClientReader::Ws(s) => s.read(out),
+            #[cfg(feature = "tls")]
+            ClientReader::Tls(s) => s.read(out),
+            #[cfg(unix)]
+            ClientReader::Unix(s) => s.read(out),
+        }
+    }
+}
+
+/// Sets up the read and write halves for a newly accepted connection, performing the TLS or
+/// WebSocket handshake if the server is configured for it.
+#[cfg(feature = "tcp_server")]
+fn accept_client(
+    stream: TcpStream,
+    protocol: TcpServerProtocol,
+    tls: Option<TlsConfig>,
+) -> io::Result<(ClientStream, ClientReader)> {
+    #[cfg(feature = "tls")]
+    if let Some(config) = tls {
+        // The parser rejects this combination, so it is never silently downgraded to raw TLS.
+        if protocol == TcpServerProtocol::Ws {
+            return Err(io::Error::other(
+                "TLS is not supported with tcp-server-protocol ws",
+            ));
+        }
+        let stream = tls::TlsStream::accept(stream, config)?;
+        return Ok((
+            ClientStream::Tls(stream.try_clone()?),
+            ClientReader::Tls(stream),
+        ));
+    }
+    #[cfg(not(feature = "tls"))]
+    let _ = tls;
+    match protocol {
+        TcpServerProtocol::Raw => Ok((
+            ClientStream::Raw(stream.try_clone()?),
+            ClientReader::Raw(stream),
+        )),
+        TcpServerProtocol::Ws => {
+            let stream = ws::WsStream::accept(stream)?;
+            Ok((
+                ClientStream::Ws(stream.try_clone()?),
+                ClientReader::Ws(stream),
+            ))
+        }
+    }
+}
+
+/// A message from the server, serialized in the format of `tcp-server-format`.
+#[cfg(feature = "tcp_server")]
+pub trait Encode {
+    fn encode(&self, format: TcpServerFormat) -> Vec<u8>;
+}
+
+#[cfg(feature = "tcp_server")]
+impl Encode for ServerMessage {
+    fn encode(&self, format: TcpServerFormat) -> Vec<u8> {
+        match format {
+            TcpServerFormat::Legacy => self.as_bytes(),
+            TcpServerFormat::Tagged => KanataMessage::from(self.clone()).as_bytes(),
+        }
+    }
+}
+
+#[cfg(feature = "tcp_server")]
+impl Encode for ServerResponse {
+    fn encode(&self, format: TcpServerFormat) -> Vec<u8> {
+        match format {
+            TcpServerFormat::Legacy => self.as_bytes(),
+            TcpServerFormat::Tagged => KanataMessage::from(self.clone()).as_bytes(),
+        }
+    }
+}
+
 #[cfg(feature = "tcp_server")]
 fn send_response(
-    stream: &mut TcpStream,
+    stream: &mut ClientStream,
     response: ServerResponse,
+    format: TcpServerFormat,
     connections: &Connections,
     addr: &str,
 ) -> bool {
-    if let Err(write_err) = stream.write_all(&response.as_bytes()) {
+    if let Err(write_err) = stream.write_all(&response.encode(format)) {
         log::error!("stream write error: {write_err}");
         connections.lock().remove(addr);
         return false;
@@ -51,65 +290,1133 @@ fn to_action(val: FakeKeyActionMessage) -> FakeKeyAction {
     }
 }
 
-/// Handles reload commands with optional wait/timeout for completion confirmation.
+/// Sets the maximum log level for `SetLogLevel` and returns the `LogLevel` response, or an
+/// `Error` if the level is not one of error, warn, info, debug or trace.
+#[cfg(feature = "tcp_server")]
+fn set_log_level(level: &str) -> ServerMessage {
+    use log::LevelFilter;
+    let new = match level.parse::<LevelFilter>() {
+        Ok(LevelFilter::Off) | Err(_) => {
+            return ServerMessage::Error {
+                msg: format!(
+                    "unknown log level: {level}, expected one of: error, warn, info, debug, trace"
+                ),
+            };
+        }
+        Ok(new) => new,
+    };
+    let previous = log::max_level();
+    log::set_max_level(new);
+    log::info!("tcp server SetLogLevel action: {previous} -> {new}");
+    ServerMessage::LogLevel {
+        previous: previous.as_str().to_lowercase(),
+        current: new.as_str().to_lowercase(),
+    }
+}
+
+/// Whether handling the command needs the state shared with the processing thread. Such commands
+/// are answered with an error rather than waiting beyond `tcp-command-timeout-ms` for it.
+#[cfg(feature = "tcp_server")]
+fn uses_processing_state(msg: &ClientMessage) -> bool {
+    !matches!(
+        msg,
+        ClientMessage::Hello { .. }
+            | ClientMessage::SetLogLevel { .. }
+            | ClientMessage::MonitorKeys { .. }
+    )
+}
+
+/// Takes the lock that was acquired to run a command, or acquires it again if the command
+/// already used it.
+#[cfg(feature = "tcp_server")]
+fn command_lock<'a>(
+    held: &mut Option<MutexGuard<'a, Kanata>>,
+    kanata: &'a Mutex<Kanata>,
+) -> MutexGuard<'a, Kanata> {
+    held.take().unwrap_or_else(|| kanata.lock())
+}
+
+/// Handles reload commands. If `wait` is true, the response is a `ReloadResult` sent once the
+/// reload completes or `timeout_ms` elapses, otherwise it is `Ok` once the reload is requested.
+/// `k` is the lock taken for the command, which is released before waiting.
 /// Returns false if the connection should be closed, true otherwise.
 #[cfg(feature = "tcp_server")]
+#[allow(clippy::too_many_arguments)]
 fn handle_reload_with_wait(
     reload_cmd: ClientMessage,
     wait: Option<bool>,
     timeout_ms: Option<u64>,
-    stream: &mut TcpStream,
+    stream: &mut ClientStream,
+    format: TcpServerFormat,
+    mut k: MutexGuard<'_, Kanata>,
     kanata: &Arc<Mutex<Kanata>>,
     connections: &Connections,
     addr: &str,
+    wakeup_channel: &Sender<KeyEvent>,
 ) -> bool {
-    let (response, reload_ok) = match kanata.lock().handle_client_command(reload_cmd) {
-        Ok(_) => (ServerResponse::Ok, true),
-        Err(e) => (
-            ServerResponse::Error {
-                msg: format!("{e}"),
-            },
-            false,
-        ),
+    let res = k.handle_client_command(reload_cmd);
+    drop(k);
+    if let Err(e) = res {
+        let response = ServerResponse::Error {
+            msg: format!("{e}"),
+        };
+        return send_response(stream, response, format, connections, addr);
+    }
+    if !wait.unwrap_or(false) {
+        return send_response(stream, ServerResponse::Ok, format, connections, addr);
+    }
+
+    // The processing loop may be blocked waiting for input, so wake it up to do the reload.
+    use kanata_parser::keys::*;
+    wakeup_channel
+        .send(KeyEvent::new(OsCode::KEY_RESERVED, KeyValue::WakeUp))
+        .expect("write key event");
+
+    let timeout_ms = timeout_ms.unwrap_or(5000);
+    let start = std::time::Instant::now();
+    let timeout_duration = std::time::Duration::from_millis(timeout_ms);
+    while start.elapsed() < timeout_duration {
+        if kanata.lock().is_reload_complete() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    let waited = start.elapsed();
+    let timed_out = waited >= timeout_duration;
+
+    let msg = if timed_out {
+        ServerMessage::ReloadResult {
+            success: false,
+            error: Some("reload did not complete before the timeout".to_string()),
+            duration_ms: waited.as_millis() as u64,
+            timeout_ms: Some(timeout_ms),
+        }
+    } else {
+        let k = kanata.lock();
+        let outcome = k.last_reload();
+        ServerMessage::ReloadResult {
+            success: outcome.error.is_none(),
+            error: outcome.error.clone(),
+            duration_ms: outcome.duration.as_millis() as u64,
+            timeout_ms: None,
+        }
     };
-    if !send_response(stream, response, connections, addr) {
+    if let Err(err) = stream.write_all(&msg.encode(format)) {
+        log::error!("Error writing ReloadResult: {err}");
+        connections.lock().remove(addr);
         return false;
     }
+    let _ = stream.flush();
+    true
+}
 
-    // If wait flag is set and reload succeeded, poll for completion
-    if reload_ok && wait.unwrap_or(false) {
-        let timeout_ms = timeout_ms.unwrap_or(5000);
-        let start = std::time::Instant::now();
-        let timeout_duration = std::time::Duration::from_millis(timeout_ms);
+/// Path of the Unix domain socket created by this process, so it can be removed on exit.
+#[cfg(all(feature = "tcp_server", unix))]
+static UNIX_SOCKET_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
 
-        while start.elapsed() < timeout_duration {
-            if kanata.lock().is_reload_complete() {
-                break;
-            }
-            std::thread::sleep(std::time::Duration::from_millis(50));
+/// Binds the Unix domain socket, replacing a stale socket file left behind by a previous run.
+/// The socket is only accessible by the current user.
+#[cfg(all(feature = "tcp_server", unix))]
+fn bind_unix_socket(path: &std::path::Path) -> io::Result<UnixListener> {
+    use std::os::unix::fs::FileTypeExt;
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
         }
-        let timed_out = start.elapsed() >= timeout_duration;
+        std::fs::remove_file(path)?;
+    }
+    // The socket is created with mode 0600, so that there is no window in which other users can
+    // connect. The umask is shared by all threads, so it is restored right away.
+    let old_umask = set_umask(0o177);
+    let listener = UnixListener::bind(path);
+    set_umask(old_umask);
+    let listener = listener?;
+    *UNIX_SOCKET_PATH.lock() = Some(path.to_owned());
+    log::info!("listening on unix socket {}", path.display());
+    Ok(listener)
+}
 
-        let ok = kanata.lock().last_reload_succeeded();
-        let msg = ServerMessage::ReloadResult {
-            ok,
-            timeout_ms: if timed_out { Some(timeout_ms) } else { None },
-        };
-        if let Err(err) = stream.write_all(&msg.as_bytes()) {
-            log::error!("Error writing ReloadResult: {err}");
-            connections.lock().remove(addr);
+/// Sets the file mode creation mask of the process and returns the previous mask.
+#[cfg(all(
+    feature = "tcp_server",
+    any(target_os = "linux", target_os = "android")
+))]
+fn set_umask(mask: u32) -> u32 {
+    use nix::sys::stat::{Mode, umask};
+    umask(Mode::from_bits_truncate(mask)).bits()
+}
+
+#[cfg(all(feature = "tcp_server", target_os = "macos"))]
+fn set_umask(mask: u32) -> u32 {
+    u32::from(unsafe { libc::umask(mask as libc::mode_t) })
+}
+
+/// Resolves the `unix-socket-path` defcfg option to a filesystem path.
+#[cfg(all(
+    feature = "tcp_server",
+    any(target_os = "linux", target_os = "android", target_os = "macos")
+))]
+pub fn resolve_unix_socket_path(path: &kanata_parser::cfg::UnixSocketPath) -> PathBuf {
+    use kanata_parser::cfg::UnixSocketPath;
+    match path {
+        UnixSocketPath::Path(p) => PathBuf::from(p),
+        UnixSocketPath::Default => {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            let uid = nix::unistd::getuid().as_raw();
+            #[cfg(target_os = "macos")]
+            let uid = unsafe { libc::getuid() };
+            PathBuf::from(format!("/run/user/{uid}/kanata.sock"))
+        }
+    }
+}
+
+/// Removes the Unix domain socket file, if one was created. Called on clean exit.
+pub fn remove_unix_socket() {
+    #[cfg(all(feature = "tcp_server", unix))]
+    if let Some(path) = UNIX_SOCKET_PATH.lock().take() {
+        match std::fs::remove_file(&path) {
+            Ok(()) => log::info!("removed unix socket {}", path.display()),
+            Err(e) => log::warn!("could not remove unix socket {}: {e}", path.display()),
+        }
+    }
+}
+
+/// Default for how long a client has to send its auth message when `tcp-server-token` is set.
+#[cfg(feature = "tcp_server")]
+const AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tokens are compared as zero-padded buffers of at least this many bytes, so that the time the
+/// comparison takes does not reveal the length of the configured token.
+#[cfg(feature = "tcp_server")]
+const AUTH_TOKEN_PAD_LEN: usize = 1024;
+
+/// Compares `auth` with `token` in constant time. Only the length of the longest of the two,
+/// if it exceeds [`AUTH_TOKEN_PAD_LEN`], affects how long this takes.
+#[cfg(feature = "tcp_server")]
+fn token_matches(auth: &str, token: &str) -> bool {
+    use subtle::ConstantTimeEq;
+    let len = AUTH_TOKEN_PAD_LEN.max(auth.len()).max(token.len());
+    let padded = |s: &str| {
+        let mut buf = vec![0u8; len];
+        buf[..s.len()].copy_from_slice(s.as_bytes());
+        buf
+    };
+    let same_len = (auth.len() as u64).ct_eq(&(token.len() as u64));
+    bool::from(padded(auth).ct_eq(&padded(token)) & same_len)
+}
+
+/// Reads the client's first message and checks it against the configured token.
+/// On failure an error is sent and false is returned; the caller should drop the connection.
+#[cfg(feature = "tcp_server")]
+fn authenticate<R: Read>(
+    de: &mut serde_json::Deserializer<serde_json::de::IoRead<R>>,
+    token: &str,
+    stream: &mut ClientStream,
+    format: TcpServerFormat,
+    addr: &str,
+) -> bool {
+    use serde::Deserialize;
+    let authorized = match ClientAuth::deserialize(de) {
+        Ok(ClientAuth { auth }) => token_matches(&auth, token),
+        Err(e) => {
+            log::warn!("client {addr} did not send an auth message: {e}");
+            false
+        }
+    };
+    let response = if authorized {
+        log::info!("client {addr} authenticated");
+        ServerResponse::Ok
+    } else {
+        log::warn!("client {addr} failed authentication, disconnecting them");
+        ServerResponse::Error {
+            msg: "authentication failed".to_string(),
+        }
+    };
+    let _ = stream.write_all(&response.encode(format));
+    authorized
+}
+
+/// Sustained rate of events that `KeyInject` may produce, summed over all clients.
+#[cfg(feature = "tcp_server")]
+const KEY_INJECT_EVENTS_PER_SEC: u32 = 1000;
+
+/// Token bucket that refills continuously at `rate` tokens per second, holding at most one
+/// second's worth of tokens.
+#[cfg(feature = "tcp_server")]
+pub struct TokenBucket {
+    rate: u32,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+#[cfg(feature = "tcp_server")]
+impl TokenBucket {
+    pub fn new(rate: u32) -> Self {
+        Self {
+            rate,
+            tokens: f64::from(rate),
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes `n` tokens if that many are available at `now`.
+    pub fn try_take(&mut self, n: u32, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * f64::from(self.rate)).min(f64::from(self.rate));
+        self.last_refill = now;
+        if self.tokens < f64::from(n) {
             return false;
         }
-        let _ = stream.flush();
+        self.tokens -= f64::from(n);
+        true
+    }
+}
+
+/// Whether any client may have `MonitorKeys` enabled. This is checked before doing any other
+/// work for a key event, so the event loops are not slowed down when nobody is monitoring.
+/// It can be stale after a monitoring client disconnects; the notification loop clears it once
+/// it finds no client to send a `KeyEvent` to.
+#[cfg(feature = "tcp_server")]
+pub static KEY_MONITOR_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Whether any client may have subscribed to `PressedKeys`. The processing loop only compares
+/// the pressed keys for changes while this is set. It is cleared by the notification loop once
+/// no client receives the notification.
+#[cfg(feature = "tcp_server")]
+pub static PRESSED_KEYS_SUBSCRIBED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "tcp_server")]
+struct KeyMonitor {
+    /// Only carries `KeyEvent` messages, so that a burst of key events can not crowd out other
+    /// notifications.
+    tx: Option<Sender<ServerMessage>>,
+    limiter: TokenBucket,
+    start: Instant,
+}
+
+#[cfg(feature = "tcp_server")]
+static KEY_MONITOR: Mutex<Option<KeyMonitor>> = Mutex::new(None);
+
+#[cfg(feature = "tcp_server")]
+fn with_key_monitor<R>(f: impl FnOnce(&mut KeyMonitor) -> R) -> R {
+    let mut monitor = KEY_MONITOR.lock();
+    f(monitor.get_or_insert_with(|| KeyMonitor {
+        tx: None,
+        limiter: TokenBucket::new(500),
+        start: Instant::now(),
+    }))
+}
+
+/// Sets the channel that `KeyEvent` messages are relayed to clients through and returns the one
+/// it replaces. It must not be the channel of the other notifications.
+#[cfg(feature = "tcp_server")]
+pub fn set_key_monitor_channel(tx: Option<Sender<ServerMessage>>) -> Option<Sender<ServerMessage>> {
+    with_key_monitor(|m| std::mem::replace(&mut m.tx, tx))
+}
+
+/// Channel of the notifications sent from outside the processing loop, e.g. `Ready`.
+#[cfg(feature = "tcp_server")]
+static NOTIFICATION_TX: Mutex<Option<Sender<ServerMessage>>> = Mutex::new(None);
+
+/// Sets the channel that `Ready` messages are relayed to clients through and returns the one it
+/// replaces.
+#[cfg(feature = "tcp_server")]
+pub fn set_notification_channel(
+    tx: Option<Sender<ServerMessage>>,
+) -> Option<Sender<ServerMessage>> {
+    std::mem::replace(&mut *NOTIFICATION_TX.lock(), tx)
+}
+
+/// Sets the `monitor-max-events-per-second` limit.
+#[cfg(feature = "tcp_server")]
+pub fn set_key_monitor_rate(events_per_sec: u16) {
+    with_key_monitor(|m| {
+        if m.limiter.rate != u32::from(events_per_sec) {
+            m.limiter = TokenBucket::new(events_per_sec.into());
+        }
+    });
+}
+
+/// Relays an input key event to clients that enabled `MonitorKeys`. Events are dropped rather
+/// than waited on when the rate limit is exceeded or the key monitor channel is full.
+pub fn monitor_key_event(_event: &KeyEvent) {
+    #[cfg(feature = "tcp_server")]
+    {
+        if !KEY_MONITOR_ACTIVE.load(Ordering::Relaxed) {
+            return;
+        }
+        let value = match _event.value {
+            KeyValue::Press => KeyEventValue::Press,
+            KeyValue::Release => KeyEventValue::Release,
+            KeyValue::Repeat => KeyEventValue::Repeat,
+            KeyValue::Tap => KeyEventValue::Tap,
+            KeyValue::WakeUp => return,
+        };
+        with_key_monitor(|m| {
+            let now = Instant::now();
+            let Some(tx) = &m.tx else {
+                return;
+            };
+            if !m.limiter.try_take(1, now) {
+                log::trace!("key monitor rate limit exceeded, dropping {_event:?}");
+                return;
+            }
+            let msg = ServerMessage::KeyEvent {
+                code: kanata_parser::keys::oscode_to_str(_event.code),
+                value,
+                timestamp_ms: now.duration_since(m.start).as_millis() as u64,
+                device_id: _event.device_id,
+            };
+            if tx.try_send(msg).is_err() {
+                log::trace!("key monitor channel full, dropping {_event:?}");
+            }
+        });
+    }
+}
+
+/// The most recent `Ready` notification, sent to clients that subscribe to it later. `None`
+/// until the input devices are seized and while they are released.
+#[cfg(feature = "tcp_server")]
+static READY: Mutex<Option<ServerMessage>> = Mutex::new(None);
+
+/// Notifies clients subscribed to `Ready` that the input devices are seized. Must only be called
+/// once the devices are actually seized, not when merely opened.
+pub fn notify_ready(_devices: Vec<String>, _recovered: bool) {
+    #[cfg(feature = "tcp_server")]
+    {
+        let msg = ServerMessage::Ready {
+            devices: _devices,
+            recovered: _recovered,
+        };
+        *READY.lock() = Some(msg.clone());
+        if let Some(tx) = &*NOTIFICATION_TX.lock()
+            && let Err(e) = tx.try_send(msg)
+        {
+            log::error!("could not send Ready event notification: {e}");
+        }
     }
-    true
+}
+
+/// Records that the input devices were released, so new subscribers are not told they are ready.
+pub fn notify_input_released() {
+    #[cfg(feature = "tcp_server")]
+    READY.lock().take();
+}
+
+/// Held by a client's thread. Frees the client's slot towards `tcp-max-clients` and removes it
+/// from the notification list when the thread ends, however the client disconnected.
+#[cfg(feature = "tcp_server")]
+struct ClientSlot {
+    addr: String,
+    connections: Connections,
+    client_count: Arc<AtomicUsize>,
+}
+
+#[cfg(feature = "tcp_server")]
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        if self.connections.lock().remove(&self.addr).is_some() {
+            log::info!("tcp client {} disconnected", self.addr);
+        }
+        self.client_count.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Spawns a thread that sets up a newly accepted client with `connect`, registers it for
+/// notifications and handles its commands until it disconnects. The client is refused if
+/// `tcp-max-clients` are already connected.
+#[cfg(feature = "tcp_server")]
+#[allow(clippy::too_many_arguments)]
+fn serve_client(
+    connect: impl FnOnce() -> io::Result<(ClientStream, ClientReader)> + Send + 'static,
+    addr: String,
+    kanata: Arc<Mutex<Kanata>>,
+    connections: Connections,
+    wakeup_channel: Sender<KeyEvent>,
+    key_inject_limiter: Arc<Mutex<TokenBucket>>,
+    client_count: Arc<AtomicUsize>,
+    auth_timeout: Duration,
+) {
+    use crate::kanata::handle_fakekey_action;
+    use kanata_parser::cfg::FAKE_KEY_ROW;
+
+    let (max_clients, command_timeout, format) = {
+        let k = kanata.lock();
+        (
+            k.tcp_max_clients,
+            k.tcp_command_timeout,
+            k.tcp_server_format,
+        )
+    };
+    let refused = client_count.fetch_add(1, Ordering::SeqCst) >= max_clients;
+    if refused {
+        client_count.fetch_sub(1, Ordering::SeqCst);
+        log::warn!("refusing tcp client {addr}: tcp-max-clients of {max_clients} reached");
+    }
+
+    // Everything, including the TLS or WebSocket handshake and authentication, happens on the
+    // client's own thread so that a slow client cannot hold up the accept loop.
+    std::thread::spawn(move || {
+        let _slot = (!refused).then(|| ClientSlot {
+            addr: addr.clone(),
+            connections: connections.clone(),
+            client_count,
+        });
+        let (mut stream, client_reader) = match connect() {
+            Ok(halves) => halves,
+            Err(e) => {
+                log::warn!("failed to set up client connection {addr}, dropping it: {e}");
+                return;
+            }
+        };
+        if refused {
+            let response = ServerResponse::Error {
+                msg: format!("too many clients, the limit is {max_clients}"),
+            };
+            let _ = stream.write_all(&response.encode(format));
+            return;
+        }
+        let mut de = serde_json::Deserializer::from_reader(client_reader);
+        let token = kanata.lock().tcp_server_token.clone();
+        if let Some(token) = token {
+            // A client that never authenticates must not hold its slot forever.
+            if let Err(e) = stream.set_read_timeout(Some(auth_timeout)) {
+                log::warn!("failed to set auth timeout for client {addr}, dropping it: {e}");
+                return;
+            }
+            if !authenticate(&mut de, &token, &mut stream, format, &addr) {
+                return;
+            }
+            if let Err(e) = stream.set_read_timeout(None) {
+                log::warn!("failed to clear auth timeout for client {addr}, dropping it: {e}");
+                return;
+            }
+        }
+
+        {
+            let mut k = kanata.lock();
+            log::info!(
+                "new client connection, sending initial LayerChange event to inform them of current layer"
+            );
+            let cur_layer = k.layout.bm().current_layer();
+            let msg = ServerMessage::LayerChange {
+                new: k.layer_info[cur_layer].name.clone(),
+                old: None,
+            };
+            drop(k);
+            if let Err(e) = stream.write_all(&msg.encode(format)) {
+                log::warn!("failed to write to stream, dropping it: {e:?}");
+                return;
+            }
+        }
+
+        // Until the client subscribes or says hello, it is sent every event notification.
+        connections.lock().insert(
+            addr.clone(),
+            ConnectedClient {
+                stream: stream.try_clone().expect("stream is clonable"),
+                subscriptions: None,
+                monitor_keys: false,
+                capabilities: None,
+                format,
+            },
+        );
+        let reader: Box<dyn Iterator<Item = serde_json::Result<ClientMessage>>> = match format {
+            TcpServerFormat::Legacy => Box::new(de.into_iter::<ClientMessage>()),
+            TcpServerFormat::Tagged => Box::new(de.into_iter::<KanataMessage>().map(|msg| {
+                msg.and_then(|msg| ClientMessage::try_from(msg).map_err(serde::de::Error::custom))
+            })),
+        };
+
+        log::info!("listening for incoming messages {addr}");
+
+        for v in reader {
+            match v {
+                Ok(event) => {
+                    log::debug!("tcp server received command: {:?}", event);
+                    // The lock is kept for the command so that the processing thread can not
+                    // take it between checking that it is free and running the command.
+                    let mut held = None;
+                    if uses_processing_state(&event) {
+                        held = kanata.try_lock_for(command_timeout);
+                        if held.is_none() {
+                            log::warn!(
+                                "tcp client {addr}: processing thread is busy, not running {event:?}"
+                            );
+                            let msg = ServerMessage::Error {
+                                msg: "processing_thread_busy".to_string(),
+                            };
+                            if let Err(err) = stream.write_all(&msg.encode(format)) {
+                                log::error!("Error writing busy response: {err}");
+                                connections.lock().remove(&addr);
+                                break;
+                            }
+                            continue;
+                        }
+                    }
+                    match event {
+                        ClientMessage::ChangeLayer { new } => {
+                            command_lock(&mut held, &kanata).change_layer(new);
+                        }
+                        ClientMessage::RequestLayerNames {} => {
+                            let msg = ServerMessage::LayerNames {
+                                names: kanata
+                                    .lock()
+                                    .layer_info
+                                    .iter()
+                                    .map(|info| info.name.clone())
+                                    .collect::<Vec<_>>(),
+                            };
+                            match stream.write_all(&msg.encode(format)) {
+                                Ok(_) => {}
+                                Err(err) => log::error!("server could not send response: {err}"),
+                            }
+                        }
+                        ClientMessage::RequestFakeKeyNames {} => {
+                            let msg = ServerMessage::FakeKeyNames {
+                                names: kanata
+                                    .lock()
+                                    .virtual_keys
+                                    .keys()
+                                    .cloned()
+                                    .collect::<Vec<_>>(),
+                            };
+                            match stream.write_all(&msg.encode(format)) {
+                                Ok(_) => {}
+                                Err(err) => log::error!("server could not send response: {err}"),
+                            }
+                        }
+                        ClientMessage::ActOnFakeKey { name, action } => {
+                            let mut k = command_lock(&mut held, &kanata);
+                            let index = match k.virtual_keys.get(&name) {
+                                Some(index) => Some(*index as u16),
+                                None => {
+                                    if let Err(e) = stream.write_all(
+                                        &ServerMessage::Error {
+                                            msg: format!("unknown virtual/fake key: {name}"),
+                                        }
+                                        .encode(format),
+                                    ) {
+                                        log::error!("stream write error: {e}");
+                                        connections.lock().remove(&addr);
+                                        break;
+                                    }
+                                    continue;
+                                }
+                            };
+                            if let Some(index) = index {
+                                log::info!("tcp server fake-key action: {name},{action:?}");
+                                handle_fakekey_action(
+                                    to_action(action),
+                                    k.layout.bm(),
+                                    FAKE_KEY_ROW,
+                                    index,
+                                );
+                            }
+                            drop(k);
+                        }
+                        ClientMessage::SetMouse { x, y } => {
+                            log::info!("tcp server SetMouse action: x {x} y {y}");
+                            match command_lock(&mut held, &kanata).kbd_out.set_mouse(x, y) {
+                                Ok(_) => {
+                                    log::info!(
+                                        "sucessfully did set mouse position to: x {x} y {y}"
+                                    );
+                                }
+                                Err(e) => {
+                                    log::error!("Failed to set mouse position: {}", e);
+                                }
+                            }
+                        }
+                        ClientMessage::RequestCurrentLayerInfo {} => {
+                            let mut k = command_lock(&mut held, &kanata);
+                            let cur_layer = k.layout.bm().current_layer();
+                            let msg = ServerMessage::CurrentLayerInfo {
+                                name: k.layer_info[cur_layer].name.clone(),
+                                cfg_text: k.layer_info[cur_layer].cfg_text.clone(),
+                            };
+                            drop(k);
+                            match stream.write_all(&msg.encode(format)) {
+                                Ok(_) => {}
+                                Err(err) => log::error!(
+                                    "Error writing response to RequestCurrentLayerInfo: {err}"
+                                ),
+                            }
+                        }
+                        ClientMessage::RequestCurrentLayerName {} => {
+                            let mut k = command_lock(&mut held, &kanata);
+                            let cur_layer = k.layout.bm().current_layer();
+                            let msg = ServerMessage::CurrentLayerName {
+                                name: k.layer_info[cur_layer].name.clone(),
+                            };
+                            drop(k);
+                            match stream.write_all(&msg.encode(format)) {
+                                Ok(_) => {}
+                                Err(err) => log::error!(
+                                    "Error writing response to RequestCurrentLayerName: {err}"
+                                ),
+                            }
+                        }
+                        ClientMessage::Ping {} => {
+                            let msg = command_lock(&mut held, &kanata).pong();
+                            match stream.write_all(&msg.encode(format)) {
+                                Ok(_) => {
+                                    let _ = stream.flush();
+                                }
+                                Err(err) => log::error!("Error writing response to Ping: {err}"),
+                            }
+                        }
+                        ClientMessage::GetState {} => {
+                            let msg = command_lock(&mut held, &kanata).state();
+                            match stream.write_all(&msg.encode(format)) {
+                                Ok(_) => {}
+                                Err(err) => {
+                                    log::error!("Error writing response to GetState: {err}")
+                                }
+                            }
+                        }
+                        ClientMessage::PlayDynamicMacro { slot } => {
+                            log::info!("tcp server PlayDynamicMacro action: {slot}");
+                            let res = command_lock(&mut held, &kanata).play_dynamic_macro(slot);
+                            if let Err(e) = res
+                                && let Err(e) = stream.write_all(
+                                    &ServerMessage::Error { msg: e.to_string() }.encode(format),
+                                )
+                            {
+                                log::error!("stream write error: {e}");
+                                connections.lock().remove(&addr);
+                                break;
+                            }
+                        }
+                        ClientMessage::SetVar { name, value } => {
+                            log::info!("tcp server SetVar action: {name} {value}");
+                            let res =
+                                command_lock(&mut held, &kanata).set_runtime_var(&name, value);
+                            if let Err(e) = res
+                                && let Err(e) = stream.write_all(
+                                    &ServerMessage::Error { msg: e.to_string() }.encode(format),
+                                )
+                            {
+                                log::error!("stream write error: {e}");
+                                connections.lock().remove(&addr);
+                                break;
+                            }
+                        }
+                        ClientMessage::PlayMacro { name } => {
+                            log::info!("tcp server PlayMacro action: {name}");
+                            let msg =
+                                match command_lock(&mut held, &kanata).named_macros.queue(&name) {
+                                    Ok(queued) => ServerMessage::MacroResult { queued },
+                                    Err(e) => ServerMessage::Error { msg: e.to_string() },
+                                };
+                            match stream.write_all(&msg.encode(format)) {
+                                Ok(_) => {}
+                                Err(err) => {
+                                    log::error!("Error writing response to PlayMacro: {err}")
+                                }
+                            }
+                        }
+                        ClientMessage::TempRemap {
+                            from,
+                            to,
+                            duration_ms,
+                        } => {
+                            log::info!("tcp server TempRemap action: {from} {to} {duration_ms}");
+                            let res = command_lock(&mut held, &kanata).add_temp_remap(
+                                &from,
+                                &to,
+                                duration_ms,
+                            );
+                            if let Err(e) = res
+                                && let Err(e) = stream.write_all(
+                                    &ServerMessage::Error { msg: e.to_string() }.encode(format),
+                                )
+                            {
+                                log::error!("stream write error: {e}");
+                                connections.lock().remove(&addr);
+                                break;
+                            }
+                        }
+                        ClientMessage::RequestPressedKeys {} => {
+                            let msg = command_lock(&mut held, &kanata).pressed_keys();
+                            match stream.write_all(&msg.encode(format)) {
+                                Ok(_) => {}
+                                Err(err) => log::error!(
+                                    "Error writing response to RequestPressedKeys: {err}"
+                                ),
+                            }
+                        }
+                        ClientMessage::GetStats {} => {
+                            let msg = ServerMessage::KeyStats(
+                                command_lock(&mut held, &kanata).key_stats.counts(),
+                            );
+                            match stream.write_all(&msg.encode(format)) {
+                                Ok(_) => {}
+                                Err(err) => {
+                                    log::error!("Error writing response to GetStats: {err}")
+                                }
+                            }
+                        }
+                        ClientMessage::SetLogLevel { level } => {
+                            let msg = set_log_level(&level);
+                            match stream.write_all(&msg.encode(format)) {
+                                Ok(_) => {}
+                                Err(err) => {
+                                    log::error!("Error writing response to SetLogLevel: {err}")
+                                }
+                            }
+                        }
+                        ClientMessage::ResetStats {} => {
+                            log::info!("tcp server ResetStats action");
+                            command_lock(&mut held, &kanata).key_stats.reset();
+                        }
+                        ClientMessage::ClearState {} => {
+                            log::info!("tcp server ClearState action");
+                            if let Err(e) = command_lock(&mut held, &kanata).clear_state() {
+                                log::error!("failed to clear state: {e}");
+                            }
+                        }
+                        ClientMessage::ClearDynamicMacro { slot } => {
+                            log::info!("tcp server ClearDynamicMacro action: {slot}");
+                            command_lock(&mut held, &kanata).clear_dynamic_macro(slot);
+                        }
+                        ClientMessage::Subscribe(events) => {
+                            if let Some(unknown) = events
+                                .iter()
+                                .find(|e| !SUBSCRIBABLE_EVENTS.contains(&e.as_str()))
+                            {
+                                let msg = ServerMessage::Error {
+                                    msg: format!(
+                                        "unknown event: {unknown}, expected one of: {}",
+                                        SUBSCRIBABLE_EVENTS.join(", ")
+                                    ),
+                                };
+                                if let Err(e) = stream.write_all(&msg.encode(format)) {
+                                    log::error!("stream write error: {e}");
+                                    connections.lock().remove(&addr);
+                                    break;
+                                }
+                                continue;
+                            }
+                            log::info!("tcp client {addr} subscribed to {events:?}");
+                            let subscribed_layer_change = events.iter().any(|e| e == "LayerChange");
+                            let subscribed_ready = events.iter().any(|e| e == "Ready");
+                            if events.iter().any(|e| e == "PressedKeys") {
+                                PRESSED_KEYS_SUBSCRIBED.store(true, Ordering::Relaxed);
+                            }
+                            if let Some(client) = connections.lock().get_mut(&addr) {
+                                client.subscriptions = Some(events.into_iter().collect());
+                            }
+                            // Inform new layer change subscribers of the current layer.
+                            if subscribed_layer_change {
+                                let mut k = command_lock(&mut held, &kanata);
+                                let cur_layer = k.layout.bm().current_layer();
+                                let msg = ServerMessage::LayerChange {
+                                    new: k.layer_info[cur_layer].name.clone(),
+                                    old: None,
+                                };
+                                drop(k);
+                                if let Err(e) = stream.write_all(&msg.encode(format)) {
+                                    log::error!("stream write error: {e}");
+                                    connections.lock().remove(&addr);
+                                    break;
+                                }
+                            }
+                            // Inform new ready subscribers if the devices are already seized.
+                            let ready = READY.lock().clone();
+                            if subscribed_ready
+                                && let Some(msg) = ready
+                                && let Err(e) = stream.write_all(&msg.encode(format))
+                            {
+                                log::error!("stream write error: {e}");
+                                connections.lock().remove(&addr);
+                                break;
+                            }
+                        }
+                        ClientMessage::KeyInject { key, value } => {
+                            use kanata_parser::keys::*;
+                            let Some(code) = str_to_oscode(&key) else {
+                                let msg = ServerMessage::Error {
+                                    msg: format!("unknown key: {key}"),
+                                };
+                                if let Err(e) = stream.write_all(&msg.encode(format)) {
+                                    log::error!("stream write error: {e}");
+                                    connections.lock().remove(&addr);
+                                    break;
+                                }
+                                continue;
+                            };
+                            let values: &[KeyValue] = match value {
+                                KeyInjectValue::Press => &[KeyValue::Press],
+                                KeyInjectValue::Release => &[KeyValue::Release],
+                                KeyInjectValue::Tap => &[KeyValue::Press, KeyValue::Release],
+                            };
+                            if !key_inject_limiter
+                                .lock()
+                                .try_take(values.len() as u32, Instant::now())
+                            {
+                                let msg = ServerMessage::Error {
+                                    msg: format!(
+                                        "KeyInject rate limit of {KEY_INJECT_EVENTS_PER_SEC} \
+                                         events per second exceeded"
+                                    ),
+                                };
+                                if let Err(e) = stream.write_all(&msg.encode(format)) {
+                                    log::error!("stream write error: {e}");
+                                    connections.lock().remove(&addr);
+                                    break;
+                                }
+                                continue;
+                            }
+                            let mut k = command_lock(&mut held, &kanata);
+                            for value in values.iter().copied() {
+                                log::info!(
+                                    "tcp server KeyInject from {addr}: {key} {value:?} (source: ipc)"
+                                );
+                                k.key_stats.mark_injected(code, value);
+                            }
+                            // Sending can block until the processing thread reads events, which
+                            // it does without the lock.
+                            drop(k);
+                            for value in values.iter().copied() {
+                                let event = KeyEvent {
+                                    injected: true,
+                                    ..KeyEvent::new(code, value)
+                                };
+                                wakeup_channel.send(event).expect("write key event");
+                            }
+                        }
+                        ClientMessage::MonitorKeys { enabled } => {
+                            log::info!("tcp client {addr} MonitorKeys: {enabled}");
+                            let mut connections = connections.lock();
+                            if let Some(client) = connections.get_mut(&addr) {
+                                client.monitor_keys = enabled;
+                            }
+                            KEY_MONITOR_ACTIVE.store(
+                                connections.values().any(|c| c.monitor_keys),
+                                Ordering::Relaxed,
+                            );
+                        }
+                        ClientMessage::RequestDynamicMacros {} => {
+                            let msg = command_lock(&mut held, &kanata).dynamic_macro_listing();
+                            match stream.write_all(&msg.encode(format)) {
+                                Ok(_) => {}
+                                Err(err) => log::error!(
+                                    "Error writing response to RequestDynamicMacros: {err}"
+                                ),
+                            }
+                        }
+                        ClientMessage::Hello {
+                            client_version: Some(client_version),
+                            capabilities,
+                        } => {
+                            let supported = parse_protocol_version(&client_version)
+                                .zip(parse_protocol_version(MIN_SUPPORTED_PROTOCOL_VERSION))
+                                .is_some_and(|(client, min)| client >= min);
+                            if !supported {
+                                log::warn!(
+                                    "closing tcp client {addr} with unsupported protocol version {client_version}"
+                                );
+                                let msg = ServerMessage::Error {
+                                    msg: format!(
+                                        "protocol version {client_version} is not supported, the minimum is {MIN_SUPPORTED_PROTOCOL_VERSION}"
+                                    ),
+                                };
+                                let _ = stream.write_all(&msg.encode(format));
+                                connections.lock().remove(&addr);
+                                break;
+                            }
+                            let negotiated: rustc_hash::FxHashSet<String> = capabilities
+                                .into_iter()
+                                .filter(|c| SERVER_CAPABILITIES.contains(&c.as_str()))
+                                .collect();
+                            log::info!(
+                                "tcp client {addr} protocol {client_version}, capabilities {negotiated:?}"
+                            );
+                            if let Some(client) = connections.lock().get_mut(&addr) {
+                                client.capabilities = Some(negotiated);
+                                client.subscriptions.get_or_insert_default();
+                            }
+                            let msg = ServerMessage::Hello {
+                                server_version: PROTOCOL_VERSION.to_string(),
+                                min_supported: MIN_SUPPORTED_PROTOCOL_VERSION.to_string(),
+                                capabilities: SERVER_CAPABILITIES
+                                    .iter()
+                                    .map(|c| c.to_string())
+                                    .collect(),
+                            };
+                            if let Err(err) = stream.write_all(&msg.encode(format)) {
+                                log::error!("Error writing Hello response: {err}");
+                                connections.lock().remove(&addr);
+                                break;
+                            }
+                        }
+                        // New command: Hello - capability detection
+                        ClientMessage::Hello {
+                            client_version: None,
+                            ..
+                        } => {
+                            if let Some(client) = connections.lock().get_mut(&addr) {
+                                client.subscriptions.get_or_insert_default();
+                            }
+                            let version = env!("CARGO_PKG_VERSION").to_string();
+                            let capabilities =
+                                SERVER_CAPABILITIES.iter().map(|c| c.to_string()).collect();
+                            let msg = ServerMessage::HelloOk {
+                                version,
+                                protocol: 1,
+                                capabilities,
+                            };
+                            match stream.write_all(&msg.encode(format)) {
+                                Ok(_) => {
+                                    let _ = stream.flush();
+                                }
+                                Err(err) => {
+                                    log::error!("Error writing HelloOk response: {err}");
+                                    connections.lock().remove(&addr);
+                                    break;
+                                }
+                            }
+                        }
+                        // Reload commands with optional wait/timeout
+                        ClientMessage::Reload { wait, timeout_ms } => {
+                            log::info!("tcp server Reload action");
+                            if !handle_reload_with_wait(
+                                ClientMessage::Reload { wait, timeout_ms },
+                                wait,
+                                timeout_ms,
+                                &mut stream,
+                                format,
+                                command_lock(&mut held, &kanata),
+                                &kanata,
+                                &connections,
+                                &addr,
+                                &wakeup_channel,
+                            ) {
+                                break;
+                            }
+                        }
+                        ClientMessage::ReloadNext { wait, timeout_ms } => {
+                            log::info!("tcp server ReloadNext action");
+                            if !handle_reload_with_wait(
+                                ClientMessage::ReloadNext { wait, timeout_ms },
+                                wait,
+                                timeout_ms,
+                                &mut stream,
+                                format,
+                                command_lock(&mut held, &kanata),
+                                &kanata,
+                                &connections,
+                                &addr,
+                                &wakeup_channel,
+                            ) {
+                                break;
+                            }
+                        }
+                        ClientMessage::ReloadPrev { wait, timeout_ms } => {
+                            log::info!("tcp server ReloadPrev action");
+                            if !handle_reload_with_wait(
+                                ClientMessage::ReloadPrev { wait, timeout_ms },
+                                wait,
+                                timeout_ms,
+                                &mut stream,
+                                format,
+                                command_lock(&mut held, &kanata),
+                                &kanata,
+                                &connections,
+                                &addr,
+                                &wakeup_channel,
+                            ) {
+                                break;
+                            }
+                        }
+                        ClientMessage::ReloadNum {
+                            index,
+                            wait,
+                            timeout_ms,
+                        } => {
+                            log::info!("tcp server ReloadNum action: index {index}");
+                            if !handle_reload_with_wait(
+                                ClientMessage::ReloadNum {
+                                    index,
+                                    wait,
+                                    timeout_ms,
+                                },
+                                wait,
+                                timeout_ms,
+                                &mut stream,
+                                format,
+                                command_lock(&mut held, &kanata),
+                                &kanata,
+                                &connections,
+                                &addr,
+                                &wakeup_channel,
+                            ) {
+                                break;
+                            }
+                        }
+                        ClientMessage::ReloadFile {
+                            path,
+                            wait,
+                            timeout_ms,
+                        } => {
+                            log::info!("tcp server ReloadFile action: path {path}");
+                            if !handle_reload_with_wait(
+                                ClientMessage::ReloadFile {
+                                    path,
+                                    wait,
+                                    timeout_ms,
+                                },
+                                wait,
+                                timeout_ms,
+                                &mut stream,
+                                format,
+                                command_lock(&mut held, &kanata),
+                                &kanata,
+                                &connections,
+                                &addr,
+                                &wakeup_channel,
+                            ) {
+                                break;
+                            }
+                        }
+                    }
+                    drop(held);
+                    use kanata_parser::keys::*;
+                    wakeup_channel
+                        .send(KeyEvent::new(OsCode::KEY_RESERVED, KeyValue::WakeUp))
+                        .expect("write key event");
+                }
+                Err(e) => {
+                    log::warn!("client sent an invalid message, disconnecting them. Err: {e:?}");
+                    // Send proper error response for malformed JSON
+                    let response = ServerResponse::Error {
+                        msg: format!("Failed to deserialize command: {e}"),
+                    };
+                    let _ = stream.write_all(&response.encode(format));
+                    connections.lock().remove(&addr);
+                    break;
+                }
+            }
+        }
+    });
 }
 
 #[cfg(feature = "tcp_server")]
 pub struct TcpServer {
-    pub address: SocketAddr,
+    pub address: Option<SocketAddr>,
+    pub unix_socket_path: Option<PathBuf>,
     pub connections: Connections,
     pub wakeup_channel: Sender<KeyEvent>,
+    pub key_inject_limiter: Arc<Mutex<TokenBucket>>,
+    /// Number of connected clients, including those that have not authenticated yet.
+    pub client_count: Arc<AtomicUsize>,
+    /// How long a client has to send its auth message when `tcp-server-token` is set.
+    pub auth_timeout: Duration,
 }
 
 #[cfg(not(feature = "tcp_server"))]
@@ -118,349 +1425,119 @@ pub struct TcpServer {
 }
 
 impl TcpServer {
+    /// Either of `address` and `unix_socket_path` may be omitted; the TCP and Unix socket
+    /// listeners are started independently and share the same set of connected clients.
     #[cfg(feature = "tcp_server")]
-    pub fn new(address: SocketAddr, wakeup_channel: Sender<KeyEvent>) -> Self {
+    pub fn new(
+        address: Option<SocketAddr>,
+        unix_socket_path: Option<PathBuf>,
+        wakeup_channel: Sender<KeyEvent>,
+    ) -> Self {
         Self {
             address,
+            unix_socket_path,
             connections: Arc::new(Mutex::new(HashMap::default())),
             wakeup_channel,
+            key_inject_limiter: Arc::new(Mutex::new(TokenBucket::new(KEY_INJECT_EVENTS_PER_SEC))),
+            client_count: Arc::new(AtomicUsize::new(0)),
+            auth_timeout: AUTH_TIMEOUT,
         }
     }
 
     #[cfg(not(feature = "tcp_server"))]
-    pub fn new(_address: SocketAddr, _wakeup_channel: Sender<KeyEvent>) -> Self {
+    pub fn new(
+        _address: Option<SocketAddr>,
+        _unix_socket_path: Option<PathBuf>,
+        _wakeup_channel: Sender<KeyEvent>,
+    ) -> Self {
         Self { connections: () }
     }
 
     #[cfg(feature = "tcp_server")]
     pub fn start(&mut self, kanata: Arc<Mutex<Kanata>>) {
-        use kanata_parser::cfg::FAKE_KEY_ROW;
-
-        use crate::kanata::handle_fakekey_action;
-
-        let listener = TcpListener::bind(self.address).expect("TCP server starts");
-
-        let connections = self.connections.clone();
-        let wakeup_channel = self.wakeup_channel.clone();
-
-        std::thread::spawn(move || {
-            for stream in listener.incoming() {
-                match stream {
-                    Ok(mut stream) => {
-                        {
-                            let k = kanata.lock();
-                            log::info!(
-                                "new client connection, sending initial LayerChange event to inform them of current layer"
-                            );
-                            if let Err(e) = stream.write(
-                                &ServerMessage::LayerChange {
-                                    new: k.layer_info[k.layout.b().current_layer()].name.clone(),
+        if let Some(address) = self.address {
+            let listener = TcpListener::bind(address).expect("TCP server starts");
+            if !address.ip().is_loopback() {
+                let suggestion = match kanata.lock().tcp_server_token {
+                    Some(_) => "",
+                    None => " Set tcp-server-token in defcfg to require clients to authenticate.",
+                };
+                log::warn!(
+                    "TCP server is listening on {address}, which other devices on the network \
+                     may be able to connect to.{suggestion}"
+                );
+            }
+            let connections = self.connections.clone();
+            let wakeup_channel = self.wakeup_channel.clone();
+            let key_inject_limiter = self.key_inject_limiter.clone();
+            let client_count = self.client_count.clone();
+            let auth_timeout = self.auth_timeout;
+            let kanata = kanata.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(stream) => {
+                            let (protocol, tls) = {
+                                let k = kanata.lock();
+                                (k.tcp_server_protocol, k.tcp_server_tls.clone())
+                            };
+                            let addr = match stream.peer_addr() {
+                                Ok(addr) => addr.to_string(),
+                                Err(e) => {
+                                    log::warn!("failed to get peer address, using fallback: {e:?}");
+                                    format!("unknown_{}", std::ptr::addr_of!(stream) as usize)
                                 }
-                                .as_bytes(),
-                            ) {
-                                log::warn!("failed to write to stream, dropping it: {e:?}");
-                                continue;
-                            }
+                            };
+                            serve_client(
+                                move || accept_client(stream, protocol, tls),
+                                addr,
+                                kanata.clone(),
+                                connections.clone(),
+                                wakeup_channel.clone(),
+                                key_inject_limiter.clone(),
+                                client_count.clone(),
+                                auth_timeout,
+                            );
                         }
+                        Err(_) => log::error!("not able to accept client connection"),
+                    }
+                }
+            });
+        }
 
-                        let addr = match stream.peer_addr() {
-                            Ok(addr) => addr.to_string(),
-                            Err(e) => {
-                                log::warn!("failed to get peer address, using fallback: {e:?}");
-                                format!("unknown_{}", std::ptr::addr_of!(stream) as usize)
-                            }
-                        };
-
-                        connections.lock().insert(
-                            addr.clone(),
-                            stream.try_clone().expect("stream is clonable"),
-                        );
-                        let reader = serde_json::Deserializer::from_reader(
-                            stream.try_clone().expect("stream is clonable"),
-                        )
-                        .into_iter::<ClientMessage>();
-
-                        log::info!("listening for incoming messages {addr}");
-
-                        let connections = connections.clone();
-                        let kanata = kanata.clone();
-                        let wakeup_channel = wakeup_channel.clone();
-                        std::thread::spawn(move || {
-                            for v in reader {
-                                match v {
-                                    Ok(event) => {
-                                        log::debug!("tcp server received command: {:?}", event);
-                                        match event {
-                                            ClientMessage::ChangeLayer { new } => {
-                                                kanata.lock().change_layer(new);
-                                            }
-                                            ClientMessage::RequestLayerNames {} => {
-                                                let msg = ServerMessage::LayerNames {
-                                                    names: kanata
-                                                        .lock()
-                                                        .layer_info
-                                                        .iter()
-                                                        .map(|info| info.name.clone())
-                                                        .collect::<Vec<_>>(),
-                                                };
-                                                match stream.write_all(&msg.as_bytes()) {
-                                                    Ok(_) => {}
-                                                    Err(err) => log::error!(
-                                                        "server could not send response: {err}"
-                                                    ),
-                                                }
-                                            }
-                                            ClientMessage::RequestFakeKeyNames {} => {
-                                                let msg = ServerMessage::FakeKeyNames {
-                                                    names: kanata
-                                                        .lock()
-                                                        .virtual_keys
-                                                        .keys()
-                                                        .cloned()
-                                                        .collect::<Vec<_>>(),
-                                                };
-                                                match stream.write_all(&msg.as_bytes()) {
-                                                    Ok(_) => {}
-                                                    Err(err) => log::error!(
-                                                        "server could not send response: {err}"
-                                                    ),
-                                                }
-                                            }
-                                            ClientMessage::ActOnFakeKey { name, action } => {
-                                                let mut k = kanata.lock();
-                                                let index = match k.virtual_keys.get(&name) {
-                                                    Some(index) => Some(*index as u16),
-                                                    None => {
-                                                        if let Err(e) = stream.write_all(
-                                                            &ServerMessage::Error {
-                                                                msg: format!(
-                                                                    "unknown virtual/fake key: {name}"
-                                                                ),
-                                                            }
-                                                            .as_bytes(),
-                                                        ) {
-                                                            log::error!("stream write error: {e}");
-                                                            connections.lock().remove(&addr);
-                                                            break;
-                                                        }
-                                                        continue;
-                                                    }
-                                                };
-                                                if let Some(index) = index {
-                                                    log::info!(
-                                                        "tcp server fake-key action: {name},{action:?}"
-                                                    );
-                                                    handle_fakekey_action(
-                                                        to_action(action),
-                                                        k.layout.bm(),
-                                                        FAKE_KEY_ROW,
-                                                        index,
-                                                    );
-                                                }
-                                                drop(k);
-                                            }
-                                            ClientMessage::SetMouse { x, y } => {
-                                                log::info!(
-                                                    "tcp server SetMouse action: x {x} y {y}"
-                                                );
-                                                match kanata.lock().kbd_out.set_mouse(x, y) {
-                                                    Ok(_) => {
-                                                        log::info!(
-                                                            "sucessfully did set mouse position to: x {x} y {y}"
-                                                        );
-                                                    }
-                                                    Err(e) => {
-                                                        log::error!(
-                                                            "Failed to set mouse position: {}",
-                                                            e
-                                                        );
-                                                    }
-                                                }
-                                            }
-                                            ClientMessage::RequestCurrentLayerInfo {} => {
-                                                let mut k = kanata.lock();
-                                                let cur_layer = k.layout.bm().current_layer();
-                                                let msg = ServerMessage::CurrentLayerInfo {
-                                                    name: k.layer_info[cur_layer].name.clone(),
-                                                    cfg_text: k.layer_info[cur_layer]
-                                                        .cfg_text
-                                                        .clone(),
-                                                };
-                                                drop(k);
-                                                match stream.write_all(&msg.as_bytes()) {
-                                                    Ok(_) => {}
-                                                    Err(err) => log::error!(
-                                                        "Error writing response to RequestCurrentLayerInfo: {err}"
-                                                    ),
-                                                }
-                                            }
-                                            ClientMessage::RequestCurrentLayerName {} => {
-                                                let mut k = kanata.lock();
-                                                let cur_layer = k.layout.bm().current_layer();
-                                                let msg = ServerMessage::CurrentLayerName {
-                                                    name: k.layer_info[cur_layer].name.clone(),
-                                                };
-                                                drop(k);
-                                                match stream.write_all(&msg.as_bytes()) {
-                                                    Ok(_) => {}
-                                                    Err(err) => log::error!(
-                                                        "Error writing response to RequestCurrentLayerName: {err}"
-                                                    ),
-                                                }
-                                            }
-                                            // New command: Hello - capability detection
-                                            ClientMessage::Hello {} => {
-                                                let version = env!("CARGO_PKG_VERSION").to_string();
-                                                let capabilities = vec![
-                                                    "reload".to_string(),
-                                                    "layer-names".to_string(),
-                                                    "fake-key-names".to_string(),
-                                                    "layer-change".to_string(),
-                                                    "hold-activated".to_string(),
-                                                    "tap-activated".to_string(),
-                                                    "current-layer-name".to_string(),
-                                                    "current-layer-info".to_string(),
-                                                    "fake-key".to_string(),
-                                                    "set-mouse".to_string(),
-                                                ];
-                                                let msg = ServerMessage::HelloOk {
-                                                    version,
-                                                    protocol: 1,
-                                                    capabilities,
-                                                };
-                                                match stream.write_all(&msg.as_bytes()) {
-                                                    Ok(_) => {
-                                                        let _ = stream.flush();
-                                                    }
-                                                    Err(err) => {
-                                                        log::error!(
-                                                            "Error writing HelloOk response: {err}"
-                                                        );
-                                                        connections.lock().remove(&addr);
-                                                        break;
-                                                    }
-                                                }
-                                            }
-                                            // Reload commands with optional wait/timeout
-                                            ClientMessage::Reload { wait, timeout_ms } => {
-                                                log::info!("tcp server Reload action");
-                                                if !handle_reload_with_wait(
-                                                    ClientMessage::Reload { wait, timeout_ms },
-                                                    wait,
-                                                    timeout_ms,
-                                                    &mut stream,
-                                                    &kanata,
-                                                    &connections,
-                                                    &addr,
-                                                ) {
-                                                    break;
-                                                }
-                                            }
-                                            ClientMessage::ReloadNext { wait, timeout_ms } => {
-                                                log::info!("tcp server ReloadNext action");
-                                                if !handle_reload_with_wait(
-                                                    ClientMessage::ReloadNext { wait, timeout_ms },
-                                                    wait,
-                                                    timeout_ms,
-                                                    &mut stream,
-                                                    &kanata,
-                                                    &connections,
-                                                    &addr,
-                                                ) {
-                                                    break;
-                                                }
-                                            }
-                                            ClientMessage::ReloadPrev { wait, timeout_ms } => {
-                                                log::info!("tcp server ReloadPrev action");
-                                                if !handle_reload_with_wait(
-                                                    ClientMessage::ReloadPrev { wait, timeout_ms },
-                                                    wait,
-                                                    timeout_ms,
-                                                    &mut stream,
-                                                    &kanata,
-                                                    &connections,
-                                                    &addr,
-                                                ) {
-                                                    break;
-                                                }
-                                            }
-                                            ClientMessage::ReloadNum {
-                                                index,
-                                                wait,
-                                                timeout_ms,
-                                            } => {
-                                                log::info!(
-                                                    "tcp server ReloadNum action: index {index}"
-                                                );
-                                                if !handle_reload_with_wait(
-                                                    ClientMessage::ReloadNum {
-                                                        index,
-                                                        wait,
-                                                        timeout_ms,
-                                                    },
-                                                    wait,
-                                                    timeout_ms,
-                                                    &mut stream,
-                                                    &kanata,
-                                                    &connections,
-                                                    &addr,
-                                                ) {
-                                                    break;
-                                                }
-                                            }
-                                            ClientMessage::ReloadFile {
-                                                path,
-                                                wait,
-                                                timeout_ms,
-                                            } => {
-                                                log::info!(
-                                                    "tcp server ReloadFile action: path {path}"
-                                                );
-                                                if !handle_reload_with_wait(
-                                                    ClientMessage::ReloadFile {
-                                                        path,
-                                                        wait,
-                                                        timeout_ms,
-                                                    },
-                                                    wait,
-                                                    timeout_ms,
-                                                    &mut stream,
-                                                    &kanata,
-                                                    &connections,
-                                                    &addr,
-                                                ) {
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                        use kanata_parser::keys::*;
-                                        wakeup_channel
-                                            .send(KeyEvent {
-                                                code: OsCode::KEY_RESERVED,
-                                                value: KeyValue::WakeUp,
-                                            })
-                                            .expect("write key event");
-                                    }
-                                    Err(e) => {
-                                        log::warn!(
-                                            "client sent an invalid message, disconnecting them. Err: {e:?}"
-                                        );
-                                        // Send proper error response for malformed JSON
-                                        let response = ServerResponse::Error {
-                                            msg: format!("Failed to deserialize command: {e}"),
-                                        };
-                                        let _ = stream.write_all(&response.as_bytes());
-                                        connections.lock().remove(&addr);
-                                        break;
-                                    }
-                                }
-                            }
-                        });
+        #[cfg(unix)]
+        if let Some(path) = self.unix_socket_path.clone() {
+            let listener = bind_unix_socket(&path).expect("Unix socket server starts");
+            let connections = self.connections.clone();
+            let wakeup_channel = self.wakeup_channel.clone();
+            let key_inject_limiter = self.key_inject_limiter.clone();
+            let client_count = self.client_count.clone();
+            let auth_timeout = self.auth_timeout;
+            std::thread::spawn(move || {
+                let mut client_num = 0usize;
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(stream) => {
+                            client_num += 1;
+                            serve_client(
+                                move || {
+                                    let reader = stream.try_clone()?;
+                                    Ok((ClientStream::Unix(stream), ClientReader::Unix(reader)))
+                                },
+                                format!("unix_{client_num}"),
+                                kanata.clone(),
+                                connections.clone(),
+                                wakeup_channel.clone(),
+                                key_inject_limiter.clone(),
+                                client_count.clone(),
+                                auth_timeout,
+                            );
+                        }
+                        Err(_) => log::error!("not able to accept client connection"),
                     }
-                    Err(_) => log::error!("not able to accept client connection"),
                 }
-            }
-        });
+            });
+        }
     }
 
     #[cfg(not(feature = "tcp_server"))]
@@ -480,3 +1557,45 @@ pub fn simple_sexpr_to_json_array(exprs: &[SimpleSExpr]) -> serde_json::Value {
 
     serde_json::Value::Array(result)
 }
+
+#[cfg(all(test, feature = "tcp_server"))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_is_created_with_owner_only_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join(format!("kanata-test-{}.sock", std::process::id()));
+        let _listener = bind_unix_socket(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        remove_unix_socket();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn token_matches_only_the_same_token() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secreT", "secret"));
+        assert!(!token_matches("secre", "secret"));
+        assert!(!token_matches("secret\0", "secret"));
+        assert!(!token_matches("", "secret"));
+        let long = "x".repeat(AUTH_TOKEN_PAD_LEN + 1);
+        assert!(token_matches(&long, &long));
+        assert!(!token_matches(&long[1..], &long));
+    }
+
+    #[test]
+    fn token_bucket_limits_rate() {
+        let mut bucket = TokenBucket::new(1000);
+        let start = bucket.last_refill;
+        assert!(bucket.try_take(1000, start));
+        assert!(!bucket.try_take(1, start));
+        assert!(!bucket.try_take(2, start + Duration::from_millis(1)));
+        assert!(bucket.try_take(2, start + Duration::from_millis(3)));
+        // Idle time refills no more than the capacity.
+        assert!(!bucket.try_take(1001, start + Duration::from_secs(10)));
+        assert!(bucket.try_take(1000, start + Duration::from_secs(10)));
+    }
+}
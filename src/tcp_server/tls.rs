@@ -0,0 +1,161 @@
+//! TLS for TCP server connections, configured by `tcp-server-tls-cert` and `tcp-server-tls-key`.
+
+use kanata_parser::cfg::CfgOptions;
+
+#[cfg(feature = "tls")]
+use parking_lot::Mutex;
+#[cfg(feature = "tls")]
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
+#[cfg(feature = "tls")]
+use rustls::{ServerConfig, ServerConnection};
+#[cfg(feature = "tls")]
+use std::io::{self, Read, Write};
+#[cfg(feature = "tls")]
+use std::net::{SocketAddr, TcpStream};
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+#[cfg(feature = "tls")]
+use std::time::Duration;
+
+/// Certificate and key that accepted connections are encrypted with.
+#[cfg(feature = "tls")]
+pub type TlsConfig = Arc<ServerConfig>;
+
+/// Without the `tls` feature, there is never a TLS configuration.
+#[cfg(not(feature = "tls"))]
+#[derive(Debug, Clone)]
+pub enum TlsConfig {}
+
+/// Loads the TLS configuration if the configuration file enables TLS.
+#[cfg(feature = "tls")]
+pub fn load_tls_config(opts: &CfgOptions) -> anyhow::Result<Option<TlsConfig>> {
+    use anyhow::Context;
+    let (Some(cert), Some(key)) = (&opts.tcp_server_tls_cert, &opts.tcp_server_tls_key) else {
+        return Ok(None);
+    };
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("could not read tcp-server-tls-cert {cert}"))?;
+    if certs.is_empty() {
+        anyhow::bail!("tcp-server-tls-cert {cert} contains no certificates");
+    }
+    let key = PrivateKeyDer::from_pem_file(key)
+        .with_context(|| format!("could not read tcp-server-tls-key {key}"))?;
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .context("invalid tcp-server-tls-cert or tcp-server-tls-key")?;
+    Ok(Some(Arc::new(config)))
+}
+
+#[cfg(not(feature = "tls"))]
+pub fn load_tls_config(opts: &CfgOptions) -> anyhow::Result<Option<TlsConfig>> {
+    if opts.tcp_server_tls_cert.is_some() {
+        anyhow::bail!("tcp-server-tls-cert needs kanata to be built with the tls feature");
+    }
+    Ok(None)
+}
+
+/// An encrypted client connection. Clones share the TLS session, so that notifications can be
+/// written while another clone waits for client messages, and so that records written by
+/// different clones never interleave.
+#[cfg(feature = "tls")]
+pub struct TlsStream {
+    conn: Arc<Mutex<ServerConnection>>,
+    sock: TcpStream,
+}
+
+#[cfg(feature = "tls")]
+impl TlsStream {
+    /// Performs the TLS handshake with a newly accepted client.
+    pub fn accept(sock: TcpStream, config: TlsConfig) -> io::Result<Self> {
+        let mut conn = ServerConnection::new(config).map_err(io::Error::other)?;
+        // Bound the handshake so a stalled client does not hold its thread forever.
+        sock.set_read_timeout(Some(Duration::from_secs(5)))?;
+        while conn.is_handshaking() {
+            conn.complete_io(&mut &sock)
+                .map_err(|e| io::Error::other(format!("TLS handshake failed: {e}")))?;
+        }
+        sock.set_read_timeout(None)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            sock,
+        })
+    }
+
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            conn: self.conn.clone(),
+            sock: self.sock.try_clone()?,
+        })
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.sock.peer_addr()
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.sock.set_read_timeout(timeout)
+    }
+}
+
+/// Sends the TLS records that the session has queued up.
+#[cfg(feature = "tls")]
+fn write_pending(conn: &mut ServerConnection, mut sock: &TcpStream) -> io::Result<()> {
+    while conn.wants_write() {
+        conn.write_tls(&mut sock)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "tls")]
+impl Read for TlsStream {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let mut buf = [0u8; 4096];
+        loop {
+            match self.conn.lock().reader().read(out) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                res => return res,
+            }
+            // The session is not locked while waiting for the client, so that writes from
+            // other clones are not held up.
+            let n = self.sock.read(&mut buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            let mut conn = self.conn.lock();
+            let mut received = &buf[..n];
+            while !received.is_empty() {
+                conn.read_tls(&mut received)?;
+                conn.process_new_packets().map_err(io::Error::other)?;
+            }
+            write_pending(&mut conn, &self.sock)?;
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The session buffers a limited amount of data. All of `buf` is written while the session
+        // is locked, so that messages written by other clones are not interleaved with it.
+        let mut conn = self.conn.lock();
+        let mut written = 0;
+        while written < buf.len() {
+            let n = conn.writer().write(&buf[written..])?;
+            write_pending(&mut conn, &self.sock)?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            written += n;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut conn = self.conn.lock();
+        conn.writer().flush()?;
+        write_pending(&mut conn, &self.sock)
+    }
+}
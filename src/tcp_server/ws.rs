@@ -0,0 +1,177 @@
+//! WebSocket framing for TCP server connections, configured by `tcp-server-protocol ws`.
+
+use parking_lot::Mutex;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::{Message, WebSocket};
+
+use super::WS_PATH;
+
+/// A WebSocket client connection. Clones share the WebSocket session, so that responses,
+/// notifications and the automatic pong and close replies never interleave their frames.
+pub struct WsStream {
+    ws: Arc<Mutex<WebSocket<WsSocket>>>,
+    sock: TcpStream,
+    /// Payload of the most recent data frame plus a trailing newline.
+    buf: Vec<u8>,
+    /// How much of `buf` has already been read out.
+    pos: usize,
+}
+
+/// The socket as seen by the shared WebSocket. After the handshake, reads only return the bytes
+/// that a reading clone has received, so that the session is not locked while waiting for the
+/// client.
+struct WsSocket {
+    sock: TcpStream,
+    received: Vec<u8>,
+    handshake_done: bool,
+}
+
+impl Read for WsSocket {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.received.is_empty() {
+            if !self.handshake_done {
+                return self.sock.read(out);
+            }
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        let n = self.received.len().min(out.len());
+        out[..n].copy_from_slice(&self.received[..n]);
+        self.received.drain(..n);
+        Ok(n)
+    }
+}
+
+impl Write for WsSocket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sock.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.sock.flush()
+    }
+}
+
+impl WsStream {
+    /// Performs the WebSocket handshake with a newly accepted client.
+    #[allow(clippy::result_large_err)] // the handshake callback signature is fixed by tungstenite
+    pub fn accept(sock: TcpStream) -> io::Result<Self> {
+        let check_path = |req: &Request, resp: Response| {
+            if req.uri().path() == WS_PATH {
+                Ok(resp)
+            } else {
+                let mut err: ErrorResponse =
+                    ErrorResponse::new(Some(format!("websocket path must be {WS_PATH}")));
+                *err.status_mut() = tungstenite::http::StatusCode::NOT_FOUND;
+                Err(err)
+            }
+        };
+        // Bound the handshake so a stalled client does not hold its thread forever.
+        sock.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let socket = WsSocket {
+            sock: sock.try_clone()?,
+            received: vec![],
+            handshake_done: false,
+        };
+        let mut ws = tungstenite::accept_hdr(socket, check_path)
+            .map_err(|e| io::Error::other(format!("websocket handshake failed: {e}")))?;
+        ws.get_mut().handshake_done = true;
+        sock.set_read_timeout(None)?;
+        Ok(Self {
+            ws: Arc::new(Mutex::new(ws)),
+            sock,
+            buf: vec![],
+            pos: 0,
+        })
+    }
+
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            ws: self.ws.clone(),
+            sock: self.sock.try_clone()?,
+            buf: vec![],
+            pos: 0,
+        })
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.sock.peer_addr()
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.sock.set_read_timeout(timeout)
+    }
+}
+
+fn ws_to_io_error(e: tungstenite::Error) -> io::Error {
+    match e {
+        tungstenite::Error::Io(e) => e,
+        e => io::Error::other(e),
+    }
+}
+
+/// Data frames are exposed as newline-separated bytes so that the same JSON stream deserializer
+/// handles both protocols.
+impl Read for WsStream {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let mut received = [0u8; 4096];
+        while self.pos >= self.buf.len() {
+            let msg = self.ws.lock().read();
+            let mut payload = match msg {
+                Ok(Message::Text(t)) => t.as_bytes().to_vec(),
+                Ok(Message::Binary(b)) => b.to_vec(),
+                Ok(Message::Close(_)) => {
+                    let _ = self.ws.lock().flush();
+                    return Ok(0);
+                }
+                Ok(_) => continue,
+                Err(tungstenite::Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => {
+                    // The session is not locked while waiting for the client, so that writes
+                    // from other clones are not held up.
+                    let n = self.sock.read(&mut received)?;
+                    if n == 0 {
+                        return Ok(0);
+                    }
+                    self.ws
+                        .lock()
+                        .get_mut()
+                        .received
+                        .extend_from_slice(&received[..n]);
+                    continue;
+                }
+                Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                    return Ok(0);
+                }
+                Err(e) => return Err(ws_to_io_error(e)),
+            };
+            payload.push(b'\n');
+            self.buf = payload;
+            self.pos = 0;
+        }
+        let n = (self.buf.len() - self.pos).min(out.len());
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Every write is expected to be one complete newline-terminated JSON message, which is sent as
+/// a single text frame without the trailing newline.
+impl Write for WsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text =
+            std::str::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.ws
+            .lock()
+            .send(Message::text(text.trim_end_matches('\n').to_owned()))
+            .map_err(ws_to_io_error)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.ws.lock().flush().map_err(ws_to_io_error)
+    }
+}
@@ -33,3 +33,44 @@ fn block_does_not_block_wheel() {
         result
     );
 }
+
+/// Sends key events the way the event loops do: events of keys that are not mapped are written
+/// straight out instead of being processed.
+fn simulate_event_loop(cfg: &str, events: &[(&str, KeyValue)]) -> String {
+    init_log();
+    let _lk = match CFG_PARSE_LOCK.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let mut k = Kanata::new_from_str(cfg, Default::default()).expect("failed to parse cfg");
+    for &(key, value) in events {
+        let code = str_to_oscode(key).expect("valid keycode");
+        if crate::MAPPED_KEYS.lock().contains(&code) {
+            k.handle_input_event(&KeyEvent::new(code, value))
+                .expect("input handles fine");
+        } else {
+            k.kbd_out.write_key(code, value).expect("writes");
+        }
+        for _ in 0..10 {
+            let _ = k.tick_ms(1, &None);
+        }
+    }
+    k.kbd_out.outputs.events.join(" ").no_time()
+}
+
+#[test]
+fn passthrough_keys_bypass_remapping() {
+    let result = simulate_event_loop(
+        "(defcfg process-unmapped-keys yes passthrough-keys (b))
+        (defsrc a b)
+        (deflayer base c d)",
+        &[
+            ("a", KeyValue::Press),
+            ("a", KeyValue::Release),
+            ("b", KeyValue::Press),
+            ("b", KeyValue::Release),
+        ],
+    )
+    .to_ascii();
+    assert_eq!("dn:C up:C dn:B up:B", result);
+}
@@ -102,3 +102,58 @@ fn caps_word_custom_toggle_does_toggle() {
         result
     );
 }
+
+const EXTRA_CFG: &str = r##"
+ (defsrc 7 8)
+ (deflayer base
+     (caps-word 1000 (kp-))
+     (caps-word-custom 200 (a b min) () ((spc min)))
+ )
+"##;
+
+#[test]
+fn caps_word_extra_nonterminal_keys() {
+    let result = simulate(
+        EXTRA_CFG,
+        "d:7 u:7 d:a u:a d:kp- u:kp- d:a u:a d:spc u:spc d:a u:a t:50",
+    )
+    .no_time();
+    assert_eq!(
+        "out:↓LShift out:↓A out:↑LShift out:↑A \
+         out:↓KpMinus out:↑KpMinus out:↓LShift out:↓A out:↑LShift out:↑A \
+         out:↓Space out:↑Space out:↓A out:↑A",
+        result
+    );
+}
+
+#[test]
+fn caps_word_custom_rewrites_keys() {
+    let result = simulate(
+        EXTRA_CFG,
+        "d:8 u:8 d:a u:a d:spc u:spc d:b u:b d:1 u:1 d:spc u:spc t:50",
+    )
+    .no_time();
+    assert_eq!(
+        "out:↓LShift out:↓A out:↑LShift out:↑A \
+         out:↓LShift out:↓Minus out:↑LShift out:↑Minus \
+         out:↓LShift out:↓B out:↑LShift out:↑B \
+         out:↓Kb1 out:↑Kb1 out:↓Space out:↑Space",
+        result
+    );
+}
+
+#[test]
+fn caps_word_custom_rewrite_refreshes_timeout() {
+    let result = simulate(
+        EXTRA_CFG,
+        "d:8 u:8 d:a u:a t:150 d:spc u:spc t:150 d:a u:a t:201 d:spc u:spc t:10",
+    )
+    .no_time();
+    assert_eq!(
+        "out:↓LShift out:↓A out:↑LShift out:↑A \
+         out:↓LShift out:↓Minus out:↑LShift out:↑Minus \
+         out:↓LShift out:↓A out:↑LShift out:↑A \
+         out:↓Space out:↑Space",
+        result
+    );
+}
@@ -500,3 +500,174 @@ d:t u:t t:10
         result
     );
 }
+
+static CHORD_PARTICIPANTS_WITH_TAP_HOLD: &str = "\
+(defcfg concurrent-tap-hold yes)
+(defsrc a s d x)
+(deflayer base (tap-hold 200 200 a lctl) (tap-hold 200 200 s lalt) d x)
+(defchordsv2
+  (a s) c 50 all-released ()
+)";
+
+#[test]
+fn sim_chord_participant_tap_hold_chord_wins() {
+    let result = simulate(
+        CHORD_PARTICIPANTS_WITH_TAP_HOLD,
+        "d:a t:10 d:s t:50 u:a t:10 u:s t:300",
+    )
+    .to_ascii();
+    assert_eq!("t:10ms dn:C t:62ms up:C", result);
+}
+
+#[test]
+fn sim_chord_participant_tap_hold_chord_held_past_hold_timeout() {
+    let result = simulate(
+        CHORD_PARTICIPANTS_WITH_TAP_HOLD,
+        "d:a t:10 d:s t:10 u:s t:300 u:a t:10",
+    )
+    .to_ascii();
+    assert_eq!("t:10ms dn:C t:312ms up:C", result);
+}
+
+#[test]
+fn sim_chord_participant_tap_hold_tap() {
+    let result = simulate(CHORD_PARTICIPANTS_WITH_TAP_HOLD, "d:a t:30 u:a t:300").to_ascii();
+    assert_eq!("t:32ms dn:A t:6ms up:A", result);
+    let result = simulate(CHORD_PARTICIPANTS_WITH_TAP_HOLD, "d:a t:100 u:a t:50").to_ascii();
+    assert_eq!("t:100ms dn:A t:6ms up:A", result);
+}
+
+#[test]
+fn sim_chord_participant_tap_hold_hold_timer_starts_at_press() {
+    // The chord window of 50ms does not delay the hold;
+    // the 200ms timeout is counted from the original press.
+    let result = simulate(CHORD_PARTICIPANTS_WITH_TAP_HOLD, "d:a t:300 u:a t:50").to_ascii();
+    assert_eq!("t:198ms dn:LCtrl t:102ms up:LCtrl", result);
+}
+
+#[test]
+fn sim_chord_participant_tap_hold_roll_after_window() {
+    let result = simulate(
+        CHORD_PARTICIPANTS_WITH_TAP_HOLD,
+        "d:a t:60 d:s t:20 u:a t:20 u:s t:300",
+    )
+    .to_ascii();
+    assert_eq!("t:101ms dn:A t:7ms dn:S t:6ms up:A t:1ms up:S", result);
+}
+
+#[test]
+fn sim_chord_participant_tap_hold_second_participant_after_hold() {
+    let result = simulate(
+        CHORD_PARTICIPANTS_WITH_TAP_HOLD,
+        "d:a t:250 d:s t:10 u:s t:10 u:a t:50",
+    )
+    .to_ascii();
+    assert_eq!(
+        "t:198ms dn:LCtrl t:64ms dn:S t:6ms up:S t:2ms up:LCtrl",
+        result
+    );
+}
+
+#[test]
+fn sim_chord_participant_tap_hold_interrupted_by_non_participant() {
+    // The non-participant ends the chord window
+    // and then waits behind the tap-hold like it would without chords.
+    let result = simulate(
+        CHORD_PARTICIPANTS_WITH_TAP_HOLD,
+        "d:a t:10 d:x t:10 u:x t:300 u:a t:50",
+    )
+    .to_ascii();
+    assert_eq!(
+        "t:198ms dn:LCtrl t:1ms dn:X t:1ms up:X t:120ms up:LCtrl",
+        result
+    );
+    let result = simulate(
+        CHORD_PARTICIPANTS_WITH_TAP_HOLD,
+        "d:a t:10 d:d t:10 u:d t:10 u:a t:300",
+    )
+    .to_ascii();
+    assert_eq!("t:30ms dn:A t:6ms dn:D t:1ms up:D t:1ms up:A", result);
+}
+
+#[test]
+fn sim_chord_participant_tap_hold_press_interrupted_by_non_participant() {
+    let cfg = CHORD_PARTICIPANTS_WITH_TAP_HOLD.replace("(tap-hold ", "(tap-hold-press ");
+    let result = simulate(cfg.as_str(), "d:a t:10 d:x t:10 u:x t:300 u:a t:50").to_ascii();
+    assert_eq!(
+        "t:12ms dn:LCtrl t:6ms dn:X t:2ms up:X t:300ms up:LCtrl",
+        result
+    );
+    let result = simulate(cfg.as_str(), "d:a t:10 d:s t:50 u:a t:10 u:s t:300").to_ascii();
+    assert_eq!("t:10ms dn:C t:62ms up:C", result);
+}
+
+#[test]
+fn sim_chord_participant_tap_hold_release_interrupted_by_non_participant() {
+    let cfg = CHORD_PARTICIPANTS_WITH_TAP_HOLD.replace("(tap-hold ", "(tap-hold-release ");
+    let result = simulate(cfg.as_str(), "d:a t:10 d:x t:10 u:x t:300 u:a t:50").to_ascii();
+    assert_eq!(
+        "t:20ms dn:LCtrl t:6ms dn:X t:1ms up:X t:293ms up:LCtrl",
+        result
+    );
+    let result = simulate(cfg.as_str(), "d:a t:10 d:x t:300 u:x t:10 u:a t:50").to_ascii();
+    assert_eq!(
+        "t:198ms dn:LCtrl t:1ms dn:X t:111ms up:X t:10ms up:LCtrl",
+        result
+    );
+}
+
+#[test]
+fn sim_chord_participant_tap_hold_chord_window_longer_than_hold() {
+    let cfg = CHORD_PARTICIPANTS_WITH_TAP_HOLD.replace("c 50 ", "c 300 ");
+    // The chord can still complete after the hold timeout has passed.
+    let result = simulate(cfg.as_str(), "d:a t:250 d:s t:10 u:s t:10 u:a t:50").to_ascii();
+    assert_eq!("t:250ms dn:C t:22ms up:C", result);
+    // Once the window expires, the hold activates immediately
+    // because its timeout has already elapsed since the press.
+    let result = simulate(cfg.as_str(), "d:a t:300 u:a t:50").to_ascii();
+    assert_eq!("t:302ms dn:LCtrl t:1ms up:LCtrl", result);
+    let result = simulate(cfg.as_str(), "d:a t:60 d:s t:20 u:a t:20 u:s t:300").to_ascii();
+    assert_eq!("t:60ms dn:C t:42ms up:C", result);
+}
+
+static CHORD_HOLD_CFG: &str = "\
+(defcfg process-unmapped-keys yes concurrent-tap-hold yes) \
+(defsrc d f j) \
+(deflayer base d f j) \
+(deflayer nav d f left) \
+(defchordsv2 \
+  (d f) (chord-hold 200 esc (layer-while-held nav)) 50 all-released () \
+)";
+
+#[test]
+fn sim_chord_hold_taps_on_first_release() {
+    let result = simulate(CHORD_HOLD_CFG, "d:d t:10 d:f t:50 u:d t:50 u:f t:50").to_ascii();
+    // Escape is tapped when `d` is released, without waiting for `f`.
+    assert_eq!("t:60ms dn:Escape t:9ms up:Escape", result);
+}
+
+#[test]
+fn sim_chord_hold_holds_until_all_released() {
+    let result = simulate(
+        CHORD_HOLD_CFG,
+        "d:d t:10 d:f t:300 d:j t:10 u:j t:10 u:d t:10 d:j t:10 u:j t:10 u:f t:10 d:j t:10 u:j t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "t:311ms dn:Left t:9ms up:Left t:21ms dn:Left t:9ms up:Left t:21ms dn:J t:9ms up:J",
+        result
+    );
+}
+
+#[test]
+fn sim_chord_hold_key_pressed_during_hold_window() {
+    let result = simulate(
+        CHORD_HOLD_CFG,
+        "d:d t:10 d:f t:50 d:j t:10 u:j t:10 u:d t:10 u:f t:50",
+    )
+    .to_ascii();
+    assert_eq!(
+        "t:80ms dn:Escape t:7ms dn:J t:1ms up:J t:3ms up:Escape",
+        result
+    );
+}
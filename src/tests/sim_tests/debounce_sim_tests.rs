@@ -0,0 +1,137 @@
+use super::*;
+use kanata_parser::cfg::DebounceAlgorithm;
+use kanata_parser::keys::OsCode;
+
+/// A press and a release of `e` that each bounce twice.
+const CHATTER: &str = "d:e t:2 u:e t:2 d:e t:50 u:e t:2 d:e t:2 u:e t:50";
+
+#[test]
+fn no_debounce_forwards_chatter() {
+    let result = simulate("(defsrc e) (deflayer base e)", CHATTER).to_ascii();
+    assert_eq!(
+        "dn:E t:2ms up:E t:2ms dn:E t:50ms up:E t:2ms dn:E t:2ms up:E",
+        result
+    );
+}
+
+#[test]
+fn debounce_defer_outputs_once_after_settling() {
+    let result = simulate(
+        "(defcfg debounce-ms 5) (defsrc e) (deflayer base e)",
+        CHATTER,
+    )
+    .to_ascii();
+    assert_eq!("t:8ms dn:E t:54ms up:E", result);
+}
+
+#[test]
+fn debounce_eager_outputs_once_immediately() {
+    let result = simulate(
+        "(defcfg debounce-ms 5 debounce-algorithm eager) (defsrc e) (deflayer base e)",
+        CHATTER,
+    )
+    .to_ascii();
+    assert_eq!("dn:E t:54ms up:E", result);
+}
+
+#[test]
+fn debounce_eager_processes_state_changed_during_lockout() {
+    // The release bounces back to pressed and settles released within the lockout.
+    let result = simulate(
+        "(defcfg debounce-ms 5 debounce-algorithm eager) (defsrc e) (deflayer base e)",
+        "d:e t:50 u:e t:1 d:e t:1 u:e t:50",
+    )
+    .to_ascii();
+    assert_eq!("dn:E t:50ms up:E", result);
+    // The key is pressed again within the lockout of its release.
+    let result = simulate(
+        "(defcfg debounce-ms 5 debounce-algorithm eager) (defsrc e) (deflayer base e)",
+        "d:e t:50 u:e t:2 d:e t:50 u:e t:50",
+    )
+    .to_ascii();
+    assert_eq!("dn:E t:50ms up:E t:4ms dn:E t:48ms up:E", result);
+}
+
+#[test]
+fn debounce_keys_overrides_per_key() {
+    let cfg = "(defcfg debounce-keys (e 5)) (defsrc a e) (deflayer base a e)";
+    let result = simulate(cfg, CHATTER).to_ascii();
+    assert_eq!("t:8ms dn:E t:54ms up:E", result);
+    let result = simulate(cfg, &CHATTER.replace('e', "a")).to_ascii();
+    assert_eq!(
+        "dn:A t:2ms up:A t:2ms dn:A t:50ms up:A t:2ms dn:A t:2ms up:A",
+        result
+    );
+    let cfg = "(defcfg debounce-ms 5 debounce-keys (a 0)) (defsrc a e) (deflayer base a e)";
+    let result = simulate(cfg, &CHATTER.replace('e', "a")).to_ascii();
+    assert_eq!(
+        "dn:A t:2ms up:A t:2ms dn:A t:50ms up:A t:2ms dn:A t:2ms up:A",
+        result
+    );
+}
+
+#[test]
+fn debounce_happens_before_tap_hold() {
+    let cfg = "(defcfg debounce-ms 5) (defsrc e) (deflayer base (tap-hold 0 200 e lsft))";
+    let result = simulate(cfg, "d:e t:2 u:e t:2 d:e t:300 u:e t:2 d:e t:2 u:e t:50").to_ascii();
+    assert_eq!("t:208ms dn:LShift t:104ms up:LShift", result);
+}
+
+/// Feeds events straight to the processing, ticking 1ms after each one.
+fn handle_events(k: &mut Kanata, events: &[KeyEvent]) {
+    for event in events {
+        k.handle_input_event(event).expect("input handles fine");
+        k.tick_ms(1, &None).expect("ticks fine");
+    }
+    k.tick_ms(50, &None).expect("ticks fine");
+}
+
+#[test]
+fn debounce_defer_releases_key_pressed_before_debouncing() {
+    init_log();
+    let _lk = match CFG_PARSE_LOCK.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let mut k = Kanata::new_from_str("(defsrc e) (deflayer base e)", Default::default())
+        .expect("failed to parse cfg");
+    handle_events(&mut k, &[KeyEvent::new(OsCode::KEY_E, KeyValue::Press)]);
+    // E.g. a live reload that enables debouncing while the key is held.
+    k.debounce.configure(DebounceAlgorithm::Defer, 5, vec![]);
+    handle_events(&mut k, &[KeyEvent::new(OsCode::KEY_E, KeyValue::Release)]);
+    assert_eq!(
+        "dn:E t:51ms up:E",
+        k.kbd_out.outputs.events.join(" ").to_ascii()
+    );
+}
+
+#[test]
+fn debounce_ignores_injected_events() {
+    init_log();
+    let _lk = match CFG_PARSE_LOCK.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let mut k = Kanata::new_from_str(
+        "(defcfg debounce-ms 5) (defsrc e) (deflayer base e)",
+        Default::default(),
+    )
+    .expect("failed to parse cfg");
+    let injected = |value| KeyEvent {
+        injected: true,
+        ..KeyEvent::new(OsCode::KEY_E, value)
+    };
+    handle_events(
+        &mut k,
+        &[
+            injected(KeyValue::Press),
+            injected(KeyValue::Release),
+            injected(KeyValue::Press),
+            injected(KeyValue::Release),
+        ],
+    );
+    assert_eq!(
+        "dn:E t:1ms up:E t:1ms dn:E t:1ms up:E",
+        k.kbd_out.outputs.events.join(" ").to_ascii()
+    );
+}
@@ -0,0 +1,56 @@
+use super::*;
+
+const CFG: &str = "
+ (defsrc a lsft lctl ralt)
+ (deflayer base
+   (fork-multi (lsft rsft) b (lctl) c (lctl ralt) d e)
+   lsft lctl ralt)
+";
+
+#[test]
+fn sim_fork_multi_default() {
+    let result = simulate(CFG, "d:a t:10 u:a t:50").no_time();
+    assert_eq!("out:↓E out:↑E", result);
+}
+
+#[test]
+fn sim_fork_multi_branches() {
+    let result = simulate(
+        CFG,
+        "d:lsft t:10 d:a t:10 u:a t:10 u:lsft t:10 d:ralt t:10 d:a t:10 u:a t:10 u:ralt t:50",
+    )
+    .no_time();
+    assert_eq!(
+        "out:↓LShift out:↓B out:↑B out:↑LShift out:↓RAlt out:↓D out:↑D out:↑RAlt",
+        result
+    );
+}
+
+#[test]
+fn sim_fork_multi_earlier_branch_wins() {
+    let result = simulate(
+        CFG,
+        "d:lctl t:10 d:ralt t:10 d:a t:10 u:a t:10 d:lsft t:10 d:a t:10 u:a t:50",
+    )
+    .no_time();
+    assert_eq!(
+        "out:↓LCtrl out:↓RAlt out:↓C out:↑C out:↓LShift out:↓B out:↑B",
+        result
+    );
+}
+
+#[test]
+fn sim_fork_two_branches() {
+    let result = simulate(
+        "
+ (defsrc a lsft)
+ (deflayer base (fork b c (lsft)) lsft)
+",
+        "d:a t:10 u:a t:10 d:lsft t:10 d:a t:10 u:a t:10 u:lsft t:50",
+    )
+    .no_time();
+    assert_eq!(
+        "out:↓B out:↑B out:↓LShift out:↓C out:↑C out:↑LShift",
+        result
+    );
+}
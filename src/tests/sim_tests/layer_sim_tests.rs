@@ -185,3 +185,241 @@ fn ls_sim_transparent_no_delegate() {
 // =============================================================================
 // End Layer Switch Simulator Input Tests
 // =============================================================================
+
+const SCHEDULE_CFG: &str = "
+(defsrc a b)
+(deflayer base a (layer-while-held nav))
+(deflayer work x (layer-while-held nav))
+(deflayer evening y (layer-while-held nav))
+(deflayer nav z _)
+(defschedule
+  (09:00 17:00 mon tue wed thu fri) work
+  (17:00 09:00) evening)
+";
+
+#[test]
+fn schedule_activates_layer_at_boundaries() {
+    let result = simulate(
+        SCHEDULE_CFG,
+        "clock:sat-12:00 d:a t:10 u:a t:10
+         clock:mon-08:59 d:a t:10 u:a t:10
+         clock:mon-09:00 d:a t:10 u:a t:10
+         clock:fri-16:59 d:a t:10 u:a t:10
+         clock:fri-17:00 d:a t:10 u:a t:10
+         clock:sat-08:59 d:a t:10 u:a t:10
+         clock:sat-09:00 d:a t:10 u:a t:10",
+    )
+    .no_time()
+    .no_releases()
+    .to_ascii();
+    // Saturday noon is in no range. 08:59 on Monday is in the evening range that started on
+    // Sunday. The base layer is restored when no range matches anymore.
+    assert_eq!("dn:A dn:Y dn:X dn:X dn:Y dn:Y dn:A", result);
+}
+
+#[test]
+fn schedule_layer_actions_stack_on_scheduled_base() {
+    let result = simulate(
+        SCHEDULE_CFG,
+        "t:1 clock:tue-10:00 t:1 d:a t:10 u:a t:10
+         d:b t:10 d:a t:10 u:a t:10 u:b t:10 d:a t:10 u:a t:10
+         ls:base d:a t:10 u:a t:10 clock:tue-12:00 d:a t:10 u:a t:10
+         clock:tue-17:00 d:a t:10 u:a t:10",
+    )
+    .no_time()
+    .no_releases()
+    .to_ascii();
+    // A layer switch is kept until the scheduled layer changes.
+    assert_eq!("dn:X dn:Z dn:X dn:A dn:A dn:Y", result);
+}
+
+#[test]
+fn layer_while_held_timeout_deactivates_after_inactivity() {
+    let cfg = "
+(defsrc a b)
+(deflayer base (layer-while-held-timeout nav 100) b)
+(deflayer nav _ x)
+";
+    // Presses on the layer restart the timeout.
+    let result = simulate(cfg, "d:a t:80 d:b t:10 u:b t:80 d:b t:10 u:b t:10 u:a t:10").to_ascii();
+    assert_eq!("t:80ms dn:X t:10ms up:X t:80ms dn:X t:10ms up:X", result);
+    // After the timeout the base layer is active while the key is still held.
+    let result = simulate(cfg, "d:a t:150 d:b t:10 u:b t:10 u:a t:10").to_ascii();
+    assert_eq!("t:150ms dn:B t:10ms up:B", result);
+    // The layer is active again after the key is pressed again.
+    let result = simulate(cfg, "d:a t:150 u:a t:10 d:a t:10 d:b t:10 u:b t:10 u:a").to_ascii();
+    assert_eq!("t:170ms dn:X t:10ms up:X", result);
+}
+
+#[test]
+fn layer_while_held_timeout_releases_keys_held_across_timeout() {
+    let result = simulate(
+        "
+(defsrc a b)
+(deflayer base (layer-while-held-timeout nav 50) b)
+(deflayer nav _ x)
+",
+        "d:a t:10 d:b t:100 u:b t:10 u:a t:10",
+    )
+    .to_ascii();
+    assert_eq!("t:10ms dn:X t:100ms up:X", result);
+}
+
+static LAYER_STACK_CFG: &str = "
+(defsrc a b c)
+(deflayer base (layer-push sym) b c)
+(deflayer sym (layer-push sub) layer-pop x)
+(deflayer sub a layer-pop y)
+";
+
+#[test]
+fn layer_pop_returns_to_layer_before_push() {
+    let result = simulate(
+        LAYER_STACK_CFG,
+        "d:a t:10 u:a t:10 d:c t:10 u:c t:10
+         d:a t:10 u:a t:10 d:c t:10 u:c t:10
+         d:b t:10 u:b t:10 d:c t:10 u:c t:10
+         d:b t:10 u:b t:10 d:c t:10 u:c t:10",
+    )
+    .no_time()
+    .no_releases()
+    .to_ascii();
+    assert_eq!("dn:X dn:Y dn:X dn:C", result);
+}
+
+#[test]
+fn layer_pop_with_empty_stack_does_nothing() {
+    let result = simulate(
+        "
+(defsrc a b c)
+(deflayer base (layer-push sym) layer-pop c)
+(deflayer sym a layer-pop x)
+",
+        "d:b t:10 u:b t:10 d:c t:10 u:c t:10 d:a t:10 u:a t:10 d:c t:10 u:c t:10",
+    )
+    .no_time()
+    .no_releases()
+    .to_ascii();
+    assert_eq!("dn:C dn:X", result);
+}
+
+#[test]
+fn layer_push_stops_at_max_depth() {
+    let result = simulate(
+        "
+(defcfg layer-stack-max-depth 1)
+(defsrc a b c)
+(deflayer base (layer-push sym) b c)
+(deflayer sym (layer-push sub) layer-pop x)
+(deflayer sub a layer-pop y)
+",
+        "d:a t:10 u:a t:10 d:a t:10 u:a t:10 d:c t:10 u:c t:10",
+    )
+    .no_time()
+    .no_releases()
+    .to_ascii();
+    assert_eq!("dn:X", result);
+}
+
+#[test]
+fn layer_process_unmapped_keys_override_passes_keys_through() {
+    let cfg = "
+(defcfg process-unmapped-keys yes)
+(defsrc a b)
+(deflayer base (tap-hold 100 100 a x) (layer-switch game))
+(deflayer (game process-unmapped-keys no) (tap-hold 100 100 a x) (layer-switch base))
+";
+    // On the base layer, c is processed, so it waits for the tap-hold to resolve.
+    let result = simulate(cfg, "d:a t:10 d:c t:10 u:c t:10 u:a t:10").to_ascii();
+    assert_eq!("t:30ms dn:A t:6ms dn:C t:1ms up:C t:1ms up:A", result);
+    // On the game layer, c is passed through right away.
+    let result = simulate(cfg, "d:b u:b t:10 d:a t:10 d:c t:10 u:c t:10 u:a t:10").to_ascii();
+    assert_eq!("t:20ms dn:C t:10ms up:C t:10ms dn:A t:6ms up:A", result);
+}
+
+#[test]
+fn layer_process_unmapped_keys_override_releases_key_pressed_on_other_layer() {
+    let cfg = "
+(defsrc a b)
+(deflayer (base process-unmapped-keys yes) (layer-switch game) b)
+(deflayer game (layer-switch base) x)
+";
+    // c is pressed while it is processed and released after switching to a layer that passes it
+    // through, and the other way around.
+    let result = simulate(
+        cfg,
+        "d:c t:10 d:a u:a t:10 u:c t:10 d:c t:10 d:a u:a t:10 u:c t:10",
+    )
+    .to_ascii();
+    assert_eq!("dn:C t:20ms up:C t:10ms dn:C t:20ms up:C", result);
+}
+
+#[test]
+fn layer_allow_hardware_repeat_override() {
+    let cfg = "
+(defsrc a b)
+(deflayer base a (layer-switch nr))
+(deflayer (nr allow-hardware-repeat no) a (layer-switch base))
+";
+    let result = simulate(
+        cfg,
+        "d:a t:10 r:a t:10 u:a t:10 d:b u:b d:a t:10 r:a t:10 u:a t:10",
+    )
+    .no_time()
+    .to_ascii();
+    assert_eq!("dn:A dn:A up:A dn:A up:A", result);
+}
+
+#[test]
+fn layer_cycle_advances_and_wraps() {
+    let cfg = "
+(defsrc a b)
+(deflayer l1 x (layer-cycle (l1 l2 l3)))
+(deflayer l2 y (layer-cycle (l1 l2 l3)))
+(deflayer l3 z (layer-cycle (l1 l2 l3)))
+";
+    let result = simulate(
+        cfg,
+        "d:a u:a t:10 d:b u:b t:10 d:a u:a t:10 d:b u:b t:10
+         d:a u:a t:10 d:b u:b t:10 d:a u:a t:10",
+    )
+    .no_time()
+    .to_ascii();
+    assert_eq!("dn:X up:X dn:Y up:Y dn:Z up:Z dn:X up:X", result);
+}
+
+#[test]
+fn layer_cycle_state_shared_by_same_layer_list() {
+    let cfg = "
+(defsrc a b c d)
+(deflayer l1 x (layer-cycle (l1 l2 l3)) (layer-cycle (l1 l2 l3)) (layer-cycle (l1 l3)))
+(deflayer l2 y (layer-cycle (l1 l2 l3)) (layer-cycle (l1 l2 l3)) (layer-cycle (l1 l3)))
+(deflayer l3 z (layer-cycle (l1 l2 l3)) (layer-cycle (l1 l2 l3)) (layer-cycle (l1 l3)))
+";
+    // b and c share a cycle, so c continues where b left off. d has its own cycle, which starts
+    // at l1 and so goes to l3 on its first tap.
+    let result = simulate(
+        cfg,
+        "d:b u:b t:10 d:a u:a t:10 d:c u:c t:10 d:a u:a t:10
+         d:d u:d t:10 d:a u:a t:10 d:b u:b t:10 d:a u:a t:10",
+    )
+    .no_time()
+    .to_ascii();
+    assert_eq!("dn:Y up:Y dn:Z up:Z dn:Z up:Z dn:X up:X", result);
+}
+
+#[test]
+fn passthrough_while_held_toggles_mid_stream() {
+    let result = simulate(
+        "(defsrc a b c) \
+         (deflayer base x passthrough-while-held lsft)",
+        "d:a t:10 d:b t:10 u:a t:10 d:c t:10 u:b t:10 u:c t:10 d:a t:10 u:a t:10",
+    )
+    .to_ascii();
+    // `a` was held when passthrough started, so its release is still remapped. `c` was pressed
+    // during passthrough, so its release is passed through after passthrough has ended.
+    assert_eq!(
+        "dn:X t:20ms up:X t:10ms dn:C t:20ms up:C t:10ms dn:X t:10ms up:X",
+        result
+    );
+}
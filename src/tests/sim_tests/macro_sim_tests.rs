@@ -12,6 +12,29 @@ fn macro_cancel_on_press() {
     test_on_press(cfg);
 }
 
+#[test]
+fn macro_cancel_key_stops_playback() {
+    // esc is not in defsrc but still reaches processing, and is output after the cancel.
+    let cfg = "\
+(defcfg macro-cancel-key esc)
+(defsrc a b)
+(deflayer base (macro z 100 y) (macro-repeat x 100))";
+    let result = simulate(cfg, "d:a t:50 d:esc t:10 u:esc t:100").to_ascii();
+    assert_eq!(
+        "t:1ms dn:Z t:1ms up:Z t:48ms dn:Escape t:10ms up:Escape",
+        result
+    );
+    // A repeating macro stops even though its key is still held.
+    let result = simulate(cfg, "d:b t:150 d:esc t:10 u:esc u:b t:200").to_ascii();
+    assert_eq!(
+        "t:1ms dn:X t:1ms up:X t:102ms dn:X t:1ms up:X t:45ms dn:Escape t:10ms up:Escape",
+        result
+    );
+    // Without a running macro the cancel key is a normal key.
+    let result = simulate(cfg, "d:esc t:10 u:esc t:10").to_ascii();
+    assert_eq!("dn:Escape t:10ms up:Escape", result);
+}
+
 fn test_on_press(cfg: &str) {
     // Cancellation should happen.
     let result = simulate(cfg, "d:a t:50 d:c t:100").to_ascii();
@@ -143,3 +166,188 @@ fn test_release(cfg: &str) {
         result
     );
 }
+
+#[test]
+fn dynamic_macro_persists_across_restart() {
+    let dir = std::env::temp_dir().join(format!("kanata-dynmacro-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("macros.json");
+    let cfg = format!(
+        "\
+(defcfg dynamic-macro-persist-file \"{}\")
+(defsrc a b c)
+(deflayer base (dynamic-macro-record 0) (dynamic-macro-play 0) c)",
+        file.display()
+    );
+
+    // Missing file starts with empty slots.
+    let result = simulate(cfg.as_str(), "d:b u:b t:50").to_ascii();
+    assert_eq!("", result);
+
+    simulate(cfg.as_str(), "d:a u:a t:10 d:c t:10 u:c t:10 d:a u:a t:10");
+    let saved = std::fs::read_to_string(&file).unwrap();
+    assert!(saved.contains("\"version\": 1"), "{saved}");
+    assert!(saved.contains("KEY_C"), "{saved}");
+
+    let result = simulate(cfg.as_str(), "d:b u:b t:100").to_ascii().no_time();
+    assert_eq!("dn:C up:C", result);
+
+    // Corrupt and unknown-version files are ignored.
+    std::fs::write(&file, "{not json").unwrap();
+    let result = simulate(cfg.as_str(), "d:b u:b t:100").to_ascii();
+    assert_eq!("", result);
+    std::fs::write(&file, r#"{"version":99,"macros":[]}"#).unwrap();
+    let result = simulate(cfg.as_str(), "d:b u:b t:100").to_ascii();
+    assert_eq!("", result);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+#[cfg(feature = "tcp_server")]
+fn dynamic_macro_client_commands() {
+    init_log();
+    let _lk = match CFG_PARSE_LOCK.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let cfg = "\
+(defsrc a b c)
+(deflayer base (dynamic-macro-record 0) dynamic-macro-record-stop c)";
+    let mut k = Kanata::new_from_str(cfg, Default::default()).expect("failed to parse cfg");
+    let sim = |k: &mut Kanata, key: &str, value: KeyValue| {
+        let code = str_to_oscode(key).unwrap();
+        k.handle_input_event(&KeyEvent::new(code, value)).unwrap();
+        for _ in 0..10 {
+            let _ = k.tick_ms(1, &None);
+        }
+    };
+    sim(&mut k, "a", KeyValue::Press);
+    sim(&mut k, "a", KeyValue::Release);
+    sim(&mut k, "c", KeyValue::Press);
+    sim(&mut k, "c", KeyValue::Release);
+    let listing = serde_json::to_string(&k.dynamic_macro_listing()).unwrap();
+    assert_eq!(r#"{"DynamicMacros":{"slots":[],"recording":0}}"#, listing);
+    sim(&mut k, "b", KeyValue::Press);
+    sim(&mut k, "b", KeyValue::Release);
+    let listing = serde_json::to_string(&k.dynamic_macro_listing()).unwrap();
+    assert_eq!(
+        r#"{"DynamicMacros":{"slots":[{"slot":0,"events":3}]}}"#,
+        listing
+    );
+
+    k.kbd_out.outputs.events.clear();
+    k.play_dynamic_macro(0).unwrap();
+    for _ in 0..50 {
+        let _ = k.tick_ms(1, &None);
+    }
+    let result = k.kbd_out.outputs.events.join("\n").to_ascii().no_time();
+    assert_eq!("dn:C up:C", result);
+
+    assert!(k.play_dynamic_macro(1).is_err());
+    k.clear_dynamic_macro(0);
+    assert!(k.play_dynamic_macro(0).is_err());
+}
+
+const CLEAN_MODS_CFG: &str = "\
+(defsrc lsft a b c)
+(deflayer base lsft (macro M-spc) (preserve-mods (macro M-spc)) (macro S-x))";
+
+#[test]
+fn macro_preserves_held_mods_by_default() {
+    let result = simulate(CLEAN_MODS_CFG, "d:lsft d:a t:10 u:a u:lsft t:10")
+        .no_time()
+        .to_ascii();
+    assert_eq!(
+        "dn:LShift dn:LGui dn:Space up:Space up:LGui up:LShift",
+        result
+    );
+}
+
+#[test]
+fn macro_clean_mods_releases_held_shift() {
+    let cfg = format!("(defcfg clean-mods-on-action yes)\n{CLEAN_MODS_CFG}");
+    let result = simulate(cfg.as_str(), "d:lsft d:a t:10 u:a u:lsft t:10")
+        .no_time()
+        .to_ascii();
+    assert_eq!(
+        "dn:LShift up:LShift dn:LGui dn:Space up:Space up:LGui dn:LShift up:LShift",
+        result
+    );
+    // A modifier released during the macro is not restored.
+    let result = simulate(cfg.as_str(), "d:lsft d:a u:lsft t:10 u:a t:10")
+        .no_time()
+        .to_ascii();
+    assert_eq!(
+        "dn:LShift up:LShift dn:LGui dn:Space up:Space up:LGui",
+        result
+    );
+    // Modifiers the macro outputs itself are kept.
+    let result = simulate(cfg.as_str(), "d:lsft d:c t:10 u:c u:lsft t:10")
+        .no_time()
+        .to_ascii();
+    assert_eq!("dn:LShift dn:X up:X up:LShift", result);
+}
+
+#[test]
+fn macro_clean_mods_per_action_override() {
+    let cfg = format!("(defcfg clean-mods-on-action yes)\n{CLEAN_MODS_CFG}");
+    let result = simulate(cfg.as_str(), "d:lsft d:b t:10 u:b u:lsft t:10")
+        .no_time()
+        .to_ascii();
+    assert_eq!(
+        "dn:LShift dn:LGui dn:Space up:Space up:LGui up:LShift",
+        result
+    );
+    let cfg = "\
+(defsrc lsft a)
+(deflayer base lsft (clean-mods (macro M-spc)))";
+    let result = simulate(cfg, "d:lsft d:a t:10 u:a u:lsft t:10")
+        .no_time()
+        .to_ascii();
+    assert_eq!(
+        "dn:LShift up:LShift dn:LGui dn:Space up:Space up:LGui dn:LShift up:LShift",
+        result
+    );
+}
+
+static RATE_LIMIT_CFG: &str = "
+(defsrc a)
+(deflayer base (macro b c d e f))";
+
+#[test]
+fn macro_burst_output_rate_limit_drop() {
+    let cfg =
+        format!("(defcfg output-rate-limit 5 output-rate-limit-policy drop)\n{RATE_LIMIT_CFG}");
+    let result = simulate(cfg.as_str(), "d:a t:50 u:a t:50")
+        .no_time()
+        .to_ascii();
+    // The release of the last written press is kept, the other keys are dropped entirely.
+    assert_eq!("dn:B up:B dn:C up:C dn:D up:D", result);
+}
+
+#[test]
+fn macro_burst_output_rate_limit_delay() {
+    let unlimited = simulate(
+        format!("(defcfg output-rate-limit 0)\n{RATE_LIMIT_CFG}").as_str(),
+        "d:a t:50 u:a t:50",
+    )
+    .no_time()
+    .to_ascii();
+    assert_eq!(
+        "dn:B up:B dn:C up:C dn:D up:D dn:E up:E dn:F up:F",
+        unlimited
+    );
+    // The 5 events over the limit of 5 per second are delayed by 200ms each.
+    let start = web_time::Instant::now();
+    let result = simulate(
+        format!("(defcfg output-rate-limit 5 output-rate-limit-policy delay)\n{RATE_LIMIT_CFG}")
+            .as_str(),
+        "d:a t:50 u:a t:50",
+    )
+    .no_time()
+    .to_ascii();
+    assert_eq!(unlimited, result);
+    assert!(start.elapsed() >= web_time::Duration::from_millis(800));
+}
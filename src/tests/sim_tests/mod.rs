@@ -6,7 +6,7 @@
 
 use crate::tests::*;
 use crate::{
-    FAKE_KEY_ROW, FakeKeyAction, Kanata,
+    FAKE_KEY_ROW, FakeKeyAction, Kanata, ScheduleTime,
     kanata::handle_fakekey_action,
     oskbd::{KeyEvent, KeyValue},
     str_to_oscode,
@@ -53,21 +53,43 @@ fn apply_layer_switch(k: &mut Kanata, layer_name: &str) {
     k.layout.bm().set_default_layer(layer_idx);
 }
 
+/// Parse a time like "mon-09:00".
+fn parse_schedule_time(val: &str) -> ScheduleTime {
+    let (day, time) = val.split_once('-').expect("clock format is day-HH:MM");
+    let weekday = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"]
+        .iter()
+        .position(|d| *d == day)
+        .unwrap_or_else(|| panic!("unknown weekday: {day}")) as u8;
+    let (h, m) = time.split_once(':').expect("clock format is day-HH:MM");
+    let minute =
+        h.parse::<u16>().expect("valid hour") * 60 + m.parse::<u16>().expect("valid minute");
+    ScheduleTime { weekday, minute }
+}
+
 mod block_keys_tests;
 mod capsword_sim_tests;
 mod chord_sim_tests;
+mod debounce_sim_tests;
 mod delay_tests;
+mod fork_sim_tests;
 mod layer_sim_tests;
 mod macro_sim_tests;
+mod mouse_sim_tests;
+mod multi_sim_tests;
 mod oneshot_tests;
 mod output_chord_tests;
 mod override_tests;
 mod release_sim_tests;
 mod repeat_sim_tests;
+mod runtime_var_sim_tests;
+mod scancode_sim_tests;
 mod seq_sim_tests;
+mod sticky_layer_sim_tests;
 mod switch_sim_tests;
 mod tap_dance_tests;
 mod tap_hold_tests;
+#[cfg(feature = "tcp_server")]
+mod tcp_server_tests;
 mod template_sim_tests;
 mod timing_tests;
 mod unicode_sim_tests;
@@ -104,11 +126,8 @@ fn simulate_with_file_content<S: AsRef<str>>(
                 }
                 "d" => {
                     let key_code = str_to_oscode(val).expect("valid keycode");
-                    k.handle_input_event(&KeyEvent {
-                        code: key_code,
-                        value: KeyValue::Press,
-                    })
-                    .expect("input handles fine");
+                    k.handle_input_event(&KeyEvent::new(key_code, KeyValue::Press))
+                        .expect("input handles fine");
                     #[cfg(not(all(target_os = "windows", not(feature = "interception_driver"))))]
                     crate::PRESSED_KEYS.lock().insert(key_code);
                     #[cfg(all(target_os = "windows", not(feature = "interception_driver")))]
@@ -118,20 +137,14 @@ fn simulate_with_file_content<S: AsRef<str>>(
                 }
                 "u" => {
                     let key_code = str_to_oscode(val).expect("valid keycode");
-                    k.handle_input_event(&KeyEvent {
-                        code: key_code,
-                        value: KeyValue::Release,
-                    })
-                    .expect("input handles fine");
+                    k.handle_input_event(&KeyEvent::new(key_code, KeyValue::Release))
+                        .expect("input handles fine");
                     crate::PRESSED_KEYS.lock().remove(&key_code);
                 }
                 "r" => {
                     let key_code = str_to_oscode(val).expect("valid keycode");
-                    k.handle_input_event(&KeyEvent {
-                        code: key_code,
-                        value: KeyValue::Repeat,
-                    })
-                    .expect("input handles fine");
+                    k.handle_input_event(&KeyEvent::new(key_code, KeyValue::Repeat))
+                        .expect("input handles fine");
                 }
                 // Virtual/fake key activation: vk:name[:action] or fakekey:name[:action]
                 // Supported actions: press (p), release, tap (t), toggle (g)
@@ -143,6 +156,11 @@ fn simulate_with_file_content<S: AsRef<str>>(
                 "ls" | "layer-switch" | "🔀" => {
                     apply_layer_switch(&mut k, val);
                 }
+                // Mocked local time for defschedule: clock:mon-09:00
+                "clock" => {
+                    k.schedule
+                        .set_mock_time(parse_schedule_time(val), k.layout.bm());
+                }
                 _ => panic!("invalid item {pair}"),
             },
             None => panic!("invalid item {pair}"),
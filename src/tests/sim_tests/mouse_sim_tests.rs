@@ -0,0 +1,273 @@
+use super::*;
+
+#[test]
+fn movemouse_speed_scales_and_stacks() {
+    let result = simulate(
+        "
+(defsrc a b c)
+(deflayer base (movemouse-right 10 8) (movemouse-speed 25) (movemouse-speed 300))
+",
+        "d:a t:15 d:b t:10 d:c t:10 u:b t:10 u:c t:10 u:a t:10",
+    )
+    .to_ascii();
+    // 25% of 8, then 25% * 300% of 8, then 300% of 8.
+    assert_eq!(
+        "out🖰:move Right,8 t:10ms out🖰:move Right,8 t:10ms out🖰:move Right,2 \
+         t:10ms out🖰:move Right,6 t:10ms out🖰:move Right,24 t:10ms out🖰:move Right,8",
+        result
+    );
+}
+
+#[test]
+fn movemouse_speed_applies_after_accel() {
+    let result = simulate(
+        "
+(defsrc a b)
+(deflayer base (movemouse-accel-down 10 40 4 20) (movemouse-speed 50))
+",
+        "d:b d:a t:60 u:a u:b t:10",
+    )
+    .to_ascii();
+    // Half of the accelerated distances 4, 8, 12, 16 and 20.
+    assert_eq!(
+        "t:1ms out🖰:move Down,2 t:10ms out🖰:move Down,4 t:10ms out🖰:move Down,6 \
+         t:10ms out🖰:move Down,8 t:10ms out🖰:move Down,10 t:10ms out🖰:move Down,10",
+        result
+    );
+}
+
+#[test]
+fn movemouse_speed_scales_mwheel_optionally() {
+    let result = simulate(
+        "
+(defsrc a b c)
+(deflayer base (mwheel-down 10 120) (movemouse-speed 50) (movemouse-speed 50 scale-mwheel))
+",
+        "d:b d:a t:15 u:a u:b t:10 d:c d:a t:15 u:a u:c t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "t:1ms scroll:Down,120 t:10ms scroll:Down,120 t:15ms scroll:Down,60 t:10ms scroll:Down,60",
+        result
+    );
+}
+
+#[test]
+fn movemouse_speed_reset_restores_normal_speed() {
+    let result = simulate(
+        "
+(defsrc a b c d)
+(deflayer base (mwheel-down 10 120) (movemouse-right 10 8) (movemouse-speed 25 scale-mwheel)
+  (movemouse-speed-reset))
+",
+        "d:c d:b d:a t:15 d:d u:d t:10 u:c t:10 d:c t:10 u:a u:b u:c t:10",
+    )
+    .to_ascii();
+    // The reset applies until the next press of a movemouse-speed key, and releasing the
+    // movemouse-speed key that was held during the reset does not change the speed.
+    assert_eq!(
+        "t:1ms out🖰:move Right,2 t:1ms scroll:Down,30 t:9ms out🖰:move Right,2 t:1ms scroll:Down,30 \
+         t:9ms out🖰:move Right,8 t:1ms scroll:Down,120 t:9ms out🖰:move Right,8 t:1ms scroll:Down,120 \
+         t:9ms out🖰:move Right,2 t:1ms scroll:Down,30",
+        result
+    );
+}
+
+#[test]
+fn movemouse_speed_reset_keeps_same_speed_pressed_after() {
+    let result = simulate(
+        "
+(defsrc a b c d)
+(deflayer base (movemouse-right 10 8) (movemouse-speed 25) (movemouse-speed 25)
+  (movemouse-speed-reset))
+",
+        "d:b d:a t:15 d:d u:d t:10 d:c t:10 u:b t:10 u:c t:10",
+    )
+    .to_ascii();
+    // Releasing the key held during the reset must not remove the speed of the key with the
+    // same speed that was pressed after it.
+    assert_eq!(
+        "t:1ms out🖰:move Right,2 t:10ms out🖰:move Right,2 t:10ms out🖰:move Right,8 \
+         t:10ms out🖰:move Right,2 t:10ms out🖰:move Right,2 t:10ms out🖰:move Right,8",
+        result
+    );
+}
+
+#[test]
+fn setmouse_pct_clamps_and_selects_monitor() {
+    let result = simulate(
+        "(defsrc a b c)
+         (deflayer base (setmouse-pct 50 25) (setmouse-pct 150 -10 primary) (setmouse-pct 0 100 1))",
+        "d:a t:10 u:a t:10 d:b t:10 u:b t:10 d:c t:10 u:c t:10",
+    )
+    .to_ascii()
+    .no_time();
+    assert_eq!(
+        "out🖰:@50%,25%,Current out🖰:@100%,0%,Primary out🖰:@0%,100%,Index(1)",
+        result
+    );
+}
+
+#[test]
+fn clear_state_releases_held_mouse_button() {
+    let result = simulate(
+        "(defsrc a b) (deflayer base mlft clear-state)",
+        "d:a t:10 d:b t:10 u:b t:10 u:a t:10",
+    )
+    .to_ascii()
+    .no_time();
+    // The button is released by clear-state and not again when its key is released.
+    assert_eq!("out🖰:↓Left out🖰:↑Left", result);
+}
+
+#[test]
+fn mwheel_fine_accelerates_and_ends_gesture() {
+    let result = simulate(
+        "(defsrc a b)
+         (deflayer base (mwheel-fine-down 10 30) (mwheel-fine-right 10 20 10 50))",
+        "d:a t:25 u:a t:20 d:b t:35 u:b t:20",
+    )
+    .to_ascii();
+    assert_eq!(
+        "scroll:Down,30 t:10ms scroll:Down,30 t:10ms scroll:Down,30 t:10ms scroll-end:Down \
+         t:15ms scroll:Right,10 t:10ms scroll:Right,30 t:10ms scroll:Right,50 t:10ms scroll:Right,50 \
+         t:10ms scroll-end:Right",
+        result
+    );
+}
+
+#[test]
+fn mouse_lock_toggles_and_releases_safely() {
+    let cfg = "
+(defsrc a b c)
+(deflayer base mllk mlft (layer-switch other))
+(deflayer other mllk mlft (layer-switch base))
+";
+    // A second tap unlocks.
+    let result = simulate(cfg, "d:a u:a t:10 d:a u:a t:10")
+        .to_ascii()
+        .no_time();
+    assert_eq!("out🖰:↓Left out🖰:↑Left", result);
+    // The plain button action takes over the held button and releases it on its release.
+    let result = simulate(cfg, "d:a u:a t:10 d:b t:10 u:b t:10")
+        .to_ascii()
+        .no_time();
+    assert_eq!("out🖰:↓Left out🖰:↑Left", result);
+    // Switching the base layer releases the lock, unless disabled.
+    let result = simulate(cfg, "d:a u:a t:10 d:c u:c t:10")
+        .to_ascii()
+        .no_time();
+    assert_eq!("out🖰:↓Left out🖰:↑Left", result);
+    let cfg = format!("(defcfg mouse-lock-release-on-layer-change no) {cfg}");
+    let result = simulate(cfg.as_str(), "d:a u:a t:10 d:c u:c t:10")
+        .to_ascii()
+        .no_time();
+    assert_eq!("out🖰:↓Left", result);
+}
+
+#[test]
+fn mouse_speed_curve_ramps_movemouse() {
+    let result = simulate(
+        "
+(defcfg mouse-speed-curve ease-in mouse-speed-max 3 mouse-speed-ramp-ms 40)
+(defsrc a b)
+(deflayer base (movemouse-right 10 10) (movemouse-accel-down 10 20 4 8))
+",
+        "d:a t:45 u:a t:10 d:b t:25 u:b t:10",
+    )
+    .to_ascii();
+    // ease-in from 1x to 3x over 40ms; the accel action keeps its own ramp.
+    assert_eq!(
+        "out🖰:move Right,10 t:10ms out🖰:move Right,12 t:10ms out🖰:move Right,16 \
+         t:10ms out🖰:move Right,22 t:10ms out🖰:move Right,30 t:15ms out🖰:move Down,4 \
+         t:10ms out🖰:move Down,6 t:10ms out🖰:move Down,8",
+        result
+    );
+}
+
+#[test]
+fn movemouse_polar_accumulates_subpixel_moves() {
+    let result = simulate(
+        "
+(defsrc a b)
+(deflayer base (movemouse-polar 45 1000) (movemouse-polar 270 500))
+",
+        "d:a t:5 u:a t:10 d:b t:5 u:b t:10",
+    )
+    .to_ascii();
+    // 0.71 px/ms per axis up and right, then 0.5 px/ms left.
+    assert_eq!(
+        "t:1ms out🖰:move Right,1 out🖰:move Up,1 t:1ms out🖰:move Right,1 out🖰:move Up,1 \
+         t:2ms out🖰:move Right,1 out🖰:move Up,1 t:12ms out🖰:move Left,1 t:2ms out🖰:move Left,1",
+        result
+    );
+}
+
+#[test]
+fn tap_hold_mouse_buttons() {
+    let cfg = "
+(defsrc a b)
+(deflayer base (tap-hold 200 200 mlft mrgt) (tap-hold 200 200 x mrgt))
+";
+    let tap = simulate(cfg, "d:a t:50 u:a t:50").to_ascii();
+    assert_eq!("t:50ms out🖰:↓Left t:6ms out🖰:↑Left", tap);
+    let hold = simulate(cfg, "d:a t:300 u:a t:50").to_ascii();
+    assert_eq!("t:200ms out🖰:↓Right t:100ms out🖰:↑Right", hold);
+    let mixed = simulate(cfg, "d:b t:50 u:b t:300 d:b t:300 u:b t:50").to_ascii();
+    assert_eq!(
+        "t:50ms dn:X t:6ms up:X t:494ms out🖰:↓Right t:100ms out🖰:↑Right",
+        mixed
+    );
+}
+
+#[test]
+fn one_shot_mouse_button_held_until_next_key() {
+    let cfg = "
+(defsrc a b c)
+(deflayer base (one-shot 500 mlft) x (one-shot 500 lsft))
+";
+    let next_key = simulate(cfg, "d:a t:50 u:a t:50 d:b t:50 u:b t:50").to_ascii();
+    assert_eq!(
+        "out🖰:↓Left t:100ms dn:X t:5ms out🖰:↑Left t:45ms up:X",
+        next_key
+    );
+    let timeout = simulate(cfg, "d:a t:50 u:a t:600").to_ascii();
+    assert_eq!("out🖰:↓Left t:500ms out🖰:↑Left", timeout);
+    let with_key_one_shot =
+        simulate(cfg, "d:c t:50 u:c t:50 d:a t:50 u:a t:50 d:b t:10 u:b t:50").to_ascii();
+    assert_eq!(
+        "dn:LShift t:100ms out🖰:↓Left t:100ms dn:X t:5ms up:LShift out🖰:↑Left t:5ms up:X",
+        with_key_one_shot
+    );
+}
+
+#[test]
+fn mwheel_accel_ramps_while_held_and_restarts_after_release() {
+    // A deceleration multiplier of 0 stops scrolling on release, so the next press starts over
+    // from the initial velocity.
+    let result = simulate(
+        "(defsrc a) (deflayer base (mwheel-accel-up 10 40 1.5 0))",
+        "d:a t:70 u:a t:40 d:a t:20 u:a t:40",
+    )
+    .to_ascii();
+    assert_eq!(
+        "scroll:Up,15 t:16ms scroll:Up,22 t:16ms scroll:Up,33 t:16ms scroll:Up,40 t:16ms \
+         scroll:Up,40 t:46ms scroll:Up,15 t:16ms scroll:Up,22",
+        result
+    );
+}
+
+#[test]
+fn mwheel_horizontal() {
+    let result = simulate(
+        "(defsrc a b c)
+         (deflayer base (mwheel-left 10 120) (mwheel-right 10 60) (mwheel-accel-right 10 40 1.5 0))",
+        "d:a t:15 u:a t:20 d:b t:15 u:b t:20 d:c t:20 u:c t:20",
+    )
+    .to_ascii();
+    assert_eq!(
+        "scroll:Left,120 t:10ms scroll:Left,120 t:25ms scroll:Right,60 t:10ms scroll:Right,60 \
+         t:25ms scroll:Right,15 t:16ms scroll:Right,22",
+        result
+    );
+}
@@ -0,0 +1,114 @@
+use super::*;
+
+#[test]
+fn multi_modifier_with_layer() {
+    let result = simulate(
+        "
+(defsrc a b)
+(deflayer base (multi lctl (layer-while-held nav)) b)
+(deflayer nav _ x)
+",
+        "d:a t:10 d:b t:10 u:b t:10 u:a t:10 d:b t:10 u:b t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LCtrl t:10ms dn:X t:10ms up:X t:10ms up:LCtrl t:10ms dn:B t:10ms up:B",
+        result
+    );
+}
+
+#[test]
+fn multi_modifiers_with_layer_reverse_release() {
+    let result = simulate(
+        "
+(defsrc a b)
+(deflayer base
+  (multi lctl lsft (layer-while-held nav) reverse-release-order) b)
+(deflayer nav _ x)
+",
+        "d:a t:10 d:b t:10 u:b t:10 u:a t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LCtrl dn:LShift t:10ms dn:X t:10ms up:X t:10ms up:LShift up:LCtrl",
+        result
+    );
+}
+
+#[test]
+fn multi_modifier_with_tap_hold() {
+    let cfg = "
+(defsrc a b)
+(deflayer base (multi lctl (tap-hold 200 200 z (layer-while-held nav))) b)
+(deflayer nav _ x)
+";
+    let result = simulate(cfg, "d:a t:300 d:b t:10 u:b t:10 u:a t:10").to_ascii();
+    assert_eq!("dn:LCtrl t:300ms dn:X t:10ms up:X t:10ms up:LCtrl", result);
+    // The modifier is held until the tap action is released.
+    let result = simulate(cfg, "d:a t:10 u:a t:10").to_ascii();
+    assert_eq!("dn:LCtrl t:10ms dn:Z t:6ms up:LCtrl up:Z", result);
+}
+
+#[test]
+fn multi_modifier_with_macro() {
+    let result = simulate(
+        "
+(defsrc a)
+(deflayer base (multi lsft (macro c d)))
+",
+        "d:a t:10 u:a t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LShift t:1ms dn:C t:1ms up:C t:1ms dn:D t:1ms up:D t:6ms up:LShift",
+        result
+    );
+}
+
+#[test]
+fn multi_seq_delays_elements_and_reverses_release() {
+    let result = simulate(
+        "
+(defsrc a b)
+(deflayer base (multi-seq lctl 10 c) (multi-seq lsft lalt 5 x 5 y))
+",
+        "d:a t:50 u:a t:10 d:b t:50 u:b t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LCtrl t:10ms dn:C t:40ms up:C up:LCtrl t:10ms \
+         dn:LShift dn:LAlt t:5ms dn:X t:5ms dn:Y t:40ms up:Y up:X up:LAlt up:LShift",
+        result
+    );
+}
+
+#[test]
+fn multi_seq_release_cancels_pending_elements() {
+    let result = simulate(
+        "
+(defsrc a)
+(deflayer base (multi-seq lctl 20 c (layer-while-held nav)))
+(deflayer nav x)
+",
+        "d:a t:10 u:a t:50",
+    )
+    .to_ascii();
+    assert_eq!("dn:LCtrl t:10ms up:LCtrl", result);
+}
+
+#[test]
+fn multi_seq_delayed_layer() {
+    let result = simulate(
+        "
+(defsrc a b)
+(deflayer base (multi-seq lctl 10 (layer-while-held nav)) b)
+(deflayer nav _ x)
+",
+        "d:a t:20 d:b t:10 u:b t:10 u:a t:10 d:b t:10 u:b t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LCtrl t:20ms dn:X t:10ms up:X t:10ms up:LCtrl t:10ms dn:B t:10ms up:B",
+        result
+    );
+}
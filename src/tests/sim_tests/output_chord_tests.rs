@@ -32,3 +32,75 @@ fn output_chord_follows_processing_delay_config() {
         result
     );
 }
+
+static CHORD_OUTPUT_CFG: &str = "\
+(defsrc lsft lctl a b)
+(deflayer base lsft lctl (chord-output lctl lalt del) b)";
+
+#[test]
+fn chord_output_presses_in_order_and_releases_in_reverse() {
+    let result = simulate(CHORD_OUTPUT_CFG, "d:a t:10 u:a t:10")
+        .no_time()
+        .to_ascii();
+    assert_eq!(
+        "dn:LCtrl dn:LAlt dn:Delete up:Delete up:LAlt up:LCtrl",
+        result
+    );
+}
+
+#[test]
+fn chord_output_physical_release_releases_all_keys() {
+    // Another key held across the release does not keep any member pressed.
+    let result = simulate(CHORD_OUTPUT_CFG, "d:a t:10 d:b t:10 u:a t:10 u:b t:10").to_ascii();
+    assert_eq!(
+        "dn:LCtrl dn:LAlt dn:Delete t:10ms dn:B t:10ms up:Delete up:LAlt up:LCtrl t:10ms up:B",
+        result
+    );
+    // A member that is also held physically stays pressed.
+    let result = simulate(CHORD_OUTPUT_CFG, "d:lctl d:a t:10 u:a t:10 u:lctl t:10")
+        .no_time()
+        .to_ascii();
+    assert_eq!(
+        "dn:LCtrl dn:LAlt dn:Delete up:Delete up:LAlt up:LCtrl",
+        result
+    );
+}
+
+#[test]
+fn chord_output_clean_mods() {
+    let result = simulate(CHORD_OUTPUT_CFG, "d:lsft d:a t:10 u:a u:lsft t:10")
+        .no_time()
+        .to_ascii();
+    assert_eq!(
+        "dn:LShift dn:LCtrl dn:LAlt dn:Delete up:Delete up:LAlt up:LCtrl up:LShift",
+        result
+    );
+    let cfg = format!("(defcfg clean-mods-on-action yes)\n{CHORD_OUTPUT_CFG}");
+    let result = simulate(cfg.as_str(), "d:lsft d:a t:10 u:a t:10 u:lsft t:10")
+        .no_time()
+        .to_ascii();
+    assert_eq!(
+        "dn:LShift up:LShift dn:LCtrl dn:LAlt dn:Delete \
+         up:Delete up:LAlt up:LCtrl dn:LShift up:LShift",
+        result
+    );
+    // Modifiers in the chord are not released.
+    let result = simulate(cfg.as_str(), "d:lctl d:a t:10 u:a t:10 u:lctl t:10")
+        .no_time()
+        .to_ascii();
+    assert_eq!(
+        "dn:LCtrl dn:LAlt dn:Delete up:Delete up:LAlt up:LCtrl",
+        result
+    );
+    let cfg = "\
+(defsrc lsft a)
+(deflayer base lsft (preserve-mods (chord-output lctl lalt del)))
+(defcfg clean-mods-on-action yes)";
+    let result = simulate(cfg, "d:lsft d:a t:10 u:a u:lsft t:10")
+        .no_time()
+        .to_ascii();
+    assert_eq!(
+        "dn:LShift dn:LCtrl dn:LAlt dn:Delete up:Delete up:LAlt up:LCtrl up:LShift",
+        result
+    );
+}
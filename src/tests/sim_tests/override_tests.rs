@@ -164,3 +164,90 @@ fn config_with_overridev2() {
         result
     );
 }
+
+const OVERRIDE_ACTIONS_CFG: &str = "
+(defcfg process-unmapped-keys yes)
+(defsrc)
+(deflayer base)
+(defoverrides
+  (lsft 3) (unicode №)
+  (lctl lsft v) (macro h i)
+  (lalt x) (multi lctl c)
+  (lsft a) (lsft 9))
+";
+
+#[test]
+fn override_unicode_action_does_not_leak_input_mods() {
+    let result = simulate(
+        OVERRIDE_ACTIONS_CFG,
+        "d:lsft t:10 d:3 t:10 u:3 t:10 u:lsft t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LShift t:10ms up:LShift t:1ms outU:№ t:3ms dn:LShift t:16ms up:LShift",
+        result
+    );
+    // Repeated presses while the modifier stays held.
+    let result = simulate(
+        OVERRIDE_ACTIONS_CFG,
+        "d:lsft t:10 d:3 t:10 u:3 d:3 t:10 u:3 t:10 u:lsft t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LShift t:10ms up:LShift t:1ms outU:№ t:3ms dn:LShift t:7ms up:LShift t:1ms outU:№ \
+         t:3ms dn:LShift t:15ms up:LShift",
+        result
+    );
+}
+
+#[test]
+fn override_macro_action_restores_mods_after_macro() {
+    // Modifiers held through the macro are restored after it.
+    let result = simulate(
+        OVERRIDE_ACTIONS_CFG,
+        "d:lctl d:lsft t:10 d:v t:10 u:v t:10 u:lsft u:lctl t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LCtrl t:1ms dn:LShift t:9ms up:LCtrl up:LShift t:2ms dn:H t:1ms up:H t:1ms dn:I \
+         t:1ms up:I t:1ms dn:LCtrl dn:LShift t:14ms up:LShift t:1ms up:LCtrl",
+        result
+    );
+    // Modifiers released during the macro are not pressed again.
+    let result = simulate(
+        OVERRIDE_ACTIONS_CFG,
+        "d:lctl d:lsft t:10 d:v t:1 u:v t:1 u:lsft u:lctl t:20",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LCtrl t:1ms dn:LShift t:9ms up:LCtrl up:LShift t:2ms dn:H t:1ms up:H t:1ms dn:I \
+         t:1ms up:I",
+        result
+    );
+}
+
+#[test]
+fn override_multi_action_keeps_its_own_mods() {
+    let result = simulate(
+        OVERRIDE_ACTIONS_CFG,
+        "d:lalt t:10 d:x t:10 u:x t:10 u:lalt t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LAlt t:10ms up:LAlt t:1ms dn:LCtrl dn:C t:3ms up:LCtrl up:C dn:LAlt t:16ms up:LAlt",
+        result
+    );
+}
+
+#[test]
+fn override_key_outputs_unchanged_next_to_actions() {
+    let result = simulate(
+        OVERRIDE_ACTIONS_CFG,
+        "d:lsft t:10 d:a t:10 u:a t:10 u:lsft t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LShift t:10ms dn:Kb9 t:10ms up:Kb9 t:10ms up:LShift",
+        result
+    );
+}
@@ -29,3 +29,104 @@ fn release_reversed() {
     .to_ascii();
     assert_eq!("dn:LAlt dn:A t:10ms up:A up:LAlt", result);
 }
+
+#[test]
+fn press_key_held_until_release_key_on_another_key() {
+    let result = simulate(
+        "
+         (defsrc a b c)
+         (deflayer base (press-key lsft) (release-key lsft) c)
+        ",
+        "
+         d:a t:10 u:a t:10 d:c t:10 u:c t:10 d:b t:10 u:b t:10 d:c t:10 u:c t:10
+        ",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LShift t:20ms dn:C t:10ms up:C t:10ms up:LShift t:20ms dn:C t:10ms up:C",
+        result
+    );
+}
+
+#[test]
+fn press_key_repeated_presses_need_one_release() {
+    let result = simulate(
+        "
+         (defsrc a b c)
+         (deflayer base (press-key c) (key↑ c) (multi (press-key lctl) (press-key c)))
+        ",
+        "
+         d:a t:10 u:a t:10 d:c t:10 u:c t:10 d:b t:10 u:b t:10
+        ",
+    )
+    .to_ascii();
+    assert_eq!("dn:C t:20ms dn:LCtrl t:20ms up:C", result);
+}
+
+#[test]
+fn clear_state_releases_everything_and_returns_to_base() {
+    let result = simulate(
+        "
+         (defsrc a s d f g j)
+         (deflayer base lctl (layer-while-held nav) (one-shot-release 2000 lalt) (layer-switch two) x (caps-word 2000))
+         (deflayer two  lctl (layer-while-held nav) (one-shot-release 2000 lalt) XX z (caps-word 2000))
+         (deflayer nav  _ _ _ _ (multi rsft clear-state) _)
+        ",
+        "
+         d:f t:10 u:f t:10 d:j t:10 u:j t:10 d:a t:10 d:d t:10 u:d t:10 d:s t:10
+         d:g t:10 u:g t:10 u:s t:10 u:a t:10
+         d:g t:10 u:g t:10
+        ",
+    )
+    .to_ascii();
+    assert_eq!(
+        "t:40ms dn:LCtrl t:10ms dn:LAlt t:30ms dn:RShift t:1ms up:LCtrl up:LAlt up:RShift \
+         t:39ms dn:X t:10ms up:X",
+        result
+    );
+}
+
+#[test]
+fn max_output_hold_releases_only_unbacked_keys() {
+    let result = simulate(
+        "
+(defcfg max-output-hold-ms 50)
+(defvirtualkeys vctl lctl)
+(defsrc a b)
+(deflayer base (on-press press-vkey vctl) b)
+",
+        "d:a u:a t:100 d:b t:100 u:b t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "t:2ms dn:LCtrl t:50ms up:LCtrl t:48ms dn:B t:100ms up:B",
+        result
+    );
+}
+
+#[test]
+fn on_release_action_fires_once_on_release() {
+    let result = simulate(
+        "(defsrc a b)
+         (deflayer base (on-release (macro x y)) (on-press b on-release c))",
+        "d:a t:20 r:a t:20 r:a t:20 u:a t:50 d:b t:20 r:b t:20 u:b t:50",
+    )
+    .no_time();
+    // Repeats of b press it again, but c is only tapped once, on the release.
+    assert_eq!(
+        "out:↓X out:↑X out:↓Y out:↑Y out:↓B out:↓B out:↑B out:↓C out:↑C",
+        result
+    );
+}
+
+#[test]
+fn on_release_action_switches_layers() {
+    let result = simulate(
+        "(defsrc a)
+         (deflayer base (on-press lsft on-release (layer-switch other)))
+         (deflayer other (on-release (layer-switch base)))",
+        "d:a t:20 u:a t:20 d:a t:20 u:a t:20 d:a t:20 u:a t:20",
+    )
+    .no_time();
+    assert_eq!("out:↓LShift out:↑LShift out:↓LShift out:↑LShift", result);
+}
@@ -134,3 +134,123 @@ fn repeat_delegate_to_base_layer_trans() {
         result
     );
 }
+
+#[test]
+fn repeat_suppressed_for_layer_and_modifier_actions() {
+    let cfg = "
+         (defsrc a b c d)
+         (deflayer base lsft (layer-while-held held) (tap-hold 50 50 x lctl) d)
+         (deflayer held e _ _ _)
+        ";
+    // Held layer and modifier keys do not repeat, while a letter held at the same time does.
+    let result = simulate(
+        cfg,
+        "d:b t:10 r:b t:10 r:b t:10 u:b t:10 d:a t:10 r:a t:10 d:d t:10 r:d r:a t:10 u:d u:a t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "t:40ms dn:LShift t:20ms dn:D t:10ms dn:D t:10ms up:D t:1ms up:LShift",
+        result
+    );
+    // A tap-hold repeats only if it resolved to its tap action.
+    let result = simulate(cfg, "d:c t:100 r:c t:10 r:c t:10 u:c t:10").to_ascii();
+    assert_eq!("t:50ms dn:LCtrl t:70ms up:LCtrl", result);
+    let result = simulate(cfg, "d:c t:10 u:c t:10 d:c t:10 r:c t:10 u:c t:10").to_ascii();
+    assert_eq!(
+        "t:10ms dn:X t:6ms up:X t:4ms dn:X t:10ms dn:X t:10ms up:X",
+        result
+    );
+}
+
+#[test]
+fn rpt_replays_macro() {
+    let result = simulate(
+        "
+         (defsrc a b c)
+         (deflayer base (macro x y) rpt c)
+        ",
+        "
+         d:a t:10 u:a t:50 d:b t:10 u:b t:50 d:c t:10 u:c t:10 d:b t:10 u:b t:10
+        ",
+    )
+    .to_ascii()
+    .no_time();
+    assert_eq!(
+        "dn:X up:X dn:Y up:Y dn:X up:X dn:Y up:Y dn:C up:C up:C dn:C up:C",
+        result
+    );
+}
+
+#[test]
+fn rpt_replays_unicode_and_multi() {
+    let result = simulate(
+        "
+         (defsrc a b c)
+         (deflayer base (unicode 🙂) rpt (multi lsft z))
+        ",
+        "
+         d:a t:10 u:a t:10 d:b t:10 u:b t:10 d:c t:10 u:c t:10 d:b t:10 u:b t:10
+        ",
+    )
+    .to_ascii()
+    .no_time();
+    assert_eq!(
+        "outU:🙂 outU:🙂 dn:LShift dn:Z up:LShift up:Z dn:LShift dn:Z up:LShift up:Z",
+        result
+    );
+}
+
+#[test]
+fn rpt_repeats_macros_no() {
+    let result = simulate(
+        "
+         (defcfg rpt-repeats-macros no)
+         (defsrc a b)
+         (deflayer base (macro x y) rpt)
+        ",
+        "
+         d:a t:10 u:a t:50 d:b t:10 u:b t:10
+        ",
+    )
+    .to_ascii()
+    .no_time();
+    assert_eq!("dn:X up:X dn:Y up:Y up:Y dn:Y up:Y", result);
+}
+
+#[test]
+fn rpt_held_repeats_key_but_not_macro() {
+    let result = simulate(
+        "
+         (defsrc a b c)
+         (deflayer base (macro x y) rpt c)
+        ",
+        "
+         d:c t:10 u:c t:10 d:b t:10 r:b t:10 r:b t:10 u:b t:10 r:b t:10
+         d:a t:10 u:a t:50 d:b t:10 r:b t:10 r:b t:50 u:b t:10
+        ",
+    )
+    .to_ascii()
+    .no_time();
+    assert_eq!(
+        "dn:C up:C up:C dn:C up:C dn:C up:C dn:C up:C \
+         dn:X up:X dn:Y up:Y dn:X up:X dn:Y up:Y",
+        result
+    );
+}
+
+#[test]
+fn rpt_replays_dynamic_macro_play() {
+    let result = simulate(
+        "
+         (defsrc a b c d)
+         (deflayer base (dynamic-macro-record 0) (dynamic-macro-play 0) c rpt)
+        ",
+        "
+         d:a u:a t:10 d:c t:10 u:c t:10 d:a u:a t:10
+         d:b u:b t:100 d:d t:10 u:d t:100
+        ",
+    )
+    .to_ascii()
+    .no_time();
+    assert_eq!("dn:C up:C dn:C up:C dn:C up:C", result);
+}
@@ -0,0 +1,89 @@
+use super::*;
+
+#[test]
+fn sim_runtime_var_cycles_mode() {
+    let result = simulate(
+        "
+ (defsrc a b)
+ (deflayer base
+   (switch ((var-eq mode 2)) (setvar mode 0) break () (incvar mode) break)
+   (switch ((var-eq mode 0)) x break ((var-eq mode 1)) y break () z break))
+",
+        "d:b t:10 u:b t:10 d:a t:10 u:a t:10 d:b t:10 u:b t:10
+         d:a t:10 u:a t:10 d:b t:10 u:b t:10 d:a t:10 u:a t:10 d:b t:10 u:b t:50",
+    )
+    .no_time();
+    assert_eq!(
+        "out:↓X out:↑X out:↓Y out:↑Y out:↓Z out:↑Z out:↓X out:↑X",
+        result
+    );
+}
+
+#[test]
+fn sim_runtime_var_unset_reads_as_zero() {
+    let result = simulate(
+        "
+ (defsrc a b c)
+ (deflayer base
+   (switch ((var-eq count 0)) x break () y break)
+   (incvar count -3)
+   (switch ((var-lt count -2)) x break ((var-gt count -4)) y break))
+",
+        "d:a t:10 u:a t:10 d:c t:10 u:c t:10 d:b t:10 u:b t:10 d:c t:10 u:c t:50",
+    )
+    .no_time();
+    assert_eq!("out:↓X out:↑X out:↓Y out:↑Y out:↓X out:↑X", result);
+}
+
+#[test]
+fn sim_runtime_var_gt_in_boolean_logic() {
+    let result = simulate(
+        "
+ (defsrc a b)
+ (deflayer base
+   (setvar count 6)
+   (switch ((and (var-gt count 5) (not (var-gt count 6)))) x break () y break))
+",
+        "d:b t:10 u:b t:10 d:a t:10 u:a t:10 d:b t:10 u:b t:50",
+    )
+    .no_time();
+    assert_eq!("out:↓Y out:↑Y out:↓X out:↑X", result);
+}
+
+#[test]
+fn sim_runtime_var_macro_delay() {
+    let result = simulate(
+        "
+ (defsrc a b c)
+ (deflayer base
+   (macro x $gap y)
+   (setvar gap 30)
+   (macro (decvar gap 20) z))
+",
+        "d:a t:10 u:a t:50 d:b t:10 u:b t:10 d:a t:10 u:a t:50
+         d:c t:10 u:c t:10 d:a t:10 u:a t:50",
+    )
+    .to_ascii();
+    assert_eq!(
+        "t:1ms dn:X t:1ms up:X t:2ms dn:Y t:1ms up:Y \
+         t:76ms dn:X t:1ms up:X t:31ms dn:Y t:1ms up:Y \
+         t:28ms dn:Z t:1ms up:Z \
+         t:18ms dn:X t:1ms up:X t:11ms dn:Y t:1ms up:Y",
+        result
+    );
+}
+
+#[test]
+fn sim_runtime_var_macro_delay_clamps() {
+    let result = simulate(
+        "
+ (defsrc a b)
+ (deflayer base
+   (macro x $gap y)
+   (decvar gap 5))
+",
+        "d:b t:10 u:b t:10 d:a t:10 u:a t:50",
+    )
+    .to_ascii();
+    assert_eq!("t:21ms dn:X t:1ms up:X t:2ms dn:Y t:1ms up:Y", result);
+}
@@ -0,0 +1,79 @@
+use super::*;
+
+/// Sends key events with hardware scan codes, ticking after each event, and returns the outputs.
+fn simulate_scancodes(cfg: &str, events: &[(&str, KeyValue, Option<u16>)]) -> String {
+    init_log();
+    let _lk = match CFG_PARSE_LOCK.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let mut k = Kanata::new_from_str(cfg, Default::default()).expect("failed to parse cfg");
+    for &(key, value, scancode) in events {
+        let code = str_to_oscode(key).expect("valid keycode");
+        let event = match scancode {
+            Some(scancode) => KeyEvent::with_scancode(code, value, scancode),
+            None => KeyEvent::new(code, value),
+        };
+        k.handle_input_event(&event).expect("input handles fine");
+        for _ in 0..10 {
+            let _ = k.tick_ms(1, &None);
+        }
+    }
+    k.kbd_out.outputs.events.join(" ").no_time()
+}
+
+#[test]
+fn passthrough_key_uses_scancode() {
+    let result = simulate_scancodes(
+        "(defsrc a b) (deflayer base a c)",
+        &[
+            ("a", KeyValue::Press, Some(0x1e)),
+            ("a", KeyValue::Release, Some(0x1e)),
+            ("b", KeyValue::Press, Some(0x30)),
+            ("b", KeyValue::Release, Some(0x30)),
+        ],
+    )
+    .to_ascii();
+    assert_eq!("out-sc:0x1e;Press out-sc:0x1e;Release dn:C up:C", result);
+}
+
+#[test]
+fn passthrough_key_without_scancode_uses_key_code() {
+    let result = simulate_scancodes(
+        "(defsrc a) (deflayer base a)",
+        &[("a", KeyValue::Press, None), ("a", KeyValue::Release, None)],
+    )
+    .to_ascii();
+    assert_eq!("dn:A up:A", result);
+}
+
+#[test]
+fn tap_output_after_physical_release_uses_key_code() {
+    // The tap is only output once the physical key has been released, so its scan code is no
+    // longer known and the press and release both use the key code.
+    let result = simulate_scancodes(
+        "(defsrc a) (deflayer base (tap-hold 50 50 a lsft))",
+        &[
+            ("a", KeyValue::Press, Some(0x1e)),
+            ("a", KeyValue::Release, Some(0x1e)),
+        ],
+    )
+    .to_ascii();
+    assert_eq!("dn:A up:A", result);
+}
+
+#[test]
+fn raw_event_mode_defsrc_accepts_scancodes() {
+    let result = simulate(
+        "(defcfg event-mode raw)
+         (defsrc 0x10 0x1e 0xE04B lctl)
+         (deflayer base a q rght C-a)",
+        "d:q t:10 u:q t:10 d:a t:10 u:a t:10 d:left t:10 u:left t:10 d:lctl t:10 u:lctl t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:A t:10ms up:A t:10ms dn:Q t:10ms up:Q t:10ms dn:Right t:10ms up:Right t:10ms \
+         dn:LCtrl dn:A t:10ms up:LCtrl up:A",
+        result
+    );
+}
@@ -259,3 +259,141 @@ d:KeyS t:10 d:KeyD t:10 u:KeyS t:10 u:KeyD t:10",
     .to_ascii();
     assert_eq!("outU:μ dn:D outU:μ dn:D", result,);
 }
+
+const WILDCARD_CFG: &str = "
+    (defsrc 0)
+    (deflayer base sldr)
+    (defvirtualkeys s1 (macro x seq-match))
+    (defvirtualkeys s2 z)
+    (defvirtualkeys s3 (macro seq-match seq-match))
+    (defseq s1 (g _))
+    (defseq s2 (g a))
+    (defseq s3 (h (any e S-i)))
+";
+
+#[test]
+fn wildcard_outputs_matched_key() {
+    let result = simulate(WILDCARD_CFG, "d:0 u:0 d:g u:g d:b u:b t:50")
+        .no_time()
+        .to_ascii();
+    assert_eq!("up:G up:B dn:X up:X dn:B up:B", result);
+}
+
+#[test]
+fn wildcard_matches_keys_shifted_with_either_shift() {
+    let result = simulate(
+        WILDCARD_CFG,
+        "d:0 u:0 d:g u:g d:lsft d:b u:b u:lsft t:50 d:0 u:0 d:g u:g d:rsft d:b u:b u:rsft t:50",
+    )
+    .no_time()
+    .to_ascii();
+    // As in chorded_keys_visible_backspaced, RShift is not released before the output.
+    assert_eq!(
+        "up:G up:LShift up:B dn:X up:X dn:LShift dn:B up:B up:LShift \
+         up:G up:B dn:X up:X up:RShift dn:LShift dn:B up:B up:LShift",
+        result
+    );
+}
+
+#[test]
+fn wildcard_prefers_exact_match() {
+    let result = simulate(WILDCARD_CFG, "d:0 u:0 d:g u:g d:a u:a t:50")
+        .no_time()
+        .to_ascii();
+    assert_eq!("up:G up:A dn:Z up:Z", result);
+}
+
+#[test]
+fn class_matches_listed_keys_only() {
+    let result = simulate(
+        WILDCARD_CFG,
+        "d:0 u:0 d:h u:h d:lsft d:i u:i u:lsft t:50 \
+         d:0 u:0 d:h u:h d:i u:i t:2000",
+    )
+    .no_time()
+    .to_ascii();
+    assert_eq!(
+        "up:H up:LShift up:I dn:LShift dn:I up:I up:LShift dn:LShift dn:I up:I up:LShift \
+         up:H up:I",
+        result
+    );
+}
+
+#[test]
+fn wildcard_shifted_key_visible_backspaced() {
+    let result = simulate(
+        format!("(defcfg sequence-input-mode visible-backspaced) {WILDCARD_CFG}").as_str(),
+        "d:0 u:0 d:g u:g d:lsft d:b u:b u:lsft t:50",
+    )
+    .no_time()
+    .to_ascii();
+    assert_eq!(
+        "dn:G up:G dn:LShift dn:B dn:BSpace up:BSpace dn:BSpace up:BSpace \
+         up:LShift up:B dn:X up:X dn:LShift dn:B up:B up:LShift",
+        result
+    );
+}
+
+const UNICODE_ECHO_CFG: &str = "
+    (defcfg sequence-input-mode visible-backspaced)
+    (defsrc 0 1 2)
+    (deflayer base sldr (unicode é) (macro (unicode e) (unicode \u{301})))
+    (defvirtualkeys acute (unicode é) s1 z)
+    (defseq acute (' e) s1 (a b))
+";
+
+#[test]
+fn visible_backspaced_sequence_outputs_unicode() {
+    let result = simulate(UNICODE_ECHO_CFG, "d:0 u:0 d:' u:' d:e u:e t:50")
+        .no_time()
+        .to_ascii();
+    assert_eq!(
+        "dn:Quote up:Quote dn:E dn:BSpace up:BSpace dn:BSpace up:BSpace up:E outU:é",
+        result
+    );
+}
+
+#[test]
+fn visible_backspaced_erases_unicode_echo() {
+    let result = simulate(
+        UNICODE_ECHO_CFG,
+        "d:0 u:0 d:a u:a d:1 u:1 d:b u:b t:50 \
+         d:0 u:0 d:a u:a d:2 u:2 t:50 d:b u:b t:50",
+    )
+    .no_time()
+    .to_ascii();
+    assert_eq!(
+        "dn:A up:A outU:é dn:B dn:BSpace up:BSpace dn:BSpace up:BSpace dn:BSpace up:BSpace \
+         up:B dn:Z up:Z \
+         dn:A up:A outU:e outU:\u{301} dn:B dn:BSpace up:BSpace dn:BSpace up:BSpace \
+         dn:BSpace up:BSpace up:B dn:Z up:Z",
+        result
+    );
+}
+
+const TIMEOUT_CFG: &str = "
+    (defsrc 0)
+    (deflayer base sldr)
+    (defvirtualkeys chem z pass x)
+    (defseq chem (h 2) (timeout 400))
+    (defseq (timeout 3000) pass (p a s s))
+";
+
+#[test]
+fn per_sequence_timeout() {
+    let result = simulate(TIMEOUT_CFG, "d:0 u:0 d:h u:h t:300 d:2 u:2 t:50")
+        .no_time()
+        .to_ascii();
+    assert_eq!("up:H up:Kb2 dn:Z up:Z", result);
+    let result = simulate(TIMEOUT_CFG, "d:0 u:0 d:h u:h t:500 d:2 u:2 t:50")
+        .no_time()
+        .to_ascii();
+    assert_eq!("up:H dn:Kb2 up:Kb2", result);
+    let result = simulate(
+        TIMEOUT_CFG,
+        "d:0 u:0 t:2500 d:p u:p t:2500 d:a u:a d:s u:s d:s u:s t:50",
+    )
+    .no_time()
+    .to_ascii();
+    assert_eq!("up:P up:A up:S up:S dn:X up:X", result);
+}
@@ -0,0 +1,57 @@
+use super::*;
+
+const CFG: &str = "
+ (defsrc a b c)
+ (deflayer base (sticky-layer nav) b c)
+ (deflayer nav _ x y)
+";
+
+#[test]
+fn sim_sticky_layer_tap_latches_for_one_key() {
+    let result = simulate(CFG, "d:a t:10 u:a t:10 d:b t:10 u:b t:10 d:b t:10 u:b t:50").no_time();
+    assert_eq!("out:↓X out:↑X out:↓B out:↑B", result);
+}
+
+#[test]
+fn sim_sticky_layer_latch_used_by_held_key() {
+    let result = simulate(CFG, "d:a t:10 u:a t:10 d:b t:10 d:c t:10 u:c u:b t:50").no_time();
+    assert_eq!("out:↓X out:↓C out:↑C out:↑X", result);
+}
+
+#[test]
+fn sim_sticky_layer_hold_is_active_while_held() {
+    let result = simulate(
+        CFG,
+        "d:a t:10 d:b t:10 u:b t:10 d:c t:10 u:c t:10 u:a t:10 d:b t:10 u:b t:50",
+    )
+    .no_time();
+    assert_eq!("out:↓X out:↑X out:↓Y out:↑Y out:↓B out:↑B", result);
+}
+
+#[test]
+fn sim_sticky_layer_long_press_does_not_latch() {
+    let result = simulate(CFG, "d:a t:300 u:a t:10 d:b t:10 u:b t:50").no_time();
+    assert_eq!("out:↓B out:↑B", result);
+}
+
+#[test]
+fn sim_sticky_layer_double_tap_locks_and_tap_unlocks() {
+    let result = simulate(
+        CFG,
+        "d:a t:10 u:a t:10 d:a t:10 u:a t:10 \
+         d:b t:10 u:b t:10 d:c t:10 u:c t:10 \
+         d:a t:10 u:a t:10 d:b t:10 u:b t:50",
+    )
+    .no_time();
+    assert_eq!("out:↓X out:↑X out:↓Y out:↑Y out:↓B out:↑B", result);
+}
+
+#[test]
+fn sim_sticky_layer_latch_then_hold_does_not_lock() {
+    let result = simulate(
+        CFG,
+        "d:a t:10 u:a t:10 d:a t:300 u:a t:10 d:b t:10 u:b t:50",
+    )
+    .no_time();
+    assert_eq!("out:↓B out:↑B", result);
+}
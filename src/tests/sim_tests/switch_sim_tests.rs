@@ -36,6 +36,33 @@ fn sim_switch_base_layer() {
     assert_eq!("out:↓X out:↑X out:↓Y out:↑Y out:↓Y out:↑Y", result);
 }
 
+#[test]
+fn sim_switch_layer_active_and_base_layer_is() {
+    let result = simulate(
+        "
+         (defcfg)
+         (defsrc a b c esc)
+         (defalias hatch (switch
+            ((and (base-layer-is gaming) (layer-active nav))) z break
+            ((base-layer-is gaming)) (layer-switch base) break
+            ((layer-active nav)) y break
+            () esc break))
+         (deflayer base (layer-switch gaming) (layer-while-held nav) (layer-while-held sym) @hatch)
+         (deflayer gaming a (layer-while-held nav) (layer-while-held sym) @hatch)
+         (deflayer nav _ _ _ _)
+         (deflayer sym _ _ _ _)
+        ",
+        "d:esc u:esc t:10 d:b t:10 d:c t:10 d:esc u:esc t:10 u:c u:b t:10 \
+         d:a u:a t:10 d:b t:10 d:esc u:esc t:10 u:b t:10 \
+         d:esc u:esc t:10 d:esc u:esc t:10",
+    )
+    .no_time();
+    assert_eq!(
+        "out:↓Escape out:↑Escape out:↓Y out:↑Y out:↓Z out:↑Z out:↓Escape out:↑Escape",
+        result
+    );
+}
+
 #[test]
 fn sim_switch_noop() {
     let result = simulate(
@@ -69,3 +96,34 @@ fn sim_switch_trans_not_top_layer() {
     .to_ascii();
     assert_eq!("t:21ms dn:B t:9ms up:B t:101ms dn:B t:9ms up:B", result);
 }
+
+static SWITCH_VKEY_ACTIVE_CFG: &str = "
+(defsrc a b c)
+(defvirtualkeys flag nop0)
+(deflayer base
+  (on-press toggle-vkey flag)
+  (switch ((vkey-active flag)) x break () y break)
+  (switch ((not (vkey-active flag))) z break)
+)
+";
+
+#[test]
+fn sim_switch_vkey_active() {
+    let result = simulate(
+        SWITCH_VKEY_ACTIVE_CFG,
+        "d:b u:b t:10 d:c u:c t:10 \
+         d:a u:a t:10 d:b u:b t:10 d:c u:c t:10 \
+         d:a u:a t:10 d:b u:b t:10",
+    )
+    .no_time()
+    .to_ascii();
+    assert_eq!("dn:Y up:Y dn:Z up:Z dn:X up:X dn:Y up:Y", result);
+}
+
+#[test]
+fn sim_switch_vkey_active_toggled_in_same_tick() {
+    let result = simulate(SWITCH_VKEY_ACTIVE_CFG, "d:a d:b t:10 u:a u:b t:10")
+        .no_time()
+        .to_ascii();
+    assert_eq!("dn:X up:X", result);
+}
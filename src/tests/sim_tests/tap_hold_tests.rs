@@ -171,3 +171,131 @@ fn tap_hold_tap_keys() {
     let result = simulate(cfg, "d:a t:20 u:a t:20 d:a t:200").to_ascii();
     assert_eq!("t:20ms dn:X t:6ms up:X t:14ms dn:X", result);
 }
+
+#[test]
+fn tap_hold_adaptive() {
+    let cfg = "
+        (defcfg tap-hold-adaptive-window 2)
+        (defsrc a b)
+        (deflayer l1 (tap-hold-adaptive 0 200 100 400 x y) b)
+    ";
+
+    // No typing yet: the base timeout applies.
+    let result = simulate(cfg, "d:a t:250 u:a t:50").to_ascii();
+    assert_eq!("t:200ms dn:Y t:50ms up:Y", result);
+
+    // Fast typing lengthens the timeout, up to the max.
+    let result = simulate(
+        cfg,
+        "d:b t:10 u:b t:40 d:b t:10 u:b t:40 d:a t:300 u:a t:50",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:B t:10ms up:B t:40ms dn:B t:10ms up:B t:340ms dn:X t:6ms up:X",
+        result
+    );
+    let result = simulate(
+        cfg,
+        "d:b t:10 u:b t:40 d:b t:10 u:b t:40 d:a t:500 u:a t:50",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:B t:10ms up:B t:40ms dn:B t:10ms up:B t:440ms dn:Y t:100ms up:Y",
+        result
+    );
+
+    // Slow typing shortens the timeout, down to the min.
+    let result = simulate(cfg, "d:b u:b t:400 d:b u:b t:400 d:a t:150 u:a t:50").to_ascii();
+    assert_eq!(
+        "dn:B t:1ms up:B t:399ms dn:B t:1ms up:B t:499ms dn:Y t:50ms up:Y",
+        result
+    );
+
+    // Only the most recent intervals are averaged.
+    let result = simulate(
+        cfg,
+        "d:b u:b t:400 d:b u:b t:400 d:b u:b t:200 d:b u:b t:200 d:a t:150 u:a t:50",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:B t:1ms up:B t:399ms dn:B t:1ms up:B t:399ms dn:B t:1ms up:B t:199ms \
+         dn:B t:1ms up:B t:349ms dn:X t:6ms up:X",
+        result
+    );
+}
+
+#[test]
+fn tap_hold_inherits_layer_tap_timeout() {
+    let cfg = "
+        (defcfg tap-hold-timeout 300)
+        (defsrc a b c)
+        (deflayer (base tap-timeout 100) (tap-hold 0 _ x y) (tap-hold 0 200 x y) (layer-while-held other))
+        (deflayer other (tap-hold 0 _ x y) _ _)
+    ";
+    // The layer timeout applies to actions without their own timeout.
+    let result = simulate(cfg, "d:a t:150 u:a t:50").to_ascii();
+    assert_eq!("t:100ms dn:Y t:50ms up:Y", result);
+    // An explicit action timeout wins over the layer timeout.
+    let result = simulate(cfg, "d:b t:150 u:b t:50").to_ascii();
+    assert_eq!("t:150ms dn:X t:6ms up:X", result);
+    let result = simulate(cfg, "d:b t:250 u:b t:50").to_ascii();
+    assert_eq!("t:200ms dn:Y t:50ms up:Y", result);
+    // Without a layer timeout, the defcfg timeout applies.
+    let result = simulate(cfg, "d:c t:10 d:a t:150 u:a t:50 u:c t:10").to_ascii();
+    assert_eq!("t:160ms dn:X t:6ms up:X", result);
+    let result = simulate(cfg, "d:c t:10 d:a t:350 u:a t:50 u:c t:10").to_ascii();
+    assert_eq!("t:310ms dn:Y t:50ms up:Y", result);
+}
+
+#[test]
+fn tap_hold_layer_tap_timeout_follows_transparent_key() {
+    let cfg = "
+        (defsrc a c)
+        (deflayer (base tap-timeout 100) (tap-hold 0 _ x y) (layer-while-held other))
+        (deflayer (other tap-timeout 300) _ _)
+    ";
+    // The transparent key resolves to the tap-hold on base, which uses the timeout of base.
+    let result = simulate(cfg, "d:c t:10 d:a t:150 u:a t:50 u:c t:10").to_ascii();
+    assert_eq!("t:110ms dn:Y t:50ms up:Y", result);
+}
+
+#[test]
+fn tap_hold_mode_changes_interleaved_resolution() {
+    let cfg = |mode: &str| {
+        format!(
+            "
+            (defcfg tap-hold-mode {mode})
+            (defsrc a b)
+            (deflayer base (tap-hold 0 200 a lctl) b)
+            "
+        )
+    };
+    // Another key pressed and released while the tap-hold is undecided.
+    let roll = "d:a t:20 d:b t:20 u:b t:20 u:a t:50";
+    // Another key pressed while the tap-hold is undecided, released after the tap-hold.
+    let overlap = "d:a t:20 d:b t:20 u:a t:20 u:b t:50";
+
+    let result = simulate(cfg("timeout").as_str(), roll).to_ascii();
+    assert_eq!("t:60ms dn:A t:6ms dn:B t:1ms up:B t:1ms up:A", result);
+    let result = simulate(cfg("timeout").as_str(), overlap).to_ascii();
+    assert_eq!("t:40ms dn:A t:6ms dn:B t:1ms up:A t:13ms up:B", result);
+
+    let result = simulate(cfg("hold-on-other-key-press").as_str(), roll).to_ascii();
+    assert_eq!(
+        "t:20ms dn:LCtrl t:6ms dn:B t:14ms up:B t:20ms up:LCtrl",
+        result
+    );
+    let result = simulate(cfg("hold-on-other-key-press").as_str(), overlap).to_ascii();
+    assert_eq!(
+        "t:20ms dn:LCtrl t:6ms dn:B t:14ms up:LCtrl t:20ms up:B",
+        result
+    );
+
+    let result = simulate(cfg("permissive-hold").as_str(), roll).to_ascii();
+    assert_eq!(
+        "t:40ms dn:LCtrl t:6ms dn:B t:1ms up:B t:13ms up:LCtrl",
+        result
+    );
+    let result = simulate(cfg("permissive-hold").as_str(), overlap).to_ascii();
+    assert_eq!("t:40ms dn:A t:6ms dn:B t:1ms up:A t:13ms up:B", result);
+}
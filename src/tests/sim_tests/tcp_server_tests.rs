@@ -0,0 +1,1180 @@
+use super::*;
+use crate::TcpServer;
+use kanata_parser::keys::OsCode;
+
+use kanata_tcp_protocol::{LayerCycle, ServerMessage};
+use parking_lot::Mutex;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+/// Starts a TCP server for kanata with the given configuration on a free local port and returns
+/// a connected client.
+fn connect(cfg: &str) -> (TcpStream, Arc<Mutex<Kanata>>) {
+    let (stream, k, wakeup_rx) = connect_with_wakeup(cfg);
+    std::thread::spawn(move || while wakeup_rx.recv().is_ok() {});
+    (stream, k)
+}
+
+/// Like `connect`, but also returns the channel that the server sends injected events to.
+fn connect_with_wakeup(cfg: &str) -> (TcpStream, Arc<Mutex<Kanata>>, Receiver<KeyEvent>) {
+    let (address, k, wakeup_rx) = start(cfg);
+    for _ in 0..100 {
+        if let Ok(stream) = TcpStream::connect(address) {
+            read_initial_layer_change(&stream);
+            return (stream, k, wakeup_rx);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    panic!("could not connect to tcp server");
+}
+
+/// Starts a TCP server for kanata with the given configuration on a free local port and returns
+/// its address.
+fn start(cfg: &str) -> (std::net::SocketAddr, Arc<Mutex<Kanata>>, Receiver<KeyEvent>) {
+    start_with(cfg, |_| {})
+}
+
+/// Like `start`, but lets `configure` change the server's settings before it starts.
+fn start_with(
+    cfg: &str,
+    configure: impl FnOnce(&mut TcpServer),
+) -> (std::net::SocketAddr, Arc<Mutex<Kanata>>, Receiver<KeyEvent>) {
+    init_log();
+    let k = {
+        let _lk = match CFG_PARSE_LOCK.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        Kanata::new_from_str(cfg, Default::default()).expect("failed to parse cfg")
+    };
+    let address = TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .expect("free port");
+    let (wakeup_tx, wakeup_rx) = std::sync::mpsc::sync_channel(10);
+    let mut server = TcpServer::new(Some(address), None, wakeup_tx);
+    configure(&mut server);
+    let k = Arc::new(Mutex::new(k));
+    server.start(k.clone());
+    (address, k, wakeup_rx)
+}
+
+/// Reads the `LayerChange` that the server sends every new client. The stream is read one byte
+/// at a time so that nothing after the message is consumed.
+fn read_initial_layer_change(mut stream: &TcpStream) -> String {
+    use std::io::Read;
+    let mut line = vec![];
+    let mut byte = [0u8];
+    while line.last() != Some(&b'\n') {
+        assert_eq!(stream.read(&mut byte).unwrap(), 1, "connection closed");
+        line.push(byte[0]);
+    }
+    let line = String::from_utf8(line).expect("utf-8");
+    // The message is in whichever format the server is configured for.
+    let msg = serde_json::from_str::<ServerMessage>(&line).unwrap_or_else(|_| {
+        line.parse::<kanata_tcp_protocol::KanataMessage>()
+            .ok()
+            .and_then(|msg| ServerMessage::try_from(msg).ok())
+            .unwrap_or_else(|| panic!("invalid message {line}"))
+    });
+    match msg {
+        ServerMessage::LayerChange { new, old: None } => new,
+        msg => panic!("unexpected initial message: {msg:?}"),
+    }
+}
+
+#[test]
+fn tcp_get_state_reports_default_layer() {
+    let (mut stream, _k) = connect(
+        "
+ (defsrc a)
+ (deflayer base a)
+ (deflayer other b)
+",
+    );
+    stream.write_all(b"{\"GetState\":{}}\n").unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    match serde_json::from_str::<ServerMessage>(&line).expect("valid message") {
+        ServerMessage::State {
+            active_layer,
+            layer_stack,
+            oneshot_active,
+            locked_layer,
+            pending_taphold,
+            ..
+        } => {
+            assert_eq!(active_layer, "base");
+            assert!(layer_stack.is_empty());
+            assert!(!oneshot_active);
+            assert_eq!(locked_layer, None);
+            assert!(!pending_taphold);
+        }
+        msg => panic!("unexpected response: {msg:?}"),
+    }
+}
+
+#[test]
+fn tcp_get_state_reports_active_virtual_keys() {
+    let (mut stream, k) = connect(
+        "
+ (defsrc a)
+ (defvirtualkeys flag nop0 other nop1)
+ (deflayer base (on-press toggle-vkey flag))
+",
+    );
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut active_virtual_keys = || {
+        stream.write_all(b"{\"GetState\":{}}\n").unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        match serde_json::from_str::<ServerMessage>(&line).expect("valid message") {
+            ServerMessage::State {
+                active_virtual_keys,
+                ..
+            } => active_virtual_keys,
+            msg => panic!("unexpected response: {msg:?}"),
+        }
+    };
+    let tap_a = || {
+        let mut k = k.lock();
+        for value in [KeyValue::Press, KeyValue::Release] {
+            k.handle_input_event(&KeyEvent::new(OsCode::KEY_A, value))
+                .unwrap();
+            k.tick_ms(2, &None).unwrap();
+        }
+    };
+    assert!(active_virtual_keys().is_empty());
+    tap_a();
+    assert_eq!(active_virtual_keys(), ["flag"]);
+    tap_a();
+    assert!(active_virtual_keys().is_empty());
+}
+
+#[test]
+fn tcp_get_state_reports_layer_cycles() {
+    let (mut stream, k) = connect(
+        "
+ (defsrc a)
+ (deflayer qwerty (layer-cycle (qwerty dvorak)))
+ (deflayer dvorak (layer-cycle (qwerty dvorak)))
+",
+    );
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut layer_cycles = || {
+        stream.write_all(b"{\"GetState\":{}}\n").unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        match serde_json::from_str::<ServerMessage>(&line).expect("valid message") {
+            ServerMessage::State {
+                active_layer,
+                layer_cycles,
+                ..
+            } => (active_layer, layer_cycles),
+            msg => panic!("unexpected response: {msg:?}"),
+        }
+    };
+    let cycle = |selected| {
+        vec![LayerCycle {
+            layers: vec!["qwerty".into(), "dvorak".into()],
+            selected,
+        }]
+    };
+    assert_eq!(layer_cycles(), ("qwerty".into(), cycle(0)));
+    {
+        let mut k = k.lock();
+        for value in [KeyValue::Press, KeyValue::Release] {
+            k.handle_input_event(&KeyEvent::new(OsCode::KEY_A, value))
+                .unwrap();
+            k.tick_ms(2, &None).unwrap();
+        }
+    }
+    assert_eq!(layer_cycles(), ("dvorak".into(), cycle(1)));
+}
+
+#[test]
+fn tcp_get_state_reports_locked_mouse_buttons() {
+    let (mut stream, k) = connect("(defsrc a) (deflayer base mrlk)");
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut locked_mouse_buttons = || {
+        stream.write_all(b"{\"GetState\":{}}\n").unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        match serde_json::from_str::<ServerMessage>(&line).expect("valid message") {
+            ServerMessage::State {
+                locked_mouse_buttons,
+                ..
+            } => locked_mouse_buttons,
+            msg => panic!("unexpected response: {msg:?}"),
+        }
+    };
+    assert!(locked_mouse_buttons().is_empty());
+    {
+        let mut k = k.lock();
+        for value in [KeyValue::Press, KeyValue::Release] {
+            k.handle_input_event(&KeyEvent::new(OsCode::KEY_A, value))
+                .unwrap();
+            k.tick_ms(2, &None).unwrap();
+        }
+    }
+    assert_eq!(locked_mouse_buttons(), ["mrgt"]);
+}
+
+#[test]
+fn tcp_ping_responds_quickly_under_load() {
+    let (mut stream, k) = connect(
+        "
+ (defsrc a b)
+ (deflayer base (tap-hold 200 200 a lsft) (macro a b c d e f))
+",
+    );
+    let stop = Arc::new(AtomicBool::new(false));
+    // Emulate a busy processing thread that frequently takes the lock.
+    let load = {
+        let stop = stop.clone();
+        std::thread::spawn(move || {
+            let mut i = 0u32;
+            while !stop.load(Ordering::Relaxed) {
+                let mut k = k.lock();
+                let code = if i.is_multiple_of(2) {
+                    OsCode::KEY_A
+                } else {
+                    OsCode::KEY_B
+                };
+                let value = if i % 4 < 2 {
+                    KeyValue::Press
+                } else {
+                    KeyValue::Release
+                };
+                let _ = k.handle_input_event(&KeyEvent::new(code, value));
+                let _ = k.tick_ms(1, &None);
+                i = i.wrapping_add(1);
+            }
+        })
+    };
+    std::thread::sleep(Duration::from_millis(50));
+
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    for _ in 0..10 {
+        let start = Instant::now();
+        stream.write_all(b"{\"Ping\":{}}\n").unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let elapsed = start.elapsed();
+        match serde_json::from_str::<ServerMessage>(&line).expect("valid message") {
+            ServerMessage::Pong {
+                version,
+                platform,
+                config_path,
+                ..
+            } => {
+                assert_eq!(version, env!("CARGO_PKG_VERSION"));
+                assert_eq!(platform, std::env::consts::OS);
+                assert_eq!(config_path, "config string");
+            }
+            msg => panic!("unexpected response: {msg:?}"),
+        }
+        assert!(
+            elapsed < Duration::from_millis(100),
+            "pong took {elapsed:?}"
+        );
+    }
+    stop.store(true, Ordering::Relaxed);
+    load.join().unwrap();
+}
+
+#[test]
+fn tcp_play_macro_queues_and_rate_limits() {
+    let (mut stream, k) = connect(
+        "
+ (defmacro m1 (a b) m2 (c))
+ (defsrc a)
+ (deflayer base a)
+",
+    );
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request = |msg: &str| {
+        stream.write_all(msg.as_bytes()).unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        serde_json::from_str::<ServerMessage>(&line).expect("valid message")
+    };
+    for name in ["m1", "m2"] {
+        let msg = request(&format!("{{\"PlayMacro\":{{\"name\":\"{name}\"}}}}\n"));
+        assert!(
+            matches!(msg, ServerMessage::MacroResult { queued: true }),
+            "unexpected response: {msg:?}"
+        );
+    }
+    let msg = request("{\"PlayMacro\":{\"name\":\"m3\"}}\n");
+    assert!(
+        matches!(msg, ServerMessage::Error { .. }),
+        "unexpected response: {msg:?}"
+    );
+
+    // The second macro starts one second after the first.
+    let mut k = k.lock();
+    for _ in 0..1100 {
+        k.tick_ms(1, &None).unwrap();
+    }
+    let result = k.kbd_out.outputs.events.join("\n").to_ascii();
+    assert_eq!(
+        "t:2ms dn:A t:1ms up:A t:1ms dn:B t:1ms up:B t:997ms dn:C t:1ms up:C",
+        result
+    );
+}
+
+#[test]
+fn tcp_temp_remap_expires_and_limits() {
+    let (mut stream, k) = connect(
+        "
+ (defcfg process-unmapped-keys yes)
+ (defsrc h)
+ (deflayer base x)
+",
+    );
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request = |msg: &str| {
+        stream.write_all(msg.as_bytes()).unwrap();
+        // Only errors get a response; a ping marks the end of the response to msg.
+        stream.write_all(b"{\"Ping\":{}}\n").unwrap();
+        let mut responses = vec![];
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            match serde_json::from_str::<ServerMessage>(&line).expect("valid message") {
+                ServerMessage::Pong { .. } => return responses,
+                msg => responses.push(msg),
+            }
+        }
+    };
+    let remap = |from: &str, duration_ms: u64| {
+        format!(
+            "{{\"TempRemap\":{{\"from\":\"{from}\",\"to\":\"left\",\"duration_ms\":{duration_ms}}}}}\n"
+        )
+    };
+    assert!(request(&remap("h", 200)).is_empty());
+    for msg in [remap("nope", 200), remap("h", 200).replace("left", "nope")] {
+        let responses = request(&msg);
+        assert!(
+            matches!(responses[..], [ServerMessage::Error { .. }]),
+            "unexpected response: {responses:?}"
+        );
+    }
+    let tap_h = |k: &Arc<Mutex<Kanata>>| {
+        let mut k = k.lock();
+        for value in [KeyValue::Press, KeyValue::Release] {
+            k.handle_input_event(&KeyEvent::new(OsCode::KEY_H, value))
+                .unwrap();
+            k.tick_ms(1, &None).unwrap();
+        }
+    };
+    tap_h(&k);
+    std::thread::sleep(Duration::from_millis(250));
+    tap_h(&k);
+    let result = k.lock().kbd_out.outputs.events.join("\n").to_ascii();
+    assert_eq!("dn:Left t:1ms up:Left t:1ms dn:X t:1ms up:X", result);
+
+    // Replacing an existing remap does not count towards the limit.
+    let keys = [
+        "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p",
+    ];
+    for key in keys.iter().chain(&["a"]) {
+        assert!(request(&remap(key, 10000)).is_empty());
+    }
+    let responses = request(&remap("q", 10000));
+    assert!(
+        matches!(responses[..], [ServerMessage::Error { .. }]),
+        "unexpected response: {responses:?}"
+    );
+}
+
+#[test]
+fn tcp_key_stats_count_physical_presses_only() {
+    let (mut stream, k, wakeup_rx) = connect_with_wakeup(
+        "
+ (defcfg process-unmapped-keys yes)
+ (defsrc a)
+ (deflayer base b)
+",
+    );
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut get_stats = |stream: &mut TcpStream| {
+        stream.write_all(b"{\"GetStats\":{}}\n").unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        match serde_json::from_str::<ServerMessage>(&line).expect("valid message") {
+            ServerMessage::KeyStats(stats) => stats,
+            msg => panic!("unexpected response: {msg:?}"),
+        }
+    };
+    let handle = |k: &Arc<Mutex<Kanata>>, code: OsCode, value: KeyValue| {
+        let mut k = k.lock();
+        k.handle_input_event(&KeyEvent::new(code, value)).unwrap();
+        k.tick_ms(1, &None).unwrap();
+    };
+    for _ in 0..3 {
+        handle(&k, OsCode::KEY_A, KeyValue::Press);
+        handle(&k, OsCode::KEY_A, KeyValue::Release);
+    }
+    handle(&k, OsCode::KEY_LEFTALT, KeyValue::Press);
+    handle(&k, OsCode::KEY_LEFTALT, KeyValue::Repeat);
+    handle(&k, OsCode::KEY_LEFTALT, KeyValue::Release);
+
+    // Injected events are processed like physical ones but not counted.
+    stream
+        .write_all(b"{\"KeyInject\":{\"key\":\"a\",\"value\":\"tap\"}}\n")
+        .unwrap();
+    for _ in 0..2 {
+        let event = wakeup_rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("injected event");
+        handle(&k, event.code, event.value);
+    }
+    let stats = get_stats(&mut stream);
+    assert_eq!(
+        stats.into_iter().collect::<Vec<_>>(),
+        [("a".to_string(), 3), ("lalt".to_string(), 1)]
+    );
+
+    stream.write_all(b"{\"ResetStats\":{}}\n").unwrap();
+    assert!(get_stats(&mut stream).is_empty());
+    handle(&k, OsCode::KEY_A, KeyValue::Press);
+    handle(&k, OsCode::KEY_A, KeyValue::Release);
+    assert_eq!(get_stats(&mut stream).get("a"), Some(&1));
+}
+
+#[test]
+fn tcp_pressed_keys_request_and_notifications() {
+    let (mut stream, k) = connect(
+        "
+ (defsrc f20)
+ (deflayer base x)
+",
+    );
+    // PRESSED_KEYS is shared with tests running in parallel, so only f20 is checked.
+    let set_pressed = |pressed: bool| {
+        let mut keys = crate::PRESSED_KEYS.lock();
+        if pressed {
+            #[cfg(not(all(target_os = "windows", not(feature = "interception_driver"))))]
+            keys.insert(OsCode::KEY_F20);
+            #[cfg(all(target_os = "windows", not(feature = "interception_driver")))]
+            keys.insert(OsCode::KEY_F20, web_time::Instant::now());
+        } else {
+            keys.remove(&OsCode::KEY_F20);
+        }
+    };
+    let contains = |keys: &[String], key: &str| keys.iter().any(|k| k == key);
+
+    stream
+        .write_all(b"{\"Subscribe\":[\"PressedKeys\"]}\n")
+        .unwrap();
+    let start = Instant::now();
+    while !crate::tcp_server::PRESSED_KEYS_SUBSCRIBED.load(Ordering::Relaxed) {
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "subscribe timed out"
+        );
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    let (tx, rx) = std::sync::mpsc::sync_channel(100);
+    let tx = Some(tx);
+    set_pressed(true);
+    {
+        let mut k = k.lock();
+        k.handle_input_event(&KeyEvent::new(OsCode::KEY_F20, KeyValue::Press))
+            .unwrap();
+        k.tick_ms(2, &tx).unwrap();
+    }
+    let notified = rx.try_iter().any(|msg| match msg {
+        ServerMessage::PressedKeys { keys, output } => {
+            contains(&keys, "f20") && output.is_some_and(|output| contains(&output, "x"))
+        }
+        _ => false,
+    });
+    assert!(notified, "no PressedKeys notification for the press");
+
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    stream.write_all(b"{\"RequestPressedKeys\":{}}\n").unwrap();
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    match serde_json::from_str::<ServerMessage>(&line).expect("valid message") {
+        ServerMessage::PressedKeys { keys, output } => {
+            assert!(contains(&keys, "f20"), "{keys:?}");
+            let output = output.expect("output differs from physical keys");
+            assert!(contains(&output, "x"), "{output:?}");
+            assert!(!contains(&output, "f20"), "{output:?}");
+        }
+        msg => panic!("unexpected response: {msg:?}"),
+    }
+
+    set_pressed(false);
+    {
+        let mut k = k.lock();
+        k.handle_input_event(&KeyEvent::new(OsCode::KEY_F20, KeyValue::Release))
+            .unwrap();
+        k.tick_ms(2, &tx).unwrap();
+    }
+    let notified = rx.try_iter().any(|msg| match msg {
+        ServerMessage::PressedKeys { keys, output } => {
+            !contains(&keys, "f20") && !output.is_some_and(|output| contains(&output, "x"))
+        }
+        _ => false,
+    });
+    assert!(notified, "no PressedKeys notification for the release");
+}
+
+#[test]
+fn tcp_notifications_sent_to_clients_without_hello_or_subscribe() {
+    init_log();
+    let k = {
+        let _lk = match CFG_PARSE_LOCK.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        Kanata::new_from_str("(defsrc a) (deflayer base a)", Default::default())
+            .expect("failed to parse cfg")
+    };
+    let address = TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .expect("free port");
+    let (wakeup_tx, _wakeup_rx) = std::sync::mpsc::sync_channel(10);
+    let mut server = TcpServer::new(Some(address), None, wakeup_tx);
+    let (ntx, nrx) = std::sync::mpsc::sync_channel(10);
+    Kanata::start_notification_loop(nrx, server.connections.clone());
+    server.start(Arc::new(Mutex::new(k)));
+    let connect = || {
+        for _ in 0..100 {
+            if let Ok(stream) = TcpStream::connect(address) {
+                // Every client is told the current layer, whether it says hello or not.
+                assert_eq!(read_initial_layer_change(&stream), "base");
+                return stream;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("could not connect to tcp server");
+    };
+    let read_msg = |reader: &mut BufReader<TcpStream>| {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        serde_json::from_str::<ServerMessage>(&line).expect("valid message")
+    };
+
+    // Both clients are registered for notifications once they answered a command.
+    let mut legacy = connect();
+    let mut legacy_reader = BufReader::new(legacy.try_clone().unwrap());
+    legacy.write_all(b"{\"Ping\":{}}\n").unwrap();
+    assert!(matches!(
+        read_msg(&mut legacy_reader),
+        ServerMessage::Pong { .. }
+    ));
+    let mut hello = connect();
+    let mut hello_reader = BufReader::new(hello.try_clone().unwrap());
+    hello.write_all(b"{\"Hello\":{}}\n").unwrap();
+    assert!(matches!(
+        read_msg(&mut hello_reader),
+        ServerMessage::HelloOk { .. }
+    ));
+
+    ntx.send(ServerMessage::LayerChange {
+        new: "base".into(),
+        old: Some("other".into()),
+    })
+    .unwrap();
+    match read_msg(&mut legacy_reader) {
+        ServerMessage::LayerChange { new, .. } => assert_eq!(new, "base"),
+        msg => panic!("unexpected message: {msg:?}"),
+    }
+    // The notification was relayed to every client before the legacy client received it.
+    hello.write_all(b"{\"Ping\":{}}\n").unwrap();
+    match read_msg(&mut hello_reader) {
+        ServerMessage::Pong { .. } => {}
+        msg => panic!("unexpected message: {msg:?}"),
+    }
+    // The notification loop panics once its channel disconnects.
+    std::mem::forget(ntx);
+}
+
+#[test]
+fn tcp_ready_sent_to_subscribers_while_devices_are_seized() {
+    let (mut stream, _k) = connect(
+        "
+ (defsrc a)
+ (deflayer base a)
+",
+    );
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    crate::tcp_server::notify_ready(vec!["kbd".into()], true);
+    stream.write_all(b"{\"Subscribe\":[\"Ready\"]}\n").unwrap();
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    match serde_json::from_str::<ServerMessage>(&line).expect("valid message") {
+        ServerMessage::Ready { devices, recovered } => {
+            assert_eq!(devices, ["kbd"]);
+            assert!(recovered);
+        }
+        msg => panic!("unexpected response: {msg:?}"),
+    }
+
+    // Subscribing while the devices are released does not report them as ready.
+    crate::tcp_server::notify_input_released();
+    stream.write_all(b"{\"Subscribe\":[\"Ready\"]}\n").unwrap();
+    stream.write_all(b"{\"GetState\":{}}\n").unwrap();
+    line.clear();
+    reader.read_line(&mut line).unwrap();
+    match serde_json::from_str::<ServerMessage>(&line).expect("valid message") {
+        ServerMessage::State { .. } => {}
+        msg => panic!("unexpected response: {msg:?}"),
+    }
+}
+
+/// Serializes the tests that replace the key monitor channel.
+static KEY_MONITOR_LOCK: Mutex<()> = Mutex::new(());
+
+#[test]
+fn monitored_key_events_report_device_id() {
+    let _lk = KEY_MONITOR_LOCK.lock();
+    let (tx, rx) = std::sync::mpsc::sync_channel(10);
+    let prev_tx = crate::tcp_server::set_key_monitor_channel(Some(tx));
+    let prev_active = crate::tcp_server::KEY_MONITOR_ACTIVE.swap(true, Ordering::Relaxed);
+    let event = KeyEvent {
+        device_id: 7,
+        ..KeyEvent::new(OsCode::KEY_A, KeyValue::Press)
+    };
+    crate::tcp_server::monitor_key_event(&event);
+    crate::tcp_server::KEY_MONITOR_ACTIVE.store(prev_active, Ordering::Relaxed);
+    crate::tcp_server::set_key_monitor_channel(prev_tx);
+    // Other tests may send messages to the same channel.
+    let device_id = rx.try_iter().find_map(|msg| match msg {
+        ServerMessage::KeyEvent {
+            code, device_id, ..
+        } if code == "a" => Some(device_id),
+        _ => None,
+    });
+    assert_eq!(device_id, Some(7));
+}
+
+#[test]
+fn monitored_key_events_do_not_fill_the_notification_channel() {
+    let _lk = KEY_MONITOR_LOCK.lock();
+    let (mtx, mrx) = std::sync::mpsc::sync_channel(1);
+    let (ntx, nrx) = std::sync::mpsc::sync_channel(1);
+    let prev_mtx = crate::tcp_server::set_key_monitor_channel(Some(mtx));
+    let prev_ntx = crate::tcp_server::set_notification_channel(Some(ntx));
+    let prev_active = crate::tcp_server::KEY_MONITOR_ACTIVE.swap(true, Ordering::Relaxed);
+    for _ in 0..10 {
+        crate::tcp_server::monitor_key_event(&KeyEvent::new(OsCode::KEY_B, KeyValue::Press));
+    }
+    crate::tcp_server::KEY_MONITOR_ACTIVE.store(prev_active, Ordering::Relaxed);
+    crate::tcp_server::set_notification_channel(prev_ntx);
+    crate::tcp_server::set_key_monitor_channel(prev_mtx);
+    // The key events beyond the capacity of their own channel are dropped.
+    assert!(matches!(
+        mrx.try_iter().collect::<Vec<_>>().as_slice(),
+        [ServerMessage::KeyEvent { .. }]
+    ));
+    // Other tests may send messages to the notification channel.
+    assert!(
+        !nrx.try_iter()
+            .any(|msg| matches!(msg, ServerMessage::KeyEvent { .. }))
+    );
+}
+
+#[test]
+fn tcp_reload_wait_timeout_reports_time_waited() {
+    // No processing loop runs, so the reload never completes.
+    let (mut stream, _k) = connect(
+        "
+ (defsrc a)
+ (deflayer base a)
+",
+    );
+    stream
+        .write_all(b"{\"Reload\":{\"wait\":true,\"timeout_ms\":50}}\n")
+        .unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    match serde_json::from_str::<ServerMessage>(&line).expect("valid message") {
+        ServerMessage::ReloadResult {
+            success,
+            duration_ms,
+            timeout_ms,
+            ..
+        } => {
+            assert!(!success);
+            assert_eq!(timeout_ms, Some(50));
+            assert!(duration_ms >= 50, "{duration_ms}");
+        }
+        msg => panic!("unexpected response: {msg:?}"),
+    }
+}
+
+#[test]
+fn tcp_set_log_level_returns_previous_level() {
+    let (mut stream, _k) = connect(
+        "
+ (defsrc a)
+ (deflayer base a)
+",
+    );
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut set_log_level = |stream: &mut TcpStream, level: &str| {
+        stream
+            .write_all(format!("{{\"SetLogLevel\":{{\"level\":\"{level}\"}}}}\n").as_bytes())
+            .unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        serde_json::from_str::<ServerMessage>(&line).expect("valid message")
+    };
+    let original = log::max_level();
+
+    match set_log_level(&mut stream, "debug") {
+        ServerMessage::LogLevel { previous, current } => {
+            assert_eq!(previous, original.as_str().to_lowercase());
+            assert_eq!(current, "debug");
+        }
+        msg => panic!("unexpected response: {msg:?}"),
+    }
+    assert_eq!(log::max_level(), log::LevelFilter::Debug);
+
+    for invalid in ["off", "verbose"] {
+        match set_log_level(&mut stream, invalid) {
+            ServerMessage::Error { msg } => assert!(msg.contains(invalid), "{msg}"),
+            msg => panic!("unexpected response: {msg:?}"),
+        }
+    }
+    assert_eq!(log::max_level(), log::LevelFilter::Debug);
+
+    match set_log_level(&mut stream, "WARN") {
+        ServerMessage::LogLevel { previous, current } => {
+            assert_eq!(previous, "debug");
+            assert_eq!(current, "warn");
+        }
+        msg => panic!("unexpected response: {msg:?}"),
+    }
+    log::set_max_level(original);
+}
+
+#[test]
+fn tcp_command_times_out_while_processing_thread_is_busy() {
+    let (mut stream, k) = connect(
+        "
+ (defcfg tcp-command-timeout-ms 50)
+ (defsrc a)
+ (deflayer base a)
+",
+    );
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut read_msg = || {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        serde_json::from_str::<ServerMessage>(&line).expect("valid message")
+    };
+
+    // Make sure the client is being served before the lock is taken.
+    stream.write_all(b"{\"Ping\":{}}\n").unwrap();
+    assert!(matches!(read_msg(), ServerMessage::Pong { .. }));
+
+    // Simulate a processing thread that is stuck, e.g. in a long blocking macro.
+    let stuck = k.lock();
+    let start = Instant::now();
+    stream.write_all(b"{\"GetState\":{}}\n").unwrap();
+    match read_msg() {
+        ServerMessage::Error { msg } => assert_eq!(msg, "processing_thread_busy"),
+        msg => panic!("unexpected response: {msg:?}"),
+    }
+    assert!(start.elapsed() < Duration::from_secs(1));
+    drop(stuck);
+
+    stream.write_all(b"{\"GetState\":{}}\n").unwrap();
+    match read_msg() {
+        ServerMessage::State { active_layer, .. } => assert_eq!(active_layer, "base"),
+        msg => panic!("unexpected response: {msg:?}"),
+    }
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn tcp_tls_connection_with_self_signed_certificate() {
+    use rustls::pki_types::{CertificateDer, ServerName, pem::PemObject};
+
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/sim_tests/tls");
+    let (address, _k, _wakeup_rx) = start(&format!(
+        "
+ (defcfg
+   tcp-server-tls-cert \"{dir}/cert.pem\"
+   tcp-server-tls-key \"{dir}/key.pem\")
+ (defsrc a)
+ (deflayer base a)
+"
+    ));
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(format!("{dir}/cert.pem")).unwrap() {
+        roots.add(cert.unwrap()).unwrap();
+    }
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    let conn =
+        rustls::ClientConnection::new(Arc::new(config), ServerName::try_from("localhost").unwrap())
+            .unwrap();
+    let stream = TcpStream::connect(address).unwrap();
+    let mut tls = rustls::StreamOwned::new(conn, stream);
+    tls.write_all(b"{\"GetState\":{}}\n").unwrap();
+    let mut reader = BufReader::new(tls);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    match serde_json::from_str::<ServerMessage>(&line).expect("valid message") {
+        ServerMessage::LayerChange { new, old: None } => assert_eq!(new, "base"),
+        msg => panic!("unexpected initial message: {msg:?}"),
+    }
+    line.clear();
+    reader.read_line(&mut line).unwrap();
+    match serde_json::from_str::<ServerMessage>(&line).expect("valid message") {
+        ServerMessage::State { active_layer, .. } => assert_eq!(active_layer, "base"),
+        msg => panic!("unexpected response: {msg:?}"),
+    }
+}
+
+#[test]
+fn tcp_websocket_handshake_and_framed_messages() {
+    use tungstenite::Message;
+
+    let (address, _k, _wakeup_rx) = start(
+        "
+ (defcfg tcp-server-protocol ws)
+ (defsrc a)
+ (deflayer base a)
+",
+    );
+    // This client never sends a handshake. It must not hold up others.
+    let stalled = TcpStream::connect(address).unwrap();
+    let stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let (mut ws, _) =
+        tungstenite::client(format!("ws://{address}/kanata"), stream).expect("websocket handshake");
+    let read_msg = |ws: &mut tungstenite::WebSocket<TcpStream>| loop {
+        match ws.read().unwrap() {
+            Message::Text(text) => {
+                break serde_json::from_str::<ServerMessage>(&text).expect("valid message");
+            }
+            Message::Ping(_) | Message::Pong(_) => continue,
+            msg => panic!("unexpected frame: {msg:?}"),
+        }
+    };
+    match read_msg(&mut ws) {
+        ServerMessage::LayerChange { new, old: None } => assert_eq!(new, "base"),
+        msg => panic!("unexpected initial message: {msg:?}"),
+    }
+    ws.send(Message::text(r#"{"Ping":{}}"#)).unwrap();
+    match read_msg(&mut ws) {
+        ServerMessage::Pong { version, .. } => assert_eq!(version, env!("CARGO_PKG_VERSION")),
+        msg => panic!("unexpected response: {msg:?}"),
+    }
+    drop(stalled);
+}
+
+/// Starts a TCP server for kanata with the given configuration that relays the notifications
+/// sent to the returned channel, and returns its address.
+fn start_with_notifications(
+    cfg: &str,
+) -> (
+    std::net::SocketAddr,
+    std::sync::mpsc::SyncSender<ServerMessage>,
+) {
+    init_log();
+    let k = {
+        let _lk = match CFG_PARSE_LOCK.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        Kanata::new_from_str(cfg, Default::default()).expect("failed to parse cfg")
+    };
+    let address = TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .expect("free port");
+    let (wakeup_tx, wakeup_rx) = std::sync::mpsc::sync_channel(10);
+    std::thread::spawn(move || while wakeup_rx.recv().is_ok() {});
+    let mut server = TcpServer::new(Some(address), None, wakeup_tx);
+    let (ntx, nrx) = std::sync::mpsc::sync_channel(1000);
+    Kanata::start_notification_loop(nrx, server.connections.clone());
+    server.start(Arc::new(Mutex::new(k)));
+    (address, ntx)
+}
+
+const CONCURRENT_COUNT: usize = 50;
+
+/// Sends large notifications from another thread. Large frames take several writes to the
+/// socket, which responses written at the same time must not interrupt.
+fn send_large_notifications(
+    ntx: std::sync::mpsc::SyncSender<ServerMessage>,
+) -> std::thread::JoinHandle<()> {
+    let notifier = std::thread::spawn(move || {
+        for _ in 0..CONCURRENT_COUNT {
+            ntx.send(ServerMessage::LayerChange {
+                new: "x".repeat(100_000),
+                old: None,
+            })
+            .unwrap();
+        }
+        // The notification loop panics once its channel disconnects.
+        std::mem::forget(ntx);
+    });
+    // Wait for the notifications to fill up the socket buffers, so that the responses are
+    // written while a notification is only partially sent.
+    std::thread::sleep(Duration::from_millis(200));
+    notifier
+}
+
+#[test]
+fn tcp_websocket_concurrent_notifications_and_responses_are_separate_frames() {
+    use tungstenite::Message;
+
+    let (address, ntx) = start_with_notifications(
+        "
+ (defcfg tcp-server-protocol ws)
+ (defsrc a)
+ (deflayer base a)
+",
+    );
+    let stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let (mut ws, _) =
+        tungstenite::client(format!("ws://{address}/kanata"), stream).expect("websocket handshake");
+    let read_msg = |ws: &mut tungstenite::WebSocket<TcpStream>| loop {
+        match ws.read().unwrap() {
+            Message::Text(text) => {
+                break serde_json::from_str::<ServerMessage>(&text)
+                    .unwrap_or_else(|e| panic!("invalid frame {text}: {e}"));
+            }
+            Message::Ping(_) | Message::Pong(_) => continue,
+            msg => panic!("unexpected frame: {msg:?}"),
+        }
+    };
+    ws.send(Message::text(r#"{"Subscribe":["LayerChange"]}"#))
+        .unwrap();
+    assert!(matches!(
+        read_msg(&mut ws),
+        ServerMessage::LayerChange { .. }
+    ));
+
+    let notifier = send_large_notifications(ntx);
+    for _ in 0..CONCURRENT_COUNT {
+        ws.send(Message::text(r#"{"Ping":{}}"#)).unwrap();
+        // Pings from the client are answered by the server while it writes other frames.
+        ws.send(Message::Ping(Vec::new().into())).unwrap();
+    }
+    let (mut pongs, mut layer_changes) = (0, 0);
+    while pongs < CONCURRENT_COUNT || layer_changes < CONCURRENT_COUNT {
+        match read_msg(&mut ws) {
+            ServerMessage::Pong { .. } => pongs += 1,
+            ServerMessage::LayerChange { .. } => layer_changes += 1,
+            msg => panic!("unexpected message: {msg:?}"),
+        }
+    }
+    notifier.join().unwrap();
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn tcp_tls_concurrent_notifications_and_responses_are_separate_records() {
+    use rustls::pki_types::{CertificateDer, ServerName, pem::PemObject};
+
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/sim_tests/tls");
+    let (address, ntx) = start_with_notifications(&format!(
+        "
+ (defcfg
+   tcp-server-tls-cert \"{dir}/cert.pem\"
+   tcp-server-tls-key \"{dir}/key.pem\")
+ (defsrc a)
+ (deflayer base a)
+"
+    ));
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(format!("{dir}/cert.pem")).unwrap() {
+        roots.add(cert.unwrap()).unwrap();
+    }
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    let conn =
+        rustls::ClientConnection::new(Arc::new(config), ServerName::try_from("localhost").unwrap())
+            .unwrap();
+    let stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut reader = BufReader::new(rustls::StreamOwned::new(conn, stream));
+    let read_msg = |reader: &mut BufReader<_>| {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        serde_json::from_str::<ServerMessage>(&line)
+            .unwrap_or_else(|e| panic!("invalid message {line}: {e}"))
+    };
+    reader
+        .get_mut()
+        .write_all(b"{\"Subscribe\":[\"LayerChange\"]}\n")
+        .unwrap();
+    assert!(matches!(
+        read_msg(&mut reader),
+        ServerMessage::LayerChange { .. }
+    ));
+
+    let notifier = send_large_notifications(ntx);
+    for _ in 0..CONCURRENT_COUNT {
+        reader.get_mut().write_all(b"{\"Ping\":{}}\n").unwrap();
+    }
+    let (mut pongs, mut layer_changes) = (0, 0);
+    while pongs < CONCURRENT_COUNT || layer_changes < CONCURRENT_COUNT {
+        match read_msg(&mut reader) {
+            ServerMessage::Pong { .. } => pongs += 1,
+            ServerMessage::LayerChange { .. } => layer_changes += 1,
+            msg => panic!("unexpected message: {msg:?}"),
+        }
+    }
+    notifier.join().unwrap();
+}
+
+#[test]
+fn tcp_tagged_format_commands_and_responses() {
+    use kanata_tcp_protocol::KanataMessage;
+    let (mut stream, _k) = connect(
+        "
+ (defcfg tcp-server-format tagged)
+ (defsrc a)
+ (deflayer base a)
+ (deflayer other b)
+",
+    );
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut read_message = || {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        line.parse::<KanataMessage>()
+            .unwrap_or_else(|e| panic!("invalid message {line}: {e}"))
+    };
+
+    stream
+        .write_all(b"{\"type\":\"SetLayer\",\"name\":\"other\"}\n")
+        .unwrap();
+    stream.write_all(b"{\"type\":\"GetState\"}\n").unwrap();
+    match read_message() {
+        KanataMessage::State { active_layer, .. } => assert_eq!(active_layer, "other"),
+        msg => panic!("unexpected response: {msg:?}"),
+    }
+
+    // Messages in the legacy format are rejected.
+    stream.write_all(b"{\"GetState\":{}}\n").unwrap();
+    match read_message() {
+        KanataMessage::Error { msg } => {
+            assert!(msg.starts_with("Failed to deserialize command"), "{msg}")
+        }
+        msg => panic!("unexpected response: {msg:?}"),
+    }
+}
+
+#[test]
+fn sequence_notifications_report_progress_and_end() {
+    init_log();
+    let mut k = {
+        let _lk = match CFG_PARSE_LOCK.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        Kanata::new_from_str(
+            "
+(defcfg sequence-timeout 100 sequence-notifications yes)
+(defsrc 0 a b c)
+(deflayer base sldr a b c)
+(defvirtualkeys v1 x v2 y)
+(defseq v1 (a b) v2 (a c))
+",
+            Default::default(),
+        )
+        .expect("failed to parse cfg")
+    };
+    let (tx, rx) = std::sync::mpsc::sync_channel(100);
+    let tx = Some(tx);
+    let tap = |k: &mut Kanata, osc: OsCode| {
+        k.handle_input_event(&KeyEvent::new(osc, KeyValue::Press))
+            .unwrap();
+        k.tick_ms(10, &tx).unwrap();
+        k.handle_input_event(&KeyEvent::new(osc, KeyValue::Release))
+            .unwrap();
+        k.tick_ms(10, &tx).unwrap();
+    };
+    tap(&mut k, OsCode::KEY_0);
+    tap(&mut k, OsCode::KEY_A);
+    tap(&mut k, OsCode::KEY_C);
+    tap(&mut k, OsCode::KEY_0);
+    k.tick_ms(200, &tx).unwrap();
+    let notifications = rx
+        .try_iter()
+        .map(|msg| serde_json::to_string(&msg).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        notifications,
+        [
+            r#"{"SequenceStarted":{"timeout_ms":100}}"#,
+            r#"{"SequenceProgress":{"keys":["a"],"candidates":2}}"#,
+            r#"{"SequenceEnded":{"reason":"completed"}}"#,
+            r#"{"SequenceStarted":{"timeout_ms":100}}"#,
+            r#"{"SequenceEnded":{"reason":"timeout"}}"#,
+        ]
+    );
+}
+
+#[test]
+fn tcp_client_that_does_not_authenticate_is_dropped_and_frees_its_slot() {
+    let (address, _k, _wakeup_rx) = start_with(
+        "
+ (defcfg tcp-server-token \"secret\" tcp-max-clients 1)
+ (defsrc a)
+ (deflayer base a)
+",
+        |server| server.auth_timeout = Duration::from_millis(50),
+    );
+    // The initial LayerChange is only sent after authentication.
+    let idle = TcpStream::connect(address).unwrap();
+    let mut reader = BufReader::new(idle);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert!(line.contains("authentication failed"), "{line}");
+    line.clear();
+    assert_eq!(reader.read_line(&mut line).unwrap(), 0);
+
+    // The server drops the slot once the client thread exits.
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(1) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"{\"auth\":\"secret\"}\n").unwrap();
+        let mut reader = BufReader::new(stream);
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        if line.contains("Ok") {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("slot was not freed: {line}");
+}
@@ -53,3 +53,30 @@ fn unicode_pulus() {
     .no_time();
     assert_eq!("outU:🚆 outU:🚆", result);
 }
+
+#[test]
+fn str_types_multibyte_characters() {
+    let result = simulate(
+        r#"
+         (defsrc a b)
+         (deflayer base (str "hé 😀") (str "ok" 5))
+        "#,
+        "d:a u:a t:10 d:b u:b t:10",
+    )
+    .no_time();
+    // Whitespace characters show as empty outputs.
+    assert_eq!("outU:h outU:é outU: outU:😀 outU:o outU:k", result);
+}
+
+#[test]
+fn str_escape_sequences() {
+    let result = simulate(
+        r###"
+         (defsrc a)
+         (deflayer base (str r#"a\tb\n\"c\\"#))
+        "###,
+        "d:a u:a t:10",
+    )
+    .no_time();
+    assert_eq!(r#"outU:a outU: outU:b outU: outU:" outU:c outU:\"#, result);
+}
@@ -106,3 +106,67 @@ fn unmod_keys_mod_list_cannot_have_invalid_keys() {
         "",
     );
 }
+
+const UNMOD_LIST_CFG: &str = "
+(defsrc a b lsft ralt lctl rctl)
+(deflayer base (unmod (ralt) a) (unmod (lsft lctl) b) lsft ralt lctl rctl)
+";
+
+#[test]
+fn unmod_ralt_keeps_shift() {
+    let result = simulate(
+        UNMOD_LIST_CFG,
+        "d:lsft t:10 d:ralt t:10 d:a t:10 u:a t:10 u:ralt t:10 u:lsft t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LShift t:10ms dn:RAlt t:10ms up:RAlt dn:A t:10ms up:A dn:RAlt t:10ms up:RAlt t:10ms up:LShift",
+        result
+    );
+}
+
+#[test]
+fn unmod_left_mods_keeps_right_mods() {
+    let result = simulate(
+        UNMOD_LIST_CFG,
+        "d:lsft d:rctl d:lctl t:10 d:b t:10 u:b t:10 u:lctl u:lsft u:rctl t:10",
+    )
+    .no_time()
+    .to_ascii();
+    assert_eq!(
+        "dn:LShift dn:RCtrl dn:LCtrl up:LShift up:LCtrl dn:B up:B dn:LShift dn:LCtrl \
+         up:LCtrl up:LShift up:RCtrl",
+        result
+    );
+}
+
+#[test]
+fn unmod_mod_released_during_hold_is_not_restored() {
+    let result = simulate(UNMOD_LIST_CFG, "d:ralt t:10 d:a t:10 u:ralt t:10 u:a t:10").to_ascii();
+    assert_eq!("dn:RAlt t:10ms up:RAlt dn:A t:20ms up:A", result);
+
+    // Only the modifiers still held are restored.
+    let result = simulate(
+        UNMOD_LIST_CFG,
+        "d:lsft d:lctl t:10 d:b t:10 u:lsft t:10 u:b t:10 u:lctl t:10",
+    )
+    .no_time()
+    .to_ascii();
+    assert_eq!(
+        "dn:LShift dn:LCtrl up:LShift up:LCtrl dn:B up:B dn:LCtrl up:LCtrl",
+        result
+    );
+}
+
+#[test]
+fn unmod_mod_repressed_during_hold_is_restored_on_release() {
+    let result = simulate(
+        UNMOD_LIST_CFG,
+        "d:ralt t:10 d:a t:10 u:ralt t:10 d:ralt t:10 u:a t:10 u:ralt t:10",
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:RAlt t:10ms up:RAlt dn:A t:30ms up:A dn:RAlt t:10ms up:RAlt",
+        result
+    );
+}
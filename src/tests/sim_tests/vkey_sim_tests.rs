@@ -241,3 +241,102 @@ fn on_idle_must_be_single_threaded() {
     .to_ascii();
     assert_eq!("t:137ms dn:LGui t:1ms up:LGui", result);
 }
+
+#[test]
+fn defcfg_on_idle_fires_once_per_idle_period() {
+    let cfg = "
+(defcfg on-idle (100 (macro x)) on-resume (macro y))
+(defvirtualkeys vk z)
+(defsrc a)
+(deflayer base a)";
+    // Fires once after 100ms without input, not on every idle tick afterwards.
+    let result = simulate(cfg, "d:a t:10 u:a t:500").to_ascii();
+    assert_eq!("dn:A t:10ms up:A t:102ms dn:X t:1ms up:X", result);
+    // Input resets the timer and runs on-resume before the key itself.
+    let result = simulate(cfg, "d:a u:a t:150 d:a u:a t:150").to_ascii();
+    assert_eq!(
+        "dn:A t:1ms up:A t:101ms dn:X t:1ms up:X t:48ms dn:Y t:1ms up:Y dn:A t:1ms up:A \
+         t:99ms dn:X t:1ms up:X",
+        result
+    );
+    // Input before the idle time is reached neither fires on-idle nor on-resume.
+    let result = simulate(cfg, "d:a u:a t:90 d:a u:a t:90").to_ascii();
+    assert_eq!("dn:A t:1ms up:A t:89ms dn:A t:1ms up:A", result);
+}
+
+#[test]
+fn defidle_watchers_fire_independently() {
+    let cfg = "
+(defcfg on-idle (150 (macro y)))
+(defidle 100 (macro x) (50 sym) (layer-switch base))
+(defsrc a b)
+(deflayer base a (layer-switch sym))
+(deflayer sym c b)";
+    // Each watcher fires once after its own idle time.
+    let result = simulate(cfg, "d:a u:a t:300").to_ascii();
+    assert_eq!(
+        "dn:A t:1ms up:A t:101ms dn:X t:1ms up:X t:49ms dn:Y t:1ms up:Y",
+        result
+    );
+    // The layer-scoped watcher only fires while its layer is active.
+    let result = simulate(cfg, "d:b u:b t:40 d:a u:a t:60 d:a u:a t:20").to_ascii();
+    assert_eq!("t:40ms dn:C t:1ms up:C t:59ms dn:A t:1ms up:A", result);
+}
+
+#[test]
+fn defcfg_on_exit_runs_before_exiting() {
+    use crate::kanata::{EXIT_REQUESTED, ON_EXIT_CONFIGURED};
+    use std::sync::atomic::Ordering::SeqCst;
+    init_log();
+    let _lk = match CFG_PARSE_LOCK.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let run_on_exit = |cfg: &str, ms: u128| {
+        let mut k = Kanata::new_from_str(cfg, Default::default()).expect("failed to parse cfg");
+        assert!(ON_EXIT_CONFIGURED.load(SeqCst));
+        EXIT_REQUESTED.store(true, SeqCst);
+        // The processing loop must keep ticking to pick up the request.
+        assert!(!k.can_block_update_idle_waiting(1));
+        k.tick_ms(1, &None).unwrap();
+        assert!(k.on_exit.is_running());
+        assert!(!k.can_block_update_idle_waiting(1));
+        k.tick_ms(ms, &None).unwrap();
+        let running = k.on_exit.is_running();
+        k.tick_ms(10, &None).unwrap();
+        assert!(!k.on_exit.is_running());
+        (running, k.kbd_out.outputs.events.join(" ").to_ascii())
+    };
+    // Exiting waits for the action to finish.
+    let cfg = "(defcfg on-exit (macro x 50 y)) (defsrc a) (deflayer base a)";
+    let (running, outputs) = run_on_exit(cfg, 50);
+    assert!(running);
+    assert_eq!("t:2ms dn:X t:1ms up:X t:51ms dn:Y t:1ms up:Y", outputs);
+    // An action that takes too long is cut short by the timeout.
+    let cfg = "(defcfg on-exit (macro x 500 y) on-exit-timeout 100) (defsrc a) (deflayer base a)";
+    let (running, outputs) = run_on_exit(cfg, 98);
+    assert!(running);
+    assert_eq!("t:2ms dn:X t:1ms up:X", outputs);
+}
+
+#[test]
+fn fakekeys_apply_in_order() {
+    const CFG: &str = "
+     (defsrc a b)
+     (defvirtualkeys
+       flag nop0
+       sft lsft
+       out (switch ((vkey-active flag)) x break () y break))
+     (deflayer base
+       (fakekeys (press sft) (toggle flag) (tap out) (toggle flag) (tap out) (release sft))
+       (fakekeys (press flag) (toggle flag) (tap out)))
+    ";
+    let result = simulate(CFG, "d:a u:a t:50").no_time();
+    assert_eq!(
+        "out:↓LShift out:↓X out:↑X out:↓Y out:↑Y out:↑LShift",
+        result
+    );
+    // The toggle sees the press before it, so it releases the flag.
+    let result = simulate(CFG, "d:b u:b t:50").no_time();
+    assert_eq!("out:↓Y out:↑Y", result);
+}
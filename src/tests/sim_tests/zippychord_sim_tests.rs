@@ -727,3 +727,217 @@ fn sim_zippychord_single_output() {
         result,
     );
 }
+
+static MACRO_CFG: &str = "
+(defsrc)
+(deflayer base)
+(defzippy file smart-space full)";
+static MACRO_FILE_CONTENT: &str = "
+kr	kind regards,(macro ret)Al
+br	[](macro left)
+br x	[x](macro left)
+hi	(macro ret)hi
+ab	a(macro ret 5 S-b)c
+abc	xyz
+";
+
+#[test]
+fn sim_zippychord_macro_output() {
+    let result = simulate_with_zippy_file_content(
+        MACRO_CFG,
+        "d:k d:r t:10 u:k u:r t:1000",
+        MACRO_FILE_CONTENT,
+    )
+    .no_releases()
+    .no_time()
+    .to_ascii();
+    assert_eq!(
+        "dn:K dn:BSpace dn:K dn:I dn:N dn:D dn:Space dn:R dn:E dn:G dn:A dn:R dn:D dn:S dn:Comma \
+         dn:Enter dn:LShift dn:A dn:L dn:Space",
+        result
+    );
+}
+
+#[test]
+fn sim_zippychord_macro_output_no_smart_space_after_macro() {
+    // The followup does not erase the left arrow.
+    let result = simulate_with_zippy_file_content(
+        MACRO_CFG,
+        "d:b d:r t:10 u:b u:r t:10 d:x t:10 u:x t:1000",
+        MACRO_FILE_CONTENT,
+    )
+    .no_releases()
+    .no_time()
+    .to_ascii();
+    assert_eq!(
+        "dn:B dn:BSpace dn:LBracket dn:RBracket dn:Left \
+         dn:BSpace dn:X dn:RBracket dn:Left",
+        result
+    );
+}
+
+#[test]
+fn sim_zippychord_macro_output_not_capitalized() {
+    // Held shift is released for the macro and capitalizes the first character.
+    let result = simulate_with_zippy_file_content(
+        MACRO_CFG,
+        "d:lsft d:h d:i t:10 u:h u:i u:lsft t:1000",
+        MACRO_FILE_CONTENT,
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LShift t:1ms dn:H t:1ms dn:BSpace up:BSpace up:LShift dn:Enter up:Enter \
+         dn:LShift up:H dn:H up:LShift up:I dn:I dn:Space up:Space dn:LShift \
+         t:8ms up:H t:1ms up:I t:1ms up:LShift",
+        result
+    );
+}
+
+#[test]
+fn sim_zippychord_macro_output_overlap() {
+    // Only the characters a, c and the smart space are erased, not the macro output.
+    let result = simulate_with_zippy_file_content(
+        MACRO_CFG,
+        "d:a d:b t:10 d:c t:10 u:a u:b u:c t:1000",
+        MACRO_FILE_CONTENT,
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:A t:1ms dn:BSpace up:BSpace up:A dn:A dn:Enter up:Enter \
+         dn:LShift up:B dn:B up:B dn:B up:LShift dn:C up:C dn:Space up:Space t:9ms \
+         dn:BSpace up:BSpace dn:BSpace up:BSpace dn:BSpace up:BSpace \
+         dn:X up:X dn:Y up:Y dn:Z up:Z dn:Space up:Space t:10ms up:A t:1ms up:B t:1ms up:C",
+        result
+    );
+}
+
+static CASE_CFG: &str = "
+(defsrc lalt)
+(deflayer base (caps-word 2000))
+(defzippy file smart-space full follow-case yes)";
+static CASE_FILE_CONTENT: &str = "
+ac	according to
+fs	first-1
+1s	1st
+gi	(no-follow-case)git
+";
+
+#[test]
+fn sim_zippychord_case_first_key_shifted() {
+    // Shift is released before the chord completes. Typing after the expansion is unaffected.
+    let result = simulate_with_zippy_file_content(
+        CASE_CFG,
+        "d:lsft d:a u:lsft d:c t:10 u:a u:c t:10 d:l u:l d:y u:y t:1000",
+        CASE_FILE_CONTENT,
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LShift t:1ms dn:A t:1ms up:LShift t:1ms dn:BSpace up:BSpace \
+         dn:LShift up:A dn:A up:LShift up:C dn:C up:C dn:C dn:O up:O dn:R up:R dn:D up:D \
+         dn:I up:I dn:N up:N dn:G up:G dn:Space up:Space dn:T up:T dn:O up:O dn:Space up:Space \
+         t:7ms up:A t:1ms up:C t:9ms dn:L t:1ms up:L t:1ms dn:Y t:1ms up:Y",
+        result
+    );
+}
+
+#[test]
+fn sim_zippychord_case_not_a_letter() {
+    // Only a first letter is capitalized and shift does not apply to the other characters.
+    let result = simulate_with_zippy_file_content(
+        CASE_CFG,
+        "d:lsft d:1 d:s t:10 u:1 u:s u:lsft t:1000",
+        CASE_FILE_CONTENT,
+    )
+    .to_ascii();
+    assert_eq!(
+        "dn:LShift t:1ms dn:Kb1 t:1ms dn:BSpace up:BSpace up:LShift \
+         up:Kb1 dn:Kb1 up:S dn:S dn:T up:T dn:Space up:Space dn:LShift \
+         t:8ms up:Kb1 t:1ms up:S t:1ms up:LShift",
+        result
+    );
+}
+
+#[test]
+fn sim_zippychord_case_caps_word() {
+    // Letters are uppercased, but shift is released for the other characters and the space.
+    let result = simulate_with_zippy_file_content(
+        CASE_CFG,
+        "d:lalt u:lalt t:10 d:f d:s t:10 u:f u:s t:1000",
+        CASE_FILE_CONTENT,
+    )
+    .to_ascii();
+    assert_eq!(
+        "t:10ms dn:LShift dn:F t:1ms dn:BSpace up:BSpace \
+         up:F dn:F dn:I up:I dn:R up:R up:S dn:S dn:T up:T \
+         up:LShift dn:Minus up:Minus dn:Kb1 up:Kb1 dn:Space up:Space dn:LShift \
+         t:9ms up:F t:1ms up:LShift up:S",
+        result
+    );
+}
+
+#[test]
+fn sim_zippychord_case_opt_out() {
+    let expected_git = "dn:BSpace up:BSpace up:LShift up:G dn:G up:I dn:I dn:T up:T \
+                        dn:Space up:Space dn:LShift";
+    let result = simulate_with_zippy_file_content(
+        CASE_CFG,
+        "d:lsft d:g d:i t:10 u:g u:i u:lsft t:1000",
+        CASE_FILE_CONTENT,
+    )
+    .no_time()
+    .to_ascii();
+    assert_eq!(
+        format!("dn:LShift dn:G {expected_git} up:G up:I up:LShift"),
+        result
+    );
+    let result = simulate_with_zippy_file_content(
+        CASE_CFG,
+        "d:lalt u:lalt t:10 d:g d:i t:10 u:g u:i t:1000",
+        CASE_FILE_CONTENT,
+    )
+    .no_time()
+    .to_ascii();
+    assert_eq!(
+        format!("dn:LShift dn:G {expected_git} up:G up:LShift up:I"),
+        result
+    );
+}
+
+#[test]
+fn sim_zippychord_case_off_by_default() {
+    // Without follow-case, a shift held during the activation still applies to the first
+    // character, but a shift released before it does not.
+    for cfg in [
+        "(defsrc)(deflayer base)(defzippy file)",
+        "(defsrc)(deflayer base)(defzippy file follow-case no)",
+    ] {
+        let result = simulate_with_zippy_file_content(
+            cfg,
+            "d:lsft d:a d:c t:10 u:a u:c u:lsft t:1000",
+            CASE_FILE_CONTENT,
+        )
+        .no_time()
+        .to_ascii();
+        assert_eq!(
+            "dn:LShift dn:A dn:BSpace up:BSpace up:A dn:A up:LShift up:C dn:C up:C dn:C \
+             dn:O up:O dn:R up:R dn:D up:D dn:I up:I dn:N up:N dn:G up:G dn:Space up:Space \
+             dn:T up:T dn:O up:O dn:LShift up:A up:C up:LShift",
+            result,
+            "{cfg}"
+        );
+        let result = simulate_with_zippy_file_content(
+            cfg,
+            "d:lsft d:a u:lsft d:c t:10 u:a u:c t:1000",
+            CASE_FILE_CONTENT,
+        )
+        .no_time()
+        .to_ascii();
+        assert_eq!(
+            "dn:LShift dn:A up:LShift dn:BSpace up:BSpace up:A dn:A up:C dn:C up:C dn:C \
+             dn:O up:O dn:R up:R dn:D up:D dn:I up:I dn:N up:N dn:G up:G dn:Space up:Space \
+             dn:T up:T dn:O up:O up:A up:C",
+            result,
+            "{cfg}"
+        );
+    }
+}
@@ -0,0 +1,9 @@
+//! Prints the protocol reference that is kept in `docs/tcp-protocol.md`:
+//!
+//! ```sh
+//! cargo run -p kanata-tcp-protocol --features schema --example protocol_reference > docs/tcp-protocol.md
+//! ```
+
+fn main() {
+    print!("{}", kanata_tcp_protocol::protocol_reference());
+}
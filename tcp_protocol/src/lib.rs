@@ -3,14 +3,36 @@
 //! This crate defines the JSON message format for communication between
 //! TCP clients and the Kanata keyboard remapping daemon.
 
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::str::FromStr;
 
+mod tagged;
+pub use tagged::*;
+
+/// Protocol version reported by the server in the versioned `Hello` handshake, as
+/// `major.minor`. Bump the minor version when adding messages.
+pub const PROTOCOL_VERSION: &str = "1.11";
+/// The oldest client protocol version that the server accepts in the `Hello` handshake.
+pub const MIN_SUPPORTED_PROTOCOL_VERSION: &str = "1.0";
+
+/// Parses a `major.minor` protocol version, for comparison.
+pub fn parse_protocol_version(version: &str) -> Option<(u16, u16)> {
+    let (major, minor) = version.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
 /// Messages sent from the server to connected clients.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ServerMessage {
+    /// `old` is the previously active layer. It is absent in the notifications sent upon
+    /// connecting and upon subscribing, which only report the current layer.
     LayerChange {
         new: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        old: Option<String>,
     },
     LayerNames {
         names: Vec<String>,
@@ -34,17 +56,32 @@ pub enum ServerMessage {
     Error {
         msg: String,
     },
-    /// Response to `Hello` command with server capabilities.
+    /// Response to a `Hello` that includes `client_version`.
+    Hello {
+        server_version: String,
+        min_supported: String,
+        capabilities: Vec<String>,
+    },
+    /// Response to `Hello` command with server capabilities, sent when the client did not
+    /// include `client_version`.
     /// Introduced in protocol v1.11.
     HelloOk {
         version: String,
         protocol: u8,
         capabilities: Vec<String>,
     },
-    /// Response to Reload commands when `wait: true` was specified.
+    /// Response to Reload commands, sent once the reload has completed or the wait timed out.
     /// Introduced in protocol v1.11.
     ReloadResult {
-        ok: bool,
+        #[serde(alias = "ok")]
+        success: bool,
+        /// Why the reload failed, e.g. the configuration parse error.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        /// Time taken to parse and apply the configuration, in milliseconds. If the wait timed
+        /// out, the time waited instead.
+        #[serde(default)]
+        duration_ms: u64,
         #[serde(skip_serializing_if = "Option::is_none")]
         timeout_ms: Option<u64>,
     },
@@ -58,9 +95,159 @@ pub enum ServerMessage {
     TapActivated {
         key: String,
     },
+    /// Response to `RequestDynamicMacros`. Lists the slots that hold a recording, ordered by
+    /// slot number. `recording` is the slot currently being recorded, if any.
+    DynamicMacros {
+        slots: Vec<DynamicMacroSlot>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        recording: Option<u16>,
+    },
+    /// An input key event as received by kanata, sent to clients that enabled `MonitorKeys`.
+    /// `timestamp_ms` counts from kanata startup. `device_id` is 0 when the source device is
+    /// unknown.
+    KeyEvent {
+        code: String,
+        value: KeyEventValue,
+        timestamp_ms: u64,
+        device_id: u32,
+    },
+    /// Sent when a `sticky-layer` key changes mode. `layer` is the layer of that key.
+    StickyLayerChange {
+        layer: String,
+        mode: StickyLayerMode,
+    },
+    /// Response to `GetState`.
+    State {
+        /// The topmost active layer.
+        active_layer: String,
+        /// Layers held above the base layer, most recently activated first.
+        layer_stack: Vec<String>,
+        /// Whether a one-shot key is waiting for the next key press.
+        oneshot_active: bool,
+        /// The layer locked by `sticky-layer`, if any.
+        locked_layer: Option<String>,
+        /// Whether a tap-hold key is pressed and not yet resolved to tap or hold.
+        pending_taphold: bool,
+        /// Names of the virtual keys that are currently pressed, sorted.
+        #[serde(default)]
+        active_virtual_keys: Vec<String>,
+        /// The `layer-cycle` actions of the configuration.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        layer_cycles: Vec<LayerCycle>,
+        /// Mouse buttons held down by the mouse lock actions, e.g. `mlft`.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        locked_mouse_buttons: Vec<String>,
+        uptime_ms: u64,
+    },
+    /// Response to `Ping`. Sent once the processing state could be accessed, so a slow response
+    /// means the processing thread is blocked.
+    Pong {
+        uptime_ms: u64,
+        version: String,
+        platform: String,
+        config_path: String,
+    },
+    /// Response to `PlayMacro`. `queued` is false if too many macros are already queued.
+    MacroResult {
+        queued: bool,
+    },
+    /// Response to `GetStats`. Maps key names, as used in configurations, to the number of
+    /// physical presses since startup or the last `ResetStats`.
+    KeyStats(BTreeMap<String, u64>),
+    /// Response to `RequestPressedKeys`, and sent to subscribers whenever the pressed keys
+    /// change. `keys` are the keys held on the physical keyboard. `output` are the keys kanata
+    /// outputs as held, present only if they differ from `keys`.
+    PressedKeys {
+        keys: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        output: Option<Vec<String>>,
+    },
+    /// Response to `SetLogLevel`.
+    LogLevel {
+        previous: String,
+        current: String,
+    },
+    /// Sent once the input devices are seized and key events are being remapped, and again when
+    /// they are seized after recovering from a lost output connection, with `recovered` set.
+    /// `devices` are the names of the seized devices.
+    Ready {
+        devices: Vec<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        recovered: bool,
+    },
+    /// Sent when a sequence starts, e.g. by pressing a `sequence` key, if the
+    /// `sequence-notifications` defcfg option is enabled. `timeout_ms` is the time to wait for
+    /// the first key.
+    SequenceStarted {
+        timeout_ms: u16,
+    },
+    /// Sent after each key accepted into an active sequence, if the `sequence-notifications`
+    /// defcfg option is enabled. `keys` are the keys typed so far and `candidates` is the number
+    /// of `defseq` entries that can still be completed.
+    SequenceProgress {
+        keys: Vec<String>,
+        candidates: usize,
+    },
+    /// Sent when an active sequence ends, if the `sequence-notifications` defcfg option is
+    /// enabled.
+    SequenceEnded {
+        reason: SequenceEndReason,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum KeyEventValue {
+    Press,
+    Release,
+    Repeat,
+    /// Used for scroll events, which have no separate press and release.
+    Tap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum StickyLayerMode {
+    Off,
+    /// Active while the key is held down.
+    Held,
+    /// Active until the next key press.
+    Latched,
+    /// Active until the key is tapped again.
+    Locked,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SequenceEndReason {
+    /// A `defseq` entry was completed.
+    Completed,
+    /// Cancelled by `sequence-cancel` or by a key that no `defseq` entry can continue with.
+    Cancelled,
+    /// No key was typed within the timeout.
+    Timeout,
+}
+
+/// The layers of a `layer-cycle` action and the index of the one it last switched to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct LayerCycle {
+    pub layers: Vec<String>,
+    pub selected: usize,
+}
+
+/// A recorded dynamic macro slot and the number of key press/release events it contains.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct DynamicMacroSlot {
+    pub slot: u16,
+    pub events: usize,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "status")]
 pub enum ServerResponse {
     Ok,
@@ -81,8 +268,69 @@ impl ServerMessage {
         msg.push(b'\n');
         msg
     }
+
+    /// Name of the message variant, as used in `Subscribe` for event notifications.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ServerMessage::LayerChange { .. } => "LayerChange",
+            ServerMessage::LayerNames { .. } => "LayerNames",
+            ServerMessage::FakeKeyNames { .. } => "FakeKeyNames",
+            ServerMessage::CurrentLayerInfo { .. } => "CurrentLayerInfo",
+            ServerMessage::ConfigFileReload { .. } => "ConfigFileReload",
+            ServerMessage::CurrentLayerName { .. } => "CurrentLayerName",
+            ServerMessage::MessagePush { .. } => "MessagePush",
+            ServerMessage::Error { .. } => "Error",
+            ServerMessage::Hello { .. } => "Hello",
+            ServerMessage::HelloOk { .. } => "HelloOk",
+            ServerMessage::ReloadResult { .. } => "ReloadResult",
+            ServerMessage::HoldActivated { .. } => "HoldActivated",
+            ServerMessage::TapActivated { .. } => "TapActivated",
+            ServerMessage::DynamicMacros { .. } => "DynamicMacros",
+            ServerMessage::KeyEvent { .. } => "KeyEvent",
+            ServerMessage::StickyLayerChange { .. } => "StickyLayerChange",
+            ServerMessage::State { .. } => "State",
+            ServerMessage::Pong { .. } => "Pong",
+            ServerMessage::MacroResult { .. } => "MacroResult",
+            ServerMessage::KeyStats(_) => "KeyStats",
+            ServerMessage::PressedKeys { .. } => "PressedKeys",
+            ServerMessage::LogLevel { .. } => "LogLevel",
+            ServerMessage::Ready { .. } => "Ready",
+            ServerMessage::SequenceStarted { .. } => "SequenceStarted",
+            ServerMessage::SequenceProgress { .. } => "SequenceProgress",
+            ServerMessage::SequenceEnded { .. } => "SequenceEnded",
+        }
+    }
+
+    /// The capability a client must have negotiated in the `Hello` handshake to be sent this
+    /// message, if any.
+    pub fn capability(&self) -> Option<&'static str> {
+        match self {
+            ServerMessage::LayerChange { .. } => Some("layer-change"),
+            ServerMessage::HoldActivated { .. } => Some("hold-activated"),
+            ServerMessage::TapActivated { .. } => Some("tap-activated"),
+            ServerMessage::KeyEvent { .. } => Some("monitor-keys"),
+            ServerMessage::StickyLayerChange { .. } => Some("sticky-layer"),
+            ServerMessage::PressedKeys { .. } => Some("pressed-keys"),
+            _ => None,
+        }
+    }
 }
 
+/// Event notifications that are only sent to clients that have subscribed to them.
+pub const SUBSCRIBABLE_EVENTS: &[&str] = &[
+    "LayerChange",
+    "ConfigFileReload",
+    "MessagePush",
+    "HoldActivated",
+    "TapActivated",
+    "StickyLayerChange",
+    "PressedKeys",
+    "Ready",
+    "SequenceStarted",
+    "SequenceProgress",
+    "SequenceEnded",
+];
+
 /// Messages sent from clients to the server.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ClientMessage {
@@ -93,6 +341,10 @@ pub enum ClientMessage {
     RequestFakeKeyNames {},
     RequestCurrentLayerInfo {},
     RequestCurrentLayerName {},
+    /// Request the active layers and pending key states. The server responds with `State`.
+    GetState {},
+    /// Health check. The server responds with `Pong`.
+    Ping {},
     ActOnFakeKey {
         name: String,
         action: FakeKeyActionMessage,
@@ -104,7 +356,9 @@ pub enum ClientMessage {
 
     /// Reload the current configuration file.
     Reload {
-        /// If true, block until reload completes or times out.
+        /// If true, respond with `ReloadResult` once the reload completes or times out. If false
+        /// or absent, respond with `Ok` as soon as the reload is requested, as servers did
+        /// before `ReloadResult` existed.
         #[serde(skip_serializing_if = "Option::is_none")]
         wait: Option<bool>,
         /// Maximum time to wait for reload (milliseconds). Default: 5000.
@@ -140,10 +394,101 @@ pub enum ClientMessage {
 
     /// Request server capabilities and version.
     /// Introduced in protocol v1.11.
-    Hello {},
+    ///
+    /// With `client_version`, the server replies with `Hello` instead of `HelloOk` and closes the
+    /// connection if the version is older than it supports. Only the listed `capabilities` that
+    /// the server also supports are then used for the connection.
+    Hello {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client_version: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        capabilities: Vec<String>,
+    },
+
+    /// Play back the dynamic macro recorded in `slot`, as `dynamic-macro-play` would.
+    PlayDynamicMacro {
+        slot: u16,
+    },
+    /// Delete the dynamic macro recorded in `slot`.
+    ClearDynamicMacro {
+        slot: u16,
+    },
+    RequestDynamicMacros {},
+
+    /// Receive the listed event notifications, e.g. `{"Subscribe":["LayerChange"]}`.
+    /// Replaces any previous subscriptions of the client; an empty list unsubscribes from all.
+    Subscribe(Vec<String>),
+
+    /// Inject a key event as if it came from the keyboard, e.g.
+    /// `{"KeyInject":{"key":"lalt","value":"press"}}`. The event goes through the full
+    /// processing pipeline, including layers and tap-hold.
+    KeyInject {
+        key: String,
+        value: KeyInjectValue,
+    },
+
+    /// Start or stop receiving a `KeyEvent` message for every input key event, e.g.
+    /// `{"MonitorKeys":{"enabled":true}}`. The stream is limited by the
+    /// `monitor-max-events-per-second` defcfg option; events over the limit are dropped.
+    MonitorKeys {
+        enabled: bool,
+    },
+
+    /// Set a runtime variable used by the configuration, as `setvar` would, e.g.
+    /// `{"SetVar":{"name":"mode","value":2}}`.
+    SetVar {
+        name: String,
+        value: i32,
+    },
+
+    /// Queue a named macro from `defmacro` to be played, e.g.
+    /// `{"PlayMacro":{"name":"insert-date"}}`. The server responds with `MacroResult` without
+    /// waiting for the macro to play.
+    PlayMacro {
+        name: String,
+    },
+
+    /// Remap an input key to an output key for a limited time, e.g.
+    /// `{"TempRemap":{"from":"h","to":"left","duration_ms":30000}}`. The remapped key bypasses
+    /// the layers. At most 16 remaps can be active; the server responds with `Error` if the limit
+    /// is exceeded or a key is unknown.
+    TempRemap {
+        from: String,
+        to: String,
+        duration_ms: u64,
+    },
+
+    /// Request the key press counts, e.g. `{"GetStats":{}}`. The server responds with
+    /// `KeyStats`. Events from `KeyInject` are not counted.
+    GetStats {},
+
+    /// Clear the key press counts, e.g. `{"ResetStats":{}}`.
+    ResetStats {},
+
+    /// Request the currently pressed keys, e.g. `{"RequestPressedKeys":{}}`. The server responds
+    /// with `PressedKeys`.
+    RequestPressedKeys {},
+
+    /// Change the log level, e.g. `{"SetLogLevel":{"level":"debug"}}`. The level is one of
+    /// `error`, `warn`, `info`, `debug` or `trace`. The server responds with `LogLevel`, which
+    /// includes the previous level so that it can be restored afterwards.
+    SetLogLevel {
+        level: String,
+    },
+
+    /// Return to the first layer and drop all held, latched and pending state, as `clear-state`
+    /// would, e.g. `{"ClearState":{}}`. Keys held by kanata are released.
+    ClearState {},
+}
+
+/// First message a client must send when the server is configured with `tcp-server-token`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientAuth {
+    pub auth: String,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub enum FakeKeyActionMessage {
     Press,
     Release,
@@ -151,6 +496,16 @@ pub enum FakeKeyActionMessage {
     Toggle,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum KeyInjectValue {
+    Press,
+    Release,
+    /// A press immediately followed by a release.
+    Tap,
+}
+
 impl FromStr for ClientMessage {
     type Err = serde_json::Error;
 
@@ -189,6 +544,152 @@ mod tests {
         assert!(json.contains("\"version\":\"1.10.0\""));
     }
 
+    #[test]
+    fn test_hello_handshake_json_format() {
+        let msg: ClientMessage = serde_json::from_str(r#"{"Hello":{}}"#).unwrap();
+        assert!(matches!(
+            msg,
+            ClientMessage::Hello { client_version: None, ref capabilities } if capabilities.is_empty()
+        ));
+        let json = r#"{"Hello":{"client_version":"1.0","capabilities":["monitor-keys"]}}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert_eq!(serde_json::to_string(&msg).unwrap(), json);
+
+        let msg = ServerMessage::Hello {
+            server_version: PROTOCOL_VERSION.into(),
+            min_supported: MIN_SUPPORTED_PROTOCOL_VERSION.into(),
+            capabilities: vec!["monitor-keys".into()],
+        };
+        assert_eq!(
+            serde_json::to_string(&msg).unwrap(),
+            r#"{"Hello":{"server_version":"1.11","min_supported":"1.0","capabilities":["monitor-keys"]}}"#
+        );
+    }
+
+    #[test]
+    fn test_parse_protocol_version() {
+        assert_eq!(parse_protocol_version("1.10"), Some((1, 10)));
+        assert!(parse_protocol_version("1.10") > parse_protocol_version("1.9"));
+        assert_eq!(parse_protocol_version("1"), None);
+        assert_eq!(parse_protocol_version("1.x"), None);
+    }
+
+    #[test]
+    fn test_state_json_format() {
+        let msg = ServerMessage::State {
+            active_layer: "base".into(),
+            layer_stack: vec![],
+            oneshot_active: false,
+            locked_layer: None,
+            pending_taphold: false,
+            active_virtual_keys: vec!["flag".into()],
+            layer_cycles: vec![],
+            locked_mouse_buttons: vec![],
+            uptime_ms: 123456,
+        };
+        assert_eq!(
+            serde_json::to_string(&msg).unwrap(),
+            r#"{"State":{"active_layer":"base","layer_stack":[],"oneshot_active":false,"locked_layer":null,"pending_taphold":false,"active_virtual_keys":["flag"],"uptime_ms":123456}}"#
+        );
+    }
+
+    #[test]
+    fn test_ping_pong_json_format() {
+        let msg: ClientMessage = r#"{"Ping":{}}"#.parse().unwrap();
+        assert!(matches!(msg, ClientMessage::Ping {}));
+        let msg = ServerMessage::Pong {
+            uptime_ms: 123456,
+            version: "1.3.0".into(),
+            platform: "linux".into(),
+            config_path: "/home/user/.config/kanata.kbd".into(),
+        };
+        assert_eq!(
+            serde_json::to_string(&msg).unwrap(),
+            r#"{"Pong":{"uptime_ms":123456,"version":"1.3.0","platform":"linux","config_path":"/home/user/.config/kanata.kbd"}}"#
+        );
+    }
+
+    #[test]
+    fn test_set_var_json_format() {
+        let msg: ClientMessage = r#"{"SetVar":{"name":"mode","value":-2}}"#.parse().unwrap();
+        assert!(matches!(msg, ClientMessage::SetVar { ref name, value: -2 } if name == "mode"));
+    }
+
+    #[test]
+    fn test_temp_remap_json_format() {
+        let json = r#"{"TempRemap":{"from":"h","to":"left","duration_ms":30000}}"#;
+        let msg: ClientMessage = json.parse().unwrap();
+        assert!(matches!(
+            msg,
+            ClientMessage::TempRemap { ref from, ref to, duration_ms: 30000 }
+                if from == "h" && to == "left"
+        ));
+        assert_eq!(serde_json::to_string(&msg).unwrap(), json);
+    }
+
+    #[test]
+    fn test_key_stats_json_format() {
+        let msg: ClientMessage = r#"{"GetStats":{}}"#.parse().unwrap();
+        assert!(matches!(msg, ClientMessage::GetStats {}));
+        let msg: ClientMessage = r#"{"ResetStats":{}}"#.parse().unwrap();
+        assert!(matches!(msg, ClientMessage::ResetStats {}));
+        let msg = ServerMessage::KeyStats(BTreeMap::from([
+            ("a".to_string(), 1234),
+            ("lalt".to_string(), 567),
+        ]));
+        assert_eq!(
+            serde_json::to_string(&msg).unwrap(),
+            r#"{"KeyStats":{"a":1234,"lalt":567}}"#
+        );
+    }
+
+    #[test]
+    fn test_set_log_level_json_format() {
+        let msg: ClientMessage = r#"{"SetLogLevel":{"level":"debug"}}"#.parse().unwrap();
+        assert!(matches!(msg, ClientMessage::SetLogLevel { level } if level == "debug"));
+        let msg = ServerMessage::LogLevel {
+            previous: "info".into(),
+            current: "debug".into(),
+        };
+        assert_eq!(
+            serde_json::to_string(&msg).unwrap(),
+            r#"{"LogLevel":{"previous":"info","current":"debug"}}"#
+        );
+    }
+
+    #[test]
+    fn test_pressed_keys_json_format() {
+        let msg: ClientMessage = r#"{"RequestPressedKeys":{}}"#.parse().unwrap();
+        assert!(matches!(msg, ClientMessage::RequestPressedKeys {}));
+        let msg = ServerMessage::PressedKeys {
+            keys: vec!["a".into(), "lsft".into()],
+            output: None,
+        };
+        assert_eq!(
+            serde_json::to_string(&msg).unwrap(),
+            r#"{"PressedKeys":{"keys":["a","lsft"]}}"#
+        );
+        let msg = ServerMessage::PressedKeys {
+            keys: vec!["caps".into()],
+            output: Some(vec!["lctl".into()]),
+        };
+        assert_eq!(
+            serde_json::to_string(&msg).unwrap(),
+            r#"{"PressedKeys":{"keys":["caps"],"output":["lctl"]}}"#
+        );
+    }
+
+    #[test]
+    fn test_play_macro_json_format() {
+        let msg: ClientMessage = r#"{"PlayMacro":{"name":"insert-date"}}"#.parse().unwrap();
+        assert!(matches!(msg, ClientMessage::PlayMacro { ref name } if name == "insert-date"));
+        let msg = ServerMessage::MacroResult { queued: true };
+        assert_eq!(
+            serde_json::to_string(&msg).unwrap(),
+            r#"{"MacroResult":{"queued":true}}"#
+        );
+    }
+
     #[test]
     fn test_reload_with_wait() {
         let msg = ClientMessage::Reload {
@@ -200,6 +701,48 @@ mod tests {
         assert!(json.contains("timeout_ms\":5000"));
     }
 
+    #[test]
+    fn test_reload_result_json_format() {
+        let msg = ServerMessage::ReloadResult {
+            success: false,
+            error: Some("failed to parse config file".to_string()),
+            duration_ms: 12,
+            timeout_ms: None,
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(
+            json,
+            r#"{"ReloadResult":{"success":false,"error":"failed to parse config file","duration_ms":12}}"#
+        );
+        // Older servers sent `ok` and no duration.
+        let msg: ServerMessage = serde_json::from_str(r#"{"ReloadResult":{"ok":true}}"#).unwrap();
+        assert!(matches!(
+            msg,
+            ServerMessage::ReloadResult {
+                success: true,
+                duration_ms: 0,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_key_event_json_format() {
+        let msg = ServerMessage::KeyEvent {
+            code: "a".to_string(),
+            value: KeyEventValue::Press,
+            timestamp_ms: 12345,
+            device_id: 0,
+        };
+        assert_eq!(
+            serde_json::to_string(&msg).unwrap(),
+            r#"{"KeyEvent":{"code":"a","value":"press","timestamp_ms":12345,"device_id":0}}"#
+        );
+        let json = r#"{"MonitorKeys":{"enabled":true}}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(msg, ClientMessage::MonitorKeys { enabled: true }));
+    }
+
     #[test]
     fn test_reload_minimal() {
         // Backward compatible: no optional fields
@@ -280,4 +823,368 @@ mod tests {
         let json = serde_json::to_string(&msg).unwrap();
         assert_eq!(json, r#"{"TapActivated":{"key":"a"}}"#);
     }
+
+    #[test]
+    fn test_dynamic_macro_commands() {
+        let json = r#"{"PlayDynamicMacro":{"slot":3}}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(msg, ClientMessage::PlayDynamicMacro { slot: 3 }));
+
+        let json = r#"{"ClearDynamicMacro":{"slot":3}}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(msg, ClientMessage::ClearDynamicMacro { slot: 3 }));
+
+        let json = r#"{"RequestDynamicMacros":{}}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(msg, ClientMessage::RequestDynamicMacros {}));
+    }
+
+    #[test]
+    fn test_dynamic_macros_json_format() {
+        let msg = ServerMessage::DynamicMacros {
+            slots: vec![DynamicMacroSlot { slot: 3, events: 4 }],
+            recording: None,
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(
+            json,
+            r#"{"DynamicMacros":{"slots":[{"slot":3,"events":4}]}}"#
+        );
+
+        let msg = ServerMessage::DynamicMacros {
+            slots: vec![],
+            recording: Some(1),
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(json, r#"{"DynamicMacros":{"slots":[],"recording":1}}"#);
+    }
+
+    #[test]
+    fn test_layer_change_json_format() {
+        let msg = ServerMessage::LayerChange {
+            new: "nav".to_string(),
+            old: Some("base".to_string()),
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(json, r#"{"LayerChange":{"new":"nav","old":"base"}}"#);
+
+        // Older servers do not send `old`.
+        let parsed: ServerMessage =
+            serde_json::from_str(r#"{"LayerChange":{"new":"nav"}}"#).unwrap();
+        assert!(matches!(
+            parsed,
+            ServerMessage::LayerChange { old: None, .. }
+        ));
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let msg: ClientMessage = serde_json::from_str(r#"{"Subscribe":["LayerChange"]}"#).unwrap();
+        assert!(matches!(msg, ClientMessage::Subscribe(events) if events == ["LayerChange"]));
+
+        let events = [
+            ServerMessage::LayerChange {
+                new: "nav".into(),
+                old: None,
+            },
+            ServerMessage::ConfigFileReload {
+                new: "a.kbd".into(),
+            },
+            ServerMessage::MessagePush {
+                message: serde_json::Value::Null,
+            },
+            ServerMessage::HoldActivated { key: "a".into() },
+            ServerMessage::TapActivated { key: "a".into() },
+            ServerMessage::StickyLayerChange {
+                layer: "nav".into(),
+                mode: StickyLayerMode::Latched,
+            },
+            ServerMessage::PressedKeys {
+                keys: vec![],
+                output: None,
+            },
+            ServerMessage::Ready {
+                devices: vec![],
+                recovered: false,
+            },
+            ServerMessage::SequenceStarted { timeout_ms: 1000 },
+            ServerMessage::SequenceProgress {
+                keys: vec!["a".into()],
+                candidates: 2,
+            },
+            ServerMessage::SequenceEnded {
+                reason: SequenceEndReason::Timeout,
+            },
+        ];
+        assert_eq!(events.len(), SUBSCRIBABLE_EVENTS.len());
+        for (msg, kind) in events.iter().zip(SUBSCRIBABLE_EVENTS) {
+            assert_eq!(msg.kind(), *kind);
+            assert!(
+                serde_json::to_string(msg)
+                    .unwrap()
+                    .starts_with(&format!("{{\"{kind}\""))
+            );
+        }
+    }
+
+    #[test]
+    fn test_key_inject() {
+        let json = r#"{"KeyInject":{"key":"lalt","value":"press"}}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            msg,
+            ClientMessage::KeyInject { ref key, value: KeyInjectValue::Press } if key == "lalt"
+        ));
+        assert_eq!(serde_json::to_string(&msg).unwrap(), json);
+
+        let json = r#"{"KeyInject":{"key":"a","value":"hold"}}"#;
+        assert!(serde_json::from_str::<ClientMessage>(json).is_err());
+    }
+
+    #[test]
+    fn test_client_auth_json_format() {
+        let auth: ClientAuth = serde_json::from_str(r#"{"auth":"secret"}"#).unwrap();
+        assert_eq!(auth.auth, "secret");
+        assert!(serde_json::from_str::<ClientAuth>(r#"{"Hello":{}}"#).is_err());
+    }
+    /// Checks `instance` against the subset of JSON schema that schemars generates.
+    #[cfg(feature = "schema")]
+    fn validate(
+        schema: &serde_json::Value,
+        root: &serde_json::Value,
+        instance: &serde_json::Value,
+    ) -> Result<(), String> {
+        use serde_json::Value;
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            let name = reference.rsplit('/').next().unwrap();
+            return validate(&root["$defs"][name], root, instance);
+        }
+        if let Some(alternatives) = schema.get("oneOf").and_then(Value::as_array) {
+            let matches = alternatives
+                .iter()
+                .filter(|s| validate(s, root, instance).is_ok())
+                .count();
+            if matches != 1 {
+                return Err(format!("{instance} matches {matches} of oneOf"));
+            }
+        }
+        if schema.get("const").is_some_and(|value| value != instance) {
+            return Err(format!("{instance} is not {}", schema["const"]));
+        }
+        if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+            if !values.contains(instance) {
+                return Err(format!("{instance} is not one of {values:?}"));
+            }
+        }
+        let types = match schema.get("type") {
+            Some(Value::String(t)) => vec![t.as_str()],
+            Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+        let has_type = |t: &str| match t {
+            "null" => instance.is_null(),
+            "boolean" => instance.is_boolean(),
+            "integer" => instance.is_i64() || instance.is_u64(),
+            "number" => instance.is_number(),
+            "string" => instance.is_string(),
+            "array" => instance.is_array(),
+            "object" => instance.is_object(),
+            _ => false,
+        };
+        if !types.is_empty() && !types.iter().any(|t| has_type(t)) {
+            return Err(format!("{instance} is not of type {types:?}"));
+        }
+        if let Some(object) = instance.as_object() {
+            for field in schema["required"].as_array().into_iter().flatten() {
+                if !object.contains_key(field.as_str().unwrap()) {
+                    return Err(format!("{instance} is missing {field}"));
+                }
+            }
+            for (field, value) in object {
+                match schema["properties"].get(field) {
+                    Some(s) => validate(s, root, value)?,
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            return Err(format!("{instance} has unknown field {field}"));
+                        }
+                        Some(s) if s.is_object() => validate(s, root, value)?,
+                        _ if schema.get("properties").is_some() => {
+                            return Err(format!("{instance} has unknown field {field}"));
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+        if let (Some(items), Some(array)) = (schema.get("items"), instance.as_array()) {
+            for item in array {
+                validate(items, root, item)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_tagged_json_format() {
+        let msg: KanataMessage = r#"{"type":"SetLayer","name":"base"}"#.parse().unwrap();
+        assert!(matches!(
+            ClientMessage::try_from(msg),
+            Ok(ClientMessage::ChangeLayer { new }) if new == "base"
+        ));
+        let msg = KanataMessage::from(ServerMessage::KeyStats(BTreeMap::from([(
+            "a".to_string(),
+            3,
+        )])));
+        assert_eq!(
+            serde_json::to_string(&msg).unwrap(),
+            r#"{"type":"KeyStats","counts":{"a":3}}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&KanataMessage::from(ServerResponse::Ok)).unwrap(),
+            r#"{"type":"Ok"}"#
+        );
+        let msg: KanataMessage = r#"{"type":"Pong","uptime_ms":1,"version":"1","platform":"linux","config_path":"a.kbd"}"#
+            .parse()
+            .unwrap();
+        assert_eq!(
+            ClientMessage::try_from(msg).unwrap_err().to_string(),
+            "Pong is not a client message"
+        );
+        // The legacy format is not accepted as a tagged message.
+        assert!(r#"{"ChangeLayer":{"new":"base"}}"#.parse::<KanataMessage>().is_err());
+    }
+
+    /// Every message used in the tests above, in the legacy format, converts to a tagged message
+    /// that is valid according to the JSON schema and converts back unchanged.
+    #[test]
+    #[cfg(feature = "schema")]
+    fn test_tagged_messages_match_schema() {
+        let client_messages = [
+            r#"{"ChangeLayer":{"new":"nav"}}"#,
+            r#"{"RequestLayerNames":{}}"#,
+            r#"{"RequestFakeKeyNames":{}}"#,
+            r#"{"RequestCurrentLayerInfo":{}}"#,
+            r#"{"RequestCurrentLayerName":{}}"#,
+            r#"{"GetState":{}}"#,
+            r#"{"Ping":{}}"#,
+            r#"{"ActOnFakeKey":{"name":"test","action":"Tap"}}"#,
+            r#"{"SetMouse":{"x":10,"y":20}}"#,
+            r#"{"Reload":{}}"#,
+            r#"{"Reload":{"wait":true,"timeout_ms":5000}}"#,
+            r#"{"ReloadNext":{}}"#,
+            r#"{"ReloadPrev":{"wait":false}}"#,
+            r#"{"ReloadNum":{"index":1}}"#,
+            r#"{"ReloadFile":{"path":"a.kbd","timeout_ms":100}}"#,
+            r#"{"Hello":{}}"#,
+            r#"{"Hello":{"client_version":"1.0","capabilities":["monitor-keys"]}}"#,
+            r#"{"PlayDynamicMacro":{"slot":3}}"#,
+            r#"{"ClearDynamicMacro":{"slot":3}}"#,
+            r#"{"RequestDynamicMacros":{}}"#,
+            r#"{"Subscribe":["LayerChange"]}"#,
+            r#"{"KeyInject":{"key":"lalt","value":"press"}}"#,
+            r#"{"MonitorKeys":{"enabled":true}}"#,
+            r#"{"SetVar":{"name":"mode","value":-2}}"#,
+            r#"{"PlayMacro":{"name":"insert-date"}}"#,
+            r#"{"TempRemap":{"from":"h","to":"left","duration_ms":30000}}"#,
+            r#"{"GetStats":{}}"#,
+            r#"{"ResetStats":{}}"#,
+            r#"{"RequestPressedKeys":{}}"#,
+            r#"{"SetLogLevel":{"level":"debug"}}"#,
+            r#"{"ClearState":{}}"#,
+        ];
+        let server_messages = [
+            r#"{"LayerChange":{"new":"nav","old":"base"}}"#,
+            r#"{"LayerChange":{"new":"nav"}}"#,
+            r#"{"LayerNames":{"names":["base","nav"]}}"#,
+            r#"{"FakeKeyNames":{"names":[]}}"#,
+            r#"{"CurrentLayerInfo":{"name":"base","cfg_text":"(deflayer base a)"}}"#,
+            r#"{"ConfigFileReload":{"new":"a.kbd"}}"#,
+            r#"{"CurrentLayerName":{"name":"base"}}"#,
+            r#"{"MessagePush":{"message":["hello",1]}}"#,
+            r#"{"Error":{"msg":"processing_thread_busy"}}"#,
+            r#"{"Hello":{"server_version":"1.11","min_supported":"1.0","capabilities":["monitor-keys"]}}"#,
+            r#"{"HelloOk":{"version":"1.10.0","protocol":1,"capabilities":["reload"]}}"#,
+            r#"{"ReloadResult":{"success":false,"error":"failed to parse config file","duration_ms":12}}"#,
+            r#"{"ReloadResult":{"success":false,"duration_ms":0,"timeout_ms":5000}}"#,
+            r#"{"HoldActivated":{"key":"caps"}}"#,
+            r#"{"TapActivated":{"key":"a"}}"#,
+            r#"{"DynamicMacros":{"slots":[{"slot":3,"events":4}],"recording":1}}"#,
+            r#"{"KeyEvent":{"code":"a","value":"press","timestamp_ms":12345,"device_id":0}}"#,
+            r#"{"StickyLayerChange":{"layer":"nav","mode":"latched"}}"#,
+            r#"{"State":{"active_layer":"base","layer_stack":[],"oneshot_active":false,"locked_layer":null,"pending_taphold":false,"active_virtual_keys":[],"uptime_ms":123456}}"#,
+            r#"{"State":{"active_layer":"qwerty","layer_stack":[],"oneshot_active":false,"locked_layer":null,"pending_taphold":false,"active_virtual_keys":[],"layer_cycles":[{"layers":["qwerty","dvorak"],"selected":0}],"uptime_ms":123456}}"#,
+            r#"{"Pong":{"uptime_ms":123456,"version":"1.3.0","platform":"linux","config_path":"/home/user/.config/kanata.kbd"}}"#,
+            r#"{"MacroResult":{"queued":true}}"#,
+            r#"{"KeyStats":{"a":1234,"lalt":567}}"#,
+            r#"{"PressedKeys":{"keys":["caps"],"output":["lctl"]}}"#,
+            r#"{"LogLevel":{"previous":"info","current":"debug"}}"#,
+            r#"{"Ready":{"devices":["/dev/input/event3"]}}"#,
+            r#"{"Ready":{"devices":["Apple Internal Keyboard"],"recovered":true}}"#,
+            r#"{"SequenceStarted":{"timeout_ms":1000}}"#,
+            r#"{"SequenceProgress":{"keys":["t","e"],"candidates":2}}"#,
+            r#"{"SequenceEnded":{"reason":"completed"}}"#,
+        ];
+        let responses = [r#"{"status":"Ok"}"#, r#"{"status":"Error","msg":"denied"}"#];
+
+        let schema = protocol_schema();
+        let schema = schema.as_value();
+        let mut covered = std::collections::BTreeSet::new();
+        let mut check = |msg: KanataMessage| {
+            let json = serde_json::to_value(&msg).unwrap();
+            validate(schema, schema, &json).unwrap_or_else(|e| panic!("{json}: {e}"));
+            let parsed: KanataMessage = serde_json::from_value(json).unwrap();
+            assert_eq!(parsed, msg);
+            covered.insert(msg.type_name());
+        };
+        for json in client_messages {
+            let msg: ClientMessage = json.parse().unwrap();
+            let tagged = KanataMessage::from(msg);
+            let back = ClientMessage::try_from(tagged.clone()).unwrap();
+            assert_eq!(serde_json::to_string(&back).unwrap(), json);
+            check(tagged);
+        }
+        for json in server_messages {
+            let msg: ServerMessage = serde_json::from_str(json).unwrap();
+            let tagged = KanataMessage::from(msg);
+            let back = ServerMessage::try_from(tagged.clone()).unwrap();
+            assert_eq!(serde_json::to_string(&back).unwrap(), json);
+            check(tagged);
+        }
+        for json in responses {
+            let msg: ServerResponse = serde_json::from_str(json).unwrap();
+            check(KanataMessage::from(msg));
+        }
+
+        let all_types = schema["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variant| variant["properties"]["type"]["const"].as_str().unwrap())
+            .map(str::to_owned)
+            .collect::<std::collections::BTreeSet<_>>();
+        assert_eq!(covered, all_types);
+
+        let invalid = [
+            serde_json::json!({"type": "SetLayer"}),
+            serde_json::json!({"type": "SetLayer", "name": 1}),
+            serde_json::json!({"type": "KeyInject", "key": "a", "value": "hold"}),
+            serde_json::json!({"type": "Unknown"}),
+        ];
+        for json in invalid {
+            assert!(validate(schema, schema, &json).is_err(), "{json}");
+            assert!(serde_json::from_value::<KanataMessage>(json).is_err());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "schema")]
+    fn test_protocol_reference_is_up_to_date() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../docs/tcp-protocol.md");
+        let committed = std::fs::read_to_string(path).unwrap();
+        assert!(
+            committed == protocol_reference(),
+            "docs/tcp-protocol.md is out of date, regenerate it with: \
+             cargo run -p kanata-tcp-protocol --features schema --example protocol_reference > docs/tcp-protocol.md"
+        );
+    }
 }
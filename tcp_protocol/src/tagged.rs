@@ -0,0 +1,762 @@
+//! Tagged JSON message format, used when the server is configured with `tcp-server-format tagged`.
+//!
+//! Every message is a JSON object whose `type` field names the message, with the message fields
+//! alongside it, e.g. `{"type":"SetLayer","name":"base"}`. Commands and responses share the
+//! single [`KanataMessage`] enum so that the whole protocol is described by one JSON schema.
+
+use crate::*;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+
+/// A message exchanged with the kanata TCP server in the tagged format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(tag = "type")]
+pub enum KanataMessage {
+    // Commands sent by clients.
+    /// Switch the base layer to the layer `name`.
+    SetLayer { name: String },
+    /// Request the names of all layers. The server responds with `LayerNames`.
+    RequestLayerNames {},
+    /// Request the names of all virtual keys. The server responds with `FakeKeyNames`.
+    RequestFakeKeyNames {},
+    /// Request the name and configuration text of the active layer. The server responds with
+    /// `CurrentLayerInfo`.
+    RequestCurrentLayerInfo {},
+    /// Request the name of the active layer. The server responds with `CurrentLayerName`.
+    RequestCurrentLayerName {},
+    /// Request the active layers and pending key states. The server responds with `State`.
+    GetState {},
+    /// Health check. The server responds with `Pong`.
+    Ping {},
+    /// Press, release, tap or toggle the virtual key `name`.
+    ActOnFakeKey {
+        name: String,
+        action: FakeKeyActionMessage,
+    },
+    /// Move the mouse cursor to the screen position `x`, `y`.
+    SetMouse { x: u16, y: u16 },
+    /// Reload the current configuration file. The server responds with `Ok`, or if `wait` is
+    /// true, with `ReloadResult` once the reload completes or `timeout_ms` (default 5000) elapses.
+    /// Waiting is opt-in so that clients written before `ReloadResult` keep working.
+    Reload {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        wait: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timeout_ms: Option<u64>,
+    },
+    /// Reload the next configuration file given on the command line, as `Reload` does.
+    ReloadNext {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        wait: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timeout_ms: Option<u64>,
+    },
+    /// Reload the previous configuration file given on the command line, as `Reload` does.
+    ReloadPrev {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        wait: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timeout_ms: Option<u64>,
+    },
+    /// Reload the configuration file at position `index` on the command line, as `Reload` does.
+    ReloadNum {
+        index: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        wait: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timeout_ms: Option<u64>,
+    },
+    /// Reload the configuration file at `path`, as `Reload` does.
+    ReloadFile {
+        path: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        wait: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timeout_ms: Option<u64>,
+    },
+    /// Request the server version and capabilities. Without `client_version` the server responds
+    /// with `HelloOk`, otherwise with `ServerHello`.
+    Hello {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client_version: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        capabilities: Vec<String>,
+    },
+    /// Play back the dynamic macro recorded in `slot`.
+    PlayDynamicMacro { slot: u16 },
+    /// Delete the dynamic macro recorded in `slot`.
+    ClearDynamicMacro { slot: u16 },
+    /// Request the recorded dynamic macros. The server responds with `DynamicMacros`.
+    RequestDynamicMacros {},
+    /// Receive the listed event notifications, replacing any previous subscriptions.
+    /// An empty list unsubscribes from all.
+    Subscribe { events: Vec<String> },
+    /// Inject a key event as if it came from the keyboard.
+    KeyInject { key: String, value: KeyInjectValue },
+    /// Start or stop receiving a `KeyEvent` message for every input key event.
+    MonitorKeys { enabled: bool },
+    /// Set a runtime variable used by the configuration, as `setvar` would.
+    SetVar { name: String, value: i32 },
+    /// Queue a named macro from `defmacro` to be played. The server responds with `MacroResult`.
+    PlayMacro { name: String },
+    /// Remap the input key `from` to the output key `to` for `duration_ms` milliseconds.
+    TempRemap {
+        from: String,
+        to: String,
+        duration_ms: u64,
+    },
+    /// Request the key press counts. The server responds with `KeyStats`.
+    GetStats {},
+    /// Clear the key press counts.
+    ResetStats {},
+    /// Request the currently pressed keys. The server responds with `PressedKeys`.
+    RequestPressedKeys {},
+    /// Change the log level to one of `error`, `warn`, `info`, `debug` or `trace`.
+    /// The server responds with `LogLevel`.
+    SetLogLevel { level: String },
+    /// Return to the first layer and drop all held, latched and pending state.
+    ClearState {},
+
+    // Responses and notifications sent by the server.
+    /// The command succeeded and has no other response.
+    Ok,
+    /// The command failed, or the message could not be handled.
+    Error { msg: String },
+    /// The active layer changed. `old` is absent in the notifications sent upon connecting and
+    /// upon subscribing.
+    LayerChange {
+        new: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        old: Option<String>,
+    },
+    /// Response to `RequestLayerNames`.
+    LayerNames { names: Vec<String> },
+    /// Response to `RequestFakeKeyNames`.
+    FakeKeyNames { names: Vec<String> },
+    /// Response to `RequestCurrentLayerInfo`.
+    CurrentLayerInfo { name: String, cfg_text: String },
+    /// A configuration file was loaded. `new` is its path.
+    ConfigFileReload { new: String },
+    /// Response to `RequestCurrentLayerName`.
+    CurrentLayerName { name: String },
+    /// A message pushed by the `push-msg` action.
+    MessagePush { message: serde_json::Value },
+    /// Response to a `Hello` that includes `client_version`.
+    ServerHello {
+        server_version: String,
+        min_supported: String,
+        capabilities: Vec<String>,
+    },
+    /// Response to a `Hello` without `client_version`.
+    HelloOk {
+        version: String,
+        protocol: u8,
+        capabilities: Vec<String>,
+    },
+    /// Response to reload commands. `error` says why the reload failed and `timeout_ms` is
+    /// present if the reload did not complete in time, in which case `duration_ms` is the time
+    /// waited.
+    ReloadResult {
+        success: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        #[serde(default)]
+        duration_ms: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timeout_ms: Option<u64>,
+    },
+    /// A tap-hold key on the physical key `key` activated its hold action.
+    HoldActivated { key: String },
+    /// A tap-hold key on the physical key `key` activated its tap action.
+    TapActivated { key: String },
+    /// Response to `RequestDynamicMacros`. `recording` is the slot being recorded, if any.
+    DynamicMacros {
+        slots: Vec<DynamicMacroSlot>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        recording: Option<u16>,
+    },
+    /// An input key event, sent to clients that enabled `MonitorKeys`.
+    KeyEvent {
+        code: String,
+        value: KeyEventValue,
+        timestamp_ms: u64,
+        device_id: u32,
+    },
+    /// A `sticky-layer` key for `layer` changed mode.
+    StickyLayerChange {
+        layer: String,
+        mode: StickyLayerMode,
+    },
+    /// Response to `GetState`.
+    State {
+        active_layer: String,
+        layer_stack: Vec<String>,
+        oneshot_active: bool,
+        locked_layer: Option<String>,
+        pending_taphold: bool,
+        #[serde(default)]
+        active_virtual_keys: Vec<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        layer_cycles: Vec<LayerCycle>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        locked_mouse_buttons: Vec<String>,
+        uptime_ms: u64,
+    },
+    /// Response to `Ping`.
+    Pong {
+        uptime_ms: u64,
+        version: String,
+        platform: String,
+        config_path: String,
+    },
+    /// Response to `PlayMacro`. `queued` is false if too many macros are already queued.
+    MacroResult { queued: bool },
+    /// Response to `GetStats`. Maps key names to their number of physical presses.
+    KeyStats { counts: BTreeMap<String, u64> },
+    /// Response to `RequestPressedKeys`, and the `PressedKeys` event notification. `output` is
+    /// present if the keys kanata outputs as held differ from the physical `keys`.
+    PressedKeys {
+        keys: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        output: Option<Vec<String>>,
+    },
+    /// Response to `SetLogLevel`.
+    LogLevel { previous: String, current: String },
+    /// The input devices `devices` are seized and key events are being remapped. `recovered` is
+    /// set when they were seized again after a lost output connection. Also sent upon
+    /// subscribing while the devices are seized.
+    Ready {
+        devices: Vec<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        recovered: bool,
+    },
+    /// A sequence started. `timeout_ms` is the time to wait for the first key. Sent only if the
+    /// `sequence-notifications` defcfg option is enabled, as are the other sequence messages.
+    SequenceStarted { timeout_ms: u16 },
+    /// A key was accepted into the active sequence. `keys` are the keys typed so far and
+    /// `candidates` is the number of `defseq` entries that can still be completed.
+    SequenceProgress {
+        keys: Vec<String>,
+        candidates: usize,
+    },
+    /// The active sequence ended.
+    SequenceEnded { reason: SequenceEndReason },
+}
+
+impl KanataMessage {
+    /// The `type` field of the message.
+    pub fn type_name(&self) -> String {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|v| v["type"].as_str().map(str::to_owned))
+            .unwrap_or_default()
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut msg = serde_json::to_vec(self).expect("KanataMessage should serialize");
+        msg.push(b'\n');
+        msg
+    }
+}
+
+impl FromStr for KanataMessage {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+impl From<ClientMessage> for KanataMessage {
+    fn from(msg: ClientMessage) -> Self {
+        use ClientMessage as C;
+        use KanataMessage as K;
+        match msg {
+            C::ChangeLayer { new } => K::SetLayer { name: new },
+            C::RequestLayerNames {} => K::RequestLayerNames {},
+            C::RequestFakeKeyNames {} => K::RequestFakeKeyNames {},
+            C::RequestCurrentLayerInfo {} => K::RequestCurrentLayerInfo {},
+            C::RequestCurrentLayerName {} => K::RequestCurrentLayerName {},
+            C::GetState {} => K::GetState {},
+            C::Ping {} => K::Ping {},
+            C::ActOnFakeKey { name, action } => K::ActOnFakeKey { name, action },
+            C::SetMouse { x, y } => K::SetMouse { x, y },
+            C::Reload { wait, timeout_ms } => K::Reload { wait, timeout_ms },
+            C::ReloadNext { wait, timeout_ms } => K::ReloadNext { wait, timeout_ms },
+            C::ReloadPrev { wait, timeout_ms } => K::ReloadPrev { wait, timeout_ms },
+            C::ReloadNum {
+                index,
+                wait,
+                timeout_ms,
+            } => K::ReloadNum {
+                index,
+                wait,
+                timeout_ms,
+            },
+            C::ReloadFile {
+                path,
+                wait,
+                timeout_ms,
+            } => K::ReloadFile {
+                path,
+                wait,
+                timeout_ms,
+            },
+            C::Hello {
+                client_version,
+                capabilities,
+            } => K::Hello {
+                client_version,
+                capabilities,
+            },
+            C::PlayDynamicMacro { slot } => K::PlayDynamicMacro { slot },
+            C::ClearDynamicMacro { slot } => K::ClearDynamicMacro { slot },
+            C::RequestDynamicMacros {} => K::RequestDynamicMacros {},
+            C::Subscribe(events) => K::Subscribe { events },
+            C::KeyInject { key, value } => K::KeyInject { key, value },
+            C::MonitorKeys { enabled } => K::MonitorKeys { enabled },
+            C::SetVar { name, value } => K::SetVar { name, value },
+            C::PlayMacro { name } => K::PlayMacro { name },
+            C::TempRemap {
+                from,
+                to,
+                duration_ms,
+            } => K::TempRemap {
+                from,
+                to,
+                duration_ms,
+            },
+            C::GetStats {} => K::GetStats {},
+            C::ResetStats {} => K::ResetStats {},
+            C::RequestPressedKeys {} => K::RequestPressedKeys {},
+            C::SetLogLevel { level } => K::SetLogLevel { level },
+            C::ClearState {} => K::ClearState {},
+        }
+    }
+}
+
+/// Error for a [`KanataMessage`] that is only ever sent by the server, when converting it to a
+/// [`ClientMessage`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotAClientMessage(pub String);
+
+impl std::fmt::Display for NotAClientMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is not a client message", self.0)
+    }
+}
+
+impl std::error::Error for NotAClientMessage {}
+
+impl TryFrom<KanataMessage> for ClientMessage {
+    type Error = NotAClientMessage;
+
+    fn try_from(msg: KanataMessage) -> Result<Self, NotAClientMessage> {
+        use ClientMessage as C;
+        use KanataMessage as K;
+        Ok(match msg {
+            K::SetLayer { name } => C::ChangeLayer { new: name },
+            K::RequestLayerNames {} => C::RequestLayerNames {},
+            K::RequestFakeKeyNames {} => C::RequestFakeKeyNames {},
+            K::RequestCurrentLayerInfo {} => C::RequestCurrentLayerInfo {},
+            K::RequestCurrentLayerName {} => C::RequestCurrentLayerName {},
+            K::GetState {} => C::GetState {},
+            K::Ping {} => C::Ping {},
+            K::ActOnFakeKey { name, action } => C::ActOnFakeKey { name, action },
+            K::SetMouse { x, y } => C::SetMouse { x, y },
+            K::Reload { wait, timeout_ms } => C::Reload { wait, timeout_ms },
+            K::ReloadNext { wait, timeout_ms } => C::ReloadNext { wait, timeout_ms },
+            K::ReloadPrev { wait, timeout_ms } => C::ReloadPrev { wait, timeout_ms },
+            K::ReloadNum {
+                index,
+                wait,
+                timeout_ms,
+            } => C::ReloadNum {
+                index,
+                wait,
+                timeout_ms,
+            },
+            K::ReloadFile {
+                path,
+                wait,
+                timeout_ms,
+            } => C::ReloadFile {
+                path,
+                wait,
+                timeout_ms,
+            },
+            K::Hello {
+                client_version,
+                capabilities,
+            } => C::Hello {
+                client_version,
+                capabilities,
+            },
+            K::PlayDynamicMacro { slot } => C::PlayDynamicMacro { slot },
+            K::ClearDynamicMacro { slot } => C::ClearDynamicMacro { slot },
+            K::RequestDynamicMacros {} => C::RequestDynamicMacros {},
+            K::Subscribe { events } => C::Subscribe(events),
+            K::KeyInject { key, value } => C::KeyInject { key, value },
+            K::MonitorKeys { enabled } => C::MonitorKeys { enabled },
+            K::SetVar { name, value } => C::SetVar { name, value },
+            K::PlayMacro { name } => C::PlayMacro { name },
+            K::TempRemap {
+                from,
+                to,
+                duration_ms,
+            } => C::TempRemap {
+                from,
+                to,
+                duration_ms,
+            },
+            K::GetStats {} => C::GetStats {},
+            K::ResetStats {} => C::ResetStats {},
+            K::RequestPressedKeys {} => C::RequestPressedKeys {},
+            K::SetLogLevel { level } => C::SetLogLevel { level },
+            K::ClearState {} => C::ClearState {},
+            other => return Err(NotAClientMessage(other.type_name())),
+        })
+    }
+}
+
+impl From<ServerMessage> for KanataMessage {
+    fn from(msg: ServerMessage) -> Self {
+        use KanataMessage as K;
+        use ServerMessage as S;
+        match msg {
+            S::LayerChange { new, old } => K::LayerChange { new, old },
+            S::LayerNames { names } => K::LayerNames { names },
+            S::FakeKeyNames { names } => K::FakeKeyNames { names },
+            S::CurrentLayerInfo { name, cfg_text } => K::CurrentLayerInfo { name, cfg_text },
+            S::ConfigFileReload { new } => K::ConfigFileReload { new },
+            S::CurrentLayerName { name } => K::CurrentLayerName { name },
+            S::MessagePush { message } => K::MessagePush { message },
+            S::Error { msg } => K::Error { msg },
+            S::Hello {
+                server_version,
+                min_supported,
+                capabilities,
+            } => K::ServerHello {
+                server_version,
+                min_supported,
+                capabilities,
+            },
+            S::HelloOk {
+                version,
+                protocol,
+                capabilities,
+            } => K::HelloOk {
+                version,
+                protocol,
+                capabilities,
+            },
+            S::ReloadResult {
+                success,
+                error,
+                duration_ms,
+                timeout_ms,
+            } => K::ReloadResult {
+                success,
+                error,
+                duration_ms,
+                timeout_ms,
+            },
+            S::HoldActivated { key } => K::HoldActivated { key },
+            S::TapActivated { key } => K::TapActivated { key },
+            S::DynamicMacros { slots, recording } => K::DynamicMacros { slots, recording },
+            S::KeyEvent {
+                code,
+                value,
+                timestamp_ms,
+                device_id,
+            } => K::KeyEvent {
+                code,
+                value,
+                timestamp_ms,
+                device_id,
+            },
+            S::StickyLayerChange { layer, mode } => K::StickyLayerChange { layer, mode },
+            S::State {
+                active_layer,
+                layer_stack,
+                oneshot_active,
+                locked_layer,
+                pending_taphold,
+                active_virtual_keys,
+                layer_cycles,
+                locked_mouse_buttons,
+                uptime_ms,
+            } => K::State {
+                active_layer,
+                layer_stack,
+                oneshot_active,
+                locked_layer,
+                pending_taphold,
+                active_virtual_keys,
+                layer_cycles,
+                locked_mouse_buttons,
+                uptime_ms,
+            },
+            S::Pong {
+                uptime_ms,
+                version,
+                platform,
+                config_path,
+            } => K::Pong {
+                uptime_ms,
+                version,
+                platform,
+                config_path,
+            },
+            S::MacroResult { queued } => K::MacroResult { queued },
+            S::KeyStats(counts) => K::KeyStats { counts },
+            S::PressedKeys { keys, output } => K::PressedKeys { keys, output },
+            S::LogLevel { previous, current } => K::LogLevel { previous, current },
+            S::Ready { devices, recovered } => K::Ready { devices, recovered },
+            S::SequenceStarted { timeout_ms } => K::SequenceStarted { timeout_ms },
+            S::SequenceProgress { keys, candidates } => K::SequenceProgress { keys, candidates },
+            S::SequenceEnded { reason } => K::SequenceEnded { reason },
+        }
+    }
+}
+
+impl TryFrom<KanataMessage> for ServerMessage {
+    type Error = KanataMessage;
+
+    /// Converts server responses and notifications, giving back any other message unchanged.
+    /// `Ok` is a [`ServerResponse`] rather than a `ServerMessage` so it is given back too.
+    fn try_from(msg: KanataMessage) -> Result<Self, KanataMessage> {
+        use KanataMessage as K;
+        use ServerMessage as S;
+        Ok(match msg {
+            K::LayerChange { new, old } => S::LayerChange { new, old },
+            K::LayerNames { names } => S::LayerNames { names },
+            K::FakeKeyNames { names } => S::FakeKeyNames { names },
+            K::CurrentLayerInfo { name, cfg_text } => S::CurrentLayerInfo { name, cfg_text },
+            K::ConfigFileReload { new } => S::ConfigFileReload { new },
+            K::CurrentLayerName { name } => S::CurrentLayerName { name },
+            K::MessagePush { message } => S::MessagePush { message },
+            K::Error { msg } => S::Error { msg },
+            K::ServerHello {
+                server_version,
+                min_supported,
+                capabilities,
+            } => S::Hello {
+                server_version,
+                min_supported,
+                capabilities,
+            },
+            K::HelloOk {
+                version,
+                protocol,
+                capabilities,
+            } => S::HelloOk {
+                version,
+                protocol,
+                capabilities,
+            },
+            K::ReloadResult {
+                success,
+                error,
+                duration_ms,
+                timeout_ms,
+            } => S::ReloadResult {
+                success,
+                error,
+                duration_ms,
+                timeout_ms,
+            },
+            K::HoldActivated { key } => S::HoldActivated { key },
+            K::TapActivated { key } => S::TapActivated { key },
+            K::DynamicMacros { slots, recording } => S::DynamicMacros { slots, recording },
+            K::KeyEvent {
+                code,
+                value,
+                timestamp_ms,
+                device_id,
+            } => S::KeyEvent {
+                code,
+                value,
+                timestamp_ms,
+                device_id,
+            },
+            K::StickyLayerChange { layer, mode } => S::StickyLayerChange { layer, mode },
+            K::State {
+                active_layer,
+                layer_stack,
+                oneshot_active,
+                locked_layer,
+                pending_taphold,
+                active_virtual_keys,
+                layer_cycles,
+                locked_mouse_buttons,
+                uptime_ms,
+            } => S::State {
+                active_layer,
+                layer_stack,
+                oneshot_active,
+                locked_layer,
+                pending_taphold,
+                active_virtual_keys,
+                layer_cycles,
+                locked_mouse_buttons,
+                uptime_ms,
+            },
+            K::Pong {
+                uptime_ms,
+                version,
+                platform,
+                config_path,
+            } => S::Pong {
+                uptime_ms,
+                version,
+                platform,
+                config_path,
+            },
+            K::MacroResult { queued } => S::MacroResult { queued },
+            K::KeyStats { counts } => S::KeyStats(counts),
+            K::PressedKeys { keys, output } => S::PressedKeys { keys, output },
+            K::LogLevel { previous, current } => S::LogLevel { previous, current },
+            K::Ready { devices, recovered } => S::Ready { devices, recovered },
+            K::SequenceStarted { timeout_ms } => S::SequenceStarted { timeout_ms },
+            K::SequenceProgress { keys, candidates } => S::SequenceProgress { keys, candidates },
+            K::SequenceEnded { reason } => S::SequenceEnded { reason },
+            other => return Err(other),
+        })
+    }
+}
+
+impl From<ServerResponse> for KanataMessage {
+    fn from(msg: ServerResponse) -> Self {
+        match msg {
+            ServerResponse::Ok => KanataMessage::Ok,
+            ServerResponse::Error { msg } => KanataMessage::Error { msg },
+        }
+    }
+}
+
+/// JSON schema of [`KanataMessage`], with the doc comments as descriptions.
+#[cfg(feature = "schema")]
+pub fn protocol_schema() -> schemars::Schema {
+    schemars::schema_for!(KanataMessage)
+}
+
+/// Renders the protocol reference in `docs/tcp-protocol.md` from [`protocol_schema`].
+#[cfg(feature = "schema")]
+pub fn protocol_reference() -> String {
+    use serde_json::Value;
+    use std::fmt::Write;
+
+    let schema = protocol_schema();
+    let mut out = String::from(
+        "# Kanata TCP protocol reference\n\n\
+         <!-- Generated by \
+         `cargo run -p kanata-tcp-protocol --features schema --example protocol_reference`. \
+         Do not edit. -->\n\n\
+         Messages in the tagged format, used with `tcp-server-format tagged`. \
+         Each message is one line of JSON with a `type` field naming the message, \
+         e.g. `{\"type\":\"SetLayer\",\"name\":\"base\"}`.\n",
+    );
+    let variants = schema
+        .get("oneOf")
+        .and_then(Value::as_array)
+        .expect("internally tagged enums have oneOf");
+    for variant in variants {
+        let props = variant["properties"]
+            .as_object()
+            .expect("variants have properties");
+        let name = props["type"]["const"].as_str().expect("type is a const");
+        let required = variant["required"]
+            .as_array()
+            .map(|r| r.iter().filter_map(Value::as_str).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let _ = write!(out, "\n## {name}\n\n");
+        if let Some(description) = variant.get("description").and_then(Value::as_str) {
+            let _ = writeln!(out, "{}\n", description.replace('\n', " "));
+        }
+        let fields = props.iter().filter(|(field, _)| *field != "type");
+        let mut fields = fields.peekable();
+        if fields.peek().is_none() {
+            let _ = writeln!(out, "No fields.");
+            continue;
+        }
+        let _ = writeln!(out, "| Field | Type | Required |\n| --- | --- | --- |");
+        for (field, field_schema) in fields {
+            let required = if required.contains(&field.as_str()) {
+                "yes"
+            } else {
+                "no"
+            };
+            let _ = writeln!(
+                out,
+                "| `{field}` | {} | {required} |",
+                schema_type(field_schema)
+            );
+        }
+    }
+    if let Some(defs) = schema.get("$defs").and_then(Value::as_object) {
+        let _ = write!(out, "\n# Types\n");
+        for (name, def) in defs {
+            let _ = write!(out, "\n## {name}\n\n");
+            if let Some(description) = def.get("description").and_then(Value::as_str) {
+                let _ = writeln!(out, "{}\n", description.replace('\n', " "));
+            }
+            let _ = writeln!(out, "{}", schema_type(def));
+        }
+    }
+    out
+}
+
+/// Short description of the JSON type accepted by `schema`.
+#[cfg(feature = "schema")]
+fn schema_type(schema: &serde_json::Value) -> String {
+    use serde_json::Value;
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let name = reference.rsplit('/').next().unwrap_or(reference);
+        return format!("[{name}](#{})", name.to_lowercase());
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        return values
+            .iter()
+            .map(|v| format!("`{v}`"))
+            .collect::<Vec<_>>()
+            .join(" \\| ");
+    }
+    if let Some(alternatives) = schema.get("oneOf").and_then(Value::as_array) {
+        return alternatives
+            .iter()
+            .map(schema_type)
+            .collect::<Vec<_>>()
+            .join(" \\| ");
+    }
+    if let Some(value) = schema.get("const") {
+        return format!("`{value}`");
+    }
+    if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+        let fields = properties
+            .iter()
+            .map(|(field, s)| format!("`{field}`: {}", schema_type(s)))
+            .collect::<Vec<_>>();
+        return format!("object with {}", fields.join(", "));
+    }
+    let types = match schema.get("type") {
+        Some(Value::String(t)) => vec![t.as_str()],
+        Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+        _ => return "any".to_string(),
+    };
+    types
+        .iter()
+        .map(|t| match *t {
+            "array" => format!("array of {}", schema_type(&schema["items"])),
+            "object" => match schema.get("additionalProperties") {
+                Some(values) if values.is_object() => {
+                    format!("object of {}", schema_type(values))
+                }
+                _ => "object".to_string(),
+            },
+            t => t.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" \\| ")
+}